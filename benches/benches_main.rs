@@ -2,8 +2,21 @@ use criterion::criterion_main;
 
 mod benchmarks;
 
+#[cfg(feature = "rsa")]
 criterion_main!(
+    benchmarks::armor::benches,
     benchmarks::key::benches,
     benchmarks::message::benches,
-    benchmarks::s2k::benches
+    benchmarks::rsa::benches,
+    benchmarks::s2k::benches,
+    benchmarks::signature::benches
+);
+
+#[cfg(not(feature = "rsa"))]
+criterion_main!(
+    benchmarks::armor::benches,
+    benchmarks::key::benches,
+    benchmarks::message::benches,
+    benchmarks::s2k::benches,
+    benchmarks::signature::benches
 );
@@ -0,0 +1,66 @@
+use criterion::{black_box, criterion_group, BenchmarkId, Criterion, Throughput};
+use pgp::armor::{self, BlockType, Dearmor};
+use pgp::errors::Result;
+use pgp::ser::Serialize;
+use rand::RngCore;
+use std::io::Read;
+
+/// Raw bytes to be armored as they are.
+struct Raw<'a>(&'a [u8]);
+
+impl Serialize for Raw<'_> {
+    fn to_writer<W: std::io::Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(self.0)?;
+        Ok(())
+    }
+}
+
+fn bench_armor(c: &mut Criterion) {
+    let mut g = c.benchmark_group("armor");
+
+    const MB: usize = 1000 * 1000;
+    let sizes = [MB, 10 * MB];
+
+    for size in &sizes {
+        let mut bytes = vec![0u8; *size];
+        rand::thread_rng().fill_bytes(&mut bytes);
+
+        let mut armored = Vec::new();
+        armor::write(&Raw(&bytes), BlockType::Message, &mut armored, None, true).unwrap();
+
+        g.throughput(Throughput::BytesDecimal(*size as u64));
+        g.bench_with_input(BenchmarkId::new("armor", size), &bytes, |b, bytes| {
+            b.iter(|| {
+                let mut out = Vec::with_capacity(armored.len());
+                armor::write(&Raw(bytes), BlockType::Message, &mut out, None, true).unwrap();
+                black_box(out)
+            })
+        });
+
+        g.bench_with_input(BenchmarkId::new("dearmor", size), &armored, |b, armored| {
+            b.iter(|| {
+                let mut out = Vec::with_capacity(bytes.len());
+                Dearmor::new(&armored[..]).read_to_end(&mut out).unwrap();
+                black_box(out)
+            })
+        });
+    }
+
+    g.finish();
+}
+
+#[cfg(feature = "profile")]
+fn profiled() -> Criterion {
+    Criterion::default().with_profiler(super::profiler::GProfiler)
+}
+
+#[cfg(not(feature = "profile"))]
+fn profiled() -> Criterion {
+    Criterion::default()
+}
+
+criterion_group!(
+    name = benches;
+    config = profiled();
+    targets = bench_armor
+);
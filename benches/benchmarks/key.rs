@@ -1,7 +1,7 @@
 use std::fs::File;
 
 use criterion::{black_box, criterion_group, Criterion};
-use pgp::composed::{Deserializable, KeyType, SignedSecretKey};
+use pgp::composed::{Deserializable, KeyType, SignedPublicKey, SignedSecretKey};
 use pgp::crypto::ecc_curve::ECCCurve;
 use pgp::ser::Serialize;
 
@@ -92,6 +92,22 @@ fn bench_key(c: &mut Criterion) {
         });
     }
 
+    g.bench_function("parse_keyring_5mb", |b| {
+        let (key, _headers) = SignedPublicKey::from_armor_single(
+            File::open("./tests/unit-tests/long-key.asc").unwrap(),
+        )
+        .unwrap();
+        let key_bytes = key.to_bytes().unwrap();
+        let keyring = key_bytes.repeat(5_000_000 / key_bytes.len() + 1);
+
+        b.iter(|| {
+            let keys = SignedPublicKey::from_bytes_many(&keyring[..])
+                .collect::<pgp::errors::Result<Vec<_>>>()
+                .unwrap();
+            black_box(keys)
+        });
+    });
+
     g.finish();
 }
 
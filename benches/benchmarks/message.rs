@@ -4,9 +4,11 @@ use std::io::Read;
 use criterion::{black_box, criterion_group, BenchmarkId, Criterion, Throughput};
 
 use pgp::composed::{Deserializable, Message, SignedSecretKey};
+use pgp::crypto::aead::AeadAlgorithm;
 use pgp::crypto::ecc_curve::ECCCurve;
 use pgp::crypto::sym::SymmetricKeyAlgorithm;
-use pgp::types::{SecretKeyTrait, StringToKey};
+use pgp::packet::SymEncryptedProtectedData;
+use pgp::types::{SecretKeyTrait, SessionKey, StringToKey};
 use pgp::KeyType;
 use rand::RngCore;
 
@@ -209,6 +211,159 @@ fn bench_message(c: &mut Criterion) {
     }
 }
 
+fn bench_seipd(c: &mut Criterion) {
+    let mut g = c.benchmark_group("seipd");
+    g.sample_size(10);
+
+    const MB: usize = 1000 * 1000;
+    let sizes = [MB, 100 * MB];
+    let sym_alg = SymmetricKeyAlgorithm::AES128;
+
+    for size in &sizes {
+        let mut rng = rand::thread_rng();
+        let mut bytes = vec![0u8; *size];
+        rng.fill_bytes(&mut bytes);
+        let mut session_key = vec![0u8; sym_alg.key_size()];
+        rng.fill_bytes(&mut session_key);
+
+        g.throughput(Throughput::BytesDecimal(*size as u64));
+        g.bench_with_input(BenchmarkId::new("v1_encrypt_aes128", size), size, |b, _| {
+            b.iter(|| {
+                let res = SymEncryptedProtectedData::encrypt_with_rng(
+                    &mut rng,
+                    sym_alg,
+                    &session_key,
+                    &bytes,
+                )
+                .unwrap();
+                black_box(res);
+            });
+        });
+
+        let encrypted =
+            SymEncryptedProtectedData::encrypt_with_rng(&mut rng, sym_alg, &session_key, &bytes)
+                .unwrap();
+        let key = SessionKey::new(session_key.to_vec(), sym_alg);
+        g.bench_with_input(BenchmarkId::new("v1_decrypt_aes128", size), size, |b, _| {
+            b.iter(|| {
+                let res = encrypted.decrypt_with_session_key(&key).unwrap();
+                black_box(res);
+            });
+        });
+
+        for (aead, aead_name) in [
+            (AeadAlgorithm::Eax, "eax"),
+            (AeadAlgorithm::Ocb, "ocb"),
+            (AeadAlgorithm::Gcm, "gcm"),
+        ] {
+            g.bench_with_input(
+                BenchmarkId::new(format!("v2_encrypt_aes128_{}", aead_name), size),
+                size,
+                |b, _| {
+                    b.iter(|| {
+                        let res = SymEncryptedProtectedData::encrypt_seipdv2(
+                            &mut rng,
+                            sym_alg,
+                            aead,
+                            CHUNK_SIZE,
+                            &session_key,
+                            &bytes,
+                        )
+                        .unwrap();
+                        black_box(res);
+                    });
+                },
+            );
+
+            let encrypted = SymEncryptedProtectedData::encrypt_seipdv2(
+                &mut rng,
+                sym_alg,
+                aead,
+                CHUNK_SIZE,
+                &session_key,
+                &bytes,
+            )
+            .unwrap();
+
+            // sanity check
+            let key = SessionKey::new_v2(session_key.to_vec());
+            assert_eq!(encrypted.decrypt_with_session_key(&key).unwrap(), bytes);
+
+            g.bench_with_input(
+                BenchmarkId::new(format!("v2_decrypt_aes128_{}", aead_name), size),
+                size,
+                |b, _| {
+                    b.iter(|| {
+                        let res = encrypted.decrypt_with_session_key(&key).unwrap();
+                        black_box(res);
+                    });
+                },
+            );
+        }
+    }
+
+    g.finish();
+}
+
+/// With the `rayon` feature, the chunks of v2 packets are encrypted in parallel. Compares it to
+/// the serial path, which is taken on a single thread.
+#[cfg(feature = "rayon")]
+fn bench_seipd_parallel(c: &mut Criterion) {
+    let mut g = c.benchmark_group("seipd_parallel");
+    g.sample_size(10);
+
+    const MB: usize = 1000 * 1000;
+    let size = 100 * MB;
+    let sym_alg = SymmetricKeyAlgorithm::AES128;
+
+    let mut rng = rand::thread_rng();
+    let mut bytes = vec![0u8; size];
+    rng.fill_bytes(&mut bytes);
+    let mut session_key = vec![0u8; sym_alg.key_size()];
+    rng.fill_bytes(&mut session_key);
+
+    let single_thread = rayon::ThreadPoolBuilder::new()
+        .num_threads(1)
+        .build()
+        .unwrap();
+    let pools = [
+        ("serial", single_thread),
+        ("parallel", rayon::ThreadPoolBuilder::new().build().unwrap()),
+    ];
+
+    g.throughput(Throughput::BytesDecimal(size as u64));
+    for (name, pool) in &pools {
+        g.bench_with_input(
+            BenchmarkId::new(format!("v2_encrypt_aes128_ocb_{}", name), size),
+            &size,
+            |b, _| {
+                b.iter(|| {
+                    let res = pool.install(|| {
+                        SymEncryptedProtectedData::encrypt_seipdv2(
+                            &mut rand::thread_rng(),
+                            sym_alg,
+                            AeadAlgorithm::Ocb,
+                            CHUNK_SIZE,
+                            &session_key,
+                            &bytes,
+                        )
+                        .unwrap()
+                    });
+                    black_box(res);
+                });
+            },
+        );
+    }
+
+    g.finish();
+}
+
+#[cfg(not(feature = "rayon"))]
+fn bench_seipd_parallel(_c: &mut Criterion) {}
+
+/// Chunks of 256 KiB.
+const CHUNK_SIZE: u8 = 12;
+
 #[cfg(feature = "profile")]
 fn profiled() -> Criterion {
     Criterion::default().with_profiler(super::profiler::GProfiler)
@@ -222,5 +377,5 @@ fn profiled() -> Criterion {
 criterion_group!(
     name = benches;
     config = profiled();
-    targets = bench_message
+    targets = bench_message, bench_seipd, bench_seipd_parallel
 );
@@ -3,9 +3,13 @@ use pgp::types::CompressionAlgorithm;
 use pgp::{KeyType, SecretKey, SecretKeyParamsBuilder, SubkeyParamsBuilder};
 use smallvec::smallvec;
 
+pub mod armor;
 pub mod key;
 pub mod message;
+#[cfg(feature = "rsa")]
+pub mod rsa;
 pub mod s2k;
+pub mod signature;
 
 #[cfg(feature = "profile")]
 pub mod profiler {
@@ -0,0 +1,112 @@
+use criterion::{black_box, criterion_group, Criterion, Throughput};
+use pgp::composed::{KeyType, SecretKeyParamsBuilder, SignedPublicKey};
+use pgp::crypto::{hash::HashAlgorithm, rsa};
+use pgp::types::{PublicKeyTrait, PublicParams, SecretKeyTrait};
+
+use super::build_key;
+
+const NUM_SIGNATURES: usize = 100;
+const NUM_USER_IDS: usize = 100;
+
+fn bench_rsa(c: &mut Criterion) {
+    let mut g = c.benchmark_group("rsa");
+
+    let key = build_key(KeyType::Rsa(2048), KeyType::Rsa(2048))
+        .sign(|| "".into())
+        .unwrap();
+    let PublicParams::RSA { ref n, ref e } = key.primary_key.public_params() else {
+        panic!("expected an RSA key");
+    };
+
+    let hash_alg = HashAlgorithm::SHA2_256;
+    let signatures: Vec<_> = (0..NUM_SIGNATURES)
+        .map(|i| {
+            let hashed = hash_alg.digest(&i.to_be_bytes()).unwrap();
+            let sig = key
+                .primary_key
+                .create_signature(|| "".into(), hash_alg, &hashed)
+                .unwrap();
+            (hashed, sig)
+        })
+        .collect();
+
+    g.throughput(Throughput::Elements(NUM_SIGNATURES as u64));
+    g.bench_function("verify_2048_many", |b| {
+        b.iter(|| {
+            for (hashed, sig) in &signatures {
+                rsa::verify(
+                    n.as_bytes(),
+                    e.as_bytes(),
+                    hash_alg,
+                    hashed,
+                    sig[0].as_bytes(),
+                )
+                .unwrap();
+            }
+        })
+    });
+
+    g.bench_function("verify_2048_many_reuse_key", |b| {
+        b.iter(|| {
+            let verifier = rsa::Verifier::new(n.as_bytes(), e.as_bytes()).unwrap();
+            for (hashed, sig) in &signatures {
+                verifier
+                    .verify(hash_alg, hashed, sig[0].as_bytes())
+                    .unwrap();
+            }
+            black_box(verifier);
+        })
+    });
+
+    // the key packet parses its verifier once, and reuses it for every signature
+    let public_key = key.primary_key.public_key();
+    g.bench_function("verify_2048_many_key_packet", |b| {
+        b.iter(|| {
+            for (hashed, sig) in &signatures {
+                public_key.verify_signature(hash_alg, hashed, sig).unwrap();
+            }
+        })
+    });
+
+    // a key with many self-certified user ids, as found in keyrings
+    let mut key_params = SecretKeyParamsBuilder::default();
+    key_params
+        .key_type(KeyType::Rsa(2048))
+        .can_certify(true)
+        .primary_user_id("Me <me@mail.com>".into())
+        .passphrase(None);
+    for i in 1..NUM_USER_IDS {
+        key_params.user_id(format!("Me {i} <me{i}@mail.com>"));
+    }
+    let key = key_params
+        .build()
+        .unwrap()
+        .generate()
+        .unwrap()
+        .sign(|| "".into())
+        .unwrap();
+    let public_key = SignedPublicKey::from(key);
+
+    g.throughput(Throughput::Elements(NUM_USER_IDS as u64));
+    g.bench_function("verify_2048_keyring_certifications", |b| {
+        b.iter(|| public_key.verify().unwrap())
+    });
+
+    g.finish();
+}
+
+#[cfg(feature = "profile")]
+fn profiled() -> Criterion {
+    Criterion::default().with_profiler(super::profiler::GProfiler)
+}
+
+#[cfg(not(feature = "profile"))]
+fn profiled() -> Criterion {
+    Criterion::default()
+}
+
+criterion_group!(
+    name = benches;
+    config = profiled();
+    targets = bench_rsa
+);
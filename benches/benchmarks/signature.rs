@@ -0,0 +1,67 @@
+use criterion::{black_box, criterion_group, BenchmarkId, Criterion, Throughput};
+use pgp::composed::{KeyType, SignedPublicKey};
+use pgp::crypto::hash::HashAlgorithm;
+use pgp::types::KeyTrait;
+
+use super::build_key;
+
+const NUM_SIGNATURES: usize = 1000;
+const NUM_FINGERPRINTS: usize = 10_000;
+
+fn bench_signature(c: &mut Criterion) {
+    let mut g = c.benchmark_group("signature");
+
+    for (kt, name) in [
+        (KeyType::EdDSA, "ed25519"),
+        (KeyType::Rsa(3072), "rsa_3072"),
+    ] {
+        let key = build_key(kt.clone(), kt).sign(|| "".into()).unwrap();
+        let public_key = SignedPublicKey::from(key.clone());
+
+        let signatures: Vec<_> = (0..NUM_SIGNATURES)
+            .map(|i| {
+                let data = i.to_be_bytes();
+                let sig = key
+                    .sign_detached(|| "".into(), HashAlgorithm::SHA2_256, &data)
+                    .unwrap();
+                (data, sig)
+            })
+            .collect();
+
+        g.throughput(Throughput::Elements(NUM_SIGNATURES as u64));
+        g.bench_function(BenchmarkId::new("verify_many", name), |b| {
+            b.iter(|| {
+                for (data, sig) in &signatures {
+                    sig.verify(&public_key, &data[..]).unwrap();
+                }
+            })
+        });
+
+        g.throughput(Throughput::Elements(NUM_FINGERPRINTS as u64));
+        g.bench_function(BenchmarkId::new("fingerprint_many", name), |b| {
+            b.iter(|| {
+                for _ in 0..NUM_FINGERPRINTS {
+                    black_box(public_key.fingerprint());
+                }
+            })
+        });
+    }
+
+    g.finish();
+}
+
+#[cfg(feature = "profile")]
+fn profiled() -> Criterion {
+    Criterion::default().with_profiler(super::profiler::GProfiler)
+}
+
+#[cfg(not(feature = "profile"))]
+fn profiled() -> Criterion {
+    Criterion::default()
+}
+
+criterion_group!(
+    name = benches;
+    config = profiled();
+    targets = bench_signature
+);
@@ -0,0 +1,277 @@
+//! A small `pgp` command line tool, built only on the public API of this crate.
+//!
+//! Keys, signatures and messages are read from files or stdin, in ASCII armor or binary, and
+//! the results are written ASCII armored to stdout.
+//!
+//! ```text
+//! pgp-cli generate <USER_ID> [--password <PW>]    generate a secret key
+//! pgp-cli public <KEY>                            extract the public key
+//! pgp-cli inspect <KEY>                           describe a public or secret key
+//! pgp-cli sign <SECRET_KEY> [FILE] [--password <PW>]
+//!                                                 create a detached signature
+//! pgp-cli verify <KEY> <SIGNATURE> [FILE]         verify a detached signature
+//! pgp-cli encrypt <KEY> [FILE]                    encrypt to the key
+//! pgp-cli decrypt <SECRET_KEY> [FILE] [--password <PW>]
+//!                                                 decrypt a message
+//! pgp-cli armor [FILE]                            armor binary OpenPGP data
+//! pgp-cli dearmor [FILE]                          remove the armor from OpenPGP data
+//! ```
+
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::process::ExitCode;
+
+use pgp::armor::{self, BlockType};
+use pgp::crypto::ecc_curve::ECCCurve;
+use pgp::crypto::hash::HashAlgorithm;
+use pgp::crypto::sym::SymmetricKeyAlgorithm;
+use pgp::errors::Result;
+use pgp::packet::PacketParser;
+use pgp::types::{CompressionAlgorithm, KeyTrait, Tag};
+use pgp::{
+    from_reader_many, ArmorOptions, Deserializable, EncryptionPurpose, KeyType, Message,
+    MessageBuilder, PublicOrSecret, SecretKeyParamsBuilder, SignedPublicKey, SignedSecretKey,
+    StandaloneSignature, SubkeyParamsBuilder,
+};
+
+const USAGE: &str =
+    "usage: pgp-cli <generate|public|inspect|sign|verify|encrypt|decrypt|armor|dearmor> [ARGS]";
+
+/// The parsed command line: positional arguments and the value of `--password`.
+struct Args {
+    positional: Vec<String>,
+    password: String,
+}
+
+impl Args {
+    fn parse() -> std::result::Result<Self, String> {
+        let mut positional = Vec::new();
+        let mut password = String::new();
+
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            if arg == "--password" {
+                password = args.next().ok_or("--password requires a value")?;
+            } else {
+                positional.push(arg);
+            }
+        }
+
+        Ok(Args {
+            positional,
+            password,
+        })
+    }
+
+    /// The positional argument at `i`, counting the command as `0`.
+    fn get(&self, i: usize) -> Option<&str> {
+        self.positional.get(i).map(String::as_str)
+    }
+
+    fn required(&self, i: usize, name: &str) -> std::result::Result<&str, String> {
+        self.get(i)
+            .ok_or_else(|| format!("missing argument <{}>\n{}", name, USAGE))
+    }
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("pgp-cli: {}", err);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run() -> std::result::Result<ExitCode, Box<dyn std::error::Error>> {
+    let args = Args::parse()?;
+    let mut out = io::stdout().lock();
+
+    match args.required(0, "COMMAND")? {
+        "generate" => {
+            let key = generate(args.required(1, "USER_ID")?, &args.password)?;
+            key.to_armored_writer(&mut out, ArmorOptions::default())?;
+        }
+        "public" => {
+            let key = read_public_key(args.required(1, "KEY")?)?;
+            key.to_armored_writer(&mut out, ArmorOptions::default())?;
+        }
+        "inspect" => {
+            let key = match read_key(args.required(1, "KEY")?)? {
+                PublicOrSecret::Public(key) => key,
+                PublicOrSecret::Secret(key) => {
+                    writeln!(out, "secret key")?;
+                    key.into()
+                }
+            };
+            inspect(&key, &mut out)?;
+        }
+        "sign" => {
+            let key = read_secret_key(args.required(1, "SECRET_KEY")?)?;
+            let data = read_input(args.get(2))?;
+            let password = args.password.clone();
+            let signature = key.sign_detached(|| password, HashAlgorithm::SHA2_256, &data)?;
+            signature.to_armored_writer(&mut out, ArmorOptions::default())?;
+        }
+        "verify" => {
+            let key = read_public_key(args.required(1, "KEY")?)?;
+            let (signature, _) =
+                StandaloneSignature::from_reader_single(open(args.required(2, "SIGNATURE")?)?)?;
+            let data = read_input(args.get(3))?;
+            match key.verify_detached(&data, &signature) {
+                Ok(key_id) => writeln!(out, "Good signature by {:X}", key_id)?,
+                Err(err) => {
+                    eprintln!("BAD signature: {}", err);
+                    return Ok(ExitCode::FAILURE);
+                }
+            }
+        }
+        "encrypt" => {
+            let key = read_public_key(args.required(1, "KEY")?)?;
+            let subkey = key
+                .best_encryption_key(EncryptionPurpose::Any)
+                .ok_or("the key has no encryption capable subkey")?;
+            let data = read_input(args.get(2))?;
+            let message = MessageBuilder::from_bytes("", &data)
+                .seipd_v1(SymmetricKeyAlgorithm::AES256)
+                .encrypt_to_key(subkey)
+                .to_armored_string(rand::thread_rng(), ArmorOptions::default())?;
+            out.write_all(message.as_bytes())?;
+        }
+        "decrypt" => {
+            let key = read_secret_key(args.required(1, "SECRET_KEY")?)?;
+            let (message, _) = Message::from_reader_single(input(args.get(2))?)?;
+            let password = args.password.clone();
+            let (message, _) = message.decrypt(|| password, &[&key])?;
+            let content = message
+                .decompress()?
+                .get_content()?
+                .ok_or("the message has no content")?;
+            out.write_all(&content)?;
+        }
+        "armor" => {
+            let data = read_input(args.get(1))?;
+            armor::write_bytes(&data, block_type(&data)?, &mut out, None, true)?;
+        }
+        "dearmor" => {
+            let mut dearmor = armor::Dearmor::new(BufReader::new(input(args.get(1))?));
+            io::copy(&mut dearmor, &mut out)?;
+        }
+        cmd => return Err(format!("unknown command {:?}\n{}", cmd, USAGE).into()),
+    }
+
+    out.flush()?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Generates an EdDSA signing key, with an ECDH encryption subkey on Curve25519.
+fn generate(user_id: &str, password: &str) -> Result<SignedSecretKey> {
+    let passphrase = (!password.is_empty()).then(|| password.to_string());
+    let params = SecretKeyParamsBuilder::default()
+        .key_type(KeyType::EdDSA)
+        .can_certify(true)
+        .can_sign(true)
+        .primary_user_id(user_id.to_string())
+        .passphrase(passphrase.clone())
+        .preferred_symmetric_algorithms(vec![
+            SymmetricKeyAlgorithm::AES256,
+            SymmetricKeyAlgorithm::AES128,
+        ])
+        .preferred_hash_algorithms(vec![HashAlgorithm::SHA2_256, HashAlgorithm::SHA2_512])
+        .preferred_compression_algorithms(vec![CompressionAlgorithm::ZLIB])
+        .subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::ECDH(ECCCurve::Curve25519))
+                .can_encrypt(true)
+                .passphrase(passphrase)
+                .build()
+                .map_err(|err| pgp::errors::Error::Message(err.to_string()))?,
+        )
+        .build()
+        .map_err(|err| pgp::errors::Error::Message(err.to_string()))?;
+
+    let password = password.to_string();
+    params.generate()?.sign(|| password)
+}
+
+fn inspect(key: &SignedPublicKey, out: &mut impl Write) -> io::Result<()> {
+    writeln!(
+        out,
+        "primary key {} {:?} created {}",
+        hex::encode_upper(key.fingerprint()),
+        key.algorithm(),
+        key.primary_key.created_at()
+    )?;
+    for user in &key.details.users {
+        writeln!(out, "user id {}", user.id.id())?;
+    }
+    for subkey in &key.public_subkeys {
+        writeln!(
+            out,
+            "subkey {} {:?} created {}{}",
+            hex::encode_upper(subkey.fingerprint()),
+            subkey.algorithm(),
+            subkey.key.created_at(),
+            if subkey.is_encryption_key() {
+                " encryption"
+            } else {
+                ""
+            }
+        )?;
+    }
+
+    Ok(())
+}
+
+/// The armor block type for binary OpenPGP data, picked by its first packet.
+fn block_type(data: &[u8]) -> Result<BlockType> {
+    let typ = match PacketParser::new(data).peek_tag()? {
+        Some(Tag::PublicKey) => BlockType::PublicKey,
+        Some(Tag::SecretKey) => BlockType::PrivateKey,
+        Some(Tag::Signature)
+            if PacketParser::new(data).all(|p| p.is_ok_and(|p| p.tag() == Tag::Signature)) =>
+        {
+            BlockType::Signature
+        }
+        _ => BlockType::Message,
+    };
+
+    Ok(typ)
+}
+
+fn read_key(path: &str) -> Result<PublicOrSecret> {
+    let (mut keys, _) = from_reader_many(open(path)?)?;
+    keys.next()
+        .unwrap_or_else(|| Err(pgp::errors::Error::NoMatchingPacket))
+}
+
+fn read_public_key(path: &str) -> Result<SignedPublicKey> {
+    match read_key(path)? {
+        PublicOrSecret::Public(key) => Ok(key),
+        PublicOrSecret::Secret(key) => Ok(key.into()),
+    }
+}
+
+fn read_secret_key(path: &str) -> Result<SignedSecretKey> {
+    let (key, _) = SignedSecretKey::from_reader_single(open(path)?)?;
+    Ok(key)
+}
+
+fn open(path: &str) -> io::Result<File> {
+    File::open(path).map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path, err)))
+}
+
+/// Opens `path`, or stdin if it is `None` or `-`.
+fn input(path: Option<&str>) -> io::Result<Box<dyn Read>> {
+    match path {
+        None | Some("-") => Ok(Box::new(io::stdin().lock())),
+        Some(path) => Ok(Box::new(open(path)?)),
+    }
+}
+
+fn read_input(path: Option<&str>) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    input(path)?.read_to_end(&mut data)?;
+    Ok(data)
+}
@@ -0,0 +1,34 @@
+//! Dearmors data, armors the result again and checks that it dearmors to the same data.
+
+#![no_main]
+
+use std::io::Read;
+
+use libfuzzer_sys::fuzz_target;
+use pgp::armor::{self, Dearmor};
+
+fn dearmor(data: &[u8]) -> Option<(armor::BlockType, armor::Headers, Vec<u8>)> {
+    let mut dearmor = Dearmor::new(data);
+    dearmor.read_header().ok()?;
+    let mut bytes = Vec::new();
+    dearmor.read_to_end(&mut bytes).ok()?;
+
+    Some((dearmor.typ?, dearmor.headers, bytes))
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Some((typ, headers, bytes)) = dearmor(data) else {
+        return;
+    };
+
+    // the reader accepts headers the writer refuses, e.g. keys containing colons
+    let mut armored = Vec::new();
+    if armor::write_bytes(&bytes, typ, &mut armored, Some(&headers), true).is_err() {
+        return;
+    }
+
+    let (typ2, headers2, bytes2) = dearmor(&armored).expect("armored data dearmors");
+    assert_eq!(typ, typ2);
+    assert_eq!(headers, headers2);
+    assert_eq!(bytes, bytes2);
+});
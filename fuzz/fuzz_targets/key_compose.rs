@@ -0,0 +1,31 @@
+//! Parses keyrings, with limits, and checks that serialized keys parse back to the same key.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pgp::ser::Serialize;
+use pgp::{from_bytes_many, from_bytes_many_with_limits, KeyringLimits, PublicOrSecret};
+
+fuzz_target!(|data: &[u8]| {
+    let limits = KeyringLimits::new(1000, 1024 * 1024);
+
+    for key in from_bytes_many_with_limits(data, limits).flatten() {
+        let _ = key.verify();
+
+        let bytes = match &key {
+            PublicOrSecret::Public(k) => k.to_bytes(),
+            PublicOrSecret::Secret(k) => k.to_bytes(),
+        };
+        let Ok(bytes) = bytes else {
+            continue;
+        };
+
+        let mut keys = from_bytes_many(&bytes[..]);
+        let reparsed = keys
+            .next()
+            .expect("a key")
+            .unwrap_or_else(|err| panic!("{:?} does not parse again: {:?}", key, err));
+        assert_eq!(key, reparsed);
+        assert!(keys.next().is_none());
+    }
+});
@@ -0,0 +1,27 @@
+//! Parses messages, with a limit on the packet size, and serializes them again.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pgp::packet::PacketParser;
+use pgp::ser::Serialize;
+use pgp::{Deserializable, Message};
+
+const MAX_PACKET_SIZE: usize = 1024 * 1024;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Message::classify(data);
+
+    let packets = PacketParser::with_max_packet_size(data, MAX_PACKET_SIZE).peekable();
+    for message in Message::from_packets(packets).flatten() {
+        let _ = message.get_literal();
+        let _ = message.get_recipients();
+        let _ = message.is_one_pass_signed();
+
+        if let Ok(bytes) = message.to_bytes() {
+            let reparsed = Message::from_bytes(&bytes[..])
+                .unwrap_or_else(|err| panic!("{:?} does not parse again: {:?}", message, err));
+            assert_eq!(message, reparsed);
+        }
+    }
+});
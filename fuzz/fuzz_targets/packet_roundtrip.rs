@@ -0,0 +1,31 @@
+//! Parses packets, serializes them and parses them again, the result must be the same packet.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pgp::packet::PacketParser;
+use pgp::ser::Serialize;
+
+const MAX_PACKET_SIZE: usize = 1024 * 1024;
+
+fuzz_target!(|data: &[u8]| {
+    for packet in PacketParser::with_max_packet_size(data, MAX_PACKET_SIZE).flatten() {
+        // not all parsed packets can be written, e.g. keys of unsupported versions
+        let Ok(bytes) = packet.to_bytes() else {
+            continue;
+        };
+
+        let mut parser = PacketParser::new(&bytes[..]);
+        let reparsed = parser
+            .next()
+            .expect("a packet")
+            .unwrap_or_else(|err| panic!("{:?} does not parse again: {:?}", packet, err));
+        assert!(
+            packet.lossless_eq(&reparsed),
+            "{:?} != {:?}",
+            packet,
+            reparsed
+        );
+        assert!(parser.next().is_none());
+    }
+});
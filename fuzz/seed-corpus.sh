@@ -0,0 +1,31 @@
+#!/bin/sh
+# Seeds the fuzz corpora with the test fixtures: armored files for armor_roundtrip, their
+# dearmored bytes and the binary fixtures for the other targets.
+set -eu
+
+cd "$(dirname "$0")"
+fixtures=../tests
+
+for target in packet_roundtrip message_parse key_compose armor_roundtrip; do
+    mkdir -p "corpus/$target"
+done
+
+find "$fixtures" -type f -size -64k -name '*.asc' | while read -r file; do
+    name=$(echo "$file" | sed 's|^\.\./tests/||; s|/|_|g')
+    cp "$file" "corpus/armor_roundtrip/$name"
+
+    if gpg --dearmor < "$file" > "corpus/$name.bin" 2>/dev/null; then
+        for target in packet_roundtrip message_parse key_compose; do
+            cp "corpus/$name.bin" "corpus/$target/$name.bin"
+        done
+    fi
+    rm -f "corpus/$name.bin"
+done
+
+find "$fixtures" -type f -size -64k \( -name '*.gpg' -o -name '*.pgp' -o -name '*.sig' \) |
+    while read -r file; do
+        name=$(echo "$file" | sed 's|^\.\./tests/||; s|/|_|g')
+        for target in packet_roundtrip message_parse key_compose; do
+            cp "$file" "corpus/$target/$name"
+        done
+    done
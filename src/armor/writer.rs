@@ -10,6 +10,7 @@ use crate::errors::Result;
 use crate::line_writer::{LineBreak, LineWriter};
 use crate::ser::Serialize;
 use crate::util::TeeWriter;
+use crate::ArmorOptions;
 
 use super::Headers;
 
@@ -32,7 +33,57 @@ pub fn write(
     Ok(())
 }
 
+/// Armors already serialized OpenPGP data, like [`write`] does for a [`Serialize`] source.
+///
+/// `data` is written as is, it is not checked to hold packets matching `typ`.
+pub fn write_bytes(
+    data: &[u8],
+    typ: BlockType,
+    writer: &mut impl Write,
+    headers: Option<&Headers>,
+    include_checksum: bool,
+) -> Result<()> {
+    write(&RawBytes(data), typ, writer, headers, include_checksum)
+}
+
+/// Serializes to the wrapped bytes.
+struct RawBytes<'a>(&'a [u8]);
+
+impl Serialize for RawBytes<'_> {
+    fn to_writer<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(self.0)?;
+        Ok(())
+    }
+}
+
+/// Armors `source` with the given options and returns the result as bytes.
+pub(crate) fn to_armored_bytes(
+    source: &impl Serialize,
+    typ: BlockType,
+    opts: ArmorOptions<'_>,
+) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    write(source, typ, &mut buf, opts.headers, opts.include_checksum)?;
+
+    Ok(buf)
+}
+
+/// Armors `source` with the given options and returns the result as a string.
+pub(crate) fn to_armored_string(
+    source: &impl Serialize,
+    typ: BlockType,
+    opts: ArmorOptions<'_>,
+) -> Result<String> {
+    let res =
+        String::from_utf8(to_armored_bytes(source, typ, opts)?).map_err(|e| e.utf8_error())?;
+    Ok(res)
+}
+
 fn write_header(writer: &mut impl Write, typ: BlockType, headers: Option<&Headers>) -> Result<()> {
+    if let Some(headers) = headers {
+        check_headers(headers)?;
+    }
+
     // write armor header
     writer.write_all(&b"-----BEGIN "[..])?;
     typ.to_writer(writer)?;
@@ -56,6 +107,26 @@ fn write_header(writer: &mut impl Write, typ: BlockType, headers: Option<&Header
     Ok(())
 }
 
+/// Rejects armor headers that would break the framing of the armor.
+fn check_headers(headers: &Headers) -> Result<()> {
+    for (key, values) in headers.iter() {
+        ensure!(
+            !key.is_empty() && !key.contains(|c: char| c == ':' || c.is_whitespace()),
+            "invalid armor header key {:?}",
+            key
+        );
+        for value in values {
+            ensure!(
+                !value.contains(|c: char| c == '\r' || c == '\n'),
+                "invalid armor header value {:?}",
+                value
+            );
+        }
+    }
+
+    Ok(())
+}
+
 fn write_body(
     writer: &mut impl Write,
     source: &impl Serialize,
@@ -214,4 +285,24 @@ mod tests {
             assert_eq!(lines[lines.len() - 1], "-----END PGP MESSAGE-----");
         }
     }
+
+    #[test]
+    fn writes_bytes() {
+        let mut rng = XorShiftRng::seed_from_u64(0);
+        let buf: Vec<u8> = (0..300).map(|_| rng.gen()).collect();
+
+        let mut expected = Vec::new();
+        write(
+            &TestSource::new(buf.clone()),
+            BlockType::Signature,
+            &mut expected,
+            None,
+            true,
+        )
+        .unwrap();
+
+        let mut dest = Vec::new();
+        write_bytes(&buf, BlockType::Signature, &mut dest, None, true).unwrap();
+        assert_eq!(dest, expected);
+    }
 }
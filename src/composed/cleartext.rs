@@ -7,19 +7,20 @@ use buffer_redux::BufReader;
 
 use chrono::SubsecRound;
 use nom::branch::alt;
-use nom::bytes::streaming::take_until1;
+use nom::bytes::streaming::take_until;
 use nom::character::streaming::line_ending;
 use nom::combinator::{complete, map_res};
 use nom::IResult;
-use smallvec::SmallVec;
 
 use crate::armor::{self, header_parser, read_from_buf, BlockType, Headers};
-use crate::crypto::hash::HashAlgorithm;
+use crate::crypto::hash::{HashAlgorithm, Hasher};
 use crate::errors::Result;
 use crate::line_writer::LineBreak;
 use crate::normalize_lines::Normalized;
-use crate::packet::{SignatureConfig, SignatureType, Subpacket, SubpacketData};
-use crate::types::{KeyVersion, PublicKeyTrait, SecretKeyTrait};
+use crate::packet::{
+    SignatureConfig, SignatureType, Subpacket, SubpacketCriticality, SubpacketData,
+};
+use crate::types::{PublicKeyTrait, SecretKeyTrait};
 use crate::{ArmorOptions, Deserializable, Signature, StandaloneSignature};
 
 /// Implementation of a Cleartext Signed Message.
@@ -65,6 +66,32 @@ impl CleartextSignedMessage {
 
     /// Sign the given text.
     pub fn sign<F>(text: &str, key: &impl SecretKeyTrait, key_pw: F) -> Result<Self>
+    where
+        F: FnOnce() -> String,
+    {
+        Self::sign_with(text, key, key_pw, None)
+    }
+
+    /// Sign the given text, like [`CleartextSignedMessage::sign`], with the given critical bits
+    /// on the standard subpackets of the signature.
+    pub fn sign_with_criticality<F>(
+        text: &str,
+        key: &impl SecretKeyTrait,
+        key_pw: F,
+        criticality: SubpacketCriticality,
+    ) -> Result<Self>
+    where
+        F: FnOnce() -> String,
+    {
+        Self::sign_with(text, key, key_pw, Some(criticality))
+    }
+
+    fn sign_with<F>(
+        text: &str,
+        key: &impl SecretKeyTrait,
+        key_pw: F,
+        criticality: Option<SubpacketCriticality>,
+    ) -> Result<Self>
     where
         F: FnOnce() -> String,
     {
@@ -72,17 +99,14 @@ impl CleartextSignedMessage {
         let algorithm = key.algorithm();
         let hash_algorithm = key.hash_alg();
         let hashed_subpackets = vec![
-            Subpacket::regular(SubpacketData::IssuerFingerprint(
-                KeyVersion::V4,
-                SmallVec::from_slice(&key.fingerprint()),
-            )),
+            Subpacket::regular(SubpacketData::issuer_fingerprint(&key)),
             Subpacket::regular(SubpacketData::SignatureCreationTime(
                 chrono::Utc::now().trunc_subsecs(0),
             )),
         ];
         let unhashed_subpackets = vec![Subpacket::regular(SubpacketData::Issuer(key_id))];
 
-        let config = SignatureConfig::new_v4(
+        let mut config = SignatureConfig::new_v4(
             Default::default(),
             SignatureType::Text,
             algorithm,
@@ -90,6 +114,7 @@ impl CleartextSignedMessage {
             hashed_subpackets,
             unhashed_subpackets,
         );
+        config.subpacket_criticality = criticality;
 
         Self::new(text, config, key, key_pw)
     }
@@ -278,6 +303,176 @@ impl CleartextSignedMessage {
     }
 }
 
+/// Hash algorithms used for streaming verification, if the message does not announce any
+/// using `Hash` headers.
+const DEFAULT_STREAMING_HASHES: &[HashAlgorithm] = &[
+    HashAlgorithm::SHA2_256,
+    HashAlgorithm::SHA2_384,
+    HashAlgorithm::SHA2_512,
+    HashAlgorithm::SHA2_224,
+    HashAlgorithm::SHA1,
+];
+
+/// Streaming verifier for a Cleartext Signed Message.
+///
+/// Yields the dash-unescaped lines of the signed text, without line endings, while hashing
+/// them in the same way as [`CleartextSignedMessage::signed_text`]. Once all lines are
+/// consumed, the trailing signature block can be checked using [`CleartextSignedReader::verify`].
+///
+/// The text is hashed with all algorithms announced in the `Hash` headers. If there are none,
+/// a default set of algorithms is used.
+pub struct CleartextSignedReader<R: BufRead> {
+    reader: R,
+    hashers: Vec<(HashAlgorithm, Box<dyn Hasher>)>,
+    /// Was the previous line terminated by a line ending. It is only hashed once it is known
+    /// not to be the last line of the text.
+    pending_line_break: bool,
+    /// The armor header line of the signature, once the end of the text was reached.
+    signature_header: Option<Vec<u8>>,
+}
+
+impl<R: BufRead> std::fmt::Debug for CleartextSignedReader<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CleartextSignedReader")
+            .field(
+                "hashes",
+                &self.hashers.iter().map(|(h, _)| h).collect::<Vec<_>>(),
+            )
+            .field("pending_line_break", &self.pending_line_break)
+            .field("done", &self.signature_header.is_some())
+            .finish()
+    }
+}
+
+impl<R: BufRead> CleartextSignedReader<R> {
+    /// Reads the armor header of the message, leaving the reader positioned at the start
+    /// of the text.
+    pub fn new(mut reader: R) -> Result<(Self, Headers)> {
+        let (typ, headers, has_leading_data) =
+            read_from_buf(&mut reader, "cleartext header", header_parser)?;
+        ensure_eq!(typ, BlockType::CleartextMessage, "unexpected block type");
+        ensure!(
+            !has_leading_data,
+            "must not have leading data for a cleartext message"
+        );
+
+        let mut hashes = validate_headers(headers.clone())?;
+        if hashes.is_empty() {
+            hashes = DEFAULT_STREAMING_HASHES.to_vec();
+        }
+        hashes.dedup();
+        let hashers = hashes
+            .into_iter()
+            .map(|h| Ok((h, h.new_hasher()?)))
+            .collect::<Result<_>>()?;
+
+        Ok((
+            Self {
+                reader,
+                hashers,
+                pending_line_break: false,
+                signature_header: None,
+            },
+            headers,
+        ))
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        for (_, hasher) in &mut self.hashers {
+            hasher.update(data);
+        }
+    }
+
+    fn read_line(&mut self) -> Result<Option<String>> {
+        if self.signature_header.is_some() {
+            return Ok(None);
+        }
+
+        let mut line = Vec::new();
+        let read = self.reader.read_until(b'\n', &mut line)?;
+        ensure!(read > 0, "missing signature");
+
+        if line.starts_with(b"-----") {
+            // End of the text, the line ending of the last line is not part of it.
+            self.signature_header = Some(line);
+            return Ok(None);
+        }
+
+        let has_line_break = line.ends_with(b"\n");
+        if has_line_break {
+            line.pop();
+            if line.ends_with(b"\r") {
+                line.pop();
+            }
+        }
+        let content = line.strip_prefix(b"- ").unwrap_or(&line[..]);
+        let content = std::str::from_utf8(content)?.to_string();
+
+        if self.pending_line_break {
+            self.update(b"\r\n");
+        }
+        let normalized: Vec<u8> = Normalized::new(content.bytes(), LineBreak::Crlf).collect();
+        self.update(&normalized);
+        self.pending_line_break = has_line_break;
+
+        Ok(Some(content))
+    }
+
+    /// Reads the remaining text and the signatures, and verifies them against the given key.
+    ///
+    /// On success returns the first signature that verified against this key.
+    pub fn verify(mut self, key: &impl PublicKeyTrait) -> Result<StandaloneSignature> {
+        while self.read_line()?.is_some() {}
+
+        let header = self
+            .signature_header
+            .take()
+            .ok_or_else(|| format_err!("missing signature"))?;
+        // The signature block is small, so buffer it, to allow parsing the header line again.
+        let mut block = header;
+        self.reader.read_to_end(&mut block)?;
+        let mut dearmor = armor::Dearmor::new(&block[..]);
+        dearmor.read_header()?;
+        let typ = dearmor
+            .typ
+            .ok_or_else(|| format_err!("dearmor failed to retrieve armor type"))?;
+        ensure_eq!(typ, BlockType::Signature, "invalid block type");
+
+        let signatures: Vec<StandaloneSignature> =
+            StandaloneSignature::from_bytes_many(&mut dearmor).collect::<Result<_>>()?;
+
+        let (_, _, _, b) = dearmor.into_parts();
+        if has_rest(b)? {
+            bail!("unexpected trailing data");
+        }
+
+        for signature in signatures {
+            let hash_alg = signature.signature.hash_alg();
+            let Some((_, hasher)) = self.hashers.iter().find(|(h, _)| *h == hash_alg) else {
+                warn!("signature uses unannounced hash algorithm {:?}", hash_alg);
+                continue;
+            };
+            if signature
+                .signature
+                .verify_hashed(key, hasher.clone_boxed())
+                .is_ok()
+            {
+                return Ok(signature);
+            }
+        }
+
+        bail!("No matching signature found")
+    }
+}
+
+impl<R: BufRead> Iterator for CleartextSignedReader<R> {
+    type Item = Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_line().transpose()
+    }
+}
+
 fn validate_headers(headers: Headers) -> Result<Vec<HashAlgorithm>> {
     let mut hashes = Vec::new();
     for (name, values) in headers {
@@ -345,8 +540,9 @@ fn to_string(b: &[u8]) -> std::result::Result<String, std::str::Utf8Error> {
 fn cleartext_body(i: &[u8]) -> IResult<&[u8], String> {
     let (i, lines) = map_res(
         alt((
-            complete(take_until1("\r\n-----")),
-            complete(take_until1("\n-----")),
+            // the signed text may be empty
+            complete(take_until("\r\n-----")),
+            complete(take_until("\n-----")),
         )),
         to_string,
     )(i)?;
@@ -431,6 +627,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "rsa")]
     fn test_cleartext_interop_testsuite_1_good() {
         let _ = pretty_env_logger::try_init();
 
@@ -550,6 +747,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "rsa")]
     fn test_sign() {
         let key_data = std::fs::read_to_string("./tests/unit-tests/cleartext-key-01.asc").unwrap();
         let (key, _) = SignedSecretKey::from_string(&key_data).unwrap();
@@ -559,6 +757,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "rsa")]
     fn test_sign_no_newline() {
         const MSG: &str = "message without newline at the end";
 
@@ -570,4 +769,143 @@ mod tests {
 
         msg.verify(&key.public_key()).unwrap();
     }
+
+    #[test]
+    #[cfg(feature = "rsa")]
+    fn test_sign_empty_and_leading_blank_lines() {
+        let key_data = std::fs::read_to_string("./tests/unit-tests/cleartext-key-01.asc").unwrap();
+        let (key, _) = SignedSecretKey::from_string(&key_data).unwrap();
+
+        for text in ["", "\n", "\n\nhello\n", "\r\n\r\nhello"] {
+            let msg = CleartextSignedMessage::sign(text, &key, String::new).unwrap();
+            let armored = msg.to_armored_string(None.into()).unwrap();
+
+            let (parsed, _) = CleartextSignedMessage::from_string(&armored).unwrap();
+            assert_eq!(parsed, msg, "{text:?}");
+            parsed.verify(&key.public_key()).unwrap();
+
+            let (reader, _) = CleartextSignedReader::new(armored.as_bytes()).unwrap();
+            reader.verify(&key.public_key()).unwrap();
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "rsa")]
+    fn test_streaming_verify_interop() {
+        let data = std::fs::read_to_string("./tests/unit-tests/cleartext-msg-01.asc").unwrap();
+        let key_data = std::fs::read_to_string("./tests/unit-tests/cleartext-key-01.asc").unwrap();
+        let (key, _) = SignedSecretKey::from_string(&key_data).unwrap();
+
+        let (mut reader, headers) = CleartextSignedReader::new(data.as_bytes()).unwrap();
+        assert_eq!(headers.get("Hash"), Some(&vec!["SHA512".to_string()]));
+
+        let lines: Vec<String> = reader.by_ref().collect::<Result<_>>().unwrap();
+        assert_eq!(
+            lines,
+            [
+                "From the grocery store we need:",
+                "",
+                "- tofu",
+                "- vegetables",
+                "- noodles",
+                "",
+                "",
+            ]
+        );
+        reader.verify(&key.public_key()).unwrap();
+    }
+
+    #[test]
+    fn test_sign_with_criticality() {
+        let (key, _) = SignedSecretKey::from_armor_single(
+            std::fs::File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+        )
+        .unwrap();
+        let criticality = SubpacketCriticality {
+            signature_creation_time: true,
+            issuer: true,
+            ..Default::default()
+        };
+
+        let msg = CleartextSignedMessage::sign_with_criticality(
+            "hello\n",
+            &key,
+            String::new,
+            criticality,
+        )
+        .unwrap();
+        let armored = msg.to_armored_string(Default::default()).unwrap();
+        let (msg, _) = CleartextSignedMessage::from_string(&armored).unwrap();
+        msg.verify(&key.public_key()).unwrap();
+
+        let config = &msg.signatures()[0].signature.config;
+        for subpacket in config
+            .hashed_subpackets()
+            .chain(config.unhashed_subpackets())
+        {
+            let critical = matches!(
+                subpacket.data,
+                SubpacketData::SignatureCreationTime(_) | SubpacketData::Issuer(_)
+            );
+            assert_eq!(subpacket.is_critical, critical, "{subpacket:?}");
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "rsa")]
+    fn test_cleartext_bom_crlf() {
+        // byte order mark, CRLF line endings and a Latin-1 comment in the signature armor
+        let data = std::fs::read("./tests/unit-tests/armor/bom-crlf-cleartext.asc").unwrap();
+        let key_data = std::fs::read_to_string("./tests/unit-tests/cleartext-key-01.asc").unwrap();
+        let (key, _) = SignedSecretKey::from_string(&key_data).unwrap();
+
+        let (msg, headers) = CleartextSignedMessage::from_armor(&data[..]).unwrap();
+        assert_eq!(
+            headers.get("Comment"),
+            Some(&vec!["gr\u{FFFD}\u{FFFD}e aus K\u{FFFD}ln".to_string()])
+        );
+        assert_eq!(
+            msg.signed_text(),
+            "From the grocery store we need:\r\n\r\n- tofu\r\n- vegetables\r\n- noodles\r\n\r\n"
+        );
+        msg.verify(&key.public_key()).unwrap();
+
+        let (any, _) = Any::from_armor(&data[..]).unwrap();
+        assert!(matches!(any, Any::Cleartext(_)));
+
+        let (reader, _) = CleartextSignedReader::new(&data[..]).unwrap();
+        reader.verify(&key.public_key()).unwrap();
+
+        // anything but a byte order mark is still leading data
+        let mut data = data;
+        data.insert(0, b' ');
+        assert!(CleartextSignedMessage::from_armor(&data[..]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "rsa")]
+    fn test_streaming_verify_roundtrip() {
+        let key_data = std::fs::read_to_string("./tests/unit-tests/cleartext-key-01.asc").unwrap();
+        let (key, _) = SignedSecretKey::from_string(&key_data).unwrap();
+
+        for text in [
+            "hello\n-world-what-\nis up\n",
+            "message without newline at the end",
+            "trailing whitespace  \nand\r\nmixed line endings\n\n",
+            "- dashes\n-----\n",
+        ] {
+            let msg = CleartextSignedMessage::sign(text, &key, String::new).unwrap();
+            let armored = msg.to_armored_bytes(None.into()).unwrap();
+
+            let (reader, _) = CleartextSignedReader::new(&armored[..]).unwrap();
+            reader.verify(&key.public_key()).unwrap();
+
+            // modified text
+            let tampered = String::from_utf8(armored.clone())
+                .unwrap()
+                .replacen("\n\n", "\n\nx", 1);
+            let (reader, _) = CleartextSignedReader::new(tampered.as_bytes()).unwrap();
+            assert!(reader.verify(&key.public_key()).is_err(), "{text:?}");
+        }
+    }
 }
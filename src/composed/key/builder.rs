@@ -5,13 +5,19 @@ use rand::{thread_rng, CryptoRng, Rng};
 use smallvec::SmallVec;
 
 use crate::composed::{KeyDetails, SecretKey, SecretSubkey};
+#[cfg(feature = "dsa")]
+use crate::crypto::dsa;
 use crate::crypto::ecc_curve::ECCCurve;
+#[cfg(feature = "ed25519")]
+use crate::crypto::eddsa;
 use crate::crypto::hash::HashAlgorithm;
 use crate::crypto::public_key::PublicKeyAlgorithm;
+#[cfg(feature = "rsa")]
+use crate::crypto::rsa;
 use crate::crypto::sym::SymmetricKeyAlgorithm;
-use crate::crypto::{dsa, ecdh, ecdsa, eddsa, rsa};
+use crate::crypto::{ecdh, ecdsa};
 use crate::errors::Result;
-use crate::packet::{self, KeyFlags, UserAttribute, UserId};
+use crate::packet::{self, Features, KeyFlags, SubpacketCriticality, UserAttribute, UserId};
 use crate::types::{self, CompressionAlgorithm, PublicParams, RevocationKey, S2kParams};
 
 #[derive(Debug, PartialEq, Eq, Builder)]
@@ -29,16 +35,28 @@ pub struct SecretKeyParams {
 
     // -- Preferences
     /// List of symmetric algorithms that indicate which algorithms the key holder prefers to use.
-    #[builder(default)]
+    #[builder(default, setter(into))]
     preferred_symmetric_algorithms: SmallVec<[SymmetricKeyAlgorithm; 8]>,
     /// List of hash algorithms that indicate which algorithms the key holder prefers to use.
-    #[builder(default)]
+    #[builder(default, setter(into))]
     preferred_hash_algorithms: SmallVec<[HashAlgorithm; 8]>,
     /// List of compression algorithms that indicate which algorithms the key holder prefers to use.
-    #[builder(default)]
+    #[builder(default, setter(into))]
     preferred_compression_algorithms: SmallVec<[CompressionAlgorithm; 8]>,
     #[builder(default)]
     revocation_key: Option<RevocationKey>,
+    /// Also publish the key flags and preferences on a direct key signature, instead of only
+    /// on the user id self-signatures.
+    #[builder(default)]
+    direct_key_signature: bool,
+    /// The features advertised on the self-signatures, modification detection (SEIPDv1)
+    /// by default. Set `seipd_v2` to receive AEAD encrypted messages.
+    #[builder(default = "Features::from(&[0x01][..])")]
+    features: Features,
+    /// The critical bit of the standard subpackets of all self-signatures and binding
+    /// signatures, none of them are critical by default.
+    #[builder(default, setter(strip_option))]
+    subpacket_criticality: Option<SubpacketCriticality>,
 
     #[builder]
     primary_user_id: String,
@@ -72,8 +90,15 @@ pub struct SubkeyParams {
     can_sign: bool,
     #[builder(default)]
     can_certify: bool,
+    /// Allows encrypting both communications and storage.
     #[builder(default)]
     can_encrypt: bool,
+    /// Allows encrypting communications, without implying storage.
+    #[builder(default)]
+    can_encrypt_comms: bool,
+    /// Allows encrypting storage, without implying communications.
+    #[builder(default)]
+    can_encrypt_storage: bool,
     #[builder(default)]
     can_authenticate: bool,
 
@@ -161,6 +186,7 @@ impl SecretKeyParamsBuilder {
 }
 
 impl SecretKeyParams {
+    /// Same as [`SecretKeyParams::generate_with_rng`], but uses [`thread_rng`] for RNG.
     pub fn generate(self) -> Result<SecretKey> {
         let rng = thread_rng();
         self.generate_with_rng(rng)
@@ -172,14 +198,14 @@ impl SecretKeyParams {
         let (public_params, secret_params) =
             self.key_type.generate_with_rng(&mut rng, passphrase, s2k)?;
         let primary_key = packet::SecretKey {
-            details: packet::PublicKey {
-                packet_version: self.packet_version,
-                version: self.version,
-                algorithm: self.key_type.to_alg(),
-                created_at: self.created_at,
-                expiration: self.expiration.map(|v| v.as_secs() as u16),
+            details: packet::PublicKey::new(
+                self.packet_version,
+                self.version,
+                self.key_type.to_alg(),
+                self.created_at,
+                self.expiration.map(|v| v.as_secs() as u16),
                 public_params,
-            },
+            )?,
             secret_params,
         };
 
@@ -189,54 +215,71 @@ impl SecretKeyParams {
         keyflags.set_encrypt_storage(self.can_encrypt);
         keyflags.set_sign(self.can_sign);
 
+        let subkeys = self
+            .subkeys
+            .into_iter()
+            .map(|subkey| {
+                let passphrase = subkey.passphrase;
+                let key_pw = passphrase.clone().unwrap_or_default();
+                let s2k = subkey
+                    .s2k
+                    .unwrap_or_else(|| S2kParams::new_default(&mut rng));
+                let (public_params, secret_params) = subkey
+                    .key_type
+                    .generate_with_rng(&mut rng, passphrase, s2k)?;
+                let mut keyflags = KeyFlags::default();
+                keyflags.set_certify(subkey.can_certify);
+                keyflags.set_encrypt_comms(subkey.can_encrypt || subkey.can_encrypt_comms);
+                keyflags.set_encrypt_storage(subkey.can_encrypt || subkey.can_encrypt_storage);
+                keyflags.set_sign(subkey.can_sign);
+                keyflags.set_authentication(subkey.can_authenticate);
+
+                // the backsig is made now, while the password of the subkey is known
+                let mut secret_subkey = SecretSubkey::new(
+                    packet::SecretSubkey {
+                        details: packet::PublicSubkey::new(
+                            subkey.packet_version,
+                            subkey.version,
+                            subkey.key_type.to_alg(),
+                            subkey.created_at,
+                            subkey.expiration.map(|v| v.as_secs() as u16),
+                            public_params,
+                        )?,
+                        secret_params,
+                    },
+                    keyflags,
+                );
+                if let Some(criticality) = self.subpacket_criticality {
+                    secret_subkey = secret_subkey.with_subpacket_criticality(criticality);
+                }
+                secret_subkey.with_backsig(&primary_key, || key_pw)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut details = KeyDetails::new(
+            UserId::from_str(Default::default(), &self.primary_user_id),
+            self.user_ids
+                .iter()
+                .map(|m| UserId::from_str(Default::default(), m))
+                .collect(),
+            self.user_attributes,
+            keyflags,
+            self.preferred_symmetric_algorithms,
+            self.preferred_hash_algorithms,
+            self.preferred_compression_algorithms,
+            self.revocation_key,
+        )
+        .with_features(self.features)
+        .with_direct_key_signature(self.direct_key_signature);
+        if let Some(criticality) = self.subpacket_criticality {
+            details = details.with_subpacket_criticality(criticality);
+        }
+
         Ok(SecretKey::new(
             primary_key,
-            KeyDetails::new(
-                UserId::from_str(Default::default(), &self.primary_user_id),
-                self.user_ids
-                    .iter()
-                    .map(|m| UserId::from_str(Default::default(), m))
-                    .collect(),
-                self.user_attributes,
-                keyflags,
-                self.preferred_symmetric_algorithms,
-                self.preferred_hash_algorithms,
-                self.preferred_compression_algorithms,
-                self.revocation_key,
-            ),
+            details,
             Default::default(),
-            self.subkeys
-                .into_iter()
-                .map(|subkey| {
-                    let passphrase = subkey.passphrase;
-                    let s2k = subkey
-                        .s2k
-                        .unwrap_or_else(|| S2kParams::new_default(&mut rng));
-                    let (public_params, secret_params) =
-                        subkey.key_type.generate(passphrase, s2k)?;
-                    let mut keyflags = KeyFlags::default();
-                    keyflags.set_certify(subkey.can_certify);
-                    keyflags.set_encrypt_comms(subkey.can_encrypt);
-                    keyflags.set_encrypt_storage(subkey.can_encrypt);
-                    keyflags.set_sign(subkey.can_sign);
-                    keyflags.set_authentication(subkey.can_authenticate);
-
-                    Ok(SecretSubkey::new(
-                        packet::SecretSubkey {
-                            details: packet::PublicSubkey {
-                                packet_version: subkey.packet_version,
-                                version: subkey.version,
-                                algorithm: subkey.key_type.to_alg(),
-                                created_at: subkey.created_at,
-                                expiration: subkey.expiration.map(|v| v.as_secs() as u16),
-                                public_params,
-                            },
-                            secret_params,
-                        },
-                        keyflags,
-                    ))
-                })
-                .collect::<Result<Vec<_>>>()?,
+            subkeys,
         ))
     }
 }
@@ -266,6 +309,7 @@ pub enum DsaKeySize {
     B3072 = 3072,
 }
 
+#[cfg(feature = "dsa")]
 impl From<DsaKeySize> for dsa::KeySize {
     fn from(value: DsaKeySize) -> Self {
         match value {
@@ -288,6 +332,7 @@ impl KeyType {
         }
     }
 
+    /// Same as [`KeyType::generate_with_rng`], but uses [`thread_rng`] for RNG.
     pub fn generate(
         &self,
         passphrase: Option<String>,
@@ -303,14 +348,28 @@ impl KeyType {
         passphrase: Option<String>,
         s2k: types::S2kParams,
     ) -> Result<(PublicParams, types::SecretParams)> {
-        let (pub_params, plain) = match self {
+        let (mut pub_params, mut plain) = match self {
+            #[cfg(feature = "rsa")]
             KeyType::Rsa(bit_size) => rsa::generate_key(rng, *bit_size as usize)?,
+            #[cfg(not(feature = "rsa"))]
+            KeyType::Rsa(_) => unsupported_err!("RSA (feature \"rsa\" is disabled)"),
             KeyType::ECDH(curve) => ecdh::generate_key(rng, curve)?,
+            #[cfg(feature = "ed25519")]
             KeyType::EdDSA => eddsa::generate_key(rng),
+            #[cfg(not(feature = "ed25519"))]
+            KeyType::EdDSA => unsupported_err!("EdDSA (feature \"ed25519\" is disabled)"),
             KeyType::ECDSA(curve) => ecdsa::generate_key(rng, curve)?,
+            #[cfg(feature = "dsa")]
             KeyType::Dsa(key_size) => dsa::generate_key(rng, (*key_size).into())?,
+            #[cfg(not(feature = "dsa"))]
+            KeyType::Dsa(_) => unsupported_err!("DSA (feature \"dsa\" is disabled)"),
         };
 
+        // Other implementations re-encode MPIs minimally, which would change the fingerprint
+        // of a key serialized with superfluous leading zeros.
+        pub_params.canonicalize();
+        plain.canonicalize();
+
         let secret = match passphrase {
             Some(passphrase) => {
                 // TODO: derive from key itself
@@ -331,13 +390,20 @@ mod tests {
 
     use super::*;
 
+    #[cfg(any(feature = "rsa", feature = "x25519"))]
     use crate::composed::{Deserializable, SignedPublicKey, SignedSecretKey};
+    #[cfg(feature = "rsa")]
+    use crate::ser::Serialize;
+    #[cfg(feature = "rsa")]
+    use crate::types::KeyTrait;
+    #[cfg(any(feature = "rsa", feature = "x25519"))]
     use crate::types::SecretKeyTrait;
 
     use rand::SeedableRng;
     use rand_chacha::ChaCha8Rng;
 
     #[test]
+    #[cfg(feature = "rsa")]
     #[ignore] // slow in debug mode
     fn test_key_gen_rsa_2048() {
         let _ = pretty_env_logger::try_init();
@@ -349,6 +415,7 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "rsa")]
     fn gen_rsa_2048<R: Rng + CryptoRng>(mut rng: R) {
         let mut key_params = SecretKeyParamsBuilder::default();
         key_params
@@ -415,9 +482,6 @@ mod tests {
             .to_armored_string(None.into())
             .expect("failed to serialize key");
 
-        std::fs::write("sample-rsa-enc.sec.asc", &armor_enc).unwrap();
-        std::fs::write("sample-rsa.sec.asc", &armor_plain).unwrap();
-
         let (signed_key2_enc, _headers) =
             SignedSecretKey::from_string(&armor_enc).expect("failed to parse key (enc)");
         signed_key2_enc.verify().expect("invalid key (enc)");
@@ -447,8 +511,6 @@ mod tests {
             .to_armored_string(None.into())
             .expect("failed to serialize public key");
 
-        std::fs::write("sample-rsa.pub.asc", &armor).unwrap();
-
         let (signed_key2, _headers) =
             SignedPublicKey::from_string(&armor).expect("failed to parse public key");
         signed_key2.verify().expect("invalid public key");
@@ -456,6 +518,7 @@ mod tests {
 
     #[ignore]
     #[test]
+    #[cfg(feature = "x25519")]
     fn key_gen_x25519_long() {
         let mut rng = ChaCha8Rng::seed_from_u64(0);
         for i in 0..10_000 {
@@ -465,6 +528,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "x25519")]
     fn key_gen_x25519_short() {
         let mut rng = ChaCha8Rng::seed_from_u64(0);
         for _ in 0..100 {
@@ -472,6 +536,7 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "x25519")]
     fn gen_x25519<R: Rng + CryptoRng>(rng: R) {
         let _ = pretty_env_logger::try_init();
 
@@ -519,7 +584,6 @@ mod tests {
             .expect("failed to serialize key");
 
         println!("armor: {armor:?}");
-        std::fs::write("sample-x25519.sec.asc", &armor).unwrap();
 
         let (signed_key2, _headers) =
             SignedSecretKey::from_string(&armor).expect("failed to parse key");
@@ -539,13 +603,12 @@ mod tests {
             .to_armored_string(None.into())
             .expect("failed to serialize public key");
 
-        std::fs::write("sample-x25519.pub.asc", &armor).unwrap();
-
         let (signed_key2, _headers) =
             SignedPublicKey::from_string(&armor).expect("failed to parse public key");
         signed_key2.verify().expect("invalid public key");
     }
 
+    #[cfg(all(any(feature = "nist", feature = "k256"), feature = "x25519"))]
     fn gen_ecdsa<R: Rng + CryptoRng>(rng: &mut R, curve: ECCCurve) {
         let _ = pretty_env_logger::try_init();
 
@@ -592,8 +655,6 @@ mod tests {
             .to_armored_string(None.into())
             .expect("failed to serialize key");
 
-        std::fs::write("sample-ecdsa.sec.asc", &armor).unwrap();
-
         let (signed_key2, _headers) =
             SignedSecretKey::from_string(&armor).expect("failed to parse key");
         signed_key2.verify().expect("invalid key");
@@ -612,14 +673,13 @@ mod tests {
             .to_armored_string(None.into())
             .expect("failed to serialize public key");
 
-        std::fs::write(format!("sample-ecdsa-{curve:?}.pub.asc"), &armor).unwrap();
-
         let (signed_key2, _headers) =
             SignedPublicKey::from_string(&armor).expect("failed to parse public key");
         signed_key2.verify().expect("invalid public key");
     }
 
     #[test]
+    #[cfg(all(feature = "nist", feature = "x25519"))]
     fn key_gen_ecdsa_p256() {
         let rng = &mut ChaCha8Rng::seed_from_u64(0);
         for _ in 0..=175 {
@@ -628,6 +688,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(all(feature = "nist", feature = "x25519"))]
     fn key_gen_ecdsa_p384() {
         let rng = &mut ChaCha8Rng::seed_from_u64(0);
         for _ in 0..100 {
@@ -636,6 +697,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(all(feature = "nist", feature = "x25519"))]
     fn key_gen_ecdsa_p521() {
         let rng = &mut ChaCha8Rng::seed_from_u64(0);
         for _ in 0..100 {
@@ -644,6 +706,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(all(feature = "k256", feature = "x25519"))]
     fn key_gen_ecdsa_secp256k1() {
         let rng = &mut ChaCha8Rng::seed_from_u64(0);
         for _ in 0..100 {
@@ -651,6 +714,7 @@ mod tests {
         }
     }
 
+    #[cfg(all(feature = "dsa", feature = "x25519"))]
     fn gen_dsa<R: Rng + CryptoRng>(rng: &mut R, key_size: DsaKeySize) {
         let _ = pretty_env_logger::try_init();
 
@@ -697,8 +761,6 @@ mod tests {
             .to_armored_string(None.into())
             .expect("failed to serialize key");
 
-        std::fs::write("sample-dsa.sec.asc", &armor).unwrap();
-
         let (signed_key2, _headers) =
             SignedSecretKey::from_string(&armor).expect("failed to parse key");
         signed_key2.verify().expect("invalid key");
@@ -717,8 +779,6 @@ mod tests {
             .to_armored_string(None.into())
             .expect("failed to serialize public key");
 
-        std::fs::write(format!("sample-dsa-{key_size:?}.pub.asc"), &armor).unwrap();
-
         let (signed_key2, _headers) =
             SignedPublicKey::from_string(&armor).expect("failed to parse public key");
         signed_key2.verify().expect("invalid public key");
@@ -726,6 +786,7 @@ mod tests {
 
     // Test is slow in debug mode
     #[test]
+    #[cfg(all(feature = "dsa", feature = "x25519"))]
     #[ignore]
     fn key_gen_dsa() {
         let rng = &mut ChaCha8Rng::seed_from_u64(0);
@@ -735,4 +796,71 @@ mod tests {
             gen_dsa(rng, DsaKeySize::B3072);
         }
     }
+
+    /// RSA is too slow for the fingerprint proptest, so it is covered by a single fixed seed.
+    #[test]
+    #[cfg(feature = "rsa")]
+    fn key_gen_rsa_fixed_seed() {
+        let created_at = chrono::DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let generate = || {
+            SecretKeyParamsBuilder::default()
+                .key_type(KeyType::Rsa(2048))
+                .can_certify(true)
+                .can_sign(true)
+                .primary_user_id("Me <me@mail.com>".into())
+                .created_at(created_at)
+                .subkey(
+                    SubkeyParamsBuilder::default()
+                        .key_type(KeyType::Rsa(2048))
+                        .can_encrypt(true)
+                        .created_at(created_at)
+                        .build()
+                        .unwrap(),
+                )
+                .build()
+                .unwrap()
+                .generate_with_rng(ChaCha8Rng::seed_from_u64(0))
+                .unwrap()
+        };
+
+        let key = generate();
+        assert_eq!(key, generate());
+
+        let signed_key = key.sign(|| "".into()).unwrap();
+        signed_key.verify().unwrap();
+        signed_key.canonical_fingerprint_check().unwrap();
+
+        let public_key = signed_key
+            .public_key()
+            .sign(&signed_key, || "".into())
+            .unwrap();
+        let bytes = public_key.to_bytes().unwrap();
+        let parsed = SignedPublicKey::from_bytes(&bytes[..]).unwrap();
+        assert_eq!(public_key.fingerprint(), parsed.fingerprint());
+        assert_eq!(
+            public_key.public_subkeys[0].fingerprint(),
+            parsed.public_subkeys[0].fingerprint()
+        );
+    }
+
+    #[test]
+    fn key_gen_rejects_versions_without_key_id() {
+        let generate = |version| {
+            SecretKeyParamsBuilder::default()
+                .key_type(KeyType::EdDSA)
+                .can_certify(true)
+                .primary_user_id("Me <me@mail.com>".into())
+                .version(version)
+                .build()
+                .unwrap()
+                .generate_with_rng(ChaCha8Rng::seed_from_u64(0))
+        };
+
+        // v3 key ids come from an RSA modulus, unknown versions have no defined key id
+        assert!(generate(types::KeyVersion::V3).is_err());
+        assert!(generate(types::KeyVersion::Other(9)).is_err());
+        assert!(generate(types::KeyVersion::V4).is_ok());
+    }
 }
@@ -16,7 +16,7 @@
 //!
 //! let mut key_params = SecretKeyParamsBuilder::default();
 //! key_params
-//!     .key_type(KeyType::Rsa(2048))
+//!     .key_type(KeyType::EdDSA)
 //!     .can_certify(false)
 //!     .can_sign(true)
 //!     .primary_user_id("Me <me@example.com>".into())
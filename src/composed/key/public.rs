@@ -1,8 +1,7 @@
 use std::io;
 
-use chrono::SubsecRound;
+use chrono::{Duration, SubsecRound};
 use rand::{CryptoRng, Rng};
-use smallvec::SmallVec;
 
 use crate::composed::{KeyDetails, SignedPublicKey, SignedPublicSubKey};
 use crate::crypto::hash::HashAlgorithm;
@@ -11,7 +10,7 @@ use crate::errors::Result;
 use crate::packet::{
     self, KeyFlags, SignatureConfigBuilder, SignatureType, Subpacket, SubpacketData,
 };
-use crate::types::{KeyId, KeyTrait, Mpi, PublicKeyTrait, SecretKeyTrait};
+use crate::types::{KeyId, KeyTrait, KeyVersion, Mpi, PublicKeyTrait, SecretKeyTrait};
 
 /// User facing interface to work with a public key.
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -25,6 +24,8 @@ pub struct PublicKey {
 pub struct PublicSubkey {
     key: packet::PublicSubkey,
     keyflags: KeyFlags,
+    /// The expiration set in the binding signature, see [`PublicSubkey::with_expiration`].
+    expiration: Option<Duration>,
 }
 
 impl PublicKey {
@@ -52,11 +53,7 @@ impl PublicKey {
             .map(|k| k.sign(sec_key, key_pw.clone()))
             .collect::<Result<Vec<_>>>()?;
 
-        Ok(SignedPublicKey {
-            primary_key,
-            details,
-            public_subkeys,
-        })
+        Ok(SignedPublicKey::new(primary_key, details, public_subkeys))
     }
 }
 
@@ -72,6 +69,10 @@ impl KeyTrait for PublicKey {
     fn algorithm(&self) -> PublicKeyAlgorithm {
         self.primary_key.algorithm()
     }
+
+    fn key_version(&self) -> KeyVersion {
+        self.primary_key.version()
+    }
 }
 
 impl PublicKeyTrait for PublicKey {
@@ -90,24 +91,38 @@ impl PublicKeyTrait for PublicKey {
 
 impl PublicSubkey {
     pub fn new(key: packet::PublicSubkey, keyflags: KeyFlags) -> Self {
-        PublicSubkey { key, keyflags }
+        PublicSubkey {
+            key,
+            keyflags,
+            expiration: None,
+        }
+    }
+
+    /// Lets the subkey expire `expiration` after its creation, independent of the expiration
+    /// of the primary key.
+    pub fn with_expiration(mut self, expiration: Duration) -> Self {
+        self.expiration = Some(expiration);
+        self
     }
 
+    /// Creates the binding signature for this subkey.
     pub fn sign<F>(self, sec_key: &impl SecretKeyTrait, key_pw: F) -> Result<SignedPublicSubKey>
     where
         F: (FnOnce() -> String) + Clone,
     {
         let key = self.key;
-        let hashed_subpackets = vec![
+        let mut hashed_subpackets = vec![
             Subpacket::regular(SubpacketData::SignatureCreationTime(
                 chrono::Utc::now().trunc_subsecs(0),
             )),
             Subpacket::regular(SubpacketData::KeyFlags(self.keyflags.into())),
-            Subpacket::regular(SubpacketData::IssuerFingerprint(
-                Default::default(),
-                SmallVec::from_slice(&sec_key.fingerprint()),
-            )),
+            Subpacket::regular(SubpacketData::issuer_fingerprint(&sec_key)),
         ];
+        if let Some(expiration) = self.expiration {
+            hashed_subpackets.push(Subpacket::regular(SubpacketData::KeyExpirationTime(
+                expiration,
+            )));
+        }
 
         let config = SignatureConfigBuilder::default()
             .typ(SignatureType::SubkeyBinding)
@@ -121,7 +136,7 @@ impl PublicSubkey {
 
         let signatures = vec![config.sign_key_binding(sec_key, key_pw, &key)?];
 
-        Ok(SignedPublicSubKey { key, signatures })
+        Ok(SignedPublicSubKey::new(key, signatures))
     }
 }
 
@@ -137,6 +152,14 @@ impl KeyTrait for PublicSubkey {
     fn algorithm(&self) -> PublicKeyAlgorithm {
         self.key.algorithm()
     }
+
+    fn key_version(&self) -> KeyVersion {
+        self.key.version()
+    }
+
+    fn key_flags(&self) -> Option<KeyFlags> {
+        Some(self.keyflags)
+    }
 }
 
 impl PublicKeyTrait for PublicSubkey {
@@ -1,13 +1,13 @@
-use chrono::SubsecRound;
-use smallvec::SmallVec;
+use chrono::{Duration, SubsecRound};
 
 use crate::composed::{KeyDetails, PublicSubkey, SignedSecretKey, SignedSecretSubKey};
 use crate::crypto::public_key::PublicKeyAlgorithm;
 use crate::errors::Result;
 use crate::packet::{
-    self, KeyFlags, SignatureConfigBuilder, SignatureType, Subpacket, SubpacketData,
+    self, KeyFlags, SignatureConfigBuilder, SignatureType, Subpacket, SubpacketCriticality,
+    SubpacketData,
 };
-use crate::types::{KeyId, KeyTrait, SecretKeyTrait};
+use crate::types::{KeyId, KeyTrait, KeyVersion, PublicKeyTrait, SecretKeyTrait};
 
 /// User facing interface to work with a secret key.
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -22,6 +22,12 @@ pub struct SecretKey {
 pub struct SecretSubkey {
     key: packet::SecretSubkey,
     keyflags: KeyFlags,
+    /// The backsig made in advance, see [`SecretSubkey::with_backsig`].
+    backsig: Option<packet::Signature>,
+    /// The expiration set in the binding signature, see [`SecretSubkey::with_expiration`].
+    expiration: Option<Duration>,
+    /// See [`SecretSubkey::with_subpacket_criticality`].
+    subpacket_criticality: Option<SubpacketCriticality>,
 }
 
 impl SecretKey {
@@ -56,12 +62,12 @@ impl SecretKey {
             .map(|k| k.sign(&primary_key, key_pw.clone()))
             .collect::<Result<Vec<_>>>()?;
 
-        Ok(SignedSecretKey {
+        Ok(SignedSecretKey::new(
             primary_key,
             details,
             public_subkeys,
             secret_subkeys,
-        })
+        ))
     }
 }
 
@@ -77,30 +83,92 @@ impl KeyTrait for SecretKey {
     fn algorithm(&self) -> PublicKeyAlgorithm {
         self.primary_key.algorithm()
     }
+
+    fn key_version(&self) -> KeyVersion {
+        self.primary_key.version()
+    }
 }
 
 impl SecretSubkey {
     pub fn new(key: packet::SecretSubkey, keyflags: KeyFlags) -> Self {
-        SecretSubkey { key, keyflags }
+        SecretSubkey {
+            key,
+            keyflags,
+            backsig: None,
+            expiration: None,
+            subpacket_criticality: None,
+        }
+    }
+
+    /// Lets the subkey expire `expiration` after its creation, independent of the expiration
+    /// of the primary key.
+    pub fn with_expiration(mut self, expiration: Duration) -> Self {
+        self.expiration = Some(expiration);
+        self
+    }
+
+    /// Sets the critical bit of the standard subpackets of the binding signature and the
+    /// backsig, see [`SubpacketCriticality`].
+    pub fn with_subpacket_criticality(mut self, criticality: SubpacketCriticality) -> Self {
+        self.subpacket_criticality = Some(criticality);
+        self
+    }
+
+    /// Makes the primary key binding signature ("backsig") of a signing capable subkey now,
+    /// unlocking the subkey with `key_pw`.
+    ///
+    /// [`SecretSubkey::sign`] embeds this backsig, instead of making one with the password of
+    /// the primary key, so subkeys with their own password can be bound. Does nothing for
+    /// subkeys that can not sign.
+    pub fn with_backsig<F>(mut self, primary: &impl PublicKeyTrait, key_pw: F) -> Result<Self>
+    where
+        F: FnOnce() -> String,
+    {
+        if self.keyflags.sign() {
+            self.backsig = Some(backsig(
+                &self.key,
+                key_pw,
+                primary,
+                self.subpacket_criticality,
+            )?);
+        }
+
+        Ok(self)
     }
 
+    /// Creates the binding signature for this subkey.
+    ///
+    /// Signing capable subkeys also get a primary key binding signature ("backsig") embedded,
+    /// made by the subkey, which is unlocked with the same `key_pw`, unless the backsig was
+    /// made in advance with [`SecretSubkey::with_backsig`].
     pub fn sign<F>(self, sec_key: &impl SecretKeyTrait, key_pw: F) -> Result<SignedSecretSubKey>
     where
         F: (FnOnce() -> String) + Clone,
     {
         let key = self.key;
-        let hashed_subpackets = vec![
+        let mut hashed_subpackets = vec![
             Subpacket::regular(SubpacketData::SignatureCreationTime(
                 chrono::Utc::now().trunc_subsecs(0),
             )),
             Subpacket::regular(SubpacketData::KeyFlags(self.keyflags.into())),
-            Subpacket::regular(SubpacketData::IssuerFingerprint(
-                Default::default(),
-                SmallVec::from_slice(&sec_key.fingerprint()),
-            )),
+            Subpacket::regular(SubpacketData::issuer_fingerprint(&sec_key)),
         ];
+        if let Some(expiration) = self.expiration {
+            hashed_subpackets.push(Subpacket::regular(SubpacketData::KeyExpirationTime(
+                expiration,
+            )));
+        }
+        if self.keyflags.sign() {
+            let backsig = match self.backsig {
+                Some(backsig) => backsig,
+                None => backsig(&key, key_pw.clone(), sec_key, self.subpacket_criticality)?,
+            };
+            hashed_subpackets.push(Subpacket::regular(SubpacketData::EmbeddedSignature(
+                Box::new(backsig),
+            )));
+        }
 
-        let config = SignatureConfigBuilder::default()
+        let mut config = SignatureConfigBuilder::default()
             .typ(SignatureType::SubkeyBinding)
             .pub_alg(sec_key.algorithm())
             .hash_alg(sec_key.hash_alg())
@@ -109,12 +177,42 @@ impl SecretSubkey {
                 sec_key.key_id(),
             ))])
             .build()?;
+        config.subpacket_criticality = self.subpacket_criticality;
         let signatures = vec![config.sign_key_binding(sec_key, key_pw, &key)?];
 
-        Ok(SignedSecretSubKey { key, signatures })
+        Ok(SignedSecretSubKey::new(key, signatures))
     }
 }
 
+/// Makes the primary key binding signature of `key`, certifying that it belongs to `primary`.
+fn backsig<F>(
+    key: &packet::SecretSubkey,
+    key_pw: F,
+    primary: &impl PublicKeyTrait,
+    criticality: Option<SubpacketCriticality>,
+) -> Result<packet::Signature>
+where
+    F: FnOnce() -> String,
+{
+    let mut config = SignatureConfigBuilder::default()
+        .typ(SignatureType::KeyBinding)
+        .pub_alg(key.algorithm())
+        .hash_alg(key.hash_alg())
+        .hashed_subpackets(vec![
+            Subpacket::regular(SubpacketData::SignatureCreationTime(
+                chrono::Utc::now().trunc_subsecs(0),
+            )),
+            Subpacket::regular(SubpacketData::issuer_fingerprint(key)),
+        ])
+        .unhashed_subpackets(vec![Subpacket::regular(SubpacketData::Issuer(
+            key.key_id(),
+        ))])
+        .build()?;
+    config.subpacket_criticality = criticality;
+
+    config.sign_primary_key_binding(key, key_pw, primary)
+}
+
 impl KeyTrait for SecretSubkey {
     fn fingerprint(&self) -> Vec<u8> {
         self.key.fingerprint()
@@ -127,4 +225,12 @@ impl KeyTrait for SecretSubkey {
     fn algorithm(&self) -> PublicKeyAlgorithm {
         self.key.algorithm()
     }
+
+    fn key_version(&self) -> KeyVersion {
+        self.key.version()
+    }
+
+    fn key_flags(&self) -> Option<KeyFlags> {
+        Some(self.keyflags)
+    }
 }
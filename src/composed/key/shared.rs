@@ -6,8 +6,8 @@ use crate::crypto::hash::HashAlgorithm;
 use crate::crypto::sym::SymmetricKeyAlgorithm;
 use crate::errors::Result;
 use crate::packet::{
-    KeyFlags, PacketTrait, SignatureConfigBuilder, SignatureType, Subpacket, SubpacketData,
-    UserAttribute, UserId,
+    Features, KeyFlags, PacketTrait, SignatureConfigBuilder, SignatureType, Subpacket,
+    SubpacketCriticality, SubpacketData, UserAttribute, UserId,
 };
 use crate::types::{CompressionAlgorithm, RevocationKey, SecretKeyTrait};
 
@@ -21,6 +21,9 @@ pub struct KeyDetails {
     preferred_hash_algorithms: SmallVec<[HashAlgorithm; 8]>,
     preferred_compression_algorithms: SmallVec<[CompressionAlgorithm; 8]>,
     revocation_key: Option<RevocationKey>,
+    features: Option<Features>,
+    direct_key_signature: bool,
+    subpacket_criticality: Option<SubpacketCriticality>,
 }
 
 impl KeyDetails {
@@ -44,9 +47,42 @@ impl KeyDetails {
             preferred_hash_algorithms,
             preferred_compression_algorithms,
             revocation_key,
+            features: Some(Features::from(&[0x01][..])),
+            direct_key_signature: false,
+            subpacket_criticality: None,
         }
     }
 
+    /// Sets the features that are advertised on the self-signatures.
+    ///
+    /// Defaults to modification detection (SEIPDv1) only.
+    pub fn with_features(mut self, features: Features) -> Self {
+        self.features = Some(features);
+        self
+    }
+
+    /// Leaves the Features subpacket out of the self-signatures.
+    pub(crate) fn without_features(mut self) -> Self {
+        self.features = None;
+        self
+    }
+
+    /// Also publish the key flags, preferences and features on a direct key signature over
+    /// the primary key, so that they apply independently of any user id.
+    ///
+    /// The user id self-signatures still carry the preferences as well.
+    pub fn with_direct_key_signature(mut self, enabled: bool) -> Self {
+        self.direct_key_signature = enabled;
+        self
+    }
+
+    /// Sets the critical bit of the standard subpackets of the self-signatures, see
+    /// [`SubpacketCriticality`].
+    pub fn with_subpacket_criticality(mut self, criticality: SubpacketCriticality) -> Self {
+        self.subpacket_criticality = Some(criticality);
+        self
+    }
+
     pub fn sign<F>(self, key: &impl SecretKeyTrait, key_pw: F) -> Result<SignedKeyDetails>
     where
         F: (FnOnce() -> String) + Clone,
@@ -56,6 +92,44 @@ impl KeyDetails {
         let preferred_hash_algorithms = self.preferred_hash_algorithms;
         let preferred_compression_algorithms = self.preferred_compression_algorithms;
         let revocation_key = self.revocation_key;
+        let criticality = self.subpacket_criticality;
+        let features = self
+            .features
+            .map(|features| Subpacket::regular(SubpacketData::Features(features.into())));
+
+        let mut direct_signatures = vec![];
+        if self.direct_key_signature {
+            let mut hashed_subpackets = vec![
+                Subpacket::regular(SubpacketData::SignatureCreationTime(
+                    chrono::Utc::now().trunc_subsecs(0),
+                )),
+                Subpacket::regular(SubpacketData::KeyFlags(keyflags.clone())),
+                Subpacket::regular(SubpacketData::PreferredSymmetricAlgorithms(
+                    preferred_symmetric_algorithms.clone(),
+                )),
+                Subpacket::regular(SubpacketData::PreferredHashAlgorithms(
+                    preferred_hash_algorithms.clone(),
+                )),
+                Subpacket::regular(SubpacketData::PreferredCompressionAlgorithms(
+                    preferred_compression_algorithms.clone(),
+                )),
+                Subpacket::regular(SubpacketData::issuer_fingerprint(&key)),
+            ];
+            hashed_subpackets.extend(features.clone());
+
+            let mut config = SignatureConfigBuilder::default()
+                .typ(SignatureType::Key)
+                .pub_alg(key.algorithm())
+                .hash_alg(key.hash_alg())
+                .hashed_subpackets(hashed_subpackets)
+                .unhashed_subpackets(vec![Subpacket::regular(SubpacketData::Issuer(
+                    key.key_id(),
+                ))])
+                .build()?;
+            config.subpacket_criticality = criticality;
+
+            direct_signatures.push(config.sign_key(key, key_pw.clone(), key)?);
+        }
 
         let mut users = vec![];
 
@@ -77,17 +151,15 @@ impl KeyDetails {
                 Subpacket::regular(SubpacketData::PreferredCompressionAlgorithms(
                     preferred_compression_algorithms.clone(),
                 )),
-                Subpacket::regular(SubpacketData::IssuerFingerprint(
-                    Default::default(),
-                    SmallVec::from_slice(&key.fingerprint()),
-                )),
+                Subpacket::regular(SubpacketData::issuer_fingerprint(&key)),
             ];
+            hashed_subpackets.extend(features.clone());
             if let Some(rkey) = revocation_key {
                 hashed_subpackets.push(Subpacket::regular(SubpacketData::RevocationKey(rkey)));
             }
 
-            let config = SignatureConfigBuilder::default()
-                .typ(SignatureType::CertGeneric)
+            let mut config = SignatureConfigBuilder::default()
+                .typ(SignatureType::CertPositive)
                 .pub_alg(key.algorithm())
                 .hash_alg(key.hash_alg())
                 .hashed_subpackets(hashed_subpackets)
@@ -95,6 +167,7 @@ impl KeyDetails {
                     key.key_id(),
                 ))])
                 .build()?;
+            config.subpacket_criticality = criticality;
 
             let sig = config.sign_certification(key, key_pw.clone(), id.tag(), &id)?;
 
@@ -107,33 +180,34 @@ impl KeyDetails {
             self.user_ids
                 .into_iter()
                 .map(|id| {
-                    let config = SignatureConfigBuilder::default()
-                        .typ(SignatureType::CertGeneric)
+                    let mut hashed_subpackets = vec![
+                        Subpacket::regular(SubpacketData::SignatureCreationTime(
+                            chrono::Utc::now().trunc_subsecs(0),
+                        )),
+                        Subpacket::regular(SubpacketData::KeyFlags(keyflags.clone())),
+                        Subpacket::regular(SubpacketData::PreferredSymmetricAlgorithms(
+                            preferred_symmetric_algorithms.clone(),
+                        )),
+                        Subpacket::regular(SubpacketData::PreferredHashAlgorithms(
+                            preferred_hash_algorithms.clone(),
+                        )),
+                        Subpacket::regular(SubpacketData::PreferredCompressionAlgorithms(
+                            preferred_compression_algorithms.clone(),
+                        )),
+                        Subpacket::regular(SubpacketData::issuer_fingerprint(&key)),
+                    ];
+                    hashed_subpackets.extend(features.clone());
+
+                    let mut config = SignatureConfigBuilder::default()
+                        .typ(SignatureType::CertPositive)
                         .pub_alg(key.algorithm())
                         .hash_alg(key.hash_alg())
-                        .hashed_subpackets(vec![
-                            Subpacket::regular(SubpacketData::SignatureCreationTime(
-                                chrono::Utc::now().trunc_subsecs(0),
-                            )),
-                            Subpacket::regular(SubpacketData::KeyFlags(keyflags.clone())),
-                            Subpacket::regular(SubpacketData::PreferredSymmetricAlgorithms(
-                                preferred_symmetric_algorithms.clone(),
-                            )),
-                            Subpacket::regular(SubpacketData::PreferredHashAlgorithms(
-                                preferred_hash_algorithms.clone(),
-                            )),
-                            Subpacket::regular(SubpacketData::PreferredCompressionAlgorithms(
-                                preferred_compression_algorithms.clone(),
-                            )),
-                            Subpacket::regular(SubpacketData::IssuerFingerprint(
-                                Default::default(),
-                                SmallVec::from_slice(&key.fingerprint()),
-                            )),
-                        ])
+                        .hashed_subpackets(hashed_subpackets)
                         .unhashed_subpackets(vec![Subpacket::regular(SubpacketData::Issuer(
                             key.key_id(),
                         ))])
                         .build()?;
+                    config.subpacket_criticality = criticality;
 
                     let sig = config.sign_certification(key, key_pw.clone(), id.tag(), &id)?;
 
@@ -145,12 +219,12 @@ impl KeyDetails {
         let user_attributes = self
             .user_attributes
             .into_iter()
-            .map(|u| u.sign(key, key_pw.clone()))
+            .map(|u| u.sign_with_criticality(key, key_pw.clone(), criticality))
             .collect::<Result<Vec<_>>>()?;
 
         Ok(SignedKeyDetails {
             revocation_signatures: Default::default(),
-            direct_signatures: Default::default(),
+            direct_signatures,
             users,
             user_attributes,
         })
@@ -0,0 +1,1366 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use bstr::{BStr, BString, ByteVec};
+use chrono::{DateTime, Utc};
+#[cfg(feature = "compression-deflate")]
+use flate2::write::{DeflateEncoder, ZlibEncoder};
+#[cfg(feature = "compression-deflate")]
+use flate2::Compression;
+use rand::{CryptoRng, Rng, RngCore};
+
+use crate::composed::signature::default_signature_config;
+use crate::composed::{Edata, Esk, Message, SignedPublicKey};
+use crate::crypto::aead::AeadAlgorithm;
+use crate::crypto::hash::{HashAlgorithm, Hasher};
+use crate::crypto::sym::SymmetricKeyAlgorithm;
+use crate::errors::Result;
+use crate::normalize_lines::CrlfWriter;
+use crate::packet::{
+    write_packet, EncryptingWriter, LiteralData, OnePassSignature, PartialBodyWriter,
+    PublicKeyEncryptedSessionKey, Signature, SignatureConfig, SignatureType, Subpacket,
+    SubpacketCriticality, SubpacketData, SymEncryptedProtectedData, SymKeyEncryptedSessionKey,
+    MAX_KEY_BLOCK_SIZE,
+};
+use crate::ser::Serialize;
+use crate::types::{
+    CompressionAlgorithm, KeyTrait, PublicKeyTrait, SecretKeyTrait, StringToKey, Tag,
+};
+use crate::ArmorOptions;
+
+/// Size of the chunks streamed packets are written in.
+const PARTIAL_CHUNK_SIZE: usize = 8 * 1024;
+
+/// Object safe stand-in for `Rng + CryptoRng`, so recipients can be stored type erased.
+trait CryptoRngDyn: RngCore + CryptoRng {}
+
+impl<R: RngCore + CryptoRng> CryptoRngDyn for R {}
+
+type SignFn<'a> = Box<dyn FnOnce(SignatureConfig, Box<dyn Hasher>) -> Result<Signature> + 'a>;
+
+/// A key the message is signed with.
+struct Signer<'a> {
+    key: &'a dyn KeyTrait,
+    hash_algorithm: HashAlgorithm,
+    /// Creates the signature from the config and the hashed data.
+    sign: SignFn<'a>,
+    /// The serialized key of the sender, embedded in the signature.
+    key_block: Option<Vec<u8>>,
+    /// The critical bits of the standard subpackets of the signature.
+    subpacket_criticality: Option<SubpacketCriticality>,
+}
+
+impl Signer<'_> {
+    fn config(&self, typ: SignatureType) -> SignatureConfig {
+        let mut config = default_signature_config(typ, self.key, self.hash_algorithm);
+        if let Some(key_block) = &self.key_block {
+            config
+                .hashed_subpackets_mut()
+                .push(Subpacket::regular(SubpacketData::KeyBlock(
+                    key_block.clone(),
+                )));
+        }
+        config.subpacket_criticality = self.subpacket_criticality;
+
+        config
+    }
+
+    fn one_pass_signature(&self, typ: SignatureType) -> OnePassSignature {
+        OnePassSignature::from_details(
+            typ,
+            self.hash_algorithm,
+            self.key.algorithm(),
+            self.key.key_id(),
+        )
+    }
+
+    /// Signs the message, like [`Message::sign`].
+    ///
+    /// Literal data is signed with a signature of type `typ`, or the type matching the data
+    /// mode.
+    fn sign_message(self, message: Message, typ: Option<SignatureType>) -> Result<Message> {
+        let (typ, data) = match &message {
+            Message::Literal(l) => (
+                typ.unwrap_or_else(|| signature_type_for(l)),
+                Cow::Borrowed(l.data()),
+            ),
+            _ => (SignatureType::Binary, Cow::Owned(message.to_bytes()?)),
+        };
+
+        let config = self.config(typ);
+        let mut hasher = self.hash_algorithm.new_hasher()?;
+        if typ == SignatureType::Text {
+            CrlfWriter::new(&mut *hasher).write_all(&data)?;
+        } else {
+            hasher.update(&data);
+        }
+        let one_pass_signature = self.one_pass_signature(typ);
+        let signature = (self.sign)(config, hasher)?;
+
+        Ok(Message::Signed {
+            message: Some(Box::new(message)),
+            one_pass_signature: Some(one_pass_signature),
+            signature,
+        })
+    }
+}
+
+/// The signature type matching the data mode of the literal data.
+fn signature_type_for(literal: &LiteralData) -> SignatureType {
+    if literal.is_text() {
+        SignatureType::Text
+    } else {
+        SignatureType::Binary
+    }
+}
+
+/// Encrypts the session key for a recipient, to the packet matching the encryption container.
+type RecipientFn<'a> = Box<dyn Fn(&mut dyn CryptoRngDyn, &[u8], &Encryption) -> Result<Esk> + 'a>;
+
+/// The encryption container of a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encryption {
+    SeipdV1 {
+        sym_alg: SymmetricKeyAlgorithm,
+    },
+    SeipdV2 {
+        sym_alg: SymmetricKeyAlgorithm,
+        aead: AeadAlgorithm,
+        chunk_size: u8,
+    },
+}
+
+/// Builds a message from literal data, which can be signed, compressed and encrypted in a
+/// single chain.
+///
+/// The layers are applied in the order GnuPG uses by default: the literal data is signed
+/// first, then compressed, then encrypted. The resulting packets are nested like this:
+///
+/// ```text
+/// Public-Key Encrypted Session Key (one per recipient key)
+/// Symmetric-Key Encrypted Session Key (one per password)
+/// Symmetrically Encrypted Integrity Protected Data
+/// └── Compressed Data
+///     ├── One-Pass Signature (one per signer)
+///     ├── Literal Data
+///     └── Signature (one per signer)
+/// ```
+///
+/// Layers that are not requested are left out: without signers there are no signature
+/// packets, without recipient keys or passwords the compressed data is not encrypted.
+///
+/// ```
+/// # #[cfg(feature = "x25519")]
+/// # fn main() -> pgp::errors::Result<()> {
+/// # use pgp::composed::{Deserializable, Message, MessageBuilder, SignedSecretKey};
+/// # use pgp::crypto::{aead::AeadAlgorithm, hash::HashAlgorithm, sym::SymmetricKeyAlgorithm};
+/// # use pgp::types::SecretKeyTrait;
+/// # let (alice, _) = SignedSecretKey::from_armor_single(
+/// #     std::fs::File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc")?,
+/// # )?;
+/// # let alice_public = alice.public_key();
+/// # let encryption_key = alice.secret_subkeys[0].public_key();
+/// let bytes = MessageBuilder::from_bytes("hello.txt", b"hello world")
+///     .sign(&alice, String::new, HashAlgorithm::SHA2_256)
+///     .seipd_v2(SymmetricKeyAlgorithm::AES128, AeadAlgorithm::Ocb, 8)
+///     .encrypt_to_key(&encryption_key)
+///     .to_bytes(&mut rand::thread_rng())?;
+///
+/// let message = Message::from_bytes(&bytes[..])?;
+/// let decrypted = message.decrypt_and_verify(String::new, &[&alice], &alice_public)?;
+/// assert_eq!(decrypted.get_content()?.unwrap(), b"hello world");
+/// # Ok(())
+/// # }
+/// # #[cfg(not(feature = "x25519"))]
+/// # fn main() {}
+/// ```
+pub struct MessageBuilder<'a> {
+    literal: LiteralData,
+    /// Streams the data of the literal packet, instead of the data stored in `literal`.
+    reader: Option<Box<dyn Read + 'a>>,
+    signers: Vec<Signer<'a>>,
+    signature_type: Option<SignatureType>,
+    /// The sender's key to embed in the signatures, and if it is minimized first.
+    sender_key: Option<(&'a SignedPublicKey, bool)>,
+    subpacket_criticality: Option<SubpacketCriticality>,
+    compression: Option<CompressionAlgorithm>,
+    encryption: Encryption,
+    recipients: Vec<RecipientFn<'a>>,
+}
+
+impl<'a> MessageBuilder<'a> {
+    /// Creates a builder for a message holding the given binary data.
+    pub fn from_bytes(file_name: impl AsRef<BStr>, data: &[u8]) -> Self {
+        Self::from_literal(LiteralData::from_bytes(file_name.as_ref(), data))
+    }
+
+    /// Creates a builder for a message holding the given text.
+    pub fn from_text(file_name: impl AsRef<BStr>, data: &str) -> Self {
+        Self::from_literal(LiteralData::from_str(file_name.as_ref(), data))
+    }
+
+    /// Creates a builder for a message holding the binary data read from `reader`.
+    ///
+    /// The data is only read when building the message. [`MessageBuilder::to_writer`] streams
+    /// it, without holding it in memory.
+    pub fn from_reader(file_name: impl AsRef<BStr>, reader: impl Read + 'a) -> Self {
+        let mut builder = Self::from_bytes(file_name, &[]);
+        builder.reader = Some(Box::new(reader));
+        builder
+    }
+
+    /// Creates a builder for a message holding the contents of the file at `path`.
+    ///
+    /// The file name and the modification time of the literal data are taken from the file,
+    /// and can be overridden with [`MessageBuilder::file_name`] and
+    /// [`MessageBuilder::modification_time`].
+    ///
+    /// The file is opened right away, so missing or unreadable files are reported here.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        let metadata = file.metadata()?;
+        ensure!(
+            metadata.is_file(),
+            "{} is not a regular file",
+            path.display()
+        );
+
+        let file_name = path
+            .file_name()
+            .map(|name| Vec::from_os_str_lossy(name).into_owned())
+            .unwrap_or_default();
+        let mut builder = Self::from_reader(BString::from(file_name), file);
+        if let Ok(modified) = metadata.modified() {
+            builder = builder.modification_time(modified.into());
+        }
+
+        Ok(builder)
+    }
+
+    fn from_literal(literal: LiteralData) -> Self {
+        MessageBuilder {
+            literal,
+            reader: None,
+            signers: Vec::new(),
+            signature_type: None,
+            sender_key: None,
+            subpacket_criticality: None,
+            compression: if cfg!(feature = "compression-deflate") {
+                Some(CompressionAlgorithm::ZLIB)
+            } else {
+                None
+            },
+            encryption: Encryption::SeipdV1 {
+                sym_alg: SymmetricKeyAlgorithm::AES128,
+            },
+            recipients: Vec::new(),
+        }
+    }
+
+    /// Sets the file name stored in the literal data.
+    pub fn file_name(mut self, file_name: impl AsRef<BStr>) -> Self {
+        self.literal.set_file_name(file_name.as_ref().to_owned());
+        self
+    }
+
+    /// Sets the modification time stored in the literal data.
+    ///
+    /// Defaults to the time the builder was created, or the modification time of the file
+    /// for [`MessageBuilder::from_path`].
+    pub fn modification_time(mut self, time: DateTime<Utc>) -> Self {
+        self.literal.set_created(time);
+        self
+    }
+
+    /// Signs the literal data with the given key.
+    ///
+    /// Can be called multiple times, the signatures are nested in the order of the calls.
+    pub fn sign<K, F>(mut self, key: &'a K, key_pw: F, hash_algorithm: HashAlgorithm) -> Self
+    where
+        K: SecretKeyTrait,
+        F: FnOnce() -> String + 'a,
+    {
+        self.signers.push(Signer {
+            key,
+            hash_algorithm,
+            sign: Box::new(move |config: SignatureConfig, hasher: Box<dyn Hasher>| {
+                config.sign_hasher(key, key_pw, hasher)
+            }),
+            key_block: None,
+            subpacket_criticality: self.subpacket_criticality,
+        });
+        self
+    }
+
+    /// Embeds the sender's key in a `Key Block` subpacket of the signatures, so recipients
+    /// can encrypt a reply without looking up the key.
+    ///
+    /// The key is embedded in the signatures made by its primary key or one of its subkeys,
+    /// reduced to [`SignedPublicKey::minimal`] first if `minimal` is set. Building the message
+    /// fails if none of the signers belongs to the key, or if the serialized key is larger
+    /// than [`MAX_KEY_BLOCK_SIZE`].
+    pub fn embed_sender_key(mut self, key: &'a SignedPublicKey, minimal: bool) -> Self {
+        self.sender_key = Some((key, minimal));
+        self
+    }
+
+    /// Sets the critical bit of the standard subpackets of the signatures, see
+    /// [`SubpacketCriticality`].
+    ///
+    /// Applies to all signers. By default the subpackets are not critical.
+    pub fn subpacket_criticality(mut self, criticality: SubpacketCriticality) -> Self {
+        self.subpacket_criticality = Some(criticality);
+        for signer in &mut self.signers {
+            signer.subpacket_criticality = Some(criticality);
+        }
+        self
+    }
+
+    /// Sets the type of the signatures over the literal data, [`SignatureType::Binary`] or
+    /// [`SignatureType::Text`].
+    ///
+    /// Defaults to the type matching the data: text signatures for literal data in text
+    /// mode, binary signatures otherwise. Text signatures are made over the data with
+    /// normalized line endings.
+    pub fn signature_type(mut self, typ: SignatureType) -> Self {
+        self.signature_type = Some(typ);
+        self
+    }
+
+    /// Sets the compression applied to the (signed) literal data, `None` disables compression.
+    ///
+    /// Defaults to ZLIB, or no compression without the `compression-deflate` feature.
+    pub fn compression(mut self, alg: Option<CompressionAlgorithm>) -> Self {
+        self.compression = alg;
+        self
+    }
+
+    /// Encrypts into a SEIPDv1 packet, using PKESK v3 and SKESK v4 packets for the
+    /// recipients.
+    ///
+    /// This is the default, with AES128.
+    pub fn seipd_v1(mut self, sym_alg: SymmetricKeyAlgorithm) -> Self {
+        self.encryption = Encryption::SeipdV1 { sym_alg };
+        self
+    }
+
+    /// Encrypts into a SEIPDv2 packet, using PKESK v6 and SKESK v6 packets for the
+    /// recipients.
+    ///
+    /// The data is encrypted in chunks of `1 << (chunk_size + 6)` octets.
+    pub fn seipd_v2(
+        mut self,
+        sym_alg: SymmetricKeyAlgorithm,
+        aead: AeadAlgorithm,
+        chunk_size: u8,
+    ) -> Self {
+        self.encryption = Encryption::SeipdV2 {
+            sym_alg,
+            aead,
+            chunk_size,
+        };
+        self
+    }
+
+    /// Encrypts the message to the given key.
+    ///
+    /// Can be called multiple times, to encrypt to multiple recipients.
+    /// Building the message fails if the key is bound for authentication only.
+    pub fn encrypt_to_key<K>(mut self, key: &'a K) -> Self
+    where
+        K: PublicKeyTrait,
+    {
+        self.recipients.push(Box::new(
+            move |mut rng: &mut dyn CryptoRngDyn, session_key: &[u8], encryption: &Encryption| {
+                if let Some(flags) = key.key_flags() {
+                    ensure!(
+                        !flags.authentication() || flags.encrypt_comms() || flags.encrypt_storage(),
+                        "key {:X} is an authentication-only key and can not be used for encryption",
+                        key.key_id()
+                    );
+                }
+
+                let pkesk = match encryption {
+                    Encryption::SeipdV1 { sym_alg } => {
+                        PublicKeyEncryptedSessionKey::from_session_key(
+                            &mut rng,
+                            session_key,
+                            *sym_alg,
+                            key,
+                        )?
+                    }
+                    Encryption::SeipdV2 { .. } => {
+                        PublicKeyEncryptedSessionKey::from_session_key_v6(
+                            &mut rng,
+                            session_key,
+                            key,
+                        )?
+                    }
+                };
+
+                Ok(Esk::PublicKeyEncryptedSessionKey(pkesk))
+            },
+        ));
+        self
+    }
+
+    /// Encrypts the message with a password, the session key is derived from it with `s2k`.
+    ///
+    /// Can be called multiple times and combined with [`MessageBuilder::encrypt_to_key`],
+    /// the message can then be decrypted with any of the passwords or keys.
+    pub fn encrypt_with_password<F>(mut self, s2k: StringToKey, msg_pw: F) -> Self
+    where
+        F: FnOnce() -> String + Clone + 'a,
+    {
+        self.recipients.push(Box::new(
+            move |mut rng: &mut dyn CryptoRngDyn, session_key: &[u8], encryption: &Encryption| {
+                let skesk = match encryption {
+                    Encryption::SeipdV1 { sym_alg } => SymKeyEncryptedSessionKey::encrypt(
+                        msg_pw.clone(),
+                        session_key,
+                        s2k.clone(),
+                        *sym_alg,
+                    )?,
+                    Encryption::SeipdV2 { sym_alg, aead, .. } => {
+                        SymKeyEncryptedSessionKey::encrypt_v6(
+                            &mut rng,
+                            msg_pw.clone(),
+                            session_key,
+                            s2k.clone(),
+                            *sym_alg,
+                            *aead,
+                        )?
+                    }
+                };
+
+                Ok(Esk::SymKeyEncryptedSessionKey(skesk))
+            },
+        ));
+        self
+    }
+
+    /// Builds the message.
+    ///
+    /// Data from a reader is read into memory, use [`MessageBuilder::to_writer`] to stream it.
+    pub fn to_message<R: Rng + CryptoRng>(mut self, mut rng: R) -> Result<Message> {
+        check_signature_type(self.signature_type)?;
+        embed_key_block(&mut self.signers, self.sender_key)?;
+        if let Some(mut reader) = self.reader.take() {
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data)?;
+            self.literal.set_data(data);
+        }
+        let mut message = Message::Literal(self.literal);
+
+        for signer in self.signers {
+            message = signer.sign_message(message, self.signature_type)?;
+        }
+
+        if let Some(alg) = self.compression {
+            message = message.compress(alg)?;
+        }
+
+        if self.recipients.is_empty() {
+            return Ok(message);
+        }
+
+        let data = message.to_bytes()?;
+        let (session_key, edata) = match self.encryption {
+            Encryption::SeipdV1 { sym_alg } => {
+                let session_key = sym_alg.new_session_key(&mut rng);
+                let edata = SymEncryptedProtectedData::encrypt_with_rng(
+                    &mut rng,
+                    sym_alg,
+                    &session_key,
+                    &data,
+                )?;
+                (session_key, edata)
+            }
+            Encryption::SeipdV2 {
+                sym_alg,
+                aead,
+                chunk_size,
+            } => {
+                let session_key = sym_alg.new_session_key(&mut rng);
+                let edata = SymEncryptedProtectedData::encrypt_seipdv2(
+                    &mut rng,
+                    sym_alg,
+                    aead,
+                    chunk_size,
+                    &session_key,
+                    &data,
+                )?;
+                (session_key, edata)
+            }
+        };
+
+        let esk = self
+            .recipients
+            .iter()
+            .map(|recipient| recipient(&mut rng, &session_key, &self.encryption))
+            .collect::<Result<_>>()?;
+
+        Ok(Message::Encrypted {
+            esk,
+            edata: Edata::SymEncryptedProtectedData(edata),
+        })
+    }
+
+    /// Builds the message and serializes it.
+    pub fn to_bytes<R: Rng + CryptoRng>(self, rng: R) -> Result<Vec<u8>> {
+        self.to_message(rng)?.to_bytes()
+    }
+
+    /// Builds the message and serializes it.
+    #[deprecated(note = "use MessageBuilder::to_bytes instead")]
+    pub fn to_vec<R: Rng + CryptoRng>(self, rng: R) -> Result<Vec<u8>> {
+        self.to_bytes(rng)
+    }
+
+    /// Builds the message and writes it to `writer` in ASCII armor.
+    pub fn to_armored_writer<R: Rng + CryptoRng>(
+        self,
+        rng: R,
+        writer: &mut impl io::Write,
+        opts: ArmorOptions<'_>,
+    ) -> Result<()> {
+        self.to_message(rng)?.to_armored_writer(writer, opts)
+    }
+
+    /// Builds the message and serializes it in ASCII armor.
+    pub fn to_armored_bytes<R: Rng + CryptoRng>(
+        self,
+        rng: R,
+        opts: ArmorOptions<'_>,
+    ) -> Result<Vec<u8>> {
+        self.to_message(rng)?.to_armored_bytes(opts)
+    }
+
+    /// Builds the message and serializes it in ASCII armor.
+    pub fn to_armored_string<R: Rng + CryptoRng>(
+        self,
+        rng: R,
+        opts: ArmorOptions<'_>,
+    ) -> Result<String> {
+        self.to_message(rng)?.to_armored_string(opts)
+    }
+
+    /// Builds the message and writes it to `writer`, streaming the literal data through all
+    /// layers.
+    ///
+    /// Only a bounded amount of the data is held in memory at any time. The streamed packets
+    /// use partial body lengths, so the output differs from [`MessageBuilder::to_bytes`].
+    /// With multiple signers, all signatures are made over the literal data, as nested
+    /// one-pass signatures are described in RFC 4880.
+    ///
+    /// Errors in the configuration and the recipient keys are reported before anything is
+    /// written.
+    pub fn to_writer<R: Rng + CryptoRng, W: io::Write>(
+        self,
+        mut rng: R,
+        mut writer: W,
+    ) -> Result<()> {
+        let MessageBuilder {
+            literal,
+            reader,
+            mut signers,
+            signature_type,
+            sender_key,
+            subpacket_criticality: _,
+            compression,
+            encryption,
+            recipients,
+        } = self;
+
+        check_signature_type(signature_type)?;
+        embed_key_block(&mut signers, sender_key)?;
+        if let Some(alg) = compression {
+            check_compression(alg)?;
+        }
+        let write_content = |writer: &mut dyn io::Write| {
+            write_compressed(writer, compression, |writer| {
+                let typ = signature_type.unwrap_or_else(|| signature_type_for(&literal));
+                write_signed(writer, literal, reader, signers, typ)
+            })
+        };
+
+        if recipients.is_empty() {
+            write_content(&mut writer)?;
+            writer.flush()?;
+            return Ok(());
+        }
+
+        let session_key = match encryption {
+            Encryption::SeipdV1 { sym_alg } => sym_alg.new_session_key(&mut rng),
+            Encryption::SeipdV2 {
+                sym_alg,
+                chunk_size,
+                ..
+            } => {
+                ensure!(chunk_size <= 16, "chunk size {} is too large", chunk_size);
+                sym_alg.new_session_key(&mut rng)
+            }
+        };
+
+        let esk = recipients
+            .iter()
+            .map(|recipient| recipient(&mut rng, &session_key, &encryption))
+            .collect::<Result<Vec<_>>>()?;
+        for esk in &esk {
+            esk.to_writer(&mut writer)?;
+        }
+
+        let mut body = PartialBodyWriter::new(
+            &mut writer,
+            Tag::SymEncryptedProtectedData,
+            PARTIAL_CHUNK_SIZE,
+        )?;
+        let mut encryptor = match encryption {
+            Encryption::SeipdV1 { sym_alg } => {
+                EncryptingWriter::new_v1(&mut rng, &mut body, sym_alg, &session_key)?
+            }
+            Encryption::SeipdV2 {
+                sym_alg,
+                aead,
+                chunk_size,
+            } => EncryptingWriter::new_v2(
+                &mut rng,
+                &mut body,
+                sym_alg,
+                aead,
+                chunk_size,
+                &session_key,
+            )?,
+        };
+        write_content(&mut encryptor)?;
+        encryptor.finish()?;
+        body.finish()?;
+
+        Ok(())
+    }
+}
+
+/// Fails for signature types that can not be used to sign literal data.
+fn check_signature_type(typ: Option<SignatureType>) -> Result<()> {
+    match typ {
+        None | Some(SignatureType::Binary | SignatureType::Text) => Ok(()),
+        Some(typ) => bail!("{:?} signatures can not be used to sign messages", typ),
+    }
+}
+
+/// Stores the serialized sender key in the signers that belong to it.
+fn embed_key_block(
+    signers: &mut [Signer<'_>],
+    sender_key: Option<(&SignedPublicKey, bool)>,
+) -> Result<()> {
+    let Some((key, minimal)) = sender_key else {
+        return Ok(());
+    };
+    let key_block = if minimal {
+        key.minimal().to_bytes()?
+    } else {
+        key.to_bytes()?
+    };
+    ensure!(
+        key_block.len() <= MAX_KEY_BLOCK_SIZE,
+        "sender key of {} bytes is too large to embed",
+        key_block.len()
+    );
+
+    let fingerprints: Vec<_> = std::iter::once(key.primary_key.fingerprint())
+        .chain(key.public_subkeys.iter().map(|k| k.key.fingerprint()))
+        .collect();
+    let mut embedded = false;
+    for signer in signers
+        .iter_mut()
+        .filter(|signer| fingerprints.contains(&signer.key.fingerprint()))
+    {
+        signer.key_block = Some(key_block.clone());
+        embedded = true;
+    }
+    ensure!(
+        embedded,
+        "the sender key does not belong to any of the signers"
+    );
+
+    Ok(())
+}
+
+/// Fails for compression algorithms that can not be used to build messages.
+fn check_compression(alg: CompressionAlgorithm) -> Result<()> {
+    match alg {
+        CompressionAlgorithm::Uncompressed => Ok(()),
+        CompressionAlgorithm::ZIP | CompressionAlgorithm::ZLIB => {
+            if cfg!(feature = "compression-deflate") {
+                Ok(())
+            } else {
+                unsupported_err!(
+                    "CompressionAlgorithm {} (feature \"compression-deflate\" is disabled)",
+                    u8::from(alg)
+                )
+            }
+        }
+        CompressionAlgorithm::BZip2 => unimplemented_err!("BZip2"),
+        CompressionAlgorithm::Private10 | CompressionAlgorithm::Other(_) => {
+            unsupported_err!("CompressionAlgorithm {} is unsupported", u8::from(alg))
+        }
+    }
+}
+
+/// Writes a compressed data packet around the packets written by `write_inner`, if
+/// compression is requested.
+fn write_compressed(
+    writer: &mut dyn io::Write,
+    compression: Option<CompressionAlgorithm>,
+    write_inner: impl FnOnce(&mut dyn io::Write) -> Result<()>,
+) -> Result<()> {
+    let Some(alg) = compression else {
+        return write_inner(writer);
+    };
+
+    let mut body = PartialBodyWriter::new(writer, Tag::CompressedData, PARTIAL_CHUNK_SIZE)?;
+    body.write_all(&[u8::from(alg)])?;
+    match alg {
+        CompressionAlgorithm::Uncompressed => write_inner(&mut body)?,
+        #[cfg(feature = "compression-deflate")]
+        CompressionAlgorithm::ZIP => {
+            let mut enc = DeflateEncoder::new(&mut body, Compression::default());
+            write_inner(&mut enc)?;
+            enc.finish()?;
+        }
+        #[cfg(feature = "compression-deflate")]
+        CompressionAlgorithm::ZLIB => {
+            let mut enc = ZlibEncoder::new(&mut body, Compression::default());
+            write_inner(&mut enc)?;
+            enc.finish()?;
+        }
+        _ => check_compression(alg)?,
+    }
+    body.finish()?;
+
+    Ok(())
+}
+
+/// Writes the one-pass signatures, the literal data and the signatures of type `typ`.
+///
+/// The data is hashed for all signers while it is written.
+fn write_signed(
+    mut writer: &mut dyn io::Write,
+    literal: LiteralData,
+    reader: Option<Box<dyn Read + '_>>,
+    signers: Vec<Signer<'_>>,
+    typ: SignatureType,
+) -> Result<()> {
+    let mut hashers = signers
+        .iter()
+        .map(|signer| signer.hash_algorithm.new_hasher())
+        .collect::<Result<Vec<_>>>()?;
+    // text signatures are made over normalized line endings, the data is written as is
+    let mut normalizer = (typ == SignatureType::Text).then(|| CrlfWriter::new(Vec::new()));
+    let mut hash = |data: &[u8]| -> Result<()> {
+        let data = match normalizer.as_mut() {
+            Some(normalizer) => {
+                normalizer.get_mut().clear();
+                normalizer.write_all(data)?;
+                &normalizer.get_ref()[..]
+            }
+            None => data,
+        };
+        for hasher in &mut hashers {
+            hasher.update(data);
+        }
+        Ok(())
+    };
+
+    // The first signer is the innermost one, the others announce a signature over the
+    // same data.
+    for (i, signer) in signers.iter().enumerate().rev() {
+        let mut ops = signer.one_pass_signature(typ);
+        if i > 0 {
+            ops.last = 0;
+        }
+        write_packet(&mut writer, &ops)?;
+    }
+
+    match reader {
+        None => {
+            hash(literal.data())?;
+            write_packet(&mut writer, &literal)?;
+        }
+        Some(mut reader) => {
+            let mut body =
+                PartialBodyWriter::new(&mut writer, Tag::LiteralData, PARTIAL_CHUNK_SIZE)?;
+            // only the header, the data comes from the reader
+            literal.to_writer(&mut body)?;
+
+            let mut buffer = vec![0u8; PARTIAL_CHUNK_SIZE];
+            loop {
+                let len = match reader.read(&mut buffer) {
+                    Ok(0) => break,
+                    Ok(len) => len,
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(err) => return Err(err.into()),
+                };
+                hash(&buffer[..len])?;
+                body.write_all(&buffer[..len])?;
+            }
+            body.finish()?;
+        }
+    }
+
+    for (signer, hasher) in signers.into_iter().zip(hashers) {
+        let config = signer.config(typ);
+        let signature = (signer.sign)(config, hasher)?;
+        write_packet(&mut writer, &signature)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use std::cell::Cell;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::rc::Rc;
+
+    #[cfg(feature = "x25519")]
+    use chrono::SubsecRound;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::composed::{Deserializable, SignedSecretKey};
+    use crate::types::SecretKeyTrait;
+    #[cfg(all(feature = "x25519", feature = "compression-deflate"))]
+    use crate::types::Tag;
+
+    fn load_alice() -> SignedSecretKey {
+        let (key, _headers) = SignedSecretKey::from_armor_single(
+            fs::File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+        )
+        .unwrap();
+        key
+    }
+
+    #[test]
+    #[cfg(all(feature = "x25519", feature = "compression-deflate"))]
+    fn test_sign_compress_encrypt() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let alice = load_alice();
+        let alice_public = alice.public_key();
+        let subkey = alice.secret_subkeys[0].public_key();
+
+        for (seipd_v2, pkesk_version, seipd_version) in [(false, 3, 1), (true, 6, 2)] {
+            let mut builder = MessageBuilder::from_bytes("hello.txt", b"hello world").sign(
+                &alice,
+                String::new,
+                HashAlgorithm::SHA2_256,
+            );
+            if seipd_v2 {
+                builder = builder.seipd_v2(SymmetricKeyAlgorithm::AES256, AeadAlgorithm::Ocb, 0);
+            } else {
+                builder = builder.seipd_v1(SymmetricKeyAlgorithm::AES256);
+            }
+            let bytes = builder.encrypt_to_key(&subkey).to_bytes(&mut rng).unwrap();
+
+            // PKESK, SEIPD
+            let message = Message::from_bytes(&bytes[..]).unwrap();
+            let Message::Encrypted { esk, edata } = &message else {
+                panic!("expected an encrypted message: {message:?}");
+            };
+            assert_eq!(esk.len(), 1);
+            let Esk::PublicKeyEncryptedSessionKey(pkesk) = &esk[0] else {
+                panic!("expected a PKESK");
+            };
+            assert_eq!(pkesk.version(), pkesk_version);
+            let Edata::SymEncryptedProtectedData(seipd) = edata else {
+                panic!("expected a SEIPD");
+            };
+            assert_eq!(seipd.version(), seipd_version);
+
+            // └── Compressed Data
+            let (decrypted, _) = message.decrypt(String::new, &[&alice]).unwrap();
+            assert!(matches!(decrypted, Message::Compressed(_)));
+
+            //     ├── One-Pass Signature, Literal Data, Signature
+            let inner = decrypted.clone().decompress().unwrap();
+            assert!(inner.is_one_pass_signed());
+            let packets: Vec<_> = crate::packet::PacketParser::new(&inner.to_bytes().unwrap()[..])
+                .map(|p| p.unwrap().tag())
+                .collect();
+            assert_eq!(
+                packets,
+                [Tag::OnePassSignature, Tag::LiteralData, Tag::Signature]
+            );
+
+            let verified = message
+                .decrypt_and_verify(String::new, &[&alice], &alice_public)
+                .unwrap();
+            assert_eq!(verified.get_content().unwrap().unwrap(), b"hello world");
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "x25519")]
+    fn test_encrypt_with_password() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let alice = load_alice();
+        let subkey = alice.secret_subkeys[0].public_key();
+
+        for (seipd_v2, skesk_version) in [(false, 4), (true, 6)] {
+            for streamed in [false, true] {
+                let mut builder = MessageBuilder::from_bytes("hello.txt", b"hello world")
+                    .encrypt_to_key(&subkey)
+                    .encrypt_with_password(StringToKey::new_default(&mut rng), || "secret".into());
+                if seipd_v2 {
+                    builder =
+                        builder.seipd_v2(SymmetricKeyAlgorithm::AES128, AeadAlgorithm::Ocb, 6);
+                }
+                let bytes = if streamed {
+                    let mut bytes = Vec::new();
+                    builder.to_writer(&mut rng, &mut bytes).unwrap();
+                    bytes
+                } else {
+                    builder.to_bytes(&mut rng).unwrap()
+                };
+
+                let message = Message::from_bytes(&bytes[..]).unwrap();
+                let Message::Encrypted { esk, .. } = &message else {
+                    panic!("expected an encrypted message: {message:?}");
+                };
+                let [Esk::PublicKeyEncryptedSessionKey(_), Esk::SymKeyEncryptedSessionKey(skesk)] =
+                    &esk[..]
+                else {
+                    panic!("expected a PKESK and a SKESK: {esk:?}");
+                };
+                assert_eq!(skesk.version(), skesk_version);
+
+                // either the password or the key decrypts the message
+                let decrypted = message.decrypt_with_password(|| "secret".into()).unwrap();
+                assert_eq!(
+                    decrypted.decompress().unwrap().get_content().unwrap(),
+                    Some(b"hello world".to_vec())
+                );
+                let (decrypted, _) = message.decrypt(String::new, &[&alice]).unwrap();
+                assert_eq!(
+                    decrypted.decompress().unwrap().get_content().unwrap(),
+                    Some(b"hello world".to_vec())
+                );
+                assert!(message.decrypt_with_password(|| "wrong".into()).is_err());
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "compression-deflate")]
+    fn test_optional_layers() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let alice = load_alice();
+
+        // nothing requested, only the literal data is compressed
+        let message = MessageBuilder::from_text("hello.txt", "hello world\n")
+            .to_message(&mut rng)
+            .unwrap();
+        assert!(matches!(message, Message::Compressed(_)));
+
+        let message = MessageBuilder::from_text("hello.txt", "hello world\n")
+            .compression(None)
+            .to_message(&mut rng)
+            .unwrap();
+        assert!(message.is_literal());
+
+        // signed, but not encrypted
+        let message = MessageBuilder::from_text("hello.txt", "hello world\n")
+            .sign(&alice, String::new, HashAlgorithm::SHA2_256)
+            .compression(None)
+            .to_message(&mut rng)
+            .unwrap();
+        assert!(message.is_one_pass_signed());
+        message.verify(&alice.public_key()).unwrap();
+
+        // decrypting a message that is not signed does not verify
+        let subkey = &alice.secret_subkeys[0].public_key();
+        let bytes = MessageBuilder::from_text("hello.txt", "hello world\n")
+            .encrypt_to_key(subkey)
+            .to_bytes(&mut rng)
+            .unwrap();
+        let message = Message::from_bytes(&bytes[..]).unwrap();
+        assert!(message
+            .decrypt_and_verify(String::new, &[&alice], &alice.public_key())
+            .is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "x25519")]
+    fn test_empty_literal_roundtrip() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let alice = load_alice();
+        let subkey = alice.secret_subkeys[0].public_key();
+
+        // only the header fields, with a fixed length
+        let bytes = MessageBuilder::from_bytes("", b"")
+            .compression(None)
+            .to_bytes(&mut rng)
+            .unwrap();
+        assert_eq!(&bytes[..4], &[0xCB, 0x06, b'b', 0x00]);
+        assert_eq!(bytes.len(), 8);
+
+        for from_reader in [false, true] {
+            for (sign, encrypt) in [(false, false), (true, false), (false, true), (true, true)] {
+                let mut builder = if from_reader {
+                    MessageBuilder::from_reader("", &b""[..])
+                } else {
+                    MessageBuilder::from_bytes("", b"")
+                };
+                if sign {
+                    builder = builder.sign(&alice, String::new, HashAlgorithm::SHA2_256);
+                }
+                if encrypt {
+                    builder = builder.encrypt_to_key(&subkey);
+                }
+                let bytes = builder.to_bytes(&mut rng).unwrap();
+
+                let mut message = Message::from_bytes(&bytes[..]).unwrap();
+                if encrypt {
+                    message = message.decrypt(String::new, &[&alice]).unwrap().0;
+                }
+                let message = message.decompress().unwrap();
+                if sign {
+                    message.verify(&alice.public_key()).unwrap();
+                }
+                assert_eq!(
+                    message.get_content().unwrap(),
+                    Some(Vec::new()),
+                    "{from_reader} {sign} {encrypt}"
+                );
+            }
+        }
+
+        let encrypted = crate::encrypt_with_password(
+            &mut rng,
+            b"",
+            "secret",
+            SymmetricKeyAlgorithm::AES128,
+            Some(AeadAlgorithm::Ocb),
+        )
+        .unwrap();
+        assert!(crate::decrypt_with_password(&encrypted, "secret")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_serialization_parity() {
+        let build = || {
+            let mut builder = MessageBuilder::from_text("hello.txt", "hello world\n");
+            // a fixed creation time, so all builders produce the same literal data
+            builder.literal.set_created(
+                chrono::TimeZone::timestamp_opt(&chrono::Utc, 1_700_000_000, 0).unwrap(),
+            );
+            builder
+        };
+        let rng = ChaCha8Rng::seed_from_u64(0);
+        let message = build().to_message(rng.clone()).unwrap();
+
+        let bytes = build().to_bytes(rng.clone()).unwrap();
+        assert_eq!(bytes, message.to_bytes().unwrap());
+        #[allow(deprecated)]
+        let legacy = build().to_vec(rng.clone()).unwrap();
+        assert_eq!(legacy, bytes);
+
+        let opts = ArmorOptions::default;
+        let armored = build().to_armored_bytes(rng.clone(), opts()).unwrap();
+        assert_eq!(armored, message.to_armored_bytes(opts()).unwrap());
+        let mut written = Vec::new();
+        build()
+            .to_armored_writer(rng.clone(), &mut written, opts())
+            .unwrap();
+        assert_eq!(written, armored);
+        assert_eq!(
+            build().to_armored_string(rng, opts()).unwrap().as_bytes(),
+            armored
+        );
+
+        // keys offer the same surface
+        let alice = load_alice();
+        let mut written = Vec::new();
+        alice.to_armored_writer(&mut written, opts()).unwrap();
+        assert_eq!(written, alice.to_armored_bytes(opts()).unwrap());
+        assert_eq!(alice.to_armored_string(opts()).unwrap().as_bytes(), written);
+        let (parsed, _) = SignedSecretKey::from_armor_single(&written[..]).unwrap();
+        assert_eq!(parsed.to_bytes().unwrap(), alice.to_bytes().unwrap());
+    }
+
+    #[test]
+    fn test_signature_type() {
+        use crate::packet::DataMode;
+
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let alice = load_alice();
+        let alice_public = alice.public_key();
+        let data = "hello\nworld\r\n";
+
+        for text in [false, true] {
+            let builder = || {
+                if text {
+                    MessageBuilder::from_text("hello.txt", data)
+                } else {
+                    MessageBuilder::from_bytes("hello.txt", data.as_bytes())
+                }
+            };
+            let mode = if text {
+                DataMode::Utf8
+            } else {
+                DataMode::Binary
+            };
+
+            // defaults to the type matching the data
+            let message = builder()
+                .sign(&alice, String::new, HashAlgorithm::SHA2_256)
+                .compression(None)
+                .to_message(&mut rng)
+                .unwrap();
+            let default_typ = if text {
+                SignatureType::Text
+            } else {
+                SignatureType::Binary
+            };
+            assert_eq!(
+                message.literal_signature_types(),
+                Some((mode, vec![default_typ]))
+            );
+
+            for typ in [SignatureType::Binary, SignatureType::Text] {
+                let message = builder()
+                    .sign(&alice, String::new, HashAlgorithm::SHA2_256)
+                    .signature_type(typ)
+                    .compression(None)
+                    .to_message(&mut rng)
+                    .unwrap();
+                assert_eq!(message.literal_signature_types(), Some((mode, vec![typ])));
+                message.verify(&alice_public).unwrap();
+
+                // streamed
+                let mut bytes = Vec::new();
+                builder()
+                    .sign(&alice, String::new, HashAlgorithm::SHA2_256)
+                    .signature_type(typ)
+                    .to_writer(&mut rng, &mut bytes)
+                    .unwrap();
+                let message = Message::from_bytes(&bytes[..])
+                    .unwrap()
+                    .decompress()
+                    .unwrap();
+                assert_eq!(message.literal_signature_types(), Some((mode, vec![typ])));
+                message.verify(&alice_public).unwrap();
+            }
+        }
+
+        // a text signature over data streamed from a reader
+        let mut bytes = Vec::new();
+        MessageBuilder::from_reader("hello.txt", data.as_bytes())
+            .sign(&alice, String::new, HashAlgorithm::SHA2_256)
+            .signature_type(SignatureType::Text)
+            .compression(None)
+            .to_writer(&mut rng, &mut bytes)
+            .unwrap();
+        let message = Message::from_bytes(&bytes[..]).unwrap();
+        assert_eq!(
+            message.literal_signature_types(),
+            Some((DataMode::Binary, vec![SignatureType::Text]))
+        );
+        message.verify(&alice_public).unwrap();
+
+        assert!(MessageBuilder::from_text("hello.txt", data)
+            .sign(&alice, String::new, HashAlgorithm::SHA2_256)
+            .signature_type(SignatureType::Standalone)
+            .to_message(&mut rng)
+            .is_err());
+    }
+
+    /// A file in the temporary directory, removed on drop.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, data: &[u8]) -> Self {
+            let path = std::env::temp_dir().join(format!("{}-{}", std::process::id(), name));
+            fs::write(&path, data).unwrap();
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "x25519")]
+    fn test_from_path() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let alice = load_alice();
+        let subkey = alice.secret_subkeys[0].public_key();
+        let file = TempFile::new("hello.txt", b"hello world");
+        let modified: DateTime<Utc> = fs::metadata(&file.0).unwrap().modified().unwrap().into();
+
+        let mut bytes = Vec::new();
+        MessageBuilder::from_path(&file.0)
+            .unwrap()
+            .sign(&alice, String::new, HashAlgorithm::SHA2_256)
+            .encrypt_to_key(&subkey)
+            .to_writer(&mut rng, &mut bytes)
+            .unwrap();
+
+        let message = Message::from_bytes(&bytes[..]).unwrap();
+        let decrypted = message
+            .decrypt_and_verify(String::new, &[&alice], &alice.public_key())
+            .unwrap();
+        assert_eq!(decrypted.get_content().unwrap().unwrap(), b"hello world");
+        let literal = decrypted.decompress().unwrap();
+        let literal = literal.get_literal().unwrap();
+        assert_eq!(
+            literal.file_name(),
+            file.0.file_name().unwrap().to_str().unwrap()
+        );
+        assert_eq!(literal.created(), &modified.trunc_subsecs(0));
+
+        // the inferred metadata can be overridden
+        let time = Utc::now().trunc_subsecs(0) - chrono::Duration::days(1);
+        let message = MessageBuilder::from_path(&file.0)
+            .unwrap()
+            .file_name("other.txt")
+            .modification_time(time)
+            .compression(None)
+            .to_message(&mut rng)
+            .unwrap();
+        let literal = message.get_literal().unwrap();
+        assert_eq!(literal.file_name(), "other.txt");
+        assert_eq!(literal.created(), &time);
+        assert_eq!(literal.data(), b"hello world");
+    }
+
+    #[test]
+    fn test_from_path_errors() {
+        let missing = std::env::temp_dir().join(format!("{}-missing.txt", std::process::id()));
+        assert!(MessageBuilder::from_path(&missing).is_err());
+        assert!(MessageBuilder::from_path(std::env::temp_dir()).is_err());
+    }
+
+    /// Reads from a file, checking that the data written so far keeps up with the data read.
+    struct CappedReader {
+        file: fs::File,
+        read: usize,
+        written: Rc<Cell<usize>>,
+    }
+
+    /// Less than the data, but more than the buffers of all layers.
+    const MEMORY_CAP: usize = 256 * 1024;
+
+    impl Read for CappedReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            assert!(
+                self.read.saturating_sub(self.written.get()) < MEMORY_CAP,
+                "read {} bytes, but wrote only {}",
+                self.read,
+                self.written.get()
+            );
+            let len = self.file.read(buf)?;
+            self.read += len;
+            Ok(len)
+        }
+    }
+
+    struct CountingWriter {
+        data: Vec<u8>,
+        written: Rc<Cell<usize>>,
+    }
+
+    impl Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.written.set(self.written.get() + buf.len());
+            self.data.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "x25519")]
+    fn test_to_writer_streams_file() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let alice = load_alice();
+        let subkey = alice.secret_subkeys[0].public_key();
+
+        // random data does not compress
+        let mut data = vec![0u8; 2 * 1024 * 1024];
+        rng.fill_bytes(&mut data);
+        let file = TempFile::new("large.bin", &data);
+
+        for seipd_v2 in [false, true] {
+            let written = Rc::new(Cell::new(0));
+            let reader = CappedReader {
+                file: fs::File::open(&file.0).unwrap(),
+                read: 0,
+                written: written.clone(),
+            };
+            let mut builder = MessageBuilder::from_reader("large.bin", reader)
+                .sign(&alice, String::new, HashAlgorithm::SHA2_256)
+                .encrypt_to_key(&subkey);
+            if seipd_v2 {
+                builder = builder.seipd_v2(SymmetricKeyAlgorithm::AES128, AeadAlgorithm::Ocb, 6);
+            }
+            let mut writer = CountingWriter {
+                data: Vec::new(),
+                written,
+            };
+            builder.to_writer(&mut rng, &mut writer).unwrap();
+            assert!(writer.data.len() > data.len());
+
+            let message = Message::from_bytes(&writer.data[..]).unwrap();
+            let decrypted = message
+                .decrypt_and_verify(String::new, &[&alice], &alice.public_key())
+                .unwrap();
+            assert!(decrypted.get_content().unwrap().unwrap() == data);
+        }
+    }
+
+    #[test]
+    fn test_subpacket_criticality() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let alice = load_alice();
+        let alice_public = alice.public_key();
+        let criticality = SubpacketCriticality {
+            signature_creation_time: true,
+            ..Default::default()
+        };
+        let builder = || {
+            MessageBuilder::from_bytes("", b"hello")
+                .compression(None)
+                .sign(&alice, String::new, HashAlgorithm::SHA2_256)
+                .subpacket_criticality(criticality)
+        };
+
+        let mut written = Vec::new();
+        builder().to_writer(&mut rng, &mut written).unwrap();
+        for bytes in [builder().to_bytes(&mut rng).unwrap(), written] {
+            let message = Message::from_bytes(&bytes[..]).unwrap();
+            message.verify(&alice_public).unwrap();
+            let Message::Signed { signature, .. } = message else {
+                panic!("expected a signed message: {message:?}");
+            };
+            for subpacket in signature.config.hashed_subpackets() {
+                let critical = matches!(subpacket.data, SubpacketData::SignatureCreationTime(_));
+                assert_eq!(subpacket.is_critical, critical, "{subpacket:?}");
+            }
+        }
+    }
+}
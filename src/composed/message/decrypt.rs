@@ -1,7 +1,7 @@
 use crate::crypto::sym::SymmetricKeyAlgorithm;
 use crate::errors::Result;
-use crate::packet::SymKeyEncryptedSessionKey;
-use crate::types::{KeyTrait, Mpi, SecretKeyRepr, SecretKeyTrait};
+use crate::packet::{PublicKeyEncryptedSessionKey, SymKeyEncryptedSessionKey};
+use crate::types::{KeyTrait, Mpi, SecretKeyRepr, SecretKeyTrait, SessionKey};
 
 /// Decrypts session key using secret key.
 pub fn decrypt_session_key<F, L>(locked_key: &L, key_pw: F, mpis: &[Mpi]) -> Result<PlainSessionKey>
@@ -18,6 +18,31 @@ where
     })
 }
 
+/// Decrypts the session key of a PKESK packet using secret key.
+///
+/// Session keys of v3 packets are returned as [`PlainSessionKey::V4`], those of v6 packets,
+/// which are used with SEIPDv2, as [`PlainSessionKey::V6`].
+pub fn decrypt_pkesk_session_key<F, L>(
+    locked_key: &L,
+    key_pw: F,
+    packet: &PublicKeyEncryptedSessionKey,
+) -> Result<PlainSessionKey>
+where
+    F: FnOnce() -> String,
+    L: SecretKeyTrait<Unlocked = SecretKeyRepr> + KeyTrait,
+{
+    match packet.version() {
+        6 => {
+            debug!("decrypt v6 session key");
+            locked_key.unlock(key_pw, |priv_key| {
+                let key = priv_key.decrypt_v6(packet.mpis(), &locked_key.fingerprint())?;
+                Ok(PlainSessionKey::V6 { key })
+            })
+        }
+        _ => decrypt_session_key(locked_key, key_pw, packet.mpis()),
+    }
+}
+
 /// Decrypted session key.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum PlainSessionKey {
@@ -33,6 +58,17 @@ pub enum PlainSessionKey {
     },
 }
 
+impl From<PlainSessionKey> for SessionKey {
+    /// Keeps the symmetric algorithm of v4 session keys, v5 and v6 session keys are used with
+    /// SEIPDv2 packets, which name their own algorithm.
+    fn from(key: PlainSessionKey) -> Self {
+        match key {
+            PlainSessionKey::V4 { sym_alg, key } => SessionKey::new(key, sym_alg),
+            PlainSessionKey::V5 { key } | PlainSessionKey::V6 { key } => SessionKey::new_v2(key),
+        }
+    }
+}
+
 /// Decrypts session key from SKESK packet.
 ///
 /// Returns decrypted or derived session key
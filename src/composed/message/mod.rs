@@ -1,6 +1,12 @@
+mod builder;
 mod decrypt;
 mod parser;
+mod password;
+mod reader;
 mod types;
 
+pub use self::builder::*;
 pub use self::decrypt::*;
+pub use self::password::*;
+pub use self::reader::*;
 pub use self::types::*;
@@ -1,12 +1,14 @@
+use std::io::{BufRead, BufReader, Read};
 use std::iter::Peekable;
 
-use crate::armor::BlockType;
-use crate::composed::message::Message;
+use crate::armor::{self, BlockType};
+use crate::composed::message::{Message, MessageKind};
+use crate::composed::shared::filter_parsed_packet_results;
 use crate::composed::Deserializable;
 use crate::errors::{Error, Result};
-use crate::packet::Packet;
+use crate::packet::{Packet, PacketParser};
 use crate::types::Tag;
-use crate::Edata;
+use crate::{Edata, Esk};
 
 pub struct MessageParser<I: Sized + Iterator<Item = Result<Packet>>> {
     source: Peekable<I>,
@@ -40,19 +42,7 @@ fn next<I: Iterator<Item = Result<Packet>>>(packets: &mut Peekable<I>) -> Option
                 return match packet.try_into() {
                     Ok(p) => {
                         let mut esk = vec![p];
-
-                        // while ESK take em
-                        while let Some(res) = packets.next_if(|res| {
-                            res.as_ref().is_ok_and(|p| {
-                                p.tag() == Tag::PublicKeyEncryptedSessionKey
-                                    || p.tag() == Tag::SymKeyEncryptedSessionKey
-                            })
-                        }) {
-                            match res {
-                                Ok(packet) => esk.push(packet.try_into().expect("peeked")),
-                                Err(e) => return Some(Err(e)),
-                            }
-                        }
+                        esk.extend(next_esks(packets));
 
                         // we expect exactly one edata after the ESKs
                         let edata = match packets.next() {
@@ -118,9 +108,7 @@ fn next<I: Iterator<Item = Result<Packet>>>(packets: &mut Peekable<I>) -> Option
                                 Err(e) => return Some(Err(e)),
                             }
                         } else {
-                            return Some(Err(format_err!(
-                                "missing signature for, one pass signature"
-                            )));
+                            return Some(Err(Error::MissingSignature));
                         };
 
                         Some(Ok(Message::Signed {
@@ -149,6 +137,51 @@ fn next<I: Iterator<Item = Result<Packet>>>(packets: &mut Peekable<I>) -> Option
     None
 }
 
+fn is_esk(tag: Tag) -> bool {
+    tag == Tag::PublicKeyEncryptedSessionKey || tag == Tag::SymKeyEncryptedSessionKey
+}
+
+/// Takes the ESK packets from the start of `packets`.
+fn next_esks<I: Iterator<Item = Result<Packet>>>(packets: &mut Peekable<I>) -> Vec<Esk> {
+    let mut esk = Vec::new();
+
+    // while ESK take em
+    while let Some(Ok(packet)) = packets.next_if(|res| res.as_ref().is_ok_and(|p| is_esk(p.tag())))
+    {
+        esk.push(packet.try_into().expect("peeked"));
+    }
+
+    esk
+}
+
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Maps the first packet of a binary message to its kind, see [`Message::classify`].
+fn classify_packets<R: Read>(reader: R) -> Result<MessageKind> {
+    let mut parser = PacketParser::new(reader);
+    loop {
+        let kind = match parser.peek_tag()? {
+            Some(Tag::PublicKeyEncryptedSessionKey | Tag::SymKeyEncryptedSessionKey) => {
+                MessageKind::Encrypted
+            }
+            Some(Tag::Signature) => MessageKind::Signed,
+            Some(Tag::OnePassSignature) => MessageKind::OnePassSigned,
+            Some(Tag::CompressedData) => MessageKind::Compressed,
+            Some(Tag::LiteralData) => MessageKind::Literal,
+            Some(Tag::Marker | Tag::Padding) => {
+                if let Some(res) = parser.next() {
+                    res?;
+                }
+                continue;
+            }
+            Some(tag) => bail!("unexpected packet {:?}", tag),
+            None => return Err(Error::NoMatchingPacket),
+        };
+
+        return Ok(kind);
+    }
+}
+
 impl<I: Sized + Iterator<Item = Result<Packet>>> Iterator for MessageParser<I> {
     type Item = Result<Message>;
 
@@ -157,6 +190,8 @@ impl<I: Sized + Iterator<Item = Result<Packet>>> Iterator for MessageParser<I> {
     }
 }
 
+impl crate::private::Sealed for Message {}
+
 impl Deserializable for Message {
     /// Parse a composed message.
     /// Ref: https://tools.ietf.org/html/rfc4880#section-11.3
@@ -175,3 +210,111 @@ impl Deserializable for Message {
         )
     }
 }
+
+impl Message {
+    /// Parses only the ESK packets at the start of an encrypted message in binary format.
+    ///
+    /// Reading stops at the header of the encrypted data packet that follows them, so the
+    /// recipients of a message can be listed without reading and parsing its, possibly large,
+    /// encrypted data. Marker packets and ESK packets of unsupported versions are skipped,
+    /// like when parsing the full message.
+    ///
+    /// The reader is buffered internally, so it may have been read past the ESK packets.
+    pub fn parse_esk_only<R: Read>(reader: R) -> Result<Vec<Esk>> {
+        let mut parser = PacketParser::new(reader);
+        let mut packets = std::iter::from_fn(|| match parser.peek_tag() {
+            Ok(Some(tag)) if is_esk(tag) || tag == Tag::Marker => parser.next(),
+            Ok(_) => None,
+            Err(err) => Some(Err(err)),
+        })
+        .filter_map(filter_parsed_packet_results)
+        .peekable();
+
+        let esk = next_esks(&mut packets);
+        if let Some(res) = packets.next() {
+            // the only packets left are the ones that failed to parse
+            res?;
+        }
+        drop(packets);
+
+        match parser.peek_tag()? {
+            Some(Tag::SymEncryptedData | Tag::SymEncryptedProtectedData) => Ok(esk),
+            Some(tag) if esk.is_empty() => bail!("not an encrypted message: {:?}", tag),
+            Some(tag) => bail!("Expected encrypted data packet, but found {:?}", tag),
+            None => bail!("Missing encrypted data packet"),
+        }
+    }
+
+    /// Determines the kind of a message from its first packet, without parsing the message.
+    ///
+    /// `reader` may be binary or in ASCII armor, a cleartext signed armor block is reported as
+    /// [`MessageKind::CleartextSigned`]. Only the header of the first packet is read, leading
+    /// marker and padding packets are skipped, like when parsing the full message. So the
+    /// message may still fail to parse, even if it could be classified.
+    pub fn classify<R: Read>(reader: R) -> Result<MessageKind> {
+        let mut reader = BufReader::new(reader);
+        let buf = reader.fill_buf()?;
+        let is_binary = buf.first().is_some_and(|b| b & 0x80 != 0) && !buf.starts_with(BOM);
+        if is_binary {
+            return classify_packets(reader);
+        }
+
+        let (typ, headers, _, rest) = armor::Dearmor::new(reader).read_only_header()?;
+        match typ {
+            BlockType::CleartextMessage => Ok(MessageKind::CleartextSigned),
+            typ if Self::matches_block_type(typ) => {
+                classify_packets(armor::Dearmor::after_header(typ, headers, rest))
+            }
+            typ => bail!("unexpected block type: {}", typ),
+        }
+    }
+
+    /// Parse a single message, that was split into multiple armored parts.
+    ///
+    /// Each reader holds one `PGP MESSAGE, PART X/Y` block, in order. The parts are reassembled
+    /// into a single binary stream before the message is parsed. Fails if the parts are not
+    /// numbered consecutively starting at 1, if they disagree on the total number of parts,
+    /// or if parts are missing.
+    ///
+    /// Returns the message and the headers of the first part.
+    pub fn from_armor_multipart<R: Read>(
+        readers: impl IntoIterator<Item = R>,
+    ) -> Result<(Self, armor::Headers)> {
+        let mut data = Vec::new();
+        let mut first_headers = None;
+        let mut total = 0;
+        let mut count = 0;
+
+        for reader in readers {
+            let mut dearmor = armor::Dearmor::new(BufReader::new(reader));
+            dearmor.read_header()?;
+
+            let (part, part_total) = match dearmor.typ {
+                Some(BlockType::MultiPartMessage(part, part_total)) => (part, part_total),
+                Some(typ) => bail!("unexpected block type: {}", typ),
+                None => bail!("dearmor failed to retrieve armor type"),
+            };
+
+            count += 1;
+            ensure_eq!(part, count, "unexpected message part");
+            if part_total != 0 {
+                if total == 0 {
+                    total = part_total;
+                }
+                ensure_eq!(part_total, total, "inconsistent number of message parts");
+            }
+
+            dearmor.read_to_end(&mut data)?;
+            if first_headers.is_none() {
+                first_headers = Some(dearmor.headers);
+            }
+        }
+
+        let headers = first_headers.ok_or(Error::NoMatchingPacket)?;
+        if total != 0 {
+            ensure_eq!(count, total, "missing message parts");
+        }
+
+        Ok((Self::from_bytes(&data[..])?, headers))
+    }
+}
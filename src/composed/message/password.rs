@@ -0,0 +1,145 @@
+use rand::{CryptoRng, Rng};
+
+use crate::composed::{Deserializable, Message, MessageBuilder};
+use crate::crypto::aead::AeadAlgorithm;
+use crate::crypto::sym::SymmetricKeyAlgorithm;
+use crate::errors::Result;
+use crate::types::StringToKey;
+use crate::ArmorOptions;
+
+/// Chunk size of SEIPDv2 packets created by [`encrypt_with_password`], 16 KiB chunks.
+const CHUNK_SIZE: u8 = 8;
+
+/// Encrypts `plaintext` with a password, without any keys involved.
+///
+/// The session key is protected by a SKESK packet, the data is encrypted into a SEIPDv1
+/// packet, or a SEIPDv2 packet using `aead` if given. Use [`MessageBuilder`] for more control
+/// over the message.
+///
+/// ```
+/// # use pgp::crypto::{aead::AeadAlgorithm, sym::SymmetricKeyAlgorithm};
+/// let encrypted = pgp::encrypt_with_password(
+///     &mut rand::thread_rng(),
+///     b"hello world",
+///     "secret",
+///     SymmetricKeyAlgorithm::AES256,
+///     Some(AeadAlgorithm::Ocb),
+/// )?;
+///
+/// let decrypted = pgp::decrypt_with_password(&encrypted, "secret")?;
+/// assert_eq!(decrypted, b"hello world");
+/// # Ok::<(), pgp::errors::Error>(())
+/// ```
+pub fn encrypt_with_password<R: CryptoRng + Rng + ?Sized>(
+    rng: &mut R,
+    plaintext: &[u8],
+    password: &str,
+    sym_alg: SymmetricKeyAlgorithm,
+    aead: Option<AeadAlgorithm>,
+) -> Result<Vec<u8>> {
+    password_builder(rng, plaintext, password, sym_alg, aead).to_bytes(rng)
+}
+
+/// Encrypts `plaintext` with a password, like [`encrypt_with_password`], and returns the
+/// message in ASCII armor.
+pub fn encrypt_with_password_armored<R: CryptoRng + Rng + ?Sized>(
+    rng: &mut R,
+    plaintext: &[u8],
+    password: &str,
+    sym_alg: SymmetricKeyAlgorithm,
+    aead: Option<AeadAlgorithm>,
+) -> Result<String> {
+    password_builder(rng, plaintext, password, sym_alg, aead)
+        .to_armored_string(rng, ArmorOptions::default())
+}
+
+fn password_builder<'a, R: CryptoRng + Rng + ?Sized>(
+    rng: &mut R,
+    plaintext: &[u8],
+    password: &'a str,
+    sym_alg: SymmetricKeyAlgorithm,
+    aead: Option<AeadAlgorithm>,
+) -> MessageBuilder<'a> {
+    let builder = MessageBuilder::from_bytes("", plaintext);
+    let builder = match aead {
+        Some(aead) => builder.seipd_v2(sym_alg, aead, CHUNK_SIZE),
+        None => builder.seipd_v1(sym_alg),
+    };
+
+    builder.encrypt_with_password(StringToKey::new_default(rng), || password.to_string())
+}
+
+/// Decrypts a password encrypted message and returns its content.
+///
+/// `message` may be binary or in ASCII armor. Compressed data is decompressed, signatures
+/// are not verified.
+pub fn decrypt_with_password(message: &[u8], password: &str) -> Result<Vec<u8>> {
+    let text = message.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(message);
+    let start = text
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(text.len());
+    let is_armored = text[start..].starts_with(b"-----BEGIN PGP MESSAGE");
+    let message = if is_armored {
+        Message::from_armor_single(message)?.0
+    } else {
+        Message::from_bytes(message)?
+    };
+
+    message
+        .decrypt_with_password(|| password.to_string())?
+        .decompress()?
+        .get_content()?
+        .ok_or_else(|| format_err!("message contains no literal data"))
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn test_password_roundtrip() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let plaintext = b"hello world\n";
+
+        for aead in [
+            None,
+            Some(AeadAlgorithm::Ocb),
+            #[cfg(feature = "aead-gcm")]
+            Some(AeadAlgorithm::Gcm),
+        ] {
+            let encrypted = encrypt_with_password(
+                &mut rng,
+                plaintext,
+                "secret",
+                SymmetricKeyAlgorithm::AES128,
+                aead,
+            )
+            .unwrap();
+            assert_eq!(
+                decrypt_with_password(&encrypted, "secret").unwrap(),
+                plaintext
+            );
+            assert!(decrypt_with_password(&encrypted, "wrong").is_err());
+
+            let armored = encrypt_with_password_armored(
+                &mut rng,
+                plaintext,
+                "secret",
+                SymmetricKeyAlgorithm::AES256,
+                aead,
+            )
+            .unwrap();
+            assert!(armored.starts_with("-----BEGIN PGP MESSAGE-----"));
+            assert_eq!(
+                decrypt_with_password(armored.as_bytes(), "secret").unwrap(),
+                plaintext
+            );
+        }
+    }
+}
@@ -0,0 +1,416 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use crate::composed::message::types::check_one_pass_signature;
+use crate::crypto::hash::Hasher;
+use crate::errors::Result;
+use crate::normalize_lines::CrlfWriter;
+use crate::packet::{
+    read_packet_header, Decompressor, LiteralData, OnePassSignature, Packet, PacketBodyReader,
+    PacketParser, Signature, SignatureType,
+};
+use crate::types::{CompressionAlgorithm, PublicKeyTrait, Tag, Version};
+
+/// One pass signature packets are a few dozen bytes, anything larger is rejected.
+const MAX_ONE_PASS_SIGNATURE_SIZE: u64 = 1024;
+
+/// The packets of a message, either read directly or from a compressed data packet.
+enum Source<R: BufRead> {
+    Plain(R),
+    Compressed(BufReader<Decompressor<PacketBodyReader<R>>>),
+}
+
+impl<R: BufRead> Read for Source<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Source::Plain(reader) => reader.read(buf),
+            Source::Compressed(reader) => reader.read(buf),
+        }
+    }
+}
+
+impl<R: BufRead> BufRead for Source<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match self {
+            Source::Plain(reader) => reader.fill_buf(),
+            Source::Compressed(reader) => reader.fill_buf(),
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        match self {
+            Source::Plain(reader) => reader.consume(amt),
+            Source::Compressed(reader) => reader.consume(amt),
+        }
+    }
+}
+
+/// Streaming verifier for a one pass signed message.
+///
+/// Yields the data of the literal data packet, while hashing it for all one pass signatures
+/// that precede it. Once all data is consumed, the trailing signatures can be checked using
+/// [`SignatureOnePassReader::verify`].
+///
+/// The message may be wrapped in a single compressed data packet. Only signatures over the
+/// literal data are supported, as produced by
+/// [`MessageBuilder::to_writer`](crate::composed::MessageBuilder::to_writer), not signatures
+/// over another signed message.
+pub struct SignatureOnePassReader<R: BufRead> {
+    body: PacketBodyReader<Source<R>>,
+    literal: LiteralData,
+    hashers: Vec<(OnePassSignature, Box<dyn Hasher>)>,
+    /// Holds the current piece of data with normalized line endings, for text signatures.
+    normalizer: Option<CrlfWriter<Vec<u8>>>,
+}
+
+impl<R: BufRead> std::fmt::Debug for SignatureOnePassReader<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SignatureOnePassReader")
+            .field("literal", &self.literal)
+            .field(
+                "one_pass_signatures",
+                &self.hashers.iter().map(|(ops, _)| ops).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl<R: BufRead> SignatureOnePassReader<R> {
+    /// Reads the one pass signatures and the header of the literal data packet, leaving the
+    /// reader positioned at the start of the data.
+    pub fn new(mut reader: R) -> Result<Self> {
+        let (mut version, mut tag, mut length) = read_packet_header(&mut reader)?;
+        let mut source = if tag == Tag::CompressedData {
+            let mut body = PacketBodyReader::new(reader, tag, length)?;
+            let mut alg = [0u8; 1];
+            body.read_exact(&mut alg)?;
+            let decompressor = Decompressor::new(CompressionAlgorithm::from(alg[0]), body)?;
+            let mut source = Source::Compressed(BufReader::new(decompressor));
+            (version, tag, length) = read_packet_header(&mut source)?;
+            source
+        } else {
+            Source::Plain(reader)
+        };
+
+        let mut hashers: Vec<(OnePassSignature, Box<dyn Hasher>)> = Vec::new();
+        loop {
+            match tag {
+                Tag::OnePassSignature => {
+                    let mut body = PacketBodyReader::new(source, tag, length)?;
+                    let mut packet = Vec::new();
+                    (&mut body)
+                        .take(MAX_ONE_PASS_SIGNATURE_SIZE + 1)
+                        .read_to_end(&mut packet)?;
+                    ensure!(
+                        packet.len() as u64 <= MAX_ONE_PASS_SIGNATURE_SIZE,
+                        "one pass signature too large"
+                    );
+                    let ops = OnePassSignature::from_slice(version, &packet)?;
+                    // Unless marked otherwise, a preceding one pass signature is made over
+                    // this signed message, not only over the literal data.
+                    if hashers.last().is_some_and(|(ops, _)| ops.last != 0) {
+                        unsupported_err!("nested one pass signatures");
+                    }
+                    if !matches!(ops.typ, SignatureType::Binary | SignatureType::Text) {
+                        unsupported_err!("one pass signature of type {:?}", ops.typ);
+                    }
+                    let hasher = ops.hash_algorithm.new_hasher()?;
+                    hashers.push((ops, hasher));
+                    source = body.into_inner();
+                }
+                Tag::LiteralData => break,
+                Tag::Signature if hashers.is_empty() => {
+                    unsupported_err!("not a one pass signed message")
+                }
+                _ => bail!("unexpected packet {:?} in a one pass signed message", tag),
+            }
+            (version, tag, length) = read_packet_header(&mut source)?;
+        }
+        if hashers.is_empty() {
+            unsupported_err!("message is not signed");
+        }
+
+        let mut body = PacketBodyReader::new(source, tag, length)?;
+        let literal = read_literal_header(&mut body, version)?;
+        let normalizer = hashers
+            .iter()
+            .any(|(ops, _)| ops.typ == SignatureType::Text)
+            .then(|| CrlfWriter::new(Vec::new()));
+
+        Ok(SignatureOnePassReader {
+            body,
+            literal,
+            hashers,
+            normalizer,
+        })
+    }
+
+    /// The header of the literal data packet, without any data.
+    pub fn literal_header(&self) -> &LiteralData {
+        &self.literal
+    }
+
+    fn update(&mut self, data: &[u8]) -> io::Result<()> {
+        let normalized = match self.normalizer.as_mut() {
+            Some(normalizer) => {
+                normalizer.get_mut().clear();
+                normalizer.write_all(data)?;
+                &normalizer.get_ref()[..]
+            }
+            None => data,
+        };
+        for (ops, hasher) in &mut self.hashers {
+            match ops.typ {
+                SignatureType::Text => hasher.update(normalized),
+                _ => hasher.update(data),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads the remaining data and the signatures, and verifies them against the given key.
+    ///
+    /// Every signature must match the one pass signature that announced it, and the message
+    /// must end after the signatures. On success returns the first signature that verified
+    /// against this key.
+    pub fn verify(mut self, key: &impl PublicKeyTrait) -> Result<Signature> {
+        io::copy(&mut self, &mut io::sink())?;
+
+        let count = self.hashers.len();
+        let mut source = self.body.into_inner();
+        let mut packets = PacketParser::new(&mut source);
+        let signatures = packets
+            .by_ref()
+            .take(count)
+            .map(|packet| match packet? {
+                Packet::Signature(signature) => Ok(signature),
+                packet => bail!("unexpected packet {:?}, expected a signature", packet.tag()),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        ensure_eq!(signatures.len(), count, "missing signature");
+        ensure!(
+            packets.next().is_none(),
+            "unexpected data after the signatures"
+        );
+        drop(packets);
+        if let Source::Compressed(reader) = source {
+            // the compressed data packet must be the last packet as well
+            let mut body = reader
+                .into_inner()
+                .into_inner()
+                .ok_or_else(|| format_err!("unsupported compression"))?;
+            io::copy(&mut body, &mut io::sink())?;
+            let mut outer = body.into_inner();
+            ensure!(
+                outer.fill_buf()?.is_empty(),
+                "unexpected data after the compressed message"
+            );
+        }
+
+        // The signatures follow the data in the reverse order of their one pass signatures.
+        let mut verified = None;
+        for ((ops, hasher), signature) in self.hashers.into_iter().zip(signatures.into_iter().rev())
+        {
+            check_one_pass_signature(&ops, &signature)?;
+            if verified.is_none() && signature.verify_hashed(key, hasher).is_ok() {
+                verified = Some(signature);
+            }
+        }
+
+        verified.ok_or_else(|| format_err!("No matching signature found"))
+    }
+}
+
+impl<R: BufRead> Read for SignatureOnePassReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.body.read(buf)?;
+        self.update(&buf[..len])?;
+
+        Ok(len)
+    }
+}
+
+/// Reads the header of a literal data packet, up to the start of the data.
+fn read_literal_header(body: &mut impl Read, version: Version) -> Result<LiteralData> {
+    // mode and length of the file name
+    let mut header = vec![0u8; 2];
+    body.read_exact(&mut header)?;
+    // file name and date
+    header.resize(2 + usize::from(header[1]) + 4, 0);
+    body.read_exact(&mut header[2..])?;
+
+    LiteralData::from_slice(version, &header)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use std::fs;
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::composed::{Deserializable, MessageBuilder, SignedSecretKey};
+    use crate::crypto::hash::HashAlgorithm;
+    use crate::ser::Serialize;
+    #[cfg(all(feature = "rsa", feature = "compression-deflate"))]
+    use crate::types::KeyTrait;
+    use crate::types::SecretKeyTrait;
+    use crate::Message;
+
+    fn load_key(path: &str) -> SignedSecretKey {
+        let (key, _headers) =
+            SignedSecretKey::from_armor_single(fs::File::open(path).unwrap()).unwrap();
+        key
+    }
+
+    /// Reads all data from `reader`, a few bytes at a time.
+    fn read_slowly(reader: &mut impl Read) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut buf = [0u8; 3];
+        loop {
+            match reader.read(&mut buf).unwrap() {
+                0 => return data,
+                len => data.extend_from_slice(&buf[..len]),
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "rsa", feature = "compression-deflate"))]
+    fn test_streaming() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let alice = load_key("./tests/autocrypt/alice@autocrypt.example.sec.asc");
+        let bob = load_key("./tests/unit-tests/text_signature_normalization_alice.key");
+        // larger than a single part, with line breaks split over reads
+        let data = "hello world\r\n".repeat(3000);
+
+        for (text, compression) in [
+            (false, None),
+            (true, None),
+            (true, Some(CompressionAlgorithm::ZLIB)),
+        ] {
+            let builder = if text {
+                MessageBuilder::from_reader("hello.txt", data.as_bytes())
+                    .signature_type(SignatureType::Text)
+            } else {
+                MessageBuilder::from_reader("hello.txt", data.as_bytes())
+            };
+            let mut bytes = Vec::new();
+            builder
+                .sign(&alice, String::new, HashAlgorithm::SHA2_256)
+                .sign(&bob, String::new, HashAlgorithm::SHA2_512)
+                .compression(compression)
+                .to_writer(&mut rng, &mut bytes)
+                .unwrap();
+
+            for key in [&alice, &bob] {
+                let mut reader = SignatureOnePassReader::new(&bytes[..]).unwrap();
+                assert_eq!(reader.literal_header().file_name(), "hello.txt");
+                assert_eq!(read_slowly(&mut reader), data.as_bytes());
+
+                let signature = reader.verify(&key.public_key()).unwrap();
+                assert_eq!(signature.issuer_fingerprint(), vec![&key.fingerprint()[..]]);
+            }
+
+            let other = load_key("./tests/unit-tests/cleartext-key-01.asc");
+            let reader = SignatureOnePassReader::new(&bytes[..]).unwrap();
+            assert!(reader.verify(&other.public_key()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_nested() {
+        let rng = ChaCha8Rng::seed_from_u64(0);
+        let alice = load_key("./tests/autocrypt/alice@autocrypt.example.sec.asc");
+        // signs the signed message again
+        let bytes = MessageBuilder::from_bytes("", b"hello")
+            .sign(&alice, String::new, HashAlgorithm::SHA2_256)
+            .sign(&alice, String::new, HashAlgorithm::SHA2_256)
+            .compression(None)
+            .to_bytes(rng)
+            .unwrap();
+
+        assert!(matches!(
+            SignatureOnePassReader::new(&bytes[..]),
+            Err(crate::errors::Error::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_not_one_pass_signed() {
+        let alice = load_key("./tests/autocrypt/alice@autocrypt.example.sec.asc");
+        let message = Message::new_literal("", "hello")
+            .sign(&alice, String::new, HashAlgorithm::SHA2_256)
+            .unwrap();
+        let Message::Signed {
+            message: Some(inner),
+            signature,
+            ..
+        } = message
+        else {
+            panic!("not signed");
+        };
+
+        // a signature before the data
+        let signed = Message::Signed {
+            message: Some(inner),
+            one_pass_signature: None,
+            signature,
+        };
+        let bytes = signed.to_bytes().unwrap();
+        assert!(matches!(
+            SignatureOnePassReader::new(&bytes[..]),
+            Err(crate::errors::Error::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_missing_signature() {
+        let rng = ChaCha8Rng::seed_from_u64(0);
+        let alice = load_key("./tests/autocrypt/alice@autocrypt.example.sec.asc");
+        let bytes = MessageBuilder::from_bytes("", b"hello")
+            .sign(&alice, String::new, HashAlgorithm::SHA2_256)
+            .compression(None)
+            .to_bytes(rng)
+            .unwrap();
+
+        // drop the signature packet
+        let Some(Ok(Packet::Signature(signature))) = PacketParser::new(&bytes[..]).nth(2) else {
+            panic!("expected a signature");
+        };
+        let mut signature_packet = Vec::new();
+        crate::packet::write_packet(&mut signature_packet, &signature).unwrap();
+        let truncated = &bytes[..bytes.len() - signature_packet.len()];
+
+        let reader = SignatureOnePassReader::new(truncated).unwrap();
+        assert!(reader.verify(&alice.public_key()).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "compression-deflate")]
+    fn test_trailing_data() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let alice = load_key("./tests/autocrypt/alice@autocrypt.example.sec.asc");
+
+        for compression in [None, Some(CompressionAlgorithm::ZLIB)] {
+            let bytes = MessageBuilder::from_bytes("", b"hello")
+                .sign(&alice, String::new, HashAlgorithm::SHA2_256)
+                .compression(compression)
+                .to_bytes(&mut rng)
+                .unwrap();
+            let reader = SignatureOnePassReader::new(&bytes[..]).unwrap();
+            assert!(reader.verify(&alice.public_key()).is_ok());
+
+            // another literal data packet after the signature
+            let mut extended = bytes.clone();
+            Message::new_literal("", "world")
+                .to_writer(&mut extended)
+                .unwrap();
+            let reader = SignatureOnePassReader::new(&extended[..]).unwrap();
+            assert!(reader.verify(&alice.public_key()).is_err());
+        }
+    }
+}
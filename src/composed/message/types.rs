@@ -1,31 +1,219 @@
-use std::io;
+use std::io::{self, Read};
 
 use bstr::BStr;
-use chrono::SubsecRound;
+use chrono::{DateTime, Utc};
+#[cfg(feature = "compression-deflate")]
 use flate2::write::{DeflateEncoder, ZlibEncoder};
+#[cfg(feature = "compression-deflate")]
 use flate2::Compression;
 use rand::{CryptoRng, Rng};
-use smallvec::SmallVec;
 
 use crate::armor;
 use crate::composed::message::decrypt::*;
+use crate::composed::message::reader::SignatureOnePassReader;
 use crate::composed::shared::Deserializable;
-use crate::composed::signed_key::SignedSecretKey;
+use crate::composed::signature::default_signature_config;
+use crate::composed::signed_key::{SecretKeyRing, SecretKeyStore, SignedSecretKey, StoreSigner};
 use crate::composed::StandaloneSignature;
 use crate::crypto::hash::HashAlgorithm;
 use crate::crypto::sym::SymmetricKeyAlgorithm;
 use crate::errors::{Error, Result};
 use crate::packet::{
-    write_packet, CompressedData, LiteralData, OnePassSignature, Packet,
-    PublicKeyEncryptedSessionKey, Signature, SignatureConfig, SignatureType, Subpacket,
-    SubpacketData, SymEncryptedData, SymEncryptedProtectedData, SymKeyEncryptedSessionKey,
+    write_packet, CompressedData, DataMode, LiteralData, OnePassSignature, Packet,
+    PublicKeyEncryptedSessionKey, Signature, SignatureType, SubpacketCriticality, SubpacketData,
+    SymEncryptedData, SymEncryptedProtectedData, SymKeyEncryptedSessionKey,
 };
 use crate::ser::Serialize;
 use crate::types::{
-    CompressionAlgorithm, KeyId, KeyTrait, KeyVersion, PublicKeyTrait, SecretKeyTrait, StringToKey,
-    Tag,
+    CancellableReader, Cancellation, CompressionAlgorithm, Fingerprint, KeyId, KeyTrait,
+    PublicKeyTrait, SecretKeyRepr, SecretKeyTrait, SessionKey, StringToKey, Tag,
 };
 
+/// Checks that a signature matches the one pass signature that announced it.
+///
+/// The data is hashed with the parameters of the one pass signature, so a trailing signature
+/// made with different parameters or by a different key must be rejected.
+pub(super) fn check_one_pass_signature(
+    ops: &OnePassSignature,
+    signature: &Signature,
+) -> Result<()> {
+    if ops.typ != signature.typ() {
+        return Err(Error::OnePassSignatureMismatch(format!(
+            "signature type {:?} != {:?}",
+            ops.typ,
+            signature.typ()
+        )));
+    }
+    if ops.hash_algorithm != signature.hash_alg() {
+        return Err(Error::OnePassSignatureMismatch(format!(
+            "hash algorithm {:?} != {:?}",
+            ops.hash_algorithm,
+            signature.hash_alg()
+        )));
+    }
+    if ops.pub_algorithm != signature.config.pub_alg {
+        return Err(Error::OnePassSignatureMismatch(format!(
+            "public key algorithm {:?} != {:?}",
+            ops.pub_algorithm, signature.config.pub_alg
+        )));
+    }
+
+    // Signatures without any issuer information can only be checked against the key.
+    let issuers = signature.issuer();
+    let fingerprints = signature.issuer_fingerprint();
+    if !issuers.is_empty() || !fingerprints.is_empty() {
+        let matches_issuer = issuers.iter().any(|id| **id == ops.key_id);
+        // the declared key version determines how the key id is derived from the fingerprint
+        let matches_fingerprint = signature
+            .config
+            .hashed_subpackets()
+            .chain(signature.config.unhashed_subpackets())
+            .any(|sp| match &sp.data {
+                SubpacketData::IssuerFingerprint(version, fp) => {
+                    KeyId::from_fingerprint(*version, fp).as_ref() == Some(&ops.key_id)
+                }
+                _ => false,
+            });
+        if !matches_issuer && !matches_fingerprint {
+            return Err(Error::OnePassSignatureMismatch(format!(
+                "issuer {:?} is not announced",
+                ops.key_id
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Object safe helper, to try decryption with primary keys and subkeys alike.
+trait DecryptionKey: KeyTrait {
+    fn decrypt_session_key(
+        &self,
+        key_pw: &dyn Fn(&Fingerprint) -> String,
+        packet: &PublicKeyEncryptedSessionKey,
+    ) -> Result<PlainSessionKey>;
+}
+
+impl<K> DecryptionKey for K
+where
+    K: SecretKeyTrait<Unlocked = SecretKeyRepr> + KeyTrait,
+{
+    fn decrypt_session_key(
+        &self,
+        key_pw: &dyn Fn(&Fingerprint) -> String,
+        packet: &PublicKeyEncryptedSessionKey,
+    ) -> Result<PlainSessionKey> {
+        let fingerprint = Fingerprint::of(self);
+        decrypt_pkesk_session_key(self, || key_pw(&fingerprint), packet)
+    }
+}
+
+/// The (sub)keys of `keys` that a PKESK packet for the recipient `id` may be encrypted to.
+///
+/// All of them for the wildcard key id.
+fn recipient_keys<'a>(
+    keys: impl IntoIterator<Item = &'a SignedSecretKey>,
+    id: &KeyId,
+) -> Vec<&'a dyn DecryptionKey> {
+    keys.into_iter()
+        .flat_map(|key| {
+            std::iter::once(&key.primary_key as &dyn DecryptionKey)
+                .chain(key.secret_subkeys.iter().map(|k| k as &dyn DecryptionKey))
+        })
+        .filter(|key| id.is_wildcard() || &key.key_id() == id)
+        .collect()
+}
+
+/// The PKESK packet a message was opened with, see [`open_pkesk`].
+struct OpenedPkesk<K> {
+    index: usize,
+    message: Message,
+    /// The candidates that decrypted the session key, the first one opened the message.
+    keys: Vec<K>,
+}
+
+/// Tries the PKESK packets in `esk`, with the candidates `find_keys` returns for their
+/// recipient key id, until a session key decrypts `edata`.
+///
+/// Packets addressed to a key id are tried first, in order. Packets with the wildcard key id
+/// match every candidate, so they are only tried after all of those.
+///
+/// Key ids are only 8 bytes and can collide, so every candidate is tried, and a session key is
+/// only trusted once it decrypted the data. Each distinct session key is tried on the data
+/// once. With `all_recipients` the remaining candidates are still unlocked after the message
+/// was opened, to report every one that holds the same session key.
+///
+/// Failures are added to `errors`, together with the index of their packet.
+fn open_pkesk<K>(
+    esk: &[Esk],
+    edata: &Edata,
+    mut find_keys: impl FnMut(&KeyId) -> Vec<K>,
+    mut decrypt_session_key: impl FnMut(&K, &PublicKeyEncryptedSessionKey) -> Result<PlainSessionKey>,
+    all_recipients: bool,
+    cancellation: &Cancellation,
+    errors: &mut Vec<(usize, Error)>,
+) -> Result<Option<OpenedPkesk<K>>> {
+    let mut failed = Vec::new();
+    let mut opened: Option<(OpenedPkesk<K>, PlainSessionKey)> = None;
+
+    let (exact, wildcard): (Vec<_>, Vec<_>) = esk
+        .iter()
+        .enumerate()
+        .filter_map(|(index, esk)| match esk {
+            Esk::PublicKeyEncryptedSessionKey(packet) => Some((index, packet)),
+            _ => None,
+        })
+        .partition(|(_, packet)| !packet.id().is_wildcard());
+
+    for (index, packet) in exact.into_iter().chain(wildcard) {
+        debug!("esk packet: {:?}", packet);
+
+        for key in find_keys(packet.id()) {
+            cancellation.check()?;
+            let session_key = match decrypt_session_key(&key, packet) {
+                Ok(session_key) => session_key,
+                Err(err) => {
+                    warn!("failed to decrypt session_key for key: {:?}", err);
+                    errors.push((index, err));
+                    continue;
+                }
+            };
+
+            if let Some((opened, opened_key)) = opened.as_mut() {
+                if *opened_key == session_key {
+                    opened.keys.push(key);
+                }
+                continue;
+            }
+            if failed.contains(&session_key) {
+                continue;
+            }
+
+            match edata.decrypt_cancellable(session_key.clone(), cancellation) {
+                Ok(message) => {
+                    let res = OpenedPkesk {
+                        index,
+                        message,
+                        keys: vec![key],
+                    };
+                    if !all_recipients {
+                        return Ok(Some(res));
+                    }
+                    opened = Some((res, session_key));
+                }
+                Err(err) => {
+                    warn!("failed to decrypt data with session key: {:?}", err);
+                    errors.push((index, err));
+                    failed.push(session_key);
+                }
+            }
+        }
+    }
+    cancellation.check()?;
+
+    Ok(opened.map(|(opened, _)| opened))
+}
+
 /// An [OpenPGP message](https://tools.ietf.org/html/rfc4880.html#section-11.3)
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Message {
@@ -45,6 +233,66 @@ pub enum Message {
     },
 }
 
+/// The kind of a message, as determined from its first packet by [`Message::classify`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MessageKind {
+    /// Starts with an ESK packet.
+    Encrypted,
+    /// Starts with a signature packet, the signed message follows it.
+    Signed,
+    /// Starts with a one pass signature packet.
+    OnePassSigned,
+    Compressed,
+    Literal,
+    /// An armored message using the Cleartext Signature Framework.
+    CleartextSigned,
+}
+
+/// The encrypted session key packet that an encrypted message was opened with.
+///
+/// `index` is the position of the packet among the ESK packets of the message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OpenedBy {
+    /// A Symmetric-Key Encrypted Session Key packet, decrypted with a password.
+    Skesk { index: usize },
+    /// A Public-Key Encrypted Session Key packet, decrypted with the (sub)key with the given
+    /// fingerprint.
+    Pkesk { index: usize, fingerprint: Vec<u8> },
+}
+
+/// A decrypted message, see [`Message::decrypt_detailed`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decrypted {
+    pub message: Message,
+    pub opened_by: OpenedBy,
+}
+
+/// The content of a signed message, see [`Message::verify_read`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verified {
+    /// The data of the signed literal data packet.
+    pub content: Vec<u8>,
+    /// The signature that verified.
+    pub signature: Signature,
+}
+
+impl Verified {
+    /// The key id of the issuer, as stated in the signature.
+    pub fn issuer(&self) -> Option<&KeyId> {
+        self.signature.issuer().first().copied()
+    }
+
+    /// The fingerprint of the issuer, as stated in the signature.
+    pub fn issuer_fingerprint(&self) -> Option<&[u8]> {
+        self.signature.issuer_fingerprint().first().copied()
+    }
+
+    /// The creation time of the signature.
+    pub fn created(&self) -> Option<&DateTime<Utc>> {
+        self.signature.created()
+    }
+}
+
 /// Encrypted Session Key
 ///
 /// Public-Key Encrypted Session Key Packet |
@@ -168,6 +416,18 @@ impl Edata {
     }
 
     pub fn decrypt(&self, key: PlainSessionKey) -> Result<Message> {
+        self.decrypt_cancellable(key, &Cancellation::new())
+    }
+
+    /// Decrypts the data, like [`Edata::decrypt`], and stops with [`Error::Cancelled`] once
+    /// `cancellation` is cancelled.
+    pub fn decrypt_cancellable(
+        &self,
+        key: PlainSessionKey,
+        cancellation: &Cancellation,
+    ) -> Result<Message> {
+        cancellation.check()?;
+
         let protected = self.tag() == Tag::SymEncryptedProtectedData;
         debug!("decrypting protected = {:?}", protected);
 
@@ -185,7 +445,8 @@ impl Edata {
                             Some(1),
                             "Version mismatch between key and integrity packet"
                         );
-                        let data = p.decrypt(&key, Some(sym_alg))?;
+                        let key = SessionKey::new(key, sym_alg);
+                        let data = p.decrypt_with_session_key_cancellable(&key, cancellation)?;
                         Message::from_bytes(&data[..])
                     }
                     Self::SymEncryptedData(p) => {
@@ -216,7 +477,9 @@ impl Edata {
             PlainSessionKey::V6 { key } => {
                 match self {
                     Self::SymEncryptedProtectedData(p) => {
-                        let decrypted_packets = p.decrypt(&key, None)?;
+                        let key = SessionKey::new_v2(key);
+                        let decrypted_packets =
+                            p.decrypt_with_session_key_cancellable(&key, cancellation)?;
 
                         let mut messages = Message::from_bytes_many(&decrypted_packets[..]);
                         // First message is the one we want to return
@@ -253,14 +516,17 @@ impl Serialize for Message {
                 signature,
                 ..
             } => {
-                if let Some(ops) = one_pass_signature {
-                    write_packet(writer, ops)?;
+                // without a one pass signature, the signature precedes the signed message
+                match one_pass_signature {
+                    Some(ops) => write_packet(writer, ops)?,
+                    None => write_packet(writer, signature)?,
                 }
                 if let Some(message) = message {
                     (**message).to_writer(writer)?;
                 }
-
-                write_packet(writer, signature)?;
+                if one_pass_signature.is_some() {
+                    write_packet(writer, signature)?;
+                }
 
                 Ok(())
             }
@@ -293,16 +559,23 @@ impl Message {
                 self.to_writer(&mut data)?;
                 data
             }
+            #[cfg(feature = "compression-deflate")]
             CompressionAlgorithm::ZIP => {
                 let mut enc = DeflateEncoder::new(Vec::new(), Compression::default());
                 self.to_writer(&mut enc)?;
                 enc.finish()?
             }
+            #[cfg(feature = "compression-deflate")]
             CompressionAlgorithm::ZLIB => {
                 let mut enc = ZlibEncoder::new(Vec::new(), Compression::default());
                 self.to_writer(&mut enc)?;
                 enc.finish()?
             }
+            #[cfg(not(feature = "compression-deflate"))]
+            CompressionAlgorithm::ZIP | CompressionAlgorithm::ZLIB => unsupported_err!(
+                "CompressionAlgorithm {} (feature \"compression-deflate\" is disabled)",
+                u8::from(alg)
+            ),
             CompressionAlgorithm::BZip2 => unimplemented_err!("BZip2"),
             CompressionAlgorithm::Private10 | CompressionAlgorithm::Other(_) => {
                 unsupported_err!("CompressionAlgorithm {} is unsupported", u8::from(alg))
@@ -322,8 +595,28 @@ impl Message {
         }
     }
 
+    /// Decompresses the data if compressed, like [`Message::decompress`], and stops with
+    /// [`Error::Cancelled`] once `cancellation` is cancelled.
+    ///
+    /// The handle is checked on every read from the decompressor, while the decompressed
+    /// packets are parsed.
+    pub fn decompress_cancellable(self, cancellation: &Cancellation) -> Result<Self> {
+        cancellation.check()?;
+
+        match self {
+            Message::Compressed(data) => {
+                let reader = CancellableReader {
+                    inner: data.decompress()?,
+                    cancellation,
+                };
+                cancellation.map_err(Message::from_bytes(reader))
+            }
+            _ => Ok(self),
+        }
+    }
+
     /// Encrypt the message to the list of passed in public keys.
-    pub fn encrypt_to_keys<R: CryptoRng + Rng>(
+    pub fn encrypt_to_keys<R: CryptoRng + Rng + ?Sized>(
         &self,
         rng: &mut R,
         alg: SymmetricKeyAlgorithm,
@@ -374,7 +667,7 @@ impl Message {
     }
 
     /// Symmetrically encrypts oneself using the provided `session_key`.
-    fn encrypt_symmetric<R: CryptoRng + Rng>(
+    fn encrypt_symmetric<R: CryptoRng + Rng + ?Sized>(
         &self,
         rng: &mut R,
         esk: Vec<Esk>,
@@ -393,6 +686,19 @@ impl Message {
         Ok(Message::Encrypted { esk, edata })
     }
 
+    /// Sign this message with a key whose secret operations are performed by a
+    /// [`SecretKeyStore`].
+    ///
+    /// `key` only provides the public parts, see [`StoreSigner`].
+    pub fn sign_with_store(
+        self,
+        key: &impl SecretKeyTrait,
+        store: &dyn SecretKeyStore,
+        hash_algorithm: HashAlgorithm,
+    ) -> Result<Self> {
+        self.sign(&StoreSigner::new(store, key), String::new, hash_algorithm)
+    }
+
     /// Sign this message using the provided key.
     pub fn sign<F>(
         self,
@@ -403,47 +709,50 @@ impl Message {
     where
         F: FnOnce() -> String,
     {
-        let key_id = key.key_id();
-        let algorithm = key.algorithm();
-        let hashed_subpackets = vec![
-            Subpacket::regular(SubpacketData::IssuerFingerprint(
-                KeyVersion::V4,
-                SmallVec::from_slice(&key.fingerprint()),
-            )),
-            Subpacket::regular(SubpacketData::SignatureCreationTime(
-                chrono::Utc::now().trunc_subsecs(0),
-            )),
-        ];
-        let unhashed_subpackets = vec![Subpacket::regular(SubpacketData::Issuer(key_id.clone()))];
+        self.sign_with(key, key_pw, hash_algorithm, None)
+    }
 
+    /// Sign this message using the provided key, like [`Message::sign`], with the given
+    /// critical bits on the standard subpackets of the signature.
+    pub fn sign_with_criticality<F>(
+        self,
+        key: &impl SecretKeyTrait,
+        key_pw: F,
+        hash_algorithm: HashAlgorithm,
+        criticality: SubpacketCriticality,
+    ) -> Result<Self>
+    where
+        F: FnOnce() -> String,
+    {
+        self.sign_with(key, key_pw, hash_algorithm, Some(criticality))
+    }
+
+    fn sign_with<F>(
+        self,
+        key: &impl SecretKeyTrait,
+        key_pw: F,
+        hash_algorithm: HashAlgorithm,
+        criticality: Option<SubpacketCriticality>,
+    ) -> Result<Self>
+    where
+        F: FnOnce() -> String,
+    {
         let (typ, signature) = match self {
             Message::Literal(ref l) => {
-                let typ = if l.is_binary() {
-                    SignatureType::Binary
-                } else {
+                let typ = if l.is_text() {
                     SignatureType::Text
+                } else {
+                    SignatureType::Binary
                 };
 
-                let signature_config = SignatureConfig::new_v4(
-                    Default::default(),
-                    typ,
-                    algorithm,
-                    hash_algorithm,
-                    hashed_subpackets,
-                    unhashed_subpackets,
-                );
+                let mut signature_config = default_signature_config(typ, key, hash_algorithm);
+                signature_config.subpacket_criticality = criticality;
                 (typ, signature_config.sign(key, key_pw, l.data())?)
             }
             _ => {
                 let typ = SignatureType::Binary;
-                let signature_config = SignatureConfig::new_v4(
-                    Default::default(),
-                    typ,
-                    algorithm,
-                    hash_algorithm,
-                    hashed_subpackets,
-                    unhashed_subpackets,
-                );
+                let mut signature_config = default_signature_config(typ, key, hash_algorithm);
+                signature_config.subpacket_criticality = criticality;
 
                 let data = self.to_bytes()?;
                 let signature = signature_config.sign(key, key_pw, &data[..])?;
@@ -451,7 +760,8 @@ impl Message {
                 (typ, signature)
             }
         };
-        let ops = OnePassSignature::from_details(typ, hash_algorithm, algorithm, key_id);
+        let ops =
+            OnePassSignature::from_details(typ, hash_algorithm, key.algorithm(), key.key_id());
 
         Ok(Message::Signed {
             message: Some(Box::new(self)),
@@ -477,6 +787,20 @@ impl Message {
         self.verify_internal(key, true)
     }
 
+    /// Verifies the one pass signed message read from `input`, and returns the signed content
+    /// together with the signature that verified.
+    ///
+    /// The data is hashed while it is read, using a [`SignatureOnePassReader`], but collected
+    /// into memory to be returned. To handle large messages, use the reader directly.
+    pub fn verify_read<R: io::Read>(input: R, key: &impl PublicKeyTrait) -> Result<Verified> {
+        let mut reader = SignatureOnePassReader::new(io::BufReader::new(input))?;
+        let mut content = Vec::new();
+        reader.read_to_end(&mut content)?;
+        let signature = reader.verify(key)?;
+
+        Ok(Verified { content, signature })
+    }
+
     /// Verifies this message.
     /// For signed messages this verifies the signature.
     ///
@@ -485,8 +809,14 @@ impl Message {
     fn verify_internal(&self, key: &impl PublicKeyTrait, decompress: bool) -> Result<()> {
         match self {
             Message::Signed {
-                signature, message, ..
+                signature,
+                message,
+                one_pass_signature,
             } => {
+                if let Some(ops) = one_pass_signature {
+                    check_one_pass_signature(ops, signature)?;
+                }
+
                 if let Some(message) = message {
                     match **message {
                         Message::Literal(ref data) => signature.verify(key, data.data()),
@@ -530,6 +860,10 @@ impl Message {
 
     /// Decrypt the message using the given key.
     /// Returns a message decrypter, and a list of [KeyId]s that are valid recipients of this message.
+    ///
+    /// All PKESK packets with a matching key id are tried, until one of them decrypts the data.
+    /// Packets with a wildcard key id are tried against all (sub)keys after those. If none does,
+    /// [`Error::NoEskOpened`] reports why each of them failed.
     pub fn decrypt<G>(&self, key_pw: G, keys: &[&SignedSecretKey]) -> Result<(Message, Vec<KeyId>)>
     where
         G: FnOnce() -> String + Clone,
@@ -543,106 +877,202 @@ impl Message {
                 None => bail!("not encrypted"),
             },
             Message::Encrypted { esk, edata, .. } => {
-                let valid_keys = keys
-                    .iter()
-                    .filter_map(|key| {
-                        // search for a packet with a key id that we have and that key.
-                        let mut packet = None;
-                        let mut encoding_key = None;
-                        let mut encoding_subkey = None;
-
-                        for esk_packet in esk.iter().filter_map(|k| match k {
-                            Esk::PublicKeyEncryptedSessionKey(k) => Some(k),
-                            _ => None,
-                        }) {
-                            debug!("esk packet: {:?}", esk_packet);
-                            debug!("{:?}", key.key_id());
-                            debug!(
-                                "{:?}",
-                                key.secret_subkeys
-                                    .iter()
-                                    .map(KeyTrait::key_id)
-                                    .collect::<Vec<_>>()
-                            );
-
-                            // find the key with the matching key id
-
-                            if &key.primary_key.key_id() == esk_packet.id() {
-                                encoding_key = Some(&key.primary_key);
-                            }
+                let mut errors = Vec::new();
+                let key_pw = |_: &Fingerprint| key_pw.clone()();
+                let opened = open_pkesk(
+                    esk,
+                    edata,
+                    |id| recipient_keys(keys.iter().copied(), id),
+                    |key, packet| key.decrypt_session_key(&key_pw, packet),
+                    true,
+                    &Cancellation::new(),
+                    &mut errors,
+                )?;
+
+                match opened {
+                    Some(opened) => {
+                        let ids = opened.keys.iter().map(|key| key.key_id()).collect();
+                        Ok((opened.message, ids))
+                    }
+                    None => Err(Error::no_esk_opened(errors)),
+                }
+            }
+        }
+    }
 
-                            if encoding_key.is_none() {
-                                encoding_subkey = key
-                                    .secret_subkeys
-                                    .iter()
-                                    .find(|&subkey| &subkey.key_id() == esk_packet.id());
-                            }
+    /// Decrypts the message and verifies the signature of the decrypted content.
+    ///
+    /// This is the counterpart to a message built with [`MessageBuilder`](crate::MessageBuilder),
+    /// which signs, compresses and then encrypts. Returns the decrypted and decompressed,
+    /// signed message.
+    pub fn decrypt_and_verify<G>(
+        &self,
+        key_pw: G,
+        keys: &[&SignedSecretKey],
+        signer: &impl PublicKeyTrait,
+    ) -> Result<Message>
+    where
+        G: FnOnce() -> String + Clone,
+    {
+        let (message, _) = self.decrypt(key_pw, keys)?;
+        let message = message.decompress()?;
+        message.verify(signer)?;
 
-                            if encoding_key.is_some() || encoding_subkey.is_some() {
-                                packet = Some(esk_packet);
-                                break;
-                            }
-                        }
+        Ok(message)
+    }
 
-                        packet.map(|packet| (packet, encoding_key, encoding_subkey))
-                    })
-                    .collect::<Vec<_>>();
+    /// Decrypt the message using any matching key from the given keyring.
+    ///
+    /// Keys are selected by the key id of the PKESK packets. Packets with a wildcard key id
+    /// are tried against all secret keys and subkeys in the ring, once no packet addressed to
+    /// a key id opened the message.
+    /// `key_pw` is called with the fingerprint of each (sub)key before it is unlocked, so
+    /// different keys can use different passwords.
+    ///
+    /// Returns the decrypted message and the [KeyId] of the (sub)key that was used.
+    pub fn decrypt_with_ring<G>(&self, key_pw: G, ring: &SecretKeyRing) -> Result<(Message, KeyId)>
+    where
+        G: Fn(&Fingerprint) -> String,
+    {
+        match self {
+            Message::Compressed { .. } | Message::Literal { .. } => {
+                bail!("not encrypted");
+            }
+            Message::Signed { message, .. } => match message {
+                Some(message) => message.as_ref().decrypt_with_ring(key_pw, ring),
+                None => bail!("not encrypted"),
+            },
+            Message::Encrypted { esk, edata, .. } => {
+                let mut errors = Vec::new();
+                let opened = open_pkesk(
+                    esk,
+                    edata,
+                    |id| recipient_keys(ring.iter(), id),
+                    |key, packet| key.decrypt_session_key(&key_pw, packet),
+                    false,
+                    &Cancellation::new(),
+                    &mut errors,
+                )?;
+
+                match opened {
+                    Some(opened) => Ok((opened.message, opened.keys[0].key_id())),
+                    None => Err(Error::no_esk_opened(errors)),
+                }
+            }
+        }
+    }
 
-                if valid_keys.is_empty() {
-                    return Err(Error::MissingKey);
+    /// Decrypt the message using the keys of a [`SecretKeyStore`].
+    ///
+    /// Keys are selected by the key id of the PKESK packets, like in
+    /// [`Message::decrypt_with_ring`], but the store unlocks them itself.
+    ///
+    /// Returns the decrypted message and the fingerprint of the (sub)key that was used.
+    pub fn decrypt_with_store(&self, store: &dyn SecretKeyStore) -> Result<(Message, Vec<u8>)> {
+        match self {
+            Message::Compressed { .. } | Message::Literal { .. } => {
+                bail!("not encrypted");
+            }
+            Message::Signed { message, .. } => match message {
+                Some(message) => message.as_ref().decrypt_with_store(store),
+                None => bail!("not encrypted"),
+            },
+            Message::Encrypted { esk, edata, .. } => {
+                let mut errors = Vec::new();
+                let opened = open_pkesk(
+                    esk,
+                    edata,
+                    |id| store.find_keys(id),
+                    |fingerprint, packet| store.decrypt_session_key(fingerprint, packet),
+                    false,
+                    &Cancellation::new(),
+                    &mut errors,
+                )?;
+
+                match opened {
+                    Some(mut opened) => Ok((opened.message, opened.keys.swap_remove(0))),
+                    None => Err(Error::no_esk_opened(errors)),
                 }
+            }
+        }
+    }
 
-                let session_keys = valid_keys
-                    .iter()
-                    .map(|(packet, encoding_key, encoding_subkey)| {
-                        if let Some(ek) = encoding_key {
-                            Ok((
-                                ek.key_id(),
-                                decrypt_session_key(ek, key_pw.clone(), packet.mpis())?,
-                            ))
-                        } else if let Some(ek) = encoding_subkey {
-                            Ok((
-                                ek.key_id(),
-                                decrypt_session_key(ek, key_pw.clone(), packet.mpis())?,
-                            ))
-                        } else {
-                            unreachable!("either a key or a subkey were found");
-                        }
-                    })
-                    .filter(|res| match res {
-                        Ok(_) => true,
+    /// Decrypt the message using the given password.
+    ///
+    /// All SKESK packets are tried in order, the first one that decrypts the message is used.
+    /// Both v4 SKESK packets for SEIPDv1 and v6 (AEAD) SKESK packets for SEIPDv2 are supported.
+    pub fn decrypt_with_password<F>(&self, msg_pw: F) -> Result<Message>
+    where
+        F: FnOnce() -> String + Clone,
+    {
+        match self {
+            Message::Compressed { .. } | Message::Literal { .. } => {
+                bail!("not encrypted");
+            }
+            Message::Signed { message, .. } => match message {
+                Some(ref message) => message.decrypt_with_password(msg_pw),
+                None => bail!("not encrypted"),
+            },
+            Message::Encrypted { esk, edata, .. } => {
+                let mut last_err = None;
+                for skesk in esk.iter().filter_map(|esk| match esk {
+                    Esk::SymKeyEncryptedSessionKey(k) => Some(k),
+                    _ => None,
+                }) {
+                    let res = decrypt_session_key_with_password(skesk, msg_pw.clone())
+                        .and_then(|session_key| edata.decrypt(session_key));
+                    match res {
+                        Ok(msg) => return Ok(msg),
                         Err(err) => {
-                            warn!("failed to decrypt session_key for key: {:?}", err);
-                            false
+                            warn!(
+                                "failed to decrypt with SKESK v{}: {:?}",
+                                skesk.version(),
+                                err
+                            );
+                            last_err = Some(err);
                         }
-                    })
-                    .collect::<Result<Vec<_>>>()?;
-
-                ensure!(!session_keys.is_empty(), "failed to decrypt session key");
-
-                // make sure all the keys are the same, otherwise we are in a bad place
-                let session_key = {
-                    let (_key_id, k0) = &session_keys[0];
-                    if !session_keys.iter().skip(1).all(|(_, k)| k0 == k) {
-                        bail!("found inconsistent session keys, possible message corruption");
                     }
+                }
 
-                    // TODO: avoid cloning
-                    k0.clone()
-                };
-
-                let ids = session_keys.into_iter().map(|(k, _)| k).collect();
-                let msg = edata.decrypt(session_key)?;
-
-                Ok((msg, ids))
+                Err(last_err.unwrap_or_else(|| format_err!("message is not password protected")))
             }
         }
     }
 
-    /// Decrypt the message using the given key.
-    /// Returns a message decrypter, and a list of [KeyId]s that are valid recipients of this message.
-    pub fn decrypt_with_password<F>(&self, msg_pw: F) -> Result<Message>
+    /// Decrypts the message with one of the given keys or a password, and reports which ESK
+    /// packet it was opened with.
+    ///
+    /// PKESK packets are tried first, with the matching (sub)keys of `keys`. Packets with a
+    /// wildcard key id are tried against all of them. If none of them can be decrypted and
+    /// `msg_pw` is given, the SKESK packets are tried with the password, in order.
+    pub fn decrypt_detailed<G, F>(
+        &self,
+        key_pw: G,
+        keys: &[&SignedSecretKey],
+        msg_pw: Option<F>,
+    ) -> Result<Decrypted>
+    where
+        G: FnOnce() -> String + Clone,
+        F: FnOnce() -> String + Clone,
+    {
+        self.decrypt_detailed_cancellable(key_pw, keys, msg_pw, &Cancellation::new())
+    }
+
+    /// Decrypts the message, like [`Message::decrypt_detailed`], and stops with
+    /// [`Error::Cancelled`] once `cancellation` is cancelled.
+    ///
+    /// The handle is checked before each ESK packet is tried, and between the chunks of SEIPDv2
+    /// encrypted data. Unlocking a secret key, deriving a key from `msg_pw`, decrypting a
+    /// session key and decrypting SEIPDv1 data are not interrupted, see [`Cancellation`].
+    pub fn decrypt_detailed_cancellable<G, F>(
+        &self,
+        key_pw: G,
+        keys: &[&SignedSecretKey],
+        msg_pw: Option<F>,
+        cancellation: &Cancellation,
+    ) -> Result<Decrypted>
     where
+        G: FnOnce() -> String + Clone,
         F: FnOnce() -> String + Clone,
     {
         match self {
@@ -650,21 +1080,61 @@ impl Message {
                 bail!("not encrypted");
             }
             Message::Signed { message, .. } => match message {
-                Some(ref message) => message.decrypt_with_password(msg_pw),
+                Some(message) => {
+                    message.decrypt_detailed_cancellable(key_pw, keys, msg_pw, cancellation)
+                }
                 None => bail!("not encrypted"),
             },
             Message::Encrypted { esk, edata, .. } => {
-                // TODO: handle multiple passwords
-                let skesk = esk.iter().find_map(|esk| match esk {
-                    Esk::SymKeyEncryptedSessionKey(k) => Some(k),
-                    _ => None,
-                });
+                let mut errors = Vec::new();
+                let key_pw = |_: &Fingerprint| key_pw.clone()();
+                let opened = open_pkesk(
+                    esk,
+                    edata,
+                    |id| recipient_keys(keys.iter().copied(), id),
+                    |key, packet| key.decrypt_session_key(&key_pw, packet),
+                    false,
+                    cancellation,
+                    &mut errors,
+                )?;
+                if let Some(opened) = opened {
+                    return Ok(Decrypted {
+                        message: opened.message,
+                        opened_by: OpenedBy::Pkesk {
+                            index: opened.index,
+                            fingerprint: opened.keys[0].fingerprint(),
+                        },
+                    });
+                }
 
-                ensure!(skesk.is_some(), "message is not password protected");
+                if let Some(msg_pw) = msg_pw {
+                    for (index, esk) in esk.iter().enumerate() {
+                        let Esk::SymKeyEncryptedSessionKey(packet) = esk else {
+                            continue;
+                        };
+                        cancellation.check()?;
+                        let res = decrypt_session_key_with_password(packet, msg_pw.clone())
+                            .and_then(|session_key| {
+                                edata.decrypt_cancellable(session_key, cancellation)
+                            });
+                        match res {
+                            Ok(message) => {
+                                return Ok(Decrypted {
+                                    message,
+                                    opened_by: OpenedBy::Skesk { index },
+                                })
+                            }
+                            Err(err) => {
+                                warn!("failed to decrypt with password: {:?}", err);
+                                errors.push((index, err));
+                            }
+                        }
+                    }
+                }
 
-                let session_key =
-                    decrypt_session_key_with_password(skesk.expect("checked above"), msg_pw)?;
-                edata.decrypt(session_key)
+                cancellation.check()?;
+                errors.sort_by_key(|(index, _)| *index);
+                Err(Error::no_esk_opened(errors))
             }
         }
     }
@@ -698,6 +1168,34 @@ impl Message {
         }
     }
 
+    /// Returns the data mode of the literal data, together with the types of the signatures
+    /// over it, the outermost signature first.
+    ///
+    /// Text data is usually signed with [`SignatureType::Text`] signatures and binary data with
+    /// [`SignatureType::Binary`] ones. Other combinations are valid, but confuse some
+    /// implementations, so callers may want to flag them.
+    ///
+    /// Returns `None` if the message does not hold literal data, compressed data is not
+    /// decompressed.
+    pub fn literal_signature_types(&self) -> Option<(DataMode, Vec<SignatureType>)> {
+        let mut types = Vec::new();
+        let mut message = self;
+        loop {
+            match message {
+                Message::Literal(data) => return Some((data.mode(), types)),
+                Message::Signed {
+                    message: inner,
+                    signature,
+                    ..
+                } => {
+                    types.push(signature.typ());
+                    message = inner.as_deref()?;
+                }
+                _ => return None,
+            }
+        }
+    }
+
     /// Returns the underlying content and `None` if the message is encrypted.
     ///
     /// Decompresses up to one layer of compressed data.
@@ -742,16 +1240,25 @@ impl Message {
     }
 
     pub fn to_armored_bytes(&self, opts: ArmorOptions<'_>) -> Result<Vec<u8>> {
-        let mut buf = Vec::new();
-
-        self.to_armored_writer(&mut buf, opts)?;
-
-        Ok(buf)
+        armor::to_armored_bytes(self, armor::BlockType::Message, opts)
     }
 
     pub fn to_armored_string(&self, opts: ArmorOptions<'_>) -> Result<String> {
-        let res = String::from_utf8(self.to_armored_bytes(opts)?).map_err(|e| e.utf8_error())?;
-        Ok(res)
+        armor::to_armored_string(self, armor::BlockType::Message, opts)
+    }
+
+    /// Armors the message with exactly the given armor headers.
+    ///
+    /// The optional CRC footer is only written if `emit_crc` is set.
+    pub fn to_armored_string_with(
+        &self,
+        headers: &armor::Headers,
+        emit_crc: bool,
+    ) -> Result<String> {
+        self.to_armored_string(ArmorOptions {
+            headers: Some(headers),
+            include_checksum: emit_crc,
+        })
     }
 }
 
@@ -791,6 +1298,91 @@ mod tests {
     use std::fs;
 
     #[test]
+    fn test_sign_with_criticality() {
+        let (key, _) = crate::SignedSecretKey::from_armor_single(
+            fs::File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+        )
+        .unwrap();
+        let criticality = SubpacketCriticality {
+            issuer_fingerprint: true,
+            ..Default::default()
+        };
+
+        let message = Message::new_literal("", "hello")
+            .sign_with_criticality(&key, String::new, HashAlgorithm::SHA2_256, criticality)
+            .unwrap();
+        let message = Message::from_bytes(&message.to_bytes().unwrap()[..]).unwrap();
+        message.verify(&key.public_key()).unwrap();
+
+        let Message::Signed { signature, .. } = message else {
+            panic!("expected a signed message: {message:?}");
+        };
+        for subpacket in signature.config.hashed_subpackets() {
+            let critical = matches!(subpacket.data, SubpacketData::IssuerFingerprint(..));
+            assert_eq!(subpacket.is_critical, critical, "{subpacket:?}");
+        }
+    }
+
+    #[test]
+    fn test_decrypt_with_password_skesk_v6() {
+        use crate::crypto::aead::AeadAlgorithm;
+
+        let mut rng = thread_rng();
+        let literal = Message::new_literal_bytes("", b"hello world");
+        let plaintext = literal.to_bytes().unwrap();
+
+        for (sym_alg, aead) in [
+            #[cfg(feature = "aead-eax")]
+            (SymmetricKeyAlgorithm::AES128, AeadAlgorithm::Eax),
+            (SymmetricKeyAlgorithm::AES128, AeadAlgorithm::Ocb),
+            #[cfg(feature = "aead-gcm")]
+            (SymmetricKeyAlgorithm::AES256, AeadAlgorithm::Gcm),
+        ] {
+            let session_key = sym_alg.new_session_key(&mut rng);
+            let edata = SymEncryptedProtectedData::encrypt_seipdv2(
+                &mut rng,
+                sym_alg,
+                aead,
+                6,
+                &session_key,
+                &plaintext,
+            )
+            .unwrap();
+
+            let skesk = |password: &str| {
+                let password = password.to_string();
+                SymKeyEncryptedSessionKey::encrypt_v6(
+                    &mut thread_rng(),
+                    || password,
+                    &session_key,
+                    StringToKey::new_iterated(thread_rng(), HashAlgorithm::SHA2_256, 0),
+                    sym_alg,
+                    aead,
+                )
+                .unwrap()
+            };
+            // the first SKESK is for another password
+            let message = Message::Encrypted {
+                esk: vec![
+                    Esk::SymKeyEncryptedSessionKey(skesk("other")),
+                    Esk::SymKeyEncryptedSessionKey(skesk("secret")),
+                ],
+                edata: Edata::SymEncryptedProtectedData(edata),
+            };
+            let message = Message::from_bytes(&message.to_bytes().unwrap()[..]).unwrap();
+
+            for password in ["secret", "other"] {
+                let decrypted = message
+                    .decrypt_with_password(|| password.to_string())
+                    .unwrap();
+                assert_eq!(decrypted, literal);
+            }
+            assert!(message.decrypt_with_password(|| "wrong".into()).is_err());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "compression-deflate")]
     fn test_compression_zlib() {
         let lit_msg = Message::new_literal("hello-zlib.txt", "hello world");
 
@@ -801,6 +1393,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "compression-deflate")]
     fn test_compression_zip() {
         let lit_msg = Message::new_literal("hello-zip.txt", "hello world");
 
@@ -823,6 +1416,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(all(feature = "rsa", feature = "compression-deflate"))]
     fn test_rsa_encryption() {
         use rand::SeedableRng;
 
@@ -850,7 +1444,6 @@ mod tests {
         assert_eq!(encrypted, encrypted2);
 
         let armored = encrypted.to_armored_bytes(None.into()).unwrap();
-        fs::write("./message-rsa.asc", &armored).unwrap();
 
         let parsed = Message::from_armor_single(&armored[..]).unwrap().0;
 
@@ -860,6 +1453,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(all(feature = "x25519", feature = "compression-deflate"))]
     fn test_x25519_encryption() {
         let (skey, _headers) = SignedSecretKey::from_armor_single(
             fs::File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
@@ -878,7 +1472,6 @@ mod tests {
                 .unwrap();
 
             let armored = encrypted.to_armored_bytes(None.into()).unwrap();
-            fs::write("./message-x25519.asc", &armored).unwrap();
 
             let parsed = Message::from_armor_single(&armored[..]).unwrap().0;
 
@@ -889,6 +1482,216 @@ mod tests {
     }
 
     #[test]
+    #[cfg(all(feature = "rsa", feature = "x25519"))]
+    fn test_decrypt_with_ring() {
+        let (rsa_key, _headers) = SignedSecretKey::from_armor_single(
+            fs::File::open("./tests/opengpg-interop/testcases/messages/gnupg-v1-001-decrypt.asc")
+                .unwrap(),
+        )
+        .unwrap();
+        let (x25519_key, _headers) = SignedSecretKey::from_armor_single(
+            fs::File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+        )
+        .unwrap();
+
+        let rsa_fingerprint = Fingerprint::of(&rsa_key.secret_subkeys[0]);
+        let key_pw = |fingerprint: &Fingerprint| {
+            if *fingerprint == rsa_fingerprint {
+                "test".to_string()
+            } else {
+                "".to_string()
+            }
+        };
+
+        let mut ring = SecretKeyRing::from(x25519_key.clone());
+        ring.push(rsa_key.clone());
+
+        let lit_msg = Message::new_literal("hello.txt", "hello world\n");
+        let mut rng = thread_rng();
+
+        for key in [&rsa_key, &x25519_key] {
+            let pkey = key.secret_subkeys[0].public_key();
+            let encrypted = lit_msg
+                .encrypt_to_keys(&mut rng, SymmetricKeyAlgorithm::AES128, &[&pkey][..])
+                .unwrap();
+
+            let (decrypted, key_id) = encrypted.decrypt_with_ring(key_pw, &ring).unwrap();
+            assert_eq!(decrypted, lit_msg);
+            assert_eq!(key_id, key.secret_subkeys[0].key_id());
+        }
+
+        // no matching key
+        let pkey = rsa_key.secret_subkeys[0].public_key();
+        let encrypted = lit_msg
+            .encrypt_to_keys(&mut rng, SymmetricKeyAlgorithm::AES128, &[&pkey][..])
+            .unwrap();
+        let ring = SecretKeyRing::from(x25519_key);
+        assert!(matches!(
+            encrypted.decrypt_with_ring(key_pw, &ring),
+            Err(Error::MissingKey)
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "rsa", feature = "x25519"))]
+    fn test_decrypt_with_ring_wildcard_last() {
+        use rand::SeedableRng;
+        use std::cell::RefCell;
+
+        let (rsa_key, _headers) = SignedSecretKey::from_armor_single(
+            fs::File::open("./tests/opengpg-interop/testcases/messages/gnupg-v1-001-decrypt.asc")
+                .unwrap(),
+        )
+        .unwrap();
+        let (x25519_key, _headers) = SignedSecretKey::from_armor_single(
+            fs::File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+        )
+        .unwrap();
+
+        let mut ring = SecretKeyRing::from(x25519_key.clone());
+        ring.push(rsa_key.clone());
+
+        let lit_msg = Message::new_literal("hello.txt", "hello world\n");
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(0);
+        let x25519_pkey = x25519_key.secret_subkeys[0].public_key();
+        let rsa_pkey = rsa_key.secret_subkeys[0].public_key();
+        let Message::Encrypted { mut esk, edata } = lit_msg
+            .encrypt_to_keys(
+                &mut rng,
+                SymmetricKeyAlgorithm::AES128,
+                &[&x25519_pkey, &rsa_pkey][..],
+            )
+            .unwrap()
+        else {
+            panic!("not encrypted");
+        };
+
+        // hide the first recipient behind the wildcard key id
+        let Esk::PublicKeyEncryptedSessionKey(first) = &esk[0] else {
+            panic!("not a PKESK");
+        };
+        esk[0] = Esk::PublicKeyEncryptedSessionKey(PublicKeyEncryptedSessionKey::v3_from_parts(
+            KeyId::from_slice(&[0u8; 8]).unwrap(),
+            first.algorithm(),
+            first.mpis().to_vec(),
+        ));
+        let encrypted = Message::Encrypted { esk, edata };
+
+        // the packet addressed to the rsa subkey is tried before the wildcard packet
+        let unlocked = RefCell::new(Vec::new());
+        let (decrypted, key_id) = encrypted
+            .decrypt_with_ring(
+                |fingerprint: &Fingerprint| {
+                    unlocked.borrow_mut().push(fingerprint.clone());
+                    "test".to_string()
+                },
+                &ring,
+            )
+            .unwrap();
+        assert_eq!(decrypted, lit_msg);
+        assert_eq!(key_id, rsa_key.secret_subkeys[0].key_id());
+        assert_eq!(
+            unlocked.into_inner(),
+            vec![Fingerprint::of(&rsa_key.secret_subkeys[0])]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "rsa")]
+    fn test_decrypt_skips_unknown_recipient_key_version() {
+        use rand::SeedableRng;
+
+        use crate::types::Version;
+
+        let (skey, _headers) = SignedSecretKey::from_armor_single(
+            fs::File::open("./tests/opengpg-interop/testcases/messages/gnupg-v1-001-decrypt.asc")
+                .unwrap(),
+        )
+        .unwrap();
+        let pkey = skey.secret_subkeys[0].public_key();
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(0);
+
+        let lit_msg = Message::new_literal("hello.txt", "hello world\n");
+        let encrypted = lit_msg
+            .encrypt_to_keys(&mut rng, SymmetricKeyAlgorithm::AES128, &[&pkey][..])
+            .unwrap();
+
+        // v6 PKESK for a recipient with an unknown key version, followed by the decryptable one
+        let mut unknown = vec![6, 33, 0x99];
+        unknown.extend_from_slice(&[0xAA; 32]);
+        unknown.extend_from_slice(&[1, 0x00, 0x08, 0xff]);
+        let mut bytes = vec![0xC1, unknown.len() as u8];
+        bytes.extend_from_slice(&unknown);
+        bytes.extend_from_slice(&encrypted.to_bytes().unwrap());
+
+        assert!(matches!(
+            PublicKeyEncryptedSessionKey::from_slice(Version::New, &unknown),
+            Err(Error::Unsupported(_))
+        ));
+
+        let parsed = Message::from_bytes(&bytes[..]).unwrap();
+        let decrypted = parsed.decrypt(|| "test".into(), &[&skey]).unwrap().0;
+        assert_eq!(decrypted, lit_msg);
+    }
+
+    #[test]
+    fn test_seipdv1_mdc_tampered() {
+        let decrypt = |name| {
+            let file =
+                fs::File::open(format!("./tests/unit-tests/seipdv1-mdc/{name}.asc")).unwrap();
+            let (msg, _) = Message::from_armor_single(file).unwrap();
+            msg.decrypt_with_password(|| "secret".into())
+        };
+
+        let msg = decrypt("valid").unwrap();
+        assert_eq!(msg.get_literal().unwrap().data(), b"hello world\r\n");
+
+        // MDC packet missing from the plaintext
+        assert!(matches!(decrypt("mdc-stripped"), Err(Error::MdcError)));
+        // single bit flipped in the MDC hash
+        assert!(matches!(decrypt("mdc-bitflip"), Err(Error::MdcError)));
+    }
+
+    #[test]
+    #[cfg(feature = "x25519")]
+    fn test_encryption_rng_is_only_randomness() {
+        use rand::SeedableRng;
+        use rand_chacha::ChaCha8Rng;
+
+        let (skey, _headers) = SignedSecretKey::from_armor_single(
+            fs::File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+        )
+        .unwrap();
+        let pkey = skey.secret_subkeys[0].public_key();
+
+        let lit_msg = Message::new_literal("hello.txt", "hello world\n");
+
+        // ephemeral ECDH key and session key
+        let encrypt_to_key = |seed| {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            lit_msg
+                .encrypt_to_keys(&mut rng, SymmetricKeyAlgorithm::AES128, &[&pkey][..])
+                .unwrap()
+        };
+        assert_eq!(encrypt_to_key(1), encrypt_to_key(1));
+        assert_ne!(encrypt_to_key(1), encrypt_to_key(2));
+
+        // s2k salt and session key
+        let encrypt_with_password = |seed| {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            let s2k = StringToKey::new_default(&mut rng);
+            lit_msg
+                .encrypt_with_password(&mut rng, s2k, SymmetricKeyAlgorithm::AES128, || {
+                    "secret".into()
+                })
+                .unwrap()
+        };
+        assert_eq!(encrypt_with_password(1), encrypt_with_password(1));
+        assert_ne!(encrypt_with_password(1), encrypt_with_password(2));
+    }
+
+    #[test]
+    #[cfg(feature = "compression-deflate")]
     fn test_password_encryption() {
         let _ = pretty_env_logger::try_init();
 
@@ -906,7 +1709,6 @@ mod tests {
             .unwrap();
 
         let armored = encrypted.to_armored_bytes(None.into()).unwrap();
-        fs::write("./message-password.asc", &armored).unwrap();
 
         let parsed = Message::from_armor_single(&armored[..]).unwrap().0;
 
@@ -981,7 +1783,6 @@ mod tests {
             .unwrap();
 
         let armored = signed_msg.to_armored_bytes(None.into()).unwrap();
-        fs::write("./message-string-signed-x25519.asc", &armored).unwrap();
 
         signed_msg.verify(&pkey).unwrap();
 
@@ -1004,7 +1805,6 @@ mod tests {
             .unwrap();
 
         let armored = signed_msg.to_armored_bytes(None.into()).unwrap();
-        fs::write("./message-bytes-signed-x25519.asc", &armored).unwrap();
 
         signed_msg.verify(&pkey).unwrap();
 
@@ -1013,6 +1813,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "compression-deflate")]
     fn test_x25519_signing_bytes_compressed() {
         let (skey, _headers) = SignedSecretKey::from_armor_single(
             fs::File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
@@ -1028,7 +1829,6 @@ mod tests {
         let compressed_msg = signed_msg.compress(CompressionAlgorithm::ZLIB).unwrap();
 
         let armored = compressed_msg.to_armored_bytes(None.into()).unwrap();
-        fs::write("./message-bytes-compressed-signed-x25519.asc", &armored).unwrap();
 
         signed_msg.verify(&pkey).unwrap();
 
@@ -1037,6 +1837,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "rsa")]
     fn test_rsa_signing_string() {
         for _ in 0..100 {
             let (skey, _headers) = SignedSecretKey::from_armor_single(
@@ -1055,7 +1856,6 @@ mod tests {
                 .unwrap();
 
             let armored = signed_msg.to_armored_bytes(None.into()).unwrap();
-            fs::write("./message-string-signed-rsa.asc", &armored).unwrap();
 
             signed_msg.verify(&pkey).unwrap();
 
@@ -1065,6 +1865,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "rsa")]
     fn test_rsa_signing_bytes() {
         let (skey, _headers) = SignedSecretKey::from_armor_single(
             fs::File::open("./tests/opengpg-interop/testcases/messages/gnupg-v1-001-decrypt.asc")
@@ -1080,7 +1881,6 @@ mod tests {
             .unwrap();
 
         let armored = signed_msg.to_armored_bytes(None.into()).unwrap();
-        fs::write("./message-bytes-signed-rsa.asc", &armored).unwrap();
 
         signed_msg.verify(&pkey).unwrap();
 
@@ -1089,6 +1889,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(all(feature = "rsa", feature = "compression-deflate"))]
     fn test_rsa_signing_bytes_compressed() {
         let (skey, _headers) = SignedSecretKey::from_armor_single(
             fs::File::open("./tests/opengpg-interop/testcases/messages/gnupg-v1-001-decrypt.asc")
@@ -1105,7 +1906,6 @@ mod tests {
 
         let compressed_msg = signed_msg.compress(CompressionAlgorithm::ZLIB).unwrap();
         let armored = compressed_msg.to_armored_bytes(None.into()).unwrap();
-        fs::write("./message-bytes-compressed-signed-rsa.asc", &armored).unwrap();
 
         signed_msg.verify(&pkey).unwrap();
 
@@ -1153,6 +1953,68 @@ mod tests {
         signed_msg.verify(&verify).expect("signature seems bad");
     }
 
+    #[test]
+    #[cfg(feature = "compression-deflate")]
+    fn test_verify_read() {
+        let (signed_msg, _header) = Message::from_armor_single(
+            fs::File::open("./tests/unit-tests/text_signature_normalization.msg").unwrap(),
+        )
+        .unwrap();
+        let (skey, _headers) = SignedSecretKey::from_armor_single(
+            fs::File::open("./tests/unit-tests/text_signature_normalization_alice.key").unwrap(),
+        )
+        .unwrap();
+        let signing = skey
+            .secret_subkeys
+            .iter()
+            .find(|key| {
+                key.key_id()
+                    == KeyId::from_slice(&[0x64, 0x35, 0x7E, 0xB6, 0xBB, 0x55, 0xDE, 0x12]).unwrap()
+            })
+            .unwrap()
+            .public_key();
+
+        let bytes = signed_msg.to_bytes().unwrap();
+        let verified = Message::verify_read(&bytes[..], &signing).unwrap();
+        assert_eq!(verified.content, b"foo\nbar\r\nbaz");
+        let Message::Signed { signature, .. } = &signed_msg else {
+            panic!("not signed");
+        };
+        assert_eq!(&verified.signature, signature);
+        assert_eq!(
+            verified.issuer_fingerprint(),
+            Some(&signing.fingerprint()[..])
+        );
+        assert_eq!(verified.created(), signature.created());
+
+        // compressed
+        let compressed = signed_msg
+            .compress(CompressionAlgorithm::ZLIB)
+            .unwrap()
+            .to_bytes()
+            .unwrap();
+        assert_eq!(
+            Message::verify_read(&compressed[..], &signing).unwrap(),
+            verified
+        );
+
+        // wrong key
+        assert!(Message::verify_read(&bytes[..], &skey.public_key()).is_err());
+
+        // modified content
+        let pos = bytes.windows(3).position(|w| w == b"foo").unwrap();
+        let mut modified = bytes.clone();
+        modified[pos] = b'g';
+        assert!(Message::verify_read(&modified[..], &signing).is_err());
+
+        // not signed
+        let literal = Message::new_literal("", "hello").to_bytes().unwrap();
+        assert!(matches!(
+            Message::verify_read(&literal[..], &signing),
+            Err(Error::Unsupported(_))
+        ));
+    }
+
     /// Tests that decompressing compression quine does not result in stack overflow.
     /// quine.out comes from <https://mumble.net/~campbell/misc/pgp-quine/>
     /// See <https://mumble.net/~campbell/2013/10/08/compression> for details.
@@ -1169,4 +2031,280 @@ mod tests {
         assert!(msg.get_content().is_err());
         assert!(msg.verify(&pkey).is_err());
     }
+
+    #[test]
+    fn test_one_pass_signature_mismatch() {
+        use crate::crypto::public_key::PublicKeyAlgorithm;
+
+        let (skey, _headers) = SignedSecretKey::from_armor_single(
+            fs::File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+        )
+        .unwrap();
+        let pkey = skey.public_key();
+
+        let signed_msg = Message::new_literal("hello.txt", "hello world\n")
+            .sign(&skey, || "".into(), HashAlgorithm::SHA2_256)
+            .unwrap();
+        let bytes = signed_msg.to_bytes().unwrap();
+        Message::from_bytes(&bytes[..])
+            .unwrap()
+            .verify(&pkey)
+            .unwrap();
+
+        let tamper = |f: &dyn Fn(&mut OnePassSignature)| {
+            let mut msg = signed_msg.clone();
+            let Message::Signed {
+                one_pass_signature: Some(ops),
+                ..
+            } = &mut msg
+            else {
+                panic!("expected a one pass signed message");
+            };
+            f(ops);
+
+            // round trip, to make sure the parser lets the crafted message through
+            let bytes = msg.to_bytes().unwrap();
+            Message::from_bytes(&bytes[..]).unwrap().verify(&pkey)
+        };
+
+        let mismatches: [&dyn Fn(&mut OnePassSignature); 4] = [
+            &|ops| ops.typ = SignatureType::Binary,
+            &|ops| ops.hash_algorithm = HashAlgorithm::SHA2_512,
+            &|ops| ops.pub_algorithm = PublicKeyAlgorithm::RSA,
+            &|ops| ops.key_id = KeyId::from_slice(&[1; 8]).unwrap(),
+        ];
+        for f in mismatches {
+            let err = tamper(f).unwrap_err();
+            assert!(
+                matches!(err, Error::OnePassSignatureMismatch(_)),
+                "unexpected error {err:?}"
+            );
+        }
+
+        // unchanged one pass signature
+        tamper(&|_| {}).unwrap();
+    }
+
+    #[test]
+    fn test_issuer_fingerprint_version() {
+        use smallvec::SmallVec;
+
+        use crate::types::KeyVersion;
+
+        let (skey, _headers) = SignedSecretKey::from_armor_single(
+            fs::File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+        )
+        .unwrap();
+
+        let signed_msg = Message::new_literal("hello.txt", "hello world\n")
+            .sign(&skey, || "".into(), HashAlgorithm::SHA2_256)
+            .unwrap();
+        let Message::Signed {
+            one_pass_signature: Some(ops),
+            signature,
+            ..
+        } = signed_msg
+        else {
+            panic!("expected a one pass signed message");
+        };
+        assert!(signature.config.hashed_subpackets().any(|sp| sp.data
+            == SubpacketData::IssuerFingerprint(
+                KeyVersion::V4,
+                SmallVec::from_slice(&skey.fingerprint())
+            )));
+        assert!(signature.issuer_fingerprint_mismatches().is_empty());
+
+        // only identified by the fingerprint
+        let mut signature = signature;
+        signature.config.unhashed_subpackets.clear();
+        check_one_pass_signature(&ops, &signature).unwrap();
+
+        // a V5 version octet in front of a V4 fingerprint
+        let mut mismatched = signature.clone();
+        for sp in mismatched.config.hashed_subpackets_mut() {
+            if let SubpacketData::IssuerFingerprint(version, _) = &mut sp.data {
+                *version = KeyVersion::V5;
+            }
+        }
+        assert_eq!(
+            mismatched.issuer_fingerprint_mismatches(),
+            vec![crate::packet::IssuerFingerprintMismatch {
+                version: KeyVersion::V5,
+                fingerprint: skey.fingerprint(),
+            }]
+        );
+        // no key id is derived from it
+        assert!(matches!(
+            check_one_pass_signature(&ops, &mismatched),
+            Err(Error::OnePassSignatureMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_one_pass_signature_missing_signature() {
+        let (skey, _headers) = SignedSecretKey::from_armor_single(
+            fs::File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+        )
+        .unwrap();
+
+        let signed_msg = Message::new_literal("hello.txt", "hello world\n")
+            .sign(&skey, || "".into(), HashAlgorithm::SHA2_256)
+            .unwrap();
+        let Message::Signed {
+            one_pass_signature: Some(ops),
+            message: Some(message),
+            ..
+        } = signed_msg
+        else {
+            panic!("expected a one pass signed message");
+        };
+
+        let mut bytes = Vec::new();
+        write_packet(&mut bytes, &ops).unwrap();
+        message.to_writer(&mut bytes).unwrap();
+
+        let err = Message::from_bytes(&bytes[..]).unwrap_err();
+        assert!(matches!(err, Error::MissingSignature), "{err:?}");
+    }
+
+    #[test]
+    fn test_prefixed_signature_roundtrip() {
+        let (skey, _headers) = SignedSecretKey::from_armor_single(
+            fs::File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+        )
+        .unwrap();
+
+        let signed_msg = Message::new_literal("hello.txt", "hello world\n")
+            .sign(&skey, || "".into(), HashAlgorithm::SHA2_256)
+            .unwrap();
+        let Message::Signed {
+            message: Some(message),
+            signature,
+            ..
+        } = signed_msg
+        else {
+            panic!("expected a signed message");
+        };
+
+        // a signature without a one pass signature comes before the message
+        let prefixed = Message::Signed {
+            message: Some(message.clone()),
+            one_pass_signature: None,
+            signature: signature.clone(),
+        };
+        let bytes = prefixed.to_bytes().unwrap();
+
+        let mut expected = Vec::new();
+        write_packet(&mut expected, &signature).unwrap();
+        message.to_writer(&mut expected).unwrap();
+        assert_eq!(bytes, expected);
+
+        let parsed = Message::from_bytes(&bytes[..]).unwrap();
+        assert_eq!(parsed, prefixed);
+        parsed.verify(&skey.public_key()).unwrap();
+    }
+
+    #[test]
+    fn test_from_armor_multipart() {
+        struct Part<'a>(&'a [u8]);
+
+        impl Serialize for Part<'_> {
+            fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<()> {
+                writer.write_all(self.0)?;
+                Ok(())
+            }
+        }
+
+        let armor_parts = |bytes: &[u8], total: usize| {
+            bytes
+                .chunks(bytes.len().div_ceil(total))
+                .enumerate()
+                .map(|(i, chunk)| {
+                    let mut buf = Vec::new();
+                    armor::write(
+                        &Part(chunk),
+                        armor::BlockType::MultiPartMessage(i + 1, total),
+                        &mut buf,
+                        None,
+                        true,
+                    )
+                    .unwrap();
+                    buf
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let lit_msg = Message::new_literal("hello.txt", &"hello world\n".repeat(100));
+        let bytes = lit_msg.to_bytes().unwrap();
+        let parts = armor_parts(&bytes, 3);
+        assert_eq!(parts.len(), 3);
+        assert!(parts[1].starts_with(b"-----BEGIN PGP MESSAGE, PART 2/3-----"));
+
+        let (msg, _headers) = Message::from_armor_multipart(parts.iter().map(|p| &p[..])).unwrap();
+        assert_eq!(msg, lit_msg);
+
+        // out of order
+        let reordered = [&parts[0][..], &parts[2][..], &parts[1][..]];
+        assert!(Message::from_armor_multipart(reordered).is_err());
+
+        // missing part
+        assert!(Message::from_armor_multipart(parts[..2].iter().map(|p| &p[..])).is_err());
+
+        // mismatching totals
+        let other = armor_parts(&bytes, 4);
+        let mixed = [&parts[0][..], &other[1][..], &parts[2][..]];
+        assert!(Message::from_armor_multipart(mixed).is_err());
+
+        // not a multipart message
+        let armored = lit_msg.to_armored_bytes(None.into()).unwrap();
+        assert!(Message::from_armor_multipart([&armored[..]]).is_err());
+    }
+
+    #[test]
+    fn test_to_armored_string_with() {
+        let lit_msg = Message::new_literal("hello.txt", "hello world\n");
+
+        let mut headers = armor::Headers::new();
+        headers.insert("Comment".to_string(), vec!["first".into(), "second".into()]);
+        headers.insert("X-Custom".to_string(), vec!["value".into()]);
+
+        for emit_crc in [true, false] {
+            let armored = lit_msg
+                .to_armored_string(ArmorOptions {
+                    headers: Some(&headers),
+                    include_checksum: emit_crc,
+                })
+                .unwrap();
+            assert!(armored.contains("\nComment: first\nComment: second\nX-Custom: value\n\n"));
+            assert_eq!(armored.lines().any(|l| l.starts_with('=')), emit_crc);
+            assert_eq!(
+                lit_msg.to_armored_string_with(&headers, emit_crc).unwrap(),
+                armored
+            );
+
+            let (msg, parsed_headers) = Message::from_string(&armored).unwrap();
+            assert_eq!(msg, lit_msg);
+            assert_eq!(parsed_headers, headers);
+        }
+
+        // no headers at all
+        let armored = lit_msg
+            .to_armored_string(ArmorOptions {
+                headers: Some(&armor::Headers::new()),
+                include_checksum: false,
+            })
+            .unwrap();
+        assert!(armored.starts_with("-----BEGIN PGP MESSAGE-----\n\n"));
+
+        // headers that would break the armor framing
+        for (key, value) in [
+            ("", "value"),
+            ("Bad: Key", "value"),
+            ("Comment", "two\nlines"),
+        ] {
+            let mut headers = armor::Headers::new();
+            headers.insert(key.to_string(), vec![value.to_string()]);
+            assert!(lit_msg.to_armored_string(Some(&headers).into()).is_err());
+        }
+    }
 }
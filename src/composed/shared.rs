@@ -5,7 +5,8 @@ use crate::armor::{self, BlockType};
 use crate::errors::{Error, Result};
 use crate::packet::{Packet, PacketParser};
 
-pub trait Deserializable: Sized {
+/// Implemented by the compositions of this crate, it can not be implemented elsewhere.
+pub trait Deserializable: Sized + crate::private::Sealed {
     /// Parse a single byte encoded composition.
     fn from_bytes(bytes: impl Read) -> Result<Self> {
         let mut el = Self::from_bytes_many(bytes);
@@ -162,6 +163,7 @@ pub trait Deserializable: Sized {
 /// - Skip any `Error::Unsupported`, those were marked as "safe to ignore" by the low level parser.
 /// - Skip `Error::Incomplete`
 /// - Skip `Error::EllipticCurve`
+/// - Pass through `Error::PacketTooLarge`, for packets that were skipped by the low level parser.
 /// - Pass through other errors.
 pub(crate) fn filter_parsed_packet_results(p: Result<Packet>) -> Option<Result<Packet>> {
     match &p {
@@ -188,6 +190,9 @@ pub(crate) fn filter_parsed_packet_results(p: Result<Packet>) -> Option<Result<P
                     return None;
                 }
             }
+            if let Error::PacketTooLarge { .. } = e {
+                return Some(p);
+            }
             if let Error::PacketIncomplete = e {
                 // We ignore incomplete packets for now (some of these occur in the SKS dumps under `tests`)
                 warn!("skipping incomplete packet: {p:?}");
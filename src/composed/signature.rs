@@ -1,11 +1,14 @@
 use std::iter::Peekable;
 
+use chrono::SubsecRound;
+
 use crate::composed::Deserializable;
+use crate::crypto::hash::HashAlgorithm;
 use crate::errors::Result;
-use crate::packet::{Packet, Signature};
+use crate::packet::{Packet, Signature, SignatureConfig, SignatureType, Subpacket, SubpacketData};
 use crate::ser::Serialize;
-use crate::types::PublicKeyTrait;
 use crate::types::Tag;
+use crate::types::{KeyTrait, PublicKeyTrait, SecretKeyTrait};
 use crate::{armor, ArmorOptions};
 
 /// Standalone signature as defined by the cleartext framework.
@@ -34,22 +37,68 @@ impl StandaloneSignature {
     }
 
     pub fn to_armored_bytes(&self, opts: ArmorOptions<'_>) -> Result<Vec<u8>> {
-        let mut buf = Vec::new();
-
-        self.to_armored_writer(&mut buf, opts)?;
-
-        Ok(buf)
+        armor::to_armored_bytes(self, armor::BlockType::Signature, opts)
     }
 
     pub fn to_armored_string(&self, opts: ArmorOptions<'_>) -> Result<String> {
-        let res = String::from_utf8(self.to_armored_bytes(opts)?).map_err(|e| e.utf8_error())?;
-        Ok(res)
+        armor::to_armored_string(self, armor::BlockType::Signature, opts)
     }
 
     /// Verify this signature.
     pub fn verify(&self, key: &impl PublicKeyTrait, content: &[u8]) -> Result<()> {
         self.signature.verify(key, content)
     }
+
+    /// Verify this signature, reporting a trailing newline mismatch on failure.
+    ///
+    /// See [`Signature::verify_with_hints`].
+    pub fn verify_with_hints(&self, key: &impl PublicKeyTrait, content: &[u8]) -> Result<()> {
+        self.signature.verify_with_hints(key, content)
+    }
+
+    /// Creates a v4 binary signature over `data`, with the default set of subpackets:
+    /// creation time and issuer fingerprint (hashed), as well as the issuer key id (unhashed).
+    pub(crate) fn sign_binary<F>(
+        key: &impl SecretKeyTrait,
+        key_pw: F,
+        hash_algorithm: HashAlgorithm,
+        data: &[u8],
+    ) -> Result<Self>
+    where
+        F: FnOnce() -> String,
+    {
+        let config = default_signature_config(SignatureType::Binary, key, hash_algorithm);
+        let signature = config.sign(key, key_pw, data)?;
+
+        Ok(StandaloneSignature::new(signature))
+    }
+}
+
+/// Creates the configuration of a v4 signature by `key`, with the default set of subpackets:
+/// issuer fingerprint and creation time (hashed), as well as the issuer key id (unhashed).
+///
+/// Shared by all signatures over data, so they carry the same subpackets.
+pub(crate) fn default_signature_config(
+    typ: SignatureType,
+    key: &(impl KeyTrait + ?Sized),
+    hash_algorithm: HashAlgorithm,
+) -> SignatureConfig {
+    let hashed_subpackets = vec![
+        Subpacket::regular(SubpacketData::issuer_fingerprint(key)),
+        Subpacket::regular(SubpacketData::SignatureCreationTime(
+            chrono::Utc::now().trunc_subsecs(0),
+        )),
+    ];
+    let unhashed_subpackets = vec![Subpacket::regular(SubpacketData::Issuer(key.key_id()))];
+
+    SignatureConfig::new_v4(
+        Default::default(),
+        typ,
+        key.algorithm(),
+        hash_algorithm,
+        hashed_subpackets,
+        unhashed_subpackets,
+    )
 }
 
 impl Serialize for StandaloneSignature {
@@ -58,6 +107,8 @@ impl Serialize for StandaloneSignature {
     }
 }
 
+impl crate::private::Sealed for StandaloneSignature {}
+
 impl Deserializable for StandaloneSignature {
     /// Parse a signature.
     fn from_packets<'a, I: Iterator<Item = Result<Packet>> + 'a>(
@@ -7,6 +7,7 @@ macro_rules! key_parser {
         /// Ref: https://tools.ietf.org/html/rfc4880.html#section-11.1
         pub struct $key_type_parser<I: Sized + Iterator<Item = $crate::errors::Result<$crate::packet::Packet>>> {
             inner: std::iter::Peekable<I>,
+            limits: $crate::composed::signed_key::KeyringLimits,
         }
 
         impl<I: Sized + Iterator<Item = $crate::errors::Result<$crate::packet::Packet>>> $key_type_parser<I> {
@@ -16,9 +17,18 @@ macro_rules! key_parser {
 
             pub fn from_packets (
                 packets: std::iter::Peekable<I>,
+            ) -> Self {
+                Self::with_limits(packets, Default::default())
+            }
+
+            /// Parses the packets, applying the component size limits of `limits`.
+            pub fn with_limits (
+                packets: std::iter::Peekable<I>,
+                limits: $crate::composed::signed_key::KeyringLimits,
             ) -> Self {
                 $key_type_parser {
                     inner: packets,
+                    limits,
                 }
             }
         }
@@ -59,97 +69,206 @@ macro_rules! key_parser {
                 let primary_key: $inner_key_type = err_opt!(next.try_into());
                 debug!("primary key: {:?}", primary_key.key_id());
 
-                // -- Zero or more revocation signatures
-                // -- followed by zero or more direct signatures in V4 keys
-                debug!("  signatures");
-                let mut revocation_signatures = Vec::new();
-                let mut direct_signatures = Vec::new();
-
-               while let Some(packet) = packets.next_if(|p| {
-                    p.as_ref().is_ok_and(|p| p.tag() == Tag::Signature)
-                }) {
-                    match packet {
-                        Ok(packet) => {
-                            debug!("parsing signature {:?}", packet.tag());
-                            let sig: Signature = err_opt!(packet.try_into());
-                            let typ = sig.typ();
-
-                            if typ == SignatureType::KeyRevocation {
-                                revocation_signatures.push(sig);
-                            } else {
-                                if primary_key.version() != KeyVersion::V4 {
-                                    // no direct signatures on V2|V3 keys
-                                    warn!("unexpected signature: {:?}", typ);
-                                }
-                                direct_signatures.push(sig);
-                            }
-                        },
-                        Err(e) => return Some(Err(e)),
-                    }
+                // -- Zero or more revocation and direct signatures,
+                // -- followed by zero or more User ID, User Attribute and Subkey packets,
+                // -- each followed by their signatures.
+                //
+                // Keyservers and other implementations do not always keep this order, so the
+                // components are accepted in any order, and each signature is associated with
+                // the component it follows, if its type fits that component.
+                #[allow(non_camel_case_types)]
+                #[derive(Clone, Copy)]
+                enum Component {
+                    Primary,
+                    // an oversized User ID or User Attribute, that is dropped
+                    Skipped,
+                    User(usize),
+                    UserAttribute(usize),
+                    $( $subkey_tag(usize), )*
                 }
 
-                // -- Zero or more User ID packets
-                // -- Zero or more User Attribute packets
-                debug!("  user");
-                let mut users = Vec::new();
-                let mut user_attributes = Vec::new();
-
-                while let Some(packet) = packets.next_if(|p| {
-                        p.as_ref().is_ok_and(|p| {
-                            debug!("peek {:?}", p.tag());
-                            p.tag() == Tag::UserId || p.tag() == Tag::UserAttribute
-                        })
-                    }) {
-                    let packet = match packet {
+                let mut primary_signatures: Vec<Signature> = Vec::new();
+                let mut users: Vec<(UserId, Vec<Signature>)> = Vec::new();
+                let mut user_attributes: Vec<(UserAttribute, Vec<Signature>)> = Vec::new();
+                $(
+                    let mut $subkey_container: Vec<($inner_subkey_type, Vec<Signature>)> = Vec::new();
+                )*
+                let mut current = Component::Primary;
+                // the last User ID or User Attribute and the last subkey seen, for signatures
+                // that do not fit the component they follow
+                let mut last_user = None;
+                let mut last_subkey = None;
+                let mut misplaced = Vec::new();
+
+                let is_primary_sig = |typ| matches!(typ, SignatureType::Key | SignatureType::KeyRevocation);
+                let is_subkey_sig = |typ| matches!(typ, SignatureType::SubkeyBinding | SignatureType::SubkeyRevocation);
+                let is_cert = |typ| matches!(
+                    typ,
+                    SignatureType::CertGeneric
+                        | SignatureType::CertPersona
+                        | SignatureType::CertCasual
+                        | SignatureType::CertPositive
+                        | SignatureType::CertRevocation
+                );
+
+                while let Some(res) = packets.next_if(|p| match p {
+                    Ok(p) => {
+                        debug!("  peek {:?}", p.tag());
+                        matches!(p.tag(), Tag::Signature | Tag::Trust | Tag::UserId | Tag::UserAttribute)
+                            $( || p.tag() == Tag::$subkey_tag )*
+                    }
+                    Err(err) => matches!(
+                        err,
+                        $crate::errors::Error::PacketTooLarge { tag: Tag::UserId | Tag::UserAttribute, .. }
+                    ),
+                }) {
+                    let packet = match res {
                         Ok(packet) => packet,
-                        Err(err) => return Some(Err(err)),
+                        // skipped by the packet parser, before its body was read into memory
+                        Err($crate::errors::Error::PacketTooLarge { tag, size, .. }) => {
+                            err_opt!(self.limits.check_component(tag, size));
+                            current = Component::Skipped;
+                            continue;
+                        }
+                        Err(e) => return Some(Err(e)),
                     };
 
-                    let tag = packet.tag();
-                    debug!("  user data: {:?}", tag);
-                    match tag {
+                    match packet.tag() {
+                        Tag::Signature => {
+                            let sig: Signature = err_opt!(packet.try_into());
+                            let typ = sig.typ();
+                            let sigs = match current {
+                                Component::Skipped => continue,
+                                Component::Primary if !is_subkey_sig(typ) && !is_cert(typ) => {
+                                    &mut primary_signatures
+                                }
+                                Component::User(i) if !is_subkey_sig(typ) && !is_primary_sig(typ) => {
+                                    &mut users[i].1
+                                }
+                                Component::UserAttribute(i) if !is_subkey_sig(typ) && !is_primary_sig(typ) => {
+                                    &mut user_attributes[i].1
+                                }
+                                $(
+                                    Component::$subkey_tag(i) if !is_cert(typ) && !is_primary_sig(typ) => {
+                                        &mut $subkey_container[i].1
+                                    }
+                                )*
+                                _ => {
+                                    misplaced.push((sig, last_user, last_subkey));
+                                    continue;
+                                }
+                            };
+                            sigs.push(sig);
+                        }
+                        Tag::Trust => {
+                            debug!("ignoring trust packet");
+                        }
                         Tag::UserId => {
                             let id: UserId = err_opt!(packet.try_into());
-
-                            // --- zero or more signature packets
-
-                            let mut sigs = Vec::new();
-
-                            while let Some(res) = packets.next_if(|p| {
-                                p.as_ref().is_ok_and(|p| p.tag() == Tag::Signature)
-                            }) {
-                                let packet = match res {
-                                    Ok(packet) => packet,
-                                    Err(e) => return Some(Err(e)),
-                                };
-                                let sig: Signature = err_opt!(packet.try_into());
-
-                                sigs.push(sig);
+                            if !err_opt!(self.limits.check_component(Tag::UserId, id.packet_len())) {
+                                current = Component::Skipped;
+                                continue;
                             }
-
-                            users.push(SignedUser::new(id, sigs));
+                            users.push((id, Vec::new()));
+                            current = Component::User(users.len() - 1);
+                            last_user = Some(current);
                         }
                         Tag::UserAttribute => {
                             let attr: UserAttribute = err_opt!(packet.try_into());
+                            if !err_opt!(self.limits.check_component(Tag::UserAttribute, attr.packet_len())) {
+                                current = Component::Skipped;
+                                continue;
+                            }
+                            user_attributes.push((attr, Vec::new()));
+                            current = Component::UserAttribute(user_attributes.len() - 1);
+                            last_user = Some(current);
+                        }
+                        $(
+                            Tag::$subkey_tag => {
+                                // -- Only V4 keys should have sub keys
+                                if primary_key.version() != KeyVersion::V4 {
+                                    return Some(Err(format_err!("only V4 keys can have subkeys")));
+                                }
 
-                            // --- zero or more signature packets
+                                let subkey: $inner_subkey_type = err_opt!(packet.try_into());
+                                $subkey_container.push((subkey, Vec::new()));
+                                current = Component::$subkey_tag($subkey_container.len() - 1);
+                                last_subkey = Some(current);
+                            }
+                        )*
+                        _ => unreachable!(),
+                    }
+                }
 
-                            let mut sigs = Vec::new();
-                            while let Some(res) = packets.next_if(|p| {
-                                p.as_ref().is_ok_and(|p| p.tag() == Tag::Signature)
-                            }) {
-                                let packet = match res {
-                                    Ok(packet) => packet,
-                                    Err(e) => return Some(Err(e)),
-                                };
-                                let sig: Signature = err_opt!(packet.try_into());
+                // -- Do not return a truncated key, when a limit was hit in the middle of it.
+                if packets
+                    .next_if(|p| matches!(p, Err($crate::errors::Error::TooManyPackets)))
+                    .is_some()
+                {
+                    return Some(Err($crate::errors::Error::TooManyPackets));
+                }
 
-                                sigs.push(sig);
-                            }
+                // -- Move signatures that can not belong to the component they follow to a
+                // -- component of the right kind. This is done without verifying them, as that
+                // -- would cost a verification per signature and component on untrusted input.
+                // -- Subkey signatures go to the subkey that issued their backsig, if any,
+                // -- otherwise signatures go to the last component of their kind preceding them,
+                // -- or the first one.
+                for (sig, last_user, last_subkey) in misplaced {
+                    let typ = sig.typ();
+                    debug!("relocating {:?} signature", typ);
+
+                    let target = if is_primary_sig(typ) {
+                        Some(Component::Primary)
+                    } else if is_subkey_sig(typ) {
+                        let backsig_issuer = sig.embedded_signature().and_then(|backsig| {
+                            let issued = |key: &dyn KeyTrait| {
+                                backsig.issuer().contains(&&key.key_id())
+                                    || backsig.issuer_fingerprint().contains(&&key.fingerprint()[..])
+                            };
+                            None
+                            $(
+                                .or_else(|| {
+                                    $subkey_container
+                                        .iter()
+                                        .position(|(subkey, _)| issued(subkey))
+                                        .map(Component::$subkey_tag)
+                                })
+                            )*
+                        });
+                        backsig_issuer
+                            .or(last_subkey)
+                            $(
+                                .or_else(|| {
+                                    (!$subkey_container.is_empty()).then_some(Component::$subkey_tag(0))
+                                })
+                            )*
+                    } else {
+                        last_user
+                            .or_else(|| (!users.is_empty()).then_some(Component::User(0)))
+                            .or_else(|| {
+                                (!user_attributes.is_empty()).then_some(Component::UserAttribute(0))
+                            })
+                    };
 
-                            user_attributes.push(SignedUserAttribute::new(attr, sigs));
+                    match target {
+                        Some(Component::Primary) => primary_signatures.push(sig),
+                        Some(Component::User(i)) => users[i].1.push(sig),
+                        Some(Component::UserAttribute(i)) => user_attributes[i].1.push(sig),
+                        $( Some(Component::$subkey_tag(i)) => $subkey_container[i].1.push(sig), )*
+                        Some(Component::Skipped) | None => {
+                            warn!("ignoring {:?} signature, the key has no component it can belong to", typ);
                         }
-                        _ => break,
+                    }
+                }
+
+                let (revocation_signatures, direct_signatures): (Vec<_>, Vec<_>) = primary_signatures
+                    .into_iter()
+                    .partition(|sig| sig.typ() == SignatureType::KeyRevocation);
+                if primary_key.version() != KeyVersion::V4 {
+                    for sig in &direct_signatures {
+                        // no direct signatures on V2|V3 keys
+                        warn!("unexpected signature: {:?}", sig.typ());
                     }
                 }
 
@@ -157,53 +276,21 @@ macro_rules! key_parser {
                     warn!("missing user ids");
                 }
 
-                // -- Zero or more Subkey packets
+                let users = users
+                    .into_iter()
+                    .map(|(id, sigs)| SignedUser::new(id, sigs))
+                    .collect();
+                let user_attributes = user_attributes
+                    .into_iter()
+                    .map(|(attr, sigs)| SignedUserAttribute::new(attr, sigs))
+                    .collect();
                 $(
-                    let mut $subkey_container = vec![];
+                    let $subkey_container = $subkey_container
+                        .into_iter()
+                        .map(|(subkey, sigs)| <$subkey_type>::new(subkey, sigs))
+                        .collect();
                 )*
 
-                debug!("  subkeys");
-
-                while let Some(res) = packets.next_if(|p|
-                    p.as_ref().is_ok_and(|p| {
-                        debug!("  peek {:?}", p.tag());
-                        $( p.tag() == Tag::$subkey_tag || )* false
-                    }
-                )) {
-                    // -- Only V4 keys should have sub keys
-                    if primary_key.version() != KeyVersion::V4 {
-                        return Some(Err(format_err!("only V4 keys can have subkeys")));
-                    }
-
-                    let packet = match res {
-                        Ok(packet) => packet,
-                        Err(e) => return Some(Err(e)),
-                    };
-
-                    match packet.tag() {
-                        $(
-                            Tag::$subkey_tag => {
-                                let subkey: $inner_subkey_type = err_opt!(packet.try_into());
-                                let mut sigs = Vec::new();
-                                while let Some(res) = packets.next_if(|packet| {
-                                    packet.is_ok() && packet.as_ref().unwrap().tag() == Tag::Signature
-                                }) {
-                                    match res {
-                                        Ok(packet) => {
-                                            let sig: Signature = err_opt!(packet.try_into());
-                                            sigs.push(sig);
-                                        }
-                                        Err(e) => return Some(Err(e)),
-                                    }
-                                }
-
-                                $subkey_container.push(<$subkey_type>::new(subkey, sigs));
-                            }
-                        )*
-                            _ => unreachable!()
-                    }
-                }
-
                 Some(Ok(<$key_type>::new(
                     primary_key,
                     $crate::composed::signed_key::SignedKeyDetails::new(
@@ -217,7 +304,21 @@ macro_rules! key_parser {
             }
         }
 
+        impl $crate::private::Sealed for $key_type {}
+
         impl $crate::composed::Deserializable for $key_type {
+            /// Parse transferable keys from raw bytes, skipping oversized User IDs and User
+            /// Attributes without reading them into memory.
+            fn from_bytes_many<'a>(
+                bytes: impl std::io::Read + 'a,
+            ) -> Box<dyn Iterator<Item = $crate::errors::Result<Self>> + 'a> {
+                let packets = $crate::composed::signed_key::KeyringLimits::default()
+                    .packet_parser(bytes)
+                    .filter_map($crate::composed::shared::filter_parsed_packet_results);
+
+                Self::from_packets(packets.peekable())
+            }
+
             /// Parse a transferable key from packets.
             /// Ref: https://tools.ietf.org/html/rfc4880.html#section-11.1
             fn from_packets<'a, I: Iterator<Item = $crate::errors::Result<$crate::packet::Packet>> + 'a> (
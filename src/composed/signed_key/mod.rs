@@ -24,7 +24,7 @@
 //! #
 //! # let mut key_params = SecretKeyParamsBuilder::default();
 //! # key_params
-//! # .key_type(KeyType::Rsa(2048))
+//! # .key_type(KeyType::EdDSA)
 //! # .can_certify(false)
 //! # .can_sign(true)
 //! # .primary_user_id("Me <me@example.com>".into())
@@ -108,10 +108,14 @@ mod key_parser_macros;
 
 mod parse;
 mod public;
+mod ring;
 mod secret;
 mod shared;
+mod store;
 
 pub use self::parse::*;
 pub use self::public::*;
+pub use self::ring::*;
 pub use self::secret::*;
 pub use self::shared::*;
+pub use self::store::*;
@@ -1,3 +1,5 @@
+use std::cell::Cell;
+use std::rc::Rc;
 use std::{io, iter};
 
 use buffer_redux::BufReader;
@@ -10,6 +12,93 @@ use crate::errors::{Error, Result};
 use crate::packet::{Packet, PacketParser};
 use crate::types::Tag;
 
+/// Limits on the amount of data accepted when parsing a keyring.
+///
+/// Protects services that parse untrusted keyrings against excessive memory and CPU usage, for
+/// example from millions of tiny signature packets. Exceeding a limit ends parsing with
+/// [`Error::TooManyPackets`].
+///
+/// User IDs and User Attributes larger than their limit are dropped, together with their
+/// signatures, and the rest of the key is kept. With `strict` set, such a component fails the
+/// whole key instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyringLimits {
+    /// The maximum number of packets.
+    pub max_packets: usize,
+    /// The maximum cumulative size of the packets, in bytes.
+    pub max_size: u64,
+    /// The maximum size of a User ID packet body, in bytes.
+    pub max_user_id_size: usize,
+    /// The maximum size of a User Attribute packet body, in bytes.
+    pub max_user_attribute_size: usize,
+    /// Reject keys with oversized components, instead of dropping the components.
+    pub strict: bool,
+}
+
+impl KeyringLimits {
+    pub fn new(max_packets: usize, max_size: u64) -> Self {
+        KeyringLimits {
+            max_packets,
+            max_size,
+            ..Default::default()
+        }
+    }
+
+    /// Creates a packet parser that skips User IDs and User Attributes larger than the limits,
+    /// without reading them into memory.
+    pub(crate) fn packet_parser<R: io::Read>(&self, input: R) -> PacketParser<R> {
+        PacketParser::new(input)
+            .with_tag_limit(Tag::UserId, self.max_user_id_size)
+            .with_tag_limit(Tag::UserAttribute, self.max_user_attribute_size)
+    }
+
+    /// Checks the size of a User ID or User Attribute, given its tag and packet body size.
+    ///
+    /// Oversized packets are normally skipped by [`Self::packet_parser`], this also covers
+    /// packets that were parsed elsewhere.
+    ///
+    /// Returns `Ok(false)` if the component should be dropped.
+    pub(crate) fn check_component(&self, tag: Tag, size: usize) -> Result<bool> {
+        let max = match tag {
+            Tag::UserId => self.max_user_id_size,
+            Tag::UserAttribute => self.max_user_attribute_size,
+            _ => return Ok(true),
+        };
+        if size <= max {
+            return Ok(true);
+        }
+
+        ensure!(
+            !self.strict,
+            "{:?} packet too large: {} bytes, limit is {}",
+            tag,
+            size,
+            max
+        );
+        warn!(
+            "dropping {:?} packet: {} bytes, limit is {}",
+            tag, size, max
+        );
+
+        Ok(false)
+    }
+}
+
+impl Default for KeyringLimits {
+    /// Generous limits, that are not reached by real world keyrings: one million packets and 1 GiB.
+    ///
+    /// User IDs are limited to 2 KiB and User Attributes to 16 MiB, oversized ones are dropped.
+    fn default() -> Self {
+        KeyringLimits {
+            max_packets: 1_000_000,
+            max_size: 1024 * 1024 * 1024,
+            max_user_id_size: 2 * 1024,
+            max_user_attribute_size: 16 * 1024 * 1024,
+            strict: false,
+        }
+    }
+}
+
 /// Parses a list of secret and public keys, from either ASCII-armored or binary OpenPGP data.
 ///
 /// Returns an iterator of public or secret keys and a BTreeMap containing armor headers
@@ -26,16 +115,29 @@ pub fn from_reader_many<'a, R: io::Read + 'a>(
 
 #[allow(clippy::type_complexity)]
 pub fn from_reader_many_buf<'a, R: io::BufRead + 'a>(
+    input: R,
+) -> Result<(
+    Box<dyn Iterator<Item = Result<PublicOrSecret>> + 'a>,
+    Option<armor::Headers>,
+)> {
+    from_reader_many_with_limits(input, KeyringLimits::default())
+}
+
+/// Parses a list of secret and public keys, from either ASCII-armored or binary OpenPGP data,
+/// enforcing the given limits.
+#[allow(clippy::type_complexity)]
+pub fn from_reader_many_with_limits<'a, R: io::BufRead + 'a>(
     mut input: R,
+    limits: KeyringLimits,
 ) -> Result<(
     Box<dyn Iterator<Item = Result<PublicOrSecret>> + 'a>,
     Option<armor::Headers>,
 )> {
     if !crate::composed::shared::is_binary(&mut input)? {
-        let (keys, headers) = from_armor_many_buf(input)?;
+        let (keys, headers) = from_armor_many_with_limits(input, limits)?;
         Ok((keys, Some(headers)))
     } else {
-        Ok((from_bytes_many(input), None))
+        Ok((from_bytes_many_with_limits(input, limits), None))
     }
 }
 
@@ -57,6 +159,17 @@ pub fn from_armor_many_buf<'a, R: io::BufRead + 'a>(
 ) -> Result<(
     Box<dyn Iterator<Item = Result<PublicOrSecret>> + 'a>,
     armor::Headers,
+)> {
+    from_armor_many_with_limits(input, KeyringLimits::default())
+}
+
+#[allow(clippy::type_complexity)]
+fn from_armor_many_with_limits<'a, R: io::BufRead + 'a>(
+    input: R,
+    limits: KeyringLimits,
+) -> Result<(
+    Box<dyn Iterator<Item = Result<PublicOrSecret>> + 'a>,
+    armor::Headers,
 )> {
     let mut dearmor = armor::Dearmor::new(input);
     dearmor.read_header()?;
@@ -71,7 +184,7 @@ pub fn from_armor_many_buf<'a, R: io::BufRead + 'a>(
         BlockType::PublicKey | BlockType::PrivateKey | BlockType::File => {
             let headers = dearmor.headers.clone(); // FIXME: avoid clone
                                                    // TODO: check that the result is what it actually said.
-            Ok((from_bytes_many(dearmor), headers))
+            Ok((from_bytes_many_with_limits(dearmor, limits), headers))
         }
         BlockType::Message
         | BlockType::MultiPartMessage(_, _)
@@ -94,17 +207,96 @@ pub fn from_armor_many_buf<'a, R: io::BufRead + 'a>(
 pub fn from_bytes_many<'a>(
     bytes: impl io::Read + 'a,
 ) -> Box<dyn Iterator<Item = Result<PublicOrSecret>> + 'a> {
-    let packets = PacketParser::new(bytes)
-        .filter_map(crate::composed::shared::filter_parsed_packet_results)
-        .peekable();
+    from_bytes_many_with_limits(bytes, KeyringLimits::default())
+}
+
+/// Parses a list of secret and public keys from raw bytes, enforcing the given limits.
+pub fn from_bytes_many_with_limits<'a>(
+    bytes: impl io::Read + 'a,
+    limits: KeyringLimits,
+) -> Box<dyn Iterator<Item = Result<PublicOrSecret>> + 'a> {
+    let exceeded = Rc::new(Cell::new(false));
+    let reader = SizeLimitedReader {
+        inner: bytes,
+        remaining: limits.max_size,
+        exceeded: exceeded.clone(),
+    };
+    let packets = limits
+        .packet_parser(reader)
+        .filter_map(crate::composed::shared::filter_parsed_packet_results);
+    let packets = LimitedPackets {
+        inner: packets,
+        remaining: limits.max_packets,
+        exceeded,
+        done: false,
+    }
+    .peekable();
 
     Box::new(PubPrivIterator {
         inner: Some(packets),
+        limits,
     })
 }
 
+/// Ends the input once more than `remaining` bytes are read, and flags this in `exceeded`.
+struct SizeLimitedReader<R> {
+    inner: R,
+    remaining: u64,
+    exceeded: Rc<Cell<bool>>,
+}
+
+impl<R: io::Read> io::Read for SizeLimitedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.exceeded.get() {
+            return Ok(0);
+        }
+
+        // read one byte more than allowed, to detect if the limit is exceeded
+        let max = usize::try_from(self.remaining.saturating_add(1))
+            .unwrap_or(usize::MAX)
+            .min(buf.len());
+        let read = self.inner.read(&mut buf[..max])?;
+        if read as u64 > self.remaining {
+            self.exceeded.set(true);
+            return Ok(0);
+        }
+        self.remaining -= read as u64;
+
+        Ok(read)
+    }
+}
+
+/// Ends the packets with [`Error::TooManyPackets`], once a limit is exceeded.
+struct LimitedPackets<I> {
+    inner: I,
+    remaining: usize,
+    exceeded: Rc<Cell<bool>>,
+    done: bool,
+}
+
+impl<I: Iterator<Item = Result<Packet>>> Iterator for LimitedPackets<I> {
+    type Item = Result<Packet>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let packet = self.inner.next();
+        // the size limit cuts the input short, which the parser can not tell from a regular end
+        if self.exceeded.get() || (packet.is_some() && self.remaining == 0) {
+            self.done = true;
+            return Some(Err(Error::TooManyPackets));
+        }
+        self.remaining = self.remaining.saturating_sub(1);
+
+        packet
+    }
+}
+
 pub struct PubPrivIterator<I: Sized + Iterator<Item = Result<Packet>>> {
     inner: Option<iter::Peekable<I>>,
+    limits: KeyringLimits,
 }
 
 impl<I: Sized + Iterator<Item = Result<Packet>>> Iterator for PubPrivIterator<I> {
@@ -117,7 +309,8 @@ impl<I: Sized + Iterator<Item = Result<Packet>>> Iterator for PubPrivIterator<I>
                 Some(Ok(peeked_packet)) => {
                     let (res, packets) = match peeked_packet.tag() {
                         Tag::SecretKey => {
-                            let mut parser = SignedSecretKeyParser::from_packets(packets);
+                            let mut parser =
+                                SignedSecretKeyParser::with_limits(packets, self.limits);
                             let p: Option<Result<SignedSecretKey>> = parser.next();
                             (
                                 p.map(|key| key.map(PublicOrSecret::Secret)),
@@ -125,7 +318,8 @@ impl<I: Sized + Iterator<Item = Result<Packet>>> Iterator for PubPrivIterator<I>
                             )
                         }
                         Tag::PublicKey => {
-                            let mut parser = SignedPublicKeyParser::from_packets(packets);
+                            let mut parser =
+                                SignedPublicKeyParser::with_limits(packets, self.limits);
                             let p: Option<Result<SignedPublicKey>> = parser.next();
                             (
                                 p.map(|key| key.map(PublicOrSecret::Public)),
@@ -139,7 +333,7 @@ impl<I: Sized + Iterator<Item = Result<Packet>>> Iterator for PubPrivIterator<I>
 
                     res
                 }
-                Some(Err(e)) => Some(Err(Error::Message(e.to_string()))),
+                Some(Err(_)) => packets.next().and_then(|res| res.err()).map(Err),
                 None => None,
             },
         }
@@ -1,17 +1,25 @@
-use std::io;
+use std::{fmt, io};
 
+use base64::engine::{general_purpose::STANDARD as BASE64_STANDARD, Engine};
 use chrono::{DateTime, Utc};
 use rand::{CryptoRng, Rng};
 
 use crate::composed::key::{PublicKey, PublicSubkey};
-use crate::composed::signed_key::SignedKeyDetails;
+use crate::composed::signed_key::{
+    binding_expires_at, check_backsig, check_signature_version, merge_signatures, Backsig,
+    EncryptionPurpose, Revoked, SignedKeyDetails, VersionMismatch,
+};
+use crate::composed::Deserializable;
+use crate::crypto::ecc_curve::ECCCurve;
 use crate::crypto::hash::HashAlgorithm;
 use crate::crypto::public_key::PublicKeyAlgorithm;
-use crate::errors::Result;
-use crate::packet::{self, write_packet, SignatureType};
+use crate::errors::{Error, Result};
+use crate::packet::{self, write_packet_as, SignatureType};
 use crate::ser::Serialize;
-use crate::types::{KeyId, KeyTrait, Mpi, PublicKeyTrait};
-use crate::{armor, ArmorOptions};
+use crate::types::{
+    KeyId, KeyTrait, KeyVersion, Mpi, PublicKeyTrait, PublicParams, SignedUser, Version,
+};
+use crate::{armor, ArmorOptions, StandaloneSignature};
 
 /// Represents a Public PGP key, which is signed and either received or ready to be transferred.
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -49,6 +57,9 @@ impl SignedPublicKey {
                 true
             }
         });
+        for subkey in &mut public_subkeys {
+            subkey.set_primary(&primary_key);
+        }
 
         SignedPublicKey {
             primary_key,
@@ -57,9 +68,48 @@ impl SignedPublicKey {
         }
     }
 
+    /// Parses the certificate of the `Key Block` subpacket of `signature`, see
+    /// [`packet::Signature::key_block`].
+    ///
+    /// Returns `Ok(None)` if there is no key block. Fails if the key block is larger than
+    /// [`packet::MAX_KEY_BLOCK_SIZE`], does not hold exactly one valid certificate, or if the
+    /// certificate does not contain the key that issued `signature`. The signature itself is
+    /// not verified.
+    pub fn from_key_block(signature: &packet::Signature) -> Result<Option<Self>> {
+        let Some(block) = signature.key_block() else {
+            return Ok(None);
+        };
+        ensure!(
+            block.len() <= packet::MAX_KEY_BLOCK_SIZE,
+            "key block of {} bytes is too large",
+            block.len()
+        );
+
+        let mut keys = SignedPublicKey::from_bytes_many(block);
+        let key = keys
+            .next()
+            .ok_or_else(|| format_err!("empty key block"))??;
+        ensure!(keys.next().is_none(), "key block holds more than one key");
+        key.verify()?;
+
+        let issuer_fingerprints = signature.issuer_fingerprint();
+        let issuers = signature.issuer();
+        let contains_issuer = std::iter::once(&key.primary_key as &dyn KeyTrait)
+            .chain(key.public_subkeys.iter().map(|k| &k.key as &dyn KeyTrait))
+            .any(|k| {
+                issuer_fingerprints.contains(&&k.fingerprint()[..])
+                    || issuers.contains(&&k.key_id())
+            });
+        ensure!(contains_issuer, "key block does not contain the issuer");
+
+        Ok(Some(key))
+    }
+
     /// Get the public key expiration as a date.
     pub fn expires_at(&self) -> Option<DateTime<Utc>> {
-        let expiration = self.details.key_expiration_time()?;
+        let expiration = self
+            .details
+            .verified_key_expiration_time(&self.primary_key)?;
         Some(*self.primary_key.created_at() + expiration)
     }
 
@@ -71,6 +121,141 @@ impl SignedPublicKey {
         Ok(())
     }
 
+    /// Returns true if both keys have the same primary key, by fingerprint.
+    ///
+    /// The keys may still differ in their user ids, subkeys and signatures, and can be
+    /// combined with [`SignedPublicKey::merge`].
+    pub fn same_primary(&self, other: &SignedPublicKey) -> bool {
+        self.primary_key.fingerprint() == other.primary_key.fingerprint()
+    }
+
+    /// Merges the user ids, user attributes, subkeys and signatures of `other` into this key.
+    ///
+    /// Components and signatures present in both keys are kept once. The signatures are not
+    /// verified. Fails with [`Error::PrimaryKeyMismatch`] if `other` has a different primary key.
+    pub fn merge(mut self, other: SignedPublicKey) -> Result<SignedPublicKey> {
+        if !self.same_primary(&other) {
+            return Err(Error::PrimaryKeyMismatch {
+                expected: self.fingerprint(),
+                found: other.fingerprint(),
+            });
+        }
+
+        self.details.merge(other.details);
+        for subkey in other.public_subkeys {
+            match self
+                .public_subkeys
+                .iter_mut()
+                .find(|k| k.key.fingerprint() == subkey.key.fingerprint())
+            {
+                Some(existing) => merge_signatures(&mut existing.signatures, subkey.signatures),
+                None => {
+                    let mut subkey = subkey;
+                    subkey.set_primary(&self.primary_key);
+                    self.public_subkeys.push(subkey);
+                }
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// Returns a minimal copy of this key, as embedded in the `Key Block` subpacket of messages.
+    ///
+    /// The copy keeps the primary key with its revocations and newest direct key signature, the
+    /// primary user id with its newest self-signature, and the subkeys that are not revoked with
+    /// their newest binding signature. Other user ids, user attributes and third party
+    /// certifications are left out.
+    pub fn minimal(&self) -> SignedPublicKey {
+        let primary_id = self.primary_key.key_id();
+        let self_sigs = self.details.self_signatures(&self.primary_key);
+
+        let users = self_sigs
+            .primary_user
+            .map(|user| {
+                let signatures = user
+                    .signatures
+                    .iter()
+                    .filter(|sig| {
+                        let issuer = sig.issuer();
+                        sig.is_revocation() && (issuer.is_empty() || issuer.contains(&&primary_id))
+                    })
+                    .chain(self_sigs.primary_user_signature)
+                    .cloned()
+                    .collect();
+                SignedUser::new(user.id.clone(), signatures)
+            })
+            .into_iter()
+            .collect();
+        let details = SignedKeyDetails::new(
+            self.details.revocation_signatures.clone(),
+            self_sigs
+                .direct_key_signature
+                .cloned()
+                .into_iter()
+                .collect(),
+            users,
+            Vec::new(),
+        );
+        let subkeys = self
+            .public_subkeys
+            .iter()
+            .filter(|subkey| !subkey.signatures.iter().any(|sig| sig.is_revocation()))
+            .filter_map(|subkey| {
+                let binding = subkey.latest_binding_signature(&self.primary_key)?.clone();
+                Some(SignedPublicSubKey::new(subkey.key.clone(), vec![binding]))
+            })
+            .collect();
+
+        SignedPublicKey::new(self.primary_key.clone(), details, subkeys)
+    }
+
+    /// Returns the signatures by the primary key, whose version or issuer subpackets do not match
+    /// the version of the primary key.
+    ///
+    /// Such signatures are not rejected by [`Self::verify`], this is a diagnostic for keys that
+    /// were assembled by buggy tools.
+    pub fn version_mismatches(&self) -> Vec<VersionMismatch> {
+        let mut mismatches = self.details.version_mismatches(&self.primary_key);
+        let sigs = self
+            .public_subkeys
+            .iter()
+            .flat_map(|subkey| &subkey.signatures);
+        for sig in sigs {
+            check_signature_version(&self.primary_key, sig, &mut mismatches);
+        }
+
+        mismatches
+    }
+
+    /// Returns all revocation signatures of this key, together with the component they
+    /// apply to.
+    ///
+    /// The signatures are not verified.
+    pub fn revocations(&self) -> Vec<(Revoked<'_>, &packet::Signature)> {
+        self.details
+            .revocations()
+            .into_iter()
+            .chain(self.public_subkeys.iter().flat_map(|subkey| {
+                subkey
+                    .signatures
+                    .iter()
+                    .filter(|sig| sig.is_revocation())
+                    .map(|sig| (Revoked::Subkey(subkey.key_id()), sig))
+            }))
+            .collect()
+    }
+
+    /// Returns the backsig state of each signing capable subkey.
+    ///
+    /// Subkeys whose backsig is not [`Backsig::Valid`] are not used for signing.
+    pub fn signing_subkey_backsigs(&self) -> Vec<(KeyId, Backsig)> {
+        self.public_subkeys
+            .iter()
+            .filter_map(|subkey| Some((subkey.key_id(), subkey.backsig(&self.primary_key)?)))
+            .collect()
+    }
+
     pub fn verify(&self) -> Result<()> {
         self.details.verify(&self.primary_key)?;
         self.verify_public_subkeys()?;
@@ -78,6 +263,106 @@ impl SignedPublicKey {
         Ok(())
     }
 
+    /// Verifies a detached signature over `data`, as created by
+    /// [`SignedSecretKey::sign_detached`](crate::SignedSecretKey::sign_detached).
+    ///
+    /// The signature is checked against the primary key and all subkeys that are bound as
+    /// signing capable. Returns the key id of the (sub)key that made the signature.
+    pub fn verify_detached(&self, data: &[u8], signature: &StandaloneSignature) -> Result<KeyId> {
+        let mut res = signature
+            .verify(&self.primary_key, data)
+            .map(|_| self.primary_key.key_id());
+
+        for subkey in &self.public_subkeys {
+            if res.is_ok() {
+                break;
+            }
+            if subkey
+                .backsig(&self.primary_key)
+                .is_some_and(|backsig| backsig == Backsig::Valid)
+            {
+                res = signature.verify(subkey, data).map(|_| subkey.key_id());
+            }
+        }
+
+        res
+    }
+
+    /// Returns the subkeys that are bound as encryption capable for the given purpose, by their
+    /// newest valid binding signature.
+    pub fn encryption_subkeys(
+        &self,
+        purpose: EncryptionPurpose,
+    ) -> impl Iterator<Item = &SignedPublicSubKey> {
+        self.encryption_bindings(purpose).map(|(key, _)| key)
+    }
+
+    /// Returns the encryption subkeys for the given purpose, together with their newest valid
+    /// binding signature, so it is only verified once.
+    fn encryption_bindings(
+        &self,
+        purpose: EncryptionPurpose,
+    ) -> impl Iterator<Item = (&SignedPublicSubKey, &packet::Signature)> {
+        self.public_subkeys.iter().filter_map(move |key| {
+            let binding = key.latest_binding_signature(&self.primary_key)?;
+            purpose
+                .matches(binding.key_flags())
+                .then_some((key, binding))
+        })
+    }
+
+    /// Returns the subkey to encrypt to for the given purpose.
+    ///
+    /// Subkeys that are revoked or expired, by their own binding or the expiration of the
+    /// primary key, are skipped, as are subkeys of algorithms that can not be encrypted to, like the native X25519
+    /// keys of RFC 9580, which are kept but not supported yet. Of the remaining encryption
+    /// subkeys, the one with the newest binding signature is used.
+    pub fn best_encryption_key(&self, purpose: EncryptionPurpose) -> Option<&SignedPublicSubKey> {
+        let now = Utc::now();
+        if self
+            .expires_at()
+            .is_some_and(|expires_at| expires_at <= now)
+        {
+            return None;
+        }
+
+        self.encryption_bindings(purpose)
+            .filter(|(key, binding)| {
+                !binding_expires_at(key.key.created_at(), binding)
+                    .is_some_and(|expires_at| expires_at <= now)
+            })
+            .filter(|(key, _)| !key.is_revoked(&self.primary_key))
+            .filter(|(key, _)| key.key.public_params().can_encrypt())
+            .max_by_key(|(key, binding)| (binding.created().copied(), *key.key.created_at()))
+            .map(|(key, _)| key)
+    }
+
+    /// Exports the authentication subkey in the OpenSSH public key format, as used in
+    /// `authorized_keys` files.
+    ///
+    /// Like `gpg --export-ssh-key`, the first subkey bound as authentication capable is used and
+    /// the comment names its key id.
+    pub fn to_openssh(&self) -> Result<String> {
+        let subkey = self
+            .public_subkeys
+            .iter()
+            .find(|key| {
+                key.latest_binding_signature(&self.primary_key)
+                    .is_some_and(|sig| sig.key_flags().authentication())
+            })
+            .ok_or_else(|| format_err!("no authentication subkey found"))?;
+
+        let (key_type, blob) = openssh_public_key(subkey.key.public_params())?;
+        let key_id = format!("{:X}", subkey.key_id());
+
+        Ok(format!(
+            "{} {} openpgp:0x{}",
+            key_type,
+            BASE64_STANDARD.encode(blob),
+            &key_id[8..]
+        ))
+    }
+
     pub fn to_armored_writer(
         &self,
         writer: &mut impl io::Write,
@@ -93,22 +378,44 @@ impl SignedPublicKey {
     }
 
     pub fn to_armored_bytes(&self, opts: ArmorOptions<'_>) -> Result<Vec<u8>> {
-        let mut buf = Vec::new();
+        armor::to_armored_bytes(self, armor::BlockType::PublicKey, opts)
+    }
 
-        self.to_armored_writer(&mut buf, opts)?;
+    pub fn to_armored_string(&self, opts: ArmorOptions<'_>) -> Result<String> {
+        armor::to_armored_string(self, armor::BlockType::PublicKey, opts)
+    }
 
-        Ok(buf)
+    /// Serializes the key, writing all packet headers in the given format.
+    ///
+    /// This is useful to reproduce keys for tools that expect old format headers. Packets with
+    /// tags above 15 can not use the old format and are always written in the new format.
+    pub fn to_writer_with_version<W: io::Write>(
+        &self,
+        writer: &mut W,
+        packet_version: Version,
+    ) -> Result<()> {
+        self.write_packets(writer, Some(packet_version))
     }
 
-    pub fn to_armored_string(&self, opts: ArmorOptions<'_>) -> Result<String> {
-        let res = String::from_utf8(self.to_armored_bytes(opts)?).map_err(|e| e.utf8_error())?;
-        Ok(res)
+    fn write_packets<W: io::Write>(
+        &self,
+        writer: &mut W,
+        packet_version: Option<Version>,
+    ) -> Result<()> {
+        write_packet_as(writer, &self.primary_key, packet_version)?;
+        self.details
+            .to_writer_with_version(writer, packet_version)?;
+        for ps in &self.public_subkeys {
+            ps.to_writer_with_version(writer, packet_version)?;
+        }
+
+        Ok(())
     }
 
     pub fn as_unsigned(&self) -> PublicKey {
         PublicKey::new(
             self.primary_key.clone(),
-            self.details.as_unsigned(),
+            self.details.as_unsigned(&self.primary_key),
             self.public_subkeys
                 .iter()
                 .map(SignedPublicSubKey::as_unsigned)
@@ -117,6 +424,64 @@ impl SignedPublicKey {
     }
 }
 
+/// Encodes public key parameters as an SSH public key blob, see RFC 4253, Section 6.6.
+fn openssh_public_key(params: &PublicParams) -> Result<(&'static str, Vec<u8>)> {
+    fn put_string(out: &mut Vec<u8>, data: &[u8]) {
+        out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        out.extend_from_slice(data);
+    }
+
+    fn put_mpint(out: &mut Vec<u8>, mpi: &Mpi) {
+        let bytes = mpi.as_bytes();
+        if bytes.first().is_some_and(|b| b & 0x80 != 0) {
+            out.extend_from_slice(&(bytes.len() as u32 + 1).to_be_bytes());
+            out.push(0);
+            out.extend_from_slice(bytes);
+        } else {
+            put_string(out, bytes);
+        }
+    }
+
+    let mut blob = Vec::new();
+    let key_type = match params {
+        PublicParams::RSA { n, e } => {
+            put_string(&mut blob, b"ssh-rsa");
+            put_mpint(&mut blob, e);
+            put_mpint(&mut blob, n);
+            "ssh-rsa"
+        }
+        PublicParams::EdDSA {
+            curve: ECCCurve::Ed25519,
+            q,
+        } => {
+            // strip the 0x40 prefix
+            let q = q.as_bytes();
+            ensure_eq!(q.len(), 33, "invalid Ed25519 public key");
+            put_string(&mut blob, b"ssh-ed25519");
+            put_string(&mut blob, &q[1..]);
+            "ssh-ed25519"
+        }
+        #[cfg(feature = "nist")]
+        PublicParams::ECDSA(params) => {
+            use crate::types::EcdsaPublicParams;
+
+            let (key_type, curve, p) = match params {
+                EcdsaPublicParams::P256 { p, .. } => ("ecdsa-sha2-nistp256", "nistp256", p),
+                EcdsaPublicParams::P384 { p, .. } => ("ecdsa-sha2-nistp384", "nistp384", p),
+                EcdsaPublicParams::P521 { p, .. } => ("ecdsa-sha2-nistp521", "nistp521", p),
+                _ => unsupported_err!("ECDSA curve for OpenSSH: {:?}", params),
+            };
+            put_string(&mut blob, key_type.as_bytes());
+            put_string(&mut blob, curve.as_bytes());
+            put_string(&mut blob, p.as_bytes());
+            key_type
+        }
+        _ => unsupported_err!("key for OpenSSH: {:?}", params),
+    };
+
+    Ok((key_type, blob))
+}
+
 impl KeyTrait for SignedPublicKey {
     fn fingerprint(&self) -> Vec<u8> {
         self.primary_key.fingerprint()
@@ -129,6 +494,10 @@ impl KeyTrait for SignedPublicKey {
     fn algorithm(&self) -> PublicKeyAlgorithm {
         self.primary_key.algorithm()
     }
+
+    fn key_version(&self) -> KeyVersion {
+        self.primary_key.version()
+    }
 }
 
 impl PublicKeyTrait for SignedPublicKey {
@@ -147,23 +516,39 @@ impl PublicKeyTrait for SignedPublicKey {
 
 impl Serialize for SignedPublicKey {
     fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<()> {
-        write_packet(writer, &self.primary_key)?;
-        self.details.to_writer(writer)?;
-        for ps in &self.public_subkeys {
-            ps.to_writer(writer)?;
-        }
-
-        Ok(())
+        self.write_packets(writer, None)
     }
 }
 
 /// Represents a Public PGP SubKey.
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Clone)]
 pub struct SignedPublicSubKey {
     pub key: packet::PublicSubkey,
     pub signatures: Vec<packet::Signature>,
+    /// The primary key the binding signatures are verified against, set once the subkey is part
+    /// of a key.
+    primary: Option<Box<packet::PublicKey>>,
+}
+
+impl fmt::Debug for SignedPublicSubKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SignedPublicSubKey")
+            .field("key", &self.key)
+            .field("signatures", &self.signatures)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The primary key is not part of the subkey itself, only the key and its signatures are
+/// compared.
+impl PartialEq for SignedPublicSubKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key && self.signatures == other.signatures
+    }
 }
 
+impl Eq for SignedPublicSubKey {}
+
 impl SignedPublicSubKey {
     pub fn new(key: packet::PublicSubkey, mut signatures: Vec<packet::Signature>) -> Self {
         signatures.retain(|sig| {
@@ -180,7 +565,77 @@ impl SignedPublicSubKey {
             }
         });
 
-        SignedPublicSubKey { key, signatures }
+        SignedPublicSubKey {
+            key,
+            signatures,
+            primary: None,
+        }
+    }
+
+    /// Sets the primary key this subkey is bound to.
+    pub(super) fn set_primary(&mut self, primary: &packet::PublicKey) {
+        self.primary = Some(Box::new(primary.clone()));
+    }
+
+    /// Returns the newest binding signature of this subkey by `primary`, ordered by signature
+    /// creation time.
+    ///
+    /// A newer binding signature supersedes older ones, so this is the signature that determines
+    /// the key flags and expiration of the subkey. Signatures that do not verify are not
+    /// considered.
+    pub fn latest_binding_signature(
+        &self,
+        primary: &impl PublicKeyTrait,
+    ) -> Option<&packet::Signature> {
+        let mut sigs: Vec<_> = self
+            .signatures
+            .iter()
+            .filter(|sig| sig.typ() == SignatureType::SubkeyBinding)
+            .collect();
+        sigs.sort_by_key(|sig| sig.created());
+
+        sigs.into_iter()
+            .rev()
+            .find(|sig| sig.verify_key_binding(primary, &self.key).is_ok())
+    }
+
+    /// Returns the newest binding signature by the primary key of the key this subkey is part
+    /// of, that verifies.
+    fn binding_signature(&self) -> Option<&packet::Signature> {
+        self.latest_binding_signature(self.primary.as_deref()?)
+    }
+
+    /// Get the subkey expiration as a date.
+    ///
+    /// The expiration is taken from the newest valid binding signature, the expiration of the
+    /// primary key is not considered. Returns `None` for a subkey that is not part of a key.
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        binding_expires_at(self.key.created_at(), self.binding_signature()?)
+    }
+
+    /// Returns true if the subkey is expired at the given time, by its own binding signature.
+    pub fn is_expired_at(&self, time: DateTime<Utc>) -> bool {
+        self.expires_at()
+            .is_some_and(|expires_at| expires_at <= time)
+    }
+
+    /// Returns true if the subkey has a subkey revocation signature by `primary`.
+    ///
+    /// Only revocations that verify are considered, so a third party can not revoke the subkey
+    /// by attaching a bogus signature.
+    pub fn is_revoked(&self, primary: &impl PublicKeyTrait) -> bool {
+        self.signatures.iter().any(|sig| {
+            sig.typ() == SignatureType::SubkeyRevocation
+                && sig.verify_key_binding(primary, &self.key).is_ok()
+        })
+    }
+
+    /// Returns the state of the backsig in the newest binding signature, which must be present
+    /// and valid for the subkey to be used for signing.
+    ///
+    /// Returns `None` if the subkey is not bound as signing capable.
+    pub fn backsig(&self, primary: &impl PublicKeyTrait) -> Option<Backsig> {
+        check_backsig(self.latest_binding_signature(primary), &self.key, primary)
     }
 
     pub fn verify(&self, key: &impl PublicKeyTrait) -> Result<()> {
@@ -192,12 +647,13 @@ impl SignedPublicSubKey {
         Ok(())
     }
 
+    /// Returns the subkey with the key flags of its newest valid binding signature, to sign it
+    /// again.
     pub fn as_unsigned(&self) -> PublicSubkey {
         let keyflags = self
-            .signatures
-            .first()
-            .expect("missing signatures")
-            .key_flags();
+            .binding_signature()
+            .map(packet::Signature::key_flags)
+            .unwrap_or_default();
 
         PublicSubkey::new(self.key.clone(), keyflags)
     }
@@ -217,6 +673,16 @@ impl KeyTrait for SignedPublicSubKey {
     fn algorithm(&self) -> PublicKeyAlgorithm {
         self.key.algorithm()
     }
+
+    fn key_version(&self) -> KeyVersion {
+        self.key.version()
+    }
+
+    /// Returns the key flags of the newest binding signature that verifies against the primary
+    /// key, `None` for a subkey that is not part of a key.
+    fn key_flags(&self) -> Option<packet::KeyFlags> {
+        self.binding_signature().map(packet::Signature::key_flags)
+    }
 }
 
 impl PublicKeyTrait for SignedPublicSubKey {
@@ -233,13 +699,23 @@ impl PublicKeyTrait for SignedPublicSubKey {
     }
 }
 
-impl Serialize for SignedPublicSubKey {
-    fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<()> {
-        write_packet(writer, &self.key)?;
+impl SignedPublicSubKey {
+    pub(crate) fn to_writer_with_version<W: io::Write>(
+        &self,
+        writer: &mut W,
+        packet_version: Option<Version>,
+    ) -> Result<()> {
+        write_packet_as(writer, &self.key, packet_version)?;
         for sig in &self.signatures {
-            write_packet(writer, sig)?;
+            write_packet_as(writer, sig, packet_version)?;
         }
 
         Ok(())
     }
 }
+
+impl Serialize for SignedPublicSubKey {
+    fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<()> {
+        self.to_writer_with_version(writer, None)
+    }
+}
@@ -0,0 +1,70 @@
+use crate::composed::signed_key::SignedSecretKey;
+
+/// A collection of secret keys, that can be used to decrypt messages
+/// addressed to any of them.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct SecretKeyRing {
+    keys: Vec<SignedSecretKey>,
+}
+
+impl SecretKeyRing {
+    pub fn new(keys: Vec<SignedSecretKey>) -> Self {
+        SecretKeyRing { keys }
+    }
+
+    /// Adds a key to the ring.
+    pub fn push(&mut self, key: SignedSecretKey) {
+        self.keys.push(key);
+    }
+
+    pub fn keys(&self) -> &[SignedSecretKey] {
+        &self.keys
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &SignedSecretKey> {
+        self.keys.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+impl From<SignedSecretKey> for SecretKeyRing {
+    fn from(key: SignedSecretKey) -> Self {
+        SecretKeyRing { keys: vec![key] }
+    }
+}
+
+impl From<Vec<SignedSecretKey>> for SecretKeyRing {
+    fn from(keys: Vec<SignedSecretKey>) -> Self {
+        SecretKeyRing { keys }
+    }
+}
+
+impl FromIterator<SignedSecretKey> for SecretKeyRing {
+    fn from_iter<I: IntoIterator<Item = SignedSecretKey>>(iter: I) -> Self {
+        SecretKeyRing {
+            keys: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl Extend<SignedSecretKey> for SecretKeyRing {
+    fn extend<I: IntoIterator<Item = SignedSecretKey>>(&mut self, iter: I) {
+        self.keys.extend(iter);
+    }
+}
+
+impl IntoIterator for SecretKeyRing {
+    type Item = SignedSecretKey;
+    type IntoIter = std::vec::IntoIter<SignedSecretKey>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.keys.into_iter()
+    }
+}
@@ -1,19 +1,24 @@
-use std::io;
+use std::{fmt, io};
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, SubsecRound, Utc};
 use rand::{CryptoRng, Rng};
 
 use crate::composed::key::{PublicKey, PublicSubkey};
-use crate::composed::signed_key::{SignedKeyDetails, SignedPublicSubKey};
+use crate::composed::signed_key::{
+    binding_expires_at, check_backsig, check_signature_version, Backsig, EncryptionPurpose,
+    Revoked, SignedKeyDetails, SignedPublicSubKey, VersionMismatch,
+};
+use crate::composed::Deserializable;
 use crate::crypto::hash::HashAlgorithm;
 use crate::crypto::public_key::PublicKeyAlgorithm;
 use crate::errors::Result;
-use crate::packet::{self, write_packet, SignatureType};
+use crate::packet::{self, write_packet_as, SignatureType};
 use crate::ser::Serialize;
 use crate::types::{
-    KeyId, KeyTrait, Mpi, PublicKeyTrait, PublicParams, SecretKeyRepr, SecretKeyTrait,
+    KeyId, KeyTrait, KeyVersion, Mpi, PublicKeyTrait, PublicParams, SecretKeyRepr, SecretKeyTrait,
+    Tag, Version,
 };
-use crate::{armor, ArmorOptions, SignedPublicKey};
+use crate::{armor, ArmorOptions, SignedPublicKey, StandaloneSignature};
 
 /// Represents a secret signed PGP key.
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -69,6 +74,13 @@ impl SignedSecretKey {
                 true
             }
         });
+        let primary = primary_key.public_key();
+        for subkey in &mut public_subkeys {
+            subkey.set_primary(&primary);
+        }
+        for subkey in &mut secret_subkeys {
+            subkey.set_primary(&primary);
+        }
 
         SignedSecretKey {
             primary_key,
@@ -80,7 +92,9 @@ impl SignedSecretKey {
 
     /// Get the secret key expiration as a date.
     pub fn expires_at(&self) -> Option<DateTime<Utc>> {
-        let expiration = self.details.key_expiration_time()?;
+        let expiration = self
+            .details
+            .verified_key_expiration_time(&self.primary_key)?;
         Some(*self.primary_key.created_at() + expiration)
     }
 
@@ -100,6 +114,70 @@ impl SignedSecretKey {
         Ok(())
     }
 
+    /// Returns the signatures by the primary key, whose version or issuer subpackets do not match
+    /// the version of the primary key.
+    ///
+    /// Such signatures are not rejected by [`Self::verify`], this is a diagnostic for keys that
+    /// were assembled by buggy tools.
+    pub fn version_mismatches(&self) -> Vec<VersionMismatch> {
+        let mut mismatches = self.details.version_mismatches(&self.primary_key);
+        let sigs = self
+            .public_subkeys
+            .iter()
+            .flat_map(|subkey| &subkey.signatures)
+            .chain(
+                self.secret_subkeys
+                    .iter()
+                    .flat_map(|subkey| &subkey.signatures),
+            );
+        for sig in sigs {
+            check_signature_version(&self.primary_key, sig, &mut mismatches);
+        }
+
+        mismatches
+    }
+
+    /// Returns all revocation signatures of this key, together with the component they
+    /// apply to.
+    ///
+    /// The signatures are not verified.
+    pub fn revocations(&self) -> Vec<(Revoked<'_>, &packet::Signature)> {
+        self.details
+            .revocations()
+            .into_iter()
+            .chain(self.public_subkeys.iter().flat_map(|subkey| {
+                subkey
+                    .signatures
+                    .iter()
+                    .filter(|sig| sig.is_revocation())
+                    .map(|sig| (Revoked::Subkey(subkey.key_id()), sig))
+            }))
+            .chain(self.secret_subkeys.iter().flat_map(|subkey| {
+                subkey
+                    .signatures
+                    .iter()
+                    .filter(|sig| sig.is_revocation())
+                    .map(|sig| (Revoked::Subkey(subkey.key_id()), sig))
+            }))
+            .collect()
+    }
+
+    /// Returns the backsig state of each signing capable subkey, public and secret.
+    ///
+    /// Subkeys whose backsig is not [`Backsig::Valid`] are not used for signing.
+    pub fn signing_subkey_backsigs(&self) -> Vec<(KeyId, Backsig)> {
+        let public = self
+            .public_subkeys
+            .iter()
+            .filter_map(|subkey| Some((subkey.key_id(), subkey.backsig(&self.primary_key)?)));
+        let secret = self
+            .secret_subkeys
+            .iter()
+            .filter_map(|subkey| Some((subkey.key_id(), subkey.backsig(&self.primary_key)?)));
+
+        public.chain(secret).collect()
+    }
+
     pub fn verify(&self) -> Result<()> {
         self.details.verify(&self.primary_key)?;
         self.verify_public_subkeys()?;
@@ -108,6 +186,358 @@ impl SignedSecretKey {
         Ok(())
     }
 
+    /// Checks that the fingerprints of the primary key and all subkeys are unchanged after
+    /// serializing and re-parsing this key.
+    ///
+    /// A key whose encoding is not canonical may be re-encoded by other implementations,
+    /// which changes its fingerprint.
+    pub fn canonical_fingerprint_check(&self) -> Result<()> {
+        let bytes = self.to_bytes()?;
+        let parsed = SignedSecretKey::from_bytes(&bytes[..])?;
+
+        ensure_eq!(
+            hex::encode(self.fingerprint()),
+            hex::encode(parsed.fingerprint()),
+            "primary key fingerprint changed after round trip"
+        );
+        ensure_eq!(
+            self.public_subkeys.len(),
+            parsed.public_subkeys.len(),
+            "public subkeys changed after round trip"
+        );
+        ensure_eq!(
+            self.secret_subkeys.len(),
+            parsed.secret_subkeys.len(),
+            "secret subkeys changed after round trip"
+        );
+
+        let fingerprints = |key: &SignedSecretKey| {
+            key.public_subkeys
+                .iter()
+                .map(KeyTrait::fingerprint)
+                .chain(key.secret_subkeys.iter().map(KeyTrait::fingerprint))
+                .map(hex::encode)
+                .collect::<Vec<_>>()
+        };
+        for (ours, theirs) in fingerprints(self).into_iter().zip(fingerprints(&parsed)) {
+            ensure_eq!(ours, theirs, "subkey fingerprint changed after round trip");
+        }
+
+        Ok(())
+    }
+
+    /// Returns the secret subkeys that are bound as encryption capable for the given purpose, by
+    /// their newest valid binding signature.
+    pub fn encryption_subkeys(
+        &self,
+        purpose: EncryptionPurpose,
+    ) -> impl Iterator<Item = &SignedSecretSubKey> {
+        self.secret_subkeys.iter().filter(move |key| {
+            key.latest_binding_signature(&self.primary_key)
+                .is_some_and(|sig| purpose.matches(sig.key_flags()))
+        })
+    }
+
+    /// Lists the components that would be dropped when converting this key into a
+    /// [`SignedPublicKey`].
+    ///
+    /// Keys constructed through [`SignedSecretKey::new`] or parsed from their serialized form
+    /// always convert without losses, so the report is empty for them. Only keys assembled
+    /// by hand can contain components the public key constructors refuse.
+    pub fn conversion_report(&self) -> Vec<ConversionLoss> {
+        let subkeys = self
+            .public_subkeys
+            .iter()
+            .map(|key| (key.key_id(), &key.signatures))
+            .chain(
+                self.secret_subkeys
+                    .iter()
+                    .map(|key| (key.key_id(), &key.signatures)),
+            );
+
+        let mut losses = Vec::new();
+        for (key_id, signatures) in subkeys {
+            if signatures.is_empty() {
+                losses.push(ConversionLoss::UnboundSubkey(key_id));
+                continue;
+            }
+
+            for sig in signatures {
+                if sig.typ() != SignatureType::SubkeyBinding
+                    && sig.typ() != SignatureType::SubkeyRevocation
+                {
+                    losses.push(ConversionLoss::SubkeySignature(key_id.clone(), sig.typ()));
+                }
+            }
+        }
+
+        losses
+    }
+
+    /// Serializes the key, writing all packet headers in the given format.
+    ///
+    /// This is useful to reproduce keys for tools that expect old format headers. Packets with
+    /// tags above 15 can not use the old format and are always written in the new format.
+    pub fn to_writer_with_version<W: io::Write>(
+        &self,
+        writer: &mut W,
+        packet_version: Version,
+    ) -> Result<()> {
+        self.write_packets(writer, Some(packet_version))
+    }
+
+    fn write_packets<W: io::Write>(
+        &self,
+        writer: &mut W,
+        packet_version: Option<Version>,
+    ) -> Result<()> {
+        write_packet_as(writer, &self.primary_key, packet_version)?;
+        self.details
+            .to_writer_with_version(writer, packet_version)?;
+        for ps in &self.public_subkeys {
+            ps.to_writer_with_version(writer, packet_version)?;
+        }
+
+        for ps in &self.secret_subkeys {
+            ps.to_writer_with_version(writer, packet_version)?;
+        }
+
+        Ok(())
+    }
+
+    /// Creates a detached signature over `data`.
+    ///
+    /// This is the default way to sign a blob of data: the first secret subkey that is bound
+    /// as signing capable is used, falling back to the primary key.
+    /// The resulting signature is a v4 binary signature, carrying the creation time and the
+    /// issuer fingerprint as hashed subpackets, and the issuer key id as unhashed subpacket.
+    ///
+    /// ```
+    /// # use pgp::{Deserializable, SignedPublicKey, SignedSecretKey};
+    /// # use pgp::crypto::hash::HashAlgorithm;
+    /// let (secret_key, _headers) = SignedSecretKey::from_armor_single(
+    ///     std::fs::File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc")?,
+    /// )?;
+    /// let public_key = SignedPublicKey::from(secret_key.clone());
+    ///
+    /// let data = b"hello world";
+    /// let signature = secret_key.sign_detached(|| "".into(), HashAlgorithm::SHA2_256, data)?;
+    /// let armored = signature.to_armored_string(None.into())?;
+    ///
+    /// public_key.verify_detached(data, &signature)?;
+    /// # Ok::<(), pgp::errors::Error>(())
+    /// ```
+    pub fn sign_detached<F>(
+        &self,
+        key_pw: F,
+        hash_algorithm: HashAlgorithm,
+        data: &[u8],
+    ) -> Result<StandaloneSignature>
+    where
+        F: FnOnce() -> String,
+    {
+        let subkey = self.secret_subkeys.iter().find(|subkey| {
+            subkey
+                .backsig(&self.primary_key)
+                .is_some_and(|backsig| backsig == Backsig::Valid)
+        });
+
+        match subkey {
+            Some(subkey) => StandaloneSignature::sign_binary(subkey, key_pw, hash_algorithm, data),
+            None => {
+                ensure!(
+                    self.details.can_sign(&self.primary_key),
+                    "no signing capable key found for {:?}",
+                    self.key_id()
+                );
+                StandaloneSignature::sign_binary(&self.primary_key, key_pw, hash_algorithm, data)
+            }
+        }
+    }
+
+    /// Creates a certification of `id` on `signee` with the primary key.
+    ///
+    /// `typ` is one of the certification types, use [`SignatureType::CertRevocation`] to
+    /// revoke an earlier certification. Pass the key itself as `signee` to self-certify.
+    pub fn certify_user_id<F>(
+        &self,
+        key_pw: F,
+        signee: &impl PublicKeyTrait,
+        id: &packet::UserId,
+        typ: SignatureType,
+    ) -> Result<packet::Signature>
+    where
+        F: FnOnce() -> String,
+    {
+        self.certify(key_pw, signee, Tag::UserId, id, typ)
+    }
+
+    /// Creates a certification of `attr` on `signee` with the primary key.
+    ///
+    /// Works like [`SignedSecretKey::certify_user_id`], for User Attributes such as photo ids.
+    pub fn certify_user_attribute<F>(
+        &self,
+        key_pw: F,
+        signee: &impl PublicKeyTrait,
+        attr: &packet::UserAttribute,
+        typ: SignatureType,
+    ) -> Result<packet::Signature>
+    where
+        F: FnOnce() -> String,
+    {
+        self.certify(key_pw, signee, Tag::UserAttribute, attr, typ)
+    }
+
+    fn certify<F>(
+        &self,
+        key_pw: F,
+        signee: &impl PublicKeyTrait,
+        tag: Tag,
+        component: &impl Serialize,
+        typ: SignatureType,
+    ) -> Result<packet::Signature>
+    where
+        F: FnOnce() -> String,
+    {
+        let config = packet::SignatureConfigBuilder::default()
+            .typ(typ)
+            .pub_alg(self.primary_key.algorithm())
+            .hash_alg(self.primary_key.hash_alg())
+            .hashed_subpackets(vec![
+                packet::Subpacket::regular(packet::SubpacketData::SignatureCreationTime(
+                    Utc::now().trunc_subsecs(0),
+                )),
+                packet::Subpacket::regular(packet::SubpacketData::issuer_fingerprint(
+                    &self.primary_key,
+                )),
+            ])
+            .unhashed_subpackets(vec![packet::Subpacket::regular(
+                packet::SubpacketData::Issuer(self.primary_key.key_id()),
+            )])
+            .build()?;
+
+        config.sign_certification_third_party(&self.primary_key, key_pw, signee, tag, component)
+    }
+
+    /// Creates a revocation signature for the primary key, giving `code` and `reason` as the
+    /// reason for the revocation.
+    ///
+    /// The signature is not added to the key. To publish it, add it to the
+    /// `revocation_signatures` of the public key, or hand it out as a revocation certificate,
+    /// see [`SignedSecretKey::revocation_certificate`].
+    pub fn revoke<F>(
+        &self,
+        key_pw: F,
+        code: packet::RevocationCode,
+        reason: &str,
+    ) -> Result<packet::Signature>
+    where
+        F: FnOnce() -> String,
+    {
+        let primary = &self.primary_key;
+        self.revocation_config(SignatureType::KeyRevocation, code, reason)?
+            .sign_key(primary, key_pw, primary)
+    }
+
+    /// Revokes the subkey with the given fingerprint, giving `code` and `reason` as the reason
+    /// for the revocation.
+    ///
+    /// The subkey revocation signature is added to the signatures of the subkey, which stays
+    /// part of the key, and returned. Once revoked, the subkey is no longer selected for
+    /// encryption, see [`SignedPublicKey::best_encryption_key`].
+    pub fn revoke_subkey<F>(
+        &mut self,
+        key_pw: F,
+        fingerprint: &[u8],
+        code: packet::RevocationCode,
+        reason: &str,
+    ) -> Result<packet::Signature>
+    where
+        F: FnOnce() -> String,
+    {
+        let config = self.revocation_config(SignatureType::SubkeyRevocation, code, reason)?;
+
+        let primary = &self.primary_key;
+        if let Some(subkey) = self
+            .secret_subkeys
+            .iter_mut()
+            .find(|subkey| subkey.fingerprint() == fingerprint)
+        {
+            let sig = config.sign_key_binding(primary, key_pw, &subkey.key.public_key())?;
+            subkey.signatures.push(sig.clone());
+            return Ok(sig);
+        }
+        if let Some(subkey) = self
+            .public_subkeys
+            .iter_mut()
+            .find(|subkey| subkey.fingerprint() == fingerprint)
+        {
+            let sig = config.sign_key_binding(primary, key_pw, &subkey.key)?;
+            subkey.signatures.push(sig.clone());
+            return Ok(sig);
+        }
+
+        bail!("no subkey with fingerprint {}", hex::encode(fingerprint))
+    }
+
+    /// Returns the config of a revocation signature of type `typ`, issued by the primary key.
+    fn revocation_config(
+        &self,
+        typ: SignatureType,
+        code: packet::RevocationCode,
+        reason: &str,
+    ) -> Result<packet::SignatureConfig> {
+        let primary = &self.primary_key;
+        let hashed_subpackets = vec![
+            packet::Subpacket::regular(packet::SubpacketData::SignatureCreationTime(
+                Utc::now().trunc_subsecs(0),
+            )),
+            packet::Subpacket::regular(packet::SubpacketData::issuer_fingerprint(primary)),
+            packet::Subpacket::regular(packet::SubpacketData::RevocationReason(
+                code,
+                reason.into(),
+            )),
+        ];
+        let mut unhashed_subpackets = Vec::new();
+        if primary.key_version() == KeyVersion::V4 {
+            unhashed_subpackets.push(packet::Subpacket::regular(packet::SubpacketData::Issuer(
+                primary.key_id(),
+            )));
+        }
+
+        let config = packet::SignatureConfigBuilder::default()
+            .typ(typ)
+            .pub_alg(primary.algorithm())
+            .hash_alg(primary.hash_alg())
+            .hashed_subpackets(hashed_subpackets)
+            .unhashed_subpackets(unhashed_subpackets)
+            .build()?;
+
+        Ok(config)
+    }
+
+    /// Creates a revocation signature, like [`SignedSecretKey::revoke`], and returns it as an
+    /// armored revocation certificate.
+    ///
+    /// Like the certificates of GnuPG, it is a public key block holding just the signature,
+    /// so it can be imported into a keyring that has the key.
+    pub fn revocation_certificate<F>(
+        &self,
+        key_pw: F,
+        code: packet::RevocationCode,
+        reason: &str,
+    ) -> Result<String>
+    where
+        F: FnOnce() -> String,
+    {
+        let signature = StandaloneSignature::new(self.revoke(key_pw, code, reason)?);
+        armor::to_armored_string(
+            &signature,
+            armor::BlockType::PublicKey,
+            ArmorOptions::default(),
+        )
+    }
+
     pub fn to_armored_writer(
         &self,
         writer: &mut impl io::Write,
@@ -123,19 +553,24 @@ impl SignedSecretKey {
     }
 
     pub fn to_armored_bytes(&self, opts: ArmorOptions<'_>) -> Result<Vec<u8>> {
-        let mut buf = Vec::new();
-
-        self.to_armored_writer(&mut buf, opts)?;
-
-        Ok(buf)
+        armor::to_armored_bytes(self, armor::BlockType::PrivateKey, opts)
     }
 
     pub fn to_armored_string(&self, opts: ArmorOptions<'_>) -> Result<String> {
-        let res = String::from_utf8(self.to_armored_bytes(opts)?).map_err(|e| e.utf8_error())?;
-        Ok(res)
+        armor::to_armored_string(self, armor::BlockType::PrivateKey, opts)
     }
 }
 
+/// A component of a [`SignedSecretKey`] that can not be represented in the [`SignedPublicKey`]
+/// derived from it.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ConversionLoss {
+    /// A subkey without any signatures.
+    UnboundSubkey(KeyId),
+    /// A signature on a subkey that is neither a binding nor a revocation signature.
+    SubkeySignature(KeyId, SignatureType),
+}
+
 impl KeyTrait for SignedSecretKey {
     /// Returns the fingerprint of the associated primary key.
     fn fingerprint(&self) -> Vec<u8> {
@@ -150,21 +585,15 @@ impl KeyTrait for SignedSecretKey {
     fn algorithm(&self) -> PublicKeyAlgorithm {
         self.primary_key.algorithm()
     }
+
+    fn key_version(&self) -> KeyVersion {
+        self.primary_key.version()
+    }
 }
 
 impl Serialize for SignedSecretKey {
     fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<()> {
-        write_packet(writer, &self.primary_key)?;
-        self.details.to_writer(writer)?;
-        for ps in &self.public_subkeys {
-            ps.to_writer(writer)?;
-        }
-
-        for ps in &self.secret_subkeys {
-            ps.to_writer(writer)?;
-        }
-
-        Ok(())
+        self.write_packets(writer, None)
     }
 }
 
@@ -199,7 +628,7 @@ impl SecretKeyTrait for SignedSecretKey {
 
         PublicKey::new(
             self.primary_key.public_key(),
-            self.details.as_unsigned(),
+            self.details.as_unsigned(&self.primary_key),
             subkeys,
         )
     }
@@ -224,12 +653,34 @@ impl PublicKeyTrait for SignedSecretKey {
 }
 
 /// Represents a composed secret PGP SubKey.
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Clone)]
 pub struct SignedSecretSubKey {
     pub key: packet::SecretSubkey,
     pub signatures: Vec<packet::Signature>,
+    /// The primary key the binding signatures are verified against, set once the subkey is part
+    /// of a key.
+    primary: Option<Box<packet::PublicKey>>,
 }
 
+impl fmt::Debug for SignedSecretSubKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SignedSecretSubKey")
+            .field("key", &self.key)
+            .field("signatures", &self.signatures)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The primary key is not part of the subkey itself, only the key and its signatures are
+/// compared.
+impl PartialEq for SignedSecretSubKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key && self.signatures == other.signatures
+    }
+}
+
+impl Eq for SignedSecretSubKey {}
+
 impl SignedSecretSubKey {
     pub fn new(key: packet::SecretSubkey, mut signatures: Vec<packet::Signature>) -> Self {
         signatures.retain(|sig| {
@@ -246,7 +697,77 @@ impl SignedSecretSubKey {
             }
         });
 
-        SignedSecretSubKey { key, signatures }
+        SignedSecretSubKey {
+            key,
+            signatures,
+            primary: None,
+        }
+    }
+
+    /// Sets the primary key this subkey is bound to.
+    pub(super) fn set_primary(&mut self, primary: &packet::PublicKey) {
+        self.primary = Some(Box::new(primary.clone()));
+    }
+
+    /// Returns the newest binding signature of this subkey by `primary`, ordered by signature
+    /// creation time.
+    ///
+    /// A newer binding signature supersedes older ones, so this is the signature that determines
+    /// the key flags and expiration of the subkey. Signatures that do not verify are not
+    /// considered.
+    pub fn latest_binding_signature(
+        &self,
+        primary: &impl PublicKeyTrait,
+    ) -> Option<&packet::Signature> {
+        let mut sigs: Vec<_> = self
+            .signatures
+            .iter()
+            .filter(|sig| sig.typ() == SignatureType::SubkeyBinding)
+            .collect();
+        sigs.sort_by_key(|sig| sig.created());
+
+        sigs.into_iter()
+            .rev()
+            .find(|sig| sig.verify_key_binding(primary, &self.key).is_ok())
+    }
+
+    /// Returns the newest binding signature by the primary key of the key this subkey is part
+    /// of, that verifies.
+    fn binding_signature(&self) -> Option<&packet::Signature> {
+        self.latest_binding_signature(self.primary.as_deref()?)
+    }
+
+    /// Get the subkey expiration as a date.
+    ///
+    /// The expiration is taken from the newest valid binding signature, the expiration of the
+    /// primary key is not considered. Returns `None` for a subkey that is not part of a key.
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        binding_expires_at(self.key.created_at(), self.binding_signature()?)
+    }
+
+    /// Returns true if the subkey is expired at the given time, by its own binding signature.
+    pub fn is_expired_at(&self, time: DateTime<Utc>) -> bool {
+        self.expires_at()
+            .is_some_and(|expires_at| expires_at <= time)
+    }
+
+    /// Returns true if the subkey has a subkey revocation signature by `primary`.
+    ///
+    /// Only revocations that verify are considered, so a third party can not revoke the subkey
+    /// by attaching a bogus signature.
+    pub fn is_revoked(&self, primary: &impl PublicKeyTrait) -> bool {
+        self.signatures.iter().any(|sig| {
+            sig.typ() == SignatureType::SubkeyRevocation
+                && sig.verify_key_binding(primary, &self.key).is_ok()
+        })
+    }
+
+    /// Returns the state of the backsig in the newest binding signature, which must be present
+    /// and valid for the subkey to be used for signing.
+    ///
+    /// Returns `None` if the subkey is not bound as signing capable.
+    pub fn backsig(&self, primary: &impl PublicKeyTrait) -> Option<Backsig> {
+        check_backsig(self.latest_binding_signature(primary), &self.key, primary)
     }
 
     pub fn verify(&self, key: &impl PublicKeyTrait) -> Result<()> {
@@ -274,19 +795,39 @@ impl KeyTrait for SignedSecretSubKey {
     fn algorithm(&self) -> PublicKeyAlgorithm {
         self.key.algorithm()
     }
+
+    fn key_version(&self) -> KeyVersion {
+        self.key.version()
+    }
+
+    /// Returns the key flags of the newest binding signature that verifies against the primary
+    /// key, `None` for a subkey that is not part of a key.
+    fn key_flags(&self) -> Option<packet::KeyFlags> {
+        self.binding_signature().map(packet::Signature::key_flags)
+    }
 }
 
-impl Serialize for SignedSecretSubKey {
-    fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<()> {
-        write_packet(writer, &self.key)?;
+impl SignedSecretSubKey {
+    pub(crate) fn to_writer_with_version<W: io::Write>(
+        &self,
+        writer: &mut W,
+        packet_version: Option<Version>,
+    ) -> Result<()> {
+        write_packet_as(writer, &self.key, packet_version)?;
         for sig in &self.signatures {
-            write_packet(writer, sig)?;
+            write_packet_as(writer, sig, packet_version)?;
         }
 
         Ok(())
     }
 }
 
+impl Serialize for SignedSecretSubKey {
+    fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<()> {
+        self.to_writer_with_version(writer, None)
+    }
+}
+
 impl SecretKeyTrait for SignedSecretSubKey {
     type PublicKey = PublicSubkey;
     type Unlocked = SecretKeyRepr;
@@ -308,10 +849,9 @@ impl SecretKeyTrait for SignedSecretSubKey {
 
     fn public_key(&self) -> Self::PublicKey {
         let keyflags = self
-            .signatures
-            .first()
-            .expect("invalid signed subkey")
-            .key_flags();
+            .binding_signature()
+            .map(packet::Signature::key_flags)
+            .unwrap_or_default();
 
         PublicSubkey::new(self.key.public_key(), keyflags)
     }
@@ -335,6 +875,19 @@ impl PublicKeyTrait for SignedSecretSubKey {
     }
 }
 
+/// Derives the public key from a secret key.
+///
+/// The primary key, all user ids, user attributes and their signatures (including third-party
+/// certifications), direct key signatures and revocations are carried over unchanged. Each
+/// secret subkey is converted to a public subkey with the same signatures.
+///
+/// The conversion can not preserve:
+/// - the relative order of public and secret subkeys: the public subkeys come first, followed
+///   by the converted secret subkeys,
+/// - the components listed by [`SignedSecretKey::conversion_report`].
+///
+/// Packets that are not part of the key structure, like trust packets, are already dropped
+/// when the secret key is parsed.
 impl From<SignedSecretKey> for SignedPublicKey {
     fn from(value: SignedSecretKey) -> Self {
         let primary = value.primary_key.public_key();
@@ -353,6 +906,11 @@ impl From<SignedSecretKey> for SignedPublicKey {
 
 impl From<SignedSecretSubKey> for SignedPublicSubKey {
     fn from(value: SignedSecretSubKey) -> Self {
-        SignedPublicSubKey::new(value.key.public_key(), value.signatures)
+        let mut subkey = SignedPublicSubKey::new(value.key.public_key(), value.signatures);
+        if let Some(primary) = value.primary {
+            subkey.set_primary(&primary);
+        }
+
+        subkey
     }
 }
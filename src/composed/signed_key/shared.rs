@@ -1,14 +1,21 @@
+use std::collections::{HashMap, HashSet};
 use std::io;
 
-use chrono::Duration;
+use chrono::{DateTime, Duration, Utc};
 use smallvec::SmallVec;
 
 use crate::composed::key::KeyDetails;
 use crate::composed::signed_key::{SignedPublicKey, SignedSecretKey};
+use crate::crypto::hash::HashAlgorithm;
 use crate::crypto::public_key::PublicKeyAlgorithm;
+use crate::crypto::sym::SymmetricKeyAlgorithm;
 use crate::errors::Result;
+use crate::packet::{SignatureType, SignatureVersion};
 use crate::ser::Serialize;
-use crate::types::{KeyId, KeyTrait, PublicKeyTrait, SignedUser, SignedUserAttribute};
+use crate::types::{
+    CompressionAlgorithm, KeyId, KeyTrait, KeyVersion, PublicKeyTrait, SignedUser,
+    SignedUserAttribute, Version,
+};
 use crate::{packet, ArmorOptions};
 
 /// Shared details between secret and public keys.
@@ -60,6 +67,9 @@ impl SignedKeyDetails {
     /// self-signed signatures) and converts it into a duration.
     /// The function returns `None` if the key has an infinite
     /// validity.
+    #[deprecated(
+        note = "considers unverified signatures, use SignedPublicKey::expires_at or SignedSecretKey::expires_at instead"
+    )]
     pub fn key_expiration_time(&self) -> Option<Duration> {
         // Find the maximum key_expiration_time in all signatures of all user ids.
         self.users
@@ -70,6 +80,107 @@ impl SignedKeyDetails {
             .cloned()
     }
 
+    /// Get the key expiration time as a duration, as stated by the self-signatures by `key`.
+    ///
+    /// The `KeyExpirationTime` is taken from the newest self-signature of the primary user id,
+    /// falling back to the newest direct key signature. The function returns `None` if the key
+    /// has an infinite validity.
+    pub(super) fn verified_key_expiration_time(
+        &self,
+        key: &impl PublicKeyTrait,
+    ) -> Option<Duration> {
+        self.self_signatures(key)
+            .iter()
+            .find_map(|sig| sig.key_expiration_time())
+            .cloned()
+    }
+
+    /// Returns true if the self-signatures allow the primary key to be used for signing data.
+    ///
+    /// Only the newest self-signature by `key` of each user id and the newest direct key
+    /// signature are considered. Self-signatures without a `KeyFlags` subpacket place no
+    /// restrictions on the key.
+    pub fn can_sign(&self, key: &impl PublicKeyTrait) -> bool {
+        let mut sigs = self
+            .users
+            .iter()
+            .filter_map(|user| user.latest_signature(key))
+            .chain(self.direct_key_signature(key))
+            .peekable();
+
+        if sigs.peek().is_none() {
+            return true;
+        }
+
+        sigs.any(|sig| {
+            let has_flags = sig
+                .config
+                .hashed_subpackets()
+                .any(|p| matches!(p.data, packet::SubpacketData::KeyFlags(_)));
+            !has_flags || sig.key_flags().sign()
+        })
+    }
+
+    /// Looks up the primary user id and the self-signatures by `key` in effect, verifying each
+    /// candidate signature once.
+    ///
+    /// The primary user id is the first one marked as primary by its newest self-signature. If
+    /// no user id is marked as primary, the first one is used.
+    pub(super) fn self_signatures(&self, key: &impl PublicKeyTrait) -> SelfSignatures<'_> {
+        let mut first = None;
+        for user in &self.users {
+            let sig = user.latest_signature(key);
+            if sig.is_some_and(|sig| sig.is_primary()) {
+                first = Some((user, sig));
+                break;
+            }
+            first = first.or(Some((user, sig)));
+        }
+
+        SelfSignatures {
+            primary_user: first.map(|(user, _)| user),
+            primary_user_signature: first.and_then(|(_, sig)| sig),
+            direct_key_signature: self.direct_key_signature(key),
+        }
+    }
+
+    /// Returns the newest direct key signature by `key`, that verifies.
+    fn direct_key_signature(&self, key: &impl PublicKeyTrait) -> Option<&packet::Signature> {
+        let mut sigs: Vec<_> = self
+            .direct_signatures
+            .iter()
+            .filter(|sig| sig.typ() == SignatureType::Key)
+            .collect();
+        sigs.sort_by_key(|sig| sig.created());
+
+        sigs.into_iter()
+            .rev()
+            .find(|sig| sig.verify_key(key).is_ok())
+    }
+
+    /// The preferred symmetric algorithms of the key holder.
+    pub fn preferred_symmetric_algs(&self, key: &impl PublicKeyTrait) -> &[SymmetricKeyAlgorithm] {
+        self.self_signatures(key)
+            .preference(|sig| sig.preferred_symmetric_algs())
+    }
+
+    /// The preferred hash algorithms of the key holder.
+    pub fn preferred_hash_algs(&self, key: &impl PublicKeyTrait) -> &[HashAlgorithm] {
+        self.self_signatures(key)
+            .preference(|sig| sig.preferred_hash_algs())
+    }
+
+    /// The preferred compression algorithms of the key holder.
+    pub fn preferred_compression_algs(&self, key: &impl PublicKeyTrait) -> &[CompressionAlgorithm] {
+        self.self_signatures(key)
+            .preference(|sig| sig.preferred_compression_algs())
+    }
+
+    /// The features supported by the key holder's implementation.
+    pub fn features(&self, key: &impl PublicKeyTrait) -> &[u8] {
+        self.self_signatures(key).preference(|sig| sig.features())
+    }
+
     fn verify_users(&self, key: &impl PublicKeyTrait) -> Result<()> {
         for user in &self.users {
             user.verify(key)?;
@@ -102,6 +213,53 @@ impl SignedKeyDetails {
         Ok(())
     }
 
+    /// Returns the signatures by `key` on the primary key and its user ids and attributes, that
+    /// do not match the version of `key`.
+    pub fn version_mismatches(&self, key: &impl PublicKeyTrait) -> Vec<VersionMismatch> {
+        let mut mismatches = Vec::new();
+        let sigs = self
+            .revocation_signatures
+            .iter()
+            .chain(&self.direct_signatures)
+            .chain(self.users.iter().flat_map(|user| &user.signatures))
+            .chain(
+                self.user_attributes
+                    .iter()
+                    .flat_map(|attr| &attr.signatures),
+            );
+        for sig in sigs {
+            check_signature_version(key, sig, &mut mismatches);
+        }
+
+        mismatches
+    }
+
+    /// Returns the revocation signatures of the primary key, its users and user attributes,
+    /// together with the component they apply to.
+    ///
+    /// The signatures are not verified.
+    pub fn revocations(&self) -> Vec<(Revoked<'_>, &packet::Signature)> {
+        let key = self
+            .revocation_signatures
+            .iter()
+            .map(|sig| (Revoked::PrimaryKey, sig));
+        let users = self.users.iter().flat_map(|user| {
+            user.signatures
+                .iter()
+                .map(move |sig| (Revoked::User(user), sig))
+        });
+        let attrs = self.user_attributes.iter().flat_map(|attr| {
+            attr.signatures
+                .iter()
+                .map(move |sig| (Revoked::UserAttribute(attr), sig))
+        });
+
+        key.chain(users)
+            .chain(attrs)
+            .filter(|(_, sig)| sig.is_revocation())
+            .collect()
+    }
+
     pub fn verify(&self, key: &impl PublicKeyTrait) -> Result<()> {
         self.verify_users(key)?;
         self.verify_attributes(key)?;
@@ -111,31 +269,32 @@ impl SignedKeyDetails {
         Ok(())
     }
 
-    pub fn as_unsigned(&self) -> KeyDetails {
-        let primary_user = self.users.iter().find(|u| u.is_primary()).map_or_else(
-            || self.users.first().expect("missing user ids"),
-            |user| user,
-        );
+    /// Returns the details as stated by the newest self-signatures by `key`, to sign them again.
+    ///
+    /// If the primary user id has no valid self-signature, it is signed again without key
+    /// flags, preferences or revocation key.
+    pub fn as_unsigned(&self, key: &impl PublicKeyTrait) -> KeyDetails {
+        let self_sigs = self.self_signatures(key);
+        let primary_user = self_sigs.primary_user.expect("missing user ids");
 
         let primary_user_id = primary_user.id.clone();
-        let primary_sig = primary_user
-            .signatures
-            .first()
-            .expect("invalid primary user");
-        let keyflags = primary_sig.key_flags();
+        let primary_sig = self_sigs.primary_user_signature;
+        let keyflags = primary_sig.map(|sig| sig.key_flags()).unwrap_or_default();
 
         let preferred_symmetric_algorithms =
-            SmallVec::from_slice(primary_sig.preferred_symmetric_algs());
-        let preferred_hash_algorithms = SmallVec::from_slice(primary_sig.preferred_hash_algs());
+            SmallVec::from_slice(self_sigs.preference(|sig| sig.preferred_symmetric_algs()));
+        let preferred_hash_algorithms =
+            SmallVec::from_slice(self_sigs.preference(|sig| sig.preferred_hash_algs()));
         let preferred_compression_algorithms =
-            SmallVec::from_slice(primary_sig.preferred_compression_algs());
-        let revocation_key = primary_sig.revocation_key().cloned();
+            SmallVec::from_slice(self_sigs.preference(|sig| sig.preferred_compression_algs()));
+        let revocation_key = primary_sig.and_then(|sig| sig.revocation_key().cloned());
+        let features = self_sigs.preference(|sig| sig.features());
 
-        KeyDetails::new(
+        let details = KeyDetails::new(
             primary_user_id,
             self.users
                 .iter()
-                .filter(|u| !u.is_primary())
+                .filter(|u| u.id != primary_user.id)
                 .map(|u| u.id.clone())
                 .collect(),
             self.user_attributes
@@ -148,31 +307,270 @@ impl SignedKeyDetails {
             preferred_compression_algorithms,
             revocation_key,
         )
+        .with_direct_key_signature(!self.direct_signatures.is_empty());
+
+        // only advertise features if the key did before
+        if features.is_empty() {
+            details.without_features()
+        } else {
+            details.with_features(packet::Features::from(features))
+        }
     }
 }
 
-impl Serialize for SignedKeyDetails {
-    fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<()> {
+/// The primary user id and the self-signatures by the primary key that are in effect.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct SelfSignatures<'a> {
+    pub(super) primary_user: Option<&'a SignedUser>,
+    /// The newest valid self-signature of the primary user id.
+    pub(super) primary_user_signature: Option<&'a packet::Signature>,
+    /// The newest valid direct key signature.
+    pub(super) direct_key_signature: Option<&'a packet::Signature>,
+}
+
+impl<'a> SelfSignatures<'a> {
+    /// The self-signatures in order of precedence.
+    fn iter(&self) -> impl Iterator<Item = &'a packet::Signature> {
+        self.primary_user_signature
+            .into_iter()
+            .chain(self.direct_key_signature)
+    }
+
+    /// Looks up a preference on the self-signatures.
+    ///
+    /// The newest self-signature of the primary user id takes precedence, the newest direct key
+    /// signature is consulted if it does not state the preference.
+    fn preference<T>(&self, get: impl Fn(&'a packet::Signature) -> &'a [T]) -> &'a [T] {
+        self.iter()
+            .map(get)
+            .find(|prefs| !prefs.is_empty())
+            .unwrap_or(&[])
+    }
+}
+
+impl SignedKeyDetails {
+    /// Adds the user ids, user attributes and signatures of `other` that are not present yet.
+    pub(crate) fn merge(&mut self, other: SignedKeyDetails) {
+        merge_signatures(&mut self.revocation_signatures, other.revocation_signatures);
+        merge_signatures(&mut self.direct_signatures, other.direct_signatures);
+
+        for user in other.users {
+            match self.users.iter_mut().find(|u| u.id.id() == user.id.id()) {
+                Some(existing) => merge_signatures(&mut existing.signatures, user.signatures),
+                None => self.users.push(user),
+            }
+        }
+
+        let mut attrs: HashMap<Vec<u8>, usize> = self
+            .user_attributes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, a)| Some((a.attr.to_bytes().ok()?, i)))
+            .collect();
+        for attr in other.user_attributes {
+            let bytes = attr.attr.to_bytes().ok();
+            match bytes.as_ref().and_then(|bytes| attrs.get(bytes)) {
+                Some(&i) => {
+                    merge_signatures(&mut self.user_attributes[i].signatures, attr.signatures)
+                }
+                None => {
+                    if let Some(bytes) = bytes {
+                        attrs.insert(bytes, self.user_attributes.len());
+                    }
+                    self.user_attributes.push(attr);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn to_writer_with_version<W: io::Write>(
+        &self,
+        writer: &mut W,
+        packet_version: Option<Version>,
+    ) -> Result<()> {
         for sig in &self.revocation_signatures {
-            packet::write_packet(writer, sig)?;
+            packet::write_packet_as(writer, sig, packet_version)?;
         }
 
         for sig in &self.direct_signatures {
-            packet::write_packet(writer, sig)?;
+            packet::write_packet_as(writer, sig, packet_version)?;
         }
 
         for user in &self.users {
-            user.to_writer(writer)?;
+            user.to_writer_with_version(writer, packet_version)?;
         }
 
         for attr in &self.user_attributes {
-            attr.to_writer(writer)?;
+            attr.to_writer_with_version(writer, packet_version)?;
         }
 
         Ok(())
     }
 }
 
+/// Adds the signatures of `other` that are not in `sigs` yet.
+///
+/// Signatures are compared by their encoded body, each of them is serialized once.
+pub(crate) fn merge_signatures(sigs: &mut Vec<packet::Signature>, other: Vec<packet::Signature>) {
+    let mut seen: HashSet<Vec<u8>> = sigs.iter().filter_map(|s| s.to_bytes().ok()).collect();
+    for sig in other {
+        // signatures that can not be encoded are kept
+        let new = sig.to_bytes().map_or(true, |bytes| seen.insert(bytes));
+        if new {
+            sigs.push(sig);
+        }
+    }
+}
+
+impl Serialize for SignedKeyDetails {
+    fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<()> {
+        self.to_writer_with_version(writer, None)
+    }
+}
+
+/// A signature made by a key, that is not in the format a key of this version produces.
+///
+/// Such signatures are a sign of keys that were assembled by buggy tools.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum VersionMismatch {
+    /// The signature version can not be used with the key version.
+    SignatureVersion(SignatureType, SignatureVersion),
+    /// An `Issuer` subpacket, on a signature by a key that is only identified by fingerprint.
+    IssuerKeyId(SignatureType),
+    /// An `IssuerFingerprint` subpacket of a different key version.
+    IssuerFingerprint(SignatureType, KeyVersion),
+}
+
+/// Records the ways in which `sig` does not match the version of `key`.
+///
+/// Signatures that were issued by other keys, such as third party certifications, are skipped.
+pub(crate) fn check_signature_version(
+    key: &impl PublicKeyTrait,
+    sig: &packet::Signature,
+    mismatches: &mut Vec<VersionMismatch>,
+) {
+    use KeyVersion::*;
+
+    if !packet::Signature::match_identity(sig, key) {
+        return;
+    }
+    let key_version = key.key_version();
+    let typ = sig.typ();
+    let sig_version = sig.config.version;
+    let valid = match key_version {
+        // legacy keys were used with V4 signatures in the transition to V4 keys
+        V2 | V3 => matches!(
+            sig_version,
+            SignatureVersion::V2 | SignatureVersion::V3 | SignatureVersion::V4
+        ),
+        V4 => matches!(sig_version, SignatureVersion::V3 | SignatureVersion::V4),
+        V5 => sig_version == SignatureVersion::V5,
+        Other(_) => true,
+    };
+    if !valid {
+        mismatches.push(VersionMismatch::SignatureVersion(typ, sig_version));
+    }
+
+    for subpacket in sig
+        .config
+        .hashed_subpackets()
+        .chain(sig.config.unhashed_subpackets())
+    {
+        match subpacket.data {
+            packet::SubpacketData::Issuer(_) if key_version == V5 => {
+                mismatches.push(VersionMismatch::IssuerKeyId(typ));
+            }
+            packet::SubpacketData::IssuerFingerprint(version, _)
+                if version != key_version && !matches!(key_version, Other(_)) =>
+            {
+                mismatches.push(VersionMismatch::IssuerFingerprint(typ, version));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The component of a key that a revocation signature applies to.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Revoked<'a> {
+    /// A key revocation, of the primary key and thereby the whole key.
+    PrimaryKey,
+    /// A subkey revocation, of the subkey with the given key id.
+    Subkey(KeyId),
+    /// A certification revocation of a User ID.
+    User(&'a SignedUser),
+    /// A certification revocation of a User Attribute.
+    UserAttribute(&'a SignedUserAttribute),
+}
+
+/// The state of the primary key binding signature ("backsig") of a signing capable subkey.
+///
+/// Only subkeys with a valid backsig are used for signing, without it anyone could claim
+/// someone else's signing key as their subkey.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Backsig {
+    /// The binding signature embeds a valid backsig.
+    Valid,
+    /// The binding signature embeds no backsig.
+    Missing,
+    /// The binding signature only embeds backsigs that do not verify.
+    Invalid,
+}
+
+/// Checks the backsig in `binding`, the latest binding signature of `subkey`.
+///
+/// Returns `None` if the subkey is not bound as signing capable.
+pub(crate) fn check_backsig(
+    binding: Option<&packet::Signature>,
+    subkey: &impl PublicKeyTrait,
+    primary: &impl PublicKeyTrait,
+) -> Option<Backsig> {
+    let binding = binding.filter(|sig| sig.key_flags().sign())?;
+    let mut backsigs = binding
+        .embedded_signatures()
+        .filter(|sig| sig.typ() == SignatureType::KeyBinding)
+        .peekable();
+
+    if backsigs.peek().is_none() {
+        Some(Backsig::Missing)
+    } else if binding.verify_backsig(subkey, primary).is_ok() {
+        Some(Backsig::Valid)
+    } else {
+        Some(Backsig::Invalid)
+    }
+}
+
+/// Returns when a subkey created at `created_at` expires, by its binding signature.
+pub(crate) fn binding_expires_at(
+    created_at: &DateTime<Utc>,
+    binding: &packet::Signature,
+) -> Option<DateTime<Utc>> {
+    let expiration = binding.key_expiration_time()?;
+    Some(*created_at + *expiration)
+}
+
+/// The kind of data an encryption subkey is used for, as stated by its key flags.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum EncryptionPurpose {
+    /// Keys that may encrypt communications or storage.
+    #[default]
+    Any,
+    /// Keys that may encrypt communications.
+    Communications,
+    /// Keys that may encrypt storage.
+    Storage,
+}
+
+impl EncryptionPurpose {
+    pub(crate) fn matches(self, flags: packet::KeyFlags) -> bool {
+        match self {
+            EncryptionPurpose::Any => flags.encrypt_comms() || flags.encrypt_storage(),
+            EncryptionPurpose::Communications => flags.encrypt_comms(),
+            EncryptionPurpose::Storage => flags.encrypt_storage(),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 #[allow(clippy::large_enum_variant)] // FIXME
 pub enum PublicOrSecret {
@@ -276,4 +674,11 @@ impl KeyTrait for PublicOrSecret {
             PublicOrSecret::Secret(k) => k.algorithm(),
         }
     }
+
+    fn key_version(&self) -> KeyVersion {
+        match self {
+            PublicOrSecret::Public(k) => k.key_version(),
+            PublicOrSecret::Secret(k) => k.key_version(),
+        }
+    }
 }
@@ -0,0 +1,539 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use rand::{CryptoRng, Rng};
+
+use crate::composed::message::{decrypt_pkesk_session_key, PlainSessionKey};
+use crate::composed::signed_key::SignedSecretKey;
+use crate::crypto::hash::HashAlgorithm;
+use crate::crypto::public_key::PublicKeyAlgorithm;
+use crate::errors::Result;
+use crate::packet::{self, PublicKeyEncryptedSessionKey};
+use crate::types::{
+    KeyId, KeyTrait, KeyVersion, Mpi, PublicKeyTrait, PublicParams, SecretKeyTrait, SecretParams,
+};
+
+/// A source of unlocked secret keys, e.g. an agent that caches key passwords.
+///
+/// The store performs the secret key operations itself, so applications can decrypt and sign
+/// without handling passwords. Keys are addressed by the fingerprint of the (sub)key.
+pub trait SecretKeyStore {
+    /// Returns the fingerprints of the (sub)keys in the store with the given key id.
+    ///
+    /// For the wildcard key id the fingerprints of all (sub)keys are returned.
+    fn find_keys(&self, key_id: &KeyId) -> Vec<Vec<u8>>;
+
+    /// Decrypts the session key of `packet` with the (sub)key with the given fingerprint.
+    fn decrypt_session_key(
+        &self,
+        fingerprint: &[u8],
+        packet: &PublicKeyEncryptedSessionKey,
+    ) -> Result<PlainSessionKey>;
+
+    /// Creates the raw signature over `digest` with the (sub)key with the given fingerprint.
+    fn create_signature(
+        &self,
+        fingerprint: &[u8],
+        hash: HashAlgorithm,
+        digest: &[u8],
+    ) -> Result<Vec<Mpi>>;
+}
+
+/// A secret (sub)key held by the [`MemoryKeyStore`].
+#[derive(Clone, Copy)]
+enum StoredKey<'a> {
+    Primary(&'a packet::SecretKey),
+    Subkey(&'a packet::SecretSubkey),
+}
+
+impl StoredKey<'_> {
+    fn is_encrypted(&self) -> bool {
+        match self {
+            StoredKey::Primary(key) => key.secret_params().is_encrypted(),
+            StoredKey::Subkey(key) => key.secret_params().is_encrypted(),
+        }
+    }
+
+    /// Decrypts the secret parameters of an encrypted key into an unlocked copy of the key.
+    fn unlock<F>(&self, key_pw: F) -> Result<UnlockedKey>
+    where
+        F: FnOnce() -> String,
+    {
+        let SecretParams::Encrypted(params) = self.secret_params() else {
+            bail!("key is not encrypted");
+        };
+        let plain = params.unlock(key_pw, self.algorithm(), self.public_params())?;
+        // validates the key material, a wrong password can still match the checksum
+        plain.as_ref().as_repr(self.public_params())?;
+
+        Ok(match self {
+            StoredKey::Primary(key) => UnlockedKey::Primary(packet::SecretKey {
+                details: key.details.clone(),
+                secret_params: SecretParams::Plain(plain),
+            }),
+            StoredKey::Subkey(key) => UnlockedKey::Subkey(packet::SecretSubkey {
+                details: key.details.clone(),
+                secret_params: SecretParams::Plain(plain),
+            }),
+        })
+    }
+
+    fn secret_params(&self) -> &SecretParams {
+        match self {
+            StoredKey::Primary(key) => key.secret_params(),
+            StoredKey::Subkey(key) => key.secret_params(),
+        }
+    }
+
+    fn algorithm(&self) -> PublicKeyAlgorithm {
+        match self {
+            StoredKey::Primary(key) => key.algorithm(),
+            StoredKey::Subkey(key) => key.algorithm(),
+        }
+    }
+
+    fn public_params(&self) -> &PublicParams {
+        match self {
+            StoredKey::Primary(key) => key.public_params(),
+            StoredKey::Subkey(key) => key.public_params(),
+        }
+    }
+
+    /// Decrypts the session key of `packet`, the key must not be encrypted.
+    fn decrypt_session_key(
+        &self,
+        packet: &PublicKeyEncryptedSessionKey,
+    ) -> Result<PlainSessionKey> {
+        match self {
+            StoredKey::Primary(key) => decrypt_pkesk_session_key(*key, String::new, packet),
+            StoredKey::Subkey(key) => decrypt_pkesk_session_key(*key, String::new, packet),
+        }
+    }
+
+    /// Signs `digest`, the key must not be encrypted.
+    fn create_signature(&self, hash: HashAlgorithm, digest: &[u8]) -> Result<Vec<Mpi>> {
+        match self {
+            StoredKey::Primary(key) => key.create_signature(String::new, hash, digest),
+            StoredKey::Subkey(key) => key.create_signature(String::new, hash, digest),
+        }
+    }
+}
+
+/// A copy of an encrypted (sub)key with decrypted secret parameters.
+///
+/// The secret key packets zeroize their secret parameters when dropped.
+enum UnlockedKey {
+    Primary(packet::SecretKey),
+    Subkey(packet::SecretSubkey),
+}
+
+impl UnlockedKey {
+    fn as_stored(&self) -> StoredKey<'_> {
+        match self {
+            UnlockedKey::Primary(key) => StoredKey::Primary(key),
+            UnlockedKey::Subkey(key) => StoredKey::Subkey(key),
+        }
+    }
+}
+
+/// An unlocked key cached by the [`MemoryKeyStore`], zeroized when it expires or is removed.
+struct CachedKey {
+    key: Arc<UnlockedKey>,
+    /// `None` if the key never expires.
+    expires_at: Option<Instant>,
+}
+
+impl CachedKey {
+    fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at
+            .map_or(false, |expires_at| expires_at <= now)
+    }
+}
+
+/// An in-memory [`SecretKeyStore`], that keeps unlocked keys for a limited time.
+///
+/// Keys are added locked, and must be [unlocked](MemoryKeyStore::unlock) with their password
+/// before they can be used. The decrypted key is then kept for the configured time to live,
+/// after which the key is locked again. The password itself is not kept. Keys that are not
+/// protected by a password are always unlocked.
+pub struct MemoryKeyStore {
+    keys: Vec<SignedSecretKey>,
+    ttl: Duration,
+    unlocked: Mutex<HashMap<Vec<u8>, CachedKey>>,
+}
+
+impl fmt::Debug for MemoryKeyStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryKeyStore")
+            .field("keys", &self.keys)
+            .field("ttl", &self.ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+impl MemoryKeyStore {
+    /// Creates an empty store, that keeps keys unlocked for `ttl` after unlocking them.
+    pub fn new(ttl: Duration) -> Self {
+        MemoryKeyStore {
+            keys: Vec::new(),
+            ttl,
+            unlocked: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Adds a key, including its secret subkeys, to the store.
+    pub fn add_key(&mut self, key: SignedSecretKey) {
+        self.keys.push(key);
+    }
+
+    pub fn keys(&self) -> &[SignedSecretKey] {
+        &self.keys
+    }
+
+    /// Unlocks the (sub)key with the given fingerprint.
+    ///
+    /// The key is decrypted with the password, and kept decrypted until the time to live
+    /// expires.
+    pub fn unlock<F>(&self, fingerprint: &[u8], key_pw: F) -> Result<()>
+    where
+        F: FnOnce() -> String,
+    {
+        let key = self.key(fingerprint)?;
+        if !key.is_encrypted() {
+            return Ok(());
+        }
+
+        let unlocked = key.unlock(key_pw)?;
+        let expires_at = Instant::now().checked_add(self.ttl);
+        self.unlocked().insert(
+            fingerprint.to_vec(),
+            CachedKey {
+                key: Arc::new(unlocked),
+                expires_at,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Locks the (sub)key with the given fingerprint again, discarding its decrypted copy.
+    pub fn lock(&self, fingerprint: &[u8]) {
+        self.unlocked().remove(fingerprint);
+    }
+
+    /// Locks all keys, discarding all decrypted copies.
+    pub fn lock_all(&self) {
+        self.unlocked().clear();
+    }
+
+    /// Returns true if the (sub)key with the given fingerprint can currently be used.
+    pub fn is_unlocked(&self, fingerprint: &[u8]) -> bool {
+        match self.key(fingerprint) {
+            Ok(key) => !key.is_encrypted() || self.unlocked_key(fingerprint).is_some(),
+            Err(_) => false,
+        }
+    }
+
+    fn unlocked(&self) -> std::sync::MutexGuard<'_, HashMap<Vec<u8>, CachedKey>> {
+        self.unlocked.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns the unlocked key for the given fingerprint, dropping all expired keys.
+    fn unlocked_key(&self, fingerprint: &[u8]) -> Option<Arc<UnlockedKey>> {
+        let now = Instant::now();
+        let mut unlocked = self.unlocked();
+        unlocked.retain(|_, cached| !cached.is_expired(now));
+        unlocked.get(fingerprint).map(|cached| cached.key.clone())
+    }
+
+    fn key(&self, fingerprint: &[u8]) -> Result<StoredKey<'_>> {
+        for key in &self.keys {
+            if key.primary_key.fingerprint() == fingerprint {
+                return Ok(StoredKey::Primary(&key.primary_key));
+            }
+            for subkey in &key.secret_subkeys {
+                if subkey.key.fingerprint() == fingerprint {
+                    return Ok(StoredKey::Subkey(&subkey.key));
+                }
+            }
+        }
+
+        bail!("no key with fingerprint {}", hex::encode_upper(fingerprint))
+    }
+
+    /// Runs `work` with the usable, unencrypted version of the given key.
+    fn with_key<T, G>(&self, fingerprint: &[u8], work: G) -> Result<T>
+    where
+        G: FnOnce(StoredKey<'_>) -> Result<T>,
+    {
+        let key = self.key(fingerprint)?;
+        if !key.is_encrypted() {
+            return work(key);
+        }
+
+        match self.unlocked_key(fingerprint) {
+            Some(unlocked) => work(unlocked.as_stored()),
+            None => bail!("key {} is locked", hex::encode_upper(fingerprint)),
+        }
+    }
+}
+
+impl SecretKeyStore for MemoryKeyStore {
+    fn find_keys(&self, key_id: &KeyId) -> Vec<Vec<u8>> {
+        let mut fingerprints = Vec::new();
+        for key in &self.keys {
+            if key_id.is_wildcard() || &key.primary_key.key_id() == key_id {
+                fingerprints.push(key.primary_key.fingerprint());
+            }
+            for subkey in &key.secret_subkeys {
+                if key_id.is_wildcard() || &subkey.key.key_id() == key_id {
+                    fingerprints.push(subkey.key.fingerprint());
+                }
+            }
+        }
+
+        fingerprints
+    }
+
+    fn decrypt_session_key(
+        &self,
+        fingerprint: &[u8],
+        packet: &PublicKeyEncryptedSessionKey,
+    ) -> Result<PlainSessionKey> {
+        self.with_key(fingerprint, |key| key.decrypt_session_key(packet))
+    }
+
+    fn create_signature(
+        &self,
+        fingerprint: &[u8],
+        hash: HashAlgorithm,
+        digest: &[u8],
+    ) -> Result<Vec<Mpi>> {
+        self.with_key(fingerprint, |key| key.create_signature(hash, digest))
+    }
+}
+
+/// Signs with a key whose secret operations are performed by a [`SecretKeyStore`].
+///
+/// The wrapped key only provides the public parts, its secret parameters are never unlocked.
+/// The signer can be used wherever a [`SecretKeyTrait`] is expected, with an empty password.
+///
+/// ```
+/// # #[cfg(feature = "rsa")]
+/// # fn main() -> pgp::errors::Result<()> {
+/// use std::time::Duration;
+///
+/// use pgp::composed::{Deserializable, MemoryKeyStore, Message, SignedSecretKey, StoreSigner};
+/// use pgp::crypto::hash::HashAlgorithm;
+/// use pgp::types::{KeyTrait, SecretKeyTrait};
+///
+/// let (key, _) = SignedSecretKey::from_armor_single(std::fs::File::open(
+///     "./tests/opengpg-interop/testcases/messages/gnupg-v1-001-decrypt.asc",
+/// )?)?;
+/// let fingerprint = key.fingerprint();
+///
+/// let mut store = MemoryKeyStore::new(Duration::from_secs(60));
+/// store.add_key(key.clone());
+/// store.unlock(&fingerprint, || "test".to_string())?;
+///
+/// let signer = StoreSigner::new(&store, &key);
+/// let message = Message::new_literal("hello.txt", "hello world\n");
+/// let signed = message.sign(&signer, String::new, HashAlgorithm::SHA2_256)?;
+/// signed.verify(&key.public_key())?;
+/// # Ok(())
+/// # }
+/// # #[cfg(not(feature = "rsa"))]
+/// # fn main() {}
+/// ```
+pub struct StoreSigner<'a, K> {
+    store: &'a dyn SecretKeyStore,
+    key: K,
+}
+
+impl<K: fmt::Debug> fmt::Debug for StoreSigner<'_, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StoreSigner")
+            .field("key", &self.key)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a, K: SecretKeyTrait> StoreSigner<'a, K> {
+    pub fn new(store: &'a dyn SecretKeyStore, key: K) -> Self {
+        StoreSigner { store, key }
+    }
+}
+
+impl<K: SecretKeyTrait> KeyTrait for StoreSigner<'_, K> {
+    fn fingerprint(&self) -> Vec<u8> {
+        self.key.fingerprint()
+    }
+
+    fn key_id(&self) -> KeyId {
+        self.key.key_id()
+    }
+
+    fn algorithm(&self) -> PublicKeyAlgorithm {
+        self.key.algorithm()
+    }
+
+    fn key_version(&self) -> KeyVersion {
+        self.key.key_version()
+    }
+
+    fn key_flags(&self) -> Option<packet::KeyFlags> {
+        self.key.key_flags()
+    }
+}
+
+impl<K: SecretKeyTrait> PublicKeyTrait for StoreSigner<'_, K> {
+    fn verify_signature(&self, hash: HashAlgorithm, data: &[u8], sig: &[Mpi]) -> Result<()> {
+        self.key.verify_signature(hash, data, sig)
+    }
+
+    fn encrypt<R: CryptoRng + Rng>(&self, rng: &mut R, plain: &[u8]) -> Result<Vec<Mpi>> {
+        self.key.encrypt(rng, plain)
+    }
+
+    fn to_writer_old(&self, writer: &mut impl io::Write) -> Result<()> {
+        self.key.to_writer_old(writer)
+    }
+}
+
+impl<K: SecretKeyTrait> SecretKeyTrait for StoreSigner<'_, K> {
+    type PublicKey = K::PublicKey;
+    type Unlocked = K::Unlocked;
+
+    fn unlock<F, G, T>(&self, _pw: F, _work: G) -> Result<T>
+    where
+        F: FnOnce() -> String,
+        G: FnOnce(&Self::Unlocked) -> Result<T>,
+    {
+        bail!("keys in a secret key store can not be unlocked directly")
+    }
+
+    fn create_signature<F>(&self, _key_pw: F, hash: HashAlgorithm, data: &[u8]) -> Result<Vec<Mpi>>
+    where
+        F: FnOnce() -> String,
+    {
+        self.store
+            .create_signature(&self.key.fingerprint(), hash, data)
+    }
+
+    fn public_key(&self) -> Self::PublicKey {
+        self.key.public_key()
+    }
+
+    fn public_params(&self) -> &PublicParams {
+        self.key.public_params()
+    }
+
+    fn hash_alg(&self) -> HashAlgorithm {
+        self.key.hash_alg()
+    }
+
+    fn supported_hash_algs(&self) -> Option<&[HashAlgorithm]> {
+        self.key.supported_hash_algs()
+    }
+}
+
+#[cfg(all(test, feature = "rsa"))]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use std::fs;
+
+    use rand::thread_rng;
+
+    use super::*;
+    use crate::composed::{Deserializable, Message};
+    use crate::crypto::sym::SymmetricKeyAlgorithm;
+
+    fn rsa_key() -> SignedSecretKey {
+        let (key, _headers) = SignedSecretKey::from_armor_single(
+            fs::File::open("./tests/opengpg-interop/testcases/messages/gnupg-v1-001-decrypt.asc")
+                .unwrap(),
+        )
+        .unwrap();
+        key
+    }
+
+    #[test]
+    fn test_memory_key_store_decrypt() {
+        let key = rsa_key();
+        let subkey = &key.secret_subkeys[0];
+        let fingerprint = subkey.fingerprint();
+
+        let mut store = MemoryKeyStore::new(Duration::from_secs(60));
+        store.add_key(key.clone());
+        assert_eq!(store.find_keys(&subkey.key_id()), vec![fingerprint.clone()]);
+        assert_eq!(
+            store.find_keys(&KeyId::from_slice(&[0; 8]).unwrap()).len(),
+            2
+        );
+
+        let lit_msg = Message::new_literal("hello.txt", "hello world\n");
+        let pkey = subkey.public_key();
+        let encrypted = lit_msg
+            .encrypt_to_keys(
+                &mut thread_rng(),
+                SymmetricKeyAlgorithm::AES128,
+                &[&pkey][..],
+            )
+            .unwrap();
+
+        // locked
+        assert!(!store.is_unlocked(&fingerprint));
+        assert!(encrypted.decrypt_with_store(&store).is_err());
+
+        // wrong password
+        assert!(store.unlock(&fingerprint, || "wrong".to_string()).is_err());
+        assert!(!store.is_unlocked(&fingerprint));
+
+        store.unlock(&fingerprint, || "test".to_string()).unwrap();
+        assert!(store.is_unlocked(&fingerprint));
+        let (decrypted, used) = encrypted.decrypt_with_store(&store).unwrap();
+        assert_eq!(decrypted, lit_msg);
+        assert_eq!(used, fingerprint);
+
+        store.lock(&fingerprint);
+        assert!(encrypted.decrypt_with_store(&store).is_err());
+
+        // expired right away
+        let mut store = MemoryKeyStore::new(Duration::ZERO);
+        store.add_key(key);
+        store.unlock(&fingerprint, || "test".to_string()).unwrap();
+        assert!(!store.is_unlocked(&fingerprint));
+        assert!(encrypted.decrypt_with_store(&store).is_err());
+    }
+
+    #[test]
+    fn test_memory_key_store_sign() {
+        let key = rsa_key();
+        let fingerprint = key.primary_key.fingerprint();
+
+        let mut store = MemoryKeyStore::new(Duration::from_secs(60));
+        store.add_key(key.clone());
+
+        let lit_msg = Message::new_literal("hello.txt", "hello world\n");
+        assert!(lit_msg
+            .clone()
+            .sign_with_store(&key, &store, HashAlgorithm::SHA2_256)
+            .is_err());
+
+        store.unlock(&fingerprint, || "test".to_string()).unwrap();
+        // the decrypted key is kept, signing does not derive the key from the password again
+        let unlocked = store.unlocked_key(&fingerprint).unwrap();
+        assert!(matches!(
+            unlocked.as_stored().secret_params(),
+            SecretParams::Plain(_)
+        ));
+        let signed = lit_msg
+            .sign_with_store(&key, &store, HashAlgorithm::SHA2_256)
+            .unwrap();
+        signed.verify(&key.public_key()).unwrap();
+    }
+}
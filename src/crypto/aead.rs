@@ -1,14 +1,14 @@
 use aes::{Aes128, Aes256};
-use aes_gcm::{
-    aead::{AeadInPlace, KeyInit},
-    Aes128Gcm, Aes256Gcm, Key as GcmKey, Nonce as GcmNonce, Tag as GcmTag,
-};
+#[cfg(feature = "aead-gcm")]
+use aes_gcm::{Aes128Gcm, Aes256Gcm, Key as GcmKey, Nonce as GcmNonce, Tag as GcmTag};
+#[cfg(feature = "aead-eax")]
 use eax::{Eax, Key as EaxKey, Nonce as EaxNonce, Tag as EaxTag};
 use generic_array::{
     typenum::{U15, U16},
     GenericArray,
 };
 use num_enum::{FromPrimitive, IntoPrimitive};
+use ocb3::aead::{AeadInPlace, KeyInit};
 use ocb3::{Nonce as Ocb3Nonce, Ocb3, Tag as OcbTag};
 
 use crate::errors::{Error, Result};
@@ -75,6 +75,35 @@ impl AeadAlgorithm {
         }
     }
 
+    /// Checks that this mode can be used with `sym_algorithm`.
+    ///
+    /// The modes defined by RFC 9580 are only implemented for AES128 and AES256, EAX and GCM
+    /// only with the `aead-eax` and `aead-gcm` features. The error names the unsupported
+    /// combination, so messages using it can still be parsed and inspected.
+    pub fn check_supported(&self, sym_algorithm: SymmetricKeyAlgorithm) -> Result<()> {
+        match self {
+            Self::Eax if !cfg!(feature = "aead-eax") => {
+                unsupported_err!("AEAD algorithm EAX (feature \"aead-eax\" is disabled)")
+            }
+            Self::Gcm if !cfg!(feature = "aead-gcm") => {
+                unsupported_err!("AEAD algorithm GCM (feature \"aead-gcm\" is disabled)")
+            }
+            Self::Eax | Self::Ocb | Self::Gcm => match sym_algorithm {
+                SymmetricKeyAlgorithm::AES128 | SymmetricKeyAlgorithm::AES256 => Ok(()),
+                _ => unsupported_err!(
+                    "AEAD algorithm {:?} is only supported with AES128 and AES256, not {:?}",
+                    self,
+                    sym_algorithm
+                ),
+            },
+            Self::None => unsupported_err!("AEAD algorithm None can not be used for encryption"),
+            _ => unsupported_err!(
+                "AEAD algorithm {} is not supported, only EAX (1), OCB (2) and GCM (3) are",
+                u8::from(*self)
+            ),
+        }
+    }
+
     /// Decrypt the provided data in place.
     pub fn decrypt_in_place(
         &self,
@@ -86,6 +115,7 @@ impl AeadAlgorithm {
         buffer: &mut [u8],
     ) -> Result<()> {
         match (sym_algorithm, self) {
+            #[cfg(feature = "aead-gcm")]
             (SymmetricKeyAlgorithm::AES128, AeadAlgorithm::Gcm) => {
                 let key = GcmKey::<Aes128Gcm>::from_slice(&key[..16]);
                 let cipher = Aes128Gcm::new(key);
@@ -95,6 +125,7 @@ impl AeadAlgorithm {
                     .decrypt_in_place_detached(nonce, associated_data, buffer, tag)
                     .map_err(|_| Error::Gcm)?;
             }
+            #[cfg(feature = "aead-gcm")]
             (SymmetricKeyAlgorithm::AES256, AeadAlgorithm::Gcm) => {
                 let key = GcmKey::<Aes256Gcm>::from_slice(&key[..32]);
                 let cipher = Aes256Gcm::new(key);
@@ -104,6 +135,7 @@ impl AeadAlgorithm {
                     .decrypt_in_place_detached(nonce, associated_data, buffer, tag)
                     .map_err(|_| Error::Gcm)?;
             }
+            #[cfg(feature = "aead-eax")]
             (SymmetricKeyAlgorithm::AES128, AeadAlgorithm::Eax) => {
                 let key = EaxKey::<Aes128>::from_slice(&key[..16]);
                 let cipher = Eax::<Aes128>::new(key);
@@ -113,6 +145,7 @@ impl AeadAlgorithm {
                     .decrypt_in_place_detached(nonce, associated_data, buffer, tag)
                     .map_err(|_| Error::Eax)?;
             }
+            #[cfg(feature = "aead-eax")]
             (SymmetricKeyAlgorithm::AES256, AeadAlgorithm::Eax) => {
                 let key = EaxKey::<Aes256>::from_slice(&key[..32]);
                 let cipher = Eax::<Aes256>::new(key);
@@ -156,6 +189,7 @@ impl AeadAlgorithm {
         buffer: &mut [u8],
     ) -> Result<Vec<u8>> {
         let tag = match (sym_algorithm, self) {
+            #[cfg(feature = "aead-gcm")]
             (SymmetricKeyAlgorithm::AES128, AeadAlgorithm::Gcm) => {
                 let key = GcmKey::<Aes128Gcm>::from_slice(&key[..16]);
                 let cipher = Aes128Gcm::new(key);
@@ -164,6 +198,7 @@ impl AeadAlgorithm {
                     .encrypt_in_place_detached(nonce, associated_data, buffer)
                     .map_err(|_| Error::Gcm)?
             }
+            #[cfg(feature = "aead-gcm")]
             (SymmetricKeyAlgorithm::AES256, AeadAlgorithm::Gcm) => {
                 let key = GcmKey::<Aes256Gcm>::from_slice(&key[..32]);
                 let cipher = Aes256Gcm::new(key);
@@ -172,6 +207,7 @@ impl AeadAlgorithm {
                     .encrypt_in_place_detached(nonce, associated_data, buffer)
                     .map_err(|_| Error::Gcm)?
             }
+            #[cfg(feature = "aead-eax")]
             (SymmetricKeyAlgorithm::AES128, AeadAlgorithm::Eax) => {
                 let key = EaxKey::<Aes128>::from_slice(&key[..16]);
                 let cipher = Eax::<Aes128>::new(key);
@@ -180,6 +216,7 @@ impl AeadAlgorithm {
                     .encrypt_in_place_detached(nonce, associated_data, buffer)
                     .map_err(|_| Error::Eax)?
             }
+            #[cfg(feature = "aead-eax")]
             (SymmetricKeyAlgorithm::AES256, AeadAlgorithm::Eax) => {
                 let key = EaxKey::<Aes256>::from_slice(&key[..32]);
                 let cipher = Eax::<Aes256>::new(key);
@@ -210,3 +247,34 @@ impl AeadAlgorithm {
         Ok(tag.to_vec())
     }
 }
+
+/// Chooses the AEAD mode to encrypt for a recipient with.
+///
+/// The first mode in the recipient's preferences that is in `our_supported` is used.
+/// Without a mutual choice OCB, which all implementations must support, is used.
+pub fn negotiate_aead_algorithm(
+    our_supported: &[AeadAlgorithm],
+    their_preferred: &[AeadAlgorithm],
+) -> AeadAlgorithm {
+    their_preferred
+        .iter()
+        .find(|alg| **alg != AeadAlgorithm::None && our_supported.contains(alg))
+        .copied()
+        .unwrap_or(AeadAlgorithm::Ocb)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate_aead_algorithm() {
+        use AeadAlgorithm::*;
+
+        let ours = [Ocb, Eax, Gcm];
+        assert_eq!(negotiate_aead_algorithm(&ours, &[Gcm, Ocb]), Gcm);
+        assert_eq!(negotiate_aead_algorithm(&ours, &[Private100, Eax]), Eax);
+        assert_eq!(negotiate_aead_algorithm(&ours, &[None, Private100]), Ocb);
+        assert_eq!(negotiate_aead_algorithm(&[], &[Gcm]), Ocb);
+    }
+}
@@ -4,14 +4,22 @@ use generic_array::sequence::{Concat, Split};
 use generic_array::typenum::U8;
 use generic_array::GenericArray;
 
-use crate::errors::Result;
+use crate::errors::{Error, Result};
 
 const IV: [u8; 8] = [0xA6, 0xA6, 0xA6, 0xA6, 0xA6, 0xA6, 0xA6, 0xA6];
 
+/// Our default target length for padded session keys (based on the size of a padded AES256 key).
+/// This value should be increased if we support symmetric keys that are longer than AES256.
+const PAD_DEFAULT_TARGET: usize = 40;
+
 /// AES Key Wrap
 /// As defined in RFC 3394.
+///
+/// The size of `key` selects the AES variant and must be 16, 24 or 32 bytes.
+/// `data` must be a multiple of 64 bits, and at least 128 bits long.
 pub fn wrap(key: &[u8], data: &[u8]) -> Result<Vec<u8>> {
     ensure_eq!(data.len() % 8, 0, "data must be a multiple of 64bit");
+    ensure!(data.len() >= 16, "data must be at least 128bit");
 
     let aes_size = key.len() * 8;
     match aes_size {
@@ -24,8 +32,13 @@ pub fn wrap(key: &[u8], data: &[u8]) -> Result<Vec<u8>> {
 
 /// AES Key Unwrap
 /// As defined in RFC 3394.
+///
+/// The size of `key` selects the AES variant and must be 16, 24 or 32 bytes.
+/// `data` must be a multiple of 64 bits, and at least 192 bits long.
+/// Fails if the integrity check of the unwrapped data fails.
 pub fn unwrap(key: &[u8], data: &[u8]) -> Result<Vec<u8>> {
     ensure_eq!(data.len() % 8, 0, "data must be a multiple of 64bit");
+    ensure!(data.len() >= 24, "data must be at least 192bit");
 
     let aes_size = key.len() * 8;
     match aes_size {
@@ -36,6 +49,79 @@ pub fn unwrap(key: &[u8], data: &[u8]) -> Result<Vec<u8>> {
     }
 }
 
+/// PKCS5-style padding of a session key, with a block-size of 8, as used by ECDH.
+///
+/// RFC 6637 describes the padding:
+/// a) "The result is padded using the method described in [PKCS5] to the 8-byte granularity."
+/// b) "For example, assuming that an AES algorithm is used for the session key, the sender MAY
+/// use 21, 13, and 5 bytes of padding for AES-128, AES-192, and AES-256, respectively, to
+/// provide the same number of octets, 40 total, as an input to the key wrapping method."
+///
+/// So the padding may exceed the length of one block, to obfuscate the key size.
+/// Ref: https://www.rfc-editor.org/rfc/rfc6637.html#section-8
+pub fn pad_session_key(plain: &[u8]) -> Vec<u8> {
+    let len = plain.len();
+
+    // The padded message length (must be a multiple of the block size)
+    let padded_len = if len < PAD_DEFAULT_TARGET {
+        // Normally, we just pad to the default target size ...
+        PAD_DEFAULT_TARGET
+    } else {
+        // ... but if `plain` isn't shorter than our target size, we pad to the next full block
+        let remainder = len % 8; // e.g. 3 for len==19
+
+        len + 8 - remainder // (e.g. "8 + 8 - 0 => 16", or "19 + 8 - 3 => 24")
+    };
+    debug_assert!(padded_len % 8 == 0, "Unexpected padded_len {}", padded_len);
+
+    // The value we'll use for padding (must not be zero, and fit into a u8)
+    let padding = padded_len - len;
+    debug_assert!(
+        padding > 0 && u8::try_from(padding).is_ok(),
+        "Unexpected padding value {}",
+        padding
+    );
+    let padding = padding as u8;
+
+    // Extend length of plain_padded, fill with `padding` value
+    let mut plain_padded = plain.to_vec();
+    plain_padded.resize(padded_len, padding);
+
+    plain_padded
+}
+
+/// Removes the padding added by [`pad_session_key`].
+///
+/// The padded length must be a multiple of 8, but the padding itself may exceed 8 bytes.
+/// Rejects a padding of zero, a padding that covers the whole padded key, and padding bytes
+/// that do not all match the padding length.
+pub fn unpad_session_key(mut padded: Vec<u8>) -> Result<Vec<u8>> {
+    let len = padded.len();
+    let block_size = 8;
+    ensure!(len % block_size == 0, "invalid key length {}", len);
+    ensure!(!padded.is_empty(), "empty key is not valid");
+
+    // The last byte should contain the padding symbol, which is also the padding length
+    let pad = *padded.last().expect("is not empty");
+
+    // PKCS5 always adds at least one byte of padding, and leaves some key
+    if pad == 0 || usize::from(pad) >= len {
+        return Err(Error::UnpadError);
+    }
+
+    // Expected length of the unpadded message
+    let unpadded_len = len - usize::from(pad);
+
+    // All bytes that constitute the padding must have the value of `pad`
+    if padded[unpadded_len..].iter().any(|byte| *byte != pad) {
+        return Err(Error::UnpadError);
+    }
+
+    padded.truncate(unpadded_len);
+
+    Ok(padded)
+}
+
 macro_rules! impl_aes_kw {
     ($name_wrap:ident, $name_unwrap:ident, $size:expr, $hasher:ty) => {
         #[inline]
@@ -163,6 +249,8 @@ impl_aes_kw!(wrap_256, unwrap_256, 256, aes::Aes256);
 
 #[cfg(test)]
 mod tests {
+    #![allow(clippy::unwrap_used)]
+
     use super::*;
 
     macro_rules! test_aes_kw {
@@ -225,4 +313,76 @@ mod tests {
         "00112233445566778899AABBCCDDEEFF000102030405060708090A0B0C0D0E0F",
         "28C9F404C4B810F4CBCCB35CFB87F8263F5786E2D80ED326CBC7F0E71A99F43BFB988B9B7A02DD21"
     );
+
+    #[test]
+    fn test_invalid_sizes() {
+        let kek = [0u8; 16];
+        let data = [0u8; 24];
+
+        // unsupported kek sizes
+        assert!(wrap(&kek[..15], &data).is_err());
+        assert!(unwrap(&[0u8; 20], &data).is_err());
+
+        // data too short, or not a multiple of 64bit
+        assert!(wrap(&kek, &data[..8]).is_err());
+        assert!(wrap(&kek, &data[..17]).is_err());
+        assert!(unwrap(&kek, &data[..16]).is_err());
+        assert!(unwrap(&kek, &data[..23]).is_err());
+    }
+
+    #[test]
+    fn test_session_key_padding() {
+        for len in [16, 24, 32, 39, 40, 41] {
+            let key = vec![0xAB; len];
+            let padded = pad_session_key(&key);
+
+            assert_eq!(padded.len() % 8, 0);
+            assert!(padded.len() >= 40);
+            assert_eq!(unpad_session_key(padded).unwrap(), key);
+        }
+
+        // AES-128 key with 24 bytes of padding
+        assert_eq!(pad_session_key(&[0u8; 16])[16..], [24u8; 24]);
+
+        // not a multiple of the block size
+        assert!(unpad_session_key(vec![1, 2, 3]).is_err());
+        assert!(unpad_session_key(vec![]).is_err());
+    }
+
+    #[test]
+    fn test_unpad_session_key_zero_padding() {
+        let mut padded = vec![0xAB; 40];
+        padded[39] = 0;
+        assert!(matches!(unpad_session_key(padded), Err(Error::UnpadError)));
+    }
+
+    #[test]
+    fn test_unpad_session_key_padding_too_long() {
+        // longer than the padded key
+        assert!(matches!(
+            unpad_session_key(vec![9u8; 8]),
+            Err(Error::UnpadError)
+        ));
+        // covers the whole padded key, leaving no key
+        assert!(matches!(
+            unpad_session_key(vec![8u8; 8]),
+            Err(Error::UnpadError)
+        ));
+        assert!(matches!(
+            unpad_session_key(vec![40u8; 40]),
+            Err(Error::UnpadError)
+        ));
+    }
+
+    #[test]
+    fn test_unpad_session_key_inconsistent_padding() {
+        assert!(matches!(
+            unpad_session_key(vec![1, 2, 3, 4, 5, 6, 2, 3]),
+            Err(Error::UnpadError)
+        ));
+
+        let mut padded = pad_session_key(&[0xAB; 16]);
+        padded[20] ^= 1;
+        assert!(matches!(unpad_session_key(padded), Err(Error::UnpadError)));
+    }
 }
@@ -46,6 +46,7 @@ impl Signer for SecretKey {
             // (See https://github.com/RustCrypto/hashes/pull/582)
             HashAlgorithm::SHA1 => signing_key.sign_prehashed_rfc6979::<sha1::Sha1>(digest),
 
+            #[cfg(feature = "ripemd160")]
             HashAlgorithm::RIPEMD160 => {
                 signing_key.sign_prehashed_rfc6979::<ripemd::Ripemd160>(digest)
             }
@@ -53,7 +54,9 @@ impl Signer for SecretKey {
             HashAlgorithm::SHA2_384 => signing_key.sign_prehashed_rfc6979::<sha2::Sha384>(digest),
             HashAlgorithm::SHA2_512 => signing_key.sign_prehashed_rfc6979::<sha2::Sha512>(digest),
             HashAlgorithm::SHA2_224 => signing_key.sign_prehashed_rfc6979::<sha2::Sha224>(digest),
+            #[cfg(feature = "sha3")]
             HashAlgorithm::SHA3_256 => signing_key.sign_prehashed_rfc6979::<sha3::Sha3_256>(digest),
+            #[cfg(feature = "sha3")]
             HashAlgorithm::SHA3_512 => signing_key.sign_prehashed_rfc6979::<sha3::Sha3_512>(digest),
             _ => unimplemented_err!("hasher {:?}", hash_algorithm),
         }?;
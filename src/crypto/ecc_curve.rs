@@ -114,7 +114,11 @@ impl ECCCurve {
         }
     }
 
-    /// Default hash algorithm for this curve
+    /// Default hash algorithm for this curve, used as the KDF hash of ECDH keys.
+    ///
+    /// Values as in <https://www.rfc-editor.org/rfc/rfc9580.html#section-9.2>, fails with
+    /// [`Error::Unsupported`](crate::errors::Error::Unsupported) for unknown curves, which
+    /// have no defined parameters.
     pub fn hash_algo(&self) -> crate::errors::Result<HashAlgorithm> {
         match self {
             ECCCurve::Curve25519
@@ -128,12 +132,17 @@ impl ECCCurve {
             ECCCurve::P521 | ECCCurve::BrainpoolP512r1 => Ok(HashAlgorithm::SHA2_512),
 
             ECCCurve::Unknown(_oid) => {
-                unsupported_err!("no default hash_algo for curve {:?}", self.to_string())
+                unsupported_err!("no default hash algorithm for curve {}", self.oid_str())
             }
         }
     }
 
-    /// Default symmetric encryption algorithm for this curve
+    /// Default symmetric encryption algorithm for this curve, used as the KEK algorithm of
+    /// ECDH keys.
+    ///
+    /// Values as in <https://www.rfc-editor.org/rfc/rfc9580.html#section-9.2>, fails with
+    /// [`Error::Unsupported`](crate::errors::Error::Unsupported) for unknown curves, which
+    /// have no defined parameters.
     pub fn sym_algo(&self) -> crate::errors::Result<SymmetricKeyAlgorithm> {
         match self {
             ECCCurve::Curve25519
@@ -147,57 +156,74 @@ impl ECCCurve {
             ECCCurve::P521 | ECCCurve::BrainpoolP512r1 => Ok(SymmetricKeyAlgorithm::AES256),
 
             ECCCurve::Unknown(_oid) => {
-                unsupported_err!("no default sym_algo for curve {:?}", self.to_string())
+                unsupported_err!(
+                    "no default symmetric algorithm for curve {}",
+                    self.oid_str()
+                )
             }
         }
     }
 
     pub fn oid(&self) -> Vec<u8> {
-        // the OID String is turned into bytes
-        // with the first two numbers combined
-        let mut id: Vec<u32> = self
-            .oid_str()
-            .split('.')
-            // safe as we hard coded these
-            .map(|v| v.parse::<u32>().expect("bad oid string"))
-            .collect();
+        self.oid_bytes().to_vec()
+    }
 
-        // combine the first two
-        let first = id.remove(0) * 40 + id.remove(0);
-        id.insert(0, first);
+    /// The OID in its binary encoding, as used in OpenPGP, without allocating.
+    pub(crate) fn oid_bytes(&self) -> &[u8] {
+        const CURVE25519: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.4.1.3029.1.5.1");
+        const ED25519: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.4.1.11591.15.1");
+        const P256: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.3.1.7");
+        const P384: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.132.0.34");
+        const P521: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.132.0.35");
+        const BRAINPOOL_P256R1: ObjectIdentifier =
+            ObjectIdentifier::new_unwrap("1.3.36.3.3.2.8.1.1.7");
+        const BRAINPOOL_P384R1: ObjectIdentifier =
+            ObjectIdentifier::new_unwrap("1.3.36.3.3.2.8.1.1.11");
+        const BRAINPOOL_P512R1: ObjectIdentifier =
+            ObjectIdentifier::new_unwrap("1.3.36.3.3.2.8.1.1.13");
+        const SECP256K1: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.132.0.10");
 
-        id.iter()
-            .flat_map(|ident| asn1_der_object_id_val_enc(*ident))
-            .collect()
+        match self {
+            ECCCurve::Curve25519 => CURVE25519.as_bytes(),
+            ECCCurve::Ed25519 => ED25519.as_bytes(),
+            ECCCurve::P256 => P256.as_bytes(),
+            ECCCurve::P384 => P384.as_bytes(),
+            ECCCurve::P521 => P521.as_bytes(),
+            ECCCurve::BrainpoolP256r1 => BRAINPOOL_P256R1.as_bytes(),
+            ECCCurve::BrainpoolP384r1 => BRAINPOOL_P384R1.as_bytes(),
+            ECCCurve::BrainpoolP512r1 => BRAINPOOL_P512R1.as_bytes(),
+            ECCCurve::Secp256k1 => SECP256K1.as_bytes(),
+            ECCCurve::Unknown(oid) => oid.as_bytes(),
+        }
     }
 }
 /// Get the right curve given an oid.
 pub fn ecc_curve_from_oid(oid: &[u8]) -> Option<ECCCurve> {
-    if ECCCurve::Curve25519.oid().as_slice() == oid {
+    if ECCCurve::Curve25519.oid_bytes() == oid {
         return Some(ECCCurve::Curve25519);
     }
-    if ECCCurve::Ed25519.oid().as_slice() == oid {
+    if ECCCurve::Ed25519.oid_bytes() == oid {
         return Some(ECCCurve::Ed25519);
     }
-    if ECCCurve::P256.oid().as_slice() == oid {
+    if ECCCurve::P256.oid_bytes() == oid {
         return Some(ECCCurve::P256);
     }
-    if ECCCurve::P384.oid().as_slice() == oid {
+    if ECCCurve::P384.oid_bytes() == oid {
         return Some(ECCCurve::P384);
     }
-    if ECCCurve::P521.oid().as_slice() == oid {
+    if ECCCurve::P521.oid_bytes() == oid {
         return Some(ECCCurve::P521);
     }
-    if ECCCurve::BrainpoolP256r1.oid().as_slice() == oid {
+    if ECCCurve::BrainpoolP256r1.oid_bytes() == oid {
         return Some(ECCCurve::BrainpoolP256r1);
     }
-    if ECCCurve::BrainpoolP384r1.oid().as_slice() == oid {
+    if ECCCurve::BrainpoolP384r1.oid_bytes() == oid {
         return Some(ECCCurve::BrainpoolP384r1);
     }
-    if ECCCurve::BrainpoolP512r1.oid().as_slice() == oid {
+    if ECCCurve::BrainpoolP512r1.oid_bytes() == oid {
         return Some(ECCCurve::BrainpoolP512r1);
     }
-    if ECCCurve::Secp256k1.oid().as_slice() == oid {
+    if ECCCurve::Secp256k1.oid_bytes() == oid {
         return Some(ECCCurve::Secp256k1);
     }
 
@@ -208,19 +234,6 @@ pub fn ecc_curve_from_oid(oid: &[u8]) -> Option<ECCCurve> {
     }
 }
 
-fn asn1_der_object_id_val_enc(val: u32) -> Vec<u8> {
-    let mut val = val;
-    let mut acc = vec![(val & 0x7f) as u8];
-    val >>= 7;
-
-    while val > 0 {
-        acc.insert(0, (0x80 | (val & 0x7f)) as u8);
-        val >>= 7;
-    }
-
-    acc
-}
-
 impl ToString for ECCCurve {
     fn to_string(&self) -> String {
         self.name().to_string()
@@ -278,8 +291,56 @@ mod tests {
     }
 
     #[test]
-    fn test_asn1_der_object_id_val_enc() {
-        assert_eq!(asn1_der_object_id_val_enc(840), vec![0x86, 0x48]);
-        assert_eq!(asn1_der_object_id_val_enc(113_549), vec![0x86, 0xf7, 0x0d]);
+    fn test_kdf_parameters() {
+        use crate::errors::Error;
+
+        assert_eq!(
+            ECCCurve::Curve25519.hash_algo().unwrap(),
+            HashAlgorithm::SHA2_256
+        );
+        assert_eq!(
+            ECCCurve::Curve25519.sym_algo().unwrap(),
+            SymmetricKeyAlgorithm::AES128
+        );
+        assert_eq!(
+            ECCCurve::BrainpoolP384r1.hash_algo().unwrap(),
+            HashAlgorithm::SHA2_384
+        );
+        assert_eq!(
+            ECCCurve::BrainpoolP384r1.sym_algo().unwrap(),
+            SymmetricKeyAlgorithm::AES192
+        );
+        assert_eq!(ECCCurve::P521.hash_algo().unwrap(), HashAlgorithm::SHA2_512);
+        assert_eq!(
+            ECCCurve::P521.sym_algo().unwrap(),
+            SymmetricKeyAlgorithm::AES256
+        );
+
+        let unknown =
+            ecc_curve_from_oid(&[0x2B, 0x24, 0x03, 0x03, 0x02, 0x08, 0x01, 0x01, 0x01]).unwrap();
+        assert!(
+            matches!(unknown.hash_algo(), Err(Error::Unsupported(msg)) if msg.contains("1.3.36.3.3.2.8.1.1.1"))
+        );
+        assert!(matches!(unknown.sym_algo(), Err(Error::Unsupported(_))));
+    }
+
+    #[test]
+    fn test_oid_bytes() {
+        for curve in [
+            ECCCurve::Curve25519,
+            ECCCurve::Ed25519,
+            ECCCurve::P256,
+            ECCCurve::P384,
+            ECCCurve::P521,
+            ECCCurve::BrainpoolP256r1,
+            ECCCurve::BrainpoolP384r1,
+            ECCCurve::BrainpoolP512r1,
+            ECCCurve::Secp256k1,
+            ECCCurve::Unknown(ObjectIdentifier::new_unwrap("1.3.36.3.3.2.8.1.1.1")),
+        ] {
+            let oid = ObjectIdentifier::from_bytes(curve.oid_bytes()).unwrap();
+            assert_eq!(oid.to_string(), curve.oid_str());
+            assert_eq!(ecc_curve_from_oid(curve.oid_bytes()).unwrap(), curve);
+        }
     }
 }
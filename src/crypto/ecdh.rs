@@ -1,14 +1,17 @@
 use std::fmt;
 
 use rand::{CryptoRng, Rng};
+#[cfg(feature = "x25519")]
 use x25519_dalek::{PublicKey, StaticSecret};
-use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+#[cfg(feature = "x25519")]
+use zeroize::Zeroizing;
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
 use crate::crypto::{
     aes_kw, ecc_curve::ECCCurve, public_key::PublicKeyAlgorithm, sym::SymmetricKeyAlgorithm,
     Decryptor, KeyParams,
 };
-use crate::errors::{Error, Result};
+use crate::errors::Result;
 use crate::types::{Mpi, PlainSecretParams, PublicParams};
 
 use super::hash::HashAlgorithm;
@@ -96,7 +99,8 @@ impl Decryptor for SecretKey {
 
         let (curve, alg_sym, hash) = self.key_params();
 
-        let shared_secret = match self {
+        let shared_secret: Vec<u8> = match self {
+            #[cfg(feature = "x25519")]
             SecretKey::Curve25519 { secret, .. } => {
                 ensure_eq!(
                     secret.len(),
@@ -135,18 +139,29 @@ impl Decryptor for SecretKey {
 
                 shared_secret.to_bytes().to_vec()
             }
+            #[cfg(not(feature = "x25519"))]
+            SecretKey::Curve25519 { .. } => {
+                unsupported_err!("ECDH on Curve25519 (feature \"x25519\" is disabled)")
+            }
+            #[cfg(feature = "nist")]
             SecretKey::P256 { secret, .. } => {
                 derive_shared_secret_decryption::<p256::NistP256>(public_point, secret, &curve, 65)?
             }
+            #[cfg(feature = "nist")]
             SecretKey::P384 { secret, .. } => {
                 derive_shared_secret_decryption::<p384::NistP384>(public_point, secret, &curve, 97)?
             }
+            #[cfg(feature = "nist")]
             SecretKey::P521 { secret, .. } => derive_shared_secret_decryption::<p521::NistP521>(
                 public_point,
                 secret,
                 &curve,
                 133,
             )?,
+            #[cfg(not(feature = "nist"))]
+            SecretKey::P256 { .. } | SecretKey::P384 { .. } | SecretKey::P521 { .. } => {
+                unsupported_err!("ECDH on {} (feature \"nist\" is disabled)", curve.name())
+            }
         };
 
         // obtain the session key from the shared secret
@@ -161,6 +176,7 @@ impl Decryptor for SecretKey {
 }
 
 /// Derive a shared secret in decryption, for a Rust Crypto curve
+#[cfg(feature = "nist")]
 fn derive_shared_secret_decryption<C>(
     public_point: &Mpi,
     secret: &[u8],
@@ -217,47 +233,8 @@ pub fn derive_session_key(
     encrypted_session_key_vec[(encrypted_key_len - encrypted_session_key.len())..]
         .copy_from_slice(encrypted_session_key);
 
-    let mut decrypted_key_padded = aes_kw::unwrap(&z, &encrypted_session_key_vec)?;
-    // PKCS5-style unpadding (PKCS5 is PKCS7 with a blocksize of 8).
-    //
-    // RFC 6637 describes the padding:
-    // a) "The result is padded using the method described in [PKCS5] to the 8-byte granularity."
-    // b) "For example, assuming that an AES algorithm is used for the session key, the sender MAY
-    // use 21, 13, and 5 bytes of padding for AES-128, AES-192, and AES-256, respectively, to
-    // provide the same number of octets, 40 total, as an input to the key wrapping method."
-    //
-    // So while the padding ensures that the length of the padded message is a multiple of 8, the
-    // padding may exceed 8 bytes in size.
-    {
-        let len = decrypted_key_padded.len();
-        let block_size = 8;
-        ensure!(len % block_size == 0, "invalid key length {}", len);
-        ensure!(!decrypted_key_padded.is_empty(), "empty key is not valid");
-
-        // The last byte should contain the padding symbol, which is also the padding length
-        let pad = decrypted_key_padded.last().expect("is not empty");
-
-        // Padding length seems to exceed size of the padded message
-        if *pad as usize > len {
-            return Err(Error::UnpadError);
-        }
-
-        // Expected length of the unpadded message
-        let unpadded_len = len - *pad as usize;
-
-        // All bytes that constitute the padding must have the value of `pad`
-        if decrypted_key_padded[unpadded_len..]
-            .iter()
-            .any(|byte| byte != pad)
-        {
-            return Err(Error::UnpadError);
-        }
-
-        decrypted_key_padded.truncate(unpadded_len);
-    }
-
-    // the key is now unpadded
-    let decrypted_key = decrypted_key_padded;
+    let decrypted_key_padded = aes_kw::unwrap(&z, &encrypted_session_key_vec)?;
+    let decrypted_key = aes_kw::unpad_session_key(decrypted_key_padded)?;
     ensure!(!decrypted_key.is_empty(), "empty unpadded key is not valid");
 
     Ok(decrypted_key)
@@ -269,6 +246,7 @@ pub fn generate_key<R: Rng + CryptoRng>(
     curve: &ECCCurve,
 ) -> Result<(PublicParams, PlainSecretParams)> {
     match curve {
+        #[cfg(feature = "x25519")]
         ECCCurve::Curve25519 => {
             let mut secret_key_bytes =
                 Zeroizing::new([0u8; ECCCurve::Curve25519.secret_key_length()]);
@@ -305,10 +283,13 @@ pub fn generate_key<R: Rng + CryptoRng>(
             ))
         }
 
+        #[cfg(feature = "nist")]
         ECCCurve::P256 => keygen::<p256::NistP256, R>(rng, curve),
 
+        #[cfg(feature = "nist")]
         ECCCurve::P384 => keygen::<p384::NistP384, R>(rng, curve),
 
+        #[cfg(feature = "nist")]
         ECCCurve::P521 => keygen::<p521::NistP521, R>(rng, curve),
 
         _ => unsupported_err!("curve {:?} for ECDH", curve),
@@ -316,6 +297,7 @@ pub fn generate_key<R: Rng + CryptoRng>(
 }
 
 /// Generate an ECDH key based on a Rust Crypto curve
+#[cfg(feature = "nist")]
 fn keygen<C, R: Rng + CryptoRng>(
     mut rng: R,
     curve: &ECCCurve,
@@ -385,45 +367,8 @@ pub fn kdf(hash: HashAlgorithm, x: &[u8], length: usize, param: &[u8]) -> Result
     Ok(digest)
 }
 
-/// PKCS5-style padding, with a block-size of 8.
-/// However, the padding may exceed the length of one block, to obfuscate key size.
-fn pad(plain: &[u8]) -> Vec<u8> {
-    let len = plain.len();
-
-    /// Our default target padded length (based on the size of a padded AES256 key).
-    /// This value should be increased if we support symmetric keys that are longer than AES256.
-    const PAD_DEFAULT_TARGET: usize = 40;
-
-    // The padded message length (must be a multiple of the block size)
-    let padded_len = if len < PAD_DEFAULT_TARGET {
-        // Normally, we just pad to the default target size ...
-        PAD_DEFAULT_TARGET
-    } else {
-        // ... but if `plain` isn't shorter than our target size, we pad to the next full block
-        let remainder = len % 8; // e.g. 3 for len==19
-
-        len + 8 - remainder // (e.g. "8 + 8 - 0 => 16", or "19 + 8 - 3 => 24")
-    };
-    debug_assert!(padded_len % 8 == 0, "Unexpected padded_len {}", padded_len);
-
-    // The value we'll use for padding (must not be zero, and fit into a u8)
-    let padding = padded_len - len;
-    debug_assert!(
-        padding > 0 && u8::try_from(padding).is_ok(),
-        "Unexpected padding value {}",
-        padding
-    );
-    let padding = padding as u8;
-
-    // Extend length of plain_padded, fill with `padding` value
-    let mut plain_padded = plain.to_vec();
-    plain_padded.resize(padded_len, padding);
-
-    plain_padded
-}
-
 /// ECDH encryption.
-pub fn encrypt<R: CryptoRng + Rng>(
+pub fn encrypt<R: CryptoRng + Rng + ?Sized>(
     rng: &mut R,
     curve: &ECCCurve,
     alg_sym: SymmetricKeyAlgorithm,
@@ -445,7 +390,8 @@ pub fn encrypt<R: CryptoRng + Rng>(
         MAX_SIZE
     );
 
-    let (encoded_public, shared_secret) = match curve {
+    let (encoded_public, shared_secret): (Vec<u8>, Vec<u8>) = match curve {
+        #[cfg(feature = "x25519")]
         ECCCurve::Curve25519 => {
             ensure_eq!(q.len(), 33, "invalid public key");
 
@@ -475,8 +421,11 @@ pub fn encrypt<R: CryptoRng + Rng>(
 
             (encoded_public, shared_secret.as_bytes().to_vec())
         }
+        #[cfg(feature = "nist")]
         ECCCurve::P256 => derive_shared_secret_encryption::<p256::NistP256, R>(rng, q)?,
+        #[cfg(feature = "nist")]
         ECCCurve::P384 => derive_shared_secret_encryption::<p384::NistP384, R>(rng, q)?,
+        #[cfg(feature = "nist")]
         ECCCurve::P521 => derive_shared_secret_encryption::<p521::NistP521, R>(rng, q)?,
         _ => unsupported_err!("curve {:?} for ECDH", curve),
     };
@@ -487,7 +436,7 @@ pub fn encrypt<R: CryptoRng + Rng>(
     let z = kdf(hash, &shared_secret, alg_sym.key_size(), &param)?;
 
     // Pad plaintext
-    let plain_padded = pad(plain);
+    let plain_padded = aes_kw::pad_session_key(plain);
 
     // Perform AES Key Wrap
     let encrypted_key = aes_kw::wrap(&z, &plain_padded)?;
@@ -499,8 +448,9 @@ pub fn encrypt<R: CryptoRng + Rng>(
 
 /// Derive a shared secret in encryption, for a Rust Crypto curve.
 /// Returns a pair of `(our_public key, shared_secret)`.
-fn derive_shared_secret_encryption<C, R: CryptoRng + Rng>(
-    rng: &mut R,
+#[cfg(feature = "nist")]
+fn derive_shared_secret_encryption<C, R: CryptoRng + Rng + ?Sized>(
+    mut rng: &mut R,
     q: &[u8],
 ) -> Result<(Vec<u8>, Vec<u8>)>
 where
@@ -510,7 +460,7 @@ where
         elliptic_curve::sec1::FromEncodedPoint<C> + elliptic_curve::sec1::ToEncodedPoint<C>,
 {
     let their_public = elliptic_curve::PublicKey::<C>::from_sec1_bytes(q)?;
-    let our_secret = elliptic_curve::ecdh::EphemeralSecret::<C>::random(rng);
+    let our_secret = elliptic_curve::ecdh::EphemeralSecret::<C>::random(&mut rng);
 
     // derive shared secret
     let shared_secret = our_secret.diffie_hellman(&their_public);
@@ -525,25 +475,31 @@ where
     ))
 }
 
-#[cfg(test)]
+#[cfg(all(test, any(feature = "x25519", feature = "nist")))]
 mod tests {
     #![allow(clippy::unwrap_used)]
 
     use super::*;
+    #[cfg(feature = "x25519")]
     use std::fs;
 
     use rand::{RngCore, SeedableRng};
     use rand_chacha::ChaChaRng;
 
     use crate::types::SecretKeyRepr;
+    #[cfg(feature = "x25519")]
     use crate::{Deserializable, Message, SignedSecretKey};
 
     #[test]
     fn test_encrypt_decrypt() {
         for curve in [
+            #[cfg(feature = "x25519")]
             ECCCurve::Curve25519,
+            #[cfg(feature = "nist")]
             ECCCurve::P256,
+            #[cfg(feature = "nist")]
             ECCCurve::P384,
+            #[cfg(feature = "nist")]
             ECCCurve::P521,
         ] {
             let mut rng = ChaChaRng::from_seed([0u8; 32]);
@@ -591,6 +547,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "x25519")]
     fn test_decrypt_padding() {
         let (decrypt_key, _headers) = SignedSecretKey::from_armor_single(
             fs::File::open("./tests/unit-tests/padding/alice.key").unwrap(),
@@ -1,9 +1,13 @@
 use std::fmt;
 
+#[cfg(feature = "nist")]
 use ecdsa::SigningKey;
+#[cfg(any(feature = "nist", feature = "k256"))]
 use elliptic_curve::sec1::ToEncodedPoint;
+#[cfg(feature = "nist")]
 use p521::NistP521;
 use rand::{CryptoRng, Rng};
+#[cfg(any(feature = "nist", feature = "k256"))]
 use signature::hazmat::{PrehashSigner, PrehashVerifier};
 use zeroize::ZeroizeOnDrop;
 
@@ -16,9 +20,13 @@ use crate::types::{Mpi, PlainSecretParams, PublicParams};
 
 #[derive(Clone, PartialEq, Eq, ZeroizeOnDrop)]
 pub enum SecretKey {
+    #[cfg(feature = "nist")]
     P256(p256::SecretKey),
+    #[cfg(feature = "nist")]
     P384(p384::SecretKey),
+    #[cfg(feature = "nist")]
     P521(p521::SecretKey),
+    #[cfg(feature = "k256")]
     Secp256k1(k256::SecretKey),
     Unsupported {
         /// The secret point.
@@ -31,9 +39,13 @@ pub enum SecretKey {
 impl fmt::Debug for SecretKey {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            #[cfg(feature = "nist")]
             Self::P256(_) => write!(f, "ECDSASecretKey::P256([..])"),
+            #[cfg(feature = "nist")]
             Self::P384(_) => write!(f, "ECDSASecretKey::P384([..])"),
+            #[cfg(feature = "nist")]
             Self::P521(_) => write!(f, "ECDSASecretKey::P521([..])"),
+            #[cfg(feature = "k256")]
             Self::Secp256k1(_) => write!(f, "ECDSASecretKey::Secp256k1([..])"),
             Self::Unsupported { curve, .. } => f
                 .debug_struct("ECDSASecretKey::Unsupported")
@@ -74,18 +86,21 @@ impl Signer for SecretKey {
         }
 
         let (r, s) = match self {
+            #[cfg(feature = "nist")]
             Self::P256(secret_key) => {
                 let secret = p256::ecdsa::SigningKey::from(secret_key);
                 let signature: p256::ecdsa::Signature = secret.sign_prehash(digest)?;
                 let (r, s) = signature.split_bytes();
                 (r.to_vec(), s.to_vec())
             }
+            #[cfg(feature = "nist")]
             Self::P384(secret_key) => {
                 let secret = p384::ecdsa::SigningKey::from(secret_key);
                 let signature: p384::ecdsa::Signature = secret.sign_prehash(digest)?;
                 let (r, s) = signature.split_bytes();
                 (r.to_vec(), s.to_vec())
             }
+            #[cfg(feature = "nist")]
             Self::P521(secret_key) => {
                 let secret: SigningKey<NistP521> = secret_key.into();
                 let signing_key = p521::ecdsa::SigningKey::from(secret);
@@ -93,6 +108,7 @@ impl Signer for SecretKey {
                 let (r, s) = signature.split_bytes();
                 (r.to_vec(), s.to_vec())
             }
+            #[cfg(feature = "k256")]
             Self::Secp256k1(secret_key) => {
                 let secret = k256::ecdsa::SigningKey::from(secret_key);
                 let signature: k256::ecdsa::Signature = secret.sign_prehash(digest)?;
@@ -111,9 +127,13 @@ impl Signer for SecretKey {
 impl SecretKey {
     pub(crate) fn secret_key_length(&self) -> Option<usize> {
         match self {
+            #[cfg(feature = "nist")]
             Self::P256 { .. } => Some(32),
+            #[cfg(feature = "nist")]
             Self::P384 { .. } => Some(48),
+            #[cfg(feature = "nist")]
             Self::P521 { .. } => Some(66),
+            #[cfg(feature = "k256")]
             Self::Secp256k1 { .. } => Some(32),
             Self::Unsupported { .. } => None,
         }
@@ -125,6 +145,7 @@ pub fn generate_key<R: Rng + CryptoRng>(
     curve: &ECCCurve,
 ) -> Result<(PublicParams, PlainSecretParams)> {
     match curve {
+        #[cfg(feature = "nist")]
         ECCCurve::P256 => {
             let secret = p256::SecretKey::random(&mut rng);
             let public = secret.public_key();
@@ -139,6 +160,7 @@ pub fn generate_key<R: Rng + CryptoRng>(
             ))
         }
 
+        #[cfg(feature = "nist")]
         ECCCurve::P384 => {
             let secret = p384::SecretKey::random(&mut rng);
             let public = secret.public_key();
@@ -153,6 +175,7 @@ pub fn generate_key<R: Rng + CryptoRng>(
             ))
         }
 
+        #[cfg(feature = "nist")]
         ECCCurve::P521 => {
             let secret = p521::SecretKey::random(&mut rng);
             let public = secret.public_key();
@@ -167,6 +190,7 @@ pub fn generate_key<R: Rng + CryptoRng>(
             ))
         }
 
+        #[cfg(feature = "k256")]
         ECCCurve::Secp256k1 => {
             let secret = k256::SecretKey::random(&mut rng);
             let public = secret.public_key();
@@ -203,6 +227,7 @@ pub fn verify(
     }
 
     match p {
+        #[cfg(feature = "nist")]
         EcdsaPublicParams::P256 { key, .. } => {
             const FLEN: usize = 32;
             ensure_eq!(sig.len(), 2);
@@ -223,6 +248,7 @@ pub fn verify(
 
             Ok(())
         }
+        #[cfg(feature = "nist")]
         EcdsaPublicParams::P384 { key, .. } => {
             const FLEN: usize = 48;
             ensure_eq!(sig.len(), 2);
@@ -246,6 +272,7 @@ pub fn verify(
 
             Ok(())
         }
+        #[cfg(feature = "nist")]
         EcdsaPublicParams::P521 { key, .. } => {
             const FLEN: usize = 66;
             ensure_eq!(sig.len(), 2);
@@ -269,6 +296,7 @@ pub fn verify(
 
             Ok(())
         }
+        #[cfg(feature = "k256")]
         EcdsaPublicParams::Secp256k1 { key, .. } => {
             const FLEN: usize = 32;
             ensure_eq!(sig.len(), 2);
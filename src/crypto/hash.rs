@@ -3,6 +3,7 @@ use std::{fmt::Display, str::FromStr};
 use digest::Digest;
 use md5::Md5;
 use num_enum::{FromPrimitive, IntoPrimitive};
+#[cfg(feature = "ripemd160")]
 use ripemd::Ripemd160;
 use sha1_checked::{CollisionResult, Sha1};
 
@@ -81,6 +82,9 @@ impl Display for HashAlgorithm {
 
 impl zeroize::DefaultIsZeroes for HashAlgorithm {}
 
+/// The size of the largest digest of any supported hash algorithm.
+pub(crate) const MAX_DIGEST_SIZE: usize = 64;
+
 /// Trait to work around the fact that the `Digest` trait from rustcrypto can not
 /// be used as `Box<Digest>`.
 pub trait Hasher: std::io::Write {
@@ -90,6 +94,8 @@ pub trait Hasher: std::io::Write {
     fn finish(self: Box<Self>) -> Vec<u8>;
     /// Finalize into the provided buffer. Truncates to the length of `out`.
     fn finish_reset_into(&mut self, out: &mut [u8]);
+    /// Clone the current state of the hasher.
+    fn clone_boxed(&self) -> Box<dyn Hasher>;
 }
 
 macro_rules! derive_hasher {
@@ -112,6 +118,10 @@ macro_rules! derive_hasher {
                 let res = self.inner.finalize_reset();
                 out.copy_from_slice(&res.as_slice()[..out.len()]);
             }
+
+            fn clone_boxed(&self) -> Box<dyn Hasher> {
+                Box::new(self.clone())
+            }
         }
 
         impl std::io::Write for $name {
@@ -129,26 +139,35 @@ macro_rules! derive_hasher {
 
 derive_hasher!(Md5Hasher, Md5);
 derive_hasher!(Sha1Hasher, Sha1);
+#[cfg(feature = "ripemd160")]
 derive_hasher!(Ripemd160Hasher, Ripemd160);
 derive_hasher!(Sha2_256Hasher, sha2::Sha256);
 derive_hasher!(Sha2_384Hasher, sha2::Sha384);
 derive_hasher!(Sha2_512Hasher, sha2::Sha512);
 derive_hasher!(Sha2_224Hasher, sha2::Sha224);
+#[cfg(feature = "sha3")]
 derive_hasher!(Sha3_256Hasher, sha3::Sha3_256);
+#[cfg(feature = "sha3")]
 derive_hasher!(Sha3_512Hasher, sha3::Sha3_512);
 
 impl HashAlgorithm {
     /// Create a new hasher.
+    ///
+    /// To compute the digest that is signed for a signature, use
+    /// [`SignatureConfig::digest`](crate::packet::SignatureConfig::digest).
     pub fn new_hasher(self) -> Result<Box<dyn Hasher>> {
         match self {
             HashAlgorithm::MD5 => Ok(Box::<Md5Hasher>::default()),
             HashAlgorithm::SHA1 => Ok(Box::<Sha1Hasher>::default()),
+            #[cfg(feature = "ripemd160")]
             HashAlgorithm::RIPEMD160 => Ok(Box::<Ripemd160Hasher>::default()),
             HashAlgorithm::SHA2_256 => Ok(Box::<Sha2_256Hasher>::default()),
             HashAlgorithm::SHA2_384 => Ok(Box::<Sha2_384Hasher>::default()),
             HashAlgorithm::SHA2_512 => Ok(Box::<Sha2_512Hasher>::default()),
             HashAlgorithm::SHA2_224 => Ok(Box::<Sha2_224Hasher>::default()),
+            #[cfg(feature = "sha3")]
             HashAlgorithm::SHA3_256 => Ok(Box::<Sha3_256Hasher>::default()),
+            #[cfg(feature = "sha3")]
             HashAlgorithm::SHA3_512 => Ok(Box::<Sha3_512Hasher>::default()),
             _ => unimplemented_err!("hasher {:?}", self),
         }
@@ -164,12 +183,15 @@ impl HashAlgorithm {
                     return Err(Error::Sha1HashCollision)
                 }
             },
+            #[cfg(feature = "ripemd160")]
             HashAlgorithm::RIPEMD160 => Ripemd160::digest(data).to_vec(),
             HashAlgorithm::SHA2_256 => sha2::Sha256::digest(data).to_vec(),
             HashAlgorithm::SHA2_384 => sha2::Sha384::digest(data).to_vec(),
             HashAlgorithm::SHA2_512 => sha2::Sha512::digest(data).to_vec(),
             HashAlgorithm::SHA2_224 => sha2::Sha224::digest(data).to_vec(),
+            #[cfg(feature = "sha3")]
             HashAlgorithm::SHA3_256 => sha3::Sha3_256::digest(data).to_vec(),
+            #[cfg(feature = "sha3")]
             HashAlgorithm::SHA3_512 => sha3::Sha3_512::digest(data).to_vec(),
 
             HashAlgorithm::Private10 => unsupported_err!("Private10 should not be used"),
@@ -177,19 +199,90 @@ impl HashAlgorithm {
         })
     }
 
-    /// Returns the expected digest size for the given algorithm.
+    /// Returns the expected digest size for the given algorithm, in bytes.
+    ///
+    /// Returns `0` for algorithms that [`HashAlgorithm::new_hasher`] does not support.
     pub fn digest_size(self) -> usize {
         match self {
             HashAlgorithm::MD5 => Md5::output_size(),
             HashAlgorithm::SHA1 => Sha1::output_size(),
+            #[cfg(feature = "ripemd160")]
             HashAlgorithm::RIPEMD160 => Ripemd160::output_size(),
             HashAlgorithm::SHA2_256 => sha2::Sha256::output_size(),
             HashAlgorithm::SHA2_384 => sha2::Sha384::output_size(),
             HashAlgorithm::SHA2_512 => sha2::Sha512::output_size(),
             HashAlgorithm::SHA2_224 => sha2::Sha224::output_size(),
+            #[cfg(feature = "sha3")]
             HashAlgorithm::SHA3_256 => sha3::Sha3_256::output_size(),
+            #[cfg(feature = "sha3")]
             HashAlgorithm::SHA3_512 => sha3::Sha3_512::output_size(),
             _ => 0,
         }
     }
 }
+
+/// Chooses the hash algorithm to sign data for a recipient with.
+///
+/// The first algorithm in the recipient's preferences that is in `our_supported` is used.
+/// Without a mutual choice SHA2-256 is used.
+pub fn negotiate_hash_algorithm(
+    our_supported: &[HashAlgorithm],
+    their_preferred: &[HashAlgorithm],
+) -> HashAlgorithm {
+    their_preferred
+        .iter()
+        .find(|alg| **alg != HashAlgorithm::None && our_supported.contains(alg))
+        .copied()
+        .unwrap_or(HashAlgorithm::SHA2_256)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+
+    #[test]
+    fn test_hasher_digest_size() {
+        use HashAlgorithm::*;
+
+        for alg in [
+            MD5,
+            SHA1,
+            #[cfg(feature = "ripemd160")]
+            RIPEMD160,
+            SHA2_256,
+            SHA2_384,
+            SHA2_512,
+            SHA2_224,
+            #[cfg(feature = "sha3")]
+            SHA3_256,
+            #[cfg(feature = "sha3")]
+            SHA3_512,
+        ] {
+            let mut hasher = alg.new_hasher().unwrap();
+            hasher.update(b"hello world");
+            let digest = hasher.finish();
+
+            assert_eq!(digest.len(), alg.digest_size(), "{alg}");
+            assert_eq!(digest, alg.digest(b"hello world").unwrap(), "{alg}");
+        }
+
+        assert!(Private10.new_hasher().is_err());
+        assert_eq!(Private10.digest_size(), 0);
+    }
+
+    #[test]
+    fn test_negotiate_hash_algorithm() {
+        use HashAlgorithm::*;
+
+        let ours = [SHA2_512, SHA2_256, SHA1];
+        assert_eq!(
+            negotiate_hash_algorithm(&ours, &[SHA2_512, SHA2_256]),
+            SHA2_512
+        );
+        assert_eq!(negotiate_hash_algorithm(&ours, &[SHA3_512, SHA1]), SHA1);
+        assert_eq!(negotiate_hash_algorithm(&ours, &[MD5]), SHA2_256);
+        assert_eq!(negotiate_hash_algorithm(&ours, &[]), SHA2_256);
+    }
+}
@@ -7,13 +7,16 @@ use self::hash::HashAlgorithm;
 pub mod aead;
 pub mod aes_kw;
 pub mod checksum;
+#[cfg(feature = "dsa")]
 pub mod dsa;
 pub mod ecc_curve;
 pub mod ecdh;
 pub mod ecdsa;
+#[cfg(feature = "ed25519")]
 pub mod eddsa;
 pub mod hash;
 pub mod public_key;
+#[cfg(feature = "rsa")]
 pub mod rsa;
 pub mod sym;
 
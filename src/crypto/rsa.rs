@@ -1,20 +1,24 @@
+use std::fmt;
 use std::ops::Deref;
+use std::sync::OnceLock;
 
 use digest::{const_oid::AssociatedOid, Digest};
 use md5::Md5;
 use num_bigint::traits::ModInverse;
 use num_bigint::BigUint;
 use rand::{CryptoRng, Rng};
+#[cfg(feature = "ripemd160")]
 use ripemd::Ripemd160;
-use rsa::pkcs1v15::{Pkcs1v15Encrypt, Signature as RsaSignature, SigningKey, VerifyingKey};
+use rsa::pkcs1v15::{Pkcs1v15Encrypt, Signature as RsaSignature, SigningKey};
 use rsa::{
     traits::{PrivateKeyParts, PublicKeyParts},
-    RsaPrivateKey, RsaPublicKey,
+    Pkcs1v15Sign, RsaPrivateKey, RsaPublicKey,
 };
 use sha1_checked::Sha1; // not used for hashing, just as a source of the OID
 use sha2::{Sha224, Sha256, Sha384, Sha512};
+#[cfg(feature = "sha3")]
 use sha3::{Sha3_256, Sha3_512};
-use signature::hazmat::{PrehashSigner, PrehashVerifier};
+use signature::hazmat::PrehashSigner;
 use signature::SignatureEncoding;
 use zeroize::ZeroizeOnDrop;
 
@@ -22,7 +26,7 @@ use crate::crypto::{hash::HashAlgorithm, Decryptor, KeyParams, Signer};
 use crate::errors::Result;
 use crate::types::{Mpi, PlainSecretParams, PublicParams};
 
-const MAX_KEY_SIZE: usize = 16384;
+pub(crate) const MAX_KEY_SIZE: usize = 16384;
 
 /// Private Key for RSA.
 #[derive(Debug, ZeroizeOnDrop)]
@@ -74,16 +78,19 @@ impl Signer for PrivateKey {
         let sig = match hash {
             HashAlgorithm::None => return Err(format_err!("none")),
             HashAlgorithm::MD5 => sign_int::<Md5>(self.0.clone(), digest),
+            #[cfg(feature = "ripemd160")]
             HashAlgorithm::RIPEMD160 => sign_int::<Ripemd160>(self.0.clone(), digest),
             HashAlgorithm::SHA1 => sign_int::<Sha1>(self.0.clone(), digest),
             HashAlgorithm::SHA2_224 => sign_int::<Sha224>(self.0.clone(), digest),
             HashAlgorithm::SHA2_256 => sign_int::<Sha256>(self.0.clone(), digest),
             HashAlgorithm::SHA2_384 => sign_int::<Sha384>(self.0.clone(), digest),
             HashAlgorithm::SHA2_512 => sign_int::<Sha512>(self.0.clone(), digest),
+            #[cfg(feature = "sha3")]
             HashAlgorithm::SHA3_256 => sign_int::<Sha3_256>(self.0.clone(), digest),
+            #[cfg(feature = "sha3")]
             HashAlgorithm::SHA3_512 => sign_int::<Sha3_512>(self.0.clone(), digest),
             HashAlgorithm::Private10 => unsupported_err!("Private10 should not be used"),
-            HashAlgorithm::Other(o) => unsupported_err!("Hash algorithm {} is unsupported", o),
+            _ => unsupported_err!("Hash algorithm {} is unsupported", hash),
         }?;
 
         Ok(vec![sig.to_vec()])
@@ -91,8 +98,8 @@ impl Signer for PrivateKey {
 }
 
 /// RSA encryption using PKCS1v15 padding.
-pub fn encrypt<R: CryptoRng + Rng>(
-    rng: &mut R,
+pub fn encrypt<R: CryptoRng + Rng + ?Sized>(
+    mut rng: &mut R,
     n: &[u8],
     e: &[u8],
     plaintext: &[u8],
@@ -102,7 +109,7 @@ pub fn encrypt<R: CryptoRng + Rng>(
         BigUint::from_bytes_be(e),
         MAX_KEY_SIZE,
     )?;
-    let data = key.encrypt(rng, Pkcs1v15Encrypt, plaintext)?;
+    let data = key.encrypt(&mut rng, Pkcs1v15Encrypt, plaintext)?;
 
     Ok(vec![data])
 }
@@ -137,15 +144,6 @@ pub fn generate_key<R: Rng + CryptoRng>(
     ))
 }
 
-fn verify_int<D>(key: RsaPublicKey, hashed: &[u8], signature: &RsaSignature) -> Result<()>
-where
-    D: Digest + AssociatedOid,
-{
-    VerifyingKey::<D>::new(key)
-        .verify_prehash(hashed, signature)
-        .map_err(Into::into)
-}
-
 fn sign_int<D>(key: RsaPrivateKey, digest: &[u8]) -> Result<RsaSignature>
 where
     D: Digest + AssociatedOid,
@@ -156,6 +154,10 @@ where
 }
 
 /// Verify a RSA, PKCS1v15 padded signature.
+///
+/// This parses the public key on every call. When checking many signatures made by the same
+/// key, construct a [`Verifier`] once and reuse it instead. Key packets do this on their own,
+/// through [`PublicKeyTrait::verify_signature`](crate::types::PublicKeyTrait::verify_signature).
 pub fn verify(
     n: &[u8],
     e: &[u8],
@@ -163,36 +165,118 @@ pub fn verify(
     hashed: &[u8],
     signature: &[u8],
 ) -> Result<()> {
-    let key = RsaPublicKey::new_with_max_size(
-        BigUint::from_bytes_be(n),
-        BigUint::from_bytes_be(e),
-        MAX_KEY_SIZE,
-    )?;
+    Verifier::new(n, e)?.verify(hash, hashed, signature)
+}
+
+/// A parsed RSA public key, that can be used to verify any number of PKCS1v15 padded
+/// signatures, without re-parsing the key material for each one.
+///
+/// ```
+/// # fn main() -> pgp::errors::Result<()> {
+/// use pgp::composed::{Deserializable, SignedSecretKey};
+/// use pgp::crypto::{hash::HashAlgorithm, rsa::Verifier};
+/// use pgp::types::{PublicParams, SecretKeyTrait};
+///
+/// let (key, _) = SignedSecretKey::from_armor_single(std::fs::File::open(
+///     "./tests/openpgp/samplekeys/rsa-primary-auth-only.sec.asc",
+/// )?)?;
+/// let PublicParams::RSA { n, e } = key.primary_key.public_params() else {
+///     unreachable!("an RSA key");
+/// };
+///
+/// let verifier = Verifier::new(n.as_bytes(), e.as_bytes())?;
+/// for data in [&b"hello"[..], b"world"] {
+///     let hashed = HashAlgorithm::SHA2_256.digest(data)?;
+///     let sig = key
+///         .primary_key
+///         .create_signature(String::new, HashAlgorithm::SHA2_256, &hashed)?;
+///     verifier.verify(HashAlgorithm::SHA2_256, &hashed, sig[0].as_bytes())?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verifier {
+    key: RsaPublicKey,
+}
+
+impl Verifier {
+    /// Parses the public key from its modulus `n` and exponent `e`.
+    pub fn new(n: &[u8], e: &[u8]) -> Result<Self> {
+        let key = RsaPublicKey::new_with_max_size(
+            BigUint::from_bytes_be(n),
+            BigUint::from_bytes_be(e),
+            MAX_KEY_SIZE,
+        )?;
+
+        Ok(Verifier { key })
+    }
+
+    /// Verify a RSA, PKCS1v15 padded signature over the already `hashed` data.
+    pub fn verify(&self, hash: HashAlgorithm, hashed: &[u8], signature: &[u8]) -> Result<()> {
+        let scheme = match hash {
+            HashAlgorithm::None => return Err(format_err!("none")),
+            HashAlgorithm::MD5 => Pkcs1v15Sign::new::<Md5>(),
+            #[cfg(feature = "ripemd160")]
+            HashAlgorithm::RIPEMD160 => Pkcs1v15Sign::new::<Ripemd160>(),
+            HashAlgorithm::SHA1 => Pkcs1v15Sign::new::<Sha1>(),
+            HashAlgorithm::SHA2_224 => Pkcs1v15Sign::new::<Sha224>(),
+            HashAlgorithm::SHA2_256 => Pkcs1v15Sign::new::<Sha256>(),
+            HashAlgorithm::SHA2_384 => Pkcs1v15Sign::new::<Sha384>(),
+            HashAlgorithm::SHA2_512 => Pkcs1v15Sign::new::<Sha512>(),
+            #[cfg(feature = "sha3")]
+            HashAlgorithm::SHA3_256 => Pkcs1v15Sign::new::<Sha3_256>(),
+            #[cfg(feature = "sha3")]
+            HashAlgorithm::SHA3_512 => Pkcs1v15Sign::new::<Sha3_512>(),
+            HashAlgorithm::Private10 => unsupported_err!("Private10 should not be used"),
+            _ => unsupported_err!("Hash algorithm {} is unsupported", hash),
+        };
+
+        let size = self.key.size();
+        if signature.len() < size {
+            // RSA short signatures are allowed by PGP, but not by the RSA crate.
+            // So we pad out the signature if we encounter a short one.
+            let mut signature_padded = vec![0u8; size];
+            signature_padded[size - signature.len()..].copy_from_slice(signature);
+            self.key.verify(scheme, hashed, &signature_padded)?;
+        } else {
+            self.key.verify(scheme, hashed, signature)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The [`Verifier`] of a key packet, parsed the first time one of its signatures is checked.
+#[derive(Default, Clone)]
+pub(crate) struct VerifierCache(OnceLock<Verifier>);
+
+impl VerifierCache {
+    /// Returns the cached verifier, parsing it from `n` and `e` if there is none yet.
+    pub(crate) fn get_or_init(&self, n: &[u8], e: &[u8]) -> Result<&Verifier> {
+        if let Some(verifier) = self.0.get() {
+            return Ok(verifier);
+        }
+
+        let verifier = Verifier::new(n, e)?;
+        Ok(self.0.get_or_init(|| verifier))
+    }
+}
+
+// Derived from the public parameters of the key, so two keys with equal parameters are equal,
+// no matter which of them was used for verification yet.
+impl PartialEq for VerifierCache {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for VerifierCache {}
 
-    let signature = if signature.len() < key.size() {
-        // RSA short signatures are allowed by PGP, but not by the RSA crate.
-        // So we pad out the signature if we encounter a short one.
-        let mut signature_padded = vec![0u8; key.size()];
-        let diff = key.size() - signature.len();
-        signature_padded[diff..].copy_from_slice(signature);
-        RsaSignature::try_from(&signature_padded[..])?
-    } else {
-        RsaSignature::try_from(signature)?
-    };
-
-    match hash {
-        HashAlgorithm::None => Err(format_err!("none")),
-        HashAlgorithm::MD5 => verify_int::<Md5>(key, hashed, &signature),
-        HashAlgorithm::RIPEMD160 => verify_int::<Ripemd160>(key, hashed, &signature),
-        HashAlgorithm::SHA1 => verify_int::<Sha1>(key, hashed, &signature),
-        HashAlgorithm::SHA2_224 => verify_int::<Sha224>(key, hashed, &signature),
-        HashAlgorithm::SHA2_256 => verify_int::<Sha256>(key, hashed, &signature),
-        HashAlgorithm::SHA2_384 => verify_int::<Sha384>(key, hashed, &signature),
-        HashAlgorithm::SHA2_512 => verify_int::<Sha512>(key, hashed, &signature),
-        HashAlgorithm::SHA3_256 => verify_int::<Sha3_256>(key, hashed, &signature),
-        HashAlgorithm::SHA3_512 => verify_int::<Sha3_512>(key, hashed, &signature),
-        HashAlgorithm::Private10 => unsupported_err!("Private10 should not be used"),
-        HashAlgorithm::Other(o) => unsupported_err!("Hash algorithm {} is unsupported", o),
+impl fmt::Debug for VerifierCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VerifierCache")
+            .field("initialized", &self.0.get().is_some())
+            .finish()
     }
-    .map_err(Into::into)
 }
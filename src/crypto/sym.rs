@@ -1,13 +1,19 @@
 use aes::{Aes128, Aes192, Aes256};
+#[cfg(feature = "blowfish")]
 use blowfish::Blowfish;
+#[cfg(feature = "camellia")]
 use camellia::{Camellia128, Camellia192, Camellia256};
+#[cfg(feature = "cast5")]
 use cast5::Cast5;
 use cfb_mode::cipher::{AsyncStreamCipher, KeyIvInit};
 use cfb_mode::{BufDecryptor, BufEncryptor, Decryptor, Encryptor};
+#[cfg(feature = "tripledes")]
 use des::TdesEde3;
+#[cfg(feature = "idea")]
 use idea::Idea;
 use num_enum::{FromPrimitive, IntoPrimitive};
 use rand::{thread_rng, CryptoRng, Rng};
+#[cfg(feature = "twofish")]
 use twofish::Twofish;
 
 use crate::errors::{Error, Result};
@@ -184,6 +190,10 @@ impl SymmetricKeyAlgorithm {
 
         // MDC is 1 byte packet tag, 1 byte length prefix and 20 bytes SHA1 hash.
         const MDC_LEN: usize = 22;
+        if res.len() < MDC_LEN {
+            // Missing MDC, the data must be treated as tampered with.
+            return Err(Error::MdcError);
+        }
         let (data, mdc) = res.split_at(res.len() - MDC_LEN);
 
         // We use regular sha1 for MDC, not sha1_checked. Collisions are not currently a concern with MDC.
@@ -227,6 +237,7 @@ impl SymmetricKeyAlgorithm {
         {
             match self {
                 SymmetricKeyAlgorithm::Plaintext => {}
+                #[cfg(feature = "idea")]
                 SymmetricKeyAlgorithm::IDEA => decrypt!(
                     Idea,
                     key,
@@ -237,6 +248,7 @@ impl SymmetricKeyAlgorithm {
                     resync
                 ),
 
+                #[cfg(feature = "tripledes")]
                 SymmetricKeyAlgorithm::TripleDES => {
                     decrypt!(
                         TdesEde3,
@@ -248,6 +260,7 @@ impl SymmetricKeyAlgorithm {
                         resync
                     );
                 }
+                #[cfg(feature = "cast5")]
                 SymmetricKeyAlgorithm::CAST5 => decrypt!(
                     Cast5,
                     key,
@@ -257,6 +270,7 @@ impl SymmetricKeyAlgorithm {
                     bs,
                     resync
                 ),
+                #[cfg(feature = "blowfish")]
                 SymmetricKeyAlgorithm::Blowfish => decrypt!(
                     Blowfish,
                     key,
@@ -293,6 +307,7 @@ impl SymmetricKeyAlgorithm {
                     bs,
                     resync
                 ),
+                #[cfg(feature = "twofish")]
                 SymmetricKeyAlgorithm::Twofish => decrypt!(
                     Twofish,
                     key,
@@ -302,6 +317,7 @@ impl SymmetricKeyAlgorithm {
                     bs,
                     resync
                 ),
+                #[cfg(feature = "camellia")]
                 SymmetricKeyAlgorithm::Camellia128 => decrypt!(
                     Camellia128,
                     key,
@@ -311,6 +327,7 @@ impl SymmetricKeyAlgorithm {
                     bs,
                     resync
                 ),
+                #[cfg(feature = "camellia")]
                 SymmetricKeyAlgorithm::Camellia192 => decrypt!(
                     Camellia192,
                     key,
@@ -320,6 +337,7 @@ impl SymmetricKeyAlgorithm {
                     bs,
                     resync
                 ),
+                #[cfg(feature = "camellia")]
                 SymmetricKeyAlgorithm::Camellia256 => decrypt!(
                     Camellia256,
                     key,
@@ -329,7 +347,7 @@ impl SymmetricKeyAlgorithm {
                     bs,
                     resync
                 ),
-                SymmetricKeyAlgorithm::Private10 | SymmetricKeyAlgorithm::Other(_) => {
+                _ => {
                     unimplemented_err!("SymmetricKeyAlgorithm {} is unsupported", u8::from(self))
                 }
             }
@@ -348,13 +366,17 @@ impl SymmetricKeyAlgorithm {
     ) -> Result<()> {
         match self {
             SymmetricKeyAlgorithm::Plaintext => {}
+            #[cfg(feature = "idea")]
             SymmetricKeyAlgorithm::IDEA => {
                 decrypt_regular!(Idea, key, iv_vec, ciphertext)
             }
+            #[cfg(feature = "tripledes")]
             SymmetricKeyAlgorithm::TripleDES => {
                 decrypt_regular!(TdesEde3, key, iv_vec, ciphertext);
             }
+            #[cfg(feature = "cast5")]
             SymmetricKeyAlgorithm::CAST5 => decrypt_regular!(Cast5, key, iv_vec, ciphertext),
+            #[cfg(feature = "blowfish")]
             SymmetricKeyAlgorithm::Blowfish => {
                 decrypt_regular!(Blowfish, key, iv_vec, ciphertext)
             }
@@ -367,19 +389,23 @@ impl SymmetricKeyAlgorithm {
             SymmetricKeyAlgorithm::AES256 => {
                 decrypt_regular!(Aes256, key, iv_vec, ciphertext)
             }
+            #[cfg(feature = "twofish")]
             SymmetricKeyAlgorithm::Twofish => {
                 decrypt_regular!(Twofish, key, iv_vec, ciphertext)
             }
+            #[cfg(feature = "camellia")]
             SymmetricKeyAlgorithm::Camellia128 => {
                 decrypt_regular!(Camellia128, key, iv_vec, ciphertext)
             }
+            #[cfg(feature = "camellia")]
             SymmetricKeyAlgorithm::Camellia192 => {
                 decrypt_regular!(Camellia192, key, iv_vec, ciphertext)
             }
+            #[cfg(feature = "camellia")]
             SymmetricKeyAlgorithm::Camellia256 => {
                 decrypt_regular!(Camellia256, key, iv_vec, ciphertext)
             }
-            SymmetricKeyAlgorithm::Private10 | SymmetricKeyAlgorithm::Other(_) => {
+            _ => {
                 unimplemented_err!("SymmetricKeyAlgorithm {} is unsupported", u8::from(self))
             }
         }
@@ -389,7 +415,7 @@ impl SymmetricKeyAlgorithm {
 
     /// Encrypt the data using CFB mode, without padding. Overwrites the input.
     /// Uses an IV of all zeroes, as specified in the openpgp cfb mode.
-    pub fn encrypt_with_rng<R: CryptoRng + Rng>(
+    pub fn encrypt_with_rng<R: CryptoRng + Rng + ?Sized>(
         self,
         rng: &mut R,
         key: &[u8],
@@ -428,7 +454,7 @@ impl SymmetricKeyAlgorithm {
         self.encrypt_with_rng(&mut thread_rng(), key, plaintext)
     }
 
-    pub fn encrypt_protected_with_rng<R: CryptoRng + Rng>(
+    pub fn encrypt_protected_with_rng<R: CryptoRng + Rng + ?Sized>(
         self,
         rng: &mut R,
         key: &[u8],
@@ -473,6 +499,10 @@ impl SymmetricKeyAlgorithm {
         Ok(ciphertext)
     }
 
+    /// Same as [`encrypt_protected_with_rng`], but uses [`rand::thread_rng`] for RNG.
+    ///
+    /// [`encrypt_protected_with_rng`]: SymmetricKeyAlgorithm::encrypt_protected_with_rng
+    /// [`rand::thread_rng`]: rand::thread_rng
     pub fn encrypt_protected(self, key: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
         self.encrypt_protected_with_rng(&mut thread_rng(), key, plaintext)
     }
@@ -498,15 +528,19 @@ impl SymmetricKeyAlgorithm {
         {
             match self {
                 SymmetricKeyAlgorithm::Plaintext => {}
+                #[cfg(feature = "idea")]
                 SymmetricKeyAlgorithm::IDEA => {
                     encrypt!(Idea, key, iv_vec, prefix, data, bs, resync)
                 }
+                #[cfg(feature = "tripledes")]
                 SymmetricKeyAlgorithm::TripleDES => {
                     encrypt!(TdesEde3, key, iv_vec, prefix, data, bs, resync);
                 }
+                #[cfg(feature = "cast5")]
                 SymmetricKeyAlgorithm::CAST5 => {
                     encrypt!(Cast5, key, iv_vec, prefix, data, bs, resync)
                 }
+                #[cfg(feature = "blowfish")]
                 SymmetricKeyAlgorithm::Blowfish => {
                     encrypt!(Blowfish, key, iv_vec, prefix, data, bs, resync)
                 }
@@ -519,19 +553,23 @@ impl SymmetricKeyAlgorithm {
                 SymmetricKeyAlgorithm::AES256 => {
                     encrypt!(Aes256, key, iv_vec, prefix, data, bs, resync)
                 }
+                #[cfg(feature = "twofish")]
                 SymmetricKeyAlgorithm::Twofish => {
                     encrypt!(Twofish, key, iv_vec, prefix, data, bs, resync)
                 }
+                #[cfg(feature = "camellia")]
                 SymmetricKeyAlgorithm::Camellia128 => {
                     encrypt!(Camellia128, key, iv_vec, prefix, data, bs, resync)
                 }
+                #[cfg(feature = "camellia")]
                 SymmetricKeyAlgorithm::Camellia192 => {
                     encrypt!(Camellia192, key, iv_vec, prefix, data, bs, resync)
                 }
+                #[cfg(feature = "camellia")]
                 SymmetricKeyAlgorithm::Camellia256 => {
                     encrypt!(Camellia256, key, iv_vec, prefix, data, bs, resync)
                 }
-                SymmetricKeyAlgorithm::Private10 | SymmetricKeyAlgorithm::Other(_) => {
+                _ => {
                     bail!("SymmetricKeyAlgorithm {} is unsupported", u8::from(self))
                 }
             }
@@ -550,28 +588,36 @@ impl SymmetricKeyAlgorithm {
         // TODO: actual cfb mode used in pgp
         match self {
             SymmetricKeyAlgorithm::Plaintext => {}
+            #[cfg(feature = "idea")]
             SymmetricKeyAlgorithm::IDEA => encrypt_regular!(Idea, key, iv_vec, plaintext),
+            #[cfg(feature = "tripledes")]
             SymmetricKeyAlgorithm::TripleDES => {
                 encrypt_regular!(TdesEde3, key, iv_vec, plaintext);
             }
+            #[cfg(feature = "cast5")]
             SymmetricKeyAlgorithm::CAST5 => encrypt_regular!(Cast5, key, iv_vec, plaintext),
+            #[cfg(feature = "blowfish")]
             SymmetricKeyAlgorithm::Blowfish => {
                 encrypt_regular!(Blowfish, key, iv_vec, plaintext)
             }
             SymmetricKeyAlgorithm::AES128 => encrypt_regular!(Aes128, key, iv_vec, plaintext),
             SymmetricKeyAlgorithm::AES192 => encrypt_regular!(Aes192, key, iv_vec, plaintext),
             SymmetricKeyAlgorithm::AES256 => encrypt_regular!(Aes256, key, iv_vec, plaintext),
+            #[cfg(feature = "twofish")]
             SymmetricKeyAlgorithm::Twofish => encrypt_regular!(Twofish, key, iv_vec, plaintext),
+            #[cfg(feature = "camellia")]
             SymmetricKeyAlgorithm::Camellia128 => {
                 encrypt_regular!(Camellia128, key, iv_vec, plaintext)
             }
+            #[cfg(feature = "camellia")]
             SymmetricKeyAlgorithm::Camellia192 => {
                 encrypt_regular!(Camellia192, key, iv_vec, plaintext)
             }
+            #[cfg(feature = "camellia")]
             SymmetricKeyAlgorithm::Camellia256 => {
                 encrypt_regular!(Camellia256, key, iv_vec, plaintext)
             }
-            SymmetricKeyAlgorithm::Private10 | SymmetricKeyAlgorithm::Other(_) => {
+            _ => {
                 unimplemented_err!("SymmetricKeyAlgorithm {} is unsupported", u8::from(self))
             }
         }
@@ -579,15 +625,32 @@ impl SymmetricKeyAlgorithm {
     }
 
     /// Generate a new session key.
-    pub fn new_session_key<R: Rng + CryptoRng>(self, rng: &mut R) -> Vec<u8> {
+    pub fn new_session_key<R: Rng + CryptoRng + ?Sized>(self, rng: &mut R) -> Vec<u8> {
         let mut session_key = vec![0u8; self.key_size()];
         rng.fill_bytes(&mut session_key);
         session_key
     }
 }
 
+/// Chooses the symmetric algorithm to encrypt for a recipient with.
+///
+/// The first algorithm in the recipient's preferences that is in `our_supported` is used.
+/// Without a mutual choice AES-256 is used. Plaintext is never chosen.
+pub fn negotiate_sym_algorithm(
+    our_supported: &[SymmetricKeyAlgorithm],
+    their_preferred: &[SymmetricKeyAlgorithm],
+) -> SymmetricKeyAlgorithm {
+    their_preferred
+        .iter()
+        .find(|alg| **alg != SymmetricKeyAlgorithm::Plaintext && our_supported.contains(alg))
+        .copied()
+        .unwrap_or(SymmetricKeyAlgorithm::AES256)
+}
+
 #[cfg(test)]
 mod tests {
+    #![allow(clippy::unwrap_used)]
+
     use super::*;
 
     use rand::SeedableRng;
@@ -632,15 +695,54 @@ mod tests {
     roundtrip!(roundtrip_aes128, SymmetricKeyAlgorithm::AES128);
     roundtrip!(roundtrip_aes192, SymmetricKeyAlgorithm::AES192);
     roundtrip!(roundtrip_aes256, SymmetricKeyAlgorithm::AES256);
+    #[cfg(feature = "tripledes")]
     roundtrip!(roundtrip_tripledes, SymmetricKeyAlgorithm::TripleDES);
+    #[cfg(feature = "blowfish")]
     roundtrip!(roundtrip_blowfish, SymmetricKeyAlgorithm::Blowfish);
+    #[cfg(feature = "twofish")]
     roundtrip!(roundtrip_twofish, SymmetricKeyAlgorithm::Twofish);
+    #[cfg(feature = "cast5")]
     roundtrip!(roundtrip_cast5, SymmetricKeyAlgorithm::CAST5);
+    #[cfg(feature = "idea")]
     roundtrip!(roundtrip_idea, SymmetricKeyAlgorithm::IDEA);
+    #[cfg(feature = "camellia")]
     roundtrip!(roundtrip_camellia128, SymmetricKeyAlgorithm::Camellia128);
+    #[cfg(feature = "camellia")]
     roundtrip!(roundtrip_camellia192, SymmetricKeyAlgorithm::Camellia192);
+    #[cfg(feature = "camellia")]
     roundtrip!(roundtrip_camellia256, SymmetricKeyAlgorithm::Camellia256);
 
+    #[test]
+    fn decrypt_protected_missing_mdc() {
+        let key = [1u8; 16];
+        let alg = SymmetricKeyAlgorithm::AES128;
+
+        for len in [0, 5, 30] {
+            let data = vec![2u8; len];
+            let mut ciphertext = alg.encrypt_protected(&key, &data).unwrap();
+            ciphertext.truncate(ciphertext.len() - 22);
+
+            let res = alg.decrypt_protected(&key, &mut ciphertext);
+            // Too short inputs fail to decrypt at all.
+            assert!(res.is_err(), "{len}");
+            if len > 0 {
+                assert!(matches!(res, Err(Error::MdcError)), "{len}: {res:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn decrypt_protected_modified_mdc() {
+        let key = [1u8; 16];
+        let alg = SymmetricKeyAlgorithm::AES128;
+
+        let mut ciphertext = alg.encrypt_protected(&key, b"hello world").unwrap();
+        *ciphertext.last_mut().unwrap() ^= 1;
+
+        let res = alg.decrypt_protected(&key, &mut ciphertext);
+        assert!(matches!(res, Err(Error::MdcError)), "{res:?}");
+    }
+
     #[test]
     pub fn decrypt_without_enough_ciphertext() {
         let key: [u8; 0] = [];
@@ -649,4 +751,19 @@ mod tests {
             .decrypt(&key, &mut cipher_text)
             .is_err());
     }
+
+    #[test]
+    fn test_negotiate_sym_algorithm() {
+        use SymmetricKeyAlgorithm::*;
+
+        let ours = [AES256, AES192, AES128, Plaintext];
+        assert_eq!(negotiate_sym_algorithm(&ours, &[AES128, AES256]), AES128);
+        assert_eq!(negotiate_sym_algorithm(&ours, &[Twofish, AES192]), AES192);
+        assert_eq!(
+            negotiate_sym_algorithm(&ours, &[Plaintext, TripleDES]),
+            AES256
+        );
+        assert_eq!(negotiate_sym_algorithm(&ours, &[]), AES256);
+        assert_eq!(negotiate_sym_algorithm(&[], &[AES128]), AES256);
+    }
 }
@@ -1,10 +1,12 @@
 use std::num::TryFromIntError;
 
-use ed25519_dalek::SignatureError;
 use nom::{
     error::{FromExternalError, ParseError},
     ErrorConvert,
 };
+use signature::Error as SignatureError;
+
+use crate::types::Tag;
 
 pub type Result<T> = ::std::result::Result<T, Error>;
 
@@ -30,8 +32,9 @@ pub enum Error {
     RequestedSizeTooLarge,
     #[error("no matching packet found")]
     NoMatchingPacket,
-    #[error("more than one matching packet was found")]
+    #[error("too many packets")]
     TooManyPackets,
+    #[cfg(feature = "rsa")]
     #[error("rsa error: {0:?}")]
     RSAError(rsa::errors::Error),
     #[error("elliptic error: {0:?}")]
@@ -87,6 +90,67 @@ pub enum Error {
     Ocb,
     #[error("SHA1 hash collision detected")]
     Sha1HashCollision,
+    #[error("truncated ciphertext")]
+    TruncatedCiphertext,
+    #[error("one pass signature does not match its signature: {0}")]
+    OnePassSignatureMismatch(String),
+    #[error("missing signature for one pass signature")]
+    MissingSignature,
+    #[error(
+        "invalid signature, but it is valid for the data {}",
+        if *.signed_with_newline { "with a trailing newline" } else { "without the trailing newline" }
+    )]
+    TrailingNewlineMismatch {
+        /// Whether the signed data ended in a newline, that the verified data lacks.
+        signed_with_newline: bool,
+    },
+    #[error("invalid key material: {0}")]
+    InvalidKeyMaterial(String),
+    /// None of the matching ESK packets of a message could be opened, with the error for
+    /// each of them, by the index of the packet.
+    #[error(
+        "no ESK could be opened: {}",
+        .0.iter().map(|(index, err)| format!("ESK {index}: {err}")).collect::<Vec<_>>().join(", ")
+    )]
+    NoEskOpened(Vec<(usize, Error)>),
+    /// A step of a [`maintain`](crate::maintain) operation failed.
+    #[error("failed to {step}: {source}")]
+    Maintenance { step: String, source: Box<Error> },
+    #[error(
+        "primary key {} does not match {}",
+        hex::encode(.found),
+        hex::encode(.expected)
+    )]
+    PrimaryKeyMismatch { expected: Vec<u8>, found: Vec<u8> },
+    #[error("unsupported S2K specifier {0}")]
+    UnsupportedS2k(u8),
+    #[error("cancelled")]
+    Cancelled,
+    /// The first authentication tag of a SEIPDv2 packet did not verify, most likely the
+    /// session key is wrong.
+    #[error("wrong session key")]
+    WrongSessionKey,
+    /// An authentication tag after the first one of a SEIPDv2 packet did not verify, the
+    /// session key is right but the ciphertext was modified or truncated.
+    #[error("corrupt ciphertext in chunk {chunk}")]
+    CiphertextCorrupt {
+        /// The index of the chunk that failed, the final tag uses the index after the last
+        /// chunk.
+        chunk: u64,
+    },
+    /// A field of a PKESK packet declares more octets than are left in the packet.
+    #[error("PKESK {field} of {len} octets is truncated, {remaining} octets are left")]
+    PkeskTruncated {
+        field: &'static str,
+        len: usize,
+        remaining: usize,
+    },
+    /// A field of a PKESK packet has a length that is not possible for it.
+    #[error("invalid PKESK {field} length {len}")]
+    InvalidPkeskLength { field: &'static str, len: usize },
+    /// The body of a packet is larger than the limit for its type.
+    #[error("{tag:?} packet too large: {size} bytes, limit is {max}")]
+    PacketTooLarge { tag: Tag, size: usize, max: usize },
 }
 
 impl Error {
@@ -101,6 +165,7 @@ impl Error {
             Error::RequestedSizeTooLarge => 6,
             Error::NoMatchingPacket => 7,
             Error::TooManyPackets => 8,
+            #[cfg(feature = "rsa")]
             Error::RSAError(_) => 9,
             Error::IOError { .. } => 10,
             Error::MissingPackets => 11,
@@ -126,6 +191,31 @@ impl Error {
             Error::Eax => 31,
             Error::Ocb => 32,
             Error::Sha1HashCollision => 33,
+            Error::TruncatedCiphertext => 34,
+            Error::OnePassSignatureMismatch(_) => 35,
+            Error::MissingSignature => 36,
+            Error::TrailingNewlineMismatch { .. } => 37,
+            Error::InvalidKeyMaterial(_) => 38,
+            Error::NoEskOpened(_) => 39,
+            Error::Maintenance { .. } => 40,
+            Error::PrimaryKeyMismatch { .. } => 41,
+            Error::UnsupportedS2k(_) => 42,
+            Error::Cancelled => 43,
+            Error::WrongSessionKey => 44,
+            Error::CiphertextCorrupt { .. } => 45,
+            Error::PkeskTruncated { .. } => 46,
+            Error::InvalidPkeskLength { .. } => 47,
+            Error::PacketTooLarge { .. } => 48,
+        }
+    }
+
+    /// Reports the errors of all ESK packets that were tried, or [`Error::MissingKey`] if
+    /// there was none to try.
+    pub(crate) fn no_esk_opened(errors: Vec<(usize, Error)>) -> Self {
+        if errors.is_empty() {
+            Error::MissingKey
+        } else {
+            Error::NoEskOpened(errors)
         }
     }
 }
@@ -184,6 +274,7 @@ impl<T> From<nom::Err<nom::error::Error<T>>> for Error {
     }
 }
 
+#[cfg(feature = "rsa")]
 impl From<rsa::errors::Error> for Error {
     fn from(err: rsa::errors::Error) -> Error {
         Error::RSAError(err)
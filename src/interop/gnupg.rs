@@ -0,0 +1,646 @@
+//! # GnuPG
+//!
+//! Reads the secret keys `gpg-agent` (GnuPG 2.1 and later) stores in `private-keys-v1.d`.
+//!
+//! These files only carry the key material, so they are combined with the matching public
+//! certificate, which supplies creation times, user ids and binding signatures.
+//!
+//! ```no_run
+//! use pgp::interop::gnupg::{import_secret_key, GnupgSecretKey};
+//! use pgp::{Deserializable, SignedPublicKey};
+//!
+//! # fn main() -> pgp::errors::Result<()> {
+//! let (public, _) = SignedPublicKey::from_armor_single(std::fs::File::open("key.asc")?)?;
+//! let keys = [
+//!     GnupgSecretKey::from_bytes(&std::fs::read("primary.key")?)?,
+//!     GnupgSecretKey::from_bytes(&std::fs::read("subkey.key")?)?,
+//! ];
+//! let secret = import_secret_key(&public, &keys, || "passphrase".into())?;
+//! # Ok(())
+//! # }
+//! ```
+
+use aes::Aes128;
+use generic_array::GenericArray;
+use ocb3::aead::{AeadInPlace, KeyInit};
+use ocb3::{Nonce as OcbNonce, Ocb3, Tag as OcbTag};
+use sha1::{Digest, Sha1};
+use zeroize::{Zeroize, Zeroizing};
+
+use super::sexp::Sexp;
+use crate::composed::signed_key::{SignedPublicKey, SignedSecretKey, SignedSecretSubKey};
+use crate::errors::{Error, Result};
+use crate::packet;
+use crate::types::{
+    EcdsaPublicParams, Mpi, PlainSecretParams, PublicParams, SecretParams, MAX_ITERATION_COUNT,
+};
+
+/// Protection mode used by GnuPG 2.1 and later: iterated and salted SHA-1 S2K and AES-128-OCB.
+const PROTECTION_OCB: &[u8] = b"openpgp-s2k3-ocb-aes";
+/// Protection mode used by GnuPG 2.0 and earlier: iterated and salted SHA-1 S2K and AES-128-CBC.
+const PROTECTION_CBC: &[u8] = b"openpgp-s2k3-sha1-aes-cbc";
+/// Keys imported unchanged from an OpenPGP secret key packet.
+const PROTECTION_NATIVE: &[u8] = b"openpgp-native";
+
+/// Size of the AES-128 key derived from the passphrase.
+const KEY_SIZE: usize = 16;
+
+type Aes128Ocb3 = Ocb3<Aes128>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// `private-key`, stored in the clear.
+    Plain,
+    /// `protected-private-key`, encrypted with a passphrase.
+    Protected,
+    /// `shadowed-private-key`, the key material lives on a smartcard.
+    Shadowed,
+}
+
+/// A secret key, as stored by `gpg-agent`.
+///
+/// Only holds the key material of a single (sub)key, without any OpenPGP metadata.
+#[derive(Clone, PartialEq, Eq)]
+pub struct GnupgSecretKey {
+    kind: Kind,
+    /// The algorithm list, e.g. `(rsa (n ..) (e ..) ..)`.
+    key: Sexp,
+}
+
+impl std::fmt::Debug for GnupgSecretKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GnupgSecretKey")
+            .field("kind", &self.kind)
+            .field(
+                "algorithm",
+                &self
+                    .key
+                    .name()
+                    .map(String::from_utf8_lossy)
+                    .unwrap_or_default(),
+            )
+            .finish()
+    }
+}
+
+impl Drop for GnupgSecretKey {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+impl GnupgSecretKey {
+    /// Parses the content of a `private-keys-v1.d/<keygrip>.key` file.
+    ///
+    /// Both the extended key format (GnuPG 2.2.20 and later) and plain S-expressions are
+    /// supported.
+    pub fn from_bytes(input: &[u8]) -> Result<Self> {
+        let sexp = if trim_start(input).starts_with(b"(") {
+            Sexp::parse(input)?
+        } else {
+            let mut value = Zeroizing::new(extended_key_value(input)?);
+            let sexp = Sexp::parse(&value);
+            value.zeroize();
+            sexp?
+        };
+
+        let kind = match sexp.name() {
+            Some(b"private-key") => Kind::Plain,
+            Some(b"protected-private-key") => Kind::Protected,
+            Some(b"shadowed-private-key") => Kind::Shadowed,
+            Some(name) => bail!("unknown key type {}", String::from_utf8_lossy(name)),
+            None => bail!("missing key type"),
+        };
+
+        let key = match sexp.as_list().and_then(|items| items.get(1)) {
+            Some(key @ Sexp::List(_)) => key.clone(),
+            _ => bail!("missing key parameters"),
+        };
+
+        Ok(GnupgSecretKey { kind, key })
+    }
+
+    /// Returns true if the key material is encrypted with a passphrase.
+    pub fn is_protected(&self) -> bool {
+        self.kind == Kind::Protected
+    }
+
+    /// Returns true if the key material is stored on a smartcard, and not in the file.
+    pub fn is_shadowed(&self) -> bool {
+        self.kind == Kind::Shadowed
+    }
+
+    /// Returns true if this is the secret part of the given public key.
+    pub fn matches(&self, public: &PublicParams) -> bool {
+        let Some(layout) = Layout::new(public) else {
+            return false;
+        };
+
+        self.key
+            .name()
+            .is_some_and(|name| layout.names.contains(&name))
+            && layout.public.iter().all(|(name, value)| {
+                self.key
+                    .value(name)
+                    .is_some_and(|v| strip_leading_zeros(v) == strip_leading_zeros(value))
+            })
+    }
+
+    /// Decrypts the key material, `key_pw` is only called for protected keys.
+    pub fn unlock<F>(&self, key_pw: F, public: &PublicParams) -> Result<PlainSecretParams>
+    where
+        F: FnOnce() -> String,
+    {
+        ensure!(
+            !self.is_shadowed(),
+            "the key material is stored on a smartcard (shadowed-private-key)"
+        );
+        ensure!(self.matches(public), "secret key does not match public key");
+        let layout = Layout::new(public).ok_or_else(|| format_err!("unsupported key"))?;
+
+        let mut params = if self.is_protected() {
+            let pw = Zeroizing::new(key_pw());
+            let secret = self.decrypt(&pw)?;
+            layout.secret_params(&secret)?
+        } else {
+            layout.secret_params(&self.key)?
+        };
+
+        // make sure the key material is consistent
+        params.repair_crt_coefficient()?;
+        params.as_ref().as_repr(public)?;
+
+        Ok(params)
+    }
+
+    /// Decrypts the `(protected ..)` element, returning the list of secret parameters.
+    fn decrypt(&self, pw: &str) -> Result<Zeroizing<Sexp>> {
+        let protected = self
+            .key
+            .find(b"protected")
+            .and_then(Sexp::as_list)
+            .ok_or_else(|| format_err!("missing protected parameters"))?;
+
+        let mode = protected.get(1).and_then(Sexp::as_atom).unwrap_or_default();
+        match mode {
+            PROTECTION_OCB => {}
+            PROTECTION_CBC => unsupported_err!("legacy CBC key protection"),
+            PROTECTION_NATIVE => unsupported_err!("openpgp-native key protection"),
+            _ => unsupported_err!("key protection {}", String::from_utf8_lossy(mode)),
+        }
+
+        let (Some(params), Some(ciphertext)) = (
+            protected.get(2).and_then(Sexp::as_list),
+            protected.get(3).and_then(Sexp::as_atom),
+        ) else {
+            bail!("invalid protected parameters");
+        };
+        let (Some(s2k), Some(nonce)) = (
+            params.first().and_then(Sexp::as_list),
+            params.get(1).and_then(Sexp::as_atom),
+        ) else {
+            bail!("invalid protected parameters");
+        };
+        let (Some(b"sha1"), Some(salt), Some(count)) = (
+            s2k.first().and_then(Sexp::as_atom),
+            s2k.get(1).and_then(Sexp::as_atom),
+            s2k.get(2).and_then(Sexp::as_atom),
+        ) else {
+            bail!("invalid S2K parameters");
+        };
+        let count: usize = std::str::from_utf8(count)?.parse()?;
+        // GnuPG uses the same limit, larger counts would keep us hashing for as long as the
+        // file asks for
+        ensure!(
+            count <= MAX_ITERATION_COUNT,
+            "S2K count too large: {}",
+            count
+        );
+
+        ensure_eq!(salt.len(), 8, "invalid S2K salt");
+        ensure_eq!(nonce.len(), 12, "invalid nonce");
+        ensure!(ciphertext.len() > 16, "invalid ciphertext");
+
+        // The whole key, except for the protected element, is authenticated.
+        let aad = Sexp::List(
+            self.key
+                .as_list()
+                .unwrap_or_default()
+                .iter()
+                .filter(|item| item.name() != Some(b"protected"))
+                .cloned()
+                .collect(),
+        )
+        .to_canonical();
+
+        let key = derive_key(pw, salt, count);
+        let (ciphertext, tag) = ciphertext.split_at(ciphertext.len() - 16);
+        let mut plaintext = Zeroizing::new(ciphertext.to_vec());
+        Aes128Ocb3::new(GenericArray::from_slice(&key[..]))
+            .decrypt_in_place_detached(
+                OcbNonce::from_slice(nonce),
+                &aad,
+                &mut plaintext,
+                OcbTag::from_slice(tag),
+            )
+            .map_err(|_| Error::Ocb)?;
+
+        // the plaintext wraps the list of secret parameters, `(((d ..) ..))`
+        let mut sexp = Zeroizing::new(Sexp::parse(&plaintext)?);
+        match &mut *sexp {
+            Sexp::List(items) if items.len() == 1 && items[0].as_list().is_some() => {
+                Ok(Zeroizing::new(items.remove(0)))
+            }
+            _ => bail!("invalid protected key material"),
+        }
+    }
+}
+
+/// How the parameters of a public key are named in the S-expressions.
+struct Layout<'a> {
+    /// Accepted algorithm names.
+    names: &'static [&'static [u8]],
+    public: Vec<(&'static [u8], &'a [u8])>,
+    secret: Secret,
+}
+
+#[derive(Clone, Copy)]
+enum Secret {
+    Rsa,
+    Dsa,
+    Elgamal,
+    Ecdsa,
+    Ecdh,
+    EdDSA,
+}
+
+impl<'a> Layout<'a> {
+    fn new(public: &'a PublicParams) -> Option<Self> {
+        const ECC: &[&[u8]] = &[b"ecc", b"ecdsa", b"ecdh", b"eddsa"];
+
+        let layout = match public {
+            PublicParams::RSA { n, e } => Layout {
+                names: &[b"rsa"],
+                public: vec![(b"n", n.as_bytes()), (b"e", e.as_bytes())],
+                secret: Secret::Rsa,
+            },
+            PublicParams::DSA { p, q, g, y } => Layout {
+                names: &[b"dsa"],
+                public: vec![
+                    (b"p", p.as_bytes()),
+                    (b"q", q.as_bytes()),
+                    (b"g", g.as_bytes()),
+                    (b"y", y.as_bytes()),
+                ],
+                secret: Secret::Dsa,
+            },
+            PublicParams::Elgamal { p, g, y } => Layout {
+                names: &[b"elg"],
+                public: vec![
+                    (b"p", p.as_bytes()),
+                    (b"g", g.as_bytes()),
+                    (b"y", y.as_bytes()),
+                ],
+                secret: Secret::Elgamal,
+            },
+            PublicParams::ECDSA(params) => {
+                let p = match params {
+                    #[cfg(feature = "nist")]
+                    EcdsaPublicParams::P256 { p, .. }
+                    | EcdsaPublicParams::P384 { p, .. }
+                    | EcdsaPublicParams::P521 { p, .. } => p,
+                    #[cfg(feature = "k256")]
+                    EcdsaPublicParams::Secp256k1 { p, .. } => p,
+                    EcdsaPublicParams::Unsupported { p, .. } => p,
+                };
+                Layout {
+                    names: ECC,
+                    public: vec![(b"q", p.as_bytes())],
+                    secret: Secret::Ecdsa,
+                }
+            }
+            PublicParams::ECDH { p, .. } => Layout {
+                names: ECC,
+                public: vec![(b"q", p.as_bytes())],
+                secret: Secret::Ecdh,
+            },
+            PublicParams::EdDSA { q, .. } => Layout {
+                names: ECC,
+                public: vec![(b"q", q.as_bytes())],
+                secret: Secret::EdDSA,
+            },
+            PublicParams::Unknown { .. } => return None,
+        };
+
+        Some(layout)
+    }
+
+    /// Collects the secret parameters from the given list.
+    fn secret_params(&self, list: &Sexp) -> Result<PlainSecretParams> {
+        let get = |name: &[u8]| -> Result<Mpi> {
+            list.value(name)
+                .map(Mpi::from_raw_slice)
+                .ok_or_else(|| format_err!("missing secret parameter {}", char::from(name[0])))
+        };
+
+        let params = match self.secret {
+            Secret::Rsa => PlainSecretParams::RSA {
+                d: get(b"d")?,
+                p: get(b"p")?,
+                q: get(b"q")?,
+                u: get(b"u")?,
+            },
+            Secret::Dsa => PlainSecretParams::DSA(get(b"x")?),
+            Secret::Elgamal => PlainSecretParams::Elgamal(get(b"x")?),
+            Secret::Ecdsa => PlainSecretParams::ECDSA(get(b"d")?),
+            Secret::Ecdh => PlainSecretParams::ECDH(get(b"d")?),
+            Secret::EdDSA => PlainSecretParams::EdDSA(get(b"d")?),
+        };
+
+        Ok(params)
+    }
+}
+
+/// Combines a public key with the matching secret keys exported from `gpg-agent`.
+///
+/// The primary key must be among `keys`, subkeys without a matching secret key are kept as public
+/// subkeys. The resulting secret key material is not protected with a passphrase.
+pub fn import_secret_key<F>(
+    public: &SignedPublicKey,
+    keys: &[GnupgSecretKey],
+    key_pw: F,
+) -> Result<SignedSecretKey>
+where
+    F: Fn() -> String,
+{
+    let find = |params: &PublicParams| keys.iter().find(|key| key.matches(params));
+
+    let primary = find(public.primary_key.public_params())
+        .ok_or_else(|| format_err!("missing secret key for the primary key"))?;
+    let primary_key = packet::SecretKey {
+        details: public.primary_key.clone(),
+        secret_params: SecretParams::Plain(
+            primary.unlock(&key_pw, public.primary_key.public_params())?,
+        ),
+    };
+
+    let mut public_subkeys = Vec::new();
+    let mut secret_subkeys = Vec::new();
+    for subkey in &public.public_subkeys {
+        match find(subkey.key.public_params()) {
+            Some(secret) => {
+                let params = secret.unlock(&key_pw, subkey.key.public_params())?;
+                secret_subkeys.push(SignedSecretSubKey::new(
+                    packet::SecretSubkey {
+                        details: subkey.key.clone(),
+                        secret_params: SecretParams::Plain(params),
+                    },
+                    subkey.signatures.clone(),
+                ));
+            }
+            None => public_subkeys.push(subkey.clone()),
+        }
+    }
+
+    Ok(SignedSecretKey::new(
+        primary_key,
+        public.details.clone(),
+        public_subkeys,
+        secret_subkeys,
+    ))
+}
+
+/// Extracts the `Key` item from the extended key format.
+///
+/// Continuation lines start with whitespace, which is dropped together with the line break.
+fn extended_key_value(input: &[u8]) -> Result<Vec<u8>> {
+    let mut value: Option<Vec<u8>> = None;
+    let mut in_key = false;
+
+    for line in input.split(|c| *c == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        match line.first() {
+            Some(b' ' | b'\t') => {
+                if let (true, Some(value)) = (in_key, value.as_mut()) {
+                    value.extend_from_slice(&line[1..]);
+                }
+            }
+            Some(b'#') | None => in_key = false,
+            Some(_) => {
+                let (name, rest) = match line.iter().position(|c| *c == b':') {
+                    Some(i) => line.split_at(i),
+                    None => bail!("invalid line in extended key format"),
+                };
+                in_key = name.eq_ignore_ascii_case(b"key");
+                if in_key {
+                    ensure!(value.is_none(), "duplicate Key item");
+                    value = Some(trim_start(&rest[1..]).to_vec());
+                }
+            }
+        }
+    }
+
+    value.ok_or_else(|| format_err!("missing Key item"))
+}
+
+/// Iterated and salted S2K with SHA-1, with a decoded `count`.
+fn derive_key(pw: &str, salt: &[u8], count: usize) -> Zeroizing<[u8; KEY_SIZE]> {
+    let mut hasher = Sha1::new();
+    let data_size = salt.len() + pw.len();
+    // if the count is less, hash one full set
+    let mut count = count.max(data_size);
+
+    while count > data_size {
+        hasher.update(salt);
+        hasher.update(pw.as_bytes());
+        count -= data_size;
+    }
+
+    if count < salt.len() {
+        hasher.update(&salt[..count]);
+    } else {
+        hasher.update(salt);
+        hasher.update(&pw.as_bytes()[..count - salt.len()]);
+    }
+
+    let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+    key.copy_from_slice(&hasher.finalize()[..KEY_SIZE]);
+    key
+}
+
+fn trim_start(value: &[u8]) -> &[u8] {
+    let start = value
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(value.len());
+    &value[start..]
+}
+
+fn strip_leading_zeros(value: &[u8]) -> &[u8] {
+    let start = value.iter().position(|b| *b != 0).unwrap_or(value.len());
+    &value[start..]
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::{Deserializable, Message};
+
+    fn load(name: &str) -> (SignedPublicKey, Vec<GnupgSecretKey>) {
+        let dir = "./tests/unit-tests/gnupg";
+        let (public, _) = SignedPublicKey::from_armor_single(
+            std::fs::File::open(format!("{dir}/{name}.pub.asc")).unwrap(),
+        )
+        .unwrap();
+        let keys = [
+            format!("{dir}/{name}.key"),
+            format!("{dir}/{name}-subkey.key"),
+        ]
+        .iter()
+        .map(|path| GnupgSecretKey::from_bytes(&std::fs::read(path).unwrap()).unwrap())
+        .collect();
+
+        (public, keys)
+    }
+
+    fn roundtrip(name: &str, pw: &str) {
+        let (public, keys) = load(name);
+        assert_eq!(keys[0].is_protected(), !pw.is_empty());
+        assert!(keys[0].matches(public.primary_key.public_params()));
+        assert!(!keys[1].matches(public.primary_key.public_params()));
+
+        let secret = import_secret_key(&public, &keys, || pw.into()).unwrap();
+        secret.verify().unwrap();
+        assert_eq!(secret.secret_subkeys.len(), 1);
+        assert!(secret.public_subkeys.is_empty());
+        assert_eq!(SignedPublicKey::from(secret.clone()), public);
+
+        // decrypt a message GnuPG encrypted to the subkey
+        let (msg, _) = Message::from_armor_single(
+            std::fs::File::open(format!("./tests/unit-tests/gnupg/{name}.msg.asc")).unwrap(),
+        )
+        .unwrap();
+        let (decrypted, _) = msg.decrypt(String::new, &[&secret]).unwrap();
+        assert_eq!(
+            decrypted.get_content().unwrap().unwrap(),
+            b"hello from gnupg\n"
+        );
+
+        // sign with the primary key
+        let signed = Message::new_literal("hello", "world")
+            .sign(
+                &secret,
+                String::new,
+                crate::crypto::hash::HashAlgorithm::SHA2_256,
+            )
+            .unwrap();
+        signed.verify(&public).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "rsa")]
+    fn test_import_rsa() {
+        roundtrip("rsa", "test");
+    }
+
+    #[test]
+    #[cfg(feature = "x25519")]
+    fn test_import_ed25519_cv25519() {
+        roundtrip("ed25519", "test");
+    }
+
+    #[test]
+    #[cfg(feature = "nist")]
+    fn test_import_nist_p256() {
+        roundtrip("p256", "test");
+    }
+
+    #[test]
+    #[cfg(feature = "x25519")]
+    fn test_import_unprotected() {
+        roundtrip("unprotected", "");
+    }
+
+    #[test]
+    fn test_import_wrong_passphrase() {
+        let (public, keys) = load("ed25519");
+        assert!(matches!(
+            import_secret_key(&public, &keys, || "wrong".into()),
+            Err(Error::Ocb)
+        ));
+    }
+
+    #[test]
+    fn test_import_missing_keys() {
+        let (public, keys) = load("ed25519");
+
+        // without the subkey, it stays public
+        let secret = import_secret_key(&public, &keys[..1], || "test".into()).unwrap();
+        assert!(secret.secret_subkeys.is_empty());
+        assert_eq!(secret.public_subkeys.len(), 1);
+
+        // without the primary key
+        assert!(import_secret_key(&public, &keys[1..], || "test".into()).is_err());
+
+        // keys of another certificate
+        let (other, _) = load("unprotected");
+        assert!(import_secret_key(&other, &keys, || "test".into()).is_err());
+    }
+
+    #[test]
+    fn test_shadowed_key() {
+        let (public, _) = load("ed25519");
+        let PublicParams::EdDSA { q, .. } = public.primary_key.public_params() else {
+            panic!("unexpected key");
+        };
+
+        let shadowed = format!(
+            "Created: 20261016T132649\nKey: (shadowed-private-key (ecc (curve Ed25519)(flags eddsa)(q\n #{}#)\n (shadowed t1-v1 (#D2760001240103040006123456780000# OPENPGP.1)\n )))\n",
+            hex::encode_upper(q.as_bytes())
+        );
+        let key = GnupgSecretKey::from_bytes(shadowed.as_bytes()).unwrap();
+        assert!(key.is_shadowed());
+        assert!(!key.is_protected());
+        assert!(key.matches(public.primary_key.public_params()));
+
+        let err = import_secret_key(&public, &[key], String::new).unwrap_err();
+        assert!(err.to_string().contains("smartcard"), "{err}");
+    }
+
+    #[test]
+    fn test_from_bytes_invalid() {
+        assert!(GnupgSecretKey::from_bytes(b"").is_err());
+        assert!(GnupgSecretKey::from_bytes(b"Created: 20261016T132649\n").is_err());
+        assert!(GnupgSecretKey::from_bytes(b"(public-key (ecc (q #40#)))").is_err());
+        assert!(GnupgSecretKey::from_bytes(b"(private-key)").is_err());
+    }
+
+    #[test]
+    fn test_import_s2k_count_too_large() {
+        let (public, _) = load("ed25519");
+        let key = std::fs::read("./tests/unit-tests/gnupg/ed25519.key").unwrap();
+        let key = String::from_utf8(key).unwrap();
+
+        let count = (MAX_ITERATION_COUNT + 1).to_string();
+        let key = key.replace("\"65536\"", &format!("\"{count}\""));
+        let key = GnupgSecretKey::from_bytes(key.as_bytes()).unwrap();
+
+        let err = import_secret_key(&public, &[key], || "test".into()).unwrap_err();
+        assert!(err.to_string().contains("S2K count"), "{err}");
+    }
+
+    #[test]
+    fn test_derive_key() {
+        // the count is smaller than salt and passphrase, hashed once
+        let key = derive_key("pw", b"saltsalt", 1);
+        assert_eq!(&key[..], &Sha1::digest(b"saltsaltpw")[..KEY_SIZE]);
+
+        let key = derive_key("pw", b"saltsalt", 25);
+        assert_eq!(
+            &key[..],
+            &Sha1::digest(b"saltsaltpwsaltsaltpwsalts")[..KEY_SIZE]
+        );
+    }
+}
@@ -0,0 +1,6 @@
+//! # Interoperability
+//!
+//! Helpers to exchange key material with other OpenPGP implementations, in their native formats.
+
+pub mod gnupg;
+mod sexp;
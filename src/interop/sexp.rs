@@ -0,0 +1,316 @@
+//! Minimal S-expression parser, for the formats used by libgcrypt.
+//!
+//! Supports the canonical encoding (`(3:rsa(1:n...)...)`) as well as the advanced, human
+//! readable one (`(rsa (n #00E0...#) ...)`), as written by GnuPG.
+
+use zeroize::Zeroize;
+
+use crate::errors::Result;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Sexp {
+    Atom(Vec<u8>),
+    List(Vec<Sexp>),
+}
+
+impl Zeroize for Sexp {
+    fn zeroize(&mut self) {
+        match self {
+            Sexp::Atom(data) => data.zeroize(),
+            Sexp::List(items) => {
+                for item in items.iter_mut() {
+                    item.zeroize();
+                }
+            }
+        }
+    }
+}
+
+impl Sexp {
+    /// Parses a single S-expression, trailing whitespace is ignored.
+    pub(crate) fn parse(input: &[u8]) -> Result<Self> {
+        let mut parser = Parser { input, pos: 0 };
+        let sexp = parser.parse_list()?;
+        parser.skip_whitespace();
+        ensure_eq!(parser.pos, input.len(), "trailing data after S-expression");
+
+        Ok(sexp)
+    }
+
+    /// Encodes the S-expression in the canonical encoding.
+    pub(crate) fn to_canonical(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.write_canonical(&mut out);
+        out
+    }
+
+    fn write_canonical(&self, out: &mut Vec<u8>) {
+        match self {
+            Sexp::Atom(data) => {
+                out.extend_from_slice(data.len().to_string().as_bytes());
+                out.push(b':');
+                out.extend_from_slice(data);
+            }
+            Sexp::List(items) => {
+                out.push(b'(');
+                for item in items {
+                    item.write_canonical(out);
+                }
+                out.push(b')');
+            }
+        }
+    }
+
+    pub(crate) fn as_atom(&self) -> Option<&[u8]> {
+        match self {
+            Sexp::Atom(data) => Some(data),
+            Sexp::List(_) => None,
+        }
+    }
+
+    pub(crate) fn as_list(&self) -> Option<&[Sexp]> {
+        match self {
+            Sexp::Atom(_) => None,
+            Sexp::List(items) => Some(items),
+        }
+    }
+
+    /// The first atom of a list, naming it.
+    pub(crate) fn name(&self) -> Option<&[u8]> {
+        self.as_list()?.first()?.as_atom()
+    }
+
+    /// Finds the sublist with the given name.
+    pub(crate) fn find(&self, name: &[u8]) -> Option<&Sexp> {
+        self.as_list()?
+            .iter()
+            .find(|item| item.name() == Some(name))
+    }
+
+    /// Returns the value of the sublist `(name value)`.
+    pub(crate) fn value(&self, name: &[u8]) -> Option<&[u8]> {
+        self.find(name)?.as_list()?.get(1)?.as_atom()
+    }
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Result<()> {
+        ensure_eq!(self.peek(), Some(c), "expected {:?}", char::from(c));
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn parse_list(&mut self) -> Result<Sexp> {
+        self.skip_whitespace();
+        self.expect(b'(')?;
+
+        let mut items = Vec::new();
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b')') => {
+                    self.pos += 1;
+                    return Ok(Sexp::List(items));
+                }
+                Some(b'(') => items.push(self.parse_list()?),
+                Some(_) => items.push(Sexp::Atom(self.parse_atom()?)),
+                None => bail!("unterminated S-expression"),
+            }
+        }
+    }
+
+    fn parse_atom(&mut self) -> Result<Vec<u8>> {
+        match self.peek() {
+            Some(b'#') => self.parse_hex(),
+            Some(b'"') => self.parse_quoted(),
+            Some(b'|') => self.parse_base64(),
+            Some(c) if c.is_ascii_digit() => {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                    self.pos += 1;
+                }
+                if self.peek() == Some(b':') {
+                    // verbatim, length prefixed
+                    let len: usize = std::str::from_utf8(&self.input[start..self.pos])?.parse()?;
+                    self.pos += 1;
+                    ensure!(
+                        self.input.len() - self.pos >= len,
+                        "truncated S-expression atom"
+                    );
+                    let data = self.input[self.pos..self.pos + len].to_vec();
+                    self.pos += len;
+                    Ok(data)
+                } else {
+                    self.pos = start;
+                    self.parse_token()
+                }
+            }
+            Some(_) => self.parse_token(),
+            None => bail!("unterminated S-expression"),
+        }
+    }
+
+    fn parse_token(&mut self) -> Result<Vec<u8>> {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || b"-./_:*+=".contains(&c))
+        {
+            self.pos += 1;
+        }
+        ensure!(self.pos > start, "invalid character in S-expression");
+
+        Ok(self.input[start..self.pos].to_vec())
+    }
+
+    fn parse_hex(&mut self) -> Result<Vec<u8>> {
+        self.expect(b'#')?;
+        let mut digits = Vec::new();
+        loop {
+            match self.peek() {
+                Some(b'#') => break,
+                Some(c) if c.is_ascii_whitespace() => {}
+                Some(c) => digits.push(c),
+                None => bail!("unterminated hex string"),
+            }
+            self.pos += 1;
+        }
+        self.pos += 1;
+
+        hex::decode(digits).map_err(|_| format_err!("invalid hex string"))
+    }
+
+    fn parse_base64(&mut self) -> Result<Vec<u8>> {
+        use base64::engine::{general_purpose, Engine as _};
+
+        self.expect(b'|')?;
+        let mut data = Vec::new();
+        loop {
+            match self.peek() {
+                Some(b'|') => break,
+                Some(c) if c.is_ascii_whitespace() => {}
+                Some(c) => data.push(c),
+                None => bail!("unterminated base64 string"),
+            }
+            self.pos += 1;
+        }
+        self.pos += 1;
+
+        Ok(general_purpose::STANDARD.decode(data)?)
+    }
+
+    fn parse_quoted(&mut self) -> Result<Vec<u8>> {
+        self.expect(b'"')?;
+        let mut data = Vec::new();
+        loop {
+            let c = self
+                .peek()
+                .ok_or_else(|| format_err!("unterminated quoted string"))?;
+            self.pos += 1;
+            match c {
+                b'"' => return Ok(data),
+                b'\\' => {
+                    let c = self
+                        .peek()
+                        .ok_or_else(|| format_err!("unterminated quoted string"))?;
+                    self.pos += 1;
+                    match c {
+                        b'n' => data.push(b'\n'),
+                        b'r' => data.push(b'\r'),
+                        b't' => data.push(b'\t'),
+                        b'v' => data.push(0x0b),
+                        b'b' => data.push(0x08),
+                        b'f' => data.push(0x0c),
+                        b'x' => {
+                            let digits = self
+                                .input
+                                .get(self.pos..self.pos + 2)
+                                .ok_or_else(|| format_err!("truncated escape sequence"))?;
+                            data.extend(
+                                hex::decode(digits)
+                                    .map_err(|_| format_err!("invalid escape sequence"))?,
+                            );
+                            self.pos += 2;
+                        }
+                        b'0'..=b'7' => {
+                            let digits = self
+                                .input
+                                .get(self.pos - 1..self.pos + 2)
+                                .ok_or_else(|| format_err!("truncated escape sequence"))?;
+                            let value = u8::from_str_radix(std::str::from_utf8(digits)?, 8)
+                                .map_err(|_| format_err!("invalid escape sequence"))?;
+                            data.push(value);
+                            self.pos += 2;
+                        }
+                        b'\n' => {}
+                        _ => data.push(c),
+                    }
+                }
+                _ => data.push(c),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+
+    #[test]
+    fn test_parse_advanced_and_canonical() {
+        let advanced = Sexp::parse(
+            b"(private-key (ecc (curve \"NIST P-256\")(q\n #04AB\n CD#)(d |AQI=|)(x 3:abc)))",
+        )
+        .unwrap();
+        let canonical = Sexp::parse(
+            b"(11:private-key(3:ecc(5:curve10:NIST P-256)(1:q3:\x04\xab\xcd)(1:d2:\x01\x02)(1:x3:abc)))",
+        )
+        .unwrap();
+        assert_eq!(advanced, canonical);
+        assert_eq!(Sexp::parse(&canonical.to_canonical()).unwrap(), canonical);
+
+        assert_eq!(advanced.name(), Some(&b"private-key"[..]));
+        let ecc = advanced.find(b"ecc").unwrap();
+        assert_eq!(ecc.value(b"curve"), Some(&b"NIST P-256"[..]));
+        assert_eq!(ecc.value(b"q"), Some(&[0x04, 0xab, 0xcd][..]));
+        assert_eq!(ecc.value(b"missing"), None);
+    }
+
+    #[test]
+    fn test_parse_quoted_escapes() {
+        let sexp = Sexp::parse(b"(a \"x\\\"\\n\\x41\\101\\\n\xff\")").unwrap();
+        assert_eq!(
+            sexp,
+            Sexp::List(vec![
+                Sexp::Atom(b"a".to_vec()),
+                Sexp::Atom(b"x\"\nAA\xff".to_vec()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(Sexp::parse(b"(a (b)").is_err());
+        assert!(Sexp::parse(b"(a #0#)").is_err());
+        assert!(Sexp::parse(b"(a 5:abc)").is_err());
+        assert!(Sexp::parse(b"(a) b").is_err());
+        assert!(Sexp::parse(b"a").is_err());
+    }
+}
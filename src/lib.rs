@@ -8,6 +8,36 @@
 //! [Key generation]: crate::composed::key
 //! [signing and verifying with external hashing]: crate::composed::signed_key
 //! [packet based signing and verifying]: crate::packet
+//!
+//! The commonly used types and traits can be imported at once with `use pgp::prelude::*;`.
+//!
+//! For the common case of encrypting data with just a password, use
+//! [`encrypt_with_password`] and [`decrypt_with_password`].
+//!
+//! ## Randomness
+//!
+//! All operations that need randomness (key generation, session keys, ephemeral ECDH keys,
+//! encryption prefixes and S2K salts) take a caller supplied `R: CryptoRng + Rng`.
+//! Signature creation is deterministic and does not consume any randomness.
+//!
+//! The only places that use an implicit source ([`rand::thread_rng`]) are the convenience
+//! wrappers that have a `*_with_rng` counterpart, such as
+//! [`SecretKeyParams::generate`](crate::composed::key::SecretKeyParams::generate) and
+//! [`SymmetricKeyAlgorithm::encrypt`](crate::crypto::sym::SymmetricKeyAlgorithm::encrypt).
+//! Use the `*_with_rng` variants for deterministic tests or to plug in a hardware RNG.
+//!
+//! The generic parameters also accept type erased RNGs, such as `&mut dyn CryptoRngCore` or
+//! `Box<dyn CryptoRngCore>` from `rand_core`, so the RNG does not have to be known at compile
+//! time (e.g. when it is supplied over FFI). Functions taking `rng: R` accept either form,
+//! functions taking `rng: &mut R` accept a `&mut dyn CryptoRngCore` directly.
+//!
+//! ## Features
+//!
+//! The algorithm backends are behind cargo features, all enabled by default. Keys and
+//! messages using a disabled algorithm still parse, operations on them fail with
+//! [`Error::Unsupported`](crate::errors::Error::Unsupported). SHA-1, SHA-2, MD5, AES and
+//! OCB are always available. A verify only build for Ed25519 signatures needs just
+//! `default-features = false, features = ["ed25519", "sha2"]`.
 
 #![forbid(unsafe_code)]
 #![deny(
@@ -22,6 +52,18 @@
 #![allow(clippy::missing_const_for_fn, clippy::use_self)]
 // Enable backtraces for thiserror.
 #![cfg_attr(feature = "nightly", feature(error_generic_member_access))]
+// Without all public key backends, parts of the shared dispatch code are never reached.
+#![cfg_attr(
+    not(all(
+        feature = "rsa",
+        feature = "dsa",
+        feature = "nist",
+        feature = "k256",
+        feature = "x25519",
+        feature = "ed25519"
+    )),
+    allow(unused_variables, unused_mut, unreachable_code, dead_code)
+)]
 
 #[macro_use]
 extern crate nom;
@@ -40,23 +82,36 @@ extern crate smallvec;
 #[macro_use]
 extern crate pretty_assertions;
 
-// public so it can be used in doc test
+// public so it can be used in doc test, not part of the supported API
+#[doc(hidden)]
 #[macro_use]
 pub mod util;
 
 #[macro_use]
 pub mod errors;
 pub mod armor;
+// building blocks of the armor reader, not part of the supported API
+#[doc(hidden)]
 pub mod base64_decoder;
+#[doc(hidden)]
 pub mod base64_reader;
 pub mod composed;
 pub mod crypto;
 pub mod de;
+pub mod interop;
 pub mod line_writer;
+pub mod maintain;
 pub mod normalize_lines;
 pub mod packet;
+pub mod prelude;
 pub mod ser;
 pub mod types;
+pub mod wkd;
+
+/// Traits, that only types of this crate may implement, have `Sealed` as a supertrait.
+mod private {
+    pub trait Sealed {}
+}
 
 // reexports for easier use
 #[allow(unused_imports)]
@@ -0,0 +1,251 @@
+//! Maintenance of existing keys, as done by scripts and automation.
+//!
+//! Each operation takes and returns an armored key and reports the step that failed as
+//! [`Error::Maintenance`].
+
+use chrono::{DateTime, Duration, SubsecRound, Utc};
+
+use crate::composed::{Deserializable, SignedSecretKey};
+use crate::errors::{Error, Result};
+use crate::packet::{
+    Signature, SignatureConfig, SignatureType, SignatureVersion, Subpacket, SubpacketData,
+};
+use crate::types::{KeyTrait, Tag};
+use crate::ArmorOptions;
+
+/// Extends the expiration of a secret key, like `gpg --quick-set-expire`.
+///
+/// The key and all its subkeys expire `new_expiry` from now. The newest self-signature of each
+/// user id, the newest direct key signature and the newest binding signature of each subkey
+/// are issued again, with the new expiration and otherwise unchanged. Revoked user ids and
+/// subkeys are left alone, a revoked key is rejected.
+///
+/// `key_pw` unlocks the primary key, which makes all signatures. The result is verified before
+/// it is returned in ASCII armor, with the headers of the input.
+///
+/// ```no_run
+/// let armored = std::fs::read_to_string("key.sec.asc")?;
+/// let refreshed = pgp::maintain::extend_expiration(
+///     &armored,
+///     || "password".to_string(),
+///     chrono::Duration::days(90),
+/// )?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn extend_expiration<F>(
+    armored_secret_key: &str,
+    key_pw: F,
+    new_expiry: Duration,
+) -> Result<String>
+where
+    F: FnOnce() -> String + Clone,
+{
+    let (mut key, headers) =
+        SignedSecretKey::from_string(armored_secret_key).map_err(step("parse the key"))?;
+
+    if !key.details.revocation_signatures.is_empty() {
+        return Err(step("extend the expiration")(format_err!(
+            "key {:?} is revoked",
+            key.key_id()
+        )));
+    }
+    if new_expiry <= Duration::zero() {
+        return Err(step("extend the expiration")(format_err!(
+            "expiration {} is not in the future",
+            new_expiry
+        )));
+    }
+
+    let now = Utc::now().trunc_subsecs(0);
+    let expires_at = now + new_expiry;
+    let primary_id = key.key_id();
+    let primary = &key.primary_key;
+    let details = &mut key.details;
+
+    let mut resigned = 0;
+    for user in &mut details.users {
+        let Some(sig) = latest_self_signature(&user.signatures, |sig| {
+            sig.verify_certification(primary, Tag::UserId, &user.id)
+        }) else {
+            continue;
+        };
+        if sig.typ() == SignatureType::CertRevocation {
+            continue;
+        }
+
+        let sig = reissue(sig, now, *primary.created_at(), expires_at)
+            .and_then(|config| {
+                config.sign_certification(primary, key_pw.clone(), Tag::UserId, &user.id)
+            })
+            .map_err(step(format!("re-sign user id {}", user.id.id())))?;
+        user.signatures.push(sig);
+        resigned += 1;
+    }
+
+    if let Some(sig) =
+        latest_self_signature(&details.direct_signatures, |sig| sig.verify_key(primary))
+    {
+        let sig = reissue(sig, now, *primary.created_at(), expires_at)
+            .and_then(|config| config.sign_key(primary, key_pw.clone(), primary))
+            .map_err(step("re-sign the direct key signature"))?;
+        details.direct_signatures.push(sig);
+        resigned += 1;
+    }
+
+    if resigned == 0 {
+        return Err(step("extend the expiration")(format_err!(
+            "key {:?} has no self-signature",
+            primary_id
+        )));
+    }
+
+    for subkey in &mut key.secret_subkeys {
+        if is_revoked_subkey(&subkey.signatures) {
+            continue;
+        }
+        let Some(sig) = subkey.latest_binding_signature(primary) else {
+            continue;
+        };
+
+        let sig = reissue(sig, now, *subkey.key.created_at(), expires_at)
+            .and_then(|config| config.sign_key_binding(primary, key_pw.clone(), &subkey.key))
+            .map_err(step(format!("re-sign subkey {:?}", subkey.key_id())))?;
+        subkey.signatures.push(sig);
+    }
+
+    for subkey in &mut key.public_subkeys {
+        if is_revoked_subkey(&subkey.signatures) {
+            continue;
+        }
+        let Some(sig) = subkey.latest_binding_signature(primary) else {
+            continue;
+        };
+
+        let sig = reissue(sig, now, *subkey.key.created_at(), expires_at)
+            .and_then(|config| config.sign_key_binding(primary, key_pw.clone(), &subkey.key))
+            .map_err(step(format!("re-sign subkey {:?}", subkey.key_id())))?;
+        subkey.signatures.push(sig);
+    }
+
+    key.verify().map_err(step("validate the result"))?;
+
+    key.to_armored_string(ArmorOptions {
+        headers: Some(&headers),
+        ..Default::default()
+    })
+    .map_err(step("armor the result"))
+}
+
+/// Wraps the error of a failed step.
+fn step(step: impl Into<String>) -> impl FnOnce(Error) -> Error {
+    move |err| Error::Maintenance {
+        step: step.into(),
+        source: Box::new(err),
+    }
+}
+
+fn is_revoked_subkey(sigs: &[Signature]) -> bool {
+    sigs.iter()
+        .any(|sig| sig.typ() == SignatureType::SubkeyRevocation)
+}
+
+/// Returns the newest of the signatures, that `verify` accepts as made by the primary key.
+///
+/// The issuer subpackets are not enough, they can be forged and are not always hashed.
+fn latest_self_signature(
+    sigs: &[Signature],
+    verify: impl Fn(&Signature) -> Result<()>,
+) -> Option<&Signature> {
+    sigs.iter()
+        .filter(|sig| verify(sig).is_ok())
+        .max_by_key(|sig| sig.created())
+}
+
+/// Returns the config of `sig`, created `now` and with the key created at `key_created`
+/// expiring at `expires_at`.
+fn reissue(
+    sig: &Signature,
+    now: DateTime<Utc>,
+    key_created: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+) -> Result<SignatureConfig> {
+    ensure_eq!(
+        sig.config.version,
+        SignatureVersion::V4,
+        "only v4 signatures can be issued again"
+    );
+    ensure!(
+        expires_at > key_created,
+        "key created at {} can not expire at {}",
+        key_created,
+        expires_at
+    );
+
+    let mut config = sig.config.clone();
+    let subpackets = config.hashed_subpackets_mut();
+    subpackets.retain(|p| {
+        !matches!(
+            p.data,
+            SubpacketData::SignatureCreationTime(_) | SubpacketData::KeyExpirationTime(_)
+        )
+    });
+    subpackets.insert(
+        0,
+        Subpacket::regular(SubpacketData::SignatureCreationTime(now)),
+    );
+    subpackets.push(Subpacket::regular(SubpacketData::KeyExpirationTime(
+        expires_at - key_created,
+    )));
+
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::crypto::hash::HashAlgorithm;
+    use crate::{KeyType, SecretKeyParamsBuilder};
+
+    fn generate(uid: &str) -> SignedSecretKey {
+        SecretKeyParamsBuilder::default()
+            .key_type(KeyType::EdDSA)
+            .can_certify(true)
+            .primary_user_id(uid.into())
+            .build()
+            .unwrap()
+            .generate()
+            .unwrap()
+            .sign(String::new)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_latest_self_signature_ignores_forged_issuer() {
+        let key = generate("Me <me@mail.com>");
+        let mallory = generate("Mallory <mallory@mail.com>");
+        let user = &key.details.users[0];
+        let valid = &user.signatures[0];
+
+        // newer, and claiming to be issued by the primary key
+        let forged = SignatureConfig::new_v4(
+            SignatureVersion::V4,
+            SignatureType::CertPositive,
+            mallory.algorithm(),
+            HashAlgorithm::SHA2_256,
+            vec![Subpacket::regular(SubpacketData::SignatureCreationTime(
+                *valid.created().unwrap() + Duration::seconds(60),
+            ))],
+            vec![Subpacket::regular(SubpacketData::Issuer(key.key_id()))],
+        )
+        .sign_certification(&mallory, String::new, Tag::UserId, &user.id)
+        .unwrap();
+        let sigs = vec![valid.clone(), forged];
+
+        let latest = latest_self_signature(&sigs, |sig| {
+            sig.verify_certification(&key.primary_key, Tag::UserId, &user.id)
+        });
+        assert_eq!(latest, Some(valid));
+    }
+}
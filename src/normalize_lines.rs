@@ -6,6 +6,7 @@
 //! Based on https://github.com/derekdreery/normalize-line-endings.
 
 use crate::line_writer::LineBreak;
+use std::io;
 use std::iter::Peekable;
 
 /// This struct wraps an u8 iterator to normalize line endings.
@@ -118,6 +119,63 @@ impl<I: Iterator<Item = u8>> Iterator for Normalized<I> {
     }
 }
 
+/// Normalizes the line endings of the data written to it to CRLF, like [`Normalized`], and
+/// writes the result to the inner writer.
+///
+/// The data can be written in pieces, line breaks split over two writes are kept intact.
+pub(crate) struct CrlfWriter<W: io::Write> {
+    inner: W,
+    prev_was_cr: bool,
+}
+
+impl<W: io::Write> CrlfWriter<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        CrlfWriter {
+            inner,
+            prev_was_cr: false,
+        }
+    }
+
+    pub(crate) fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+}
+
+impl<W: io::Write> io::Write for CrlfWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut start = 0;
+        for (i, b) in buf.iter().enumerate() {
+            match b {
+                b'\r' => {
+                    self.inner.write_all(&buf[start..i])?;
+                    self.inner.write_all(b"\r\n")?;
+                    start = i + 1;
+                }
+                b'\n' => {
+                    self.inner.write_all(&buf[start..i])?;
+                    if !self.prev_was_cr {
+                        self.inner.write_all(b"\r\n")?;
+                    }
+                    start = i + 1;
+                }
+                _ => {}
+            }
+            self.prev_was_cr = *b == b'\r';
+        }
+        self.inner.write_all(&buf[start..])?;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
 // tests
 #[cfg(test)]
 mod tests {
@@ -150,4 +208,20 @@ mod tests {
             "This is a string \r\n with \r\n some \r\n\r\n random newlines\r\n\r\n\r\n"
         );
     }
+
+    #[test]
+    fn crlf_writer_split_writes() {
+        use std::io::Write;
+
+        let input = "This is a string \n with \r some \n\r\n random newlines\r\r\n\n";
+        let expected: Vec<u8> = Normalized::new(input.bytes(), LineBreak::Crlf).collect();
+
+        for piece_len in 1..input.len() {
+            let mut writer = CrlfWriter::new(Vec::new());
+            for piece in input.as_bytes().chunks(piece_len) {
+                writer.write_all(piece).unwrap();
+            }
+            assert_eq!(writer.get_ref(), &expected, "{piece_len}");
+        }
+    }
 }
@@ -1,6 +1,7 @@
 use std::fmt;
 use std::io::{self, Read};
 
+#[cfg(feature = "compression-deflate")]
 use flate2::read::{DeflateDecoder, ZlibDecoder};
 
 use crate::errors::Result;
@@ -17,16 +18,54 @@ pub struct CompressedData {
 
 pub enum Decompressor<R> {
     Uncompressed(R),
+    #[cfg(feature = "compression-deflate")]
     Zip(DeflateDecoder<R>),
+    #[cfg(feature = "compression-deflate")]
     Zlib(ZlibDecoder<R>),
     Bzip2,
 }
 
-impl<'a> Read for Decompressor<&'a [u8]> {
+impl<R: Read> Decompressor<R> {
+    /// Creates a decompressor for data compressed with `alg`, that is read from `reader`.
+    pub fn new(alg: CompressionAlgorithm, reader: R) -> Result<Self> {
+        match alg {
+            CompressionAlgorithm::Uncompressed => Ok(Decompressor::Uncompressed(reader)),
+            #[cfg(feature = "compression-deflate")]
+            CompressionAlgorithm::ZIP => Ok(Decompressor::Zip(DeflateDecoder::new(reader))),
+            #[cfg(feature = "compression-deflate")]
+            CompressionAlgorithm::ZLIB => Ok(Decompressor::Zlib(ZlibDecoder::new(reader))),
+            #[cfg(not(feature = "compression-deflate"))]
+            CompressionAlgorithm::ZIP | CompressionAlgorithm::ZLIB => unsupported_err!(
+                "CompressionAlgorithm {} (feature \"compression-deflate\" is disabled)",
+                u8::from(alg)
+            ),
+            CompressionAlgorithm::BZip2 => unimplemented_err!("BZip2"),
+            CompressionAlgorithm::Private10 | CompressionAlgorithm::Other(_) => {
+                unsupported_err!("CompressionAlgorithm {} is unsupported", u8::from(alg))
+            }
+        }
+    }
+
+    /// Returns the reader of the compressed data, discarding any data it buffered.
+    pub(crate) fn into_inner(self) -> Option<R> {
+        match self {
+            Decompressor::Uncompressed(c) => Some(c),
+            #[cfg(feature = "compression-deflate")]
+            Decompressor::Zip(c) => Some(c.into_inner()),
+            #[cfg(feature = "compression-deflate")]
+            Decompressor::Zlib(c) => Some(c.into_inner()),
+            Decompressor::Bzip2 => None,
+        }
+    }
+}
+
+impl<R: Read> Read for Decompressor<R> {
     fn read(&mut self, into: &mut [u8]) -> io::Result<usize> {
         match self {
             Decompressor::Uncompressed(ref mut c) => c.read(into),
+            #[cfg(feature = "compression-deflate")]
             Decompressor::Zip(ref mut c) => c.read(into),
+            #[cfg(feature = "compression-deflate")]
             Decompressor::Zlib(ref mut c) => c.read(into),
             Decompressor::Bzip2 => unimplemented!("bzip2"),
         }
@@ -55,22 +94,11 @@ impl CompressedData {
     }
 
     pub fn decompress(&self) -> Result<Decompressor<&[u8]>> {
-        match self.compression_algorithm {
-            CompressionAlgorithm::Uncompressed => {
-                Ok(Decompressor::Uncompressed(&self.compressed_data[..]))
-            }
-            CompressionAlgorithm::ZIP => Ok(Decompressor::Zip(DeflateDecoder::new(
-                &self.compressed_data[..],
-            ))),
-            CompressionAlgorithm::ZLIB => Ok(Decompressor::Zlib(ZlibDecoder::new(
-                &self.compressed_data[..],
-            ))),
-            CompressionAlgorithm::BZip2 => unimplemented_err!("BZip2"),
-            CompressionAlgorithm::Private10 | CompressionAlgorithm::Other(_) => unsupported_err!(
-                "CompressionAlgorithm {} is unsupported",
-                u8::from(self.compression_algorithm)
-            ),
-        }
+        Decompressor::new(self.compression_algorithm, &self.compressed_data[..])
+    }
+
+    pub fn compression_algorithm(&self) -> CompressionAlgorithm {
+        self.compression_algorithm
     }
 
     pub fn compressed_data(&self) -> &[u8] {
@@ -87,6 +115,8 @@ impl Serialize for CompressedData {
     }
 }
 
+impl crate::private::Sealed for CompressedData {}
+
 impl PacketTrait for CompressedData {
     fn packet_version(&self) -> Version {
         self.packet_version
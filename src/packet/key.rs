@@ -1,5 +1,331 @@
+use crate::types::{KeyId, KeyVersion};
+
 impl_public_key!(PublicKey, crate::types::Tag::PublicKey);
 impl_public_key!(PublicSubkey, crate::types::Tag::PublicSubkey);
 
 impl_secret_key!(SecretKey, crate::types::Tag::SecretKey, PublicKey);
 impl_secret_key!(SecretSubkey, crate::types::Tag::SecretSubkey, PublicSubkey);
+
+/// The version of a key packet, limited to the versions whose fingerprint and key id are
+/// defined.
+///
+/// V2 and V3 keys carry their key id, the low 64 bits of the RSA modulus, which is taken when
+/// the key is created.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(crate) enum KnownKeyVersion {
+    V2(KeyId),
+    V3(KeyId),
+    V4,
+    V5,
+}
+
+impl From<&KnownKeyVersion> for KeyVersion {
+    fn from(version: &KnownKeyVersion) -> Self {
+        match version {
+            KnownKeyVersion::V2(_) => KeyVersion::V2,
+            KnownKeyVersion::V3(_) => KeyVersion::V3,
+            KnownKeyVersion::V4 => KeyVersion::V4,
+            KnownKeyVersion::V5 => KeyVersion::V5,
+        }
+    }
+}
+
+impl PublicSubkey {
+    /// Returns the same key material as a primary key packet.
+    ///
+    /// Useful for code that only deals with primary keys, e.g. a hardware token holding
+    /// the encryption subkey. The result is not a valid certificate on its own.
+    pub fn rebind_as_primary(&self) -> PublicKey {
+        PublicKey {
+            packet_version: self.packet_version,
+            version: self.version.clone(),
+            algorithm: self.algorithm,
+            created_at: self.created_at,
+            expiration: self.expiration,
+            public_params: self.public_params.clone(),
+            #[cfg(feature = "rsa")]
+            rsa_verifier: self.rsa_verifier.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::crypto::public_key::PublicKeyAlgorithm;
+    use crate::errors::Error;
+    use crate::ser::Serialize;
+    use crate::types::{KeyTrait, KeyVersion, Mpi, PublicParams, Version};
+
+    #[test]
+    fn test_fingerprint_and_key_id_byte_order() {
+        let body = [
+            0x04, // version
+            0x65, 0x43, 0x21, 0x0f, // creation time
+            0x01, // RSA
+            0x00, 0x80, 0xc1, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b,
+            0x0c, 0x0d, 0x0e, 0x0f, // n
+            0x00, 0x11, 0x01, 0x00, 0x01, // e
+        ];
+
+        let key = PublicKey::from_slice(Version::New, &body).unwrap();
+        assert_eq!(key.created_at().timestamp(), 0x6543_210f);
+        assert_eq!(key.to_bytes().unwrap(), body);
+
+        // SHA1 over 0x99, the two octet big endian length and the body
+        assert_eq!(
+            hex::encode(key.fingerprint()),
+            "c34878ddf04383ac159783f54906d5ff9f8192ba"
+        );
+        assert_eq!(hex::encode(key.key_id()), "4906d5ff9f8192ba");
+
+        // the key id of v3 keys are the low 64 bits of the modulus
+        let key = PublicKey::new(
+            Version::Old,
+            KeyVersion::V3,
+            PublicKeyAlgorithm::RSA,
+            *key.created_at(),
+            None,
+            key.public_params().clone(),
+        )
+        .unwrap();
+        assert_eq!(hex::encode(key.key_id()), "08090a0b0c0d0e0f");
+    }
+
+    #[test]
+    fn test_unknown_key_version() {
+        let body = [
+            0x07, // version
+            0x65, 0x00, 0x00, 0x00, // creation time
+            0x16, // EdDSA
+            0x01, 0x02, 0x03, 0x04, 0x05, // unknown layout
+        ];
+
+        // no defined fingerprint or key id, so the key is rejected instead of guessing one
+        assert!(PublicKey::from_slice(Version::New, &body).is_err());
+        assert!(matches!(
+            PublicKey::new(
+                Version::New,
+                KeyVersion::Other(7),
+                PublicKeyAlgorithm::EdDSA,
+                chrono::Utc::now(),
+                None,
+                PublicParams::Unknown {
+                    data: vec![1, 2, 3, 4, 5]
+                },
+            ),
+            Err(Error::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_unknown_algorithm_secret_key() {
+        let body = [
+            0x04, // version
+            0x65, 0x00, 0x00, 0x00, // creation time
+            0x69, // private use algorithm 105
+            0x01, 0x02, 0x03, // public key material
+            0x00, // unprotected
+            0x04, 0x05, 0x06, // secret key material
+        ];
+
+        // the public key material is kept as is
+        let key = PublicKey::from_slice(Version::New, &body[..9]).unwrap();
+        assert_eq!(
+            key.public_params(),
+            &PublicParams::Unknown {
+                data: vec![1, 2, 3]
+            }
+        );
+
+        // where the public part ends is unknown, the secret part must not end up in it
+        assert!(matches!(
+            SecretKey::from_slice(Version::New, &body),
+            Err(Error::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_old_key_version_without_expiration() {
+        let key = PublicKey::new(
+            Version::Old,
+            KeyVersion::V3,
+            PublicKeyAlgorithm::RSA,
+            chrono::Utc::now(),
+            None,
+            PublicParams::RSA {
+                n: Mpi::from_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 9]),
+                e: Mpi::from_slice(&[3]),
+            },
+        )
+        .unwrap();
+
+        assert!(key.to_bytes().is_err());
+        assert_eq!(key.key_id().as_ref(), &[2, 3, 4, 5, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn test_old_key_version_short_modulus() {
+        let short = PublicParams::RSA {
+            n: Mpi::from_slice(&[1, 2, 3, 4, 5, 6, 7]),
+            e: Mpi::from_slice(&[3]),
+        };
+        let created_at = chrono::Utc::now();
+        assert!(PublicKey::new(
+            Version::Old,
+            KeyVersion::V3,
+            PublicKeyAlgorithm::RSA,
+            created_at,
+            Some(0),
+            short.clone(),
+        )
+        .is_err());
+
+        // v4 keys do not derive their key id from the modulus
+        let key = PublicKey::new(
+            Version::Old,
+            KeyVersion::V4,
+            PublicKeyAlgorithm::RSA,
+            created_at,
+            None,
+            short,
+        )
+        .unwrap();
+
+        // the same (corrupt) key material in a v3 packet fails to parse
+        let mut body = key.to_bytes().unwrap();
+        body[0] = 3;
+        body.splice(5..5, [0, 0]);
+        assert!(PublicKey::from_slice(Version::Old, &body).is_err());
+    }
+
+    #[test]
+    fn test_v5_key_roundtrip() {
+        use crate::composed::{Deserializable, SignedPublicKey};
+
+        let (key, _) = SignedPublicKey::from_armor_single(
+            std::fs::File::open("./tests/autocrypt/alice@autocrypt.example.pub.asc").unwrap(),
+        )
+        .unwrap();
+        let primary = &key.primary_key;
+        let key = PublicKey::new(
+            Version::New,
+            KeyVersion::V5,
+            primary.algorithm(),
+            *primary.created_at(),
+            None,
+            primary.public_params().clone(),
+        )
+        .unwrap();
+
+        let body = key.to_bytes().unwrap();
+        let v4 = primary.to_bytes().unwrap();
+        // the same as v4, with the version and the length of the key material
+        assert_eq!(body[0], 5);
+        assert_eq!(body[1..6], v4[1..6]);
+        assert_eq!(body[6..10], ((v4.len() - 6) as u32).to_be_bytes());
+        assert_eq!(body[10..], v4[6..]);
+
+        assert_eq!(PublicKey::from_slice(Version::New, &body).unwrap(), key);
+
+        // the key material must fill the declared length
+        let mut longer = body.clone();
+        longer[9] += 1;
+        assert!(PublicKey::from_slice(Version::New, &longer).is_err());
+        longer.push(0);
+        assert!(PublicKey::from_slice(Version::New, &longer).is_err());
+    }
+
+    #[test]
+    fn test_v5_fingerprint_and_key_id() {
+        let body = [
+            0x05, // version
+            0x5c, 0x91, 0xf4, 0xe4, // creation time
+            0x16, // EdDSA
+            0x00, 0x00, 0x00, 0x2d, // length of the key material
+            0x09, 0x2b, 0x06, 0x01, 0x04, 0x01, 0xda, 0x47, 0x0f, 0x01, // Ed25519
+            0x01, 0x07, 0x40, 0xbb, 0x37, 0x73, 0x6c, 0x35, 0x5f, 0xb7, 0x50, 0xf6, 0x4d, 0x00,
+            0x5c, 0x79, 0xaa, 0xa2, 0x20, 0xf7, 0x93, 0xa0, 0x70, 0xc7, 0x9c, 0x14, 0x26, 0xc8,
+            0xe6, 0x1e, 0x1e, 0x90, 0xf1, 0xa2, 0xba, // q
+        ];
+
+        let key = PublicKey::from_slice(Version::Old, &body).unwrap();
+        assert_eq!(key.version(), KeyVersion::V5);
+        assert_eq!(key.to_bytes().unwrap(), body);
+
+        // SHA256 over 0x9A, the four octet big endian length and the body
+        assert_eq!(
+            hex::encode(key.fingerprint()),
+            "db8d6d99231292fb8eb53319b6253138bbaaca686ff652f19f2bb5bf7b4b5b15"
+        );
+        // the high 64 bits
+        assert_eq!(hex::encode(key.key_id()), "db8d6d99231292fb");
+        assert_eq!(key.key_version(), KeyVersion::V5);
+        assert_eq!(
+            crate::types::KeyId::from_fingerprint(KeyVersion::V5, &key.fingerprint()),
+            Some(key.key_id())
+        );
+    }
+
+    #[test]
+    fn test_rebind_as_primary() {
+        use crate::composed::{Deserializable, SignedPublicKey};
+        use crate::packet::PacketTrait;
+
+        let (key, _) = SignedPublicKey::from_armor_single(
+            std::fs::File::open("./tests/autocrypt/alice@autocrypt.example.pub.asc").unwrap(),
+        )
+        .unwrap();
+        let subkey = &key.public_subkeys[0].key;
+        let primary = subkey.rebind_as_primary();
+
+        assert_eq!(primary.tag(), crate::types::Tag::PublicKey);
+        assert_eq!(primary.fingerprint(), subkey.fingerprint());
+        assert_eq!(primary.to_bytes().unwrap(), subkey.to_bytes().unwrap());
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn v5_key_roundtrip(
+            created_at in 0u32..u32::MAX,
+            n in proptest::collection::vec(1u8.., 8..64),
+            e in proptest::collection::vec(1u8.., 1..4),
+            unknown in proptest::collection::vec(proptest::num::u8::ANY, 0..64),
+            rsa: bool,
+        ) {
+            use chrono::TimeZone;
+
+            let (algorithm, public_params) = if rsa {
+                (
+                    PublicKeyAlgorithm::RSA,
+                    PublicParams::RSA {
+                        n: Mpi::from_slice(&n),
+                        e: Mpi::from_slice(&e),
+                    },
+                )
+            } else {
+                (
+                    PublicKeyAlgorithm::Unknown(99),
+                    PublicParams::Unknown { data: unknown },
+                )
+            };
+            let key = PublicKey::new(
+                Version::New,
+                KeyVersion::V5,
+                algorithm,
+                chrono::Utc.timestamp_opt(i64::from(created_at), 0).unwrap(),
+                None,
+                public_params,
+            )
+            .unwrap();
+
+            let body = key.to_bytes().unwrap();
+            let params_len = key.public_params().to_bytes().unwrap().len();
+            proptest::prop_assert_eq!(body.len(), 10 + params_len);
+            proptest::prop_assert_eq!(&body[6..10], &(params_len as u32).to_be_bytes()[..]);
+            proptest::prop_assert_eq!(PublicKey::from_slice(Version::New, &body).unwrap(), key);
+        }
+    }
+}
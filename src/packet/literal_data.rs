@@ -31,12 +31,17 @@ pub struct LiteralData {
     data: Vec<u8>,
 }
 
+/// Format of the data contained in a literal data packet.
+/// https://tools.ietf.org/html/rfc4880.html#section-5.9
 #[derive(Debug, Copy, Clone, FromPrimitive, IntoPrimitive, PartialEq, Eq)]
 #[repr(u8)]
 pub enum DataMode {
     Binary = b'b',
     Text = b't',
     Utf8 = b'u',
+    /// Local mode, deprecated. The data is treated as binary.
+    Local = b'l',
+    /// MIME encoded data, see RFC 4880bis.
     Mime = b'm',
 
     #[num_enum(catch_all)]
@@ -79,10 +84,45 @@ impl LiteralData {
         matches!(self.mode, DataMode::Binary)
     }
 
+    /// Returns true if the data is marked as text, and should be canonicalized
+    /// when signing.
+    ///
+    /// `Mime` and `Local` data is passed through as is.
+    pub fn is_text(&self) -> bool {
+        matches!(self.mode, DataMode::Text | DataMode::Utf8)
+    }
+
+    pub fn mode(&self) -> DataMode {
+        self.mode
+    }
+
     pub fn data(&self) -> &[u8] {
         &self.data
     }
 
+    /// The name of the file the data was read from, may contain non utf-8 bytes.
+    pub fn file_name(&self) -> &BStr {
+        self.file_name.as_ref()
+    }
+
+    /// The modification time of the file, or the time the packet was created.
+    pub fn created(&self) -> &DateTime<Utc> {
+        &self.created
+    }
+
+    pub(crate) fn set_file_name(&mut self, file_name: BString) {
+        self.file_name = file_name;
+    }
+
+    pub(crate) fn set_created(&mut self, created: DateTime<Utc>) {
+        self.created = created.trunc_subsecs(0);
+    }
+
+    /// Replaces the data, which is stored as is.
+    pub(crate) fn set_data(&mut self, data: Vec<u8>) {
+        self.data = data;
+    }
+
     /// Convert the data to a UTF-8 string, if appropriate for the type.
     /// Returns `None` if `mode` is `Binary`, or the data is not valid UTF-8.
     pub fn to_string(&self) -> Option<String> {
@@ -128,6 +168,8 @@ fn parse(packet_version: Version) -> impl Fn(&[u8]) -> IResult<&[u8], LiteralDat
     }
 }
 
+impl crate::private::Sealed for LiteralData {}
+
 impl PacketTrait for LiteralData {
     fn packet_version(&self) -> Version {
         self.packet_version
@@ -158,3 +200,24 @@ fn test_utf8_literal() {
     let literal = LiteralData::from_str("", slogan);
     assert!(String::from_utf8(literal.data).unwrap() == slogan);
 }
+
+#[test]
+fn test_mime_local_literal_roundtrip() {
+    #![allow(clippy::unwrap_used)]
+
+    for (raw_mode, mode) in [(b'm', DataMode::Mime), (b'l', DataMode::Local)] {
+        let mut packet = vec![raw_mode, 4];
+        packet.extend_from_slice(b"test");
+        packet.extend_from_slice(&[0x5f, 0x00, 0x00, 0x00]);
+        packet.extend_from_slice(b"hello\nworld\n");
+
+        let literal = LiteralData::from_slice(Version::New, &packet).unwrap();
+        assert_eq!(literal.mode(), mode);
+        assert!(!literal.is_binary());
+        assert!(!literal.is_text());
+        // no line ending normalization happens
+        assert_eq!(literal.data(), b"hello\nworld\n");
+
+        assert_eq!(literal.to_bytes().unwrap(), packet);
+    }
+}
@@ -6,7 +6,7 @@ use buffer_redux::BufReader;
 use crate::errors::{Error, Result};
 use crate::packet::packet_sum::Packet;
 use crate::packet::single;
-use crate::types::{PacketLength, Tag};
+use crate::types::{PacketLength, Tag, MIN_FIRST_PARTIAL_LENGTH};
 
 const MAX_CAPACITY: usize = 1024 * 1024 * 1024;
 
@@ -17,13 +17,125 @@ pub struct PacketParser<R> {
     reader: BufReader<R, MinBuffered>,
     /// Remember if we are done.
     done: bool,
+    /// The largest packet body that is read, in bytes.
+    max_packet_size: usize,
+    /// Smaller limits for the bodies of some packet types, in bytes.
+    tag_limits: Vec<(Tag, usize)>,
 }
 
 impl<R: Read> PacketParser<R> {
+    /// Creates a parser that accepts packets of up to 1 GiB.
     pub fn new(inner: R) -> Self {
+        Self::with_max_packet_size(inner, MAX_CAPACITY)
+    }
+
+    /// Creates a parser that stops with an error at the first packet, whose body is larger
+    /// than `max_packet_size` bytes.
+    ///
+    /// Bodies are read as they arrive, so the declared length of a packet is not allocated
+    /// upfront, but a long input still needs memory for the packets it holds.
+    pub fn with_max_packet_size(inner: R, max_packet_size: usize) -> Self {
         PacketParser {
             reader: BufReader::with_capacity(DEFAULT_CAPACITY, inner).set_policy(READER_POLICY),
             done: false,
+            max_packet_size,
+            tag_limits: Vec::new(),
+        }
+    }
+
+    /// Skips packets of type `tag` with a body larger than `max` bytes, returning
+    /// [`Error::PacketTooLarge`] in their place, and continues with the next packet.
+    ///
+    /// The body of a skipped packet is discarded as it is read, it is never held in memory.
+    /// Packets with partial body lengths can not be skipped, for them the error ends parsing.
+    pub fn with_tag_limit(mut self, tag: Tag, max: usize) -> Self {
+        self.tag_limits.push((tag, max));
+        self
+    }
+
+    /// Like [`Self::check_size`], also checking the limit for `tag`, as partial bodies can
+    /// not be skipped.
+    fn check_partial_size(&mut self, tag: Tag, len: usize) -> Result<()> {
+        self.check_size(len)?;
+        if let Some(max) = self.tag_limit(tag).filter(|max| len > *max) {
+            self.done = true;
+            return Err(Error::PacketTooLarge {
+                tag,
+                size: len,
+                max,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn tag_limit(&self, tag: Tag) -> Option<usize> {
+        self.tag_limits
+            .iter()
+            .find(|(t, _)| *t == tag)
+            .map(|(_, max)| *max)
+    }
+
+    /// Reads and discards the rest of a packet body that is larger than the limit for its type.
+    ///
+    /// `len` is the number of bytes left to skip, `None` for a body that runs until the end of
+    /// the input, of which `read` bytes were already read.
+    fn skip_body(&mut self, tag: Tag, read: usize, len: Option<usize>) -> Error {
+        let skipped = match len {
+            Some(len) => std::io::copy(
+                &mut (&mut self.reader).take(len as u64),
+                &mut std::io::sink(),
+            ),
+            None => std::io::copy(&mut self.reader, &mut std::io::sink()),
+        };
+        let skipped = match skipped {
+            Ok(skipped) => skipped as usize,
+            Err(err) => {
+                self.done = true;
+                return err.into();
+            }
+        };
+        if len.is_some_and(|len| skipped < len) {
+            self.done = true;
+            return std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into();
+        }
+
+        Error::PacketTooLarge {
+            tag,
+            size: read + skipped,
+            max: self.tag_limit(tag).unwrap_or_default(),
+        }
+    }
+
+    /// Stops parsing with an error, if a body of `len` bytes is too large.
+    fn check_size(&mut self, len: usize) -> Result<()> {
+        if len > self.max_packet_size {
+            self.done = true;
+            bail!(
+                "packet too large: {} bytes, limit is {}",
+                len,
+                self.max_packet_size
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Returns the tag of the next packet, reading only its header.
+    pub fn peek_tag(&mut self) -> Result<Option<Tag>> {
+        if self.done {
+            return Ok(None);
+        }
+
+        let buf = self.reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(None);
+        }
+
+        match single::parser(buf) {
+            Ok((_, (_, tag, _))) => Ok(Some(tag)),
+            Err(nom::Err::Incomplete(_)) => Err(Error::PacketIncomplete),
+            Err(err) => Err(err.into()),
         }
     }
 }
@@ -85,9 +197,11 @@ impl<R: Read> Iterator for PacketParser<R> {
                         }
                         Ok(r) => {
                             body.extend_from_slice(&buf[..r]);
-                            if body.len() >= MAX_CAPACITY {
-                                self.done = true;
-                                return Some(Err(format_err!("Indeterminate packet too large")));
+                            if let Err(err) = self.check_size(body.len()) {
+                                return Some(Err(err));
+                            }
+                            if self.tag_limit(tag).is_some_and(|max| body.len() > max) {
+                                return Some(Err(self.skip_body(tag, body.len(), None)));
                             }
                         }
                         Err(err) => {
@@ -106,11 +220,25 @@ impl<R: Read> Iterator for PacketParser<R> {
                 }
             }
             PacketLength::Fixed(len) => {
+                if let Err(err) = self.check_size(len) {
+                    return Some(Err(err));
+                }
+                if self.tag_limit(tag).is_some_and(|max| len > max) {
+                    return Some(Err(self.skip_body(tag, 0, Some(len))));
+                }
+
                 let res = if len <= self.reader.policy().0 {
                     // small enough to reuse our internal buffer
                     self.reader.make_room();
                     let body = match self.reader.fill_buf() {
-                        Ok(body) => body,
+                        Ok(body) if body.len() >= len => body,
+                        Ok(_) => {
+                            self.done = true;
+                            return Some(Err(std::io::Error::from(
+                                std::io::ErrorKind::UnexpectedEof,
+                            )
+                            .into()));
+                        }
                         Err(err) => {
                             self.done = true;
                             return Some(Err(err.into()));
@@ -120,10 +248,10 @@ impl<R: Read> Iterator for PacketParser<R> {
                     self.reader.consume(len);
                     res
                 } else {
-                    let mut buffer = vec![0u8; len];
-                    if let Err(err) = self.reader.read_exact(&mut buffer) {
+                    let mut buffer = Vec::new();
+                    if let Err(err) = read_fixed(&mut self.reader, len, &mut buffer) {
                         self.done = true;
-                        return Some(Err(err.into()));
+                        return Some(Err(err));
                     };
                     single::body_parser(version, tag, &buffer)
                 };
@@ -142,13 +270,7 @@ impl<R: Read> Iterator for PacketParser<R> {
                 // "An implementation MAY use Partial Body Lengths for data packets, be
                 // they literal, compressed, or encrypted [...]
                 // Partial Body Lengths MUST NOT be used for any other packet types"
-                if !matches!(
-                    tag,
-                    Tag::LiteralData
-                        | Tag::CompressedData
-                        | Tag::SymEncryptedData
-                        | Tag::SymEncryptedProtectedData
-                ) {
+                if !tag.allows_partial_length() {
                     self.done = true;
                     return Some(Err(format_err!(
                         "Partial body length is not allowed for packet type {:?}",
@@ -158,18 +280,22 @@ impl<R: Read> Iterator for PacketParser<R> {
 
                 // https://datatracker.ietf.org/doc/html/rfc4880#section-4.2.2.4
                 // "The first partial length MUST be at least 512 octets long."
-                if len < 512 {
+                if len < MIN_FIRST_PARTIAL_LENGTH {
                     self.done = true;
                     return Some(Err(format_err!(
-                        "Illegal first partial body length {} (shorter than 512 bytes)",
+                        "Illegal first partial body length {} (shorter than {} bytes)",
                         len,
+                        MIN_FIRST_PARTIAL_LENGTH,
                     )));
                 }
 
-                let mut body = vec![0u8; len];
-                if let Err(err) = self.reader.read_exact(&mut body) {
+                if let Err(err) = self.check_partial_size(tag, len) {
+                    return Some(Err(err));
+                }
+                let mut body = Vec::new();
+                if let Err(err) = read_fixed(&mut self.reader, len, &mut body) {
                     self.done = true;
-                    return Some(Err(err.into()));
+                    return Some(Err(err));
                 };
 
                 // Read n partials + 1 final fixed
@@ -187,6 +313,11 @@ impl<R: Read> Iterator for PacketParser<R> {
                             let read = buf.len() - rest.len();
                             self.reader.consume(read);
 
+                            if let Err(err) =
+                                self.check_partial_size(tag, body.len().saturating_add(len))
+                            {
+                                return Some(Err(err));
+                            }
                             if let Err(err) = read_fixed(&mut self.reader, len, &mut body) {
                                 self.done = true;
                                 return Some(Err(err));
@@ -196,6 +327,11 @@ impl<R: Read> Iterator for PacketParser<R> {
                             let read = buf.len() - rest.len();
                             self.reader.consume(read);
 
+                            if let Err(err) =
+                                self.check_partial_size(tag, body.len().saturating_add(len))
+                            {
+                                return Some(Err(err));
+                            }
                             if let Err(err) = read_fixed(&mut self.reader, len, &mut body) {
                                 self.done = true;
                                 return Some(Err(err));
@@ -229,14 +365,17 @@ impl<R: Read> Iterator for PacketParser<R> {
     }
 }
 
+/// Appends the next `len` bytes to `out`, growing it only as the data arrives, so a bogus
+/// length in a short input does not allocate its full size.
 fn read_fixed<R: Read>(
     reader: &mut BufReader<R, MinBuffered>,
     len: usize,
     out: &mut Vec<u8>,
 ) -> Result<()> {
-    let out_len = out.len();
-    out.resize(out_len + len, 0u8);
-    reader.read_exact(&mut out[out_len..])?;
+    let read = reader.take(len as u64).read_to_end(out)?;
+    if read < len {
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+    }
 
     Ok(())
 }
@@ -252,6 +391,7 @@ mod tests {
     use std::path::Path;
 
     use crate::ser::Serialize;
+    use crate::types::Tag;
 
     #[test]
     #[ignore]
@@ -434,4 +574,115 @@ mod tests {
         );
         assert!(res.is_err());
     }
+
+    #[test]
+    fn test_max_packet_size() {
+        // new format literal data packets, with five octet lengths
+        let small = [&[0xCB, 0xFF, 0, 0, 0, 6, b'b', 0, 0, 0, 0, 0][..]].concat();
+        let mut large = vec![0xCB, 0xFF, 0, 0, 0x10, 0x06, b'b', 0, 0, 0, 0, 0];
+        large.resize(large.len() + 0x1000, b'x');
+
+        let input = [&small[..], &large[..], &small[..]].concat();
+        let mut parser = PacketParser::with_max_packet_size(&input[..], 0x1000);
+        assert_eq!(parser.next().unwrap().unwrap().tag(), Tag::LiteralData);
+        let err = parser.next().unwrap().unwrap_err();
+        assert!(err.to_string().contains("packet too large"), "{err}");
+        // parsing stops at the oversized packet
+        assert!(parser.next().is_none());
+
+        // partial lengths are summed up: 2048 + 2048 + 6 bytes
+        let mut partial = vec![0xCB, 0xEB];
+        partial.resize(partial.len() + 2048, b'x');
+        partial.push(0xEB);
+        partial.resize(partial.len() + 2048, b'x');
+        partial.extend_from_slice(&[6, b'b', 0, 0, 0, 0, 0]);
+        let mut parser = PacketParser::with_max_packet_size(&partial[..], 0x1000);
+        assert!(parser.next().unwrap().is_err());
+        assert!(parser.next().is_none());
+
+        let mut parser = PacketParser::with_max_packet_size(&partial[..], 0x1006);
+        assert_eq!(parser.next().unwrap().unwrap().tag(), Tag::LiteralData);
+        assert!(parser.next().is_none());
+    }
+
+    #[test]
+    fn test_truncated_large_packet() {
+        // declares a body of nearly 4 GiB, which must not be allocated upfront
+        let input = [
+            0xCB, 0xFF, 0xFF, 0xFF, 0xFF, 0xF0, b'b', 0, 0, 0, 0, 0, b'x',
+        ];
+        let mut parser = PacketParser::new(&input[..]);
+        let err = parser.next().unwrap().unwrap_err();
+        assert!(err.to_string().contains("packet too large"), "{err}");
+
+        let input = [
+            0xCB, 0xFF, 0x00, 0x10, 0x00, 0x00, b'b', 0, 0, 0, 0, 0, b'x',
+        ];
+        let mut parser = PacketParser::new(&input[..]);
+        assert!(parser.next().unwrap().is_err());
+        assert!(parser.next().is_none());
+    }
+
+    #[test]
+    fn test_tag_limit() {
+        let small = [0xCD, 5, b'a', b'l', b'i', b'c', b'e'];
+        // a user id with a two octet length of 1000 bytes
+        let mut large = vec![0xCD, 0xC3, 0x28];
+        large.resize(large.len() + 1000, b'x');
+
+        let input = [&small[..], &large[..], &small[..]].concat();
+        let mut parser = PacketParser::new(&input[..]).with_tag_limit(Tag::UserId, 100);
+        assert_eq!(parser.next().unwrap().unwrap().tag(), Tag::UserId);
+        let err = parser.next().unwrap().unwrap_err();
+        assert!(
+            matches!(
+                err,
+                Error::PacketTooLarge {
+                    tag: Tag::UserId,
+                    size: 1000,
+                    max: 100
+                }
+            ),
+            "{err:?}"
+        );
+        // the oversized body was skipped
+        assert_eq!(parser.next().unwrap().unwrap().tag(), Tag::UserId);
+        assert!(parser.next().is_none());
+
+        // other packet types are not affected
+        let parser = PacketParser::new(&input[..]).with_tag_limit(Tag::UserAttribute, 100);
+        assert_eq!(parser.filter(|p| p.is_ok()).count(), 3);
+
+        // truncated bodies are still reported
+        let mut parser =
+            PacketParser::new(&large[..large.len() - 1]).with_tag_limit(Tag::UserId, 100);
+        let err = parser.next().unwrap().unwrap_err();
+        assert!(!matches!(err, Error::PacketTooLarge { .. }), "{err:?}");
+        assert!(parser.next().is_none());
+    }
+
+    #[test]
+    fn test_lossless_eq() {
+        // the same literal data packet, with an old format header, a new format header and a
+        // non canonical five octet length
+        let body = [b'b', 0, 0, 0, 0, 0, b'x'];
+        let inputs = [
+            [&[0xAC, 7][..], &body[..]].concat(),
+            [&[0xCB, 7][..], &body[..]].concat(),
+            [&[0xCB, 0xFF, 0, 0, 0, 7][..], &body[..]].concat(),
+        ];
+        let packets: Vec<_> = inputs
+            .iter()
+            .map(|input| PacketParser::new(&input[..]).next().unwrap().unwrap())
+            .collect();
+        for a in &packets {
+            for b in &packets {
+                assert!(a.lossless_eq(b), "{a:?} {b:?}");
+            }
+        }
+
+        let other = [&[0xCB, 7][..], &body[..6], b"y"].concat();
+        let other = PacketParser::new(&other[..]).next().unwrap().unwrap();
+        assert!(!packets[0].lossless_eq(&other));
+    }
 }
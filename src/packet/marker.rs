@@ -35,6 +35,8 @@ impl Serialize for Marker {
     }
 }
 
+impl crate::private::Sealed for Marker {}
+
 impl PacketTrait for Marker {
     fn packet_version(&self) -> Version {
         self.packet_version
@@ -19,7 +19,7 @@
 //! #
 //! # let mut key_params = SecretKeyParamsBuilder::default();
 //! # key_params
-//! #     .key_type(KeyType::Rsa(2048))
+//! #     .key_type(KeyType::EdDSA)
 //! #     .can_certify(false)
 //! #     .can_sign(true)
 //! #     .primary_user_id("Me <me@example.com>".into())
@@ -52,7 +52,7 @@
 //! let sig_cfg = sig_cfg_bldr
 //!      .version(packet::SignatureVersion::V4)
 //!      .typ(packet::SignatureType::Binary)
-//!      .pub_alg(PublicKeyAlgorithm::RSA)
+//!      .pub_alg(PublicKeyAlgorithm::EdDSA)
 //!      .hash_alg(HashAlgorithm::SHA2_256)
 //!      .issuer(Some(signing_key.key_id()))
 //!      .created(Some(now))
@@ -92,7 +92,9 @@ mod literal_data;
 mod marker;
 mod mod_detection_code;
 mod one_pass_signature;
+mod packet_body_reader;
 mod padding;
+mod partial_body_writer;
 mod public_key_encrypted_session_key;
 mod signature;
 mod sym_encrypted_data;
@@ -112,6 +114,7 @@ pub use self::marker::*;
 pub use self::mod_detection_code::*;
 pub use self::one_pass_signature::*;
 pub use self::padding::*;
+pub use self::partial_body_writer::*;
 pub use self::public_key_encrypted_session_key::*;
 pub use self::signature::*;
 pub use self::sym_encrypted_data::*;
@@ -124,4 +127,6 @@ pub use self::user_id::*;
 pub use self::many::*;
 pub use self::packet_sum::*;
 
+pub(crate) use self::packet_body_reader::{read_packet_header, PacketBodyReader};
+
 pub use self::sym_encrypted_protected_data::Data;
@@ -36,6 +36,8 @@ impl Serialize for ModDetectionCode {
     }
 }
 
+impl crate::private::Sealed for ModDetectionCode {}
+
 impl PacketTrait for ModDetectionCode {
     fn packet_version(&self) -> Version {
         self.packet_version
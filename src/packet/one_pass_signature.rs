@@ -100,6 +100,8 @@ impl Serialize for OnePassSignature {
     }
 }
 
+impl crate::private::Sealed for OnePassSignature {}
+
 impl PacketTrait for OnePassSignature {
     fn packet_version(&self) -> Version {
         self.packet_version
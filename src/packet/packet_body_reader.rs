@@ -0,0 +1,185 @@
+use std::io::{self, BufRead, Read};
+
+use crate::errors::{IResult, Result};
+use crate::packet::single;
+use crate::types::{PacketLength, Tag, Version, MIN_FIRST_PARTIAL_LENGTH};
+
+/// Reads the header of the next packet from `reader`, consuming only the header.
+pub(crate) fn read_packet_header<R: BufRead>(
+    reader: &mut R,
+) -> Result<(Version, Tag, PacketLength)> {
+    read_streaming(reader, single::parser)
+}
+
+/// Reads single bytes from `reader`, until `parser` has enough of them.
+///
+/// Headers and lengths are only a few bytes long, but may be split over reads.
+fn read_streaming<R: BufRead, T>(
+    reader: &mut R,
+    parser: impl Fn(&[u8]) -> IResult<&[u8], T>,
+) -> Result<T> {
+    let mut buf = Vec::with_capacity(6);
+    loop {
+        match parser(&buf) {
+            Ok((_, value)) => return Ok(value),
+            Err(nom::Err::Incomplete(_)) => {}
+            Err(err) => return Err(err.into()),
+        }
+
+        let mut byte = [0u8; 1];
+        reader.read_exact(&mut byte)?;
+        buf.push(byte[0]);
+    }
+}
+
+/// Reads the body of a single packet, following partial body lengths.
+///
+/// The counterpart of [`PartialBodyWriter`](super::PartialBodyWriter), the body is read as
+/// it arrives, and never held in memory as a whole.
+#[derive(Debug)]
+pub(crate) struct PacketBodyReader<R: BufRead> {
+    reader: R,
+    /// Bytes left in the current part, `None` for a body that runs until the end of the input.
+    remaining: Option<usize>,
+    /// Is the current part followed by another one.
+    partial: bool,
+}
+
+impl<R: BufRead> PacketBodyReader<R> {
+    /// Creates a reader for the body of a packet of type `tag`, whose header was just read.
+    pub(crate) fn new(reader: R, tag: Tag, length: PacketLength) -> Result<Self> {
+        let (remaining, partial) = match length {
+            PacketLength::Fixed(len) => (Some(len), false),
+            PacketLength::Indeterminate => (None, false),
+            PacketLength::Partial(len) => {
+                ensure!(
+                    tag.allows_partial_length(),
+                    "Partial body length is not allowed for packet type {:?}",
+                    tag
+                );
+                ensure!(
+                    len >= MIN_FIRST_PARTIAL_LENGTH,
+                    "Illegal first partial body length {} (shorter than {} bytes)",
+                    len,
+                    MIN_FIRST_PARTIAL_LENGTH
+                );
+                (Some(len), true)
+            }
+        };
+
+        Ok(PacketBodyReader {
+            reader,
+            remaining,
+            partial,
+        })
+    }
+
+    /// Returns the underlying reader, positioned after the part of the body read so far.
+    pub(crate) fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: BufRead> Read for PacketBodyReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            match self.remaining {
+                None => return self.reader.read(buf),
+                Some(0) if !self.partial => return Ok(0),
+                Some(0) => {
+                    let length = read_streaming(&mut self.reader, single::read_packet_len)
+                        .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+                    match length {
+                        PacketLength::Partial(len) => self.remaining = Some(len),
+                        PacketLength::Fixed(len) => {
+                            self.remaining = Some(len);
+                            self.partial = false;
+                        }
+                        PacketLength::Indeterminate => {
+                            return Err(io::Error::new(
+                                io::ErrorKind::InvalidData,
+                                "indeterminate length in a partial body",
+                            ));
+                        }
+                    }
+                }
+                Some(remaining) => {
+                    let len = buf.len().min(remaining);
+                    let read = self.reader.read(&mut buf[..len])?;
+                    if read == 0 {
+                        return Err(io::ErrorKind::UnexpectedEof.into());
+                    }
+                    self.remaining = Some(remaining - read);
+
+                    return Ok(read);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use std::io::Write;
+
+    use super::*;
+    use crate::packet::PartialBodyWriter;
+
+    fn read_body(mut bytes: &[u8]) -> (Tag, Vec<u8>) {
+        let (_, tag, length) = read_packet_header(&mut bytes).unwrap();
+        let mut reader = PacketBodyReader::new(bytes, tag, length).unwrap();
+        let mut body = Vec::new();
+        reader.read_to_end(&mut body).unwrap();
+        assert!(reader.into_inner().is_empty());
+
+        (tag, body)
+    }
+
+    #[test]
+    fn test_partial_roundtrip() {
+        let data: Vec<u8> = (0..5000u32).map(|i| i as u8).collect();
+
+        for len in [0, 1, 511, 512, 513, 1024, 4999, 5000] {
+            let mut writer = PartialBodyWriter::new(Vec::new(), Tag::LiteralData, 512).unwrap();
+            writer.write_all(&data[..len]).unwrap();
+            let bytes = writer.finish().unwrap();
+
+            assert_eq!(read_body(&bytes), (Tag::LiteralData, data[..len].to_vec()));
+        }
+    }
+
+    #[test]
+    fn test_old_format_indeterminate() {
+        // literal data, indeterminate length
+        let bytes = [0xAF, 1, 2, 3];
+        assert_eq!(read_body(&bytes), (Tag::LiteralData, vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn test_invalid_partial() {
+        // user id with a partial length
+        let mut bytes = &[0xCD, 0xE9][..];
+        let (_, tag, length) = read_packet_header(&mut bytes).unwrap();
+        assert!(PacketBodyReader::new(bytes, tag, length).is_err());
+
+        // literal data with a first part of 256 bytes
+        let mut bytes = &[0xCB, 0xE8][..];
+        let (_, tag, length) = read_packet_header(&mut bytes).unwrap();
+        assert!(PacketBodyReader::new(bytes, tag, length).is_err());
+    }
+
+    #[test]
+    fn test_truncated() {
+        let mut bytes = &[0xCB, 0x05, 1, 2][..];
+        let (_, tag, length) = read_packet_header(&mut bytes).unwrap();
+        let mut reader = PacketBodyReader::new(bytes, tag, length).unwrap();
+        let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
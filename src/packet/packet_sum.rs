@@ -8,7 +8,7 @@ use crate::packet::{
     UserId,
 };
 use crate::ser::Serialize;
-use crate::types::{Tag, Version};
+use crate::types::{PacketHeader, Tag, Version};
 
 #[derive(Debug)]
 #[allow(clippy::large_enum_variant)] // TODO: fix me
@@ -80,6 +80,46 @@ impl Packet {
             Packet::Padding(p) => p.packet_version(),
         }
     }
+
+    /// Compares two packets by their contents, ignoring how they were framed: the header
+    /// format and the encoding of the body length.
+    ///
+    /// A packet that is serialized and parsed again is `lossless_eq` to the original. Packets
+    /// that fail to serialize are never equal.
+    pub fn lossless_eq(&self, other: &Packet) -> bool {
+        if self.tag() != other.tag() {
+            return false;
+        }
+
+        match (self.body_to_bytes(), other.body_to_bytes()) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    /// Serializes the body of the packet, without a header.
+    fn body_to_bytes(&self) -> Result<Vec<u8>> {
+        match self {
+            Packet::CompressedData(p) => p.to_bytes(),
+            Packet::PublicKey(p) => p.to_bytes(),
+            Packet::PublicSubkey(p) => p.to_bytes(),
+            Packet::SecretKey(p) => p.to_bytes(),
+            Packet::SecretSubkey(p) => p.to_bytes(),
+            Packet::LiteralData(p) => p.to_bytes(),
+            Packet::Marker(p) => p.to_bytes(),
+            Packet::ModDetectionCode(p) => p.to_bytes(),
+            Packet::OnePassSignature(p) => p.to_bytes(),
+            Packet::PublicKeyEncryptedSessionKey(p) => p.to_bytes(),
+            Packet::Signature(p) => p.to_bytes(),
+            Packet::SymEncryptedData(p) => p.to_bytes(),
+            Packet::SymEncryptedProtectedData(p) => p.to_bytes(),
+            Packet::SymKeyEncryptedSessionKey(p) => p.to_bytes(),
+            Packet::Trust(p) => p.to_bytes(),
+            Packet::UserAttribute(p) => p.to_bytes(),
+            Packet::UserId(p) => p.to_bytes(),
+            Packet::Padding(p) => p.to_bytes(),
+        }
+    }
 }
 
 impl_try_from_into!(
@@ -130,11 +170,14 @@ impl Serialize for Packet {
     }
 }
 
-pub trait PacketTrait: Serialize {
+/// Implemented by the packet types of this crate, it can not be implemented elsewhere.
+pub trait PacketTrait: Serialize + crate::private::Sealed {
     fn packet_version(&self) -> Version;
     fn tag(&self) -> Tag;
 }
 
+impl<'a, T: 'a + PacketTrait> crate::private::Sealed for &'a T {}
+
 impl<'a, T: 'a + PacketTrait> PacketTrait for &'a T {
     fn packet_version(&self) -> Version {
         (*self).packet_version()
@@ -146,7 +189,19 @@ impl<'a, T: 'a + PacketTrait> PacketTrait for &'a T {
 }
 
 pub fn write_packet(writer: &mut impl io::Write, packet: &impl PacketTrait) -> Result<()> {
-    let packet_version = packet.packet_version();
+    write_packet_with_version(writer, packet, packet.packet_version())
+}
+
+/// Writes a packet like [`write_packet`], but using the given header format instead of the one
+/// the packet was created or parsed with.
+///
+/// The old format can only encode tags up to 15, packets with higher tags are always written
+/// in the new format.
+pub fn write_packet_with_version(
+    writer: &mut impl io::Write,
+    packet: &impl PacketTrait,
+    packet_version: Version,
+) -> Result<()> {
     let mut buf = Vec::new();
     packet.to_writer(&mut buf)?;
     debug!(
@@ -157,10 +212,24 @@ pub fn write_packet(writer: &mut impl io::Write, packet: &impl PacketTrait) -> R
     );
 
     // header
-    packet_version.write_header(writer, packet.tag().into(), buf.len())?;
+    PacketHeader::new_fixed_with_version(packet_version, packet.tag(), buf.len())
+        .to_writer(writer)?;
 
     // the actual packet body
     writer.write_all(&buf)?;
 
     Ok(())
 }
+
+/// Writes a packet in its own header format, unless `packet_version` overrides it.
+pub(crate) fn write_packet_as(
+    writer: &mut impl io::Write,
+    packet: &impl PacketTrait,
+    packet_version: Option<Version>,
+) -> Result<()> {
+    write_packet_with_version(
+        writer,
+        packet,
+        packet_version.unwrap_or_else(|| packet.packet_version()),
+    )
+}
@@ -49,6 +49,8 @@ impl Serialize for Padding {
     }
 }
 
+impl crate::private::Sealed for Padding {}
+
 impl PacketTrait for Padding {
     fn packet_version(&self) -> Version {
         self.packet_version
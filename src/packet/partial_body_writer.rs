@@ -0,0 +1,185 @@
+use std::io;
+
+use crate::errors::Result;
+use crate::ser::Serialize;
+use crate::types::{write_fixed_length, write_partial_length, PacketHeader, Tag};
+
+/// Writes the body of a single data packet, framed using partial body lengths.
+///
+/// Data is buffered and emitted in chunks of `chunk_size` bytes, the remainder is written as
+/// the final, fixed length part when calling [`PartialBodyWriter::finish`].
+/// Bodies that fit into a single chunk are written with a regular fixed length header.
+///
+/// The produced framing follows the rules of
+/// https://tools.ietf.org/html/rfc4880.html#section-4.2.2.4
+///
+/// Dropping the writer without calling `finish` results in a truncated packet.
+#[derive(Debug)]
+pub struct PartialBodyWriter<W: io::Write> {
+    writer: W,
+    tag: Tag,
+    chunk_size: usize,
+    buffer: Vec<u8>,
+    /// Set once the header and the first chunk have been written.
+    started: bool,
+}
+
+impl<W: io::Write> PartialBodyWriter<W> {
+    /// Creates a new writer for a packet of type `tag`, using chunks of `chunk_size` bytes.
+    ///
+    /// Fails under the same conditions as [`PacketHeader::new_partial`].
+    pub fn new(writer: W, tag: Tag, chunk_size: usize) -> Result<Self> {
+        // validate early
+        PacketHeader::new_partial(tag, chunk_size)?;
+
+        Ok(PartialBodyWriter {
+            writer,
+            tag,
+            chunk_size,
+            buffer: Vec::with_capacity(chunk_size),
+            started: false,
+        })
+    }
+
+    /// Writes out the remaining data as the final part of the packet and returns
+    /// the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        if self.started {
+            write_fixed_length(&mut self.writer, self.buffer.len())?;
+        } else {
+            PacketHeader::new_fixed(self.tag, self.buffer.len()).to_writer(&mut self.writer)?;
+        }
+        self.writer.write_all(&self.buffer)?;
+        self.buffer.clear();
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+
+    fn write_chunk(&mut self) -> Result<()> {
+        debug_assert_eq!(self.buffer.len(), self.chunk_size);
+
+        if self.started {
+            write_partial_length(&mut self.writer, self.chunk_size)?;
+        } else {
+            PacketHeader::new_partial(self.tag, self.chunk_size)?.to_writer(&mut self.writer)?;
+            self.started = true;
+        }
+        self.writer.write_all(&self.buffer)?;
+        self.buffer.clear();
+
+        Ok(())
+    }
+}
+
+impl<W: io::Write> io::Write for PartialBodyWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Only write out a chunk once there is more data, as the last part must always
+        // be written with a fixed length.
+        if self.buffer.len() == self.chunk_size && !buf.is_empty() {
+            self.write_chunk()
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        }
+
+        let len = buf.len().min(self.chunk_size - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use std::fs::File;
+    use std::io::Write;
+
+    use super::*;
+    use crate::packet::{LiteralData, Packet, PacketParser};
+    use crate::{Deserializable, Message};
+
+    fn write_literal(data: &[u8], chunk_size: usize) -> Vec<u8> {
+        let literal = LiteralData::from_bytes("".into(), data);
+
+        let mut writer = PartialBodyWriter::new(Vec::new(), Tag::LiteralData, chunk_size).unwrap();
+        literal.to_writer(&mut writer).unwrap();
+        writer.finish().unwrap()
+    }
+
+    fn parse_literal(bytes: &[u8]) -> LiteralData {
+        let mut packets = PacketParser::new(bytes);
+        let Packet::LiteralData(literal) = packets.next().unwrap().unwrap() else {
+            panic!("expected literal data");
+        };
+        assert!(packets.next().is_none());
+
+        literal
+    }
+
+    #[test]
+    fn test_partial_body_writer_roundtrip() {
+        for size in [0, 1, 500, 505, 506, 1000, 1024, 4096, 10_000] {
+            let data: Vec<u8> = (0..size).map(|i| i as u8).collect();
+            let bytes = write_literal(&data, 512);
+
+            // 6 bytes of literal data header
+            if size + 6 > 512 {
+                // new format literal data, first chunk is 512 bytes long
+                assert_eq!(&bytes[..2], &[0xCB, 0xE9]);
+            } else {
+                assert_eq!(bytes[0], 0xCB);
+                assert!(bytes[1] < 0xE0);
+            }
+            assert_eq!(parse_literal(&bytes).data(), &data[..]);
+        }
+    }
+
+    #[test]
+    fn test_partial_body_writer_gnupg_fixture() {
+        // Re-encode the body of a message that GnuPG accepts, and make sure our own
+        // encoding parses to the same packet.
+        let (message, _) = Message::from_armor_single(
+            File::open("./tests/unit-tests/partial-body-length/literal.packet-partial.512.asc")
+                .unwrap(),
+        )
+        .unwrap();
+        let Message::Literal(expected) = message else {
+            panic!("expected literal data");
+        };
+
+        let mut writer = PartialBodyWriter::new(Vec::new(), Tag::LiteralData, 512).unwrap();
+        expected.to_writer(&mut writer).unwrap();
+        let bytes = writer.finish().unwrap();
+
+        assert_eq!(&bytes[..2], &[0xCB, 0xE9]);
+        assert_eq!(parse_literal(&bytes), expected);
+    }
+
+    #[test]
+    fn test_partial_body_writer_invalid() {
+        assert!(PartialBodyWriter::new(Vec::new(), Tag::LiteralData, 256).is_err());
+        assert!(PartialBodyWriter::new(Vec::new(), Tag::LiteralData, 513).is_err());
+        assert!(PartialBodyWriter::new(Vec::new(), Tag::Signature, 512).is_err());
+    }
+
+    #[test]
+    fn test_partial_body_writer_small_writes() {
+        let data = vec![7u8; 3000];
+
+        let mut writer = PartialBodyWriter::new(Vec::new(), Tag::LiteralData, 1024).unwrap();
+        let literal = LiteralData::from_bytes("".into(), &data);
+        let mut body = Vec::new();
+        literal.to_writer(&mut body).unwrap();
+        for b in &body {
+            writer.write_all(&[*b]).unwrap();
+        }
+        let bytes = writer.finish().unwrap();
+
+        assert_eq!(parse_literal(&bytes).data(), &data[..]);
+    }
+}
@@ -3,75 +3,181 @@ use std::io;
 use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
 use nom::bytes::streaming::take;
 use nom::combinator::{map, map_res};
-use nom::number::streaming::be_u8;
-use nom::sequence::pair;
+use nom::number::streaming::{be_u16, be_u8};
 use rand::{CryptoRng, Rng};
 
 use crate::crypto::checksum;
 use crate::crypto::public_key::PublicKeyAlgorithm;
 use crate::crypto::sym::SymmetricKeyAlgorithm;
-use crate::errors::{IResult, Result};
+use crate::errors::{Error, IResult, Result};
 use crate::packet::PacketTrait;
 use crate::ser::Serialize;
-use crate::types::{mpi, KeyId, Mpi, PublicKeyTrait, Tag, Version};
+use crate::types::{mpi, KeyId, KeyVersion, Mpi, PublicKeyTrait, Tag, Version};
 
 /// Public Key Encrypted Session Key Packet
 /// https://tools.ietf.org/html/rfc4880.html#section-5.1
+///
+/// Version 3 packets are used with SEIPDv1 and identify the recipient by key id,
+/// version 6 packets are used with SEIPDv2 and identify the recipient by fingerprint.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PublicKeyEncryptedSessionKey {
     packet_version: Version,
     version: u8,
     id: KeyId,
+    /// Version and fingerprint of the recipient, only set for v6 packets.
+    fingerprint: Option<(KeyVersion, Vec<u8>)>,
     algorithm: PublicKeyAlgorithm,
     mpis: Vec<Mpi>,
 }
 
+/// The recipient of a PKESK packet, as written into the packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PkeskRecipient {
+    /// The key id of the recipient, used by v3 packets.
+    KeyId(KeyId),
+    /// The key version and fingerprint of the recipient, used by v6 packets.
+    Fingerprint(KeyVersion, Vec<u8>),
+    /// The recipient is hidden.
+    Anonymous,
+}
+
 impl PublicKeyEncryptedSessionKey {
     /// Parses a `PublicKeyEncryptedSessionKey` packet from the given slice.
     pub fn from_slice(version: Version, input: &[u8]) -> Result<Self> {
         let (_, pk) = parse(version)(input)?;
 
-        if pk.version != 3 {
+        if pk.version != 3 && pk.version != 6 {
             unsupported_err!("unsupported PKESK version {}", pk.version);
         }
 
         Ok(pk)
     }
 
-    /// Encrypts the given session key to the passed in public key.
-    pub fn from_session_key<R: CryptoRng + Rng>(
+    /// Encrypts the given session key to the passed in public key, creating a v3 packet.
+    pub fn from_session_key<R: CryptoRng + Rng + ?Sized>(
         rng: &mut R,
         session_key: &[u8],
         alg: SymmetricKeyAlgorithm,
         pkey: &impl PublicKeyTrait,
     ) -> Result<Self> {
         // the session key is prefixed with symmetric key algorithm
-        let len = session_key.len();
-        let mut data = vec![0u8; len + 3];
-        data[0] = u8::from(alg);
-        data[1..=len].copy_from_slice(session_key);
-
-        // and appended a checksum
-        BigEndian::write_u16(
-            &mut data[len + 1..],
-            checksum::calculate_simple(session_key),
-        );
+        let mut data = Vec::with_capacity(session_key.len() + 3);
+        data.push(u8::from(alg));
+        data.extend_from_slice(session_key);
+        let mpis = encrypt_with_checksum(rng, data, session_key, pkey)?;
 
-        let mpis = pkey.encrypt(rng, &data)?;
+        Ok(Self::v3_from_parts(pkey.key_id(), pkey.algorithm(), mpis))
+    }
 
-        Ok(PublicKeyEncryptedSessionKey {
+    /// Encrypts the given session key to the passed in public key, creating a v6 packet.
+    ///
+    /// V6 packets are used together with SEIPDv2, which carries the symmetric algorithm
+    /// itself, so it is not part of the encrypted session key.
+    pub fn from_session_key_v6<R: CryptoRng + Rng + ?Sized>(
+        rng: &mut R,
+        session_key: &[u8],
+        pkey: &impl PublicKeyTrait,
+    ) -> Result<Self> {
+        let (key_version, fingerprint) = fingerprint_with_version(pkey)?;
+        let mpis = encrypt_with_checksum(rng, session_key.to_vec(), session_key, pkey)?;
+
+        Self::v6_from_parts(key_version, fingerprint, pkey.algorithm(), mpis)
+    }
+
+    /// Creates a v3 packet for a session key that is already encrypted to the key with the
+    /// given `id`.
+    ///
+    /// `mpis` are the algorithm specific values, as returned by [`PublicKeyTrait::encrypt`]
+    /// for the session key prefixed with its symmetric algorithm and followed by its checksum.
+    pub fn v3_from_parts(id: KeyId, algorithm: PublicKeyAlgorithm, mpis: Vec<Mpi>) -> Self {
+        PublicKeyEncryptedSessionKey {
             packet_version: Default::default(),
             version: 3,
-            id: pkey.key_id(),
-            algorithm: pkey.algorithm(),
+            id,
+            fingerprint: None,
+            algorithm,
+            mpis,
+        }
+    }
+
+    /// Creates a v6 packet for a session key that is already encrypted to the key with the
+    /// given version and fingerprint, without needing the key itself.
+    ///
+    /// `mpis` are the algorithm specific values, as returned by [`PublicKeyTrait::encrypt`]
+    /// for the session key followed by its checksum. Fails if the length of `fingerprint`
+    /// does not match `key_version`.
+    pub fn v6_from_parts(
+        key_version: KeyVersion,
+        fingerprint: Vec<u8>,
+        algorithm: PublicKeyAlgorithm,
+        mpis: Vec<Mpi>,
+    ) -> Result<Self> {
+        let Some(id) = KeyId::from_fingerprint(key_version, &fingerprint) else {
+            bail!(
+                "invalid fingerprint length {} for key version {:?}",
+                fingerprint.len(),
+                key_version
+            );
+        };
+
+        Ok(PublicKeyEncryptedSessionKey {
+            packet_version: Default::default(),
+            version: 6,
+            id,
+            fingerprint: Some((key_version, fingerprint)),
+            algorithm,
             mpis,
         })
     }
 
+    /// Returns the recipient that is written into a PKESK packet of the given `version`
+    /// (3 or 6), when encrypting to `pkey`.
+    ///
+    /// This allows checking the recipient of existing packets, or deciding whether to hide
+    /// it, before encrypting.
+    pub fn recipient_for(version: u8, pkey: &impl PublicKeyTrait) -> Result<PkeskRecipient> {
+        match version {
+            3 => Ok(PkeskRecipient::KeyId(pkey.key_id())),
+            6 => {
+                let (key_version, fingerprint) = fingerprint_with_version(pkey)?;
+                Ok(PkeskRecipient::Fingerprint(key_version, fingerprint))
+            }
+            _ => unsupported_err!("unsupported PKESK version {}", version),
+        }
+    }
+
+    /// The recipient of this packet.
+    pub fn recipient(&self) -> PkeskRecipient {
+        match &self.fingerprint {
+            Some((key_version, fp)) => PkeskRecipient::Fingerprint(*key_version, fp.clone()),
+            None if self.version == 6 || self.id.is_wildcard() => PkeskRecipient::Anonymous,
+            None => PkeskRecipient::KeyId(self.id.clone()),
+        }
+    }
+
+    /// The version of this packet, either 3 or 6.
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// The key version and fingerprint of the recipient, for v6 packets.
+    ///
+    /// Returns `None` for v3 packets and anonymous recipients.
+    pub fn fingerprint(&self) -> Option<(KeyVersion, &[u8])> {
+        self.fingerprint
+            .as_ref()
+            .map(|(version, fp)| (*version, &fp[..]))
+    }
+
     pub fn id(&self) -> &KeyId {
         &self.id
     }
 
+    /// The public key algorithm the session key is encrypted with.
+    pub fn algorithm(&self) -> PublicKeyAlgorithm {
+        self.algorithm
+    }
+
     pub fn mpis(&self) -> &[Mpi] {
         &self.mpis
     }
@@ -81,25 +187,87 @@ impl PublicKeyEncryptedSessionKey {
     }
 }
 
+/// Returns the fingerprint of `pkey`, together with the key version it belongs to.
+fn fingerprint_with_version(pkey: &impl PublicKeyTrait) -> Result<(KeyVersion, Vec<u8>)> {
+    let fingerprint = pkey.fingerprint();
+    let key_version = match fingerprint.len() {
+        20 => KeyVersion::V4,
+        32 => KeyVersion::V5,
+        len => bail!("invalid fingerprint length {}", len),
+    };
+
+    Ok((key_version, fingerprint))
+}
+
+/// Appends the checksum of `session_key` to `data` and encrypts the result to `pkey`.
+fn encrypt_with_checksum<R: CryptoRng + Rng + ?Sized>(
+    mut rng: &mut R,
+    mut data: Vec<u8>,
+    session_key: &[u8],
+    pkey: &impl PublicKeyTrait,
+) -> Result<Vec<Mpi>> {
+    let mut checksum = [0u8; 2];
+    BigEndian::write_u16(&mut checksum, checksum::calculate_simple(session_key));
+    data.extend_from_slice(&checksum);
+
+    pkey.encrypt(&mut rng, &data)
+}
+
+/// The smallest AES key wrap output, the wrapped session key of ECDH.
+const MIN_WRAPPED_KEY_LEN: usize = 24;
+
+/// Takes the `len` octets of `field`, failing with [`Error::PkeskTruncated`] if fewer are left.
+fn take_field<'i>(field: &'static str, len: usize, i: &'i [u8]) -> IResult<&'i [u8], &'i [u8]> {
+    if i.len() < len {
+        return Err(nom::Err::Error(Error::PkeskTruncated {
+            field,
+            len,
+            remaining: i.len(),
+        }));
+    }
+    Ok((&i[len..], &i[..len]))
+}
+
+/// Parses an MPI `field`, which must fit into the packet and can not be empty.
+fn mpi_field<'i>(field: &'static str, i: &'i [u8]) -> IResult<&'i [u8], Mpi> {
+    let (rest, bits) = be_u16(i)?;
+    let len = usize::from(bits).div_ceil(8);
+    if len == 0 {
+        return Err(nom::Err::Error(Error::InvalidPkeskLength { field, len }));
+    }
+    take_field(field, len, rest)?;
+
+    map(mpi, |v| v.to_owned())(i)
+}
+
 fn parse_mpis<'i>(alg: &PublicKeyAlgorithm, i: &'i [u8]) -> IResult<&'i [u8], Vec<Mpi>> {
     match alg {
         PublicKeyAlgorithm::RSA | PublicKeyAlgorithm::RSASign | PublicKeyAlgorithm::RSAEncrypt => {
-            map(mpi, |v| vec![v.to_owned()])(i)
+            let (i, c) = mpi_field("RSA ciphertext", i)?;
+            Ok((i, vec![c]))
         }
         PublicKeyAlgorithm::Elgamal | PublicKeyAlgorithm::ElgamalSign => {
-            map(pair(mpi, mpi), |(first, second)| {
-                vec![first.to_owned(), second.to_owned()]
-            })(i)
+            let (i, first) = mpi_field("Elgamal g^k", i)?;
+            let (i, second) = mpi_field("Elgamal m * y^k", i)?;
+            Ok((i, vec![first, second]))
         }
         PublicKeyAlgorithm::ECDSA | PublicKeyAlgorithm::DSA | PublicKeyAlgorithm::DiffieHellman => {
             Ok((i, vec![]))
         }
         PublicKeyAlgorithm::ECDH => {
-            let (i, a) = mpi(i)?;
+            let (i, a) = mpi_field("ECDH ephemeral point", i)?;
             let (i, blen) = be_u8(i)?;
-            let (i, b) = take(blen)(i)?;
+            let len = usize::from(blen);
+            // AES key wrap works on at least two blocks of 8 octets, adding one more
+            if len < MIN_WRAPPED_KEY_LEN || len % 8 != 0 {
+                return Err(nom::Err::Error(Error::InvalidPkeskLength {
+                    field: "ECDH wrapped session key",
+                    len,
+                }));
+            }
+            let (i, b) = take_field("ECDH wrapped session key", len, i)?;
             let v: [u8; 1] = [blen];
-            Ok((i, vec![a.to_owned(), (&v[..]).into(), b.into()]))
+            Ok((i, vec![a, (&v[..]).into(), b.into()]))
         }
         PublicKeyAlgorithm::Unknown(_) => Ok((i, vec![])), // we don't know the format of this data
         _ => Err(nom::Err::Error(crate::errors::Error::ParsingError(
@@ -113,10 +281,39 @@ fn parse(
     packet_version: Version,
 ) -> impl Fn(&[u8]) -> IResult<&[u8], PublicKeyEncryptedSessionKey> {
     move |i: &[u8]| {
-        // version, only 3 is allowed
+        // version, 3 or 6
         let (i, version) = be_u8(i)?;
-        // the key id this maps to
-        let (i, id) = map_res(take(8u8), KeyId::from_slice)(i)?;
+
+        let (i, id, fingerprint) = if version == 6 {
+            // the key version and fingerprint of the recipient, empty for anonymous recipients
+            let (i, len) = be_u8(i)?;
+            if len == 0 {
+                (i, KeyId::from_slice(&[0u8; 8]).expect("fixed size"), None)
+            } else {
+                let (i, recipient) = take_field("recipient", len.into(), i)?;
+                let key_version = KeyVersion::from(recipient[0]);
+                let fp = &recipient[1..];
+                let id = KeyId::from_fingerprint(key_version, fp).ok_or_else(|| {
+                    nom::Err::Error(match key_version.fingerprint_len() {
+                        Some(_) => Error::InvalidPkeskLength {
+                            field: "fingerprint",
+                            len: fp.len(),
+                        },
+                        // skipped like other unsupported ESK packets
+                        None => Error::Unsupported(format!(
+                            "Unsupported PKESK recipient key version {:?}",
+                            key_version
+                        )),
+                    })
+                })?;
+                (i, id, Some((key_version, fp.to_vec())))
+            }
+        } else {
+            // the key id this maps to
+            let (i, id) = map_res(take(8u8), KeyId::from_slice)(i)?;
+            (i, id, None)
+        };
+
         // the symmetric key algorithm
         let (i, alg) = map(be_u8, PublicKeyAlgorithm::from)(i)?;
 
@@ -129,6 +326,7 @@ fn parse(
                 packet_version,
                 version,
                 id,
+                fingerprint,
                 algorithm: alg,
                 mpis,
             },
@@ -139,7 +337,17 @@ fn parse(
 impl Serialize for PublicKeyEncryptedSessionKey {
     fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<()> {
         writer.write_all(&[self.version])?;
-        writer.write_all(self.id.as_ref())?;
+        if self.version == 6 {
+            match &self.fingerprint {
+                Some((key_version, fp)) => {
+                    writer.write_all(&[u8::try_from(fp.len() + 1)?, (*key_version).into()])?;
+                    writer.write_all(fp)?;
+                }
+                None => writer.write_all(&[0])?,
+            }
+        } else {
+            writer.write_all(self.id.as_ref())?;
+        }
         writer.write_all(&[self.algorithm.into()])?;
 
         match self.algorithm {
@@ -153,22 +361,27 @@ impl Serialize for PublicKeyEncryptedSessionKey {
                 }
             }
             PublicKeyAlgorithm::ECDH => {
-                self.mpis[0].to_writer(writer)?;
+                let [point, blen, key] = &self.mpis[..] else {
+                    bail!("invalid ECDH session key values");
+                };
+                point.to_writer(writer)?;
                 // The second value is not encoded as an actual MPI, but rather as a length prefixed
                 // number.
-                let blen: usize = match self.mpis[1].first() {
+                let blen: usize = match blen.first() {
                     Some(l) => *l as usize,
                     None => 0,
                 };
                 writer.write_all(&[blen as u8])?;
-                let padding_len = blen - self.mpis[2].as_bytes().len();
+                let padding_len = blen
+                    .checked_sub(key.as_bytes().len())
+                    .ok_or_else(|| format_err!("invalid ECDH session key length"))?;
                 for _ in 0..padding_len {
                     writer.write_u8(0)?;
                 }
-                writer.write_all(self.mpis[2].as_bytes())?;
+                writer.write_all(key.as_bytes())?;
             }
             _ => {
-                unimplemented_err!("writing {:?}", self.algorithm);
+                unsupported_err!("writing {:?}", self.algorithm);
             }
         }
 
@@ -176,6 +389,8 @@ impl Serialize for PublicKeyEncryptedSessionKey {
     }
 }
 
+impl crate::private::Sealed for PublicKeyEncryptedSessionKey {}
+
 impl PacketTrait for PublicKeyEncryptedSessionKey {
     fn packet_version(&self) -> Version {
         self.packet_version
@@ -185,3 +400,316 @@ impl PacketTrait for PublicKeyEncryptedSessionKey {
         Tag::PublicKeyEncryptedSessionKey
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::errors::Error;
+
+    #[test]
+    fn test_write_unsupported_algorithm() {
+        // parsed, but the algorithm specific data is unknown
+        let mut input = vec![3];
+        input.extend_from_slice(&[1; 8]);
+        input.extend_from_slice(&[99, 0, 8, 0xff]);
+        let pkesk = PublicKeyEncryptedSessionKey::from_slice(Version::New, &input).unwrap();
+        assert_eq!(pkesk.algorithm, PublicKeyAlgorithm::Unknown(99));
+
+        assert!(matches!(pkesk.to_bytes(), Err(Error::Unsupported(_))));
+
+        // malformed ECDH values
+        let pkesk = PublicKeyEncryptedSessionKey {
+            algorithm: PublicKeyAlgorithm::ECDH,
+            mpis: vec![Mpi::from_slice(&[1])],
+            ..pkesk
+        };
+        assert!(pkesk.to_bytes().is_err());
+        let pkesk = PublicKeyEncryptedSessionKey {
+            mpis: vec![
+                Mpi::from_slice(&[1]),
+                Mpi::from_slice(&[1]),
+                Mpi::from_slice(&[1, 2, 3]),
+            ],
+            ..pkesk
+        };
+        assert!(pkesk.to_bytes().is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "x25519")]
+    fn test_recipient_for() {
+        use rand::SeedableRng;
+
+        use crate::composed::{Deserializable, SignedPublicKey};
+        use crate::types::KeyTrait;
+
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(0);
+        let (key, _) = SignedPublicKey::from_armor_single(
+            std::fs::File::open("./tests/autocrypt/alice@autocrypt.example.pub.asc").unwrap(),
+        )
+        .unwrap();
+        let subkey = &key.public_subkeys[0].key;
+        let session_key = [7u8; 16];
+
+        let pkesk = PublicKeyEncryptedSessionKey::from_session_key(
+            &mut rng,
+            &session_key,
+            SymmetricKeyAlgorithm::AES128,
+            subkey,
+        )
+        .unwrap();
+        let expected = PublicKeyEncryptedSessionKey::recipient_for(3, subkey).unwrap();
+        assert_eq!(expected, PkeskRecipient::KeyId(subkey.key_id()));
+        assert_eq!(pkesk.recipient(), expected);
+
+        let pkesk =
+            PublicKeyEncryptedSessionKey::from_session_key_v6(&mut rng, &session_key, subkey)
+                .unwrap();
+        let expected = PublicKeyEncryptedSessionKey::recipient_for(6, subkey).unwrap();
+        assert_eq!(
+            expected,
+            PkeskRecipient::Fingerprint(KeyVersion::V4, subkey.fingerprint())
+        );
+        assert_eq!(pkesk.recipient(), expected);
+        let parsed =
+            PublicKeyEncryptedSessionKey::from_slice(Version::New, &pkesk.to_bytes().unwrap())
+                .unwrap();
+        assert_eq!(parsed.recipient(), expected);
+
+        // anonymous v6 recipient
+        let pkesk = PublicKeyEncryptedSessionKey {
+            fingerprint: None,
+            ..parsed
+        };
+        assert_eq!(pkesk.recipient(), PkeskRecipient::Anonymous);
+
+        assert!(PublicKeyEncryptedSessionKey::recipient_for(5, subkey).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "x25519")]
+    fn test_from_parts() {
+        use rand::SeedableRng;
+
+        use crate::composed::{Deserializable, SignedPublicKey};
+        use crate::types::KeyTrait;
+
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(0);
+        let (key, _) = SignedPublicKey::from_armor_single(
+            std::fs::File::open("./tests/autocrypt/alice@autocrypt.example.pub.asc").unwrap(),
+        )
+        .unwrap();
+        let subkey = &key.public_subkeys[0].key;
+        let session_key = [7u8; 16];
+
+        let pkesk =
+            PublicKeyEncryptedSessionKey::from_session_key_v6(&mut rng, &session_key, subkey)
+                .unwrap();
+        let from_parts = PublicKeyEncryptedSessionKey::v6_from_parts(
+            KeyVersion::V4,
+            subkey.fingerprint(),
+            subkey.algorithm(),
+            pkesk.mpis().to_vec(),
+        )
+        .unwrap();
+        assert_eq!(from_parts, pkesk);
+        assert_eq!(from_parts.id(), &subkey.key_id());
+
+        // the fingerprint does not match the key version
+        assert!(PublicKeyEncryptedSessionKey::v6_from_parts(
+            KeyVersion::V5,
+            subkey.fingerprint(),
+            subkey.algorithm(),
+            pkesk.mpis().to_vec(),
+        )
+        .is_err());
+        assert!(PublicKeyEncryptedSessionKey::v6_from_parts(
+            KeyVersion::V4,
+            subkey.fingerprint()[..16].to_vec(),
+            subkey.algorithm(),
+            pkesk.mpis().to_vec(),
+        )
+        .is_err());
+
+        let pkesk = PublicKeyEncryptedSessionKey::from_session_key(
+            &mut rng,
+            &session_key,
+            SymmetricKeyAlgorithm::AES128,
+            subkey,
+        )
+        .unwrap();
+        let from_parts = PublicKeyEncryptedSessionKey::v3_from_parts(
+            subkey.key_id(),
+            subkey.algorithm(),
+            pkesk.mpis().to_vec(),
+        );
+        assert_eq!(from_parts, pkesk);
+    }
+
+    /// An ECDH PKESK of the given version to the subkey of alice, and the offsets of its
+    /// ephemeral point and of the length octet of the wrapped session key.
+    #[cfg(feature = "x25519")]
+    fn ecdh_pkesk(version: u8) -> (Vec<u8>, usize, usize) {
+        use rand::SeedableRng;
+
+        use crate::composed::{Deserializable, SignedPublicKey};
+
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(0);
+        let (key, _) = SignedPublicKey::from_armor_single(
+            std::fs::File::open("./tests/autocrypt/alice@autocrypt.example.pub.asc").unwrap(),
+        )
+        .unwrap();
+        let subkey = &key.public_subkeys[0].key;
+        let session_key = [7u8; 16];
+        let pkesk = match version {
+            3 => PublicKeyEncryptedSessionKey::from_session_key(
+                &mut rng,
+                &session_key,
+                SymmetricKeyAlgorithm::AES128,
+                subkey,
+            ),
+            _ => PublicKeyEncryptedSessionKey::from_session_key_v6(&mut rng, &session_key, subkey),
+        }
+        .unwrap();
+
+        let bytes = pkesk.to_bytes().unwrap();
+        let len_pos = bytes.len() - pkesk.mpis[2].len() - 1;
+        let point_pos = len_pos - pkesk.mpis[0].len();
+        (bytes, point_pos, len_pos)
+    }
+
+    #[test]
+    #[cfg(feature = "x25519")]
+    fn test_parse_invalid_lengths() {
+        let (bytes, point_pos, len_pos) = ecdh_pkesk(3);
+        assert!(PublicKeyEncryptedSessionKey::from_slice(Version::New, &bytes).is_ok());
+
+        // wrapped session keys are a multiple of 8 octets, and at least 24
+        for len in [0, 1, 16, 23, 25] {
+            let mut bytes = bytes.clone();
+            bytes[len_pos] = len;
+            let res = PublicKeyEncryptedSessionKey::from_slice(Version::New, &bytes);
+            assert!(
+                matches!(res, Err(Error::InvalidPkeskLength { len: l, .. }) if l == usize::from(len)),
+                "{len}: {res:?}"
+            );
+        }
+
+        // empty ephemeral point
+        let mut bytes = bytes.clone();
+        bytes[point_pos - 2..point_pos].copy_from_slice(&[0, 0]);
+        let res = PublicKeyEncryptedSessionKey::from_slice(Version::New, &bytes);
+        assert!(
+            matches!(
+                res,
+                Err(Error::InvalidPkeskLength {
+                    field: "ECDH ephemeral point",
+                    len: 0
+                })
+            ),
+            "{res:?}"
+        );
+
+        // v6 recipients that are longer than the packet, or too short for the key version
+        let (mut bytes, _, _) = ecdh_pkesk(6);
+        bytes[1] = 0xff;
+        let res = PublicKeyEncryptedSessionKey::from_slice(Version::New, &bytes);
+        assert!(
+            matches!(
+                res,
+                Err(Error::PkeskTruncated {
+                    field: "recipient",
+                    len: 255,
+                    ..
+                })
+            ),
+            "{res:?}"
+        );
+        bytes[1] = 1;
+        let res = PublicKeyEncryptedSessionKey::from_slice(Version::New, &bytes);
+        assert!(
+            matches!(
+                res,
+                Err(Error::InvalidPkeskLength {
+                    field: "fingerprint",
+                    len: 0
+                })
+            ),
+            "{res:?}"
+        );
+
+        // an RSA ciphertext without any octets
+        let mut bytes = vec![3];
+        bytes.extend_from_slice(&[1; 8]);
+        bytes.extend_from_slice(&[1, 0, 0]);
+        let res = PublicKeyEncryptedSessionKey::from_slice(Version::New, &bytes);
+        assert!(
+            matches!(
+                res,
+                Err(Error::InvalidPkeskLength {
+                    field: "RSA ciphertext",
+                    len: 0
+                })
+            ),
+            "{res:?}"
+        );
+    }
+
+    #[cfg(feature = "x25519")]
+    proptest::proptest! {
+        #[test]
+        fn pkesk_truncated(version in proptest::sample::select(vec![3u8, 6]), cut: proptest::sample::Index) {
+            let (bytes, point_pos, len_pos) = ecdh_pkesk(version);
+            let cut = cut.index(bytes.len());
+
+            let res = PublicKeyEncryptedSessionKey::from_slice(Version::New, &bytes[..cut]);
+            proptest::prop_assert!(res.is_err());
+            let field = if cut > len_pos {
+                Some("ECDH wrapped session key")
+            } else if cut >= point_pos && cut < len_pos {
+                Some("ECDH ephemeral point")
+            } else {
+                None
+            };
+            if let Some(field) = field {
+                proptest::prop_assert!(
+                    matches!(res, Err(Error::PkeskTruncated { field: f, .. }) if f == field),
+                    "{:?}", res
+                );
+            }
+        }
+
+        #[test]
+        fn pkesk_inflated_length(version in proptest::sample::select(vec![3u8, 6]), add in 1u8..=200) {
+            let (mut bytes, _, len_pos) = ecdh_pkesk(version);
+            let len = usize::from(bytes[len_pos]) + usize::from(add);
+            proptest::prop_assume!(len <= 255);
+            bytes[len_pos] = len as u8;
+
+            let res = PublicKeyEncryptedSessionKey::from_slice(Version::New, &bytes);
+            if len % 8 == 0 {
+                proptest::prop_assert!(
+                    matches!(res, Err(Error::PkeskTruncated { len: l, .. }) if l == len),
+                    "{:?}", res
+                );
+            } else {
+                proptest::prop_assert!(
+                    matches!(res, Err(Error::InvalidPkeskLength { len: l, .. }) if l == len),
+                    "{:?}", res
+                );
+            }
+        }
+
+        #[test]
+        fn pkesk_modified_octet(version in proptest::sample::select(vec![3u8, 6]), pos: proptest::sample::Index, value: u8) {
+            let (mut bytes, _, _) = ecdh_pkesk(version);
+            let pos = pos.index(bytes.len());
+            bytes[pos] = value;
+
+            // must not panic
+            let _ = PublicKeyEncryptedSessionKey::from_slice(Version::New, &bytes);
+        }
+    }
+}
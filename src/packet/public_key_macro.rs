@@ -4,11 +4,13 @@ macro_rules! impl_public_key {
         #[derive(Debug, PartialEq, Eq, Clone)]
         pub struct $name {
             pub(crate) packet_version: $crate::types::Version,
-            pub(crate) version: $crate::types::KeyVersion,
+            pub(crate) version: $crate::packet::KnownKeyVersion,
             pub(crate) algorithm: $crate::crypto::public_key::PublicKeyAlgorithm,
             pub(crate) created_at: chrono::DateTime<chrono::Utc>,
             pub(crate) expiration: Option<u16>,
             pub(crate) public_params: $crate::types::PublicParams,
+            #[cfg(feature = "rsa")]
+            pub(crate) rsa_verifier: $crate::crypto::rsa::VerifierCache,
         }
 
         impl $name {
@@ -22,21 +24,50 @@ macro_rules! impl_public_key {
                 public_params: $crate::types::PublicParams,
             ) -> $crate::errors::Result<Self> {
                 use $crate::crypto::public_key::PublicKeyAlgorithm;
-                use $crate::types::KeyVersion;
-
-                if version == KeyVersion::V2 || version == KeyVersion::V3 {
-                    if !(algorithm == PublicKeyAlgorithm::RSA
-                        || algorithm == PublicKeyAlgorithm::RSAEncrypt
-                        || algorithm == PublicKeyAlgorithm::RSASign)
-                    {
-                        // It's sufficient to throw a "soft" Error::Unsupported
-                        unsupported_err!(
-                            "Invalid algorithm {:?} for key version: {:?}",
-                            algorithm,
-                            version,
-                        );
+                use $crate::packet::KnownKeyVersion;
+                use $crate::types::{KeyId, KeyVersion};
+
+                let version = match version {
+                    KeyVersion::V2 | KeyVersion::V3 => {
+                        if !(algorithm == PublicKeyAlgorithm::RSA
+                            || algorithm == PublicKeyAlgorithm::RSAEncrypt
+                            || algorithm == PublicKeyAlgorithm::RSASign)
+                        {
+                            // It's sufficient to throw a "soft" Error::Unsupported
+                            unsupported_err!(
+                                "Invalid algorithm {:?} for key version: {:?}",
+                                algorithm,
+                                version,
+                            );
+                        }
+
+                        // the key id is taken from the low 64 bits of the modulus
+                        let key_id = match &public_params {
+                            $crate::types::PublicParams::RSA { n, .. } => {
+                                ensure!(
+                                    n.len() >= 8,
+                                    "RSA modulus too short for a {:?} key: {} bytes",
+                                    version,
+                                    n.len()
+                                );
+                                KeyId::from_slice(&n.as_bytes()[n.len() - 8..])?
+                            }
+                            params => {
+                                bail!("Invalid params for a {:?} key: {:?}", version, params)
+                            }
+                        };
+
+                        if version == KeyVersion::V2 {
+                            KnownKeyVersion::V2(key_id)
+                        } else {
+                            KnownKeyVersion::V3(key_id)
+                        }
                     }
-                }
+                    KeyVersion::V4 => KnownKeyVersion::V4,
+                    KeyVersion::V5 => KnownKeyVersion::V5,
+                    // without a known layout, there is no fingerprint or key id for the key
+                    KeyVersion::Other(v) => unsupported_err!("Unsupported key version {}", v),
+                };
 
                 Ok($name {
                     packet_version,
@@ -45,6 +76,8 @@ macro_rules! impl_public_key {
                     created_at,
                     expiration,
                     public_params,
+                    #[cfg(feature = "rsa")]
+                    rsa_verifier: Default::default(),
                 })
             }
 
@@ -67,7 +100,7 @@ macro_rules! impl_public_key {
             }
 
             pub fn version(&self) -> $crate::types::KeyVersion {
-                self.version
+                (&self.version).into()
             }
 
             pub fn created_at(&self) -> &chrono::DateTime<chrono::Utc> {
@@ -82,8 +115,13 @@ macro_rules! impl_public_key {
                 &self.public_params
             }
 
+            /// The numeric id of the public key algorithm, also for unknown algorithms.
+            pub fn algorithm_id(&self) -> u8 {
+                u8::from(self.algorithm)
+            }
+
             pub fn verify(&self) -> $crate::errors::Result<()> {
-                unimplemented!("verify");
+                unimplemented_err!("verify");
             }
 
             fn to_writer_old<W: std::io::Write>(
@@ -96,7 +134,7 @@ macro_rules! impl_public_key {
                 writer.write_u32::<BigEndian>(self.created_at.timestamp() as u32)?;
                 writer.write_u16::<BigEndian>(
                     self.expiration
-                        .expect("old key versions have an expiration"),
+                        .ok_or_else(|| format_err!("old key versions need an expiration"))?,
                 )?;
                 writer.write_all(&[self.algorithm.into()])?;
                 self.public_params.to_writer(writer)?;
@@ -118,6 +156,25 @@ macro_rules! impl_public_key {
                 Ok(())
             }
 
+            /// Writes a draft v5 key, with the four-octet length of the public key material.
+            fn to_writer_v5<W: std::io::Write>(
+                &self,
+                writer: &mut W,
+            ) -> $crate::errors::Result<()> {
+                use byteorder::{BigEndian, WriteBytesExt};
+                use $crate::ser::Serialize;
+
+                let mut len = $crate::util::ByteCounter::default();
+                self.public_params.to_writer(&mut len)?;
+
+                writer.write_u32::<BigEndian>(self.created_at.timestamp() as u32)?;
+                writer.write_all(&[self.algorithm.into()])?;
+                writer.write_u32::<BigEndian>(u32::try_from(len.0)?)?;
+                self.public_params.to_writer(writer)?;
+
+                Ok(())
+            }
+
             pub fn sign<F>(
                 &self,
                 key: &impl $crate::types::SecretKeyTrait,
@@ -157,21 +214,20 @@ macro_rules! impl_public_key {
 
         impl $crate::ser::Serialize for $name {
             fn to_writer<W: std::io::Write>(&self, writer: &mut W) -> $crate::errors::Result<()> {
-                writer.write_all(&[u8::from(self.version)])?;
+                use $crate::packet::KnownKeyVersion;
+
+                writer.write_all(&[u8::from(self.version())])?;
 
                 match self.version {
-                    $crate::types::KeyVersion::V2 | $crate::types::KeyVersion::V3 => {
-                        self.to_writer_old(writer)
-                    }
-                    $crate::types::KeyVersion::V4 => self.to_writer_new(writer),
-                    $crate::types::KeyVersion::V5 => unimplemented_err!("V5 keys"),
-                    $crate::types::KeyVersion::Other(v) => {
-                        unimplemented_err!("Unsupported key version {}", v)
-                    }
+                    KnownKeyVersion::V2(_) | KnownKeyVersion::V3(_) => self.to_writer_old(writer),
+                    KnownKeyVersion::V4 => self.to_writer_new(writer),
+                    KnownKeyVersion::V5 => self.to_writer_v5(writer),
                 }
             }
         }
 
+        impl $crate::private::Sealed for $name {}
+
         impl $crate::packet::PacketTrait for $name {
             fn packet_version(&self) -> $crate::types::Version {
                 self.packet_version
@@ -187,77 +243,87 @@ macro_rules! impl_public_key {
             ///
             /// In case of SHA1 collisions, the "mitigated" hash digest is returned.
             fn fingerprint(&self) -> Vec<u8> {
-                use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
+                use byteorder::{BigEndian, WriteBytesExt};
                 use md5::Md5;
                 use sha1_checked::{Digest, Sha1};
 
+                use $crate::packet::KnownKeyVersion;
                 use $crate::ser::Serialize;
-                use $crate::types::KeyVersion;
 
-                match self.version() {
-                    KeyVersion::V2 | KeyVersion::V3 => {
+                match self.version {
+                    KnownKeyVersion::V2(_) | KnownKeyVersion::V3(_) => {
+                        // MD5 over the bodies of the MPIs, without their length prefix
                         let mut h = Md5::new();
-                        self.public_params
-                            .to_writer(&mut h)
-                            .expect("write to hasher");
+                        match &self.public_params {
+                            $crate::types::PublicParams::RSA { n, e } => {
+                                h.update(n.as_bytes());
+                                h.update(e.as_bytes());
+                            }
+                            params => params.to_writer(&mut h).expect("write to hasher"),
+                        }
                         h.finalize().to_vec()
                     }
-                    KeyVersion::V4 => {
-                        // A one-octet version number (4).
-                        let mut packet = vec![4, 0, 0, 0, 0];
-
-                        // A four-octet number denoting the time that the key was created.
-                        BigEndian::write_u32(
-                            &mut packet[1..5],
-                            self.created_at().timestamp() as u32,
-                        );
-
-                        // A one-octet number denoting the public-key algorithm of this key.
-                        packet.push(self.algorithm().into());
-                        self.public_params
-                            .to_writer(&mut packet)
-                            .expect("write to vec");
+                    KnownKeyVersion::V4 => {
+                        // The packet body is serialized twice, to find its length first,
+                        // instead of buffering it.
+                        let mut len = $crate::util::ByteCounter::default();
+                        self.to_writer(&mut len).expect("write to counter");
 
                         let mut h = Sha1::new();
-                        h.update(&[0x99]);
-                        h.write_u16::<BigEndian>(packet.len() as u16)
+                        h.update([0x99]);
+                        h.write_u16::<BigEndian>(len.0 as u16)
+                            .expect("write to hasher");
+                        self.to_writer(&mut h).expect("write to hasher");
+
+                        h.finalize().to_vec()
+                    }
+                    KnownKeyVersion::V5 => {
+                        // SHA256 over 0x9A, the four octet length and the body
+                        let mut len = $crate::util::ByteCounter::default();
+                        self.to_writer(&mut len).expect("write to counter");
+
+                        let mut h = sha2::Sha256::new();
+                        h.update([0x9A]);
+                        h.write_u32::<BigEndian>(len.0 as u32)
                             .expect("write to hasher");
-                        h.update(&packet);
+                        self.to_writer(&mut h).expect("write to hasher");
 
                         h.finalize().to_vec()
                     }
-                    KeyVersion::V5 => unimplemented!("V5 keys"),
-                    KeyVersion::Other(v) => unimplemented!("Unsupported key version {}", v),
                 }
             }
 
+            /// Returns the key id of this key.
             fn key_id(&self) -> $crate::types::KeyId {
-                use $crate::types::{KeyId, KeyVersion, PublicParams};
-
-                match self.version() {
-                    KeyVersion::V2 | KeyVersion::V3 => match &self.public_params {
-                        PublicParams::RSA { n, .. } => {
-                            let offset = n.len() - 8;
+                use $crate::packet::KnownKeyVersion;
+                use $crate::types::KeyId;
 
-                            KeyId::from_slice(&n.as_bytes()[offset..]).expect("fixed size slice")
-                        }
-                        _ => panic!("invalid key constructed: {:?}", &self.public_params),
-                    },
-                    KeyVersion::V4 => {
+                match &self.version {
+                    // taken from the modulus when the key was created
+                    KnownKeyVersion::V2(key_id) | KnownKeyVersion::V3(key_id) => key_id.clone(),
+                    KnownKeyVersion::V4 => {
                         // Lower 64 bits
                         let f = self.fingerprint();
                         let offset = f.len() - 8;
 
                         KeyId::from_slice(&f[offset..]).expect("fixed size slice")
                     }
-                    KeyVersion::V5 => unimplemented!("V5 keys"),
-                    KeyVersion::Other(v) => unimplemented!("Unsupported key version {}", v),
+                    KnownKeyVersion::V5 => {
+                        // Upper 64 bits, like GnuPG
+                        let f = self.fingerprint();
+
+                        KeyId::from_slice(&f[..8]).expect("fixed size slice")
+                    }
                 }
             }
 
             fn algorithm(&self) -> $crate::crypto::public_key::PublicKeyAlgorithm {
                 self.algorithm
             }
+
+            fn key_version(&self) -> $crate::types::KeyVersion {
+                self.version()
+            }
         }
 
         impl $crate::types::PublicKeyTrait for $name {
@@ -270,19 +336,25 @@ macro_rules! impl_public_key {
                 use $crate::types::PublicParams;
 
                 match self.public_params {
+                    #[cfg(feature = "rsa")]
                     PublicParams::RSA { ref n, ref e } => {
                         ensure_eq!(sig.len(), 1, "invalid signature");
-                        $crate::crypto::rsa::verify(
-                            n.as_bytes(),
-                            e.as_bytes(),
-                            hash,
-                            hashed,
-                            sig[0].as_bytes(),
-                        )
+                        self.rsa_verifier
+                            .get_or_init(n.as_bytes(), e.as_bytes())?
+                            .verify(hash, hashed, sig[0].as_bytes())
+                    }
+                    #[cfg(not(feature = "rsa"))]
+                    PublicParams::RSA { .. } => {
+                        unsupported_err!("verify RSA (feature \"rsa\" is disabled)");
                     }
+                    #[cfg(feature = "ed25519")]
                     PublicParams::EdDSA { ref curve, ref q } => {
                         $crate::crypto::eddsa::verify(curve, q.as_bytes(), hash, hashed, sig)
                     }
+                    #[cfg(not(feature = "ed25519"))]
+                    PublicParams::EdDSA { .. } => {
+                        unsupported_err!("verify EdDSA (feature \"ed25519\" is disabled)");
+                    }
                     PublicParams::ECDSA(ref params) => {
                         $crate::crypto::ecdsa::verify(params, hash, hashed, sig)
                     }
@@ -297,6 +369,7 @@ macro_rules! impl_public_key {
                     PublicParams::Elgamal { .. } => {
                         unimplemented_err!("verify Elgamal");
                     }
+                    #[cfg(feature = "dsa")]
                     PublicParams::DSA {
                         ref p,
                         ref q,
@@ -315,6 +388,10 @@ macro_rules! impl_public_key {
                             sig[1].clone().into(),
                         )
                     }
+                    #[cfg(not(feature = "dsa"))]
+                    PublicParams::DSA { .. } => {
+                        unsupported_err!("verify DSA (feature \"dsa\" is disabled)");
+                    }
                     PublicParams::Unknown { .. } => {
                         unimplemented_err!("verify unknown");
                     }
@@ -329,9 +406,14 @@ macro_rules! impl_public_key {
                 use $crate::types::{KeyTrait, PublicParams};
 
                 let res = match self.public_params {
+                    #[cfg(feature = "rsa")]
                     PublicParams::RSA { ref n, ref e } => {
                         $crate::crypto::rsa::encrypt(rng, n.as_bytes(), e.as_bytes(), plain)
                     }
+                    #[cfg(not(feature = "rsa"))]
+                    PublicParams::RSA { .. } => {
+                        unsupported_err!("encryption with RSA (feature \"rsa\" is disabled)")
+                    }
                     PublicParams::EdDSA { .. } => bail!("EdDSA is only used for signing"),
                     PublicParams::ECDSA { .. } => bail!("ECDSA is only used for signing"),
                     PublicParams::ECDH {
@@ -1,6 +1,6 @@
 use chrono::{DateTime, TimeZone, Utc};
 use nom::bytes::streaming::tag;
-use nom::combinator::{map, map_opt, map_res};
+use nom::combinator::{map, map_opt, map_res, rest};
 use nom::multi::length_data;
 use nom::number::streaming::{be_u16, be_u32, be_u8};
 use nom::sequence::{pair, tuple};
@@ -113,21 +113,29 @@ fn rsa(i: &[u8]) -> IResult<&[u8], PublicParams> {
     })(i)
 }
 
+/// Keeps the key material of an unknown algorithm as is.
+///
+/// The layout of the fields is not known, so the rest of the packet is taken.
 fn unknown(i: &[u8]) -> IResult<&[u8], PublicParams> {
-    Ok((i, PublicParams::Unknown { data: vec![] })) // FIXME
+    map(rest, |data: &[u8]| PublicParams::Unknown {
+        data: data.to_vec(),
+    })(i)
 }
 
-/// Parse the fields of a public key.
-pub fn parse_pub_fields(typ: PublicKeyAlgorithm) -> impl Fn(&[u8]) -> IResult<&[u8], PublicParams> {
-    move |i: &[u8]| match typ {
-        PublicKeyAlgorithm::RSA | PublicKeyAlgorithm::RSAEncrypt | PublicKeyAlgorithm::RSASign => {
-            rsa(i)
-        }
-        PublicKeyAlgorithm::DSA => dsa(i),
-        PublicKeyAlgorithm::ECDSA => ecdsa(i),
-        PublicKeyAlgorithm::ECDH => ecdh(i),
-        PublicKeyAlgorithm::Elgamal | PublicKeyAlgorithm::ElgamalSign => elgamal(i),
-        PublicKeyAlgorithm::EdDSA => eddsa(i),
+/// Returns `true` if the fields of keys of this algorithm can be parsed.
+///
+/// The key material of the other algorithms is kept as [`PublicParams::Unknown`].
+pub(crate) fn has_known_layout(typ: PublicKeyAlgorithm) -> bool {
+    match typ {
+        PublicKeyAlgorithm::RSA
+        | PublicKeyAlgorithm::RSAEncrypt
+        | PublicKeyAlgorithm::RSASign
+        | PublicKeyAlgorithm::DSA
+        | PublicKeyAlgorithm::ECDSA
+        | PublicKeyAlgorithm::ECDH
+        | PublicKeyAlgorithm::Elgamal
+        | PublicKeyAlgorithm::ElgamalSign
+        | PublicKeyAlgorithm::EdDSA => true,
 
         PublicKeyAlgorithm::DiffieHellman
         | PublicKeyAlgorithm::Private100
@@ -141,7 +149,22 @@ pub fn parse_pub_fields(typ: PublicKeyAlgorithm) -> impl Fn(&[u8]) -> IResult<&[
         | PublicKeyAlgorithm::Private108
         | PublicKeyAlgorithm::Private109
         | PublicKeyAlgorithm::Private110
-        | PublicKeyAlgorithm::Unknown(_) => unknown(i),
+        | PublicKeyAlgorithm::Unknown(_) => false,
+    }
+}
+
+/// Parse the fields of a public key.
+pub fn parse_pub_fields(typ: PublicKeyAlgorithm) -> impl Fn(&[u8]) -> IResult<&[u8], PublicParams> {
+    move |i: &[u8]| match typ {
+        PublicKeyAlgorithm::RSA | PublicKeyAlgorithm::RSAEncrypt | PublicKeyAlgorithm::RSASign => {
+            rsa(i)
+        }
+        PublicKeyAlgorithm::DSA => dsa(i),
+        PublicKeyAlgorithm::ECDSA => ecdsa(i),
+        PublicKeyAlgorithm::ECDH => ecdh(i),
+        PublicKeyAlgorithm::Elgamal | PublicKeyAlgorithm::ElgamalSign => elgamal(i),
+        PublicKeyAlgorithm::EdDSA => eddsa(i),
+        _ => unknown(i),
     }
 }
 
@@ -167,6 +190,36 @@ fn new_public_key_parser(
     }
 }
 
+/// Parses a draft v5 key, which prefixes the public key material with its four-octet length.
+///
+/// Ref: https://datatracker.ietf.org/doc/html/draft-ietf-openpgp-crypto-refresh-06#section-5.5.2
+fn v5_public_key_parser(
+    key_ver: &KeyVersion,
+) -> impl Fn(
+    &[u8],
+) -> IResult<
+    &[u8],
+    (
+        KeyVersion,
+        PublicKeyAlgorithm,
+        DateTime<Utc>,
+        Option<u16>,
+        PublicParams,
+    ),
+> + '_ {
+    |i: &[u8]| {
+        let (i, created_at) = map_opt(be_u32, |v| Utc.timestamp_opt(i64::from(v), 0).single())(i)?;
+        let (i, alg) = map(be_u8, PublicKeyAlgorithm::from)(i)?;
+        let (i, material) = length_data(be_u32)(i)?;
+        let (rest, params) = parse_pub_fields(alg)(material)?;
+        if !rest.is_empty() {
+            return Err(nom::Err::Error(crate::errors::Error::InvalidInput));
+        }
+
+        Ok((i, (*key_ver, alg, created_at, None, params)))
+    }
+}
+
 fn old_public_key_parser(
     key_ver: &KeyVersion,
 ) -> impl Fn(
@@ -210,7 +263,8 @@ pub(crate) fn parse(
     let (i, key) = match &key_ver {
         &KeyVersion::V2 | &KeyVersion::V3 => old_public_key_parser(&key_ver)(i)?,
         &KeyVersion::V4 => new_public_key_parser(&key_ver)(i)?,
-        KeyVersion::V5 | KeyVersion::Other(_) => {
+        &KeyVersion::V5 => v5_public_key_parser(&key_ver)(i)?,
+        KeyVersion::Other(_) => {
             return Err(nom::Err::Error(crate::errors::Error::Unsupported(format!(
                 "Unsupported key version {}",
                 u8::from(key_ver)
@@ -32,14 +32,14 @@ macro_rules! impl_secret_key {
                 let (version, algorithm, created_at, expiration, public_params, secret_params) =
                     details;
                 Ok($name {
-                    details: $crate::packet::$details {
+                    details: $crate::packet::$details::new(
                         packet_version,
                         version,
                         algorithm,
                         created_at,
                         expiration,
                         public_params,
-                    },
+                    )?,
                     secret_params,
                 })
             }
@@ -61,7 +61,7 @@ macro_rules! impl_secret_key {
             }
 
             pub fn verify(&self) -> $crate::errors::Result<()> {
-                unimplemented!("verify");
+                unimplemented_err!("verify");
             }
 
             fn repr_from_ciphertext<F>(
@@ -187,18 +187,21 @@ macro_rules! impl_secret_key {
                 self.unlock(key_pw, |priv_key| {
                     debug!("unlocked key");
                     let sig = match *priv_key {
+                        #[cfg(feature = "rsa")]
                         SecretKeyRepr::RSA(ref priv_key) => {
                             priv_key.sign(hash, data, self.public_params())
                         }
                         SecretKeyRepr::ECDSA(ref priv_key) => {
                             priv_key.sign(hash, data, self.public_params())
                         }
+                        #[cfg(feature = "dsa")]
                         SecretKeyRepr::DSA(ref priv_key) => {
                             priv_key.sign(hash, data, self.public_params())
                         }
                         SecretKeyRepr::ECDH(_) => {
                             bail!("ECDH can not be used to for signing operations")
                         }
+                        #[cfg(feature = "ed25519")]
                         SecretKeyRepr::EdDSA(ref priv_key) => {
                             priv_key.sign(hash, data, self.public_params())
                         }
@@ -234,14 +237,16 @@ macro_rules! impl_secret_key {
                         self.to_writer_old(writer)
                     }
                     $crate::types::KeyVersion::V4 => self.to_writer_new(writer),
-                    $crate::types::KeyVersion::V5 => unimplemented_err!("V5 keys"),
+                    $crate::types::KeyVersion::V5 => unsupported_err!("writing V5 keys"),
                     $crate::types::KeyVersion::Other(v) => {
-                        unimplemented_err!("Unsupported key version {}", v)
+                        unsupported_err!("writing key version {}", v)
                     }
                 }
             }
         }
 
+        impl $crate::private::Sealed for $name {}
+
         impl $crate::packet::PacketTrait for $name {
             fn packet_version(&self) -> $crate::types::Version {
                 self.details.packet_version()
@@ -265,6 +270,10 @@ macro_rules! impl_secret_key {
             fn algorithm(&self) -> $crate::crypto::public_key::PublicKeyAlgorithm {
                 self.details.algorithm()
             }
+
+            fn key_version(&self) -> $crate::types::KeyVersion {
+                self.details.version()
+            }
         }
 
         impl $crate::types::PublicKeyTrait for $name {
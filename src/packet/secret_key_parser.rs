@@ -5,14 +5,24 @@ use nom::sequence::tuple;
 
 use crate::crypto::public_key::PublicKeyAlgorithm;
 use crate::errors::{Error, IResult};
-use crate::packet::public_key_parser::parse_pub_fields;
+use crate::packet::public_key_parser::{has_known_layout, parse_pub_fields};
 use crate::types::{KeyVersion, PublicParams, SecretParams};
 
 /// Parse the whole private key, both public and private fields.
+///
+/// Unlike public keys, keys of unknown algorithms are rejected: without knowing the layout, the
+/// public fields can not be told apart from the secret ones.
 fn parse_pub_priv_fields(
     typ: PublicKeyAlgorithm,
 ) -> impl Fn(&[u8]) -> IResult<&[u8], (PublicParams, SecretParams)> {
     move |i| {
+        if !has_known_layout(typ) {
+            return Err(nom::Err::Error(Error::Unsupported(format!(
+                "secret key of unknown algorithm {}",
+                u8::from(typ)
+            ))));
+        }
+
         map_res(tuple((parse_pub_fields(typ), rest)), |(pub_params, v)| {
             let secret_params = SecretParams::from_slice(v, typ, &pub_params)?;
             Ok::<_, Error>((pub_params, secret_params))
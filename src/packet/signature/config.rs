@@ -1,18 +1,22 @@
 use std::fmt;
 use std::io::Read;
 
+use bstr::{BStr, BString};
 use byteorder::{BigEndian, ByteOrder};
 use chrono::{DateTime, Utc};
 
-use crate::crypto::hash::{HashAlgorithm, Hasher};
+use crate::crypto::hash::{HashAlgorithm, Hasher, MAX_DIGEST_SIZE};
 use crate::crypto::public_key::PublicKeyAlgorithm;
 use crate::errors::{Error, Result};
-use crate::packet::{Signature, SignatureType, SignatureVersion, Subpacket, SubpacketData};
+use crate::packet::{
+    CriticalityPolicy, IssuerFingerprintMismatch, Signature, SignatureType, SignatureVersion,
+    Subpacket, SubpacketCriticality, SubpacketData,
+};
 use crate::ser::Serialize;
-use crate::types::{KeyId, PublicKeyTrait, SecretKeyTrait, Tag};
+use crate::types::{ensure_supported_hash_alg, KeyId, PublicKeyTrait, SecretKeyTrait, Tag};
 
-#[derive(Clone, PartialEq, Eq, Builder)]
-#[builder(build_fn(error = "Error"))]
+#[derive(Clone, Builder)]
+#[builder(build_fn(error = "Error", validate = "Self::validate"))]
 pub struct SignatureConfig {
     #[builder(default)]
     pub version: SignatureVersion,
@@ -21,15 +25,82 @@ pub struct SignatureConfig {
     pub hash_alg: HashAlgorithm,
 
     pub unhashed_subpackets: Vec<Subpacket>,
-    pub hashed_subpackets: Vec<Subpacket>,
+    /// Only modified through [`SignatureConfig::hashed_subpackets_mut`], which keeps
+    /// `hashed_area` in sync.
+    hashed_subpackets: Vec<Subpacket>,
 
     // only set on V2 and V3 keys
     #[builder(default)]
     pub created: Option<DateTime<Utc>>,
     #[builder(default)]
     pub issuer: Option<KeyId>,
+
+    /// Which subpackets may be marked critical, checked when building and signing.
+    ///
+    /// Only set through the builder, so that the subpackets are checked against it. Not part of
+    /// the serialized signature.
+    #[builder(default)]
+    criticality_policy: CriticalityPolicy,
+
+    /// The critical bit of the standard subpackets, set in both areas when signing.
+    ///
+    /// With `None` the subpackets are signed as they are. The subpackets rpgp emits are not
+    /// critical. Only set through the builder outside of this crate. Not part of the serialized
+    /// signature.
+    #[builder(default, setter(strip_option))]
+    pub(crate) subpacket_criticality: Option<SubpacketCriticality>,
+
+    /// The names of the notations the application understands, which may be critical in a
+    /// verified signature, see [`Signature::set_known_notations`]. Not part of the serialized
+    /// signature.
+    #[builder(default, setter(skip))]
+    pub(crate) known_notations: Vec<BString>,
+
+    /// The encoded hashed subpackets of a parsed signature.
+    ///
+    /// Hashed and written as is, instead of encoding `hashed_subpackets` again. Dropped
+    /// whenever the hashed subpackets are modified, so it always holds the same subpackets.
+    #[builder(default, setter(skip))]
+    pub(crate) hashed_area: Option<Vec<u8>>,
+}
+
+impl SignatureConfigBuilder {
+    fn validate(&self) -> std::result::Result<(), Error> {
+        let policy = self.criticality_policy.unwrap_or_default();
+        for subpacket in self
+            .hashed_subpackets
+            .iter()
+            .chain(self.unhashed_subpackets.iter())
+            .flatten()
+        {
+            policy.check(subpacket)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl PartialEq for SignatureConfig {
+    fn eq(&self, other: &Self) -> bool {
+        // the criticality policy, subpacket criticality and known notations are not serialized
+        self.version == other.version
+            && self.typ == other.typ
+            && self.pub_alg == other.pub_alg
+            && self.hash_alg == other.hash_alg
+            && self.unhashed_subpackets == other.unhashed_subpackets
+            && self.hashed_subpackets == other.hashed_subpackets
+            && match (&self.hashed_area, &other.hashed_area) {
+                (None, None) => true,
+                // the same subpackets may be encoded differently in parsed signatures
+                _ => self.hashed_area().ok() == other.hashed_area().ok(),
+            }
+            && self.created == other.created
+            && self.issuer == other.issuer
+    }
 }
 
+impl Eq for SignatureConfig {}
+
 impl SignatureConfig {
     pub fn new_v4(
         version: SignatureVersion,
@@ -48,7 +119,52 @@ impl SignatureConfig {
             unhashed_subpackets,
             issuer: None,
             created: None,
+            criticality_policy: CriticalityPolicy::default(),
+            subpacket_criticality: None,
+            known_notations: Vec::new(),
+            hashed_area: None,
+        }
+    }
+
+    /// Mutable access to the hashed subpackets.
+    ///
+    /// Drops the hashed area, as it was parsed, so the modified subpackets are used for hashing
+    /// and serialization.
+    pub fn hashed_subpackets_mut(&mut self) -> &mut Vec<Subpacket> {
+        self.hashed_area = None;
+        &mut self.hashed_subpackets
+    }
+
+    /// Returns the encoded hashed subpackets, as they are hashed.
+    ///
+    /// The hashed area of a parsed signature is returned as is.
+    pub(crate) fn hashed_area(&self) -> Result<std::borrow::Cow<'_, [u8]>> {
+        if let Some(area) = &self.hashed_area {
+            return Ok(area.into());
+        }
+
+        let mut area = Vec::new();
+        for packet in &self.hashed_subpackets {
+            packet.to_writer(&mut area)?;
+        }
+
+        Ok(area.into())
+    }
+
+    /// Sets the critical bit of the standard subpackets, checks all subpackets against the
+    /// criticality policy, and drops the hashed area of a parsed signature, as a new signature
+    /// is made over the current subpackets.
+    fn prepare_to_sign(&mut self) -> Result<()> {
+        self.hashed_area = None;
+        if let Some(criticality) = self.subpacket_criticality {
+            criticality.apply(&mut self.hashed_subpackets);
+            criticality.apply(&mut self.unhashed_subpackets);
+        }
+        for subpacket in self.hashed_subpackets().chain(self.unhashed_subpackets()) {
+            self.criticality_policy.check(subpacket)?;
         }
+
+        Ok(())
     }
 
     /// Sign the given data.
@@ -57,13 +173,30 @@ impl SignatureConfig {
         F: FnOnce() -> String,
         R: Read,
     {
+        ensure_supported_hash_alg(key, self.hash_alg)?;
         let mut hasher = self.hash_alg.new_hasher()?;
-
         self.hash_data_to_sign(&mut *hasher, data)?;
-        let len = self.hash_signature_data(&mut *hasher)?;
-        hasher.update(&self.trailer(len)?);
 
-        let hash = &hasher.finish()[..];
+        self.sign_hasher(key, key_pw, hasher)
+    }
+
+    /// Sign the data that was already fed to `hasher`, for data that is hashed while it is
+    /// being written.
+    ///
+    /// `hasher` must have been created for the hash algorithm of this config.
+    pub(crate) fn sign_hasher<F>(
+        mut self,
+        key: &impl SecretKeyTrait,
+        key_pw: F,
+        hasher: Box<dyn Hasher>,
+    ) -> Result<Signature>
+    where
+        F: FnOnce() -> String,
+    {
+        ensure_supported_hash_alg(key, self.hash_alg)?;
+        self.prepare_to_sign()?;
+
+        let hash = &self.finish_digest(hasher)?[..];
 
         let signed_hash_value = [hash[0], hash[1]];
         let signature = key.create_signature(key_pw, self.hash_alg, hash)?;
@@ -71,6 +204,47 @@ impl SignatureConfig {
         Ok(Signature::from_config(self, signed_hash_value, signature))
     }
 
+    /// Returns the digest a key signs when this config is used to sign `data`, as it is passed
+    /// to [`SecretKeyTrait::create_signature`].
+    ///
+    /// This allows computing the digest outside of rpgp's signing, e.g. for a smartcard. It
+    /// covers `data`, the hashed fields of the signature and the trailer. Signatures up to v5
+    /// are not salted, so nothing is hashed before `data`.
+    pub fn digest<R: Read>(&self, data: R) -> Result<Vec<u8>> {
+        let mut config = self.clone();
+        config.prepare_to_sign()?;
+
+        let mut hasher = config.hash_alg.new_hasher()?;
+        config.hash_data_to_sign(&mut *hasher, data)?;
+
+        config.finish_digest(hasher)
+    }
+
+    /// Hashes the signature fields and the trailer into `hasher` and returns the digest.
+    fn finish_digest(&self, mut hasher: Box<dyn Hasher>) -> Result<Vec<u8>> {
+        let len = self.hash_signature_data(&mut *hasher)?;
+        self.hash_trailer(&mut *hasher, len)?;
+
+        Ok(hasher.finish())
+    }
+
+    /// Like `finish_digest`, but finalizes into `buf` instead of allocating the digest.
+    ///
+    /// `hasher` must use the hash algorithm of this config.
+    pub(crate) fn finish_digest_into<'a>(
+        &self,
+        hasher: &mut dyn Hasher,
+        buf: &'a mut [u8; MAX_DIGEST_SIZE],
+    ) -> Result<&'a [u8]> {
+        let len = self.hash_signature_data(hasher)?;
+        self.hash_trailer(hasher, len)?;
+
+        let digest = &mut buf[..self.hash_alg.digest_size()];
+        hasher.finish_reset_into(digest);
+
+        Ok(digest)
+    }
+
     /// Create a certification self-signature.
     pub fn sign_certification<F>(
         self,
@@ -87,7 +261,7 @@ impl SignatureConfig {
 
     /// Create a certification third-party signature.
     pub fn sign_certification_third_party<F>(
-        self,
+        mut self,
         signer: &impl SecretKeyTrait,
         signer_pw: F,
         signee: &impl PublicKeyTrait,
@@ -102,6 +276,8 @@ impl SignatureConfig {
             "can not sign non certification as certification"
         );
         debug!("signing certification {:#?}", self.typ);
+        ensure_supported_hash_alg(signer, self.hash_alg)?;
+        self.prepare_to_sign()?;
 
         let mut hasher = self.hash_alg.new_hasher()?;
 
@@ -136,7 +312,7 @@ impl SignatureConfig {
         hasher.update(&packet_buf);
 
         let len = self.hash_signature_data(&mut *hasher)?;
-        hasher.update(&self.trailer(len)?);
+        self.hash_trailer(&mut *hasher, len)?;
 
         let hash = &hasher.finish()[..];
 
@@ -148,7 +324,7 @@ impl SignatureConfig {
 
     /// Sign a key binding.
     pub fn sign_key_binding<F>(
-        self,
+        mut self,
         signing_key: &impl SecretKeyTrait,
         key_pw: F,
         key: &impl PublicKeyTrait,
@@ -160,6 +336,8 @@ impl SignatureConfig {
             "signing key binding: {:#?} - {:#?} - {:#?}",
             self, signing_key, key
         );
+        ensure_supported_hash_alg(signing_key, self.hash_alg)?;
+        self.prepare_to_sign()?;
 
         let mut hasher = self.hash_alg.new_hasher()?;
 
@@ -170,7 +348,7 @@ impl SignatureConfig {
         key.to_writer_old(&mut hasher)?;
 
         let len = self.hash_signature_data(&mut *hasher)?;
-        hasher.update(&self.trailer(len)?);
+        self.hash_trailer(&mut *hasher, len)?;
 
         let hash = &hasher.finish()[..];
         let signed_hash_value = [hash[0], hash[1]];
@@ -179,9 +357,40 @@ impl SignatureConfig {
         Ok(Signature::from_config(self, signed_hash_value, signature))
     }
 
+    /// Sign a primary key binding ("back signature"), by which a signing `subkey` certifies
+    /// that it belongs to `primary`.
+    pub fn sign_primary_key_binding<F>(
+        mut self,
+        subkey: &impl SecretKeyTrait,
+        subkey_pw: F,
+        primary: &impl PublicKeyTrait,
+    ) -> Result<Signature>
+    where
+        F: FnOnce() -> String,
+    {
+        debug!("signing primary key binding: {:#?} - {:#?}", self, subkey);
+        ensure_supported_hash_alg(subkey, self.hash_alg)?;
+        self.prepare_to_sign()?;
+
+        let mut hasher = self.hash_alg.new_hasher()?;
+
+        // the primary key is hashed first, as for the subkey binding
+        primary.to_writer_old(&mut hasher)?;
+        subkey.to_writer_old(&mut hasher)?;
+
+        let len = self.hash_signature_data(&mut *hasher)?;
+        self.hash_trailer(&mut *hasher, len)?;
+
+        let hash = &hasher.finish()[..];
+        let signed_hash_value = [hash[0], hash[1]];
+        let signature = subkey.create_signature(subkey_pw, self.hash_alg, hash)?;
+
+        Ok(Signature::from_config(self, signed_hash_value, signature))
+    }
+
     /// Signs a direct key signature or a revocation.
     pub fn sign_key<F>(
-        self,
+        mut self,
         signing_key: &impl SecretKeyTrait,
         key_pw: F,
         key: &impl PublicKeyTrait,
@@ -190,13 +399,15 @@ impl SignatureConfig {
         F: FnOnce() -> String,
     {
         debug!("signing key (revocation): {:#?} - {:#?}", self, key);
+        ensure_supported_hash_alg(signing_key, self.hash_alg)?;
+        self.prepare_to_sign()?;
 
         let mut hasher = self.hash_alg.new_hasher()?;
 
         key.to_writer_old(&mut hasher)?;
 
         let len = self.hash_signature_data(&mut *hasher)?;
-        hasher.update(&self.trailer(len)?);
+        self.hash_trailer(&mut *hasher, len)?;
 
         let hash = &hasher.finish()[..];
         let signed_hash_value = [hash[0], hash[1]];
@@ -210,18 +421,21 @@ impl SignatureConfig {
         self.typ
     }
 
+    /// Returns the policy for critical subpackets this config is checked against.
+    pub fn criticality_policy(&self) -> CriticalityPolicy {
+        self.criticality_policy
+    }
+
     /// Calculate the serialized version of this packet, but only the part relevant for hashing.
     pub fn hash_signature_data(&self, hasher: &mut dyn Hasher) -> Result<usize> {
         match self.version {
             SignatureVersion::V2 | SignatureVersion::V3 => {
                 let mut buf = [0u8; 5];
                 buf[0] = self.typ as u8;
-                BigEndian::write_u32(
-                    &mut buf[1..],
-                    self.created
-                        .expect("must exist for a v3 signature")
-                        .timestamp() as u32,
-                );
+                let created = self
+                    .created
+                    .ok_or_else(|| format_err!("missing creation time for a v3 signature"))?;
+                BigEndian::write_u32(&mut buf[1..], created.timestamp() as u32);
 
                 hasher.update(&buf);
 
@@ -232,7 +446,8 @@ impl SignatureConfig {
                 // TODO: validate this is the right thing to do for v5
                 // TODO: reduce duplication with serialization code
 
-                let mut res = vec![
+                let hashed_area = self.hashed_area()?;
+                let mut header = [
                     // the signature version
                     self.version.into(),
                     // the signature type
@@ -241,27 +456,19 @@ impl SignatureConfig {
                     self.pub_alg.into(),
                     // the hash algorithm
                     self.hash_alg.into(),
-                    // will be filled with the length
+                    // the length of the hashed subpackets
                     0u8,
                     0u8,
                 ];
+                BigEndian::write_u16(&mut header[4..6], hashed_area.len().try_into()?);
 
-                // hashed subpackets
-                let mut hashed_subpackets = Vec::new();
-                for packet in &self.hashed_subpackets {
-                    debug!("hashing {:#?}", packet);
-                    packet.to_writer(&mut hashed_subpackets)?;
-                }
-
-                BigEndian::write_u16(&mut res[4..6], hashed_subpackets.len().try_into()?);
-                res.extend(hashed_subpackets);
-
-                hasher.update(&res);
+                hasher.update(&header);
+                hasher.update(&hashed_area);
 
                 // TODO: V5 signatures hash additional values here
                 // see https://datatracker.ietf.org/doc/html/draft-ietf-openpgp-rfc4880bis-10#name-computing-signatures
 
-                Ok(res.len())
+                Ok(header.len() + hashed_area.len())
             }
             SignatureVersion::Other(version) => {
                 bail!("unsupported signature version {}", version)
@@ -305,15 +512,29 @@ impl SignatureConfig {
     }
 
     pub fn trailer(&self, len: usize) -> Result<Vec<u8>> {
+        let (trailer, trailer_len) = self.trailer_bytes(len)?;
+        Ok(trailer[..trailer_len].to_vec())
+    }
+
+    /// Hashes the trailer for `len` bytes of hashed signature data into `hasher`.
+    pub(crate) fn hash_trailer(&self, hasher: &mut dyn Hasher, len: usize) -> Result<()> {
+        let (trailer, trailer_len) = self.trailer_bytes(len)?;
+        hasher.update(&trailer[..trailer_len]);
+
+        Ok(())
+    }
+
+    /// Returns the trailer in a fixed size buffer, together with its actual length.
+    fn trailer_bytes(&self, len: usize) -> Result<([u8; 6], usize)> {
         match self.version {
             SignatureVersion::V2 | SignatureVersion::V3 => {
                 // Nothing to do
-                Ok(Vec::new())
+                Ok(([0; 6], 0))
             }
             SignatureVersion::V4 | SignatureVersion::V5 => {
-                let mut trailer = vec![0x04, 0xFF, 0, 0, 0, 0];
+                let mut trailer = [0x04, 0xFF, 0, 0, 0, 0];
                 BigEndian::write_u32(&mut trailer[2..], len as u32);
-                Ok(trailer)
+                Ok((trailer, trailer.len()))
             }
             SignatureVersion::Other(version) => {
                 bail!("unsupported signature version {}", version)
@@ -352,6 +573,16 @@ impl SignatureConfig {
         )
     }
 
+    /// Returns if the signature is a revocation or not.
+    pub fn is_revocation(&self) -> bool {
+        matches!(
+            self.typ,
+            SignatureType::KeyRevocation
+                | SignatureType::SubkeyRevocation
+                | SignatureType::CertRevocation
+        )
+    }
+
     /// Signature Creation Time.
     ///
     /// The time the signature was made.
@@ -399,6 +630,34 @@ impl SignatureConfig {
             .collect()
     }
 
+    /// Signer's User ID.
+    ///
+    /// The User ID of the key holder that is responsible for the signature.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc4880#section-5.2.3.22
+    ///
+    /// Returns the first Signer's User ID subpacket, only from the hashed area.
+    pub fn signers_userid(&self) -> Option<&BStr> {
+        self.hashed_subpackets().find_map(|p| match &p.data {
+            SubpacketData::SignersUserID(d) => Some(d.as_ref()),
+            _ => None,
+        })
+    }
+
+    /// Sets the Signer's User ID, replacing any existing one.
+    ///
+    /// The subpacket is placed in the hashed area, so that it is covered by the signature.
+    pub fn set_signers_userid(&mut self, user_id: impl Into<BString>) {
+        let is_signers_userid = |p: &Subpacket| matches!(p.data, SubpacketData::SignersUserID(_));
+        self.unhashed_subpackets.retain(|p| !is_signers_userid(p));
+
+        let hashed_subpackets = self.hashed_subpackets_mut();
+        hashed_subpackets.retain(|p| !is_signers_userid(p));
+        hashed_subpackets.push(Subpacket::regular(SubpacketData::SignersUserID(
+            user_id.into(),
+        )));
+    }
+
     /// Issuer Fingerprint.
     ///
     /// The OpenPGP Key fingerprint of the key issuing the signature.
@@ -418,6 +677,28 @@ impl SignatureConfig {
             })
             .collect()
     }
+
+    /// Returns the Issuer Fingerprint subpackets from both the hashed and unhashed area, whose
+    /// key version does not match the length of their fingerprint.
+    ///
+    /// Such subpackets are written by buggy tools. Their fingerprints are still used to find
+    /// the issuer, but no key id is derived from them.
+    pub fn issuer_fingerprint_mismatches(&self) -> Vec<IssuerFingerprintMismatch> {
+        self.hashed_subpackets()
+            .chain(self.unhashed_subpackets())
+            .filter_map(|sp| match &sp.data {
+                SubpacketData::IssuerFingerprint(version, fp)
+                    if version.fingerprint_len() != Some(fp.len()) =>
+                {
+                    Some(IssuerFingerprintMismatch {
+                        version: *version,
+                        fingerprint: fp.to_vec(),
+                    })
+                }
+                _ => None,
+            })
+            .collect()
+    }
 }
 
 impl fmt::Debug for SignatureConfig {
@@ -431,6 +712,9 @@ impl fmt::Debug for SignatureConfig {
             .field("issuer", &self.issuer)
             .field("unhashed_subpackets", &self.unhashed_subpackets)
             .field("hashed_subpackets", &self.hashed_subpackets)
+            .field("criticality_policy", &self.criticality_policy)
+            .field("subpacket_criticality", &self.subpacket_criticality)
+            .field("known_notations", &self.known_notations)
             .finish()
     }
 }
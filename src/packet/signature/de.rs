@@ -1,10 +1,13 @@
+use std::io::{self, Read};
 use std::str;
 
+use byteorder::{BigEndian, ReadBytesExt};
+
 use bstr::BString;
 use chrono::{DateTime, Duration, TimeZone, Utc};
 use nom::bytes::streaming::{tag, take};
 use nom::combinator::{complete, map, map_opt, map_parser, map_res, rest};
-use nom::multi::{fold_many_m_n, length_data, many0};
+use nom::multi::{fold_many_m_n, many0};
 use nom::number::streaming::{be_u16, be_u32, be_u8};
 use nom::sequence::{pair, tuple};
 use smallvec::SmallVec;
@@ -25,9 +28,7 @@ use crate::util::{clone_into_array, packet_length};
 impl Deserialize for Signature {
     /// Parses a `Signature` packet from the given slice.
     fn from_slice(packet_version: Version, input: &[u8]) -> Result<Self> {
-        let (_, pk) = parse(packet_version)(input)?;
-
-        Ok(pk)
+        Signature::try_from_reader(packet_version, input)
     }
 }
 
@@ -260,9 +261,9 @@ fn sig_target(i: &[u8]) -> IResult<&[u8], SubpacketData> {
 
 /// Ref: https://tools.ietf.org/html/rfc4880.html#section-5.2.3.26
 fn embedded_sig(i: &[u8]) -> IResult<&[u8], SubpacketData> {
-    map(parse(Version::New), |sig| {
-        SubpacketData::EmbeddedSignature(Box::new(sig))
-    })(i)
+    let sig = Signature::try_from_reader(Version::New, i).map_err(nom::Err::Error)?;
+
+    Ok((&b""[..], SubpacketData::EmbeddedSignature(Box::new(sig))))
 }
 
 /// Parse an issuer subpacket
@@ -276,6 +277,17 @@ fn issuer_fingerprint(i: &[u8]) -> IResult<&[u8], SubpacketData> {
     )(i)
 }
 
+/// Parse a key block subpacket, keeping key blocks with an unknown reserved octet as they are.
+/// Ref: https://datatracker.ietf.org/doc/html/draft-ietf-openpgp-rfc4880bis-10#section-5.2.3.33
+fn key_block(body: &[u8]) -> IResult<&[u8], SubpacketData> {
+    let data = match body.split_first() {
+        Some((0, key)) => SubpacketData::KeyBlock(key.to_vec()),
+        _ => SubpacketData::Other(SubpacketType::KeyBlock.as_u8(false), body.to_vec()),
+    };
+
+    Ok((&b""[..], data))
+}
+
 /// Parse a preferred aead subpacket
 fn pref_aead_alg(body: &[u8]) -> IResult<&[u8], SubpacketData> {
     let list: SmallVec<[AeadAlgorithm; 2]> = body.iter().map(|v| AeadAlgorithm::from(*v)).collect();
@@ -313,6 +325,7 @@ fn subpacket(typ: SubpacketType, is_critical: bool, body: &[u8]) -> IResult<&[u8
         EmbeddedSignature => embedded_sig(body),
         IssuerFingerprint => issuer_fingerprint(body),
         PreferredAead => pref_aead_alg(body),
+        KeyBlock => key_block(body),
         Experimental(n) => Ok((
             body,
             SubpacketData::Experimental(n, SmallVec::from_slice(body)),
@@ -329,7 +342,7 @@ fn subpacket(typ: SubpacketType, is_critical: bool, body: &[u8]) -> IResult<&[u8
     res
 }
 
-fn subpackets<'a>(i: &'a [u8]) -> IResult<&'a [u8], Vec<Subpacket>> {
+pub(super) fn subpackets<'a>(i: &'a [u8]) -> IResult<&'a [u8], Vec<Subpacket>> {
     many0(complete(|i: &'a [u8]| {
         // the subpacket length (1, 2, or 5 octets)
         let (i, len) = packet_length(i)?;
@@ -417,66 +430,159 @@ fn v3_parser(
     }
 }
 
+/// Unhashed subpackets larger than this are skipped while parsing.
+///
+/// The unhashed area is not covered by the signature, large subpackets in it (like images or
+/// notations) are not trustworthy, and would only use up memory.
+const MAX_UNHASHED_SUBPACKET_SIZE: usize = 8 * 1024;
+
+impl Signature {
+    /// Parses a `Signature` packet body from the given reader.
+    ///
+    /// The unhashed area is read one subpacket at a time, unhashed subpackets larger than 8 KiB
+    /// are skipped without being buffered, so memory use is proportional to the retained
+    /// subpackets. The hashed area is kept as it was encoded, for verification.
+    pub fn try_from_reader(packet_version: Version, mut reader: impl io::Read) -> Result<Self> {
+        let version = SignatureVersion::from(reader.read_u8()?);
+        match version {
+            SignatureVersion::V2 | SignatureVersion::V3 => {
+                let mut body = Vec::new();
+                reader.read_to_end(&mut body)?;
+                let (_, sig) = v3_parser(packet_version, version)(&body)?;
+
+                Ok(sig)
+            }
+            SignatureVersion::V4 | SignatureVersion::V5 => {
+                v4_from_reader(packet_version, version, reader)
+            }
+            _ => unsupported_err!("unknown signature version {:?}", version),
+        }
+    }
+}
+
 /// Parse a v4 or v5 signature packet
 /// Ref: https://tools.ietf.org/html/rfc4880.html#section-5.2.3
-fn v4_parser(
+fn v4_from_reader(
     packet_version: Version,
     version: SignatureVersion,
-) -> impl Fn(&[u8]) -> IResult<&[u8], Signature> {
-    move |i: &[u8]| {
-        let (i, (typ, pub_alg, hash_alg, hsub, usub, ls_hash)) = tuple((
-            // One-octet signature type.
-            map_res(be_u8, SignatureType::try_from),
-            // One-octet public-key algorithm.
-            map(be_u8, PublicKeyAlgorithm::from),
-            // One-octet hash algorithm.
-            map(be_u8, HashAlgorithm::from),
-            // Two-octet scalar octet count for following hashed subpacket data.
-            // Hashed subpacket data set (zero or more subpackets).
-            map_parser(length_data(be_u16), subpackets),
-            // Two-octet scalar octet count for the following unhashed subpacket data.
-            // Unhashed subpacket data set (zero or more subpackets).
-            map_parser(length_data(be_u16), subpackets),
-            // Two-octet field holding the left 16 bits of the signed hash value.
-            take(2usize),
-        ))(i)?;
-        // One or more multiprecision integers comprising the signature.
-        let (i, sig) = actual_signature(&pub_alg)(i)?;
-        Ok((
-            i,
-            Signature::new(
-                packet_version,
-                version,
-                typ,
-                pub_alg,
-                hash_alg,
-                clone_into_array(ls_hash),
-                sig,
-                hsub,
-                usub,
-            ),
-        ))
+    mut reader: impl io::Read,
+) -> Result<Signature> {
+    // One-octet signature type.
+    let typ = SignatureType::try_from(reader.read_u8()?)
+        .map_err(|e| format_err!("invalid signature type {}", e.number))?;
+    // One-octet public-key algorithm.
+    let pub_alg = PublicKeyAlgorithm::from(reader.read_u8()?);
+    // One-octet hash algorithm.
+    let hash_alg = HashAlgorithm::from(reader.read_u8()?);
+
+    // Two-octet scalar octet count for following hashed subpacket data.
+    let hashed_len = reader.read_u16::<BigEndian>()?;
+    // Hashed subpacket data set (zero or more subpackets).
+    let mut hashed_area = vec![0u8; usize::from(hashed_len)];
+    reader.read_exact(&mut hashed_area)?;
+    let (_, hsub) = subpackets(&hashed_area)?;
+
+    // Two-octet scalar octet count for the following unhashed subpacket data.
+    let unhashed_len = reader.read_u16::<BigEndian>()?;
+    // Unhashed subpacket data set (zero or more subpackets).
+    let usub = unhashed_subpackets((&mut reader).take(u64::from(unhashed_len)))?;
+
+    // Two-octet field holding the left 16 bits of the signed hash value.
+    let mut ls_hash = [0u8; 2];
+    reader.read_exact(&mut ls_hash)?;
+
+    // One or more multiprecision integers comprising the signature.
+    let mut body = Vec::new();
+    reader.read_to_end(&mut body)?;
+    let (_, sig) = actual_signature(&pub_alg)(&body)?;
+
+    let mut signature = Signature::new(
+        packet_version,
+        version,
+        typ,
+        pub_alg,
+        hash_alg,
+        ls_hash,
+        sig,
+        hsub,
+        usub,
+    );
+    signature.config.hashed_area = Some(hashed_area);
+
+    Ok(signature)
+}
+
+/// Parses an encoded hashed area, which has to consist of complete subpackets.
+pub(super) fn hashed_area_subpackets(area: &[u8]) -> Result<Vec<Subpacket>> {
+    ensure!(
+        area.len() <= usize::from(u16::MAX),
+        "hashed area too large: {} bytes",
+        area.len()
+    );
+    let (rest, subpackets) = subpackets(area)?;
+    ensure!(rest.is_empty(), "invalid subpacket in the hashed area");
+
+    Ok(subpackets)
+}
+
+/// Reads the unhashed subpackets, reusing a single buffer for their bodies.
+///
+/// Anyone can add subpackets to the unhashed area without invalidating the signature, so
+/// subpackets that are too large or fail to parse are dropped instead of rejecting the
+/// signature. A malformed length discards the rest of the area.
+fn unhashed_subpackets(mut reader: io::Take<impl io::Read>) -> Result<Vec<Subpacket>> {
+    let mut subpackets = Vec::new();
+    let mut buf = Vec::new();
+
+    while reader.limit() > 0 {
+        // the subpacket length (1, 2, or 5 octets)
+        let len = match reader.read_u8()? {
+            olen @ 0..=191 => usize::from(olen),
+            olen @ 192..=254 => {
+                ((usize::from(olen) - 192) << 8) + 192 + usize::from(reader.read_u8()?)
+            }
+            255 => reader.read_u32::<BigEndian>()? as usize,
+        };
+        if len == 0 || len as u64 > reader.limit() {
+            warn!(
+                "invalid unhashed subpacket length {}, skipping the rest",
+                len
+            );
+            let rest = reader.limit();
+            skip(&mut reader, rest)?;
+            break;
+        }
+
+        // the subpacket type (1 octet)
+        let (typ, is_critical) = SubpacketType::from_u8(reader.read_u8()?);
+        let body_len = len - 1;
+        if body_len > MAX_UNHASHED_SUBPACKET_SIZE {
+            warn!(
+                "skipping unhashed subpacket {:?}: {} bytes, limit is {}",
+                typ, body_len, MAX_UNHASHED_SUBPACKET_SIZE
+            );
+            skip(&mut reader, body_len as u64)?;
+            continue;
+        }
+
+        buf.clear();
+        (&mut reader).take(body_len as u64).read_to_end(&mut buf)?;
+        ensure_eq!(buf.len(), body_len, "truncated subpacket");
+        match subpacket(typ, is_critical, &buf) {
+            Ok((_, subpacket)) => subpackets.push(subpacket),
+            Err(err) => warn!("skipping invalid unhashed subpacket {:?}: {:?}", typ, err),
+        }
     }
-}
 
-fn invalid_version(_body: &[u8], version: SignatureVersion) -> IResult<&[u8], Signature> {
-    Err(nom::Err::Error(crate::errors::Error::Unsupported(format!(
-        "unknown signature version {version:?}"
-    ))))
+    Ok(subpackets)
 }
 
-/// Parse a signature packet (Tag 2)
-/// Ref: https://tools.ietf.org/html/rfc4880.html#section-5.2
-fn parse(packet_version: Version) -> impl Fn(&[u8]) -> IResult<&[u8], Signature> {
-    move |i: &[u8]| {
-        let (i, version) = map(be_u8, SignatureVersion::from)(i)?;
-        let (i, signature) = match &version {
-            &SignatureVersion::V2 | &SignatureVersion::V3 => v3_parser(packet_version, version)(i),
-            &SignatureVersion::V4 | &SignatureVersion::V5 => v4_parser(packet_version, version)(i),
-            _ => invalid_version(i, version),
-        }?;
-        Ok((i, signature))
-    }
+/// Reads and discards exactly `len` bytes.
+fn skip(reader: &mut impl io::Read, len: u64) -> Result<()> {
+    let skipped = io::copy(&mut reader.take(len), &mut io::sink())?;
+    ensure_eq!(skipped, len, "truncated subpacket");
+
+    Ok(())
 }
 
 #[cfg(test)]
@@ -520,4 +626,213 @@ SW+kj0jFDKC2xb/o8hbkTpwPtsoI
         assert!(rc.is_some());
         assert!(matches!(rc.unwrap(), RevocationCode::Other(0x42)));
     }
+
+    /// One subpacket of every type.
+    fn subpacket_samples() -> Vec<SubpacketData> {
+        use crate::ser::Serialize;
+
+        let (key, _) = crate::SignedPublicKey::from_armor_single(
+            std::fs::File::open("./tests/autocrypt/alice@autocrypt.example.pub.asc").unwrap(),
+        )
+        .unwrap();
+        // embedded signatures have no packet header
+        let sig = Signature::from_slice(
+            Version::New,
+            &key.details.users[0].signatures[0].to_bytes().unwrap(),
+        )
+        .unwrap();
+
+        vec![
+            SubpacketData::SignatureCreationTime(Utc.timestamp_opt(1_700_000_000, 0).unwrap()),
+            SubpacketData::SignatureExpirationTime(Duration::seconds(3600)),
+            SubpacketData::KeyExpirationTime(Duration::seconds(86400)),
+            SubpacketData::Issuer(KeyId::from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]).unwrap()),
+            SubpacketData::PreferredSymmetricAlgorithms(smallvec::smallvec![
+                SymmetricKeyAlgorithm::AES256
+            ]),
+            SubpacketData::PreferredHashAlgorithms(smallvec::smallvec![HashAlgorithm::SHA2_512]),
+            SubpacketData::PreferredCompressionAlgorithms(smallvec::smallvec![
+                CompressionAlgorithm::ZLIB
+            ]),
+            SubpacketData::KeyServerPreferences(smallvec::smallvec![0x80]),
+            SubpacketData::KeyFlags(smallvec::smallvec![0x03]),
+            SubpacketData::Features(smallvec::smallvec![0x01]),
+            SubpacketData::RevocationReason(RevocationCode::KeyRetired, "retired".into()),
+            SubpacketData::IsPrimary(true),
+            SubpacketData::Revocable(false),
+            SubpacketData::EmbeddedSignature(Box::new(sig)),
+            SubpacketData::PreferredKeyServer("hkps://keys.example.org".into()),
+            SubpacketData::Notation(Notation {
+                readable: true,
+                name: "test@example.org".into(),
+                value: "value".into(),
+            }),
+            SubpacketData::RevocationKey(RevocationKey::new(
+                RevocationKeyClass::Default,
+                PublicKeyAlgorithm::EdDSA,
+                &[0xAB; 20],
+            )),
+            SubpacketData::SignersUserID("alice@example.org".into()),
+            SubpacketData::PolicyURI("https://example.org/policy".into()),
+            SubpacketData::TrustSignature(1, 120),
+            SubpacketData::RegularExpression("<[^>]+[@.]example\\.org>$".into()),
+            SubpacketData::ExportableCertification(false),
+            SubpacketData::IssuerFingerprint(KeyVersion::V4, SmallVec::from_slice(&[0xCD; 20])),
+            SubpacketData::PreferredAeadAlgorithms(smallvec::smallvec![AeadAlgorithm::Ocb]),
+            SubpacketData::KeyBlock(vec![0xC6, 0x33, 0x04]),
+            // a key block with an unknown reserved octet
+            SubpacketData::Other(38, vec![1, 0xC6, 0x33, 0x04]),
+            SubpacketData::Experimental(101, smallvec::smallvec![1, 2, 3]),
+            SubpacketData::Other(99, vec![4, 5, 6]),
+            SubpacketData::SignatureTarget(
+                PublicKeyAlgorithm::EdDSA,
+                HashAlgorithm::SHA2_256,
+                vec![0xEF; 32],
+            ),
+        ]
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn subpacket_criticality_roundtrip(
+            data in proptest::sample::select(subpacket_samples()),
+            is_critical: bool,
+        ) {
+            use crate::ser::Serialize;
+
+            let subpacket = Subpacket { is_critical, data };
+            let bytes = subpacket.to_bytes().unwrap();
+
+            let (rest, parsed) = subpackets(&bytes).unwrap();
+            proptest::prop_assert!(rest.is_empty());
+            proptest::prop_assert_eq!(parsed.len(), 1);
+            proptest::prop_assert_eq!(parsed[0].is_critical(), is_critical);
+            proptest::prop_assert_eq!(&parsed[0], &subpacket);
+        }
+    }
+
+    #[test]
+    fn test_subpacket_invalid_type() {
+        use crate::ser::Serialize;
+
+        // the type would collide with the critical bit
+        let subpacket = Subpacket::regular(SubpacketData::Other(0x85, vec![1]));
+        assert!(subpacket.to_bytes().is_err());
+    }
+
+    #[test]
+    fn test_large_unhashed_area() {
+        use crate::composed::SignedSecretKey;
+        use crate::ser::Serialize;
+
+        let (key, _) = SignedSecretKey::from_armor_single(
+            std::fs::File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+        )
+        .unwrap();
+        let sig = &key.details.users[0].signatures[0];
+
+        let with_unhashed = |subpacket: Subpacket| {
+            let mut sig = sig.clone();
+            sig.config.unhashed_subpackets.push(subpacket);
+            sig.to_bytes().unwrap()
+        };
+
+        // a pathological signature, with the unhashed area filled by a single large subpacket
+        let bytes = with_unhashed(Subpacket::regular(SubpacketData::Other(
+            100,
+            vec![0xaa; 60 * 1024],
+        )));
+        assert!(bytes.len() > 60 * 1024);
+        // an invalid unhashed subpacket, a creation time of three octets
+        let invalid = with_unhashed(Subpacket::regular(SubpacketData::Other(2, vec![0; 3])));
+
+        // both are dropped, and the signature is kept
+        for bytes in [bytes, invalid] {
+            let parsed = Signature::try_from_reader(Version::New, &bytes[..]).unwrap();
+            assert_eq!(
+                parsed.config.unhashed_subpackets,
+                sig.config.unhashed_subpackets
+            );
+            parsed
+                .verify_certification(
+                    &key.primary_key,
+                    crate::types::Tag::UserId,
+                    &key.details.users[0].id,
+                )
+                .unwrap();
+        }
+
+        let bytes = with_unhashed(Subpacket::regular(SubpacketData::Other(
+            100,
+            vec![0xaa; MAX_UNHASHED_SUBPACKET_SIZE],
+        )));
+        let parsed = Signature::try_from_reader(Version::New, &bytes[..]).unwrap();
+        assert_eq!(
+            parsed.config.unhashed_subpackets.len(),
+            sig.config.unhashed_subpackets.len() + 1
+        );
+        assert!(parsed
+            .config
+            .hashed_subpackets()
+            .eq(sig.config.hashed_subpackets()));
+        parsed
+            .verify_certification(
+                &key.primary_key,
+                crate::types::Tag::UserId,
+                &key.details.users[0].id,
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_hashed_area_preserved() {
+        use crate::ser::Serialize;
+
+        let sig = Signature::new(
+            Version::New,
+            SignatureVersion::V4,
+            SignatureType::Binary,
+            PublicKeyAlgorithm::EdDSA,
+            HashAlgorithm::SHA2_256,
+            [0, 0],
+            vec![Mpi::from_slice(&[1]), Mpi::from_slice(&[2])],
+            vec![Subpacket::regular(SubpacketData::KeyFlags(
+                SmallVec::from_slice(&[0x03]),
+            ))],
+            vec![],
+        );
+        let mut bytes = sig.to_bytes().unwrap();
+        // re-encode the key flags subpacket with a five octet length
+        assert_eq!(&bytes[4..9], &[0, 3, 2, 27, 0x03]);
+        bytes.splice(6..7, [255, 0, 0, 0, 2]);
+        bytes[5] = 7;
+
+        let parsed = Signature::from_slice(Version::New, &bytes).unwrap();
+        assert!(parsed
+            .config
+            .hashed_subpackets()
+            .eq(sig.config.hashed_subpackets()));
+        assert_eq!(parsed.to_bytes().unwrap(), bytes);
+        // the encoding differs
+        assert_ne!(parsed, sig);
+
+        // modifications drop the hashed area
+        let mut modified = parsed.clone();
+        modified.config.hashed_subpackets_mut();
+        assert_eq!(modified, sig);
+        assert_eq!(modified.to_bytes().unwrap(), sig.to_bytes().unwrap());
+
+        let mut modified = parsed.clone();
+        modified
+            .config
+            .hashed_subpackets_mut()
+            .push(Subpacket::regular(SubpacketData::SignatureCreationTime(
+                Utc.timestamp_opt(1, 0).unwrap(),
+            )));
+        let reparsed = Signature::from_slice(Version::New, &modified.to_bytes().unwrap()).unwrap();
+        assert!(reparsed
+            .config
+            .hashed_subpackets()
+            .eq(modified.config.hashed_subpackets()));
+    }
 }
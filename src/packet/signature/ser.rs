@@ -116,6 +116,11 @@ impl Subpacket {
             SubpacketData::PreferredAeadAlgorithms(algs) => {
                 writer.write_all(&algs.iter().map(|&alg| alg.into()).collect::<Vec<_>>())?;
             }
+            SubpacketData::KeyBlock(key) => {
+                // reserved octet
+                writer.write_all(&[0])?;
+                writer.write_all(key)?;
+            }
             SubpacketData::Experimental(_, body) => {
                 writer.write_all(body)?;
             }
@@ -156,7 +161,7 @@ impl Subpacket {
                 (*sig).to_writer(&mut buf)?;
                 buf.len()
             }
-            SubpacketData::PreferredKeyServer(server) => server.chars().count(),
+            SubpacketData::PreferredKeyServer(server) => server.len(),
             SubpacketData::Notation(n) => {
                 // 4 for the flags, 2 for the name length, 2 for the value length, m for the name, n for the value
                 4 + 2 + 2 + n.name.len() + n.value.len()
@@ -172,6 +177,7 @@ impl Subpacket {
             SubpacketData::ExportableCertification(_) => 1,
             SubpacketData::IssuerFingerprint(_, fp) => 1 + fp.len(),
             SubpacketData::PreferredAeadAlgorithms(algs) => algs.len(),
+            SubpacketData::KeyBlock(key) => 1 + key.len(),
             SubpacketData::Experimental(_, body) => body.len(),
             SubpacketData::Other(_, body) => body.len(),
             SubpacketData::SignatureTarget(_, _, hash) => 2 + hash.len(),
@@ -210,6 +216,7 @@ impl Subpacket {
             SubpacketData::ExportableCertification(_) => SubpacketType::ExportableCertification,
             SubpacketData::IssuerFingerprint(_, _) => SubpacketType::IssuerFingerprint,
             SubpacketData::PreferredAeadAlgorithms(_) => SubpacketType::PreferredAead,
+            SubpacketData::KeyBlock(_) => SubpacketType::KeyBlock,
             SubpacketData::Experimental(n, _) => SubpacketType::Experimental(*n),
             SubpacketData::Other(n, _) => SubpacketType::Other(*n),
             SubpacketData::SignatureTarget(_, _, _) => SubpacketType::SignatureTarget,
@@ -219,8 +226,16 @@ impl Subpacket {
 
 impl Serialize for Subpacket {
     fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<()> {
+        let typ = self.typ();
+        // the high bit is the critical bit
+        ensure!(
+            typ.as_u8(false) < 0b1000_0000,
+            "invalid subpacket type {}",
+            typ.as_u8(false)
+        );
+
         write_packet_length(1 + self.body_len()?, writer)?;
-        writer.write_all(&[self.typ().as_u8(self.is_critical)])?;
+        writer.write_all(&[typ.as_u8(self.is_critical)])?;
         self.body_to_writer(writer)?;
 
         Ok(())
@@ -237,18 +252,16 @@ impl SignatureConfig {
             self.typ as u8,
         ])?;
 
-        writer.write_u32::<BigEndian>(
-            self.created
-                .expect("must exist for a v3 signature")
-                .timestamp() as u32,
-        )?;
-
-        writer.write_all(
-            self.issuer
-                .as_ref()
-                .expect("must exist for a v3 signature")
-                .as_ref(),
-        )?;
+        let created = self
+            .created
+            .ok_or_else(|| format_err!("missing creation time for a v3 signature"))?;
+        writer.write_u32::<BigEndian>(created.timestamp() as u32)?;
+
+        let issuer = self
+            .issuer
+            .as_ref()
+            .ok_or_else(|| format_err!("missing issuer for a v3 signature"))?;
+        writer.write_all(issuer.as_ref())?;
         writer.write_all(&[
             // public algorithm
             u8::from(self.pub_alg),
@@ -271,13 +284,9 @@ impl SignatureConfig {
         ])?;
 
         // hashed subpackets
-        let mut hashed_subpackets = Vec::new();
-        for packet in &self.hashed_subpackets {
-            packet.to_writer(&mut hashed_subpackets)?;
-        }
-
-        writer.write_u16::<BigEndian>(hashed_subpackets.len() as u16)?;
-        writer.write_all(&hashed_subpackets)?;
+        let hashed_area = self.hashed_area()?;
+        writer.write_u16::<BigEndian>(hashed_area.len().try_into()?)?;
+        writer.write_all(&hashed_area)?;
 
         // unhashed subpackets
         let mut unhashed_subpackets = Vec::new();
@@ -381,9 +390,412 @@ mod tests {
         test_roundtrip("sig-2-keys-2.sig");
     }
 
+    #[test]
+    fn packet_signature_bytes() {
+        for name in ["sig-1-key-1.sig", "sig-1-key-2.sig"] {
+            let bytes = std::fs::read(Path::new("./tests/openpgp/samplemsgs").join(name)).unwrap();
+            let Packet::Signature(sig) = PacketParser::new(&bytes[..]).next().unwrap().unwrap()
+            else {
+                panic!("expected a signature");
+            };
+
+            // the signature value is encoded at the end of the packet
+            let value = sig.signature_bytes().unwrap();
+            assert!(!value.is_empty());
+            assert!(bytes.ends_with(&value), "{name}");
+        }
+    }
+
+    #[test]
+    fn subpacket_preferred_key_server_len() {
+        // the length counts bytes, not characters
+        let server = Subpacket::regular(SubpacketData::PreferredKeyServer("hkps://ü".into()));
+        let bytes = server.to_bytes().unwrap();
+        assert_eq!(bytes[0] as usize, bytes.len() - 1);
+        assert_eq!(&bytes[2..], "hkps://ü".as_bytes());
+    }
+
+    #[test]
+    fn packet_signature_byte_order() {
+        use crate::crypto::hash::HashAlgorithm;
+        use crate::crypto::public_key::PublicKeyAlgorithm;
+        use crate::packet::SignatureType;
+        use chrono::TimeZone;
+
+        let created = chrono::Utc.timestamp_opt(0x6543_210f, 0).unwrap();
+        let creation_time = Subpacket::regular(SubpacketData::SignatureCreationTime(created));
+        assert_eq!(
+            creation_time.to_bytes().unwrap(),
+            [0x05, 0x02, 0x65, 0x43, 0x21, 0x0f]
+        );
+
+        let config = SignatureConfig::new_v4(
+            SignatureVersion::V4,
+            SignatureType::Binary,
+            PublicKeyAlgorithm::EdDSA,
+            HashAlgorithm::SHA2_256,
+            vec![creation_time],
+            vec![],
+        );
+        assert_eq!(
+            config.trailer(0x0102_0304).unwrap(),
+            [0x04, 0xff, 0x01, 0x02, 0x03, 0x04]
+        );
+    }
+
     // Tries to roundtrip a signature containing a name + E-Mail with complicated multibyte unicode characters
     #[test]
     fn packet_signature_roundtrip_openpgp_with_unicode() {
         test_roundtrip("unicode.sig");
     }
+
+    #[test]
+    fn packet_signature_roundtrip_signers_userid() {
+        use crate::composed::{Deserializable, SignedSecretKey};
+        use crate::crypto::hash::HashAlgorithm;
+        use crate::de::Deserialize;
+        use crate::packet::{SignatureConfig, SignatureType, SignatureVersion};
+        use crate::types::KeyTrait;
+        use chrono::SubsecRound;
+
+        let (key, _) = SignedSecretKey::from_armor_single(
+            File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+        )
+        .unwrap();
+        let data = b"hello world";
+
+        let mut config = SignatureConfig::new_v4(
+            SignatureVersion::V4,
+            SignatureType::Binary,
+            key.algorithm(),
+            HashAlgorithm::SHA2_256,
+            vec![Subpacket::regular(SubpacketData::SignatureCreationTime(
+                chrono::Utc::now().trunc_subsecs(0),
+            ))],
+            vec![Subpacket::regular(SubpacketData::SignersUserID(
+                "unhashed <mallory@example.org>".into(),
+            ))],
+        );
+        config.set_signers_userid("other <alice@example.org>");
+        config.set_signers_userid("Alice Lovelace <alice@autocrypt.example>");
+        assert_eq!(config.unhashed_subpackets().count(), 0);
+
+        let sig = config
+            .sign(&key.primary_key, String::new, &data[..])
+            .unwrap();
+
+        let mut serialized = Vec::new();
+        sig.to_writer(&mut serialized).unwrap();
+        let parsed = Signature::from_slice(Default::default(), &serialized).unwrap();
+
+        assert_eq!(parsed, sig);
+        assert_eq!(
+            parsed.signers_userid().unwrap(),
+            "Alice Lovelace <alice@autocrypt.example>"
+        );
+        parsed.verify(&key.primary_key, &data[..]).unwrap();
+
+        // only the hashed area is trusted
+        let mut config = parsed.config.clone();
+        config.hashed_subpackets_mut().clear();
+        config
+            .unhashed_subpackets
+            .push(Subpacket::regular(SubpacketData::SignersUserID(
+                "unhashed <mallory@example.org>".into(),
+            )));
+        assert_eq!(config.signers_userid(), None);
+    }
+
+    #[test]
+    fn packet_signature_critical_subpackets() {
+        use crate::composed::{Deserializable, SignedSecretKey};
+        use crate::de::Deserialize;
+        use crate::errors::Error;
+        use crate::types::{KeyTrait, Tag};
+
+        let (key, _) = SignedSecretKey::from_armor_single(
+            File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+        )
+        .unwrap();
+        let user = &key.details.users[0];
+
+        let certify = |hashed_subpackets: Vec<Subpacket>| {
+            let mut config = user.signatures[0].config.clone();
+            *config.hashed_subpackets_mut() = hashed_subpackets;
+            let sig = config
+                .sign_certification(&key.primary_key, String::new, Tag::UserId, &user.id)
+                .unwrap();
+
+            let mut serialized = Vec::new();
+            sig.to_writer(&mut serialized).unwrap();
+            let parsed = Signature::from_slice(Default::default(), &serialized).unwrap();
+            assert_eq!(parsed, sig);
+
+            parsed.verify_certification(&key.primary_key, Tag::UserId, &user.id)
+        };
+
+        // known subpackets are honored, regardless of the critical bit
+        let mut hashed: Vec<_> = user.signatures[0]
+            .config
+            .hashed_subpackets()
+            .map(|p| match p.data {
+                SubpacketData::KeyFlags(_)
+                | SubpacketData::Features(_)
+                | SubpacketData::SignatureCreationTime(_)
+                | SubpacketData::IssuerFingerprint(_, _) => Subpacket::critical(p.data.clone()),
+                _ => p.clone(),
+            })
+            .collect();
+        assert!(hashed
+            .iter()
+            .any(|p| p.is_critical && matches!(p.data, SubpacketData::KeyFlags(_))));
+        hashed.push(Subpacket::critical(SubpacketData::Issuer(
+            key.primary_key.key_id(),
+        )));
+        certify(hashed.clone()).unwrap();
+
+        // unknown subpackets are fine, as long as they are not critical
+        hashed.push(Subpacket::regular(SubpacketData::Other(99, vec![1, 2, 3])));
+        certify(hashed.clone()).unwrap();
+
+        hashed.push(Subpacket::critical(SubpacketData::Other(99, vec![1, 2, 3])));
+        let err = certify(hashed).unwrap_err();
+        assert!(matches!(err, Error::Unsupported(_)), "{err:?}");
+    }
+
+    #[test]
+    fn packet_signature_critical_notation() {
+        use chrono::{SubsecRound, Utc};
+
+        use crate::composed::{Deserializable, SignedSecretKey};
+        use crate::crypto::hash::HashAlgorithm;
+        use crate::crypto::public_key::PublicKeyAlgorithm;
+        use crate::de::Deserialize;
+        use crate::errors::Error;
+        use crate::packet::{Notation, SignatureConfigBuilder, SignatureType};
+
+        let (key, _) = SignedSecretKey::from_armor_single(
+            File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+        )
+        .unwrap();
+
+        let sign = |notation: Subpacket| {
+            let sig = SignatureConfigBuilder::default()
+                .typ(SignatureType::Binary)
+                .pub_alg(PublicKeyAlgorithm::EdDSA)
+                .hash_alg(HashAlgorithm::SHA2_256)
+                .hashed_subpackets(vec![
+                    Subpacket::regular(SubpacketData::SignatureCreationTime(
+                        Utc::now().trunc_subsecs(0),
+                    )),
+                    Subpacket::regular(SubpacketData::issuer_fingerprint(&key.primary_key)),
+                    notation,
+                ])
+                .unhashed_subpackets(vec![])
+                .build()
+                .unwrap()
+                .sign(&key.primary_key, String::new, &b"hello"[..])
+                .unwrap();
+            Signature::from_slice(Default::default(), &sig.to_bytes().unwrap()).unwrap()
+        };
+        let notation = SubpacketData::Notation(Notation {
+            readable: true,
+            name: "experiment@example.org".into(),
+            value: "1".into(),
+        });
+
+        // notations that are not critical may be ignored
+        let sig = sign(Subpacket::regular(notation.clone()));
+        sig.verify(&key.primary_key, &b"hello"[..]).unwrap();
+
+        // critical notations are rejected, unless the application knows them
+        let mut sig = sign(Subpacket::critical(notation));
+        let err = sig.verify(&key.primary_key, &b"hello"[..]).unwrap_err();
+        assert!(matches!(err, Error::Unsupported(_)), "{err:?}");
+
+        sig.set_known_notations(["other@example.org"]);
+        assert!(sig.verify(&key.primary_key, &b"hello"[..]).is_err());
+
+        sig.set_known_notations(["experiment@example.org"]);
+        sig.verify(&key.primary_key, &b"hello"[..]).unwrap();
+    }
+
+    #[test]
+    fn packet_signature_criticality_policy() {
+        use chrono::{SubsecRound, Utc};
+
+        use crate::composed::{Deserializable, SignedSecretKey};
+        use crate::crypto::hash::HashAlgorithm;
+        use crate::crypto::public_key::PublicKeyAlgorithm;
+        use crate::de::Deserialize;
+        use crate::packet::{CriticalityPolicy, Notation, SignatureConfigBuilder, SignatureType};
+
+        let (key, _) = SignedSecretKey::from_armor_single(
+            File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+        )
+        .unwrap();
+
+        let notation = SubpacketData::Notation(Notation {
+            readable: true,
+            name: "experiment@example.org".into(),
+            value: "1".into(),
+        });
+        let build = |hashed: Vec<Subpacket>, policy: CriticalityPolicy| {
+            SignatureConfigBuilder::default()
+                .typ(SignatureType::Binary)
+                .pub_alg(PublicKeyAlgorithm::EdDSA)
+                .hash_alg(HashAlgorithm::SHA2_256)
+                .hashed_subpackets(hashed)
+                .unhashed_subpackets(vec![])
+                .criticality_policy(policy)
+                .build()
+        };
+
+        // known subpackets may always be critical
+        let created = Subpacket::critical(SubpacketData::SignatureCreationTime(
+            Utc::now().trunc_subsecs(0),
+        ));
+        build(vec![created.clone()], CriticalityPolicy::Strict).unwrap();
+
+        for data in [
+            notation.clone(),
+            SubpacketData::Experimental(101, smallvec::smallvec![1]),
+            SubpacketData::Other(99, vec![1]),
+        ] {
+            let hashed = vec![created.clone(), Subpacket::critical(data.clone())];
+            build(hashed.clone(), CriticalityPolicy::Permissive).unwrap();
+            assert!(build(hashed, CriticalityPolicy::Strict).is_err());
+
+            let hashed = vec![created.clone(), Subpacket::regular(data)];
+            build(hashed, CriticalityPolicy::Strict).unwrap();
+        }
+
+        // the policy is not part of the signature
+        let config = build(vec![created.clone()], CriticalityPolicy::Strict).unwrap();
+        let sig = config
+            .sign(&key.primary_key, String::new, &b"hello"[..])
+            .unwrap();
+        let parsed = Signature::from_slice(Default::default(), &sig.to_bytes().unwrap()).unwrap();
+        assert_eq!(parsed, sig);
+
+        // and checked again when signing
+        let mut config = build(vec![created], CriticalityPolicy::Strict).unwrap();
+        assert_eq!(config.criticality_policy(), CriticalityPolicy::Strict);
+        config
+            .hashed_subpackets_mut()
+            .push(Subpacket::critical(notation));
+        assert!(config
+            .sign(&key.primary_key, String::new, &b"hello"[..])
+            .is_err());
+    }
+
+    #[test]
+    fn packet_signature_subpacket_criticality() {
+        use chrono::{TimeZone, Utc};
+
+        use crate::composed::{Deserializable, SignedSecretKey};
+        use crate::crypto::hash::HashAlgorithm;
+        use crate::de::Deserialize;
+        use crate::packet::{SignatureConfigBuilder, SignatureType, SubpacketCriticality};
+        use crate::types::KeyTrait;
+
+        let (key, _) = SignedSecretKey::from_armor_single(
+            File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+        )
+        .unwrap();
+        let created = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let build = |criticality: Option<SubpacketCriticality>| {
+            let mut builder = SignatureConfigBuilder::default();
+            builder
+                .typ(SignatureType::Binary)
+                .pub_alg(key.primary_key.algorithm())
+                .hash_alg(HashAlgorithm::SHA2_256)
+                .hashed_subpackets(vec![
+                    Subpacket::regular(SubpacketData::issuer_fingerprint(&key)),
+                    Subpacket::regular(SubpacketData::SignatureCreationTime(created)),
+                    Subpacket::critical(SubpacketData::Features(smallvec::smallvec![0x01])),
+                ])
+                .unhashed_subpackets(vec![Subpacket::regular(SubpacketData::Issuer(
+                    key.key_id(),
+                ))]);
+            if let Some(criticality) = criticality {
+                builder.subpacket_criticality(criticality);
+            }
+            builder.build().unwrap()
+        };
+        let sign = |criticality: Option<SubpacketCriticality>| {
+            let sig = build(criticality)
+                .sign(&key.primary_key, String::new, &b"hello"[..])
+                .unwrap();
+            let bytes = sig.to_bytes().unwrap();
+            let parsed = Signature::from_slice(Default::default(), &bytes).unwrap();
+            assert_eq!(parsed, sig);
+            parsed.verify(&key.primary_key, &b"hello"[..]).unwrap();
+
+            parsed
+                .config
+                .hashed_subpackets()
+                .chain(parsed.config.unhashed_subpackets())
+                .map(|p| (p.typ(), p.is_critical()))
+                .collect::<Vec<_>>()
+        };
+
+        // signed as they are by default
+        let expected = [
+            (SubpacketType::IssuerFingerprint, false),
+            (SubpacketType::SignatureCreationTime, false),
+            (SubpacketType::Features, true),
+            (SubpacketType::Issuer, false),
+        ];
+        assert_eq!(sign(None), expected);
+        assert_eq!(sign(Some(SubpacketCriticality::default())), expected);
+
+        let criticality = SubpacketCriticality {
+            signature_creation_time: true,
+            issuer: true,
+            issuer_fingerprint: true,
+            key_flags: false,
+        };
+        assert_eq!(
+            sign(Some(criticality)),
+            [
+                (SubpacketType::IssuerFingerprint, true),
+                (SubpacketType::SignatureCreationTime, true),
+                (SubpacketType::Features, true),
+                (SubpacketType::Issuer, true),
+            ]
+        );
+
+        // the critical bit is part of the encoded subpacket type
+        let sig = build(Some(criticality))
+            .sign(&key.primary_key, String::new, &b"hello"[..])
+            .unwrap();
+        let hashed_area = sig.config.hashed_area().unwrap();
+        assert_eq!(&hashed_area[..3], &[22, 0x80 | 33, 4]);
+        assert_eq!(&hashed_area[23..25], &[5, 0x80 | 2]);
+    }
+
+    #[test]
+    fn packet_signature_v3_missing_fields() {
+        use crate::crypto::hash::HashAlgorithm;
+        use crate::crypto::public_key::PublicKeyAlgorithm;
+        use crate::types::Version;
+
+        // v3 signatures carry the creation time and issuer outside of subpackets
+        let sig = Signature::new(
+            Version::Old,
+            SignatureVersion::V3,
+            SignatureType::Binary,
+            PublicKeyAlgorithm::RSA,
+            HashAlgorithm::SHA2_256,
+            [0, 0],
+            vec![],
+            vec![],
+            vec![],
+        );
+        assert!(sig.to_bytes().is_err());
+
+        let mut hasher = HashAlgorithm::SHA2_256.new_hasher().unwrap();
+        assert!(sig.config.hash_signature_data(&mut *hasher).is_err());
+    }
 }
@@ -8,7 +8,7 @@ use iter_read::IterRead;
 use num_enum::{FromPrimitive, IntoPrimitive, TryFromPrimitive};
 
 use crate::crypto::aead::AeadAlgorithm;
-use crate::crypto::hash::HashAlgorithm;
+use crate::crypto::hash::{HashAlgorithm, Hasher, MAX_DIGEST_SIZE};
 use crate::crypto::public_key::PublicKeyAlgorithm;
 use crate::crypto::sym::SymmetricKeyAlgorithm;
 use crate::errors::Result;
@@ -18,7 +18,7 @@ use crate::packet::signature::SignatureConfig;
 use crate::packet::PacketTrait;
 use crate::ser::Serialize;
 use crate::types::{
-    self, CompressionAlgorithm, KeyId, KeyVersion, Mpi, PublicKeyTrait, Tag, Version,
+    self, CompressionAlgorithm, KeyId, KeyTrait, KeyVersion, Mpi, PublicKeyTrait, Tag, Version,
 };
 use smallvec::SmallVec;
 
@@ -75,6 +75,73 @@ impl Signature {
         }
     }
 
+    /// Reassembles a v4 or v5 signature from its parts, as stored e.g. by another library.
+    ///
+    /// `hashed_area` holds the encoded hashed subpackets. Nothing is signed or verified, only
+    /// the structure is checked: the hashed area must consist of valid subpackets including a
+    /// signature creation time, and the number of MPIs must match `pub_alg`. The hashed area is
+    /// hashed and serialized as given, so signatures with an unusual encoding still verify.
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_parts(
+        version: SignatureVersion,
+        typ: SignatureType,
+        pub_alg: PublicKeyAlgorithm,
+        hash_alg: HashAlgorithm,
+        hashed_area: &[u8],
+        unhashed_subpackets: Vec<Subpacket>,
+        signed_hash_value: [u8; 2],
+        signature: Vec<Mpi>,
+    ) -> Result<Self> {
+        match version {
+            SignatureVersion::V4 | SignatureVersion::V5 => {}
+            SignatureVersion::V2 | SignatureVersion::V3 => {
+                bail!("{:?} signatures have no subpackets", version)
+            }
+            SignatureVersion::Other(version) => {
+                unsupported_err!("signature version {}", version)
+            }
+        }
+
+        let hashed_subpackets = super::de::hashed_area_subpackets(hashed_area)?;
+        ensure!(
+            hashed_subpackets
+                .iter()
+                .any(|p| matches!(p.data, SubpacketData::SignatureCreationTime(_))),
+            "missing signature creation time in the hashed area"
+        );
+
+        let expected_mpis = match pub_alg {
+            PublicKeyAlgorithm::RSA | PublicKeyAlgorithm::RSASign => Some(1),
+            PublicKeyAlgorithm::DSA | PublicKeyAlgorithm::ECDSA | PublicKeyAlgorithm::EdDSA => {
+                Some(2)
+            }
+            _ => None,
+        };
+        if let Some(expected) = expected_mpis {
+            ensure_eq!(
+                signature.len(),
+                expected,
+                "invalid number of MPIs for {:?}",
+                pub_alg
+            );
+        }
+
+        let mut sig = Signature::new(
+            Default::default(),
+            version,
+            typ,
+            pub_alg,
+            hash_alg,
+            signed_hash_value,
+            signature,
+            hashed_subpackets,
+            unhashed_subpackets,
+        );
+        sig.config.hashed_area = Some(hashed_area.to_vec());
+
+        Ok(sig)
+    }
+
     /// Returns what kind of signature this is.
     pub fn typ(&self) -> SignatureType {
         self.config.typ()
@@ -85,12 +152,21 @@ impl Signature {
         self.config.hash_alg
     }
 
+    /// Returns the encoded signature value, the algorithm specific MPIs at the end of the
+    /// signature packet, following the signed hash value.
+    ///
+    /// Two signatures with the same value are the same signature, so these octets can be hashed
+    /// or compared to identify it.
+    pub fn signature_bytes(&self) -> Result<Vec<u8>> {
+        self.signature.to_bytes()
+    }
+
     /// Does `key` match any issuer or issuer_fingerprint subpacket in `sig`?
     /// If yes, we consider `key` a candidate to verify `sig` against.
     ///
     /// We also consider `key` a match for `sig` by default, if `sig` contains no issuer-related
     /// subpackets.
-    fn match_identity(sig: &Signature, key: &impl PublicKeyTrait) -> bool {
+    pub(crate) fn match_identity(sig: &Signature, key: &impl PublicKeyTrait) -> bool {
         let issuers = sig.issuer();
         let issuer_fps = sig.issuer_fingerprint();
 
@@ -105,11 +181,37 @@ impl Signature {
             || issuer_fps.iter().any(|&fp| fp == key.fingerprint())
     }
 
+    /// Rejects signatures that carry a critical subpacket we do not understand.
+    ///
+    /// See [`CriticalityPolicy::check_verified`]. Only the hashed area is considered, the
+    /// unhashed area is not protected by the signature and can be altered by anyone.
+    fn check_critical_subpackets(&self) -> Result<()> {
+        for packet in self.config.hashed_subpackets() {
+            CriticalityPolicy::check_verified(packet, &self.config.known_notations)?;
+        }
+
+        Ok(())
+    }
+
+    /// Sets the names of the notations the application understands.
+    ///
+    /// A critical notation must make the signature invalid, unless the application knows how
+    /// to handle it. By default no notation is known, so signatures with a critical notation
+    /// fail to verify.
+    pub fn set_known_notations<I, N>(&mut self, names: I)
+    where
+        I: IntoIterator<Item = N>,
+        N: Into<BString>,
+    {
+        self.config.known_notations = names.into_iter().map(Into::into).collect();
+    }
+
     /// Verify this signature.
     pub fn verify<R>(&self, key: &impl PublicKeyTrait, data: R) -> Result<()>
     where
         R: Read,
     {
+        self.check_critical_subpackets()?;
         ensure!(
             Self::match_identity(self, key),
             "verify: No matching issuer or issuer_fingerprint for Key ID: {:?}",
@@ -126,10 +228,65 @@ impl Signature {
         } else {
             self.config.hash_data_to_sign(&mut *hasher, data)?;
         }
-        let len = self.config.hash_signature_data(&mut *hasher)?;
-        hasher.update(&self.config.trailer(len)?);
 
-        let hash = &hasher.finish()[..];
+        self.verify_finish(key, hasher)
+    }
+
+    /// Verify this signature over `data`, diagnosing a trailing newline mismatch on failure.
+    ///
+    /// Signatures cover the exact data, in text mode only line endings are normalized. Data
+    /// that gained or lost a final newline on its way from the signer does not verify. If the
+    /// signature is invalid for `data`, but valid once a trailing newline is added or removed,
+    /// [`Error::TrailingNewlineMismatch`](crate::errors::Error::TrailingNewlineMismatch) is
+    /// returned instead of the original error. The signature is invalid for `data` either way.
+    pub fn verify_with_hints(&self, key: &impl PublicKeyTrait, data: &[u8]) -> Result<()> {
+        let err = match self.verify(key, data) {
+            Ok(()) => return Ok(()),
+            Err(err) => err,
+        };
+
+        let (candidates, signed_with_newline) = match data
+            .strip_suffix(b"\r\n")
+            .or_else(|| data.strip_suffix(b"\n"))
+        {
+            Some(stripped) => (vec![stripped.to_vec()], false),
+            None => (vec![[data, b"\n"].concat(), [data, b"\r\n"].concat()], true),
+        };
+
+        if candidates
+            .iter()
+            .any(|candidate| self.verify(key, &candidate[..]).is_ok())
+        {
+            return Err(crate::errors::Error::TrailingNewlineMismatch {
+                signed_with_newline,
+            });
+        }
+
+        Err(err)
+    }
+
+    /// Verify this signature, using a `hasher` that already consumed the signed data.
+    ///
+    /// This allows hashing the data in a streaming fashion, before the signature is known.
+    /// `hasher` must use the hash algorithm of this signature.
+    pub(crate) fn verify_hashed(
+        &self,
+        key: &impl PublicKeyTrait,
+        hasher: Box<dyn Hasher>,
+    ) -> Result<()> {
+        self.check_critical_subpackets()?;
+        ensure!(
+            Self::match_identity(self, key),
+            "verify: No matching issuer or issuer_fingerprint for Key ID: {:?}",
+            &key.key_id(),
+        );
+
+        self.verify_finish(key, hasher)
+    }
+
+    fn verify_finish(&self, key: &impl PublicKeyTrait, mut hasher: Box<dyn Hasher>) -> Result<()> {
+        let mut buf = [0u8; MAX_DIGEST_SIZE];
+        let hash = self.config.finish_digest_into(&mut *hasher, &mut buf)?;
         ensure_eq!(
             &self.signed_hash_value,
             &hash[0..2],
@@ -149,6 +306,25 @@ impl Signature {
         self.verify_third_party_certification(&key, &key, tag, id)
     }
 
+    /// Verifies a third-party certification and returns its level.
+    ///
+    /// Fails for signatures that are not certifications, including certification revocations,
+    /// which [`Signature::verify_third_party_certification`] accepts.
+    pub fn verify_certification_level(
+        &self,
+        signee: &impl PublicKeyTrait,
+        signer: &impl PublicKeyTrait,
+        tag: Tag,
+        id: &impl Serialize,
+    ) -> Result<CertificationLevel> {
+        let Some(level) = self.certification_level() else {
+            bail!("not a certification: {:?}", self.typ());
+        };
+        self.verify_third_party_certification(signee, signer, tag, id)?;
+
+        Ok(level)
+    }
+
     /// Verifies a certification signature type (for third-party signatures).
     pub fn verify_third_party_certification(
         &self,
@@ -160,6 +336,8 @@ impl Signature {
         let key_id = signee.key_id();
         debug!("verifying certification {:?} {:#?}", key_id, self);
 
+        self.check_critical_subpackets()?;
+
         ensure!(
             Self::match_identity(self, signer),
             "verify_certification: No matching issuer or issuer_fingerprint for Key ID: {:?}",
@@ -206,10 +384,8 @@ impl Signature {
             hasher.update(&packet_buf);
         }
 
-        let len = self.config.hash_signature_data(&mut *hasher)?;
-        hasher.update(&self.config.trailer(len)?);
-
-        let hash = &hasher.finish()[..];
+        let mut buf = [0u8; MAX_DIGEST_SIZE];
+        let hash = self.config.finish_digest_into(&mut *hasher, &mut buf)?;
         ensure_eq!(
             &self.signed_hash_value,
             &hash[0..2],
@@ -256,6 +432,8 @@ impl Signature {
             self, signer, signee, backsig
         );
 
+        self.check_critical_subpackets()?;
+
         let mut hasher = self.config.hash_alg.new_hasher()?;
 
         // Hash the two keys:
@@ -285,10 +463,8 @@ impl Signature {
             hasher.update(&key_buf);
         }
 
-        let len = self.config.hash_signature_data(&mut *hasher)?;
-        hasher.update(&self.config.trailer(len)?);
-
-        let hash = &hasher.finish()[..];
+        let mut buf = [0u8; MAX_DIGEST_SIZE];
+        let hash = self.config.finish_digest_into(&mut *hasher, &mut buf)?;
         ensure_eq!(
             &self.signed_hash_value,
             &hash[0..2],
@@ -302,6 +478,8 @@ impl Signature {
     pub fn verify_key(&self, key: &impl PublicKeyTrait) -> Result<()> {
         debug!("verifying key (revocation): {:#?} - {:#?}", self, key);
 
+        self.check_critical_subpackets()?;
+
         ensure!(
             Self::match_identity(self, key),
             "verify_key: No matching issuer or issuer_fingerprint for Key ID: {:?}",
@@ -317,10 +495,8 @@ impl Signature {
             hasher.update(&key_buf);
         }
 
-        let len = self.config.hash_signature_data(&mut *hasher)?;
-        hasher.update(&self.config.trailer(len)?);
-
-        let hash = &hasher.finish()[..];
+        let mut buf = [0u8; MAX_DIGEST_SIZE];
+        let hash = self.config.finish_digest_into(&mut *hasher, &mut buf)?;
         ensure_eq!(
             &self.signed_hash_value,
             &hash[0..2],
@@ -335,6 +511,17 @@ impl Signature {
         self.config.is_certification()
     }
 
+    /// The level of a User ID certification, `None` for other signatures, including
+    /// certification revocations.
+    pub fn certification_level(&self) -> Option<CertificationLevel> {
+        self.typ().certification_level()
+    }
+
+    /// Returns if the signature is a revocation of a key, subkey or certification.
+    pub fn is_revocation(&self) -> bool {
+        self.config.is_revocation()
+    }
+
     pub fn key_expiration_time(&self) -> Option<&Duration> {
         self.config.hashed_subpackets().find_map(|p| match &p.data {
             SubpacketData::KeyExpirationTime(d) => Some(d),
@@ -361,6 +548,10 @@ impl Signature {
         self.config.issuer_fingerprint()
     }
 
+    pub fn issuer_fingerprint_mismatches(&self) -> Vec<IssuerFingerprintMismatch> {
+        self.config.issuer_fingerprint_mismatches()
+    }
+
     pub fn preferred_symmetric_algs(&self) -> &[SymmetricKeyAlgorithm] {
         self.config
             .hashed_subpackets()
@@ -469,6 +660,53 @@ impl Signature {
             })
     }
 
+    /// Returns all embedded signatures, from the hashed and the unhashed area.
+    ///
+    /// On subkey binding signatures of signing capable subkeys, these are the primary key
+    /// binding signatures ("backsigs") made by the subkey.
+    pub fn embedded_signatures(&self) -> impl Iterator<Item = &Signature> {
+        self.config
+            .hashed_subpackets()
+            .chain(self.config.unhashed_subpackets())
+            .filter_map(|p| match &p.data {
+                SubpacketData::EmbeddedSignature(d) => Some(&**d),
+                _ => None,
+            })
+    }
+
+    /// Verifies the primary key binding signature ("backsig") embedded in this subkey binding
+    /// signature.
+    ///
+    /// With the backsig, a signing capable `subkey` certifies that it belongs to `primary`,
+    /// without it anyone could bind someone else's signing key as their subkey.
+    /// Fails if no embedded primary key binding signature is valid, or if there is none.
+    pub fn verify_backsig(
+        &self,
+        subkey: &impl PublicKeyTrait,
+        primary: &impl PublicKeyTrait,
+    ) -> Result<()> {
+        let mut res = Err(format_err!("missing primary key binding signature"));
+        for backsig in self
+            .embedded_signatures()
+            .filter(|sig| sig.typ() == SignatureType::KeyBinding)
+        {
+            res = backsig.verify_backwards_key_binding(subkey, primary);
+            if res.is_ok() {
+                break;
+            }
+        }
+
+        res
+    }
+
+    /// Returns the serialized certificate of a `Key Block` subpacket in the hashed area.
+    pub fn key_block(&self) -> Option<&[u8]> {
+        self.config.hashed_subpackets().find_map(|p| match &p.data {
+            SubpacketData::KeyBlock(d) => Some(&d[..]),
+            _ => None,
+        })
+    }
+
     pub fn preferred_key_server(&self) -> Option<&str> {
         self.config.hashed_subpackets().find_map(|p| match &p.data {
             SubpacketData::PreferredKeyServer(d) => Some(d.as_str()),
@@ -499,10 +737,7 @@ impl Signature {
     /// using a different encoding. But since the RFC describes every
     /// text as utf-8 it is up to the caller whether to error on non utf-8 data.
     pub fn signers_userid(&self) -> Option<&BStr> {
-        self.config.hashed_subpackets().find_map(|p| match &p.data {
-            SubpacketData::SignersUserID(d) => Some(d.as_ref()),
-            _ => None,
-        })
+        self.config.signers_userid()
     }
 
     pub fn policy_uri(&self) -> Option<&str> {
@@ -653,6 +888,48 @@ pub enum SignatureType {
     ThirdParty = 0x50,
 }
 
+impl SignatureType {
+    /// The certification level of a User ID certification, `None` for other signature types,
+    /// including certification revocations.
+    pub fn certification_level(self) -> Option<CertificationLevel> {
+        match self {
+            SignatureType::CertGeneric => Some(CertificationLevel::Generic),
+            SignatureType::CertPersona => Some(CertificationLevel::Persona),
+            SignatureType::CertCasual => Some(CertificationLevel::Casual),
+            SignatureType::CertPositive => Some(CertificationLevel::Positive),
+            _ => None,
+        }
+    }
+}
+
+/// How well the issuer of a certification checked the claim of identity, given by the
+/// signature types `0x10` to `0x13`.
+///
+/// The levels are not ordered: a generic certification makes no statement, so it is not
+/// weaker than a persona certification.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+pub enum CertificationLevel {
+    /// No particular assertion (`0x10`).
+    Generic,
+    /// No verification of the claim of identity (`0x11`).
+    Persona,
+    /// Casual verification (`0x12`).
+    Casual,
+    /// Substantial verification (`0x13`), used for self-signatures.
+    Positive,
+}
+
+impl From<CertificationLevel> for SignatureType {
+    fn from(level: CertificationLevel) -> Self {
+        match level {
+            CertificationLevel::Generic => SignatureType::CertGeneric,
+            CertificationLevel::Persona => SignatureType::CertPersona,
+            CertificationLevel::Casual => SignatureType::CertCasual,
+            CertificationLevel::Positive => SignatureType::CertPositive,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 /// Available signature subpacket types
 pub enum SubpacketType {
@@ -681,6 +958,7 @@ pub enum SubpacketType {
     EmbeddedSignature,
     IssuerFingerprint,
     PreferredAead,
+    KeyBlock,
     Experimental(u8),
     Other(u8),
 }
@@ -713,6 +991,7 @@ impl SubpacketType {
             SubpacketType::EmbeddedSignature => 32,
             SubpacketType::IssuerFingerprint => 33,
             SubpacketType::PreferredAead => 34,
+            SubpacketType::KeyBlock => 38,
             SubpacketType::Experimental(n) => *n,
             SubpacketType::Other(n) => *n,
         };
@@ -757,6 +1036,7 @@ impl SubpacketType {
             32 => SubpacketType::EmbeddedSignature,
             33 => SubpacketType::IssuerFingerprint,
             34 => SubpacketType::PreferredAead,
+            38 => SubpacketType::KeyBlock,
             100..=110 => SubpacketType::Experimental(n),
             _ => SubpacketType::Other(n),
         };
@@ -787,6 +1067,94 @@ impl Subpacket {
             data,
         }
     }
+
+    /// Returns true if the critical bit is set.
+    pub const fn is_critical(&self) -> bool {
+        self.is_critical
+    }
+}
+
+/// Decides which subpackets may be marked critical when creating a signature.
+///
+/// Implementations must reject signatures with critical subpackets they do not understand, so
+/// marking a subpacket critical that other implementations are unlikely to know about makes the
+/// signature unusable for them.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum CriticalityPolicy {
+    /// Any subpacket may be critical.
+    #[default]
+    Permissive,
+    /// Experimental and unknown subpackets, key blocks and notations must not be critical.
+    Strict,
+}
+
+impl CriticalityPolicy {
+    /// Checks a single subpacket against this policy.
+    pub fn check(self, subpacket: &Subpacket) -> Result<()> {
+        if self == CriticalityPolicy::Permissive || !subpacket.is_critical() {
+            return Ok(());
+        }
+
+        match &subpacket.data {
+            SubpacketData::Experimental(typ, _) | SubpacketData::Other(typ, _) => {
+                bail!("subpacket {} must not be critical", typ)
+            }
+            SubpacketData::Notation(notation) => {
+                bail!("notation {} must not be critical", notation.name)
+            }
+            SubpacketData::KeyBlock(_) => bail!("key blocks must not be critical"),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks a hashed subpacket of a signature that is verified.
+    ///
+    /// Critical subpackets that are not understood make the signature invalid. Known
+    /// subpackets are accepted regardless of their critical bit, notations only if their name
+    /// is in `known_notations`, as rpgp does not interpret any of them.
+    pub fn check_verified(subpacket: &Subpacket, known_notations: &[BString]) -> Result<()> {
+        if !subpacket.is_critical() {
+            return Ok(());
+        }
+
+        match &subpacket.data {
+            SubpacketData::Experimental(typ, _) | SubpacketData::Other(typ, _) => {
+                unsupported_err!("unknown critical subpacket {}", typ)
+            }
+            SubpacketData::Notation(notation) if !known_notations.contains(&notation.name) => {
+                unsupported_err!("unknown critical notation {}", notation.name)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The critical bit of the standard subpackets that are emitted when making signatures.
+///
+/// Implementations differ in which of these they expect to be critical. Setting the bits
+/// allows matching strict verifiers, or reproducing reference test vectors byte for byte.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct SubpacketCriticality {
+    pub signature_creation_time: bool,
+    pub issuer: bool,
+    pub issuer_fingerprint: bool,
+    pub key_flags: bool,
+}
+
+impl SubpacketCriticality {
+    /// Sets the critical bit of the standard subpackets, other subpackets are left as they are.
+    pub fn apply(self, subpackets: &mut [Subpacket]) {
+        for subpacket in subpackets {
+            let is_critical = match subpacket.data {
+                SubpacketData::SignatureCreationTime(_) => self.signature_creation_time,
+                SubpacketData::Issuer(_) => self.issuer,
+                SubpacketData::IssuerFingerprint(_, _) => self.issuer_fingerprint,
+                SubpacketData::KeyFlags(_) => self.key_flags,
+                _ => continue,
+            };
+            subpacket.is_critical = is_critical;
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -821,13 +1189,49 @@ pub enum SubpacketData {
     TrustSignature(u8, u8),
     RegularExpression(BString),
     ExportableCertification(bool),
+    /// The key version and fingerprint of the key issuing the signature.
+    ///
+    /// Both are kept as found, see [`IssuerFingerprintMismatch`] for subpackets where they
+    /// disagree.
     IssuerFingerprint(KeyVersion, SmallVec<[u8; 20]>),
     PreferredAeadAlgorithms(SmallVec<[AeadAlgorithm; 2]>),
+    /// A serialized transferable public key of the signer, so recipients can reply without
+    /// looking it up.
+    ///
+    /// Only key blocks with the reserved octet set to zero are parsed into this variant.
+    /// Ref: https://datatracker.ietf.org/doc/html/draft-ietf-openpgp-rfc4880bis-10#section-5.2.3.33
+    KeyBlock(Vec<u8>),
     Experimental(u8, SmallVec<[u8; 2]>),
     Other(u8, Vec<u8>),
     SignatureTarget(PublicKeyAlgorithm, HashAlgorithm, Vec<u8>),
 }
 
+impl SubpacketData {
+    /// An `IssuerFingerprint` subpacket for `key`, with the version octet of its key version.
+    pub fn issuer_fingerprint(key: &(impl KeyTrait + ?Sized)) -> Self {
+        SubpacketData::IssuerFingerprint(
+            key.key_version(),
+            SmallVec::from_slice(&key.fingerprint()),
+        )
+    }
+}
+
+/// The largest `Key Block` subpacket that is created or parsed, in bytes.
+///
+/// The hashed area of a signature holds at most 64 KiB, so this leaves room for the other
+/// subpackets.
+pub const MAX_KEY_BLOCK_SIZE: usize = 32 * 1024;
+
+/// An `IssuerFingerprint` subpacket whose key version does not match the length of its
+/// fingerprint, such as a V4 version octet in front of a 32 byte fingerprint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IssuerFingerprintMismatch {
+    /// The key version declared by the subpacket.
+    pub version: KeyVersion,
+    /// The fingerprint, as found in the subpacket.
+    pub fingerprint: Vec<u8>,
+}
+
 bitfield! {
     #[derive(Default, PartialEq, Eq, Copy, Clone)]
     pub struct KeyFlags(u8);
@@ -858,6 +1262,60 @@ impl From<KeyFlags> for SmallVec<[u8; 1]> {
     }
 }
 
+/// The features supported by the key holder's implementation, as published in the Features
+/// subpacket.
+///
+/// All octets of the subpacket are kept, so features that are not known here are passed on.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Features(SmallVec<[u8; 1]>);
+
+impl Features {
+    /// Modification detection, using SEIPDv1 packets.
+    pub fn seipd_v1(&self) -> bool {
+        self.bit(0)
+    }
+
+    pub fn set_seipd_v1(&mut self, value: bool) {
+        self.set_bit(0, value);
+    }
+
+    /// AEAD encrypted data, using SEIPDv2 packets.
+    pub fn seipd_v2(&self) -> bool {
+        self.bit(3)
+    }
+
+    pub fn set_seipd_v2(&mut self, value: bool) {
+        self.set_bit(3, value);
+    }
+
+    fn bit(&self, bit: u8) -> bool {
+        self.0.first().is_some_and(|octet| octet & (1 << bit) != 0)
+    }
+
+    fn set_bit(&mut self, bit: u8, value: bool) {
+        if self.0.is_empty() {
+            self.0.push(0);
+        }
+        if value {
+            self.0[0] |= 1 << bit;
+        } else {
+            self.0[0] &= !(1 << bit);
+        }
+    }
+}
+
+impl<'a> From<&'a [u8]> for Features {
+    fn from(other: &'a [u8]) -> Self {
+        Features(SmallVec::from_slice(other))
+    }
+}
+
+impl From<Features> for SmallVec<[u8; 1]> {
+    fn from(features: Features) -> Self {
+        features.0
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Notation {
     pub readable: bool,
@@ -915,6 +1373,8 @@ impl fmt::Debug for Signature {
     }
 }
 
+impl crate::private::Sealed for Signature {}
+
 impl PacketTrait for Signature {
     fn packet_version(&self) -> Version {
         self.packet_version
@@ -927,8 +1387,107 @@ impl PacketTrait for Signature {
 
 #[cfg(test)]
 mod tests {
+    #![allow(clippy::unwrap_used)]
+
     use super::*;
 
+    #[test]
+    fn test_from_parts_roundtrip() {
+        use crate::composed::{Deserializable, SignedSecretKey};
+
+        for file in [
+            "./tests/autocrypt/alice@autocrypt.example.sec.asc",
+            "./tests/unit-tests/hsm/alice-rsa4096.priv",
+        ] {
+            let (key, _) =
+                SignedSecretKey::from_armor_single(std::fs::File::open(file).unwrap()).unwrap();
+
+            let sigs = key
+                .details
+                .users
+                .iter()
+                .flat_map(|u| &u.signatures)
+                .chain(&key.details.direct_signatures)
+                .chain(key.secret_subkeys.iter().flat_map(|k| &k.signatures));
+
+            for sig in sigs {
+                let rebuilt = Signature::from_parts(
+                    sig.config.version,
+                    sig.typ(),
+                    sig.config.pub_alg,
+                    sig.hash_alg(),
+                    &sig.config.hashed_area().unwrap(),
+                    sig.config.unhashed_subpackets().cloned().collect(),
+                    sig.signed_hash_value,
+                    sig.signature.clone(),
+                )
+                .unwrap();
+
+                assert_eq!(rebuilt.config, sig.config);
+                assert_eq!(rebuilt.to_bytes().unwrap(), sig.to_bytes().unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_parts_invalid() {
+        let created = Subpacket::regular(SubpacketData::SignatureCreationTime(
+            DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+        ));
+        let created = created.to_bytes().unwrap();
+        let mpis = vec![Mpi::from_slice(&[1]), Mpi::from_slice(&[2])];
+        let from_parts = |version, hashed: &[u8], mpis| {
+            Signature::from_parts(
+                version,
+                SignatureType::Binary,
+                PublicKeyAlgorithm::EdDSA,
+                HashAlgorithm::SHA2_256,
+                hashed,
+                vec![],
+                [0, 0],
+                mpis,
+            )
+        };
+
+        assert!(from_parts(SignatureVersion::V4, &created, mpis.clone()).is_ok());
+        assert!(from_parts(SignatureVersion::V3, &created, mpis.clone()).is_err());
+        assert!(from_parts(SignatureVersion::V4, &[], mpis.clone()).is_err());
+        assert!(from_parts(SignatureVersion::V4, &created, mpis[..1].to_vec()).is_err());
+        // a truncated subpacket
+        assert!(from_parts(SignatureVersion::V4, &created[..4], mpis.clone()).is_err());
+    }
+
+    #[test]
+    fn test_from_parts_non_canonical() {
+        use crate::de::Deserialize;
+
+        let created = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        // a signature creation time subpacket, with a five octet length
+        let mut hashed_area = vec![255, 0, 0, 0, 5, 2];
+        hashed_area.extend_from_slice(&1_700_000_000u32.to_be_bytes());
+
+        let sig = Signature::from_parts(
+            SignatureVersion::V4,
+            SignatureType::Binary,
+            PublicKeyAlgorithm::EdDSA,
+            HashAlgorithm::SHA2_256,
+            &hashed_area,
+            vec![],
+            [0, 0],
+            vec![Mpi::from_slice(&[1]), Mpi::from_slice(&[2])],
+        )
+        .unwrap();
+        assert_eq!(sig.created(), Some(&created));
+        assert_eq!(&sig.config.hashed_area().unwrap()[..], &hashed_area[..]);
+
+        // the hashed area is written as given, like that of a parsed signature
+        let bytes = sig.to_bytes().unwrap();
+        assert_eq!(&bytes[4..6], &[0, 10]);
+        assert_eq!(&bytes[6..16], &hashed_area[..]);
+        let parsed = Signature::from_slice(Default::default(), &bytes).unwrap();
+        assert_eq!(parsed, sig);
+    }
+
     #[test]
     fn test_keyflags() {
         let flags: KeyFlags = Default::default();
@@ -964,6 +1523,26 @@ mod tests {
         assert_eq!(flags.0, 0x80);
     }
 
+    #[test]
+    fn test_features() {
+        let mut features = Features::default();
+        assert!(!features.seipd_v1());
+        features.set_seipd_v2(true);
+        assert!(features.seipd_v2());
+        assert_eq!(&SmallVec::<[u8; 1]>::from(features)[..], &[0x08]);
+
+        // octets beyond the first one are kept
+        let mut features = Features::from(&[0x01, 0x20, 0x04][..]);
+        assert!(features.seipd_v1());
+        assert!(!features.seipd_v2());
+        features.set_seipd_v1(false);
+        features.set_seipd_v2(true);
+        assert_eq!(
+            &SmallVec::<[u8; 1]>::from(features)[..],
+            &[0x08, 0x20, 0x04]
+        );
+    }
+
     #[test]
     fn test_critical() {
         use SubpacketType::*;
@@ -34,6 +34,8 @@ impl Serialize for SymEncryptedData {
     }
 }
 
+impl crate::private::Sealed for SymEncryptedData {}
+
 impl PacketTrait for SymEncryptedData {
     fn packet_version(&self) -> Version {
         self.packet_version
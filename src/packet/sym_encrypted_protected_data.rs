@@ -11,7 +11,7 @@ use crate::crypto::sym::SymmetricKeyAlgorithm;
 use crate::errors::{Error, IResult, Result};
 use crate::packet::PacketTrait;
 use crate::ser::Serialize;
-use crate::types::{Tag, Version};
+use crate::types::{Cancellation, SessionKey, Tag, Version};
 
 /// Symmetrically Encrypted Integrity Protected Data Packet
 /// https://tools.ietf.org/html/rfc4880.html#section-5.12
@@ -73,7 +73,7 @@ impl SymEncryptedProtectedData {
     }
 
     /// Encrypts the data using the given symmetric key.
-    pub fn encrypt_with_rng<R: CryptoRng + Rng>(
+    pub fn encrypt_with_rng<R: CryptoRng + Rng + ?Sized>(
         rng: &mut R,
         alg: SymmetricKeyAlgorithm,
         key: &[u8],
@@ -87,6 +87,144 @@ impl SymEncryptedProtectedData {
         })
     }
 
+    /// Encrypts the data using the given session key, creating a v2 packet.
+    ///
+    /// The plaintext is split into chunks of `1 << (chunk_size + 6)` octets, each of which is
+    /// encrypted with the given `aead` mode.
+    pub fn encrypt_seipdv2<R: CryptoRng + Rng + ?Sized>(
+        rng: &mut R,
+        sym_alg: SymmetricKeyAlgorithm,
+        aead: AeadAlgorithm,
+        chunk_size: u8,
+        session_key: &[u8],
+        plaintext: &[u8],
+    ) -> Result<Self> {
+        // invalid chunk sizes are rejected when encrypting
+        let chunk_len = expand_chunk_size(chunk_size.min(16)) as usize;
+
+        // Chunks are encrypted independently, so with the `rayon` feature they are encrypted
+        // in parallel, once there is more than one and more than one thread to use.
+        #[cfg(feature = "rayon")]
+        if plaintext.len() > chunk_len && rayon::current_num_threads() > 1 {
+            return Self::encrypt_seipdv2_parallel(
+                rng,
+                sym_alg,
+                aead,
+                chunk_size,
+                session_key,
+                plaintext,
+            );
+        }
+
+        let mut data = Vec::with_capacity(
+            V2_HEADER_LEN + plaintext.len() + (plaintext.len() / chunk_len + 2) * aead.tag_size(),
+        );
+        Self::encrypt_seipdv2_reader(
+            rng,
+            sym_alg,
+            aead,
+            chunk_size,
+            session_key,
+            plaintext,
+            &mut data,
+        )?;
+
+        // the body starts with the version, the algorithms, the chunk size and the salt
+        let salt = data[4..V2_HEADER_LEN].try_into().expect("written");
+        data.drain(..V2_HEADER_LEN);
+
+        Ok(SymEncryptedProtectedData {
+            packet_version: Default::default(),
+            data: Data::V2 {
+                sym_alg,
+                aead,
+                chunk_size,
+                salt,
+                data,
+            },
+        })
+    }
+
+    /// Encrypts the plaintext read from `reader` using the given session key, and writes the
+    /// body of a v2 packet to `writer`.
+    ///
+    /// Works like [`SymEncryptedProtectedData::encrypt_seipdv2`], but holds at most one chunk
+    /// of plaintext in memory. The body is written without the packet header.
+    pub fn encrypt_seipdv2_reader<R: CryptoRng + Rng + ?Sized, I: io::Read, W: io::Write>(
+        rng: &mut R,
+        sym_alg: SymmetricKeyAlgorithm,
+        aead: AeadAlgorithm,
+        chunk_size: u8,
+        session_key: &[u8],
+        mut reader: I,
+        writer: W,
+    ) -> Result<()> {
+        let mut writer =
+            EncryptingWriter::new_v2(rng, writer, sym_alg, aead, chunk_size, session_key)?;
+        io::copy(&mut reader, &mut writer)?;
+        writer.finish()?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "rayon")]
+    fn encrypt_seipdv2_parallel<R: CryptoRng + Rng + ?Sized>(
+        rng: &mut R,
+        sym_alg: SymmetricKeyAlgorithm,
+        aead: AeadAlgorithm,
+        chunk_size: u8,
+        session_key: &[u8],
+        plaintext: &[u8],
+    ) -> Result<Self> {
+        ensure!(chunk_size <= 16, "chunk size {} is too large", chunk_size);
+        aead.check_supported(sym_alg)?;
+        ensure_eq!(
+            session_key.len(),
+            sym_alg.key_size(),
+            "invalid session key length for {:?}",
+            sym_alg
+        );
+
+        let mut salt = [0u8; 32];
+        rng.fill_bytes(&mut salt);
+
+        let (info, message_key, mut nonce) =
+            derive_key(session_key, sym_alg, aead, chunk_size, &salt);
+        let chunk_len = usize::try_from(expand_chunk_size(chunk_size))?;
+
+        let mut data = encrypt_chunks_parallel(
+            sym_alg,
+            aead,
+            &message_key,
+            &nonce,
+            &info,
+            chunk_len,
+            plaintext,
+        )?;
+
+        // The final tag uses the chunk index following the last chunk.
+        let chunk_count = plaintext.len().div_ceil(chunk_len);
+        set_chunk_index(&mut nonce, chunk_count as u64);
+
+        // Associated data is extended with number of plaintext octets.
+        let mut final_info = info.to_vec();
+        final_info.extend_from_slice(&(plaintext.len() as u64).to_be_bytes());
+        let tag =
+            aead.encrypt_in_place(&sym_alg, &message_key, &nonce, &final_info, &mut [][..])?;
+        data.extend_from_slice(&tag);
+
+        Ok(SymEncryptedProtectedData {
+            packet_version: Default::default(),
+            data: Data::V2 {
+                sym_alg,
+                aead,
+                chunk_size,
+                salt,
+                data,
+            },
+        })
+    }
+
     /// Same as [`encrypt_with_rng`], but uses [`thread_rng`] for RNG.
     ///
     /// [`encrypt_with_rng`]: SymEncryptedProtectedData::encrypt_with_rng
@@ -114,17 +252,60 @@ impl SymEncryptedProtectedData {
     }
 
     /// Decrypts the inner data, returning the result.
+    #[deprecated(note = "use SymEncryptedProtectedData::decrypt_with_session_key instead")]
     pub fn decrypt(
         &self,
         session_key: &[u8],
         sym_alg: Option<SymmetricKeyAlgorithm>,
     ) -> Result<Vec<u8>> {
+        #[allow(deprecated)]
+        self.decrypt_cancellable(session_key, sym_alg, &Cancellation::new())
+    }
+
+    /// Decrypts the inner data, like [`SymEncryptedProtectedData::decrypt`], and stops with
+    /// [`Error::Cancelled`] before the next chunk once
+    /// `cancellation` is cancelled.
+    #[deprecated(
+        note = "use SymEncryptedProtectedData::decrypt_with_session_key_cancellable instead"
+    )]
+    pub fn decrypt_cancellable(
+        &self,
+        session_key: &[u8],
+        sym_alg: Option<SymmetricKeyAlgorithm>,
+        cancellation: &Cancellation,
+    ) -> Result<Vec<u8>> {
+        let session_key = match sym_alg {
+            Some(sym_alg) => SessionKey::new(session_key.to_vec(), sym_alg),
+            None => SessionKey::new_v2(session_key.to_vec()),
+        };
+        self.decrypt_with_session_key_cancellable(&session_key, cancellation)
+    }
+
+    /// Decrypts the inner data, returning the result.
+    ///
+    /// SEIPDv1 packets need a session key with a symmetric algorithm. If a session key for a
+    /// SEIPDv2 packet has one, it must match the algorithm of the packet.
+    pub fn decrypt_with_session_key(&self, session_key: &SessionKey) -> Result<Vec<u8>> {
+        self.decrypt_with_session_key_cancellable(session_key, &Cancellation::new())
+    }
+
+    /// Decrypts the inner data, like [`SymEncryptedProtectedData::decrypt_with_session_key`],
+    /// and stops with [`Error::Cancelled`] before the next chunk once `cancellation` is
+    /// cancelled.
+    pub fn decrypt_with_session_key_cancellable(
+        &self,
+        session_key: &SessionKey,
+        cancellation: &Cancellation,
+    ) -> Result<Vec<u8>> {
+        cancellation.check()?;
+
         match &self.data {
             Data::V1 { data } => {
+                let Some(sym_alg) = session_key.sym_alg() else {
+                    bail!("missing symmetric algorithm for a v1 session key");
+                };
                 let mut data = data.clone();
-                let res = sym_alg
-                    .expect("v1")
-                    .decrypt_protected(session_key, &mut data)?;
+                let res = sym_alg.decrypt_protected(session_key.as_bytes(), &mut data)?;
                 Ok(res.to_vec())
             }
             Data::V2 {
@@ -134,52 +315,39 @@ impl SymEncryptedProtectedData {
                 salt,
                 data,
             } => {
-                // Initial key material is the session key.
-                let ikm = session_key;
-
-                // Salt is used.
-                let salt = Some(&salt[..]);
-
-                let info = [
-                    Tag::SymEncryptedProtectedData.encode(), // packet type
-                    0x02,                                    // version
-                    (*sym_alg).into(),
-                    (*aead).into(),
-                    *chunk_size,
-                ];
-
-                let chunk_size = expand_chunk_size(*chunk_size);
-                let hk = hkdf::Hkdf::<Sha256>::new(salt, ikm);
-                let mut okm = [0u8; 42];
-                hk.expand(&info, &mut okm).expect("42");
-                debug!("info: {} - hkdf: {}", hex::encode(info), hex::encode(okm));
-                let message_key = &okm[..sym_alg.key_size()];
-                let raw_iv_len = aead.nonce_size() - 8;
-                let iv = &okm[sym_alg.key_size()..sym_alg.key_size() + raw_iv_len];
-                let mut nonce = vec![0u8; aead.nonce_size()];
-                nonce[..raw_iv_len].copy_from_slice(iv);
-
-                debug!("message_key: {}", hex::encode(message_key));
-                debug!("iv: {}", hex::encode(iv));
-                debug!("nonce: {}", hex::encode(&nonce));
+                if let Some(key_alg) = session_key.sym_alg() {
+                    ensure_eq!(key_alg, *sym_alg, "session key algorithm mismatch");
+                }
+                let session_key = session_key.as_bytes();
+                aead.check_supported(*sym_alg)?;
+                let chunk_len = usize::try_from(expand_chunk_size(*chunk_size))?;
+
+                // Reject lengths that can not be a sequence of chunks followed by the final tag,
+                // before attempting any decryption.
+                check_ciphertext_len(data.len(), chunk_len, aead.tag_size())?;
+
+                let (info, message_key, mut nonce) =
+                    derive_key(session_key, *sym_alg, *aead, *chunk_size, salt);
+                let message_key = &message_key[..];
 
                 let mut data = data.clone();
 
                 debug!(
                     "data {}, chunk_size {} - {}",
                     hex::encode(&data),
-                    chunk_size,
+                    chunk_len,
                     data.len()
                 );
                 let mut out = Vec::new();
-                let chunk_size = usize::try_from(chunk_size)?;
 
                 // There are n chunks, n auth tags + 1 final auth tag
                 let offset = data.len() - aead.tag_size();
                 let (main_chunks, final_auth_tag) = data.split_at_mut(offset);
 
                 let mut chunk_index: u64 = 0;
-                for chunk in main_chunks.chunks_mut(chunk_size + aead.tag_size()) {
+                for chunk in main_chunks.chunks_mut(chunk_len + aead.tag_size()) {
+                    cancellation.check()?;
+
                     let offset = chunk.len() - aead.tag_size();
                     let (chunk, auth_tag) = chunk.split_at_mut(offset);
 
@@ -189,14 +357,14 @@ impl SymEncryptedProtectedData {
                         hex::encode(&auth_tag)
                     );
 
-                    aead.decrypt_in_place(sym_alg, message_key, &nonce, &info, auth_tag, chunk)?;
+                    aead.decrypt_in_place(sym_alg, message_key, &nonce, &info, auth_tag, chunk)
+                        .map_err(|err| tag_error(err, chunk_index))?;
                     debug!("decrypted {}", hex::encode(&chunk));
                     out.extend_from_slice(chunk);
 
                     // Update nonce to include the next chunk index
                     chunk_index += 1;
-                    let l = nonce.len() - 8;
-                    nonce[l..].copy_from_slice(&chunk_index.to_be_bytes());
+                    set_chunk_index(&mut nonce, chunk_index);
                 }
 
                 // verify final auth tag
@@ -218,7 +386,8 @@ impl SymEncryptedProtectedData {
                     &final_info,
                     final_auth_tag,
                     &mut [][..], // encrypts empty string
-                )?;
+                )
+                .map_err(|err| tag_error(err, chunk_index))?;
 
                 Ok(out)
             }
@@ -226,6 +395,247 @@ impl SymEncryptedProtectedData {
     }
 }
 
+/// The error for an authentication tag of a v2 packet that does not verify.
+///
+/// The first tag is checked before any other, so a failure there almost always means a wrong
+/// session key. A failure on a later tag means the key was right, but the data is corrupt.
+/// Errors other than authentication failures, such as unsupported algorithms, are passed on.
+fn tag_error(err: Error, chunk_index: u64) -> Error {
+    match err {
+        Error::Gcm | Error::Eax | Error::Ocb if chunk_index == 0 => Error::WrongSessionKey,
+        Error::Gcm | Error::Eax | Error::Ocb => Error::CiphertextCorrupt { chunk: chunk_index },
+        err => err,
+    }
+}
+
+/// Size of the plaintext buffer of v1 packets, a multiple of all cipher block sizes.
+const V1_BUFFER_SIZE: usize = 8 * 1024;
+
+/// Length of the start of a v2 packet body: version, algorithms, chunk size and salt.
+const V2_HEADER_LEN: usize = 4 + 32;
+
+/// Encrypts everything written to it into the body of a SEIPD packet, holding at most one
+/// chunk of plaintext in memory.
+///
+/// Produces the same body as [`SymEncryptedProtectedData::encrypt_with_rng`] and
+/// [`SymEncryptedProtectedData::encrypt_seipdv2`], but without the packet header, so it can
+/// be framed by a [`PartialBodyWriter`](crate::packet::PartialBodyWriter).
+///
+/// Dropping the writer without calling `finish` results in a truncated packet.
+pub(crate) struct EncryptingWriter<W: io::Write> {
+    writer: W,
+    buffer: Vec<u8>,
+    chunk_len: usize,
+    state: EncryptionState,
+}
+
+enum EncryptionState {
+    V1 {
+        sym_alg: SymmetricKeyAlgorithm,
+        key: Vec<u8>,
+        /// The last block of ciphertext, CFB mode continues from it.
+        iv: Vec<u8>,
+        mdc: sha1::Sha1,
+    },
+    V2 {
+        sym_alg: SymmetricKeyAlgorithm,
+        aead: AeadAlgorithm,
+        info: [u8; 5],
+        message_key: Vec<u8>,
+        nonce: Vec<u8>,
+        chunk_index: u64,
+        /// Number of plaintext octets, authenticated by the final tag.
+        total_len: u64,
+    },
+}
+
+impl<W: io::Write> EncryptingWriter<W> {
+    /// Starts a v1 packet, encrypted with `sym_alg` in CFB mode and protected by a MDC.
+    pub(crate) fn new_v1<R: CryptoRng + Rng + ?Sized>(
+        rng: &mut R,
+        mut writer: W,
+        sym_alg: SymmetricKeyAlgorithm,
+        session_key: &[u8],
+    ) -> Result<Self> {
+        use sha1::Digest;
+
+        let bs = sym_alg.block_size();
+        ensure!(bs > 0, "{:?} can not be used for encryption", sym_alg);
+        writer.write_all(&[0x01])?;
+
+        // random prefix, with the last two octets repeated as quick check
+        let mut buffer = Vec::with_capacity(V1_BUFFER_SIZE + bs + 2);
+        buffer.resize(bs, 0);
+        rng.fill_bytes(&mut buffer);
+        buffer.extend_from_slice(&[buffer[bs - 2], buffer[bs - 1]]);
+        let mut mdc = sha1::Sha1::new();
+        mdc.update(&buffer);
+
+        Ok(EncryptingWriter {
+            writer,
+            buffer,
+            chunk_len: V1_BUFFER_SIZE,
+            state: EncryptionState::V1 {
+                sym_alg,
+                key: session_key.to_vec(),
+                iv: vec![0u8; bs],
+                mdc,
+            },
+        })
+    }
+
+    /// Starts a v2 packet, encrypted in chunks of `1 << (chunk_size + 6)` octets with the
+    /// given `aead` mode.
+    pub(crate) fn new_v2<R: CryptoRng + Rng + ?Sized>(
+        rng: &mut R,
+        mut writer: W,
+        sym_alg: SymmetricKeyAlgorithm,
+        aead: AeadAlgorithm,
+        chunk_size: u8,
+        session_key: &[u8],
+    ) -> Result<Self> {
+        ensure!(chunk_size <= 16, "chunk size {} is too large", chunk_size);
+        aead.check_supported(sym_alg)?;
+        ensure_eq!(
+            session_key.len(),
+            sym_alg.key_size(),
+            "invalid session key length for {:?}",
+            sym_alg
+        );
+
+        let mut salt = [0u8; 32];
+        rng.fill_bytes(&mut salt);
+
+        writer.write_all(&[0x02, sym_alg.into(), aead.into(), chunk_size])?;
+        writer.write_all(&salt)?;
+
+        let (info, message_key, nonce) = derive_key(session_key, sym_alg, aead, chunk_size, &salt);
+        let chunk_len = usize::try_from(expand_chunk_size(chunk_size))?;
+
+        Ok(EncryptingWriter {
+            writer,
+            buffer: Vec::with_capacity(chunk_len),
+            chunk_len,
+            state: EncryptionState::V2 {
+                sym_alg,
+                aead,
+                info,
+                message_key,
+                nonce,
+                chunk_index: 0,
+                total_len: 0,
+            },
+        })
+    }
+
+    /// Encrypts the buffered plaintext and writes it out.
+    fn encrypt_buffer(&mut self) -> Result<()> {
+        match &mut self.state {
+            EncryptionState::V1 {
+                sym_alg, key, iv, ..
+            } => {
+                sym_alg.encrypt_with_iv_regular(key, iv, &mut self.buffer)?;
+                self.writer.write_all(&self.buffer)?;
+                // only whole blocks are encrypted before the final part
+                if let Some(last_block) = self.buffer.len().checked_sub(iv.len()) {
+                    iv.copy_from_slice(&self.buffer[last_block..]);
+                }
+            }
+            EncryptionState::V2 {
+                sym_alg,
+                aead,
+                info,
+                message_key,
+                nonce,
+                chunk_index,
+                total_len,
+            } => {
+                let tag = aead.encrypt_in_place(
+                    sym_alg,
+                    message_key,
+                    nonce,
+                    &info[..],
+                    &mut self.buffer,
+                )?;
+                self.writer.write_all(&self.buffer)?;
+                self.writer.write_all(&tag)?;
+                *total_len += self.buffer.len() as u64;
+
+                // Update nonce to include the next chunk index
+                *chunk_index += 1;
+                set_chunk_index(nonce, *chunk_index);
+            }
+        }
+        self.buffer.clear();
+
+        Ok(())
+    }
+
+    /// Encrypts the remaining data, writes the trailing MDC or authentication tag and returns
+    /// the underlying writer.
+    pub(crate) fn finish(mut self) -> Result<W> {
+        if let EncryptionState::V1 { mdc, .. } = &mut self.state {
+            use sha1::Digest;
+
+            // the MDC packet header is part of the hash
+            self.buffer.extend_from_slice(&[0xD3, 0x14]);
+            mdc.update([0xD3, 0x14]);
+            let checksum = mdc.clone().finalize();
+            self.buffer.extend_from_slice(&checksum);
+        }
+
+        // v2 packets never end in an empty chunk
+        if !self.buffer.is_empty() {
+            self.encrypt_buffer()?;
+        }
+
+        if let EncryptionState::V2 {
+            sym_alg,
+            aead,
+            info,
+            message_key,
+            nonce,
+            total_len,
+            ..
+        } = &self.state
+        {
+            // Associated data is extended with number of plaintext octets.
+            let mut final_info = info.to_vec();
+            final_info.extend_from_slice(&total_len.to_be_bytes());
+            let tag =
+                aead.encrypt_in_place(sym_alg, message_key, nonce, &final_info, &mut [][..])?;
+            self.writer.write_all(&tag)?;
+        }
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
+impl<W: io::Write> io::Write for EncryptingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Only encrypt a full chunk once there is more data, the final chunk is handled
+        // in `finish`.
+        if self.buffer.len() >= self.chunk_len && !buf.is_empty() {
+            self.encrypt_buffer()
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        }
+
+        let len = buf.len().min(self.chunk_len - self.buffer.len());
+        let buf = &buf[..len];
+        if let EncryptionState::V1 { mdc, .. } = &mut self.state {
+            sha1::Digest::update(mdc, buf);
+        }
+        self.buffer.extend_from_slice(buf);
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
 impl Serialize for SymEncryptedProtectedData {
     fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<()> {
         match &self.data {
@@ -250,6 +660,8 @@ impl Serialize for SymEncryptedProtectedData {
     }
 }
 
+impl crate::private::Sealed for SymEncryptedProtectedData {}
+
 impl PacketTrait for SymEncryptedProtectedData {
     fn packet_version(&self) -> Version {
         self.packet_version
@@ -273,6 +685,104 @@ fn expand_chunk_size(s: u8) -> u32 {
     1u32 << (s as u32 + 6)
 }
 
+/// Derives the associated data, the message key and the initial nonce for a v2 packet from
+/// the session key.
+fn derive_key(
+    session_key: &[u8],
+    sym_alg: SymmetricKeyAlgorithm,
+    aead: AeadAlgorithm,
+    chunk_size: u8,
+    salt: &[u8; 32],
+) -> ([u8; 5], Vec<u8>, Vec<u8>) {
+    // Initial key material is the session key.
+    let ikm = session_key;
+
+    // Salt is used.
+    let salt = Some(&salt[..]);
+
+    let info = [
+        Tag::SymEncryptedProtectedData.encode(), // packet type
+        0x02,                                    // version
+        sym_alg.into(),
+        aead.into(),
+        chunk_size,
+    ];
+
+    let hk = hkdf::Hkdf::<Sha256>::new(salt, ikm);
+    let mut okm = [0u8; 42];
+    hk.expand(&info, &mut okm).expect("42");
+    debug!("info: {} - hkdf: {}", hex::encode(info), hex::encode(okm));
+    let message_key = okm[..sym_alg.key_size()].to_vec();
+    let raw_iv_len = aead.nonce_size() - 8;
+    let iv = &okm[sym_alg.key_size()..sym_alg.key_size() + raw_iv_len];
+    let mut nonce = vec![0u8; aead.nonce_size()];
+    nonce[..raw_iv_len].copy_from_slice(iv);
+
+    debug!("message_key: {}", hex::encode(&message_key));
+    debug!("iv: {}", hex::encode(iv));
+    debug!("nonce: {}", hex::encode(&nonce));
+
+    (info, message_key, nonce)
+}
+
+/// Encrypts `plaintext` in chunks of `chunk_len` octets in parallel, each followed by its
+/// authentication tag. `nonce` is the nonce of the first chunk.
+#[cfg(feature = "rayon")]
+fn encrypt_chunks_parallel(
+    sym_alg: SymmetricKeyAlgorithm,
+    aead: AeadAlgorithm,
+    message_key: &[u8],
+    nonce: &[u8],
+    info: &[u8],
+    chunk_len: usize,
+    plaintext: &[u8],
+) -> Result<Vec<u8>> {
+    use rayon::prelude::*;
+
+    let tag_size = aead.tag_size();
+    let chunk_count = plaintext.len().div_ceil(chunk_len);
+    let mut data = vec![0u8; plaintext.len() + chunk_count * tag_size];
+
+    data.par_chunks_mut(chunk_len + tag_size)
+        .zip(plaintext.par_chunks(chunk_len))
+        .enumerate()
+        .try_for_each(|(chunk_index, (out, chunk))| {
+            let mut nonce = nonce.to_vec();
+            set_chunk_index(&mut nonce, chunk_index as u64);
+
+            let (out, out_tag) = out.split_at_mut(chunk.len());
+            out.copy_from_slice(chunk);
+            let tag = aead.encrypt_in_place(&sym_alg, message_key, &nonce, info, out)?;
+            out_tag.copy_from_slice(&tag);
+
+            Ok::<_, Error>(())
+        })?;
+
+    Ok(data)
+}
+
+/// Stores the big endian `chunk_index` in the last 8 octets of `nonce`, after the IV.
+fn set_chunk_index(nonce: &mut [u8], chunk_index: u64) {
+    let l = nonce.len() - 8;
+    nonce[l..].copy_from_slice(&chunk_index.to_be_bytes());
+}
+
+/// Checks that `len` octets of encrypted data can be split into chunks of at most `chunk_size`
+/// octets, each followed by an authentication tag, and the final authentication tag.
+///
+/// Only the last chunk may be shorter, but it must not be empty.
+fn check_ciphertext_len(len: usize, chunk_size: usize, tag_size: usize) -> Result<()> {
+    let Some(chunks_len) = len.checked_sub(tag_size) else {
+        return Err(Error::TruncatedCiphertext);
+    };
+    let last_chunk_len = chunks_len % (chunk_size + tag_size);
+    if last_chunk_len != 0 && last_chunk_len <= tag_size {
+        return Err(Error::TruncatedCiphertext);
+    }
+
+    Ok(())
+}
+
 fn parse() -> impl Fn(&[u8]) -> IResult<&[u8], Data> {
     move |i: &[u8]| {
         let (i, version) = be_u8(i)?;
@@ -282,6 +792,13 @@ fn parse() -> impl Fn(&[u8]) -> IResult<&[u8], Data> {
                 let (i, sym_alg) = map_res(be_u8, SymmetricKeyAlgorithm::try_from)(i)?;
                 let (i, aead) = map_res(be_u8, AeadAlgorithm::try_from)(i)?;
                 let (i, chunk_size) = be_u8(i)?;
+                // RFC 9580 limits the chunk size octet to 16, for chunks of 4 MiB
+                if chunk_size > 16 {
+                    return Err(nom::Err::Error(format_err!(
+                        "chunk size {} is too large",
+                        chunk_size
+                    )));
+                }
                 let (i, salt) = take(32usize)(i)?;
 
                 Ok((
@@ -302,3 +819,455 @@ fn parse() -> impl Fn(&[u8]) -> IResult<&[u8], Data> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use hex_literal::hex;
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+
+    /// Builds a v2 packet, encrypting `plaintext` in chunks of `chunk_size`.
+    fn encrypt_v2(
+        sym_alg: SymmetricKeyAlgorithm,
+        aead: AeadAlgorithm,
+        chunk_size: u8,
+        session_key: &[u8],
+        plaintext: &[u8],
+    ) -> SymEncryptedProtectedData {
+        let mut rng = ChaChaRng::from_seed([0u8; 32]);
+        SymEncryptedProtectedData::encrypt_seipdv2(
+            &mut rng,
+            sym_alg,
+            aead,
+            chunk_size,
+            session_key,
+            plaintext,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_derive_key_rfc9580() {
+        // RFC 9580, A.9. Sample AEAD-EAX Encryption and Decryption
+        let session_key = hex!("3881bafe985412459b86c36f98cb9a5e");
+        let salt = hex!("9ff90e3b321964f3a42913c8dcc6619325015227efb7eaeaa49f04c2e674175d");
+
+        let (info, message_key, mut nonce) = derive_key(
+            &session_key,
+            SymmetricKeyAlgorithm::AES128,
+            AeadAlgorithm::Eax,
+            6,
+            &salt,
+        );
+        assert_eq!(info, hex!("d202070106"));
+        assert_eq!(message_key, hex!("b50422ac1c26be9ddd831d5bbb36b64f"));
+        assert_eq!(nonce, hex!("78b833f2e94a60c00000000000000000"));
+
+        // the chunk index is big endian, independent of the host
+        set_chunk_index(&mut nonce, 1);
+        assert_eq!(nonce, hex!("78b833f2e94a60c00000000000000001"));
+        set_chunk_index(&mut nonce, 0x0102_0304_0506_0708);
+        assert_eq!(nonce, hex!("78b833f2e94a60c00102030405060708"));
+        set_chunk_index(&mut nonce, u64::MAX);
+        assert_eq!(nonce, hex!("78b833f2e94a60c0ffffffffffffffff"));
+
+        // the largest chunks still fit in a 32 bit usize
+        assert_eq!(usize::try_from(expand_chunk_size(16)).unwrap(), 1 << 22);
+    }
+
+    #[test]
+    fn test_decrypt_v2_roundtrip() {
+        let session_key = [7u8; 16];
+
+        for aead in [
+            #[cfg(feature = "aead-eax")]
+            AeadAlgorithm::Eax,
+            AeadAlgorithm::Ocb,
+            #[cfg(feature = "aead-gcm")]
+            AeadAlgorithm::Gcm,
+        ] {
+            for len in [0, 1, 63, 64, 65, 200] {
+                let plaintext: Vec<u8> = (0..len).map(|i| i as u8).collect();
+                let packet = encrypt_v2(
+                    SymmetricKeyAlgorithm::AES128,
+                    aead,
+                    0,
+                    &session_key,
+                    &plaintext,
+                );
+                assert_eq!(
+                    packet
+                        .decrypt_with_session_key(&SessionKey::new_v2(session_key.to_vec()))
+                        .unwrap(),
+                    plaintext
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_tag_error_passes_other_errors() {
+        assert!(matches!(tag_error(Error::Ocb, 0), Error::WrongSessionKey));
+        assert!(matches!(
+            tag_error(Error::Gcm, 2),
+            Error::CiphertextCorrupt { chunk: 2 }
+        ));
+
+        let err = tag_error(Error::Unimplemented("AEAD not supported".into()), 0);
+        assert!(matches!(err, Error::Unimplemented(_)), "{err:?}");
+    }
+
+    #[test]
+    fn test_decrypt_v2_tag_errors() {
+        let session_key = [7u8; 16];
+        let wrong_key = SessionKey::new_v2([8u8; 16].to_vec());
+
+        for aead in [
+            #[cfg(feature = "aead-eax")]
+            AeadAlgorithm::Eax,
+            AeadAlgorithm::Ocb,
+            #[cfg(feature = "aead-gcm")]
+            AeadAlgorithm::Gcm,
+        ] {
+            let chunk_len = 64 + aead.tag_size();
+
+            // 200 octets are 4 chunks, the final tag uses index 4
+            for len in [0, 200] {
+                let plaintext = vec![1u8; len];
+                let packet = encrypt_v2(
+                    SymmetricKeyAlgorithm::AES128,
+                    aead,
+                    0,
+                    &session_key,
+                    &plaintext,
+                );
+                let res = packet.decrypt_with_session_key(&wrong_key);
+                assert!(matches!(res, Err(Error::WrongSessionKey)), "{res:?}");
+
+                let body = packet.to_bytes().unwrap();
+                let mut positions = vec![(body.len() - 1, (len as u64).div_ceil(64))];
+                if len > 0 {
+                    positions.push((V2_HEADER_LEN, 0));
+                    positions.push((V2_HEADER_LEN + chunk_len + 1, 1));
+                    positions.push((body.len() - aead.tag_size() - 1, 3));
+                }
+                for (pos, chunk) in positions {
+                    let mut body = body.clone();
+                    body[pos] ^= 1;
+                    let packet =
+                        SymEncryptedProtectedData::from_slice(Version::New, &body).unwrap();
+                    let res =
+                        packet.decrypt_with_session_key(&SessionKey::new_v2(session_key.to_vec()));
+                    if chunk == 0 {
+                        // a modified first chunk looks the same as a wrong key
+                        assert!(matches!(res, Err(Error::WrongSessionKey)), "{res:?}");
+                    } else {
+                        assert!(
+                            matches!(res, Err(Error::CiphertextCorrupt { chunk: c }) if c == chunk),
+                            "{aead:?} {len} {pos}: {res:?}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_encrypt_seipdv2_reader() {
+        /// Hands out one octet per read.
+        struct Trickle<'a>(&'a [u8]);
+
+        impl io::Read for Trickle<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                let len = self.0.len().min(buf.len()).min(1);
+                buf[..len].copy_from_slice(&self.0[..len]);
+                self.0 = &self.0[len..];
+                Ok(len)
+            }
+        }
+
+        let session_key = [7u8; 16];
+
+        for aead in [
+            #[cfg(feature = "aead-eax")]
+            AeadAlgorithm::Eax,
+            AeadAlgorithm::Ocb,
+            #[cfg(feature = "aead-gcm")]
+            AeadAlgorithm::Gcm,
+        ] {
+            for len in 0..=128 {
+                let plaintext: Vec<u8> = (0..len).map(|i| i as u8).collect();
+                let mut body = Vec::new();
+                SymEncryptedProtectedData::encrypt_seipdv2_reader(
+                    &mut ChaChaRng::from_seed([0u8; 32]),
+                    SymmetricKeyAlgorithm::AES128,
+                    aead,
+                    0,
+                    &session_key,
+                    Trickle(&plaintext),
+                    &mut body,
+                )
+                .unwrap();
+
+                let packet = encrypt_v2(
+                    SymmetricKeyAlgorithm::AES128,
+                    aead,
+                    0,
+                    &session_key,
+                    &plaintext,
+                );
+                assert_eq!(packet.to_bytes().unwrap(), body, "{aead:?} {len}");
+
+                let packet = SymEncryptedProtectedData::from_slice(Version::New, &body).unwrap();
+                assert_eq!(
+                    packet
+                        .decrypt_with_session_key(&SessionKey::new_v2(session_key.to_vec()))
+                        .unwrap(),
+                    plaintext
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_encrypt_seipdv2_parallel_matches_serial() {
+        let session_key = [7u8; 16];
+
+        for aead in [
+            #[cfg(feature = "aead-eax")]
+            AeadAlgorithm::Eax,
+            AeadAlgorithm::Ocb,
+            #[cfg(feature = "aead-gcm")]
+            AeadAlgorithm::Gcm,
+        ] {
+            for len in [0, 1, 64, 65, 1000] {
+                let plaintext: Vec<u8> = (0..len).map(|i| i as u8).collect();
+                let parallel = SymEncryptedProtectedData::encrypt_seipdv2_parallel(
+                    &mut ChaChaRng::from_seed([0u8; 32]),
+                    SymmetricKeyAlgorithm::AES128,
+                    aead,
+                    0,
+                    &session_key,
+                    &plaintext,
+                )
+                .unwrap();
+
+                let mut serial = Vec::new();
+                SymEncryptedProtectedData::encrypt_seipdv2_reader(
+                    &mut ChaChaRng::from_seed([0u8; 32]),
+                    SymmetricKeyAlgorithm::AES128,
+                    aead,
+                    0,
+                    &session_key,
+                    &plaintext[..],
+                    &mut serial,
+                )
+                .unwrap();
+                assert_eq!(parallel.to_bytes().unwrap(), serial, "{aead:?} {len}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_decrypt_session_key_alg() {
+        let mut rng = ChaChaRng::from_seed([0u8; 32]);
+        let session_key = [7u8; 16];
+        let v1 = SymEncryptedProtectedData::encrypt_with_rng(
+            &mut rng,
+            SymmetricKeyAlgorithm::AES128,
+            &session_key,
+            b"hello",
+        )
+        .unwrap();
+        let v2 = encrypt_v2(
+            SymmetricKeyAlgorithm::AES128,
+            AeadAlgorithm::Ocb,
+            0,
+            &session_key,
+            b"hello",
+        );
+
+        let key = SessionKey::new(session_key.to_vec(), SymmetricKeyAlgorithm::AES128);
+        assert_eq!(v1.decrypt_with_session_key(&key).unwrap(), b"hello");
+        assert_eq!(v2.decrypt_with_session_key(&key).unwrap(), b"hello");
+
+        // v1 packets do not name their algorithm
+        let key = SessionKey::new_v2(session_key.to_vec());
+        assert!(v1.decrypt_with_session_key(&key).is_err());
+
+        let key = SessionKey::new(session_key.to_vec(), SymmetricKeyAlgorithm::AES256);
+        assert!(v2.decrypt_with_session_key(&key).is_err());
+
+        #[allow(deprecated)]
+        {
+            let res = v1.decrypt(&session_key, Some(SymmetricKeyAlgorithm::AES128));
+            assert_eq!(res.unwrap(), b"hello");
+            assert_eq!(v2.decrypt(&session_key, None).unwrap(), b"hello");
+        }
+    }
+
+    #[test]
+    fn test_decrypt_v2_truncated() {
+        let session_key = [7u8; 16];
+        // one full chunk of 64 octets and a last chunk of 2 octets
+        let plaintext = [42u8; 66];
+
+        let packet = encrypt_v2(
+            SymmetricKeyAlgorithm::AES128,
+            AeadAlgorithm::Ocb,
+            0,
+            &session_key,
+            &plaintext,
+        );
+        let Data::V2 { data, .. } = &packet.data else {
+            unreachable!()
+        };
+
+        let truncate = |cut: usize| {
+            let mut truncated = packet.clone();
+            if let Data::V2 { data, .. } = &mut truncated.data {
+                data.truncate(data.len() - cut);
+            }
+            truncated
+                .decrypt_with_session_key(&SessionKey::new_v2(session_key.to_vec()))
+                .unwrap_err()
+        };
+
+        // leaves no room for the last chunk and the final tag
+        for cut in [3, 10, 17, data.len() - 1, data.len()] {
+            let err = truncate(cut);
+            assert!(matches!(err, Error::TruncatedCiphertext), "{cut}: {err:?}");
+        }
+
+        // structurally valid, but the authentication fails
+        assert!(matches!(truncate(1), Error::CiphertextCorrupt { .. }));
+    }
+
+    #[test]
+    fn test_parse_v2_chunk_size_too_large() {
+        let mut packet = vec![0x02, 0x07, 0x02, 16];
+        packet.extend_from_slice(&[0u8; 32 + 16]);
+        assert!(SymEncryptedProtectedData::from_slice(Version::New, &packet).is_ok());
+
+        for chunk_size in [17, 26, 0xFF] {
+            packet[3] = chunk_size;
+            let err = SymEncryptedProtectedData::from_slice(Version::New, &packet).unwrap_err();
+            assert!(matches!(err, Error::Message(_)), "{chunk_size}: {err:?}");
+        }
+    }
+
+    #[test]
+    fn test_decrypt_v2_unsupported_aead() {
+        let session_key = [7u8; 16];
+        let packet = encrypt_v2(
+            SymmetricKeyAlgorithm::AES128,
+            AeadAlgorithm::Ocb,
+            0,
+            &session_key,
+            b"hello",
+        );
+        let mut bytes = packet.to_bytes().unwrap();
+
+        for (sym_alg, aead, msg) in [
+            (7, 99, "AEAD algorithm 99 is not supported"),
+            (7, 100, "AEAD algorithm 100 is not supported"),
+            (8, 2, "only supported with AES128 and AES256, not AES192"),
+        ] {
+            bytes[1] = sym_alg;
+            bytes[2] = aead;
+
+            // the packet still parses, so the message can be inspected
+            let packet = SymEncryptedProtectedData::from_slice(Version::New, &bytes).unwrap();
+            let Data::V2 { aead: parsed, .. } = &packet.data else {
+                panic!("expected a v2 packet");
+            };
+            assert_eq!(u8::from(*parsed), aead);
+
+            let err = packet
+                .decrypt_with_session_key(&SessionKey::new_v2(session_key.to_vec()))
+                .unwrap_err();
+            assert!(
+                matches!(&err, Error::Unsupported(err) if err.contains(msg)),
+                "{err:?}"
+            );
+        }
+
+        let mut rng = ChaChaRng::from_seed([0u8; 32]);
+        assert!(SymEncryptedProtectedData::encrypt_seipdv2(
+            &mut rng,
+            SymmetricKeyAlgorithm::AES128,
+            AeadAlgorithm::Other(99),
+            0,
+            &session_key,
+            b"hello",
+        )
+        .is_err());
+    }
+
+    /// Writes `plaintext` through an [`EncryptingWriter`] in uneven pieces.
+    fn write_in_pieces(mut writer: EncryptingWriter<Vec<u8>>, plaintext: &[u8]) -> Vec<u8> {
+        use std::io::Write;
+
+        for piece in plaintext.chunks(1000) {
+            writer.write_all(piece).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn test_encrypting_writer_matches_packet() {
+        let session_key = [7u8; 16];
+        let sym_alg = SymmetricKeyAlgorithm::AES128;
+
+        for len in [0, 1, 63, 64, 65, 8000, V1_BUFFER_SIZE, 20_000] {
+            let plaintext: Vec<u8> = (0..len).map(|i| i as u8).collect();
+
+            let mut rng = ChaChaRng::from_seed([0u8; 32]);
+            let expected = SymEncryptedProtectedData::encrypt_with_rng(
+                &mut rng,
+                sym_alg,
+                &session_key,
+                &plaintext,
+            )
+            .unwrap();
+            let mut rng = ChaChaRng::from_seed([0u8; 32]);
+            let writer =
+                EncryptingWriter::new_v1(&mut rng, Vec::new(), sym_alg, &session_key).unwrap();
+            assert_eq!(
+                write_in_pieces(writer, &plaintext),
+                expected.to_bytes().unwrap(),
+                "v1 {len}"
+            );
+
+            for chunk_size in [0, 4] {
+                let expected = encrypt_v2(
+                    sym_alg,
+                    AeadAlgorithm::Ocb,
+                    chunk_size,
+                    &session_key,
+                    &plaintext,
+                );
+                let mut rng = ChaChaRng::from_seed([0u8; 32]);
+                let writer = EncryptingWriter::new_v2(
+                    &mut rng,
+                    Vec::new(),
+                    sym_alg,
+                    AeadAlgorithm::Ocb,
+                    chunk_size,
+                    &session_key,
+                )
+                .unwrap();
+                assert_eq!(
+                    write_in_pieces(writer, &plaintext),
+                    expected.to_bytes().unwrap(),
+                    "v2 {len} {chunk_size}"
+                );
+            }
+        }
+    }
+}
@@ -3,6 +3,7 @@ use std::io;
 use nom::bytes::streaming::take;
 use nom::combinator::map_res;
 use nom::number::streaming::be_u8;
+use rand::{CryptoRng, Rng};
 use sha2::Sha256;
 
 use crate::crypto::aead::AeadAlgorithm;
@@ -223,6 +224,61 @@ impl SymKeyEncryptedSessionKey {
             encrypted_key: Some(encrypted_key),
         })
     }
+
+    /// Encrypts the `session_key` of a SEIPDv2 packet with the password, into a v6 packet.
+    ///
+    /// The key encryption key is derived from the password with `s2k` and HKDF, the session
+    /// key is then encrypted with `aead`.
+    pub fn encrypt_v6<R, F>(
+        rng: &mut R,
+        msg_pw: F,
+        session_key: &[u8],
+        s2k: StringToKey,
+        sym_algorithm: SymmetricKeyAlgorithm,
+        aead: AeadAlgorithm,
+    ) -> Result<Self>
+    where
+        R: CryptoRng + Rng,
+        F: FnOnce() -> String + Clone,
+    {
+        ensure!(
+            s2k.uses_salt(),
+            "Can not use an s2k algorithm without a salt: {:?}",
+            s2k
+        );
+        aead.check_supported(sym_algorithm)?;
+
+        let key = s2k.derive_key(&msg_pw(), sym_algorithm.key_size())?;
+
+        let info = [
+            Tag::SymKeyEncryptedSessionKey.encode(), // packet type
+            0x06,                                    // version
+            sym_algorithm.into(),
+            aead.into(),
+        ];
+
+        let hk = hkdf::Hkdf::<Sha256>::new(None, &key);
+        let mut okm = vec![0u8; sym_algorithm.key_size()];
+        hk.expand(&info, &mut okm)
+            .map_err(|_| format_err!("invalid key size {}", okm.len()))?;
+
+        let mut iv = vec![0u8; aead.iv_size()];
+        rng.fill_bytes(&mut iv);
+
+        let mut encrypted_key = session_key.to_vec();
+        let auth_tag =
+            aead.encrypt_in_place(&sym_algorithm, &okm, &iv, &info, &mut encrypted_key)?;
+
+        Ok(SymKeyEncryptedSessionKey::V6 {
+            packet_version: Default::default(),
+            sym_algorithm,
+            s2k,
+            aead,
+            iv,
+            auth_tag,
+            encrypted_key,
+        })
+    }
 }
 
 fn parse(packet_version: Version) -> impl Fn(&[u8]) -> IResult<&[u8], SymKeyEncryptedSessionKey> {
@@ -385,6 +441,8 @@ impl Serialize for SymKeyEncryptedSessionKey {
     }
 }
 
+impl crate::private::Sealed for SymKeyEncryptedSessionKey {}
+
 impl PacketTrait for SymKeyEncryptedSessionKey {
     fn packet_version(&self) -> Version {
         match self {
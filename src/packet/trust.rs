@@ -30,6 +30,8 @@ impl Serialize for Trust {
     }
 }
 
+impl crate::private::Sealed for Trust {}
+
 impl PacketTrait for Trust {
     fn packet_version(&self) -> Version {
         self.packet_version
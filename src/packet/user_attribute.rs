@@ -4,13 +4,15 @@ use chrono::{SubsecRound, Utc};
 
 use byteorder::{LittleEndian, WriteBytesExt};
 use nom::bytes::streaming::take;
-use nom::combinator::{map, map_parser, rest};
+use nom::combinator::{map, map_opt, map_parser, rest, verify};
 use nom::multi::length_data;
 use nom::number::streaming::{be_u8, le_u16};
 use nom::sequence::pair;
 
 use crate::errors::{IResult, Result};
-use crate::packet::{PacketTrait, Signature, SignatureConfigBuilder, SignatureType, Subpacket};
+use crate::packet::{
+    PacketTrait, Signature, SignatureConfigBuilder, SignatureType, Subpacket, SubpacketCriticality,
+};
 use crate::ser::Serialize;
 use crate::types::{PublicKeyTrait, SecretKeyTrait, SignedUserAttribute, Tag, Version};
 use crate::util::{packet_length, write_packet_length};
@@ -48,11 +50,16 @@ impl UserAttribute {
         }
     }
 
+    /// The size of the packet body, in bytes.
     pub fn packet_len(&self) -> usize {
         match self {
-            UserAttribute::Image { ref data, .. } => {
-                // typ + image header + data length
-                1 + 16 + data.len()
+            UserAttribute::Image {
+                ref header,
+                ref data,
+                ..
+            } => {
+                // typ + image header length + image header + data length
+                1 + 2 + header.len() + data.len()
             }
             UserAttribute::Unknown { ref data, .. } => {
                 // typ + data length
@@ -61,15 +68,29 @@ impl UserAttribute {
         }
     }
 
-    /// Create a self-signature
+    /// Create a self-signature, a positive certification.
     pub fn sign<F>(&self, key: &impl SecretKeyTrait, key_pw: F) -> Result<SignedUserAttribute>
     where
         F: FnOnce() -> String,
     {
-        self.sign_third_party(key, key_pw, key)
+        self.sign_with_criticality(key, key_pw, None)
+    }
+
+    /// Create a self-signature, setting the critical bit of the standard subpackets if
+    /// `criticality` is given.
+    pub(crate) fn sign_with_criticality<F>(
+        &self,
+        key: &impl SecretKeyTrait,
+        key_pw: F,
+        criticality: Option<SubpacketCriticality>,
+    ) -> Result<SignedUserAttribute>
+    where
+        F: FnOnce() -> String,
+    {
+        self.certify(key, key_pw, key, SignatureType::CertPositive, criticality)
     }
 
-    /// Create a third-party signature
+    /// Create a third-party signature, a generic certification.
     pub fn sign_third_party<F>(
         &self,
         signer: &impl SecretKeyTrait,
@@ -79,17 +100,35 @@ impl UserAttribute {
     where
         F: FnOnce() -> String,
     {
-        let config = SignatureConfigBuilder::default()
-            .typ(SignatureType::CertGeneric)
+        self.certify(signer, signer_pw, signee, SignatureType::CertGeneric, None)
+    }
+
+    fn certify<F>(
+        &self,
+        signer: &impl SecretKeyTrait,
+        signer_pw: F,
+        signee: &impl PublicKeyTrait,
+        typ: SignatureType,
+        criticality: Option<SubpacketCriticality>,
+    ) -> Result<SignedUserAttribute>
+    where
+        F: FnOnce() -> String,
+    {
+        let mut config = SignatureConfigBuilder::default()
+            .typ(typ)
             .pub_alg(signer.algorithm())
             .hash_alg(signer.hash_alg())
-            .hashed_subpackets(vec![Subpacket::regular(
-                SubpacketData::SignatureCreationTime(Utc::now().trunc_subsecs(0)),
-            )])
+            .hashed_subpackets(vec![
+                Subpacket::regular(SubpacketData::SignatureCreationTime(
+                    Utc::now().trunc_subsecs(0),
+                )),
+                Subpacket::regular(SubpacketData::issuer_fingerprint(&signer)),
+            ])
             .unhashed_subpackets(vec![Subpacket::regular(SubpacketData::Issuer(
                 signer.key_id(),
             ))])
             .build()?;
+        config.subpacket_criticality = criticality;
 
         let sig =
             config.sign_certification_third_party(signer, signer_pw, signee, self.tag(), &self)?;
@@ -120,7 +159,7 @@ fn image(packet_version: Version) -> impl Fn(&[u8]) -> IResult<&[u8], UserAttrib
         map(
             pair(
                 // little endian, for historical reasons..
-                length_data(map(le_u16, |l| l - 2)),
+                length_data(map_opt(le_u16, |l| l.checked_sub(2))),
                 // the actual image is the rest
                 rest,
             ),
@@ -135,7 +174,8 @@ fn image(packet_version: Version) -> impl Fn(&[u8]) -> IResult<&[u8], UserAttrib
 
 fn parse(packet_version: Version) -> impl Fn(&[u8]) -> IResult<&[u8], UserAttribute> {
     move |i: &[u8]| {
-        let (i, len) = packet_length(i)?;
+        // the length includes the type octet
+        let (i, len) = verify(packet_length, |len| *len > 0)(i)?;
         let (i, typ) = be_u8(i)?;
         let (i, attr) = map_parser(take(len - 1), |i| match typ {
             1 => image(packet_version)(i),
@@ -201,6 +241,8 @@ impl fmt::Debug for UserAttribute {
     }
 }
 
+impl crate::private::Sealed for UserAttribute {}
+
 impl PacketTrait for UserAttribute {
     fn packet_version(&self) -> Version {
         match self {
@@ -213,3 +255,39 @@ impl PacketTrait for UserAttribute {
         Tag::UserAttribute
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+
+    #[test]
+    fn test_image_header_len() {
+        // an image header of 18 bytes, instead of the usual 16
+        let mut body = vec![0x01, 0x14, 0x00, 0x01, 0x01];
+        body.extend_from_slice(&[0; 16]);
+        body.extend_from_slice(b"image");
+        let mut input = Vec::new();
+        write_packet_length(body.len(), &mut input).unwrap();
+        input.extend_from_slice(&body);
+
+        let attr = UserAttribute::from_slice(Version::New, &input).unwrap();
+        let UserAttribute::Image { header, data, .. } = &attr else {
+            panic!("expected an image");
+        };
+        assert_eq!(header.len(), 18);
+        assert_eq!(data, b"image");
+
+        assert_eq!(attr.packet_len(), body.len());
+        assert_eq!(attr.to_bytes().unwrap(), input);
+    }
+
+    #[test]
+    fn test_invalid_lengths() {
+        // image header length smaller than its own size
+        assert!(UserAttribute::from_slice(Version::New, &[0x04, 0x01, 0x01, 0x00, 0x01]).is_err());
+        // empty subpacket, without a type
+        assert!(UserAttribute::from_slice(Version::New, &[0x00, 0x01]).is_err());
+    }
+}
@@ -38,26 +38,44 @@ impl UserId {
         self.id.as_ref()
     }
 
-    /// Create a self-signature
+    /// The size of the packet body, in bytes.
+    pub fn packet_len(&self) -> usize {
+        self.id.len()
+    }
+
+    /// Create a self-signature, a positive certification.
     pub fn sign<F>(&self, key: &impl SecretKeyTrait, key_pw: F) -> Result<SignedUser>
     where
         F: FnOnce() -> String,
     {
-        self.sign_third_party(key, key_pw, key)
+        self.certify(key, key_pw, key, SignatureType::CertPositive)
     }
 
-    /// Create a third-party signature
+    /// Create a third-party signature, a generic certification.
     pub fn sign_third_party<F>(
         &self,
         signer: &impl SecretKeyTrait,
         signer_pw: F,
         signee: &impl PublicKeyTrait,
     ) -> Result<SignedUser>
+    where
+        F: FnOnce() -> String,
+    {
+        self.certify(signer, signer_pw, signee, SignatureType::CertGeneric)
+    }
+
+    fn certify<F>(
+        &self,
+        signer: &impl SecretKeyTrait,
+        signer_pw: F,
+        signee: &impl PublicKeyTrait,
+        typ: SignatureType,
+    ) -> Result<SignedUser>
     where
         F: FnOnce() -> String,
     {
         let config = SignatureConfigBuilder::default()
-            .typ(SignatureType::CertGeneric)
+            .typ(typ)
             .pub_alg(signer.algorithm())
             .hash_alg(signer.hash_alg())
             .hashed_subpackets(vec![Subpacket::regular(
@@ -93,6 +111,8 @@ impl fmt::Display for UserId {
     }
 }
 
+impl crate::private::Sealed for UserId {}
+
 impl PacketTrait for UserId {
     fn packet_version(&self) -> Version {
         self.packet_version
@@ -121,14 +141,15 @@ mod tests {
             .unwrap();
 
         let alice_sec = packet::SecretKey {
-            details: packet::PublicKey {
-                packet_version: Version::New,
-                version: KeyVersion::V4,
-                algorithm: key_type.to_alg(),
-                created_at: Utc::now().trunc_subsecs(0),
-                expiration: None,
+            details: packet::PublicKey::new(
+                Version::New,
+                KeyVersion::V4,
+                key_type.to_alg(),
+                Utc::now().trunc_subsecs(0),
+                None,
                 public_params,
-            },
+            )
+            .unwrap(),
             secret_params,
         };
 
@@ -148,14 +169,15 @@ mod tests {
             .unwrap();
 
         let signer_sec = packet::SecretKey {
-            details: packet::PublicKey {
-                packet_version: Version::New,
-                version: KeyVersion::V4,
-                algorithm: key_type.to_alg(),
-                created_at: Utc::now().trunc_subsecs(0),
-                expiration: None,
+            details: packet::PublicKey::new(
+                Version::New,
+                KeyVersion::V4,
+                key_type.to_alg(),
+                Utc::now().trunc_subsecs(0),
+                None,
                 public_params,
-            },
+            )
+            .unwrap(),
             secret_params,
         };
 
@@ -0,0 +1,29 @@
+//! # Prelude
+//!
+//! The supported surface of the crate for the common tasks: generating and parsing keys,
+//! building, encrypting, signing and verifying messages.
+//!
+//! ```rust
+//! use pgp::prelude::*;
+//! ```
+//!
+//! The modules behind these re-exports may be reorganized, importing from the prelude keeps
+//! code working when they are.
+
+pub use crate::composed::cleartext::CleartextSignedMessage;
+pub use crate::composed::{decrypt_with_password, encrypt_with_password};
+pub use crate::composed::{
+    ArmorOptions, Deserializable, EncryptionPurpose, KeyType, Message, MessageBuilder,
+    SecretKeyParams, SecretKeyParamsBuilder, SignedPublicKey, SignedPublicSubKey, SignedSecretKey,
+    SignedSecretSubKey, StandaloneSignature, SubkeyParams, SubkeyParamsBuilder,
+};
+pub use crate::crypto::aead::AeadAlgorithm;
+pub use crate::crypto::ecc_curve::ECCCurve;
+pub use crate::crypto::hash::HashAlgorithm;
+pub use crate::crypto::public_key::PublicKeyAlgorithm;
+pub use crate::crypto::sym::SymmetricKeyAlgorithm;
+pub use crate::packet::{PublicKey, PublicSubkey, RevocationCode, Signature, SignatureType};
+pub use crate::ser::Serialize;
+pub use crate::types::{
+    CompressionAlgorithm, KeyId, KeyTrait, KeyVersion, PublicKeyTrait, SecretKeyTrait, StringToKey,
+};
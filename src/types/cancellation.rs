@@ -0,0 +1,74 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::errors::{Error, Result};
+
+/// A handle to cancel a long running operation, like the decryption of a large message,
+/// from another thread.
+///
+/// Clones share their state. Operations check the handle between chunks and packets, and
+/// return [`Error::Cancelled`] once it is cancelled.
+///
+/// Only these operations take a handle:
+///
+/// - [`Message::decrypt_detailed_cancellable`](crate::Message::decrypt_detailed_cancellable)
+/// - [`Message::decompress_cancellable`](crate::Message::decompress_cancellable)
+/// - [`SymEncryptedProtectedData::decrypt_with_session_key_cancellable`](crate::packet::SymEncryptedProtectedData::decrypt_with_session_key_cancellable)
+///
+/// Parsing, verification, and the key derivation from a password (S2K) are not cancellable,
+/// and neither is the decryption of the session key or of SEIPDv1 data, which each happen
+/// in one step. A cancellation during one of those takes effect once it is done.
+#[derive(Debug, Clone, Default)]
+pub struct Cancellation {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Cancellation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels all operations using this handle or one of its clones.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Returns [`Error::Cancelled`] if cancelled.
+    pub(crate) fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        Ok(())
+    }
+
+    /// Replaces the error of an operation that failed because it was cancelled.
+    pub(crate) fn map_err<T>(&self, res: Result<T>) -> Result<T> {
+        res.map_err(|err| {
+            if self.is_cancelled() {
+                Error::Cancelled
+            } else {
+                err
+            }
+        })
+    }
+}
+
+/// A reader that fails once `cancellation` is cancelled.
+pub(crate) struct CancellableReader<'a, R> {
+    pub(crate) inner: R,
+    pub(crate) cancellation: &'a Cancellation,
+}
+
+impl<R: io::Read> io::Read for CancellableReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.cancellation.is_cancelled() {
+            return Err(io::Error::new(io::ErrorKind::Other, "cancelled"));
+        }
+        self.inner.read(buf)
+    }
+}
@@ -0,0 +1,78 @@
+use std::fmt;
+
+use crate::types::{KeyId, KeyTrait, KeyVersion};
+
+/// Represents the fingerprint of a key, together with the version of that key.
+#[derive(Clone, Eq, PartialEq)]
+pub struct Fingerprint {
+    version: KeyVersion,
+    bytes: Vec<u8>,
+}
+
+impl AsRef<[u8]> for Fingerprint {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl Fingerprint {
+    pub fn new(version: KeyVersion, bytes: Vec<u8>) -> Self {
+        Fingerprint { version, bytes }
+    }
+
+    /// The fingerprint of `key`.
+    pub fn of(key: &impl KeyTrait) -> Self {
+        Fingerprint::new(key.key_version(), key.fingerprint())
+    }
+
+    /// The version of the key this is the fingerprint of.
+    pub fn version(&self) -> KeyVersion {
+        self.version
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Derives the key id, see [`KeyId::from_fingerprint`].
+    pub fn key_id(&self) -> Option<KeyId> {
+        KeyId::from_fingerprint(self.version, &self.bytes)
+    }
+}
+
+impl PartialEq<[u8]> for Fingerprint {
+    fn eq(&self, other: &[u8]) -> bool {
+        self.bytes == other
+    }
+}
+
+impl PartialEq<Vec<u8>> for Fingerprint {
+    fn eq(&self, other: &Vec<u8>) -> bool {
+        &self.bytes == other
+    }
+}
+
+impl fmt::Debug for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Fingerprint({:?}, {})",
+            self.version,
+            hex::encode(self.as_ref())
+        )
+    }
+}
+
+impl fmt::LowerHex for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.as_ref()))
+    }
+}
+
+impl fmt::UpperHex for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut encoded = hex::encode(self.as_ref());
+        encoded.make_ascii_uppercase();
+        write!(f, "{encoded}")
+    }
+}
@@ -1,5 +1,6 @@
 use crate::crypto::public_key::PublicKeyAlgorithm;
-use crate::types::KeyId;
+use crate::packet::KeyFlags;
+use crate::types::{KeyId, KeyVersion};
 
 pub trait KeyTrait: ::std::fmt::Debug {
     fn fingerprint(&self) -> Vec<u8>;
@@ -9,6 +10,18 @@ pub trait KeyTrait: ::std::fmt::Debug {
 
     fn algorithm(&self) -> PublicKeyAlgorithm;
 
+    /// Returns the version of this key.
+    ///
+    /// Defaults to the version that matches the length of the fingerprint, V4 if there is
+    /// none. Keys that are backed by a key packet return its version.
+    fn key_version(&self) -> KeyVersion {
+        match self.fingerprint().len() {
+            16 => KeyVersion::V3,
+            32 => KeyVersion::V5,
+            _ => KeyVersion::V4,
+        }
+    }
+
     fn is_signing_key(&self) -> bool {
         use crate::crypto::public_key::PublicKeyAlgorithm::*;
         matches!(
@@ -25,6 +38,14 @@ pub trait KeyTrait: ::std::fmt::Debug {
             RSA | RSAEncrypt | ECDH | DiffieHellman | Elgamal
         )
     }
+
+    /// Returns the key flags bound to this key, if they are known.
+    ///
+    /// Bare key packets carry no flags, only keys that come with their binding information
+    /// (like signed subkeys) return them.
+    fn key_flags(&self) -> Option<KeyFlags> {
+        None
+    }
 }
 
 impl<'a, T: KeyTrait> KeyTrait for &'a T {
@@ -40,4 +61,12 @@ impl<'a, T: KeyTrait> KeyTrait for &'a T {
     fn algorithm(&self) -> PublicKeyAlgorithm {
         (*self).algorithm()
     }
+
+    fn key_version(&self) -> KeyVersion {
+        (*self).key_version()
+    }
+
+    fn key_flags(&self) -> Option<KeyFlags> {
+        (*self).key_flags()
+    }
 }
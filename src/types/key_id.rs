@@ -1,6 +1,7 @@
 use std::fmt;
 
 use crate::errors::Result;
+use crate::types::KeyVersion;
 
 /// Represents a Key ID.
 #[derive(Clone, Eq, PartialEq)]
@@ -21,9 +22,32 @@ impl KeyId {
         Ok(KeyId(r))
     }
 
+    /// Derives the key id from the fingerprint of a key of the given version.
+    ///
+    /// V4 key ids are the low 64 bits of the fingerprint, V5 key ids the high 64 bits. Returns
+    /// `None` for other versions, whose key ids are not derived from the fingerprint, and if
+    /// the length of `fingerprint` does not match `version`.
+    pub fn from_fingerprint(version: KeyVersion, fingerprint: &[u8]) -> Option<KeyId> {
+        if version.fingerprint_len() != Some(fingerprint.len()) {
+            return None;
+        }
+        let id = match version {
+            KeyVersion::V4 => &fingerprint[12..],
+            KeyVersion::V5 => &fingerprint[..8],
+            _ => return None,
+        };
+
+        KeyId::from_slice(id).ok()
+    }
+
     pub fn to_vec(&self) -> Vec<u8> {
         self.0.to_vec()
     }
+
+    /// Returns true for the all zero key id, used to hide the recipient of a message.
+    pub fn is_wildcard(&self) -> bool {
+        self.0 == [0u8; 8]
+    }
 }
 
 impl fmt::Debug for KeyId {
@@ -1,4 +1,6 @@
+mod cancellation;
 mod compression;
+mod fingerprint;
 mod key;
 mod key_id;
 mod mpi;
@@ -9,9 +11,12 @@ mod revocation_key;
 mod s2k;
 mod secret_key;
 mod secret_key_repr;
+mod session_key;
 mod user;
 
+pub use self::cancellation::*;
 pub use self::compression::*;
+pub use self::fingerprint::*;
 pub use self::key::*;
 pub use self::key_id::*;
 pub use self::mpi::*;
@@ -22,4 +27,5 @@ pub use self::revocation_key::*;
 pub use self::s2k::*;
 pub use self::secret_key::*;
 pub use self::secret_key_repr::*;
+pub use self::session_key::*;
 pub use self::user::*;
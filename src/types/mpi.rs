@@ -44,7 +44,7 @@ pub fn mpi(input: &[u8]) -> IResult<&[u8], MpiRef<'_>> {
             Err(needed) => Err(nom::Err::Incomplete(needed)),
             Ok(index) => {
                 let (rest, n) = number.take_split(index);
-                let n_stripped: MpiRef<'_> = strip_leading_zeros(n).into();
+                let n_stripped: MpiRef<'_> = strip_all_leading_zeros(n).into();
 
                 Ok((rest, n_stripped))
             }
@@ -52,6 +52,14 @@ pub fn mpi(input: &[u8]) -> IResult<&[u8], MpiRef<'_>> {
     }
 }
 
+/// Strips the leading zeros of an MPI, they are not part of its encoding and zero has no bytes.
+fn strip_all_leading_zeros(bytes: &[u8]) -> &[u8] {
+    bytes
+        .iter()
+        .position(|b| *b != 0)
+        .map_or(&[][..], |offset| &bytes[offset..])
+}
+
 /// Represents an owned MPI value.
 /// The inner value is ready to be serialized, without the need to strip leading zeros.
 #[derive(Default, Clone, PartialEq, Eq, Zeroize, ZeroizeOnDrop)]
@@ -83,6 +91,11 @@ impl Mpi {
         Mpi(strip_leading_zeros(raw).to_vec())
     }
 
+    /// Strips leading zeros in place, leaving the minimal encoding of the value.
+    pub(crate) fn canonicalize(&mut self) {
+        strip_leading_zeros_vec(&mut self.0);
+    }
+
     pub fn as_ref(&self) -> MpiRef<'_> {
         MpiRef(&self.0)
     }
@@ -147,7 +160,7 @@ impl Serialize for Mpi {
 
 impl<'a> Serialize for MpiRef<'a> {
     fn to_writer<W: io::Write>(&self, w: &mut W) -> errors::Result<()> {
-        let bytes = &self.0;
+        let bytes = strip_all_leading_zeros(self.0);
         let size = bit_size(bytes);
         w.write_u16::<BigEndian>(size as u16)?;
         w.write_all(bytes)?;
@@ -278,6 +291,30 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_mpi_leading_zeros() {
+        for (raw, encoded, value) in [
+            (&[0][..], "0000", &[][..]),
+            (&[0, 0][..], "0000", &[][..]),
+            (&[0, 0x12][..], "000512", &[0x12][..]),
+            (&[0, 0, 0x80, 1][..], "00108001", &[0x80, 1][..]),
+        ] {
+            let mut res = Vec::new();
+            MpiRef::from_slice(raw).to_writer(&mut res).unwrap();
+            assert_eq!(hex::encode(&res), encoded);
+
+            let (rest, parsed) = mpi(&res).unwrap();
+            assert!(rest.is_empty());
+            assert_eq!(parsed.as_bytes(), value);
+        }
+
+        // zero is parsed without bytes, even when encoded with some
+        assert_eq!(
+            mpi(&[0x00, 0x08, 0x00][..]).unwrap(),
+            (&b""[..], (&[][..]).into())
+        );
+    }
+
     #[test]
     fn test_strip_trailing_zeroes() {
         let bytes = [1, 2, 3, 4, 0];
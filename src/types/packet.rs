@@ -4,6 +4,7 @@ use byteorder::{BigEndian, WriteBytesExt};
 use num_enum::{FromPrimitive, IntoPrimitive, TryFromPrimitive};
 
 use crate::errors::Result;
+use crate::ser::Serialize;
 
 /// Represents a Packet. A packet is the record structure used to encode a chunk of data in OpenPGP.
 /// Ref: https://tools.ietf.org/html/rfc4880.html#section-4
@@ -81,6 +82,144 @@ impl Tag {
         let t: u8 = self.into();
         0b1100_0000 | t
     }
+
+    /// Returns true for packet types that are allowed to use partial body lengths.
+    ///
+    /// Ref: https://tools.ietf.org/html/rfc4880.html#section-4.2.2.4
+    pub fn allows_partial_length(self) -> bool {
+        matches!(
+            self,
+            Tag::LiteralData
+                | Tag::CompressedData
+                | Tag::SymEncryptedData
+                | Tag::SymEncryptedProtectedData
+        )
+    }
+}
+
+/// The smallest length allowed for the first chunk of a packet using partial body lengths.
+pub const MIN_FIRST_PARTIAL_LENGTH: usize = 512;
+
+/// The largest chunk size that can be encoded as a partial body length.
+pub const MAX_PARTIAL_LENGTH: usize = 1 << 30;
+
+/// Header of a single packet, consisting of the packet format version, the tag
+/// and the length of the body.
+///
+/// For packets using partial body lengths, the length is the size of the first chunk.
+/// Ref: https://tools.ietf.org/html/rfc4880.html#section-4.2
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PacketHeader {
+    version: Version,
+    tag: Tag,
+    length: PacketLength,
+}
+
+impl PacketHeader {
+    /// Creates a new format header, for a body of exactly `len` bytes.
+    pub fn new_fixed(tag: Tag, len: usize) -> Self {
+        Self::new_fixed_with_version(Version::New, tag, len)
+    }
+
+    /// Creates a header in the given format, for a body of exactly `len` bytes.
+    ///
+    /// The old format can only encode tags up to 15, the new format is used for higher tags.
+    pub fn new_fixed_with_version(version: Version, tag: Tag, len: usize) -> Self {
+        let version = if u8::from(tag) > 0b1111 {
+            Version::New
+        } else {
+            version
+        };
+
+        PacketHeader {
+            version,
+            tag,
+            length: PacketLength::Fixed(len),
+        }
+    }
+
+    /// Creates a new format header, announcing a first chunk of `chunk_size` bytes
+    /// with a partial body length.
+    ///
+    /// Fails if the packet type may not use partial lengths, or if `chunk_size` is not
+    /// a power of two between 512 bytes and 1 GiB.
+    pub fn new_partial(tag: Tag, chunk_size: usize) -> Result<Self> {
+        ensure!(
+            tag.allows_partial_length(),
+            "Partial body length is not allowed for packet type {:?}",
+            tag
+        );
+        ensure!(
+            chunk_size >= MIN_FIRST_PARTIAL_LENGTH,
+            "Illegal first partial body length {} (shorter than {} bytes)",
+            chunk_size,
+            MIN_FIRST_PARTIAL_LENGTH
+        );
+        ensure!(
+            chunk_size.is_power_of_two() && chunk_size <= MAX_PARTIAL_LENGTH,
+            "Illegal partial body length {}",
+            chunk_size
+        );
+
+        Ok(PacketHeader {
+            version: Version::New,
+            tag,
+            length: PacketLength::Partial(chunk_size),
+        })
+    }
+
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    pub fn tag(&self) -> Tag {
+        self.tag
+    }
+
+    pub fn length(&self) -> &PacketLength {
+        &self.length
+    }
+}
+
+impl Serialize for PacketHeader {
+    fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<()> {
+        match self.length {
+            PacketLength::Fixed(len) => self.version.write_header(writer, self.tag.into(), len),
+            PacketLength::Partial(len) => {
+                writer.write_all(&[self.tag.encode()])?;
+                write_partial_length(writer, len)
+            }
+            PacketLength::Indeterminate => {
+                unsupported_err!("writing packets of indeterminate length")
+            }
+        }
+    }
+}
+
+/// Writes a new format, fixed body length.
+pub(crate) fn write_fixed_length(writer: &mut impl io::Write, len: usize) -> Result<()> {
+    if len < 192 {
+        writer.write_all(&[len as u8])?;
+    } else if len < 8384 {
+        writer.write_all(&[(((len - 192) >> 8) + 192) as u8, ((len - 192) & 0xFF) as u8])?;
+    } else {
+        writer.write_all(&[255])?;
+        writer.write_u32::<BigEndian>(len.try_into()?)?;
+    }
+
+    Ok(())
+}
+
+/// Writes a partial body length, `len` must be a power of two, no larger than 1 GiB.
+pub(crate) fn write_partial_length(writer: &mut impl io::Write, len: usize) -> Result<()> {
+    ensure!(
+        len.is_power_of_two() && len <= MAX_PARTIAL_LENGTH,
+        "Illegal partial body length {}",
+        len
+    );
+    writer.write_all(&[224 + len.trailing_zeros() as u8])?;
+
+    Ok(())
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, TryFromPrimitive)]
@@ -95,11 +234,14 @@ pub enum Version {
 }
 
 impl Version {
+    /// Writes a packet header in this format.
+    ///
+    /// Tags above 15 can not be encoded in the old format, the new format is used for them.
     pub fn write_header(self, writer: &mut impl io::Write, tag: u8, len: usize) -> Result<()> {
         debug!("write_header {:?} {} {}", self, tag, len);
 
         match self {
-            Version::Old => {
+            Version::Old if tag <= 0b1111 => {
                 if len < 256 {
                     // one octet
                     writer.write_all(&[0b1000_0000 | tag << 2, len as u8])?;
@@ -110,22 +252,12 @@ impl Version {
                 } else {
                     // four octets
                     writer.write_all(&[0b1000_0010 | tag << 2])?;
-                    writer.write_u32::<BigEndian>(len as u32)?;
+                    writer.write_u32::<BigEndian>(len.try_into()?)?;
                 }
             }
-            Version::New => {
+            Version::Old | Version::New => {
                 writer.write_all(&[0b1100_0000 | tag])?;
-                if len < 192 {
-                    writer.write_all(&[len as u8])?;
-                } else if len < 8384 {
-                    writer.write_all(&[
-                        (((len - 192) >> 8) + 192) as u8,
-                        ((len - 192) & 0xFF) as u8,
-                    ])?;
-                } else {
-                    writer.write_all(&[255])?;
-                    writer.write_u32::<BigEndian>(len as u32)?;
-                }
+                write_fixed_length(writer, len)?;
             }
         }
 
@@ -152,6 +284,18 @@ impl Default for KeyVersion {
     }
 }
 
+impl KeyVersion {
+    /// The length of the fingerprints of keys of this version, `None` for unknown versions.
+    pub fn fingerprint_len(self) -> Option<usize> {
+        match self {
+            KeyVersion::V2 | KeyVersion::V3 => Some(16),
+            KeyVersion::V4 => Some(20),
+            KeyVersion::V5 => Some(32),
+            KeyVersion::Other(_) => None,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #![allow(clippy::unwrap_used)]
@@ -181,4 +325,86 @@ mod tests {
 
         assert_eq!(hex::encode(buf), "c2c06f");
     }
+
+    #[test]
+    fn test_write_header_length_boundaries() {
+        use crate::packet::{Packet, PacketParser};
+
+        // (format, body length, expected header)
+        let cases = [
+            (Version::Old, 191, "b4bf"),
+            (Version::Old, 192, "b4c0"),
+            (Version::Old, 255, "b4ff"),
+            (Version::Old, 256, "b50100"),
+            (Version::Old, 8383, "b520bf"),
+            (Version::Old, 8384, "b520c0"),
+            (Version::Old, 65535, "b5ffff"),
+            (Version::Old, 65536, "b600010000"),
+            (Version::New, 191, "cdbf"),
+            (Version::New, 192, "cdc000"),
+            (Version::New, 8383, "cddfff"),
+            (Version::New, 8384, "cdff000020c0"),
+            (Version::New, 65535, "cdff0000ffff"),
+        ];
+
+        for (version, len, expected) in cases {
+            let mut buf = Vec::new();
+            version
+                .write_header(&mut buf, Tag::UserId.into(), len)
+                .unwrap();
+            assert_eq!(hex::encode(&buf), expected, "{:?} {}", version, len);
+
+            buf.resize(buf.len() + len, b'a');
+            let mut parser = PacketParser::new(&buf[..]);
+            let packet = parser.next().unwrap().unwrap();
+            assert_eq!(packet.packet_version(), version);
+            match packet {
+                Packet::UserId(id) => assert_eq!(id.id().len(), len, "{:?}", version),
+                p => panic!("unexpected packet {:?}", p.tag()),
+            }
+            assert!(parser.next().is_none());
+        }
+    }
+
+    #[test]
+    fn test_packet_header_partial() {
+        let header = PacketHeader::new_partial(Tag::LiteralData, 512).unwrap();
+        assert_eq!(hex::encode(header.to_bytes().unwrap()), "cbe9");
+
+        let header = PacketHeader::new_partial(Tag::SymEncryptedProtectedData, 1 << 30).unwrap();
+        assert_eq!(hex::encode(header.to_bytes().unwrap()), "d2fe");
+
+        // too short for a first chunk
+        assert!(PacketHeader::new_partial(Tag::LiteralData, 256).is_err());
+        // not a power of two
+        assert!(PacketHeader::new_partial(Tag::LiteralData, 1000).is_err());
+        // too large
+        assert!(PacketHeader::new_partial(Tag::LiteralData, 1 << 31).is_err());
+        // not a data packet
+        assert!(PacketHeader::new_partial(Tag::Signature, 512).is_err());
+
+        let header = PacketHeader::new_fixed(Tag::Signature, 302);
+        assert_eq!(hex::encode(header.to_bytes().unwrap()), "c2c06e");
+    }
+
+    #[test]
+    fn test_packet_header_old_format() {
+        let header = PacketHeader::new_fixed_with_version(Version::Old, Tag::Signature, 302);
+        assert_eq!(header.version(), Version::Old);
+        assert_eq!(hex::encode(header.to_bytes().unwrap()), "89012e");
+
+        let header = PacketHeader::new_fixed_with_version(Version::Old, Tag::UserId, 17);
+        assert_eq!(hex::encode(header.to_bytes().unwrap()), "b411");
+
+        // the old format has no room for tags above 15
+        let header = PacketHeader::new_fixed_with_version(Version::Old, Tag::Padding, 4);
+        assert_eq!(header.version(), Version::New);
+        assert_eq!(hex::encode(header.to_bytes().unwrap()), "d504");
+
+        let mut buf = Vec::new();
+        Version::Old
+            .write_header(&mut buf, Tag::UserAttribute.into(), 4)
+            .unwrap();
+        assert_eq!(hex::encode(buf), "d104");
+    }
 }
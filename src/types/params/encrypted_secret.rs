@@ -35,20 +35,24 @@ impl EncryptedSecretParams {
         &self.s2k_params
     }
 
+    /// Returns the checksum at the end of the encrypted data.
+    ///
+    /// Empty for keys with an unknown S2K, which keep their encrypted data in the S2K.
     pub fn checksum(&self) -> Vec<u8> {
-        match self.s2k_params {
+        let len = match self.s2k_params {
             S2kParams::Unprotected => unreachable!(),
             S2kParams::LegacyCfb { .. }
             | S2kParams::Aead { .. }
             | S2kParams::MaleableCfb { .. } => {
                 // 2 octets
-                self.data[self.data.len() - 2..].to_vec()
+                2
             }
             S2kParams::Cfb { .. } => {
                 // 20 octets SHA1
-                self.data[self.data.len() - 20..].to_vec()
+                20
             }
-        }
+        };
+        self.data[self.data.len().saturating_sub(len)..].to_vec()
     }
 
     pub fn unlock<F>(
@@ -61,7 +65,7 @@ impl EncryptedSecretParams {
         F: FnOnce() -> String,
     {
         match &self.s2k_params {
-            S2kParams::Unprotected => unreachable!(),
+            S2kParams::Unprotected => bail!("secret params are not encrypted"),
             S2kParams::LegacyCfb { sym_alg, iv } => {
                 let key = md5::Md5::digest(pw());
 
@@ -138,7 +142,7 @@ impl Serialize for EncryptedSecretParams {
 
         match &self.s2k_params {
             S2kParams::Unprotected => {
-                panic!("encrypted secret params should not have an unencrypted identifier")
+                bail!("encrypted secret params should not have an unencrypted identifier")
             }
             S2kParams::LegacyCfb { ref iv, .. } => {
                 writer.write_all(iv)?;
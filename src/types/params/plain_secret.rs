@@ -4,12 +4,20 @@ use std::{fmt, io};
 use byteorder::{BigEndian, ByteOrder};
 use nom::combinator::map;
 use nom::sequence::tuple;
+#[cfg(feature = "rsa")]
+use num_bigint::traits::ModInverse;
+#[cfg(feature = "rsa")]
+use num_bigint::BigUint;
+#[cfg(feature = "rsa")]
 use rsa::RsaPrivateKey;
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
 use crate::crypto::checksum;
+#[cfg(any(feature = "x25519", feature = "nist", feature = "ed25519"))]
 use crate::crypto::ecc_curve::ECCCurve;
 use crate::crypto::public_key::PublicKeyAlgorithm;
+#[cfg(feature = "rsa")]
+use crate::errors::Error;
 use crate::errors::{IResult, Result};
 use crate::ser::Serialize;
 use crate::types::*;
@@ -127,21 +135,30 @@ impl<'a> PlainSecretParamsRef<'a> {
 
     pub fn as_repr(&self, public_params: &PublicParams) -> Result<SecretKeyRepr> {
         match self {
+            #[cfg(feature = "rsa")]
             PlainSecretParamsRef::RSA { d, p, q, .. } => match public_params {
                 PublicParams::RSA { ref n, ref e } => {
+                    let invalid = |err: rsa::errors::Error| {
+                        Error::InvalidKeyMaterial(format!("RSA secret key: {err}"))
+                    };
                     let secret_key = RsaPrivateKey::from_components(
                         n.into(),
                         e.into(),
                         d.into(),
                         vec![p.into(), q.into()],
-                    )?;
-                    secret_key.validate()?;
+                    )
+                    .map_err(invalid)?;
+                    secret_key.validate().map_err(invalid)?;
                     Ok(SecretKeyRepr::RSA(crate::crypto::rsa::PrivateKey(
                         secret_key,
                     )))
                 }
                 _ => unreachable!("inconsistent key state"),
             },
+            #[cfg(not(feature = "rsa"))]
+            PlainSecretParamsRef::RSA { .. } => {
+                unsupported_err!("RSA (feature \"rsa\" is disabled)")
+            }
             PlainSecretParamsRef::ECDH(d) => match public_params {
                 PublicParams::ECDH {
                     ref curve,
@@ -149,6 +166,7 @@ impl<'a> PlainSecretParamsRef<'a> {
                     ref alg_sym,
                     ..
                 } => match curve {
+                    #[cfg(feature = "x25519")]
                     ECCCurve::Curve25519 => {
                         const SIZE: usize = ECCCurve::Curve25519.secret_key_length();
 
@@ -160,6 +178,7 @@ impl<'a> PlainSecretParamsRef<'a> {
                             },
                         ))
                     }
+                    #[cfg(feature = "nist")]
                     ECCCurve::P256 => {
                         const SIZE: usize = ECCCurve::P256.secret_key_length();
 
@@ -169,6 +188,7 @@ impl<'a> PlainSecretParamsRef<'a> {
                             alg_sym: *alg_sym,
                         }))
                     }
+                    #[cfg(feature = "nist")]
                     ECCCurve::P384 => {
                         const SIZE: usize = ECCCurve::P384.secret_key_length();
 
@@ -178,6 +198,7 @@ impl<'a> PlainSecretParamsRef<'a> {
                             alg_sym: *alg_sym,
                         }))
                     }
+                    #[cfg(feature = "nist")]
                     ECCCurve::P521 => {
                         const SIZE: usize = ECCCurve::P521.secret_key_length();
 
@@ -193,6 +214,7 @@ impl<'a> PlainSecretParamsRef<'a> {
             },
             PlainSecretParamsRef::EdDSA(d) => match public_params {
                 PublicParams::EdDSA { ref curve, .. } => match *curve {
+                    #[cfg(feature = "ed25519")]
                     ECCCurve::Ed25519 => {
                         const SIZE: usize = ECCCurve::Ed25519.secret_key_length();
 
@@ -205,14 +227,18 @@ impl<'a> PlainSecretParamsRef<'a> {
                 },
                 _ => unreachable!("inconsistent key state"),
             },
+            #[cfg(feature = "dsa")]
             PlainSecretParamsRef::DSA(x) => Ok(SecretKeyRepr::DSA(crate::crypto::dsa::SecretKey {
                 x: x.into(),
             })),
+            #[cfg(not(feature = "dsa"))]
+            PlainSecretParamsRef::DSA(_) => unsupported_err!("DSA (feature \"dsa\" is disabled)"),
             PlainSecretParamsRef::Elgamal(_) => {
                 unimplemented_err!("Elgamal");
             }
             PlainSecretParamsRef::ECDSA(d) => match public_params {
                 PublicParams::ECDSA(params) => match params {
+                    #[cfg(feature = "nist")]
                     EcdsaPublicParams::P256 { .. } => {
                         let secret = p256::SecretKey::from_slice(d.as_bytes())?;
 
@@ -220,6 +246,7 @@ impl<'a> PlainSecretParamsRef<'a> {
                             secret,
                         )))
                     }
+                    #[cfg(feature = "nist")]
                     EcdsaPublicParams::P384 { .. } => {
                         let secret = p384::SecretKey::from_slice(d.as_bytes())?;
 
@@ -227,6 +254,7 @@ impl<'a> PlainSecretParamsRef<'a> {
                             secret,
                         )))
                     }
+                    #[cfg(feature = "nist")]
                     EcdsaPublicParams::P521 { .. } => {
                         let secret = p521::SecretKey::from_slice(d.as_bytes())?;
 
@@ -234,6 +262,7 @@ impl<'a> PlainSecretParamsRef<'a> {
                             secret,
                         )))
                     }
+                    #[cfg(feature = "k256")]
                     EcdsaPublicParams::Secp256k1 { .. } => {
                         let secret = k256::SecretKey::from_slice(d.as_bytes())?;
 
@@ -252,15 +281,48 @@ impl<'a> PlainSecretParamsRef<'a> {
 }
 
 impl PlainSecretParams {
+    /// Parses the unencrypted secret key material, see [`Self::repair_crt_coefficient`].
     pub fn from_slice(
         data: &[u8],
         alg: PublicKeyAlgorithm,
         _params: &PublicParams,
     ) -> Result<Self> {
-        let (_, repr) = parse_secret_params(alg)(data)?;
+        let (_, mut repr) = parse_secret_params(alg)(data)?;
+        repr.repair_crt_coefficient()?;
         Ok(repr)
     }
 
+    /// Checks the CRT coefficient `u` of an RSA secret key, which OpenPGP defines as
+    /// `p⁻¹ mod q`.
+    ///
+    /// Some implementations store the primes in the opposite order, so that `u` is
+    /// `q⁻¹ mod p`. Such a `u` is replaced with `p⁻¹ mod q`. A `u` that matches neither order
+    /// means the key material is corrupt. Done once, when the key material is parsed or
+    /// decrypted.
+    pub(crate) fn repair_crt_coefficient(&mut self) -> Result<()> {
+        #[cfg(feature = "rsa")]
+        if let PlainSecretParams::RSA { p, q, u, .. } = self {
+            let (p, q) = (BigUint::from(p.clone()), BigUint::from(q.clone()));
+            let expected = rsa_inverse(&p, &q).ok_or_else(|| {
+                Error::InvalidKeyMaterial("RSA primes are not coprime".to_string())
+            })?;
+            let current = BigUint::from_bytes_be(u.as_bytes());
+            if current != expected {
+                if rsa_inverse(&q, &p).as_ref() != Some(&current) {
+                    return Err(Error::InvalidKeyMaterial(
+                        "RSA CRT coefficient does not match the primes".to_string(),
+                    ));
+                }
+                warn!(
+                    "RSA secret key stores its primes swapped, the CRT coefficient is recomputed"
+                );
+                *u = expected.into();
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn string_to_key_id(&self) -> u8 {
         self.as_ref().string_to_key_id()
     }
@@ -274,6 +336,23 @@ impl PlainSecretParams {
         self.as_ref().checksum_sha1()
     }
 
+    /// Reduces all MPIs to their minimal encoding.
+    pub(crate) fn canonicalize(&mut self) {
+        match self {
+            PlainSecretParams::RSA { d, p, q, u } => {
+                d.canonicalize();
+                p.canonicalize();
+                q.canonicalize();
+                u.canonicalize();
+            }
+            PlainSecretParams::DSA(v)
+            | PlainSecretParams::ECDSA(v)
+            | PlainSecretParams::ECDH(v)
+            | PlainSecretParams::Elgamal(v)
+            | PlainSecretParams::EdDSA(v) => v.canonicalize(),
+        }
+    }
+
     pub fn as_ref(&self) -> PlainSecretParamsRef<'_> {
         match self {
             PlainSecretParams::RSA { d, p, q, u } => PlainSecretParamsRef::RSA {
@@ -384,6 +463,14 @@ fn parse_secret_params(
     }
 }
 
+/// Returns `a⁻¹ mod m`.
+#[cfg(feature = "rsa")]
+fn rsa_inverse(a: &BigUint, m: &BigUint) -> Option<BigUint> {
+    a.clone()
+        .mod_inverse(m)
+        .and_then(|inverse| inverse.to_biguint())
+}
+
 // Parse the decrpyted private params of an RSA private key.
 fn rsa_secret_params(i: &[u8]) -> IResult<&[u8], PlainSecretParams> {
     map(tuple((mpi, mpi, mpi, mpi)), |(d, p, q, u)| {
@@ -395,3 +482,121 @@ fn rsa_secret_params(i: &[u8]) -> IResult<&[u8], PlainSecretParams> {
         }
     })(i)
 }
+
+#[cfg(all(test, feature = "rsa"))]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::composed::{Deserializable, SignedSecretKey};
+    use crate::crypto::hash::HashAlgorithm;
+    use crate::crypto::Signer;
+
+    /// Unlocks `params` and signs a fixed digest with the result.
+    fn sign(params: &PlainSecretParams, public_params: &PublicParams) -> Result<Vec<Vec<u8>>> {
+        let repr = params.as_ref().as_repr(public_params)?;
+        let SecretKeyRepr::RSA(ref key) = repr else {
+            panic!("expected an RSA key");
+        };
+        key.sign(HashAlgorithm::SHA2_256, &[7u8; 32], public_params)
+    }
+
+    #[test]
+    fn test_rsa_swapped_primes() {
+        let (key, _) = SignedSecretKey::from_armor_single(
+            std::fs::File::open("./tests/openpgp/samplekeys/rsa-primary-auth-only.sec.asc")
+                .unwrap(),
+        )
+        .unwrap();
+        let public_params = key.primary_key.public_params();
+        let SecretParams::Plain(params) = key.primary_key.secret_params() else {
+            panic!("expected an unencrypted key");
+        };
+        let PlainSecretParams::RSA { d, p, q, u } = params else {
+            panic!("expected an RSA key");
+        };
+        let expected = sign(params, public_params).unwrap();
+
+        // primes in the opposite order, as some implementations store them
+        let mut swapped = PlainSecretParams::RSA {
+            d: d.clone(),
+            p: q.clone(),
+            q: p.clone(),
+            u: u.clone(),
+        };
+        swapped.repair_crt_coefficient().unwrap();
+        let PlainSecretParams::RSA { u: repaired, .. } = &swapped else {
+            unreachable!();
+        };
+        let inverse = BigUint::from(q.clone())
+            .mod_inverse(BigUint::from(p.clone()))
+            .and_then(|u| u.to_biguint())
+            .unwrap();
+        assert_eq!(BigUint::from(repaired.clone()), inverse);
+        assert_eq!(sign(&swapped, public_params).unwrap(), expected);
+
+        // a CRT coefficient that matches neither order
+        let mut corrupt_u = PlainSecretParams::RSA {
+            d: d.clone(),
+            p: p.clone(),
+            q: q.clone(),
+            u: Mpi::from_slice(&[3]),
+        };
+        assert!(matches!(
+            corrupt_u.repair_crt_coefficient(),
+            Err(Error::InvalidKeyMaterial(_))
+        ));
+
+        // primes that do not multiply to the modulus
+        let mut wrong_prime = PlainSecretParams::RSA {
+            d: d.clone(),
+            p: p.clone(),
+            q: p.clone(),
+            u: u.clone(),
+        };
+        assert!(matches!(
+            wrong_prime.repair_crt_coefficient(),
+            Err(Error::InvalidKeyMaterial(_))
+        ));
+        assert!(matches!(
+            sign(&wrong_prime, public_params),
+            Err(Error::InvalidKeyMaterial(_))
+        ));
+    }
+
+    #[test]
+    fn test_rsa_swapped_primes_fixture() {
+        use crate::composed::{Message, SignedPublicKey};
+        use crate::types::SecretKeyTrait;
+
+        // OpenSSL's primes (p > q) and CRT coefficient (q⁻¹ mod p), stored as is; GnuPG
+        // imports this key, but fails to sign with it
+        let (key, _) = SignedSecretKey::from_armor_single(
+            std::fs::File::open("./tests/unit-tests/rsa-swapped-primes/key.sec.asc").unwrap(),
+        )
+        .unwrap();
+        key.verify().unwrap();
+        let SecretParams::Plain(PlainSecretParams::RSA { p, q, u, .. }) =
+            key.primary_key.secret_params()
+        else {
+            panic!("expected an unencrypted RSA key");
+        };
+        let inverse = BigUint::from(p.clone())
+            .mod_inverse(BigUint::from(q.clone()))
+            .and_then(|u| u.to_biguint())
+            .unwrap();
+        assert_eq!(BigUint::from(u.clone()), inverse);
+
+        let public = SignedPublicKey::from(key.clone());
+        let message = Message::new_literal("", "hello")
+            .sign(&key, String::new, HashAlgorithm::SHA2_256)
+            .unwrap();
+        message.verify(&public).unwrap();
+
+        // the repaired coefficient is written back
+        let bytes = key.to_bytes().unwrap();
+        let reparsed = SignedSecretKey::from_bytes(&bytes[..]).unwrap();
+        assert_eq!(reparsed.to_bytes().unwrap(), bytes);
+        assert_eq!(reparsed.public_key().fingerprint(), key.fingerprint());
+    }
+}
@@ -1,7 +1,12 @@
 use std::{fmt, io};
 
+#[cfg(feature = "rsa")]
+use num_bigint::BigUint;
+
 use crate::crypto::ecc_curve::ECCCurve;
 use crate::crypto::hash::HashAlgorithm;
+#[cfg(feature = "rsa")]
+use crate::crypto::rsa::MAX_KEY_SIZE;
 use crate::crypto::sym::SymmetricKeyAlgorithm;
 use crate::errors::Result;
 use crate::ser::Serialize;
@@ -41,23 +46,32 @@ pub enum PublicParams {
     },
 }
 
+/// The public parameters of an ECDSA key.
+///
+/// Curves whose crate feature is disabled are kept as [`EcdsaPublicParams::Unsupported`], so
+/// the set of variants depends on the enabled features.
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
 pub enum EcdsaPublicParams {
+    #[cfg(feature = "nist")]
     P256 {
         key: p256::PublicKey,
         /// Stores the original Mpi, to ensure we keep the padding around.
         p: Mpi,
     },
+    #[cfg(feature = "nist")]
     P384 {
         key: p384::PublicKey,
         /// Stores the original Mpi, to ensure we keep the padding around.
         p: Mpi,
     },
+    #[cfg(feature = "nist")]
     P521 {
         key: p521::PublicKey,
         /// Stores the original Mpi, to ensure we keep the padding around.
         p: Mpi,
     },
+    #[cfg(feature = "k256")]
     Secp256k1 {
         key: k256::PublicKey,
         /// Stores the original Mpi, to ensure we keep the padding around.
@@ -72,6 +86,7 @@ pub enum EcdsaPublicParams {
 impl EcdsaPublicParams {
     pub fn try_from_mpi(p: MpiRef<'_>, curve: ECCCurve) -> Result<Self> {
         match curve {
+            #[cfg(feature = "nist")]
             ECCCurve::P256 => {
                 ensure!(p.len() <= 65, "invalid public key length");
                 let mut key = [0u8; 65];
@@ -83,6 +98,7 @@ impl EcdsaPublicParams {
                     p: p.to_owned(),
                 })
             }
+            #[cfg(feature = "nist")]
             ECCCurve::P384 => {
                 ensure!(p.len() <= 97, "invalid public key length");
                 let mut key = [0u8; 97];
@@ -94,6 +110,7 @@ impl EcdsaPublicParams {
                     p: p.to_owned(),
                 })
             }
+            #[cfg(feature = "nist")]
             ECCCurve::P521 => {
                 ensure!(p.len() <= 133, "invalid public key length");
                 let mut key = [0u8; 133];
@@ -105,6 +122,7 @@ impl EcdsaPublicParams {
                     p: p.to_owned(),
                 })
             }
+            #[cfg(feature = "k256")]
             ECCCurve::Secp256k1 => {
                 ensure!(p.len() <= 65, "invalid public key length");
                 let mut key = [0u8; 65];
@@ -125,38 +143,277 @@ impl EcdsaPublicParams {
 
     pub const fn secret_key_length(&self) -> Option<usize> {
         match self {
+            #[cfg(feature = "nist")]
             EcdsaPublicParams::P256 { .. } => Some(32),
+            #[cfg(feature = "nist")]
             EcdsaPublicParams::P384 { .. } => Some(48),
+            #[cfg(feature = "nist")]
             EcdsaPublicParams::P521 { .. } => Some(66),
+            #[cfg(feature = "k256")]
             EcdsaPublicParams::Secp256k1 { .. } => Some(32),
             EcdsaPublicParams::Unsupported { .. } => None,
         }
     }
 }
 
+impl PublicParams {
+    /// Returns true if session keys can be encrypted to keys with these parameters.
+    pub fn can_encrypt(&self) -> bool {
+        matches!(self, PublicParams::RSA { .. } | PublicParams::ECDH { .. })
+    }
+
+    /// Returns the unparsed key material of keys with an unknown algorithm or key version.
+    ///
+    /// The data is kept byte for byte, so that such keys can be serialized and fingerprinted.
+    pub fn raw(&self) -> Option<&[u8]> {
+        match self {
+            PublicParams::Unknown { data } => Some(data),
+            _ => None,
+        }
+    }
+
+    /// Reduces all MPIs to their minimal encoding.
+    ///
+    /// Only applied to freshly generated keys: the fingerprint of a parsed key is computed over
+    /// its original encoding, which must be kept as is.
+    pub(crate) fn canonicalize(&mut self) {
+        match self {
+            PublicParams::RSA { n, e } => {
+                n.canonicalize();
+                e.canonicalize();
+            }
+            PublicParams::DSA { p, q, g, y } => {
+                p.canonicalize();
+                q.canonicalize();
+                g.canonicalize();
+                y.canonicalize();
+            }
+            PublicParams::ECDSA(params) => match params {
+                #[cfg(feature = "nist")]
+                EcdsaPublicParams::P256 { p, .. }
+                | EcdsaPublicParams::P384 { p, .. }
+                | EcdsaPublicParams::P521 { p, .. } => p.canonicalize(),
+                #[cfg(feature = "k256")]
+                EcdsaPublicParams::Secp256k1 { p, .. } => p.canonicalize(),
+                EcdsaPublicParams::Unsupported { p, .. } => p.canonicalize(),
+            },
+            PublicParams::ECDH { p, .. } => p.canonicalize(),
+            PublicParams::Elgamal { p, g, y } => {
+                p.canonicalize();
+                g.canonicalize();
+                y.canonicalize();
+            }
+            PublicParams::EdDSA { q, .. } => q.canonicalize(),
+            PublicParams::Unknown { .. } => {}
+        }
+    }
+
+    /// Encodes the public key as a DER encoded X.509 `SubjectPublicKeyInfo`.
+    ///
+    /// Supported are RSA keys, ECDSA and ECDH keys on the NIST curves and secp256k1,
+    /// as well as Ed25519 keys.
+    pub fn to_spki_der(&self) -> Result<Vec<u8>> {
+        #[cfg(any(
+            feature = "rsa",
+            feature = "nist",
+            feature = "k256",
+            feature = "ed25519"
+        ))]
+        use elliptic_curve::pkcs8::EncodePublicKey;
+
+        let doc: elliptic_curve::pkcs8::spki::Result<elliptic_curve::pkcs8::Document> = match self {
+            #[cfg(feature = "rsa")]
+            PublicParams::RSA { n, e } => rsa_public_key(n, e)?.to_public_key_der(),
+            #[cfg(feature = "nist")]
+            PublicParams::ECDSA(EcdsaPublicParams::P256 { key, .. }) => key.to_public_key_der(),
+            #[cfg(feature = "nist")]
+            PublicParams::ECDSA(EcdsaPublicParams::P384 { key, .. }) => key.to_public_key_der(),
+            #[cfg(feature = "nist")]
+            PublicParams::ECDSA(EcdsaPublicParams::P521 { key, .. }) => key.to_public_key_der(),
+            #[cfg(feature = "k256")]
+            PublicParams::ECDSA(EcdsaPublicParams::Secp256k1 { key, .. }) => {
+                key.to_public_key_der()
+            }
+            PublicParams::ECDH { curve, p, .. } => match curve {
+                #[cfg(feature = "nist")]
+                ECCCurve::P256 => {
+                    p256::PublicKey::from_sec1_bytes(p.as_bytes())?.to_public_key_der()
+                }
+                #[cfg(feature = "nist")]
+                ECCCurve::P384 => {
+                    p384::PublicKey::from_sec1_bytes(p.as_bytes())?.to_public_key_der()
+                }
+                #[cfg(feature = "nist")]
+                ECCCurve::P521 => {
+                    p521::PublicKey::from_sec1_bytes(p.as_bytes())?.to_public_key_der()
+                }
+                _ => unsupported_err!("SPKI encoding for ECDH on {}", curve.name()),
+            },
+            PublicParams::EdDSA { curve, q } => match curve {
+                #[cfg(feature = "ed25519")]
+                ECCCurve::Ed25519 => ed25519_public_key(q)?.to_public_key_der(),
+                _ => unsupported_err!("SPKI encoding for EdDSA on {}", curve.name()),
+            },
+            _ => unsupported_err!("SPKI encoding for {:?}", self),
+        };
+        let doc = doc.map_err(|e| format_err!("failed to encode SPKI: {}", e))?;
+
+        Ok(doc.into_vec())
+    }
+
+    /// Encodes the public key in the native format of its algorithm.
+    ///
+    /// - RSA: DER encoded PKCS#1 `RSAPublicKey`
+    /// - ECDSA and ECDH on the NIST curves and secp256k1: uncompressed SEC1 point
+    /// - Ed25519 and Curve25519: the 32 byte public key
+    pub fn to_raw(&self) -> Result<Vec<u8>> {
+        match self {
+            #[cfg(feature = "rsa")]
+            PublicParams::RSA { n, e } => {
+                use rsa::pkcs1::EncodeRsaPublicKey;
+
+                let doc = rsa_public_key(n, e)?
+                    .to_pkcs1_der()
+                    .map_err(|e| format_err!("failed to encode PKCS#1: {}", e))?;
+                Ok(doc.into_vec())
+            }
+            #[cfg(feature = "nist")]
+            PublicParams::ECDSA(EcdsaPublicParams::P256 { key, .. }) => {
+                Ok(key.to_sec1_bytes().to_vec())
+            }
+            #[cfg(feature = "nist")]
+            PublicParams::ECDSA(EcdsaPublicParams::P384 { key, .. }) => {
+                Ok(key.to_sec1_bytes().to_vec())
+            }
+            #[cfg(feature = "nist")]
+            PublicParams::ECDSA(EcdsaPublicParams::P521 { key, .. }) => {
+                Ok(key.to_sec1_bytes().to_vec())
+            }
+            #[cfg(feature = "k256")]
+            PublicParams::ECDSA(EcdsaPublicParams::Secp256k1 { key, .. }) => {
+                Ok(key.to_sec1_bytes().to_vec())
+            }
+            PublicParams::ECDH { curve, p, .. } => match curve {
+                ECCCurve::Curve25519 => {
+                    ensure_eq!(p.len(), 33, "invalid public key length");
+                    ensure_eq!(p.as_bytes()[0], 0x40, "invalid public key prefix");
+                    Ok(p.as_bytes()[1..].to_vec())
+                }
+                #[cfg(feature = "nist")]
+                ECCCurve::P256 => Ok(p256::PublicKey::from_sec1_bytes(p.as_bytes())?
+                    .to_sec1_bytes()
+                    .to_vec()),
+                #[cfg(feature = "nist")]
+                ECCCurve::P384 => Ok(p384::PublicKey::from_sec1_bytes(p.as_bytes())?
+                    .to_sec1_bytes()
+                    .to_vec()),
+                #[cfg(feature = "nist")]
+                ECCCurve::P521 => Ok(p521::PublicKey::from_sec1_bytes(p.as_bytes())?
+                    .to_sec1_bytes()
+                    .to_vec()),
+                _ => unsupported_err!("raw encoding for ECDH on {}", curve.name()),
+            },
+            PublicParams::EdDSA { curve, q } => match curve {
+                #[cfg(feature = "ed25519")]
+                ECCCurve::Ed25519 => Ok(ed25519_public_key(q)?.to_bytes().to_vec()),
+                _ => unsupported_err!("raw encoding for EdDSA on {}", curve.name()),
+            },
+            _ => unsupported_err!("raw encoding for {:?}", self),
+        }
+    }
+
+    /// Builds the parameters of an EdDSA key on Ed25519 from the raw 32 byte public key.
+    ///
+    /// Fails if the bytes do not encode a point on the curve.
+    #[cfg(feature = "ed25519")]
+    pub fn ed25519_from_bytes(key: &[u8; 32]) -> Result<Self> {
+        ed25519_dalek::VerifyingKey::from_bytes(key)?;
+
+        Ok(PublicParams::EdDSA {
+            curve: ECCCurve::Ed25519,
+            q: prefixed_native_point(key),
+        })
+    }
+
+    /// Builds the parameters of an ECDH key on Curve25519 from the raw 32 byte public key.
+    ///
+    /// The KDF parameters are the defaults for the curve, as used for newly generated keys.
+    /// Fails for the all zero key, which results in an all zero shared secret.
+    pub fn x25519_from_bytes(key: &[u8; 32]) -> Result<Self> {
+        ensure!(key.iter().any(|b| *b != 0), "invalid X25519 public key");
+
+        let curve = ECCCurve::Curve25519;
+        Ok(PublicParams::ECDH {
+            p: prefixed_native_point(key),
+            hash: curve.hash_algo()?,
+            alg_sym: curve.sym_algo()?,
+            curve,
+        })
+    }
+}
+
+/// Encodes a point in the native format of its curve, as used by Ed25519 and Curve25519.
+fn prefixed_native_point(key: &[u8; 32]) -> Mpi {
+    let mut p = Vec::with_capacity(33);
+    p.push(0x40);
+    p.extend_from_slice(key);
+    p.into()
+}
+
+#[cfg(feature = "rsa")]
+fn rsa_public_key(n: &Mpi, e: &Mpi) -> Result<rsa::RsaPublicKey> {
+    let key = rsa::RsaPublicKey::new_with_max_size(
+        BigUint::from_bytes_be(n.as_bytes()),
+        BigUint::from_bytes_be(e.as_bytes()),
+        MAX_KEY_SIZE,
+    )?;
+
+    Ok(key)
+}
+
+#[cfg(feature = "ed25519")]
+fn ed25519_public_key(q: &Mpi) -> Result<ed25519_dalek::VerifyingKey> {
+    ensure_eq!(q.len(), 33, "invalid Q (len)");
+    ensure_eq!(q.as_bytes()[0], 0x40, "invalid Q (prefix)");
+
+    let key = ed25519_dalek::VerifyingKey::from_bytes(
+        &q.as_bytes()[1..].try_into().expect("pre verified"),
+    )?;
+
+    Ok(key)
+}
+
 impl Serialize for EcdsaPublicParams {
     fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<()> {
         let oid = match self {
-            EcdsaPublicParams::P256 { .. } => ECCCurve::P256.oid(),
-            EcdsaPublicParams::P384 { .. } => ECCCurve::P384.oid(),
-            EcdsaPublicParams::P521 { .. } => ECCCurve::P521.oid(),
-            EcdsaPublicParams::Secp256k1 { .. } => ECCCurve::Secp256k1.oid(),
-            EcdsaPublicParams::Unsupported { curve, .. } => curve.oid(),
+            #[cfg(feature = "nist")]
+            EcdsaPublicParams::P256 { .. } => ECCCurve::P256.oid_bytes(),
+            #[cfg(feature = "nist")]
+            EcdsaPublicParams::P384 { .. } => ECCCurve::P384.oid_bytes(),
+            #[cfg(feature = "nist")]
+            EcdsaPublicParams::P521 { .. } => ECCCurve::P521.oid_bytes(),
+            #[cfg(feature = "k256")]
+            EcdsaPublicParams::Secp256k1 { .. } => ECCCurve::Secp256k1.oid_bytes(),
+            EcdsaPublicParams::Unsupported { curve, .. } => curve.oid_bytes(),
         };
 
         writer.write_all(&[oid.len() as u8])?;
-        writer.write_all(&oid)?;
+        writer.write_all(oid)?;
 
         match self {
+            #[cfg(feature = "nist")]
             EcdsaPublicParams::P256 { p, .. } => {
                 p.as_ref().to_writer(writer)?;
             }
+            #[cfg(feature = "nist")]
             EcdsaPublicParams::P384 { p, .. } => {
                 p.as_ref().to_writer(writer)?;
             }
+            #[cfg(feature = "nist")]
             EcdsaPublicParams::P521 { p, .. } => {
                 p.as_ref().to_writer(writer)?;
             }
+            #[cfg(feature = "k256")]
             EcdsaPublicParams::Secp256k1 { p, .. } => {
                 p.as_ref().to_writer(writer)?;
             }
@@ -196,9 +453,9 @@ impl Serialize for PublicParams {
                 ref hash,
                 ref alg_sym,
             } => {
-                let oid = curve.oid();
+                let oid = curve.oid_bytes();
                 writer.write_all(&[oid.len() as u8])?;
-                writer.write_all(&oid)?;
+                writer.write_all(oid)?;
 
                 p.to_writer(writer)?;
 
@@ -221,9 +478,9 @@ impl Serialize for PublicParams {
                 y.to_writer(writer)?;
             }
             PublicParams::EdDSA { ref curve, ref q } => {
-                let oid = curve.oid();
+                let oid = curve.oid_bytes();
                 writer.write_all(&[oid.len() as u8])?;
-                writer.write_all(&oid)?;
+                writer.write_all(oid)?;
 
                 q.to_writer(writer)?;
             }
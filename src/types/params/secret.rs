@@ -91,7 +91,7 @@ fn parse_secret_fields(
                 let (i, sym_alg) = map_res(be_u8, SymmetricKeyAlgorithm::try_from)(i)?;
                 let (i, aead_mode) = map_res(be_u8, AeadAlgorithm::try_from)(i)?;
                 let (i, s2k) = s2k_parser(i)?;
-                let (i, nonce) = take(aead_mode.nonce_size())(i)?;
+                let (i, nonce) = s2k_iv(&s2k, aead_mode.nonce_size())(i)?;
                 (
                     i,
                     S2kParams::Aead {
@@ -106,7 +106,7 @@ fn parse_secret_fields(
             S2kUsage::Cfb => {
                 let (i, sym_alg) = map_res(be_u8, SymmetricKeyAlgorithm::try_from)(i)?;
                 let (i, s2k) = s2k_parser(i)?;
-                let (i, iv) = take(sym_alg.block_size())(i)?;
+                let (i, iv) = s2k_iv(&s2k, sym_alg.block_size())(i)?;
                 (
                     i,
                     S2kParams::Cfb {
@@ -119,7 +119,7 @@ fn parse_secret_fields(
             S2kUsage::MalleableCfb => {
                 let (i, sym_alg) = map_res(be_u8, SymmetricKeyAlgorithm::try_from)(i)?;
                 let (i, s2k) = s2k_parser(i)?;
-                let (i, iv) = take(sym_alg.block_size())(i)?;
+                let (i, iv) = s2k_iv(&s2k, sym_alg.block_size())(i)?;
                 (
                     i,
                     S2kParams::Cfb {
@@ -145,3 +145,16 @@ fn parse_secret_fields(
         Ok((i, res))
     }
 }
+
+/// Takes the IV or nonce of `len` bytes following `s2k`.
+///
+/// The parameters of an unknown S2K extend to the end of the packet, so the IV and the
+/// encrypted data are kept in them, and the key can still be serialized as it was.
+fn s2k_iv(s2k: &StringToKey, len: usize) -> impl Fn(&[u8]) -> IResult<&[u8], &[u8]> + '_ {
+    move |i: &[u8]| {
+        if s2k.is_opaque() {
+            return Ok((i, &[]));
+        }
+        take(len)(i)
+    }
+}
@@ -1,6 +1,8 @@
 use crate::crypto::hash::HashAlgorithm;
 use crate::errors::Result;
-use crate::types::{EcdsaPublicParams, Mpi, PublicKeyTrait, PublicParams};
+#[cfg(feature = "nist")]
+use crate::types::EcdsaPublicParams;
+use crate::types::{Mpi, PublicKeyTrait, PublicParams};
 
 pub trait SecretKeyTrait: PublicKeyTrait {
     type PublicKey;
@@ -24,15 +26,86 @@ pub trait SecretKeyTrait: PublicKeyTrait {
 
     /// The suggested hash algorithm to calculate the signature hash digest with, when using this
     /// key as a signer
+    ///
+    /// If the key restricts its [supported hash algorithms](SecretKeyTrait::supported_hash_algs)
+    /// and the default for its parameters is not among them, the first supported one is used.
     fn hash_alg(&self) -> HashAlgorithm {
-        match self.public_params() {
+        let default = match self.public_params() {
+            #[cfg(feature = "nist")]
             PublicParams::ECDSA(EcdsaPublicParams::P384 { .. }) => HashAlgorithm::SHA2_384,
+            #[cfg(feature = "nist")]
             PublicParams::ECDSA(EcdsaPublicParams::P521 { .. }) => HashAlgorithm::SHA2_512,
             _ => HashAlgorithm::default(),
+        };
+
+        match self.supported_hash_algs() {
+            Some(supported) if !supported.contains(&default) => {
+                supported.first().copied().unwrap_or(default)
+            }
+            _ => default,
+        }
+    }
+
+    /// The hash algorithms this key can create signatures with, `None` if it has no restrictions.
+    ///
+    /// Signers backed by hardware often only support a few digests, and should declare them here.
+    fn supported_hash_algs(&self) -> Option<&[HashAlgorithm]> {
+        None
+    }
+
+    /// Chooses the hash algorithm to create a signature with.
+    ///
+    /// An explicit choice of the caller takes precedence. Otherwise the first algorithm in
+    /// `policy` that the key supports is used, or [`SecretKeyTrait::hash_alg`] if the policy is
+    /// empty.
+    ///
+    /// Fails if the explicit choice is not supported by the key, or if the key supports none of
+    /// the algorithms the policy allows.
+    fn select_hash_alg(
+        &self,
+        explicit: Option<HashAlgorithm>,
+        policy: &[HashAlgorithm],
+    ) -> Result<HashAlgorithm> {
+        if let Some(hash_alg) = explicit {
+            ensure_supported_hash_alg(self, hash_alg)?;
+            return Ok(hash_alg);
+        }
+
+        if policy.is_empty() {
+            return Ok(self.hash_alg());
+        }
+
+        let supported = self.supported_hash_algs();
+        match policy
+            .iter()
+            .find(|alg| supported.map_or(true, |s| s.contains(alg)))
+        {
+            Some(hash_alg) => Ok(*hash_alg),
+            None => unsupported_err!(
+                "the signing key supports none of the hash algorithms {:?}",
+                policy
+            ),
         }
     }
 }
 
+/// Fails if `key` declares its supported hash algorithms, and `hash_alg` is not one of them.
+pub(crate) fn ensure_supported_hash_alg(
+    key: &(impl SecretKeyTrait + ?Sized),
+    hash_alg: HashAlgorithm,
+) -> Result<()> {
+    if let Some(supported) = key.supported_hash_algs() {
+        if !supported.contains(&hash_alg) {
+            unsupported_err!(
+                "hash algorithm {:?} is not supported by the signing key",
+                hash_alg
+            );
+        }
+    }
+
+    Ok(())
+}
+
 impl<'a, T: SecretKeyTrait> SecretKeyTrait for &'a T {
     type PublicKey = T::PublicKey;
     type Unlocked = T::Unlocked;
@@ -59,4 +132,12 @@ impl<'a, T: SecretKeyTrait> SecretKeyTrait for &'a T {
     fn public_params(&self) -> &PublicParams {
         (*self).public_params()
     }
+
+    fn hash_alg(&self) -> HashAlgorithm {
+        (*self).hash_alg()
+    }
+
+    fn supported_hash_algs(&self) -> Option<&[HashAlgorithm]> {
+        (*self).supported_hash_algs()
+    }
 }
@@ -1,7 +1,13 @@
 use zeroize::ZeroizeOnDrop;
 
+#[cfg(feature = "dsa")]
+use crate::crypto::dsa;
+#[cfg(feature = "ed25519")]
+use crate::crypto::eddsa;
+#[cfg(feature = "rsa")]
+use crate::crypto::rsa;
 use crate::crypto::sym::SymmetricKeyAlgorithm;
-use crate::crypto::{checksum, dsa, ecdh, ecdsa, eddsa, rsa, Decryptor};
+use crate::crypto::{checksum, ecdh, ecdsa, Decryptor};
 use crate::errors::Result;
 
 use super::Mpi;
@@ -10,10 +16,13 @@ use super::Mpi;
 #[allow(clippy::large_enum_variant)] // FIXME
 #[derive(Debug, ZeroizeOnDrop)]
 pub enum SecretKeyRepr {
+    #[cfg(feature = "rsa")]
     RSA(rsa::PrivateKey),
+    #[cfg(feature = "dsa")]
     DSA(dsa::SecretKey),
     ECDSA(ecdsa::SecretKey),
     ECDH(ecdh::SecretKey),
+    #[cfg(feature = "ed25519")]
     EdDSA(eddsa::SecretKey),
 }
 
@@ -23,13 +32,8 @@ impl SecretKeyRepr {
         mpis: &[Mpi],
         fingerprint: &[u8],
     ) -> Result<(Vec<u8>, SymmetricKeyAlgorithm)> {
-        let decrypted_key = match self {
-            SecretKeyRepr::RSA(ref priv_key) => priv_key.decrypt(mpis, fingerprint)?,
-            SecretKeyRepr::DSA(_) => bail!("DSA is only used for signing"),
-            SecretKeyRepr::ECDSA(_) => bail!("ECDSA is only used for signing"),
-            SecretKeyRepr::ECDH(ref priv_key) => priv_key.decrypt(mpis, fingerprint)?,
-            SecretKeyRepr::EdDSA(_) => unimplemented_err!("EdDSA"),
-        };
+        let decrypted_key = self.decrypt_raw(mpis, fingerprint)?;
+        ensure!(!decrypted_key.is_empty(), "invalid session key");
 
         let session_key_algorithm = SymmetricKeyAlgorithm::from(decrypted_key[0]);
         ensure!(
@@ -42,6 +46,7 @@ impl SecretKeyRepr {
         let (k, checksum) = match self {
             SecretKeyRepr::ECDH(_) => {
                 let dec_len = decrypted_key.len();
+                ensure!(dec_len > 3, "invalid session key");
                 (
                     &decrypted_key[1..dec_len - 2],
                     &decrypted_key[dec_len - 2..],
@@ -49,6 +54,10 @@ impl SecretKeyRepr {
             }
             _ => {
                 let key_size = session_key_algorithm.key_size();
+                ensure!(
+                    decrypted_key.len() >= key_size + 3,
+                    "invalid session key length"
+                );
                 (
                     &decrypted_key[1..=key_size],
                     &decrypted_key[key_size + 1..key_size + 3],
@@ -60,4 +69,30 @@ impl SecretKeyRepr {
 
         Ok((k.to_vec(), alg))
     }
+
+    /// Decrypts the session key of a v6 PKESK packet.
+    ///
+    /// Unlike in v3 packets, the session key is not prefixed with the symmetric algorithm.
+    pub fn decrypt_v6(&self, mpis: &[Mpi], fingerprint: &[u8]) -> Result<Vec<u8>> {
+        let decrypted_key = self.decrypt_raw(mpis, fingerprint)?;
+        ensure!(decrypted_key.len() > 2, "invalid session key");
+
+        let (k, checksum) = decrypted_key.split_at(decrypted_key.len() - 2);
+        checksum::simple(checksum, k)?;
+
+        Ok(k.to_vec())
+    }
+
+    fn decrypt_raw(&self, mpis: &[Mpi], fingerprint: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            #[cfg(feature = "rsa")]
+            SecretKeyRepr::RSA(ref priv_key) => priv_key.decrypt(mpis, fingerprint),
+            #[cfg(feature = "dsa")]
+            SecretKeyRepr::DSA(_) => bail!("DSA is only used for signing"),
+            SecretKeyRepr::ECDSA(_) => bail!("ECDSA is only used for signing"),
+            SecretKeyRepr::ECDH(ref priv_key) => priv_key.decrypt(mpis, fingerprint),
+            #[cfg(feature = "ed25519")]
+            SecretKeyRepr::EdDSA(_) => unimplemented_err!("EdDSA"),
+        }
+    }
 }
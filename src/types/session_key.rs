@@ -0,0 +1,75 @@
+use std::fmt;
+
+use zeroize::Zeroizing;
+
+use crate::crypto::sym::SymmetricKeyAlgorithm;
+
+/// A decrypted session key, used to decrypt the data of an encrypted message.
+///
+/// The key material is zeroed when dropped and not shown by `Debug`. Keys for SEIPDv1 and
+/// SED packets carry the symmetric algorithm they are used with, for SEIPDv2 packets the
+/// algorithm is stored in the packet instead.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SessionKey {
+    key: Zeroizing<Vec<u8>>,
+    sym_alg: Option<SymmetricKeyAlgorithm>,
+}
+
+impl SessionKey {
+    /// A session key for SEIPDv1 or SED packets, encrypted with `sym_alg`.
+    pub fn new(key: Vec<u8>, sym_alg: SymmetricKeyAlgorithm) -> Self {
+        SessionKey {
+            key: Zeroizing::new(key),
+            sym_alg: Some(sym_alg),
+        }
+    }
+
+    /// A session key for SEIPDv2 packets, which name their own symmetric algorithm.
+    pub fn new_v2(key: Vec<u8>) -> Self {
+        SessionKey {
+            key: Zeroizing::new(key),
+            sym_alg: None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// The symmetric algorithm, `None` for SEIPDv2 session keys.
+    pub fn sym_alg(&self) -> Option<SymmetricKeyAlgorithm> {
+        self.sym_alg
+    }
+}
+
+impl fmt::Debug for SessionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionKey")
+            .field("key", &"[..]")
+            .field("sym_alg", &self.sym_alg)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::composed::PlainSessionKey;
+
+    #[test]
+    fn test_from_plain_session_key() {
+        let key = SessionKey::from(PlainSessionKey::V4 {
+            sym_alg: SymmetricKeyAlgorithm::AES128,
+            key: vec![1; 16],
+        });
+        assert_eq!(key.as_bytes(), &[1; 16]);
+        assert_eq!(key.sym_alg(), Some(SymmetricKeyAlgorithm::AES128));
+
+        let key = SessionKey::from(PlainSessionKey::V6 { key: vec![2; 32] });
+        assert_eq!(key, SessionKey::new_v2(vec![2; 32]));
+        assert_eq!(key.sym_alg(), None);
+
+        // the key material is not printed
+        assert!(!format!("{:?}", key).contains('2'));
+    }
+}
@@ -1,9 +1,9 @@
 use std::io;
 
 use crate::errors::Result;
-use crate::packet::{write_packet, Signature, UserAttribute, UserId};
+use crate::packet::{write_packet_as, Signature, SignatureType, UserAttribute, UserId};
 use crate::ser::Serialize;
-use crate::types::{PublicKeyTrait, Tag};
+use crate::types::{PublicKeyTrait, Tag, Version};
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct SignedUser {
@@ -62,19 +62,49 @@ impl SignedUser {
     pub fn is_primary(&self) -> bool {
         self.signatures.iter().any(Signature::is_primary)
     }
+
+    /// Returns the newest self-signature of this user id made by `key`, ordered by signature
+    /// creation time.
+    ///
+    /// A newer self-signature supersedes older ones, so this is the signature that determines
+    /// key flags, expiration and preferences. Revocations, certifications by other keys and
+    /// signatures that do not verify are not considered.
+    pub fn latest_signature(&self, key: &impl PublicKeyTrait) -> Option<&Signature> {
+        let mut sigs: Vec<_> = self
+            .signatures
+            .iter()
+            .filter(|sig| sig.typ() != SignatureType::CertRevocation)
+            .collect();
+        // stable, so on equal creation times the signature that comes last wins
+        sigs.sort_by_key(|sig| sig.created());
+
+        sigs.into_iter()
+            .rev()
+            .find(|sig| sig.verify_certification(key, Tag::UserId, &self.id).is_ok())
+    }
 }
 
-impl Serialize for SignedUser {
-    fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<()> {
-        write_packet(writer, &self.id)?;
+impl SignedUser {
+    pub(crate) fn to_writer_with_version<W: io::Write>(
+        &self,
+        writer: &mut W,
+        packet_version: Option<Version>,
+    ) -> Result<()> {
+        write_packet_as(writer, &self.id, packet_version)?;
         for sig in &self.signatures {
-            write_packet(writer, sig)?;
+            write_packet_as(writer, sig, packet_version)?;
         }
 
         Ok(())
     }
 }
 
+impl Serialize for SignedUser {
+    fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<()> {
+        self.to_writer_with_version(writer, None)
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct SignedUserAttribute {
     pub attr: UserAttribute,
@@ -138,13 +168,23 @@ impl SignedUserAttribute {
     }
 }
 
-impl Serialize for SignedUserAttribute {
-    fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<()> {
-        write_packet(writer, &self.attr)?;
+impl SignedUserAttribute {
+    pub(crate) fn to_writer_with_version<W: io::Write>(
+        &self,
+        writer: &mut W,
+        packet_version: Option<Version>,
+    ) -> Result<()> {
+        write_packet_as(writer, &self.attr, packet_version)?;
         for sig in &self.signatures {
-            write_packet(writer, sig)?;
+            write_packet_as(writer, sig, packet_version)?;
         }
 
         Ok(())
     }
 }
+
+impl Serialize for SignedUserAttribute {
+    fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<()> {
+        self.to_writer_with_version(writer, None)
+    }
+}
@@ -82,10 +82,8 @@ pub fn strip_leading_zeros(bytes: &[u8]) -> &[u8] {
 
 #[inline]
 pub fn strip_leading_zeros_vec(bytes: &mut Vec<u8>) {
-    if let Some(offset) = bytes.iter_mut().position(|b| b != &0) {
-        for i in 0..offset {
-            bytes.remove(i);
-        }
+    if let Some(offset) = bytes.iter().position(|b| b != &0) {
+        bytes.drain(..offset);
     }
 }
 
@@ -205,6 +203,23 @@ impl<'a, A: hash::Hasher, B: io::Write> io::Write for TeeWriter<'a, A, B> {
     }
 }
 
+/// Counts the bytes written to it, without keeping them.
+///
+/// Used to find the serialized length of a value, without allocating a buffer for it.
+#[derive(Debug, Default)]
+pub(crate) struct ByteCounter(pub usize);
+
+impl io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
 /// The same as the std lib, but doesn't choke on write 0. This is a hack, to be compatible with
 /// rust-base64.
 pub fn write_all(writer: &mut impl io::Write, mut buf: &[u8]) -> io::Result<()> {
@@ -238,4 +253,40 @@ mod tests {
         write_packet_length(12870, &mut res).unwrap();
         assert_eq!(hex::encode(res), "ff00003246");
     }
+
+    #[test]
+    fn test_packet_length_boundaries() {
+        for (len, expected) in [
+            (191, "bf"),
+            (192, "c000"),
+            (8383, "dfff"),
+            (8384, "ff000020c0"),
+            (65535, "ff0000ffff"),
+        ] {
+            let mut res = Vec::new();
+            write_packet_length(len, &mut res).unwrap();
+            assert_eq!(hex::encode(&res), expected, "{len}");
+
+            let (rest, parsed) = packet_length(&res).unwrap();
+            assert!(rest.is_empty());
+            assert_eq!(parsed, len);
+        }
+    }
+
+    #[test]
+    fn test_strip_leading_zeros_vec() {
+        for (input, expected) in [
+            (vec![], vec![]),
+            (vec![0], vec![0]),
+            (vec![1, 0], vec![1, 0]),
+            (vec![0, 1, 2], vec![1, 2]),
+            (vec![0, 0, 3, 4], vec![3, 4]),
+            (vec![0, 0, 0, 0x80, 0], vec![0x80, 0]),
+        ] {
+            let mut v = input.clone();
+            strip_leading_zeros_vec(&mut v);
+            assert_eq!(v, expected, "{input:?}");
+            assert_eq!(strip_leading_zeros(&input), &expected[..]);
+        }
+    }
 }
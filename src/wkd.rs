@@ -0,0 +1,75 @@
+//! # OpenPGP Web Key Directory
+//!
+//! Helpers to build the URLs keys are looked up with in a
+//! [Web Key Directory](https://datatracker.ietf.org/doc/draft-koch-openpgp-webkey-service/).
+
+use sha1::{Digest, Sha1};
+
+/// The alphabet of the human-oriented base-32 encoding (z-base-32).
+const ZBASE32_ALPHABET: &[u8; 32] = b"ybndrfg8ejkmcpqxot1uwisza345h769";
+
+/// Computes the hash of the local-part of an email address, as used in WKD URLs.
+///
+/// Uppercase ASCII letters are mapped to lowercase, the result is hashed with SHA-1 and encoded
+/// with z-base-32. The same hash is used by the direct and the advanced method.
+///
+/// ```
+/// assert_eq!(
+///     pgp::wkd::local_part_hash("Joe.Doe"),
+///     "iy9q119eutrkn8s1mk4r39qejnbu3n5q"
+/// );
+/// ```
+pub fn local_part_hash(local_part: &str) -> String {
+    let digest = Sha1::digest(local_part.to_ascii_lowercase().as_bytes());
+    zbase32_encode(&digest)
+}
+
+/// Encodes `data` with z-base-32, the final group of bits is padded with zeros.
+fn zbase32_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity((data.len() * 8 + 4) / 5);
+    let mut buffer = 0u16;
+    let mut bits = 0;
+
+    for byte in data {
+        buffer = (buffer << 8) | u16::from(*byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(char::from(
+                ZBASE32_ALPHABET[usize::from((buffer >> bits) & 0x1f)],
+            ));
+        }
+    }
+
+    if bits > 0 {
+        out.push(char::from(
+            ZBASE32_ALPHABET[usize::from((buffer << (5 - bits)) & 0x1f)],
+        ));
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zbase32_encode() {
+        assert_eq!(zbase32_encode(b""), "");
+        assert_eq!(zbase32_encode(&[0x00]), "yy");
+        assert_eq!(zbase32_encode(&[0xff]), "9h");
+        assert_eq!(zbase32_encode(&[0xf0, 0xbf, 0xc7]), "6n9hq");
+    }
+
+    #[test]
+    fn test_local_part_hash() {
+        // example from the draft
+        assert_eq!(
+            local_part_hash("Joe.Doe"),
+            "iy9q119eutrkn8s1mk4r39qejnbu3n5q"
+        );
+        assert_eq!(local_part_hash("joe.doe"), local_part_hash("JOE.DOE"));
+        assert_eq!(local_part_hash("alice").len(), 32);
+    }
+}
@@ -0,0 +1,91 @@
+//! Counts the heap allocations of hot paths, using a counting global allocator.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::fs::File;
+
+use pgp::composed::{Deserializable, SignedPublicKey};
+use pgp::types::KeyTrait;
+
+struct CountingAllocator;
+
+thread_local! {
+    // Counted per thread, as the tests run in parallel.
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Runs `f`, returning its result and the number of allocations it made on this thread.
+fn count_allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let res = f();
+    let after = ALLOCATIONS.with(Cell::get);
+
+    (res, after - before)
+}
+
+#[test]
+fn fingerprint_allocations() {
+    let keys = [
+        "e2e-001",
+        "gnupg-v1-001",
+        "gnupg-v1-003",
+        "openkeychain-001",
+        "subkey-001",
+        "uid-001",
+    ];
+
+    for name in keys {
+        let dir = "./tests/opengpg-interop/testcases/keys";
+        let json: serde_json::Value =
+            serde_json::from_reader(File::open(format!("{dir}/{name}.json")).unwrap()).unwrap();
+        let (key, _) =
+            SignedPublicKey::from_armor_single(File::open(format!("{dir}/{name}.asc")).unwrap())
+                .unwrap();
+
+        // only the returned fingerprint is allocated
+        let (fingerprint, allocations) = count_allocations(|| key.primary_key.fingerprint());
+        assert_eq!(allocations, 1, "{name}");
+        assert_eq!(json["expected_fingerprint"], hex::encode(fingerprint));
+
+        let expected_subkeys = json["expected_subkeys"].as_array();
+        for (i, subkey) in key.public_subkeys.iter().enumerate() {
+            let (fingerprint, allocations) = count_allocations(|| subkey.key.fingerprint());
+            assert_eq!(allocations, 1, "{name} subkey {i}");
+            if let Some(expected) = expected_subkeys.and_then(|subkeys| subkeys.get(i)) {
+                assert_eq!(expected["expected_fingerprint"], hex::encode(fingerprint));
+            }
+        }
+    }
+}
+
+#[test]
+fn fingerprint_allocations_ecc() {
+    for file in [
+        "./tests/autocrypt/alice@autocrypt.example.pub.asc",
+        "./tests/openpgpjs/x25519.pub.asc",
+        "./tests/draft-bre-openpgp-samples-00/bob.pub.asc",
+    ] {
+        let (key, _) = SignedPublicKey::from_armor_single(File::open(file).unwrap()).unwrap();
+
+        let (_, allocations) = count_allocations(|| key.fingerprint());
+        assert_eq!(allocations, 1, "{file}");
+        for subkey in &key.public_subkeys {
+            let (_, allocations) = count_allocations(|| subkey.fingerprint());
+            assert_eq!(allocations, 1, "{file}");
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! Tests that hold for any selection of crate features.
+//!
+//! Meant to be run with a minimal selection as well, e.g. for a verify only build:
+//! `cargo test --no-default-features --features ed25519,sha2 --test features_test`
+
+#[cfg(feature = "ed25519")]
+use std::fs::File;
+
+#[cfg(any(feature = "ed25519", not(feature = "rsa")))]
+use pgp::composed::cleartext::CleartextSignedMessage;
+#[cfg(any(feature = "ed25519", not(feature = "compression-deflate")))]
+use pgp::composed::Message;
+#[cfg(any(feature = "ed25519", not(feature = "rsa")))]
+use pgp::composed::{Deserializable, SignedSecretKey};
+#[cfg(feature = "ed25519")]
+use pgp::types::{KeyId, KeyTrait, SecretKeyTrait};
+
+#[cfg(feature = "ed25519")]
+fn alice() -> SignedSecretKey {
+    let (key, _) = SignedSecretKey::from_armor_single(
+        File::open("./tests/unit-tests/text_signature_normalization_alice.key").unwrap(),
+    )
+    .unwrap();
+    key
+}
+
+#[test]
+#[cfg(feature = "ed25519")]
+fn ed25519_verify_message() {
+    let (msg, _) = Message::from_armor_single(
+        File::open("./tests/unit-tests/text_signature_normalization.msg").unwrap(),
+    )
+    .unwrap();
+
+    let key = alice();
+    let signing = key
+        .secret_subkeys
+        .iter()
+        .find(|key| key.key_id() == KeyId::from_slice(&hex_id("64357EB6BB55DE12")).unwrap())
+        .unwrap();
+
+    msg.verify(&signing.public_key()).unwrap();
+    assert_eq!(
+        msg.get_content().unwrap().unwrap(),
+        b"foo\nbar\r\nbaz".to_vec()
+    );
+}
+
+#[test]
+#[cfg(feature = "ed25519")]
+fn ed25519_sign_cleartext() {
+    let key = alice();
+
+    let msg = CleartextSignedMessage::sign("firmware 1.2.3\n", &key, String::new).unwrap();
+    let armored = msg.to_armored_string(Default::default()).unwrap();
+
+    let (msg, _) = CleartextSignedMessage::from_string(&armored).unwrap();
+    msg.verify(&key.public_key()).unwrap();
+}
+
+#[test]
+#[cfg(not(feature = "rsa"))]
+fn rsa_disabled() {
+    let (key, _) = SignedSecretKey::from_string(
+        &std::fs::read_to_string("./tests/unit-tests/cleartext-key-01.asc").unwrap(),
+    )
+    .unwrap();
+    let (msg, _) = CleartextSignedMessage::from_string(
+        &std::fs::read_to_string("./tests/unit-tests/cleartext-msg-01.asc").unwrap(),
+    )
+    .unwrap();
+
+    // the key and the message are still parsed, only the signature can not be checked
+    assert!(matches!(
+        msg.verify_many(|_, sig, text| sig.verify(&key, text)),
+        Err(pgp::errors::Error::Unsupported(_))
+    ));
+}
+
+#[test]
+#[cfg(not(feature = "compression-deflate"))]
+fn compression_disabled() {
+    use pgp::crypto::sym::SymmetricKeyAlgorithm;
+    use pgp::packet::CompressedData;
+    use pgp::types::CompressionAlgorithm;
+
+    let msg = Message::Compressed(CompressedData::from_compressed(
+        CompressionAlgorithm::ZLIB,
+        vec![0x78, 0x9c, 0x03, 0x00, 0x00, 0x00, 0x00, 0x01],
+    ));
+    assert!(matches!(
+        msg.decompress(),
+        Err(pgp::errors::Error::Unsupported(_))
+    ));
+
+    let literal = Message::new_literal("", "hello");
+    assert!(matches!(
+        literal.compress(CompressionAlgorithm::ZIP),
+        Err(pgp::errors::Error::Unsupported(_))
+    ));
+
+    // messages are built without compression
+    let msg = pgp::encrypt_with_password(
+        &mut rand::thread_rng(),
+        b"hello",
+        "password",
+        SymmetricKeyAlgorithm::AES128,
+        None,
+    )
+    .unwrap();
+    assert_eq!(
+        pgp::decrypt_with_password(&msg, "password").unwrap(),
+        b"hello"
+    );
+}
+
+#[cfg(feature = "ed25519")]
+fn hex_id(id: &str) -> Vec<u8> {
+    hex::decode(id).unwrap()
+}
@@ -3,14 +3,11 @@ use std::fmt::Debug;
 use std::fs::File;
 
 use pgp::crypto::checksum;
-use pgp::crypto::ecc_curve::ECCCurve;
-use pgp::crypto::hash::HashAlgorithm;
-use pgp::crypto::public_key::PublicKeyAlgorithm;
-use pgp::crypto::sym::SymmetricKeyAlgorithm;
-use pgp::packet::{PacketTrait, PublicKey, SignatureConfig};
-use pgp::types::{KeyId, KeyTrait, Mpi, PublicKeyTrait, PublicParams, SecretKeyTrait};
-use pgp::{packet, Deserializable, Esk};
-use pgp::{Message, SignedPublicKey};
+use pgp::errors::Result;
+use pgp::packet::{self, SignatureConfig};
+use pgp::prelude::*;
+use pgp::types::{Mpi, PublicParams};
+use pgp::Esk;
 use rand::{CryptoRng, Rng};
 
 #[derive(Debug, Clone)]
@@ -22,17 +19,25 @@ pub struct FakeHsm {
 
     // data to card -> data from card
     sign_data: Option<(&'static [u8], &'static [u8])>,
+
+    // digests the card can sign
+    hash_algs: Option<&'static [HashAlgorithm]>,
 }
 
 impl FakeHsm {
-    pub fn with_public_key(public_key: PublicKey) -> Result<Self, pgp::errors::Error> {
+    pub fn with_public_key(public_key: PublicKey) -> Result<Self> {
         Ok(Self {
             public_key,
             decrypt_data: None,
             sign_data: None,
+            hash_algs: None,
         })
     }
 
+    pub fn set_supported_hash_algs(&mut self, hash_algs: &'static [HashAlgorithm]) {
+        self.hash_algs = Some(hash_algs);
+    }
+
     pub fn set_fake_decryption_data(
         &mut self,
         input: &'static [&'static [u8]],
@@ -66,24 +71,15 @@ impl KeyTrait for FakeHsm {
 }
 
 impl PublicKeyTrait for FakeHsm {
-    fn verify_signature(
-        &self,
-        hash: HashAlgorithm,
-        data: &[u8],
-        sig: &[Mpi],
-    ) -> pgp::errors::Result<()> {
+    fn verify_signature(&self, hash: HashAlgorithm, data: &[u8], sig: &[Mpi]) -> Result<()> {
         self.public_key.verify_signature(hash, data, sig)
     }
 
-    fn encrypt<R: CryptoRng + Rng>(
-        &self,
-        rng: &mut R,
-        plain: &[u8],
-    ) -> pgp::errors::Result<Vec<Mpi>> {
+    fn encrypt<R: CryptoRng + Rng>(&self, rng: &mut R, plain: &[u8]) -> Result<Vec<Mpi>> {
         self.public_key.encrypt(rng, plain)
     }
 
-    fn to_writer_old(&self, writer: &mut impl std::io::Write) -> pgp::errors::Result<()> {
+    fn to_writer_old(&self, writer: &mut impl std::io::Write) -> Result<()> {
         self.public_key.to_writer_old(writer)
     }
 }
@@ -96,20 +92,15 @@ impl SecretKeyTrait for FakeHsm {
 
     type Unlocked = Self;
 
-    fn unlock<F, G, T>(&self, _pw: F, work: G) -> pgp::errors::Result<T>
+    fn unlock<F, G, T>(&self, _pw: F, work: G) -> Result<T>
     where
         F: FnOnce() -> String,
-        G: FnOnce(&Self::Unlocked) -> pgp::errors::Result<T>,
+        G: FnOnce(&Self::Unlocked) -> Result<T>,
     {
         work(self)
     }
 
-    fn create_signature<F>(
-        &self,
-        _key_pw: F,
-        _hash: HashAlgorithm,
-        data: &[u8],
-    ) -> pgp::errors::Result<Vec<Mpi>>
+    fn create_signature<F>(&self, _key_pw: F, _hash: HashAlgorithm, data: &[u8]) -> Result<Vec<Mpi>>
     where
         F: FnOnce() -> String,
     {
@@ -152,10 +143,14 @@ impl SecretKeyTrait for FakeHsm {
     fn public_params(&self) -> &PublicParams {
         self.public_key.public_params()
     }
+
+    fn supported_hash_algs(&self) -> Option<&[HashAlgorithm]> {
+        self.hash_algs
+    }
 }
 
 impl FakeHsm {
-    pub fn decrypt(&self, mpis: &[Mpi]) -> pgp::errors::Result<(Vec<u8>, SymmetricKeyAlgorithm)> {
+    pub fn decrypt(&self, mpis: &[Mpi]) -> Result<(Vec<u8>, SymmetricKeyAlgorithm)> {
         assert_eq!(
             mpis.iter().map(|m| m.as_bytes()).collect::<Vec<_>>(),
             self.decrypt_data.unwrap().0
@@ -331,15 +326,7 @@ fn card_decrypt() {
 
         // Transform subkey packet into primary key packet
         // (This is a hack: FakeHsm wants a primary key packet)
-        let as_primary = PublicKey::new(
-            enc_subkey.packet_version(),
-            enc_subkey.version(),
-            enc_subkey.algorithm(),
-            *enc_subkey.created_at(),
-            enc_subkey.expiration(),
-            enc_subkey.public_params().clone(),
-        )
-        .unwrap();
+        let as_primary = enc_subkey.rebind_as_primary();
 
         let mut hsm = FakeHsm::with_public_key(as_primary).unwrap();
         hsm.set_fake_decryption_data(input, out);
@@ -375,10 +362,12 @@ fn card_decrypt() {
     }
 }
 
+#[cfg(feature = "rsa")]
 const SIGN_RSA_IN: &[u8] = &[
     0xb4, 0x8f, 0x7e, 0x1a, 0x7e, 0x38, 0x38, 0xad, 0x80, 0xcb, 0xc6, 0x10, 0xd9, 0x10, 0xb0, 0x64,
     0xf7, 0x08, 0xa6, 0x7c, 0x64, 0x3c, 0x8e, 0x6c, 0x92, 0x40, 0x75, 0xc4, 0x99, 0xe5, 0xeb, 0x06,
 ];
+#[cfg(feature = "rsa")]
 const SIGN_RSA_OUT: &[u8] = &[
     0x03, 0x8e, 0x16, 0x3a, 0x5e, 0x27, 0x06, 0x63, 0x45, 0xd6, 0xad, 0x0c, 0xcf, 0xe8, 0xd7, 0x91,
     0xc6, 0x20, 0x86, 0x3f, 0x82, 0x63, 0x66, 0x7e, 0x87, 0x7c, 0x5b, 0xd8, 0x9d, 0x9e, 0x50, 0xc0,
@@ -429,6 +418,7 @@ const SIGN_ECC_OUT: &[u8] = &[
 #[test]
 fn card_sign() {
     let cases = [
+        #[cfg(feature = "rsa")]
         (
             // RSA test case
             "tests/unit-tests/hsm/alice-rsa4096.priv",
@@ -479,3 +469,108 @@ fn card_sign() {
         signature.verify(&pubkey, DATA).expect("ok");
     }
 }
+
+#[test]
+#[cfg(feature = "rsa")]
+fn card_sign_digest() {
+    let key_file = File::open("tests/unit-tests/hsm/alice-rsa4096.priv").unwrap();
+    let (mut x, _) = pgp::composed::signed_key::from_reader_many(key_file).unwrap();
+    let key = x.next().unwrap().unwrap().into_secret();
+    let pubkey: SignedPublicKey = key.into();
+
+    let config = SignatureConfig::new_v4(
+        packet::SignatureVersion::V4,
+        packet::SignatureType::Binary,
+        pubkey.primary_key.algorithm(),
+        HashAlgorithm::SHA2_256,
+        vec![
+            packet::Subpacket::regular(packet::SubpacketData::SignatureCreationTime(
+                DateTime::<Utc>::from_timestamp(1711230710, 0).unwrap(),
+            )),
+            packet::Subpacket::regular(packet::SubpacketData::Issuer(pubkey.key_id())),
+        ],
+        vec![],
+    );
+
+    // the digest the card is asked to sign in `card_sign`
+    let digest = config.digest(&b"Hello World"[..]).unwrap();
+    assert_eq!(digest, SIGN_RSA_IN);
+    assert_eq!(digest.len(), HashAlgorithm::SHA2_256.digest_size());
+
+    // a signature assembled from the card output verifies
+    let signature =
+        packet::Signature::from_config(config, [digest[0], digest[1]], vec![SIGN_RSA_OUT.into()]);
+    signature.verify(&pubkey, &b"Hello World"[..]).unwrap();
+}
+
+#[test]
+#[cfg(feature = "rsa")]
+fn card_sign_hash_negotiation() {
+    let key_file = File::open("tests/unit-tests/hsm/alice-rsa4096.priv").unwrap();
+    let (mut x, _) = pgp::composed::signed_key::from_reader_many(key_file).unwrap();
+    let key = x.next().unwrap().unwrap().into_secret();
+    let pubkey: SignedPublicKey = key.into();
+
+    let mut hsm = FakeHsm::with_public_key(pubkey.primary_key.clone()).unwrap();
+    hsm.set_fake_signing_data(SIGN_RSA_IN, SIGN_RSA_OUT);
+
+    // without restrictions, the policy decides
+    assert_eq!(
+        hsm.select_hash_alg(None, &[HashAlgorithm::SHA2_512])
+            .unwrap(),
+        HashAlgorithm::SHA2_512
+    );
+
+    hsm.set_supported_hash_algs(&[HashAlgorithm::SHA2_256]);
+    assert_eq!(hsm.hash_alg(), HashAlgorithm::SHA2_256);
+
+    // explicit choice > policy intersected with the card > key default
+    assert_eq!(
+        hsm.select_hash_alg(Some(HashAlgorithm::SHA2_256), &[HashAlgorithm::SHA2_512])
+            .unwrap(),
+        HashAlgorithm::SHA2_256
+    );
+    assert_eq!(
+        hsm.select_hash_alg(None, &[HashAlgorithm::SHA2_512, HashAlgorithm::SHA2_256])
+            .unwrap(),
+        HashAlgorithm::SHA2_256
+    );
+    assert_eq!(
+        hsm.select_hash_alg(None, &[]).unwrap(),
+        HashAlgorithm::SHA2_256
+    );
+
+    // the card can not satisfy these
+    assert!(hsm
+        .select_hash_alg(Some(HashAlgorithm::SHA2_512), &[])
+        .is_err());
+    assert!(hsm
+        .select_hash_alg(None, &[HashAlgorithm::SHA2_512])
+        .is_err());
+
+    let config = |hash_alg| {
+        SignatureConfig::new_v4(
+            packet::SignatureVersion::V4,
+            packet::SignatureType::Binary,
+            hsm.public_key().algorithm(),
+            hash_alg,
+            vec![
+                packet::Subpacket::regular(packet::SubpacketData::SignatureCreationTime(
+                    DateTime::<Utc>::from_timestamp(1711230710, 0).unwrap(),
+                )),
+                packet::Subpacket::regular(packet::SubpacketData::Issuer(hsm.key_id())),
+            ],
+            vec![],
+        )
+    };
+
+    // refused before the card is asked to sign
+    assert!(config(HashAlgorithm::SHA2_512)
+        .sign(&hsm, String::new, &b"Hello World"[..])
+        .is_err());
+
+    let signature = config(hsm.select_hash_alg(None, &[]).unwrap())
+        .sign(&hsm, String::new, &b"Hello World"[..])
+        .unwrap();
+    signature.verify(&pubkey, &b"Hello World"[..]).unwrap();
+}
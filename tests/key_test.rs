@@ -10,11 +10,16 @@ use std::io::Read;
 use std::path::Path;
 
 use buffer_redux::BufReader;
+#[cfg(any(feature = "rsa", feature = "x25519"))]
 use chrono::{DateTime, Utc};
+#[cfg(feature = "rsa")]
 use num_bigint::BigUint;
+#[cfg(feature = "rsa")]
 use num_traits::ToPrimitive;
 use pgp::armor;
+#[cfg(any(feature = "rsa", feature = "x25519"))]
 use rand::thread_rng;
+#[cfg(feature = "rsa")]
 use rsa::{
     traits::{PrivateKeyParts, PublicKeyParts},
     RsaPrivateKey, RsaPublicKey,
@@ -23,21 +28,32 @@ use smallvec::SmallVec;
 
 use pgp::composed::signed_key::*;
 use pgp::composed::Deserializable;
+#[cfg(any(feature = "nist", feature = "k256"))]
 use pgp::crypto::ecdsa::SecretKey as ECDSASecretKey;
 use pgp::crypto::{
     ecc_curve::ECCCurve, hash::HashAlgorithm, public_key::PublicKeyAlgorithm,
     sym::SymmetricKeyAlgorithm,
 };
 use pgp::errors::Error;
+#[cfg(any(feature = "rsa", feature = "x25519"))]
+use pgp::packet::Signature;
 use pgp::packet::{
-    KeyFlags, Signature, SignatureType, SignatureVersion, Subpacket, SubpacketData, UserAttribute,
-    UserId,
+    KeyFlags, SignatureType, SignatureVersion, Subpacket, SubpacketData, UserAttribute, UserId,
 };
 use pgp::ser::Serialize;
+#[cfg(any(
+    feature = "rsa",
+    feature = "nist",
+    feature = "k256",
+    feature = "ed25519"
+))]
+use pgp::types::SecretKeyRepr;
 use pgp::types::{
-    CompressionAlgorithm, KeyId, KeyTrait, KeyVersion, Mpi, PublicParams, S2kParams, SecretKeyRepr,
-    SecretKeyTrait, SecretParams, SignedUser, StringToKey, Version,
+    CompressionAlgorithm, KeyId, KeyTrait, KeyVersion, PublicParams, SecretKeyTrait, SignedUser,
+    SignedUserAttribute,
 };
+#[cfg(feature = "rsa")]
+use pgp::types::{Mpi, S2kParams, SecretParams, StringToKey, Version};
 
 fn read_file<P: AsRef<Path> + ::std::fmt::Debug>(path: P) -> File {
     // Open the path in read-only mode, returns `io::Result<File>`
@@ -49,6 +65,7 @@ fn read_file<P: AsRef<Path> + ::std::fmt::Debug>(path: P) -> File {
     }
 }
 
+#[cfg(all(feature = "rsa", feature = "dsa"))]
 fn get_test_key(name: &str) -> File {
     read_file(Path::new("./tests/opengpg-interop/testcases/keys").join(name))
 }
@@ -235,6 +252,7 @@ parse_dumps!(
 );
 
 #[test]
+#[cfg(all(feature = "rsa", feature = "dsa"))]
 fn test_parse_gnupg_v1() {
     let _ = pretty_env_logger::try_init();
 
@@ -268,6 +286,7 @@ fn test_parse_gnupg_v1() {
 }
 
 #[test]
+#[cfg(feature = "rsa")]
 fn test_parse_openpgp_sample_rsa_private() {
     let p = Path::new("./tests/openpgp/samplekeys/rsa-primary-auth-only.sec.asc");
     let mut file = read_file(p.to_path_buf());
@@ -331,6 +350,7 @@ fn test_parse_openpgp_sample_rsa_private() {
 }
 
 #[test]
+#[cfg(feature = "rsa")]
 fn test_parse_details() {
     let _ = pretty_env_logger::try_init();
 
@@ -618,6 +638,7 @@ fn test_parse_details() {
 }
 
 #[test]
+#[cfg(feature = "rsa")]
 fn encrypted_private_key() {
     let p = Path::new("./tests/opengpg-interop/testcases/messages/gnupg-v1-001-decrypt.asc");
     let mut file = read_file(p.to_path_buf());
@@ -705,6 +726,7 @@ fn get_test_fingerprint(filename: &str) -> (serde_json::Value, SignedPublicKey)
 }
 
 #[test]
+#[cfg(feature = "rsa")]
 fn test_fingerprint_rsa() {
     let (json, key) = get_test_fingerprint("gnupg-v1-003");
     assert_eq!(json["expected_fingerprint"], hex::encode(key.fingerprint()));
@@ -729,6 +751,7 @@ fn test_fingerprint_ecdsa() {
 }
 
 #[test]
+#[cfg(feature = "dsa")]
 fn test_fingerprint_ecdh() {
     let (json, key) = get_test_fingerprint("gnupg-v1-001");
     key.verify().expect("invalid key");
@@ -873,12 +896,14 @@ macro_rules! openpgp_key {
     };
 }
 
+#[cfg(feature = "rsa")]
 openpgp_key!(
     key_openpgp_samplekeys_e6,
     "samplekeys/E657FB607BB4F21C90BB6651BC067AF28BC90111.asc",
     true,
     true
 );
+#[cfg(feature = "rsa")]
 openpgp_key!(
     key_openpgp_samplekeys_authenticate_only_pub,
     "samplekeys/authenticate-only.pub.asc",
@@ -892,12 +917,14 @@ openpgp_key!(
     true,
     ""
 );
+#[cfg(feature = "rsa")]
 openpgp_key!(
     key_openpgp_samplekeys_dda252ebb8ebe1af_1,
     "samplekeys/dda252ebb8ebe1af-1.asc",
     true,
     true
 );
+#[cfg(feature = "rsa")]
 openpgp_key!(
     key_openpgp_samplekeys_dda252ebb8ebe1af_2,
     "samplekeys/dda252ebb8ebe1af-2.asc",
@@ -917,12 +944,14 @@ openpgp_key!(
     false, // packet lengths are not minimally encoded
     "a"
 );
+#[cfg(feature = "nist")]
 openpgp_key!(
     key_openpgp_samplekeys_ecc_sample_1_pub,
     "samplekeys/ecc-sample-1-pub.asc",
     true,
     true
 );
+#[cfg(feature = "nist")]
 openpgp_key!(
     key_openpgp_samplekeys_ecc_sample_1_sec,
     "samplekeys/ecc-sample-1-sec.asc",
@@ -930,12 +959,14 @@ openpgp_key!(
     true,
     "ecc"
 );
+#[cfg(feature = "nist")]
 openpgp_key!(
     key_openpgp_samplekeys_ecc_sample_2_pub,
     "samplekeys/ecc-sample-2-pub.asc",
     true,
     false // packet lengths are not minimally encoded
 );
+#[cfg(feature = "nist")]
 openpgp_key!(
     key_openpgp_samplekeys_ecc_sample_2_sec,
     "samplekeys/ecc-sample-2-sec.asc",
@@ -956,12 +987,14 @@ openpgp_key!(
     false, // unsigned subkey & userid
     "ecc"
 );
+#[cfg(feature = "k256")]
 openpgp_key!(
     key_openpgp_samplekeys_ecc_sample_4_pub,
     "samplekeys/ecc-sample-4-pub.asc",
     true,
     false
 );
+#[cfg(feature = "k256")]
 openpgp_key!(
     key_openpgp_samplekeys_ecc_sample_4_sec,
     "samplekeys/ecc-sample-4-sec.asc",
@@ -988,6 +1021,7 @@ openpgp_key!(
     true,
     "abc"
 );
+#[cfg(feature = "rsa")]
 openpgp_key!(
     key_openpgp_samplekeys_issue2346,
     "samplekeys/issue2346.gpg",
@@ -999,18 +1033,21 @@ openpgp_key_bin!(
     "samplekeys/no-creation-time.gpg",
     false
 );
+#[cfg(feature = "rsa")]
 openpgp_key!(
     key_openpgp_samplekeys_rsa_primary_auth_only_pub,
     "samplekeys/rsa-primary-auth-only.pub.asc",
     true,
     true
 );
+#[cfg(feature = "rsa")]
 openpgp_key!(
     key_openpgp_samplekeys_rsa_primary_auth_only_sec,
     "samplekeys/rsa-primary-auth-only.sec.asc",
     true,
     true
 );
+#[cfg(feature = "rsa")]
 openpgp_key!(
     key_openpgp_samplekeys_rsa_rsa_sample_1,
     "samplekeys/rsa-rsa-sample-1.asc",
@@ -1018,6 +1055,7 @@ openpgp_key!(
     true,
     "ecc"
 );
+#[cfg(feature = "rsa")]
 openpgp_key!(
     key_openpgp_samplekeys_silent_running,
     "samplekeys/silent-running.asc",
@@ -1055,6 +1093,7 @@ openpgp_key!(
 );
 
 #[test]
+#[cfg(feature = "nist")]
 fn private_ecc1_verify() {
     let f = read_file("./tests/openpgp/samplekeys/ecc-sample-1-sec.asc");
     let (sk, _headers) = SignedSecretKey::from_armor_single(f).expect("failed to parse key");
@@ -1080,6 +1119,7 @@ fn private_ecc1_verify() {
 }
 
 #[test]
+#[cfg(feature = "nist")]
 fn private_ecc2_verify() {
     let f = read_file("./tests/openpgp/samplekeys/ecc-sample-2-sec.asc");
     let (sk, _headers) = SignedSecretKey::from_armor_single(f).expect("failed to parse key");
@@ -1108,6 +1148,7 @@ fn private_ecc2_verify() {
 }
 
 #[test]
+#[cfg(feature = "k256")]
 fn private_ecc3_verify() {
     let f = read_file("./tests/openpgp/samplekeys/ecc-sample-4-sec.asc");
     let (sk, _headers) = SignedSecretKey::from_armor_single(f).expect("failed to parse key");
@@ -1133,6 +1174,7 @@ fn private_ecc3_verify() {
 }
 
 #[test]
+#[cfg(feature = "ed25519")]
 fn private_x25519_verify() {
     let f = read_file("./tests/openpgpjs/x25519.sec.asc");
     let (sk, _headers) = SignedSecretKey::from_armor_single(f).expect("failed to parse key");
@@ -1253,6 +1295,7 @@ fn test_invalid() {
 }
 
 #[test]
+#[cfg(feature = "rsa")]
 fn test_handle_incomplete_packets_end() {
     let _ = pretty_env_logger::try_init();
     let p = Path::new("./tests/opengpg-interop/testcases/messages/gnupg-v1-001-decrypt.asc");
@@ -1303,3 +1346,2269 @@ fn test_encrypted_key() {
 
     let _signed_key = unsigned_pubkey.sign(&key, || "123".into()).unwrap();
 }
+
+#[test]
+#[cfg(feature = "rsa")]
+fn test_unknown_s2k() {
+    let p = Path::new("./tests/opengpg-interop/testcases/messages/gnupg-v1-001-decrypt.asc");
+    let (key, _headers) = SignedSecretKey::from_armor_single(read_file(p.to_path_buf()))
+        .expect("failed to parse key");
+    let mut bytes = key.to_bytes().unwrap();
+
+    // change the iterated and salted S2K of the primary key to an unknown one
+    let s2k = hex::decode("0308CB18E77884F2F055").unwrap();
+    let pos = bytes
+        .windows(s2k.len())
+        .position(|w| w == &s2k[..])
+        .unwrap();
+    bytes[pos] = 0x17;
+
+    let parsed = SignedSecretKey::from_bytes(&bytes[..]).expect("failed to parse key");
+    parsed.verify().expect("invalid key");
+    assert_eq!(parsed.to_bytes().unwrap(), bytes);
+
+    // the public parts are still usable
+    assert_eq!(parsed.public_key(), key.public_key());
+    assert_eq!(parsed.details.users, key.details.users);
+
+    let SecretParams::Encrypted(pp) = parsed.primary_key.secret_params() else {
+        panic!("should be encrypted");
+    };
+    let S2kParams::Cfb { s2k, iv, .. } = pp.string_to_key_params() else {
+        panic!("unexpected s2k param: {:?}", pp);
+    };
+    assert_eq!(s2k.id(), 0x17);
+    assert!(s2k.is_opaque());
+    assert!(iv.is_empty());
+
+    let err = parsed
+        .unlock(|| "test".to_string(), |_| Ok(()))
+        .unwrap_err();
+    assert!(matches!(err, Error::UnsupportedS2k(0x17)), "{err:?}");
+}
+
+#[test]
+fn test_sign_detached_subkey_selection() {
+    use pgp::{KeyType, SecretKeyParamsBuilder, SubkeyParamsBuilder};
+
+    let key_params = SecretKeyParamsBuilder::default()
+        .key_type(KeyType::EdDSA)
+        .can_certify(true)
+        .can_sign(false)
+        .primary_user_id("Me <me@mail.com>".into())
+        .subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::EdDSA)
+                .can_sign(true)
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap();
+    let key = key_params.generate().unwrap().sign(|| "".into()).unwrap();
+    let public_key = SignedPublicKey::from(key.clone());
+
+    let data = b"hello world";
+    let signature = key
+        .sign_detached(|| "".into(), HashAlgorithm::SHA2_256, data)
+        .unwrap();
+
+    // made by the signing subkey, not the certification-only primary
+    let subkey_id = key.secret_subkeys[0].key_id();
+    assert_eq!(signature.signature.issuer(), vec![&subkey_id]);
+    assert_eq!(
+        signature.signature.issuer_fingerprint(),
+        vec![&key.secret_subkeys[0].fingerprint()[..]]
+    );
+    assert_eq!(
+        public_key.verify_detached(data, &signature).unwrap(),
+        subkey_id
+    );
+    assert!(public_key
+        .verify_detached(b"other data", &signature)
+        .is_err());
+}
+
+#[test]
+#[cfg(feature = "x25519")]
+fn test_signing_subkey_backsig() {
+    use pgp::{KeyType, SecretKeyParamsBuilder, SubkeyParamsBuilder};
+
+    let key_params = SecretKeyParamsBuilder::default()
+        .key_type(KeyType::EdDSA)
+        .can_certify(true)
+        .can_sign(false)
+        .primary_user_id("Me <me@mail.com>".into())
+        .subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::EdDSA)
+                .can_sign(true)
+                .build()
+                .unwrap(),
+        )
+        .subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::ECDH(ECCCurve::Curve25519))
+                .can_encrypt(true)
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap();
+    let key = key_params.generate().unwrap().sign(|| "".into()).unwrap();
+    let subkey = &key.secret_subkeys[0];
+    let binding = subkey.latest_binding_signature(&key.primary_key).unwrap();
+
+    // generated signing subkeys carry a backsig, encryption subkeys do not need one
+    let backsigs: Vec<_> = binding.embedded_signatures().collect();
+    assert_eq!(backsigs.len(), 1);
+    assert_eq!(backsigs[0].typ(), SignatureType::KeyBinding);
+    binding
+        .verify_backsig(&subkey.key, &key.primary_key)
+        .unwrap();
+    assert!(binding
+        .verify_backsig(&key.secret_subkeys[1].key, &key.primary_key)
+        .is_err());
+    assert_eq!(key.secret_subkeys[1].backsig(&key.primary_key), None);
+    assert_eq!(
+        key.signing_subkey_backsigs(),
+        vec![(subkey.key_id(), Backsig::Valid)]
+    );
+    let public_key = SignedPublicKey::from(key.clone());
+    assert_eq!(
+        public_key.signing_subkey_backsigs(),
+        vec![(subkey.key_id(), Backsig::Valid)]
+    );
+
+    let data = b"hello world";
+    let signature = key
+        .sign_detached(|| "".into(), HashAlgorithm::SHA2_256, data)
+        .unwrap();
+    assert_eq!(
+        public_key.verify_detached(data, &signature).unwrap(),
+        subkey.key_id()
+    );
+
+    // rebinds the signing subkey, with the embedded signatures replaced
+    let rebind = |backsig: Option<Signature>| {
+        let mut config = binding.config.clone();
+        let hashed = config.hashed_subpackets_mut();
+        hashed.retain(|p| !matches!(p.data, SubpacketData::EmbeddedSignature(_)));
+        if let Some(backsig) = backsig {
+            hashed.push(Subpacket::regular(SubpacketData::EmbeddedSignature(
+                Box::new(backsig),
+            )));
+        }
+        let binding = config
+            .sign_key_binding(&key.primary_key, || "".into(), &subkey.key)
+            .unwrap();
+
+        let mut key = key.clone();
+        key.secret_subkeys[0].signatures = vec![binding];
+        key
+    };
+
+    let mut invalid = backsigs[0].clone();
+    invalid.signed_hash_value[0] ^= 0xff;
+    for (key, state) in [
+        (rebind(None), Backsig::Missing),
+        (rebind(Some(invalid)), Backsig::Invalid),
+    ] {
+        // the binding itself is still valid, but the subkey is not used for signing
+        key.verify().unwrap();
+        assert_eq!(
+            key.signing_subkey_backsigs(),
+            vec![(subkey.key_id(), state)]
+        );
+        assert!(key
+            .sign_detached(|| "".into(), HashAlgorithm::SHA2_256, data)
+            .is_err());
+
+        let public_key = SignedPublicKey::from(key);
+        assert_eq!(
+            public_key.signing_subkey_backsigs(),
+            vec![(subkey.key_id(), state)]
+        );
+        assert!(public_key.verify_detached(data, &signature).is_err());
+    }
+}
+
+#[test]
+#[cfg(feature = "x25519")]
+fn test_generated_minimal_signatures() {
+    use pgp::{KeyType, SecretKeyParamsBuilder, SubkeyParamsBuilder};
+
+    // the signing subkey has a password of its own, which generate uses for the backsig
+    let key_params = SecretKeyParamsBuilder::default()
+        .key_type(KeyType::EdDSA)
+        .can_certify(true)
+        .passphrase(Some("primary".into()))
+        .primary_user_id("Me <me@mail.com>".into())
+        .subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::EdDSA)
+                .can_sign(true)
+                .passphrase(Some("subkey".into()))
+                .build()
+                .unwrap(),
+        )
+        .subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::ECDH(ECCCurve::Curve25519))
+                .can_encrypt(true)
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap();
+    let key = key_params
+        .generate()
+        .unwrap()
+        .sign(|| "primary".into())
+        .unwrap();
+
+    // one self-signature on the user id, one binding per subkey
+    assert!(key.details.direct_signatures.is_empty());
+    assert!(key.details.revocation_signatures.is_empty());
+    assert_eq!(key.details.users.len(), 1);
+    assert_eq!(key.details.users[0].signatures.len(), 1);
+    for subkey in &key.secret_subkeys {
+        assert_eq!(subkey.signatures.len(), 1);
+    }
+    // only the signing subkey needs a backsig
+    let signing = &key.secret_subkeys[0];
+    assert_eq!(signing.signatures[0].embedded_signatures().count(), 1);
+    assert_eq!(
+        key.secret_subkeys[1].signatures[0]
+            .embedded_signatures()
+            .count(),
+        0
+    );
+
+    // verified from the public side, which enforces backsigs for signing subkeys
+    let public_key = SignedPublicKey::from(key.clone());
+    public_key.verify().unwrap();
+    assert_eq!(
+        public_key.signing_subkey_backsigs(),
+        vec![(signing.key_id(), Backsig::Valid)]
+    );
+
+    let data = b"hello world";
+    let signature = key
+        .sign_detached(|| "subkey".into(), HashAlgorithm::SHA2_256, data)
+        .unwrap();
+    assert_eq!(
+        public_key.verify_detached(data, &signature).unwrap(),
+        signing.key_id()
+    );
+}
+
+#[test]
+fn test_direct_key_signature_preferences() {
+    use pgp::types::Tag;
+    use pgp::{KeyType, SecretKeyParamsBuilder};
+
+    let key_params = SecretKeyParamsBuilder::default()
+        .key_type(KeyType::EdDSA)
+        .can_certify(true)
+        .can_sign(true)
+        .direct_key_signature(true)
+        .primary_user_id("Me <me@mail.com>".into())
+        .preferred_symmetric_algorithms(smallvec![SymmetricKeyAlgorithm::AES256])
+        .preferred_hash_algorithms(smallvec![HashAlgorithm::SHA2_512])
+        .preferred_compression_algorithms(smallvec![CompressionAlgorithm::ZLIB])
+        .build()
+        .unwrap();
+    let key = key_params.generate().unwrap().sign(|| "".into()).unwrap();
+    key.verify().unwrap();
+
+    let dks = &key.details.direct_signatures;
+    assert_eq!(dks.len(), 1);
+    assert_eq!(dks[0].typ(), SignatureType::Key);
+    assert_eq!(
+        dks[0].preferred_symmetric_algs(),
+        &[SymmetricKeyAlgorithm::AES256]
+    );
+    assert_eq!(dks[0].preferred_hash_algs(), &[HashAlgorithm::SHA2_512]);
+    assert_eq!(
+        dks[0].preferred_compression_algs(),
+        &[CompressionAlgorithm::ZLIB]
+    );
+    assert_eq!(dks[0].features(), &[0x01]);
+    assert!(dks[0].key_flags().sign());
+
+    // survives a round trip
+    let public_key = key.public_key().sign(&key, || "".into()).unwrap();
+    let armored = public_key.to_armored_string(None.into()).unwrap();
+    let (public_key, _) = SignedPublicKey::from_string(&armored).unwrap();
+    public_key.verify().unwrap();
+    assert_eq!(public_key.details.direct_signatures.len(), 1);
+
+    // the user id self-signature takes precedence, the direct key signature is the fallback
+    let primary = &public_key.primary_key;
+    let mut details = public_key.details.clone();
+    let mut config = details.direct_signatures[0].config.clone();
+    config
+        .hashed_subpackets_mut()
+        .retain(|p| !matches!(p.data, SubpacketData::PreferredHashAlgorithms(_)));
+    config.hashed_subpackets_mut().push(Subpacket::regular(
+        SubpacketData::PreferredHashAlgorithms(smallvec![HashAlgorithm::SHA2_256]),
+    ));
+    details.direct_signatures[0] = config.sign_key(&key, || "".into(), &key).unwrap();
+    assert_eq!(
+        details.preferred_hash_algs(primary),
+        &[HashAlgorithm::SHA2_512]
+    );
+    assert_eq!(details.features(primary), &[0x01]);
+
+    let mut config = details.users[0].signatures[0].config.clone();
+    config
+        .hashed_subpackets_mut()
+        .retain(|p| !matches!(p.data, SubpacketData::PreferredHashAlgorithms(_)));
+    details.users[0].signatures[0] = config
+        .sign_certification(&key, || "".into(), Tag::UserId, &details.users[0].id)
+        .unwrap();
+    assert_eq!(
+        details.preferred_hash_algs(primary),
+        &[HashAlgorithm::SHA2_256]
+    );
+    assert_eq!(
+        details.preferred_symmetric_algs(primary),
+        &[SymmetricKeyAlgorithm::AES256]
+    );
+
+    // off by default
+    let key_params = SecretKeyParamsBuilder::default()
+        .key_type(KeyType::EdDSA)
+        .can_sign(true)
+        .primary_user_id("Me <me@mail.com>".into())
+        .build()
+        .unwrap();
+    let key = key_params.generate().unwrap().sign(|| "".into()).unwrap();
+    assert!(key.details.direct_signatures.is_empty());
+}
+
+#[test]
+#[cfg(all(feature = "rsa", feature = "nist"))]
+fn test_public_params_standard_encodings() {
+    use pgp::types::EcdsaPublicParams;
+    use rsa::pkcs1::DecodeRsaPublicKey;
+    use rsa::pkcs8::DecodePublicKey;
+    use rsa::traits::PublicKeyParts;
+
+    let load = |name: &str| -> SignedPublicKey {
+        let f = read_file(Path::new("./tests/openpgp/samplekeys/").join(name));
+        let (mut keys, _) = from_armor_many(f).unwrap();
+        match keys.next().unwrap().unwrap() {
+            PublicOrSecret::Public(key) => key,
+            PublicOrSecret::Secret(key) => key.into(),
+        }
+    };
+
+    // RSA: SPKI and PKCS#1
+    let key = load("rsa-rsa-sample-1.asc");
+    let PublicParams::RSA { n, e } = key.primary_key.public_params() else {
+        panic!("unexpected key");
+    };
+    let params = key.primary_key.public_params();
+    let spki = rsa::RsaPublicKey::from_public_key_der(&params.to_spki_der().unwrap()).unwrap();
+    let pkcs1 = rsa::RsaPublicKey::from_pkcs1_der(&params.to_raw().unwrap()).unwrap();
+    assert_eq!(spki, pkcs1);
+    assert_eq!(spki.n().to_bytes_be(), n.as_bytes());
+    assert_eq!(spki.e().to_bytes_be(), e.as_bytes());
+
+    // NIST P-256: SPKI and SEC1
+    let key = load("e2e-p256-1-clr.asc");
+    let params = key.primary_key.public_params();
+    let PublicParams::ECDSA(EcdsaPublicParams::P256 { key: ec_key, p }) = params else {
+        panic!("unexpected key");
+    };
+    let spki = p256::PublicKey::from_public_key_der(&params.to_spki_der().unwrap()).unwrap();
+    assert_eq!(&spki, ec_key);
+    assert_eq!(params.to_raw().unwrap(), p.as_bytes());
+
+    // Ed25519 and Curve25519: raw 32 bytes, SPKI for Ed25519 only
+    let key = load("ed25519-cv25519-sample-1.asc");
+    let params = key.primary_key.public_params();
+    let PublicParams::EdDSA { q, .. } = params else {
+        panic!("unexpected key");
+    };
+    let raw = params.to_raw().unwrap();
+    assert_eq!(raw, &q.as_bytes()[1..]);
+    let spki = params.to_spki_der().unwrap();
+    assert_eq!(
+        spki,
+        [&hex::decode("302a300506032b6570032100").unwrap()[..], &raw].concat()
+    );
+
+    let params = key.public_subkeys[0].key.public_params();
+    let PublicParams::ECDH { p, .. } = params else {
+        panic!("unexpected key");
+    };
+    assert_eq!(params.to_raw().unwrap(), &p.as_bytes()[1..]);
+    assert!(params.to_spki_der().is_err());
+}
+
+#[test]
+#[cfg(feature = "ed25519")]
+fn test_public_params_from_raw_bytes() {
+    let (key, _) = SignedPublicKey::from_armor_single(read_file(
+        "./tests/openpgp/samplekeys/ed25519-cv25519-sample-1.asc",
+    ))
+    .unwrap();
+
+    let params = key.primary_key.public_params();
+    let raw: [u8; 32] = params.to_raw().unwrap().try_into().unwrap();
+    assert_eq!(&PublicParams::ed25519_from_bytes(&raw).unwrap(), params);
+
+    let params = key.public_subkeys[0].key.public_params();
+    let raw: [u8; 32] = params.to_raw().unwrap().try_into().unwrap();
+    let PublicParams::ECDH {
+        curve: ECCCurve::Curve25519,
+        p,
+        hash,
+        alg_sym,
+    } = PublicParams::x25519_from_bytes(&raw).unwrap()
+    else {
+        panic!("unexpected params");
+    };
+    let PublicParams::ECDH { p: expected, .. } = params else {
+        panic!("unexpected key");
+    };
+    assert_eq!(&p, expected);
+    assert_eq!(hash, HashAlgorithm::SHA2_256);
+    assert_eq!(alg_sym, SymmetricKeyAlgorithm::AES128);
+
+    // not on the curve
+    let mut invalid = [0u8; 32];
+    invalid[0] = 2;
+    assert!(PublicParams::ed25519_from_bytes(&invalid).is_err());
+    assert!(PublicParams::x25519_from_bytes(&[0; 32]).is_err());
+}
+
+#[test]
+fn test_parse_out_of_order_components() {
+    use pgp::packet::{Packet, PacketParser};
+    use pgp::types::Tag;
+
+    let (key, _) = SignedPublicKey::from_armor_single(read_file(
+        "./tests/autocrypt/alice@autocrypt.example.pub.asc",
+    ))
+    .unwrap();
+    key.verify().unwrap();
+
+    let packets: Vec<Packet> = PacketParser::new(&key.to_bytes().unwrap()[..])
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let tags: Vec<_> = packets.iter().map(|p| p.tag()).collect();
+    assert_eq!(
+        tags,
+        [
+            Tag::PublicKey,
+            Tag::UserId,
+            Tag::Signature,
+            Tag::PublicSubkey,
+            Tag::Signature
+        ]
+    );
+
+    for order in [
+        // subkeys before user ids
+        [0, 3, 4, 1, 2],
+        // user id certification appended after the subkey
+        [0, 1, 3, 4, 2],
+        // subkey binding appended after the user id
+        [0, 3, 1, 2, 4],
+        // all signatures at the end
+        [0, 1, 3, 2, 4],
+        [0, 3, 1, 4, 2],
+    ] {
+        let mut bytes = Vec::new();
+        for i in order {
+            packets[i].to_writer(&mut bytes).unwrap();
+        }
+
+        let parsed = SignedPublicKey::from_bytes(&bytes[..]).unwrap();
+        parsed.verify().unwrap();
+        assert_eq!(parsed, key, "{order:?}");
+    }
+}
+
+#[test]
+#[cfg(feature = "x25519")]
+fn test_parse_misplaced_signatures() {
+    use pgp::packet::{Packet, PacketParser};
+    use pgp::types::Tag;
+    use pgp::{KeyType, SecretKeyParamsBuilder, SubkeyParamsBuilder};
+
+    let key_params = SecretKeyParamsBuilder::default()
+        .key_type(KeyType::EdDSA)
+        .can_certify(true)
+        .primary_user_id("Me <me@mail.com>".into())
+        .subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::EdDSA)
+                .can_sign(true)
+                .build()
+                .unwrap(),
+        )
+        .subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::ECDH(ECCCurve::Curve25519))
+                .can_encrypt(true)
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap();
+    let key = key_params.generate().unwrap().sign(|| "".into()).unwrap();
+    let key = SignedPublicKey::from(key);
+    let signing_subkey = key.public_subkeys[0].key_id();
+
+    let packets: Vec<Packet> = PacketParser::new(&key.to_bytes().unwrap()[..])
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let tags: Vec<_> = packets.iter().map(|p| p.tag()).collect();
+    assert_eq!(
+        tags,
+        [
+            Tag::PublicKey,
+            Tag::UserId,
+            Tag::Signature,
+            Tag::PublicSubkey,
+            Tag::Signature,
+            Tag::PublicSubkey,
+            Tag::Signature
+        ]
+    );
+
+    // a certification of the user id by another key
+    let (other, _) = SignedSecretKey::from_armor_single(read_file(
+        "./tests/autocrypt/alice@autocrypt.example.sec.asc",
+    ))
+    .unwrap();
+    let third_party = other
+        .certify_user_id(
+            || "".into(),
+            &key.primary_key,
+            &key.details.users[0].id,
+            SignatureType::CertGeneric,
+        )
+        .unwrap();
+    let mut third_party_bytes = Vec::new();
+    pgp::packet::write_packet(&mut third_party_bytes, &third_party).unwrap();
+
+    // the binding of the signing subkey in front of both subkeys, placed by the issuer of its
+    // backsig, and the third-party certification after the subkeys
+    let mut bytes = Vec::new();
+    for i in [0, 1, 2, 4, 5, 6, 3] {
+        packets[i].to_writer(&mut bytes).unwrap();
+    }
+    bytes.extend_from_slice(&third_party_bytes);
+
+    let parsed = SignedPublicKey::from_bytes(&bytes[..]).unwrap();
+    assert_eq!(parsed.public_subkeys.len(), 2);
+    for subkey in &parsed.public_subkeys {
+        subkey.verify(&parsed.primary_key).unwrap();
+    }
+    assert_eq!(parsed.public_subkeys[1].key_id(), signing_subkey);
+
+    // kept, although it does not verify against the primary key
+    assert_eq!(parsed.details.users[0].signatures.len(), 2);
+    assert_eq!(parsed.details.users[0].signatures[1], third_party);
+}
+
+#[test]
+fn test_newest_self_signature_wins() {
+    use chrono::Duration;
+    use pgp::types::Tag;
+    use pgp::{KeyType, SecretKeyParamsBuilder};
+
+    let key_params = SecretKeyParamsBuilder::default()
+        .key_type(KeyType::EdDSA)
+        .can_certify(true)
+        .can_sign(false)
+        .primary_user_id("Me <me@mail.com>".into())
+        .build()
+        .unwrap();
+    let mut key = key_params.generate().unwrap().sign(|| "".into()).unwrap();
+    assert!(!key.details.can_sign(&key.primary_key));
+    assert!(key
+        .sign_detached(|| "".into(), HashAlgorithm::SHA2_256, b"hello")
+        .is_err());
+    assert_eq!(key.expires_at(), None);
+
+    // re-certify the user id with a later creation time, adding the signing flag and an expiration
+    let old_sig = key.details.users[0].signatures[0].clone();
+    let created = *old_sig.created().unwrap() + Duration::seconds(60);
+
+    let mut flags = KeyFlags::default();
+    flags.set_certify(true);
+    flags.set_sign(true);
+
+    let mut config = old_sig.config.clone();
+    config.hashed_subpackets_mut().retain(|p| {
+        !matches!(
+            p.data,
+            SubpacketData::SignatureCreationTime(_) | SubpacketData::KeyFlags(_)
+        )
+    });
+    config.hashed_subpackets_mut().extend([
+        Subpacket::regular(SubpacketData::SignatureCreationTime(created)),
+        Subpacket::regular(SubpacketData::KeyFlags(flags.into())),
+        Subpacket::regular(SubpacketData::KeyExpirationTime(Duration::days(365))),
+    ]);
+    let new_sig = config
+        .sign_certification(&key, || "".into(), Tag::UserId, &key.details.users[0].id)
+        .unwrap();
+
+    // the position of the signature does not matter, only its creation time
+    key.details.users[0].signatures.insert(0, new_sig);
+    key.verify().unwrap();
+
+    let user = &key.details.users[0];
+    assert_eq!(
+        user.latest_signature(&key.primary_key).unwrap().created(),
+        Some(&created)
+    );
+    assert!(key.details.can_sign(&key.primary_key));
+    assert_eq!(
+        key.expires_at(),
+        Some(*key.primary_key.created_at() + Duration::days(365))
+    );
+    key.sign_detached(|| "".into(), HashAlgorithm::SHA2_256, b"hello")
+        .unwrap();
+    assert!(key.expires_at().is_some());
+}
+
+#[test]
+fn test_third_party_certification_is_not_a_self_signature() {
+    use chrono::Duration;
+    use pgp::types::Tag;
+    use pgp::{KeyType, SecretKeyParamsBuilder};
+
+    let params = |uid: &str| {
+        SecretKeyParamsBuilder::default()
+            .key_type(KeyType::EdDSA)
+            .can_certify(true)
+            .can_sign(false)
+            .primary_user_id(uid.into())
+            .build()
+            .unwrap()
+    };
+    let key = params("Me <me@mail.com>")
+        .generate()
+        .unwrap()
+        .sign(|| "".into())
+        .unwrap();
+    let mallory = params("Mallory <mallory@mail.com>")
+        .generate()
+        .unwrap()
+        .sign(|| "".into())
+        .unwrap();
+
+    // a newer certification by another key, claiming signing capability, an expiration and
+    // the primary user id
+    let old_sig = key.details.users[0].signatures[0].clone();
+    let created = *old_sig.created().unwrap() + Duration::seconds(60);
+
+    let mut flags = KeyFlags::default();
+    flags.set_certify(true);
+    flags.set_sign(true);
+
+    let config = pgp::packet::SignatureConfig::new_v4(
+        SignatureVersion::V4,
+        SignatureType::CertPositive,
+        mallory.algorithm(),
+        HashAlgorithm::SHA2_256,
+        vec![
+            Subpacket::regular(SubpacketData::SignatureCreationTime(created)),
+            Subpacket::regular(SubpacketData::IssuerFingerprint(
+                KeyVersion::V4,
+                mallory.fingerprint().into(),
+            )),
+            Subpacket::regular(SubpacketData::KeyFlags(flags.into())),
+            Subpacket::regular(SubpacketData::KeyExpirationTime(Duration::days(1))),
+            Subpacket::regular(SubpacketData::IsPrimary(true)),
+        ],
+        vec![Subpacket::regular(SubpacketData::Issuer(mallory.key_id()))],
+    );
+    let third_party = config
+        .sign_certification_third_party(
+            &mallory,
+            || "".into(),
+            &key.primary_key,
+            Tag::UserId,
+            &key.details.users[0].id,
+        )
+        .unwrap();
+    third_party
+        .verify_third_party_certification(
+            &key.primary_key,
+            &mallory.primary_key,
+            Tag::UserId,
+            &key.details.users[0].id,
+        )
+        .unwrap();
+
+    let mut public_key = key.public_key().sign(&key, || "".into()).unwrap();
+    let self_sig = public_key.details.users[0].signatures[0].clone();
+    public_key.details.users[0]
+        .signatures
+        .push(third_party.clone());
+
+    let primary = &public_key.primary_key;
+    let user = &public_key.details.users[0];
+    assert_eq!(user.latest_signature(primary), Some(&self_sig));
+    assert!(!public_key.details.can_sign(primary));
+    assert_eq!(public_key.expires_at(), None);
+
+    // without a valid self-signature, the primary user id is signed again without key flags
+    let mut unverified = key.clone();
+    unverified.details.users[0].signatures = vec![third_party];
+    let resigned = unverified.public_key().sign(&key, || "".into()).unwrap();
+    resigned.verify().unwrap();
+    assert!(!resigned.details.can_sign(&resigned.primary_key));
+    assert_eq!(resigned.expires_at(), None);
+}
+
+#[test]
+fn test_secret_to_public_conversion() {
+    // public keys exported by GnuPG for the same secret keys
+    for (sec, public) in [
+        (
+            "./tests/autocrypt/alice@autocrypt.example.sec.asc",
+            "./tests/autocrypt/alice@autocrypt.example.pub.asc",
+        ),
+        (
+            "./tests/autocrypt/bob@autocrypt.example.sec.asc",
+            "./tests/autocrypt/bob@autocrypt.example.pub.asc",
+        ),
+        (
+            "./tests/autocrypt/carol@autocrypt.example.sec.asc",
+            "./tests/autocrypt/carol@autocrypt.example.pub.asc",
+        ),
+        (
+            "./tests/draft-bre-openpgp-samples-00/bob.sec.asc",
+            "./tests/draft-bre-openpgp-samples-00/bob.pub.asc",
+        ),
+    ] {
+        let (key, _) = SignedSecretKey::from_armor_single(read_file(sec)).unwrap();
+        let (exported, _) = SignedPublicKey::from_armor_single(read_file(public)).unwrap();
+
+        assert!(key.conversion_report().is_empty());
+        let converted = SignedPublicKey::from(key);
+        assert_eq!(converted, exported, "{sec}");
+        assert_eq!(converted.to_bytes().unwrap(), exported.to_bytes().unwrap());
+    }
+}
+
+#[test]
+fn test_secret_key_fingerprints() {
+    for sec in [
+        "./tests/autocrypt/alice@autocrypt.example.sec.asc",
+        "./tests/draft-bre-openpgp-samples-00/bob.sec.asc",
+        "./tests/openpgpjs/x25519.sec.asc",
+        "./tests/unit-tests/key-block/carol.sec.asc",
+    ] {
+        let (key, _) = SignedSecretKey::from_armor_single(read_file(sec)).unwrap();
+        let public = SignedPublicKey::from(key.clone());
+
+        // secret keys are fingerprinted by their public part, without converting them
+        assert_eq!(key.fingerprint(), public.fingerprint(), "{sec}");
+        assert_eq!(key.key_id(), public.key_id(), "{sec}");
+        assert_eq!(key.primary_key.fingerprint(), public.fingerprint(), "{sec}");
+        assert_eq!(
+            key.primary_key.public_key().fingerprint(),
+            public.fingerprint()
+        );
+
+        assert_eq!(key.secret_subkeys.len(), public.public_subkeys.len());
+        for (secret, public) in key.secret_subkeys.iter().zip(&public.public_subkeys) {
+            assert_eq!(secret.fingerprint(), public.fingerprint(), "{sec}");
+            assert_eq!(secret.key_id(), public.key_id(), "{sec}");
+            assert_eq!(secret.key.fingerprint(), public.key.fingerprint(), "{sec}");
+        }
+    }
+}
+
+#[test]
+fn test_secret_to_public_conversion_losses() {
+    use pgp::types::Tag;
+
+    let (mut key, _) = SignedSecretKey::from_armor_single(read_file(
+        "./tests/autocrypt/alice@autocrypt.example.sec.asc",
+    ))
+    .unwrap();
+    let (bob, _) = SignedSecretKey::from_armor_single(read_file(
+        "./tests/autocrypt/bob@autocrypt.example.sec.asc",
+    ))
+    .unwrap();
+
+    // third-party certifications are kept
+    let user = key.details.users[0].clone();
+    let certification = bob
+        .certify_user_id(
+            String::new,
+            &key.primary_key,
+            &user.id,
+            SignatureType::CertGeneric,
+        )
+        .unwrap();
+    certification
+        .verify_third_party_certification(&key.primary_key, &bob.primary_key, Tag::UserId, &user.id)
+        .unwrap();
+    key.details.users[0].signatures.push(certification.clone());
+
+    // hand-assembled components the public key can not hold
+    let subkey_id = key.secret_subkeys[0].key_id();
+    key.secret_subkeys[0]
+        .signatures
+        .push(key.details.users[0].signatures[0].clone());
+    let mut unbound = key.secret_subkeys[0].clone();
+    unbound.signatures.clear();
+    key.secret_subkeys.push(unbound);
+
+    assert_eq!(
+        key.conversion_report(),
+        vec![
+            ConversionLoss::SubkeySignature(subkey_id.clone(), SignatureType::CertPositive),
+            ConversionLoss::UnboundSubkey(subkey_id),
+        ]
+    );
+
+    let converted = SignedPublicKey::from(key);
+    assert_eq!(converted.public_subkeys.len(), 1);
+    assert_eq!(converted.public_subkeys[0].signatures.len(), 1);
+    assert!(converted.details.users[0]
+        .signatures
+        .contains(&certification));
+}
+
+#[test]
+#[cfg(feature = "x25519")]
+fn test_restricted_subkey_policies() {
+    use base64::engine::{general_purpose::STANDARD, Engine};
+    use pgp::composed::{EncryptionPurpose, Message, MessageBuilder};
+    use pgp::{KeyType, SecretKeyParamsBuilder, SubkeyParamsBuilder};
+
+    let key_params = SecretKeyParamsBuilder::default()
+        .key_type(KeyType::EdDSA)
+        .can_certify(true)
+        .can_sign(true)
+        .primary_user_id("Me <me@mail.com>".into())
+        .subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::ECDH(ECCCurve::Curve25519))
+                .can_encrypt_storage(true)
+                .build()
+                .unwrap(),
+        )
+        .subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::EdDSA)
+                .can_authenticate(true)
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap();
+    let key = key_params.generate().unwrap().sign(|| "".into()).unwrap();
+    let public_key = SignedPublicKey::from(key.clone());
+    let storage_key = &public_key.public_subkeys[0];
+    let auth_key = &public_key.public_subkeys[1];
+
+    // encryption subkey selection
+    let ids = |keys: Vec<&SignedPublicSubKey>| keys.iter().map(|k| k.key_id()).collect::<Vec<_>>();
+    assert_eq!(
+        ids(public_key
+            .encryption_subkeys(EncryptionPurpose::Storage)
+            .collect()),
+        vec![storage_key.key_id()]
+    );
+    assert_eq!(
+        ids(public_key
+            .encryption_subkeys(EncryptionPurpose::Any)
+            .collect()),
+        vec![storage_key.key_id()]
+    );
+    assert_eq!(
+        public_key
+            .encryption_subkeys(EncryptionPurpose::Communications)
+            .count(),
+        0
+    );
+    assert_eq!(
+        key.encryption_subkeys(EncryptionPurpose::Storage)
+            .map(|k| k.key_id())
+            .collect::<Vec<_>>(),
+        vec![storage_key.key_id()]
+    );
+
+    // ssh export uses the authentication subkey
+    let ssh = public_key.to_openssh().unwrap();
+    let parts: Vec<_> = ssh.split(' ').collect();
+    assert_eq!(parts[0], "ssh-ed25519");
+    assert_eq!(
+        parts[2],
+        format!("openpgp:0x{}", &format!("{:X}", auth_key.key_id())[8..])
+    );
+    let blob = STANDARD.decode(parts[1]).unwrap();
+    assert_eq!(&blob[..4], &[0, 0, 0, 11]);
+    assert_eq!(&blob[4..15], b"ssh-ed25519");
+    assert_eq!(&blob[15..19], &[0, 0, 0, 32]);
+    match auth_key.key.public_params() {
+        PublicParams::EdDSA { q, .. } => assert_eq!(&blob[19..], &q.as_bytes()[1..]),
+        _ => panic!("unexpected params"),
+    }
+
+    // authentication-only keys are refused as recipients
+    let err = MessageBuilder::from_bytes("", b"hello")
+        .encrypt_to_key(auth_key)
+        .to_bytes(thread_rng())
+        .unwrap_err();
+    assert!(err.to_string().contains("authentication-only"), "{err}");
+
+    let bytes = MessageBuilder::from_bytes("", b"hello")
+        .encrypt_to_key(storage_key)
+        .to_bytes(thread_rng())
+        .unwrap();
+    let (decrypted, _) = Message::from_bytes(&bytes[..])
+        .unwrap()
+        .decrypt(|| "".into(), &[&key])
+        .unwrap();
+    assert_eq!(
+        decrypted
+            .decompress()
+            .unwrap()
+            .get_content()
+            .unwrap()
+            .unwrap(),
+        b"hello"
+    );
+}
+
+#[test]
+#[cfg(feature = "x25519")]
+fn test_serialize_with_packet_header_version() {
+    use pgp::packet::PacketParser;
+    use pgp::types::Version;
+    use pgp::{KeyType, SecretKeyParamsBuilder, SubkeyParamsBuilder};
+
+    // a reference key using old format headers throughout
+    let mut reference = Vec::new();
+    armor::Dearmor::new(BufReader::new(read_file(
+        "./tests/draft-bre-openpgp-samples-00/bob.pub.asc",
+    )))
+    .read_to_end(&mut reference)
+    .unwrap();
+
+    let key = SignedPublicKey::from_bytes(&reference[..]).unwrap();
+    let mut new_format = Vec::new();
+    key.to_writer_with_version(&mut new_format, Version::New)
+        .unwrap();
+    assert_ne!(new_format, reference);
+
+    // reproduce the reference bytes from a key that was read in the new format
+    let key = SignedPublicKey::from_bytes(&new_format[..]).unwrap();
+    assert_eq!(key.to_bytes().unwrap(), new_format);
+    let mut old_format = Vec::new();
+    key.to_writer_with_version(&mut old_format, Version::Old)
+        .unwrap();
+    assert_eq!(old_format, reference);
+
+    // generated keys default to the new format
+    let key = SecretKeyParamsBuilder::default()
+        .key_type(KeyType::EdDSA)
+        .can_certify(true)
+        .primary_user_id("Me <me@mail.com>".into())
+        .subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::ECDH(ECCCurve::Curve25519))
+                .can_encrypt(true)
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap()
+        .generate()
+        .unwrap()
+        .sign(|| "".into())
+        .unwrap();
+
+    let packet_formats = |bytes: &[u8]| {
+        PacketParser::new(bytes)
+            .map(|p| p.unwrap().packet_version())
+            .collect::<Vec<_>>()
+    };
+    let new_format = key.to_bytes().unwrap();
+    assert!(packet_formats(&new_format)
+        .iter()
+        .all(|v| *v == Version::New));
+
+    let mut old_format = Vec::new();
+    key.to_writer_with_version(&mut old_format, Version::Old)
+        .unwrap();
+    assert!(packet_formats(&old_format)
+        .iter()
+        .all(|v| *v == Version::Old));
+    let parsed = SignedSecretKey::from_bytes(&old_format[..]).unwrap();
+    assert_eq!(parsed.fingerprint(), key.fingerprint());
+    parsed.verify().unwrap();
+}
+
+#[cfg(any(feature = "x25519", feature = "nist"))]
+mod canonical_encoding {
+    use super::*;
+
+    use pgp::{KeyType, SecretKeyParamsBuilder, SubkeyParamsBuilder};
+    use proptest::prelude::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn signing_key_type() -> impl Strategy<Value = KeyType> {
+        prop::sample::select(vec![
+            KeyType::EdDSA,
+            #[cfg(feature = "nist")]
+            KeyType::ECDSA(ECCCurve::P256),
+            #[cfg(feature = "nist")]
+            KeyType::ECDSA(ECCCurve::P384),
+            #[cfg(feature = "nist")]
+            KeyType::ECDSA(ECCCurve::P521),
+            #[cfg(feature = "k256")]
+            KeyType::ECDSA(ECCCurve::Secp256k1),
+        ])
+    }
+
+    fn encryption_key_type() -> impl Strategy<Value = KeyType> {
+        prop::sample::select(vec![
+            #[cfg(feature = "x25519")]
+            KeyType::ECDH(ECCCurve::Curve25519),
+            #[cfg(feature = "nist")]
+            KeyType::ECDH(ECCCurve::P256),
+            #[cfg(feature = "nist")]
+            KeyType::ECDH(ECCCurve::P384),
+            #[cfg(feature = "nist")]
+            KeyType::ECDH(ECCCurve::P521),
+        ])
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn fingerprint_stable_after_round_trip(
+            primary in signing_key_type(),
+            subkey in encryption_key_type(),
+            seed: u64,
+        ) {
+            let rng = ChaCha8Rng::seed_from_u64(seed);
+            let key_params = SecretKeyParamsBuilder::default()
+                .key_type(primary)
+                .can_certify(true)
+                .can_sign(true)
+                .primary_user_id("Me <me@mail.com>".into())
+                .subkey(
+                    SubkeyParamsBuilder::default()
+                        .key_type(subkey)
+                        .can_encrypt(true)
+                        .build()
+                        .unwrap(),
+                )
+                .build()
+                .unwrap();
+            let key = key_params
+                .generate_with_rng(rng)
+                .unwrap()
+                .sign(|| "".into())
+                .unwrap();
+
+            key.canonical_fingerprint_check().unwrap();
+
+            let public_key = key.public_key().sign(&key, || "".into()).unwrap();
+            let bytes = public_key.to_bytes().unwrap();
+            let parsed = SignedPublicKey::from_bytes(&bytes[..]).unwrap();
+            prop_assert_eq!(public_key.fingerprint(), parsed.fingerprint());
+            prop_assert_eq!(
+                public_key.public_subkeys[0].fingerprint(),
+                parsed.public_subkeys[0].fingerprint()
+            );
+        }
+    }
+}
+
+#[test]
+fn test_unknown_algorithm_subkey_roundtrip() {
+    let mut reference = Vec::new();
+    armor::Dearmor::new(BufReader::new(read_file(
+        "./tests/unit-tests/private-use-algorithm.asc",
+    )))
+    .read_to_end(&mut reference)
+    .unwrap();
+
+    let key = SignedPublicKey::from_bytes(&reference[..]).unwrap();
+    key.verify().unwrap();
+
+    // the subkey uses the private use algorithm 105, its material is kept as is
+    let subkey = &key.public_subkeys[0];
+    assert_eq!(subkey.key.algorithm_id(), 105);
+    assert_eq!(subkey.algorithm(), PublicKeyAlgorithm::Private105);
+    assert_eq!(
+        subkey.key.public_params().raw(),
+        Some(&b"\x00\x08\xa5private-use key material"[..])
+    );
+    assert_eq!(key.primary_key.public_params().raw(), None);
+    assert_eq!(
+        hex::encode_upper(subkey.fingerprint()),
+        "50D4AF2CFDB696984CA0118A556A33794026D71F"
+    );
+
+    assert_eq!(key.to_bytes().unwrap(), reference);
+    let key = SignedPublicKey::from_bytes(&key.to_bytes().unwrap()[..]).unwrap();
+    assert_eq!(
+        hex::encode_upper(key.public_subkeys[0].fingerprint()),
+        "50D4AF2CFDB696984CA0118A556A33794026D71F"
+    );
+}
+
+#[test]
+fn test_verify_with_trailing_newline_hints() {
+    let (key, _headers) = SignedSecretKey::from_armor_single(read_file(
+        "./tests/autocrypt/alice@autocrypt.example.sec.asc",
+    ))
+    .unwrap();
+    let public_key = SignedPublicKey::from(key.clone());
+
+    let sign = |data: &[u8]| {
+        key.sign_detached(|| "".into(), HashAlgorithm::SHA2_256, data)
+            .unwrap()
+    };
+
+    let signature = sign(b"hello world\n");
+    signature
+        .verify_with_hints(&public_key.primary_key, b"hello world\n")
+        .unwrap();
+    assert!(matches!(
+        signature.verify_with_hints(&public_key.primary_key, b"hello world"),
+        Err(Error::TrailingNewlineMismatch {
+            signed_with_newline: true
+        })
+    ));
+
+    let signature = sign(b"hello world");
+    for data in [&b"hello world\n"[..], &b"hello world\r\n"[..]] {
+        assert!(matches!(
+            signature.verify_with_hints(&public_key.primary_key, data),
+            Err(Error::TrailingNewlineMismatch {
+                signed_with_newline: false
+            })
+        ));
+    }
+
+    // any other difference is reported as usual
+    let err = signature
+        .verify_with_hints(&public_key.primary_key, b"hello world!")
+        .unwrap_err();
+    assert!(!matches!(err, Error::TrailingNewlineMismatch { .. }));
+}
+
+#[test]
+fn test_signature_version_mismatches() {
+    let (key, _) = SignedPublicKey::from_armor_single(read_file(
+        "./tests/autocrypt/alice@autocrypt.example.pub.asc",
+    ))
+    .unwrap();
+    assert!(key.version_mismatches().is_empty());
+    key.verify().unwrap();
+
+    // the unhashed area is not covered by the signature, so only the version check fails
+    let mut bad = key.clone();
+    let sig = &mut bad.details.users[0].signatures[0];
+    sig.config
+        .unhashed_subpackets
+        .push(Subpacket::regular(SubpacketData::IssuerFingerprint(
+            KeyVersion::V5,
+            SmallVec::from_slice(&[0u8; 32]),
+        )));
+    sig.config
+        .unhashed_subpackets
+        .push(Subpacket::regular(SubpacketData::Issuer(key.key_id())));
+    assert_eq!(
+        bad.version_mismatches(),
+        vec![VersionMismatch::IssuerFingerprint(
+            SignatureType::CertPositive,
+            KeyVersion::V5
+        )]
+    );
+    // mismatches are diagnostics, they do not fail verification
+    bad.verify().unwrap();
+
+    // a V5 signature on a subkey binding of a V4 key
+    let mut bad = key.clone();
+    bad.public_subkeys[0].signatures[0].config.version = SignatureVersion::V5;
+    assert_eq!(
+        bad.version_mismatches(),
+        vec![VersionMismatch::SignatureVersion(
+            SignatureType::SubkeyBinding,
+            SignatureVersion::V5
+        )]
+    );
+
+    // signatures by other keys are not checked
+    let (bob, _) = SignedPublicKey::from_armor_single(read_file(
+        "./tests/autocrypt/bob@autocrypt.example.pub.asc",
+    ))
+    .unwrap();
+    let mut third_party = bob.details.users[0].signatures[0].clone();
+    third_party.config.version = SignatureVersion::V5;
+    assert!(!third_party.issuer().is_empty());
+    let mut certified = key.clone();
+    certified.details.users[0].signatures.push(third_party);
+    assert!(certified.version_mismatches().is_empty());
+}
+
+#[test]
+fn test_keyring_limits() {
+    let (key, _) = SignedPublicKey::from_armor_single(read_file(
+        "./tests/autocrypt/alice@autocrypt.example.pub.asc",
+    ))
+    .unwrap();
+
+    // a certificate bloated with copies of a signature
+    let mut bloated = key.clone();
+    let sig = bloated.details.users[0].signatures[0].clone();
+    bloated.details.users[0].signatures = vec![sig; 100];
+    let keyring = [key.to_bytes().unwrap(), bloated.to_bytes().unwrap()].concat();
+    let packets = 5 + 104;
+
+    let parse = |limits: KeyringLimits| -> Result<Vec<PublicOrSecret>, Error> {
+        from_bytes_many_with_limits(&keyring[..], limits).collect()
+    };
+
+    assert_eq!(parse(KeyringLimits::default()).unwrap().len(), 2);
+    assert_eq!(
+        parse(KeyringLimits::new(packets, keyring.len() as u64))
+            .unwrap()
+            .len(),
+        2
+    );
+
+    // the first key is fine, the second one exceeds the limit
+    let mut keys = from_bytes_many_with_limits(&keyring[..], KeyringLimits::new(50, u64::MAX));
+    assert!(keys.next().unwrap().is_ok());
+    assert!(matches!(keys.next().unwrap(), Err(Error::TooManyPackets)));
+    assert!(keys.next().is_none());
+
+    assert!(matches!(
+        parse(KeyringLimits::new(packets - 1, u64::MAX)),
+        Err(Error::TooManyPackets)
+    ));
+    assert!(matches!(
+        parse(KeyringLimits::new(usize::MAX, keyring.len() as u64 - 1)),
+        Err(Error::TooManyPackets)
+    ));
+
+    // also applies to armored input
+    let armored = bloated
+        .to_armored_bytes(pgp::ArmorOptions::default())
+        .unwrap();
+    let (mut keys, _) =
+        from_reader_many_with_limits(&armored[..], KeyringLimits::new(50, u64::MAX)).unwrap();
+    assert!(matches!(keys.next().unwrap(), Err(Error::TooManyPackets)));
+}
+
+#[test]
+fn test_parse_v3_rsa_key() {
+    let (key, _) =
+        SignedPublicKey::from_armor_single(read_file("./tests/openpgp/pgp263-test.pub.asc"))
+            .unwrap();
+    assert_eq!(key.primary_key.version(), KeyVersion::V3);
+    assert_eq!(key.primary_key.algorithm(), PublicKeyAlgorithm::RSA);
+    assert_eq!(key.primary_key.expiration(), Some(0));
+    assert_eq!(key.details.users[0].id.id(), "pgp2.6.3-test-key");
+
+    // the key id is the low 64 bits of the modulus
+    let PublicParams::RSA { n, .. } = key.primary_key.public_params() else {
+        panic!("expected an RSA key");
+    };
+    assert_eq!(n.len(), 111);
+    assert_eq!(hex::encode(key.key_id()), "dc70c124a50283f1");
+    assert_eq!(key.key_id().as_ref(), &n.as_bytes()[103..]);
+    assert_eq!(
+        hex::encode(key.fingerprint()),
+        "ccd99fd6a66e720fb8deeb1b0e970899"
+    );
+
+    let bytes = key.to_bytes().unwrap();
+    let parsed = SignedPublicKey::from_bytes(&bytes[..]).unwrap();
+    assert_eq!(parsed, key);
+    assert_eq!(parsed.to_bytes().unwrap(), bytes);
+}
+
+#[test]
+fn test_user_attribute_certifications() {
+    use pgp::{KeyType, SecretKeyParamsBuilder};
+
+    let generate = |uid: &str| {
+        SecretKeyParamsBuilder::default()
+            .key_type(KeyType::EdDSA)
+            .can_certify(true)
+            .primary_user_id(uid.into())
+            .build()
+            .unwrap()
+            .generate()
+            .unwrap()
+            .sign(|| "".into())
+            .unwrap()
+    };
+    let alice = generate("Alice <alice@example.org>");
+    let bob = generate("Bob <bob@example.org>");
+
+    let mut header = vec![0x01, 0x01];
+    header.resize(14, 0);
+    let photo = UserAttribute::Image {
+        packet_version: Default::default(),
+        header,
+        data: vec![0xff, 0xd8, 0xff, 0xe0, 1, 2, 3, 4, 0xff, 0xd9],
+    };
+
+    // self-certification of a photo id
+    let self_sig = alice
+        .certify_user_attribute(
+            || "".into(),
+            &alice.primary_key,
+            &photo,
+            SignatureType::CertPositive,
+        )
+        .unwrap();
+    let signed = SignedUserAttribute::new(photo.clone(), vec![self_sig.clone()]);
+    signed.verify(&alice.primary_key).unwrap();
+
+    // the signature is bound to the User Attribute packet type
+    assert!(self_sig
+        .verify_certification(&alice.primary_key, pgp::types::Tag::UserId, &photo)
+        .is_err());
+
+    // third-party certification, and its revocation
+    let cert = bob
+        .certify_user_attribute(
+            || "".into(),
+            &alice.primary_key,
+            &photo,
+            SignatureType::CertCasual,
+        )
+        .unwrap();
+    let revocation = bob
+        .certify_user_attribute(
+            || "".into(),
+            &alice.primary_key,
+            &photo,
+            SignatureType::CertRevocation,
+        )
+        .unwrap();
+    assert_eq!(revocation.typ(), SignatureType::CertRevocation);
+    // the issuer is identified by a hashed fingerprint
+    assert!(cert.config.hashed_subpackets().any(|p| p.data
+        == SubpacketData::IssuerFingerprint(
+            KeyVersion::V4,
+            SmallVec::from_slice(&bob.primary_key.fingerprint())
+        )));
+    let signed = SignedUserAttribute::new(photo.clone(), vec![cert, revocation]);
+    assert_eq!(signed.signatures.len(), 2);
+    signed
+        .verify_third_party(&alice.primary_key, &bob.primary_key)
+        .unwrap();
+    assert!(signed.verify(&alice.primary_key).is_err());
+
+    // altering a single byte of the image invalidates the certifications
+    let UserAttribute::Image {
+        packet_version,
+        header,
+        mut data,
+    } = photo
+    else {
+        unreachable!()
+    };
+    data[5] ^= 1;
+    let altered = UserAttribute::Image {
+        packet_version,
+        header,
+        data,
+    };
+    assert!(SignedUserAttribute::new(altered.clone(), vec![self_sig])
+        .verify(&alice.primary_key)
+        .is_err());
+    assert!(SignedUserAttribute::new(altered, signed.signatures)
+        .verify_third_party(&alice.primary_key, &bob.primary_key)
+        .is_err());
+
+    // user ids are certified the same way
+    let uid = &alice.details.users[0].id;
+    let cert = bob
+        .certify_user_id(
+            || "".into(),
+            &alice.primary_key,
+            uid,
+            SignatureType::CertGeneric,
+        )
+        .unwrap();
+    SignedUser::new(uid.clone(), vec![cert])
+        .verify_third_party(&alice.primary_key, &bob.primary_key)
+        .unwrap();
+}
+
+#[test]
+fn test_certification_levels() {
+    use pgp::packet::CertificationLevel;
+    use pgp::types::Tag;
+    use pgp::{KeyType, SecretKeyParamsBuilder};
+
+    let generate = |uid: &str| {
+        SecretKeyParamsBuilder::default()
+            .key_type(KeyType::EdDSA)
+            .can_certify(true)
+            .primary_user_id(uid.into())
+            .user_id("Other <other@example.org>")
+            .build()
+            .unwrap()
+            .generate()
+            .unwrap()
+            .sign(|| "".into())
+            .unwrap()
+    };
+    let alice = generate("Alice <alice@example.org>");
+    let bob = generate("Bob <bob@example.org>");
+
+    // generated self-signatures are positive certifications
+    for user in &alice.details.users {
+        let sig = &user.signatures[0];
+        assert_eq!(sig.typ(), SignatureType::CertPositive);
+        assert_eq!(sig.typ() as u8, 0x13);
+        assert_eq!(
+            sig.verify_certification_level(
+                &alice.primary_key,
+                &alice.primary_key,
+                Tag::UserId,
+                &user.id
+            )
+            .unwrap(),
+            CertificationLevel::Positive
+        );
+    }
+
+    // third-party certifications keep their level
+    let uid = &alice.details.users[0].id;
+    for level in [
+        CertificationLevel::Generic,
+        CertificationLevel::Persona,
+        CertificationLevel::Casual,
+        CertificationLevel::Positive,
+    ] {
+        let cert = bob
+            .certify_user_id(|| "".into(), &alice.primary_key, uid, level.into())
+            .unwrap();
+        assert_eq!(cert.certification_level(), Some(level));
+        assert_eq!(
+            cert.verify_certification_level(&alice.primary_key, &bob.primary_key, Tag::UserId, uid)
+                .unwrap(),
+            level
+        );
+        // made by bob, not alice
+        assert!(cert
+            .verify_certification_level(&alice.primary_key, &alice.primary_key, Tag::UserId, uid)
+            .is_err());
+    }
+
+    // revocations are certifications without a level
+    let revocation = bob
+        .certify_user_id(
+            || "".into(),
+            &alice.primary_key,
+            uid,
+            SignatureType::CertRevocation,
+        )
+        .unwrap();
+    assert!(revocation.is_certification());
+    assert_eq!(revocation.certification_level(), None);
+    revocation
+        .verify_third_party_certification(&alice.primary_key, &bob.primary_key, Tag::UserId, uid)
+        .unwrap();
+    assert!(revocation
+        .verify_certification_level(&alice.primary_key, &bob.primary_key, Tag::UserId, uid)
+        .is_err());
+}
+
+#[test]
+#[cfg(feature = "x25519")]
+fn test_revocations() {
+    use pgp::packet::{Packet, PacketParser, SignatureConfigBuilder};
+    use pgp::{KeyType, SecretKeyParamsBuilder, SubkeyParamsBuilder};
+
+    let mut key = SecretKeyParamsBuilder::default()
+        .key_type(KeyType::EdDSA)
+        .can_certify(true)
+        .primary_user_id("Me <me@mail.com>".into())
+        .subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::ECDH(ECCCurve::Curve25519))
+                .can_encrypt(true)
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap()
+        .generate()
+        .unwrap()
+        .sign(|| "".into())
+        .unwrap();
+    assert!(key.revocations().is_empty());
+    assert!(key
+        .details
+        .users
+        .iter()
+        .flat_map(|user| &user.signatures)
+        .all(|sig| !sig.is_revocation()));
+
+    // a key revocation, taken from a revocation certificate
+    let mut bytes = Vec::new();
+    armor::Dearmor::new(BufReader::new(read_file(
+        "./tests/openpgp/samplemsgs/revoke-2D727CC768697734.asc",
+    )))
+    .read_to_end(&mut bytes)
+    .unwrap();
+    let Some(Ok(Packet::Signature(key_revocation))) = PacketParser::new(&bytes[..]).next() else {
+        panic!("expected a signature");
+    };
+    assert!(key_revocation.is_revocation());
+    key.details
+        .revocation_signatures
+        .push(key_revocation.clone());
+
+    // revocation of the self-certification
+    let uid = key.details.users[0].id.clone();
+    let uid_revocation = key
+        .certify_user_id(
+            || "".into(),
+            &key.primary_key,
+            &uid,
+            SignatureType::CertRevocation,
+        )
+        .unwrap();
+    assert!(uid_revocation.is_revocation());
+    key.details.users[0].signatures.push(uid_revocation.clone());
+
+    // revocation of the subkey
+    let subkey_revocation = SignatureConfigBuilder::default()
+        .typ(SignatureType::SubkeyRevocation)
+        .pub_alg(key.primary_key.algorithm())
+        .hash_alg(HashAlgorithm::SHA2_256)
+        .hashed_subpackets(vec![Subpacket::regular(
+            SubpacketData::SignatureCreationTime(Utc::now()),
+        )])
+        .unhashed_subpackets(vec![])
+        .build()
+        .unwrap()
+        .sign_key_binding(&key.primary_key, || "".into(), &key.secret_subkeys[0].key)
+        .unwrap();
+    assert!(subkey_revocation.is_revocation());
+    key.secret_subkeys[0]
+        .signatures
+        .push(subkey_revocation.clone());
+    let subkey_id = key.secret_subkeys[0].key_id();
+
+    let expected = vec![
+        (Revoked::PrimaryKey, &key_revocation),
+        (Revoked::User(&key.details.users[0]), &uid_revocation),
+        (Revoked::Subkey(subkey_id.clone()), &subkey_revocation),
+    ];
+    assert_eq!(key.revocations(), expected);
+
+    let public_key = SignedPublicKey::from(key.clone());
+    let revocations = public_key.revocations();
+    assert_eq!(revocations.len(), 3);
+    assert_eq!(revocations[2].0, Revoked::Subkey(subkey_id));
+    assert_eq!(revocations[2].1, &subkey_revocation);
+}
+
+#[test]
+fn test_revoke_primary_key() {
+    use pgp::packet::{Packet, PacketParser, RevocationCode};
+
+    let (key, _) = SignedSecretKey::from_armor_single(read_file(
+        "./tests/autocrypt/alice@autocrypt.example.sec.asc",
+    ))
+    .unwrap();
+    let mut public_key = SignedPublicKey::from(key.clone());
+
+    let revocation = key
+        .revoke(|| "".into(), RevocationCode::KeyCompromised, "leaked")
+        .unwrap();
+    assert_eq!(revocation.typ(), SignatureType::KeyRevocation);
+    assert!(revocation.created().is_some());
+    assert_eq!(revocation.issuer(), vec![&key.key_id()]);
+    assert_eq!(
+        revocation.issuer_fingerprint(),
+        vec![&key.fingerprint()[..]]
+    );
+    assert_eq!(
+        revocation.revocation_reason_code(),
+        Some(&RevocationCode::KeyCompromised)
+    );
+    assert_eq!(
+        revocation.revocation_reason_string(),
+        Some(b"leaked".as_ref().into())
+    );
+    revocation.verify_key(&public_key).unwrap();
+
+    public_key
+        .details
+        .revocation_signatures
+        .push(revocation.clone());
+    public_key.verify().unwrap();
+    assert_eq!(
+        public_key.revocations(),
+        vec![(Revoked::PrimaryKey, &revocation)]
+    );
+
+    // the certificate holds just the signature
+    let cert = key
+        .revocation_certificate(|| "".into(), RevocationCode::KeyRetired, "")
+        .unwrap();
+    assert!(cert.starts_with("-----BEGIN PGP PUBLIC KEY BLOCK-----"));
+    let mut bytes = Vec::new();
+    armor::Dearmor::new(BufReader::new(cert.as_bytes()))
+        .read_to_end(&mut bytes)
+        .unwrap();
+    let mut packets = PacketParser::new(&bytes[..]);
+    let Some(Ok(Packet::Signature(revocation))) = packets.next() else {
+        panic!("expected a signature");
+    };
+    assert!(packets.next().is_none());
+    assert_eq!(
+        revocation.revocation_reason_code(),
+        Some(&RevocationCode::KeyRetired)
+    );
+    revocation.verify_key(&public_key).unwrap();
+}
+
+#[test]
+#[cfg(feature = "x25519")]
+fn test_revoke_subkey() {
+    use pgp::packet::RevocationCode;
+    use pgp::{KeyType, SecretKeyParamsBuilder, SubkeyParamsBuilder};
+
+    let key_params = SecretKeyParamsBuilder::default()
+        .key_type(KeyType::EdDSA)
+        .can_certify(true)
+        .can_sign(true)
+        .primary_user_id("Me <me@mail.com>".into())
+        .subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::ECDH(ECCCurve::Curve25519))
+                .can_encrypt(true)
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap();
+    let mut key = key_params.generate().unwrap().sign(|| "".into()).unwrap();
+    let public_key = SignedPublicKey::from(key.clone());
+    let subkey = public_key
+        .best_encryption_key(EncryptionPurpose::Any)
+        .unwrap();
+    assert!(!subkey.is_revoked(&public_key.primary_key));
+
+    let revocation = key
+        .revoke_subkey(
+            || "".into(),
+            &subkey.fingerprint(),
+            RevocationCode::KeySuperseded,
+            "replaced",
+        )
+        .unwrap();
+    assert_eq!(revocation.typ(), SignatureType::SubkeyRevocation);
+    assert_eq!(
+        revocation.revocation_reason_code(),
+        Some(&RevocationCode::KeySuperseded)
+    );
+    assert!(key
+        .revoke_subkey(
+            || "".into(),
+            &key.fingerprint(),
+            RevocationCode::NoReason,
+            ""
+        )
+        .is_err());
+
+    // the revocation survives serialization and is kept next to the binding
+    let key = SignedSecretKey::from_bytes(&key.to_bytes().unwrap()[..]).unwrap();
+    key.verify().unwrap();
+    assert_eq!(key.secret_subkeys[0].signatures.len(), 2);
+    assert!(key.secret_subkeys[0].is_revoked(&key.primary_key));
+
+    let public_key = SignedPublicKey::from(key);
+    let public_key = SignedPublicKey::from_bytes(&public_key.to_bytes().unwrap()[..]).unwrap();
+    public_key.verify().unwrap();
+    assert_eq!(
+        public_key.revocations(),
+        vec![(
+            Revoked::Subkey(public_key.public_subkeys[0].key_id()),
+            &revocation
+        )]
+    );
+    assert!(public_key.public_subkeys[0].is_revoked(&public_key.primary_key));
+    assert!(public_key
+        .best_encryption_key(EncryptionPurpose::Any)
+        .is_none());
+
+    // a revocation that does not verify is ignored
+    let mut forged = public_key.clone();
+    let sig = &mut forged.public_subkeys[0].signatures[1];
+    sig.signature[0] = sig.signature[1].clone();
+    assert!(!forged.public_subkeys[0].is_revoked(&forged.primary_key));
+}
+
+#[test]
+#[cfg(feature = "x25519")]
+fn test_generate_v4_key_with_seipd_v2_feature() {
+    use pgp::crypto::aead::AeadAlgorithm;
+    use pgp::packet::Features;
+    use pgp::{KeyType, MessageBuilder, SecretKeyParamsBuilder, SubkeyParamsBuilder};
+
+    let mut features = Features::default();
+    features.set_seipd_v1(true);
+    features.set_seipd_v2(true);
+
+    let key = SecretKeyParamsBuilder::default()
+        .key_type(KeyType::EdDSA)
+        .can_certify(true)
+        .can_sign(true)
+        .features(features)
+        .direct_key_signature(true)
+        .primary_user_id("Me <me@mail.com>".into())
+        .subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::ECDH(ECCCurve::Curve25519))
+                .can_encrypt(true)
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap()
+        .generate()
+        .unwrap()
+        .sign(|| "".into())
+        .unwrap();
+    key.verify().unwrap();
+    assert_eq!(key.primary_key.version(), KeyVersion::V4);
+    assert_eq!(key.details.features(&key.primary_key), &[0x09]);
+    assert_eq!(key.details.direct_signatures[0].features(), &[0x09]);
+    assert!(Features::from(key.details.features(&key.primary_key)).seipd_v2());
+
+    let public_key =
+        SignedPublicKey::from_bytes(&SignedPublicKey::from(key.clone()).to_bytes().unwrap()[..])
+            .unwrap();
+    assert_eq!(
+        public_key.details.features(&public_key.primary_key),
+        &[0x09]
+    );
+
+    // kept when signing the key again
+    let resigned = public_key.as_unsigned().sign(&key, || "".into()).unwrap();
+    assert_eq!(resigned.details.features(&resigned.primary_key), &[0x09]);
+
+    // the advertised feature is usable with SEIPDv2
+    let bytes = MessageBuilder::from_bytes("", b"hello world")
+        .seipd_v2(SymmetricKeyAlgorithm::AES256, AeadAlgorithm::Ocb, 6)
+        .encrypt_to_key(&public_key.public_subkeys[0])
+        .to_bytes(thread_rng())
+        .unwrap();
+    let message = pgp::Message::from_bytes(&bytes[..]).unwrap();
+    let (decrypted, _) = message.decrypt(String::new, &[&key]).unwrap();
+    assert_eq!(
+        decrypted.decompress().unwrap().get_content().unwrap(),
+        Some(b"hello world".to_vec())
+    );
+
+    // modification detection only, by default
+    let key = SecretKeyParamsBuilder::default()
+        .key_type(KeyType::EdDSA)
+        .primary_user_id("Me <me@mail.com>".into())
+        .build()
+        .unwrap()
+        .generate()
+        .unwrap()
+        .sign(|| "".into())
+        .unwrap();
+    assert_eq!(key.details.features(&key.primary_key), &[0x01]);
+
+    // keys without a Features subpacket do not get one when signed again
+    let mut public_key = SignedPublicKey::from(key.clone());
+    let mut config = public_key.details.users[0].signatures[0].config.clone();
+    config
+        .hashed_subpackets_mut()
+        .retain(|p| !matches!(p.data, SubpacketData::Features(_)));
+    public_key.details.users[0].signatures[0] = config
+        .sign_certification(
+            &key,
+            || "".into(),
+            pgp::types::Tag::UserId,
+            &public_key.details.users[0].id,
+        )
+        .unwrap();
+    let resigned = public_key.as_unsigned().sign(&key, || "".into()).unwrap();
+    resigned.verify().unwrap();
+    assert!(resigned.details.users[0].signatures[0]
+        .config
+        .hashed_subpackets()
+        .all(|p| !matches!(p.data, SubpacketData::Features(_))));
+}
+
+#[test]
+fn test_keyring_component_size_limits() {
+    let (key, _) = SignedPublicKey::from_armor_single(read_file(
+        "./tests/autocrypt/alice@autocrypt.example.pub.asc",
+    ))
+    .unwrap();
+    assert_eq!(key.details.users.len(), 1);
+    let sig = key.details.users[0].signatures[0].clone();
+
+    let mut oversized = key.clone();
+    let long_id = format!("{} <alice@example.org>", "a".repeat(3000));
+    oversized.details.users.push(SignedUser::new(
+        UserId::from_str(Default::default(), &long_id),
+        vec![sig.clone()],
+    ));
+    oversized
+        .details
+        .user_attributes
+        .push(SignedUserAttribute::new(
+            UserAttribute::Unknown {
+                packet_version: Default::default(),
+                typ: 100,
+                data: vec![0; 4096],
+            },
+            vec![sig],
+        ));
+    assert_eq!(oversized.details.users[1].id.packet_len(), long_id.len());
+    assert_eq!(oversized.details.user_attributes[0].attr.packet_len(), 4097);
+    let bytes = oversized.to_bytes().unwrap();
+
+    let parse = |limits: KeyringLimits| -> Result<Vec<PublicOrSecret>, Error> {
+        from_bytes_many_with_limits(&bytes[..], limits).collect()
+    };
+
+    // the oversized user id is dropped, with its signature
+    let keys = parse(KeyringLimits::default()).unwrap();
+    assert_eq!(keys.len(), 1);
+    let PublicOrSecret::Public(parsed) = &keys[0] else {
+        panic!("expected a public key");
+    };
+    assert_eq!(parsed.details.users, key.details.users);
+    assert_eq!(parsed.details.user_attributes.len(), 1);
+
+    // the default limits also apply without a keyring
+    let parsed = SignedPublicKey::from_bytes(&bytes[..]).unwrap();
+    assert_eq!(parsed.details.users, key.details.users);
+
+    // tighter limit for user attributes
+    let limits = KeyringLimits {
+        max_user_attribute_size: 1024,
+        ..Default::default()
+    };
+    let keys = parse(limits).unwrap();
+    let PublicOrSecret::Public(parsed) = &keys[0] else {
+        panic!("expected a public key");
+    };
+    assert_eq!(parsed.details, key.details);
+    parsed.verify().unwrap();
+
+    // hard fail
+    let limits = KeyringLimits {
+        strict: true,
+        ..Default::default()
+    };
+    assert!(parse(limits).is_err());
+
+    let limits = KeyringLimits {
+        max_user_id_size: 4096,
+        strict: true,
+        ..Default::default()
+    };
+    assert_eq!(parse(limits).unwrap().len(), 1);
+}
+
+#[test]
+#[cfg(feature = "x25519")]
+fn test_subkey_binding_expiration() {
+    use chrono::{Duration, SubsecRound};
+    use pgp::composed::EncryptionPurpose;
+    use pgp::{KeyType, SecretKeyParamsBuilder, SubkeyParamsBuilder};
+
+    let now = Utc::now().trunc_subsecs(0);
+    let subkey = |created_at| {
+        SubkeyParamsBuilder::default()
+            .key_type(KeyType::ECDH(ECCCurve::Curve25519))
+            .can_encrypt(true)
+            .created_at(created_at)
+            .build()
+            .unwrap()
+    };
+    let key = SecretKeyParamsBuilder::default()
+        .key_type(KeyType::EdDSA)
+        .can_certify(true)
+        .can_sign(true)
+        .primary_user_id("Me <me@mail.com>".into())
+        .created_at(now - Duration::days(100))
+        .subkey(subkey(now - Duration::days(40)))
+        .subkey(subkey(now - Duration::days(100)))
+        .build()
+        .unwrap()
+        .generate()
+        .unwrap()
+        .sign(|| "".into())
+        .unwrap();
+    let mut public_key = key.public_key().sign(&key, || "".into()).unwrap();
+    assert_eq!(public_key.expires_at(), None);
+    assert_eq!(public_key.public_subkeys[0].expires_at(), None);
+    assert_eq!(
+        public_key
+            .best_encryption_key(EncryptionPurpose::Any)
+            .unwrap()
+            .key_id(),
+        public_key.public_subkeys[0].key_id()
+    );
+
+    // the newer subkey expired 10 days ago, while the primary key does not expire
+    public_key.public_subkeys[0] = public_key.public_subkeys[0]
+        .as_unsigned()
+        .with_expiration(Duration::days(30))
+        .sign(&key, || "".into())
+        .unwrap();
+    public_key.verify().unwrap();
+
+    let (public_key, _) = SignedPublicKey::from_armor_single(
+        &public_key
+            .to_armored_bytes(pgp::ArmorOptions::default())
+            .unwrap()[..],
+    )
+    .unwrap();
+    let subkey = &public_key.public_subkeys[0];
+    assert_eq!(subkey.expires_at(), Some(now - Duration::days(10)));
+    assert!(subkey.is_expired_at(now));
+    assert!(!subkey.is_expired_at(now - Duration::days(11)));
+    assert_eq!(public_key.expires_at(), None);
+    assert_eq!(
+        public_key
+            .best_encryption_key(EncryptionPurpose::Any)
+            .unwrap()
+            .key_id(),
+        public_key.public_subkeys[1].key_id()
+    );
+}
+
+#[test]
+#[cfg(feature = "x25519")]
+fn test_subkey_key_flags_from_valid_binding() {
+    use chrono::Duration;
+    use pgp::composed::EncryptionPurpose;
+    use pgp::{KeyType, SecretKeyParamsBuilder, SubkeyParamsBuilder};
+
+    let params = |uid: &str| {
+        SecretKeyParamsBuilder::default()
+            .key_type(KeyType::EdDSA)
+            .can_certify(true)
+            .primary_user_id(uid.into())
+            .subkey(
+                SubkeyParamsBuilder::default()
+                    .key_type(KeyType::ECDH(ECCCurve::Curve25519))
+                    .can_encrypt(true)
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap()
+    };
+    let key = params("Me <me@mail.com>")
+        .generate()
+        .unwrap()
+        .sign(|| "".into())
+        .unwrap();
+    let mallory = params("Mallory <mallory@mail.com>")
+        .generate()
+        .unwrap()
+        .sign(|| "".into())
+        .unwrap();
+    let mut public_key = key.public_key().sign(&key, || "".into()).unwrap();
+
+    // a newer binding by another key, making the subkey authentication only
+    let binding = public_key.public_subkeys[0].signatures[0].clone();
+    let mut flags = KeyFlags::default();
+    flags.set_authentication(true);
+    let config = pgp::packet::SignatureConfig::new_v4(
+        SignatureVersion::V4,
+        SignatureType::SubkeyBinding,
+        mallory.algorithm(),
+        HashAlgorithm::SHA2_256,
+        vec![
+            Subpacket::regular(SubpacketData::SignatureCreationTime(
+                *binding.created().unwrap() + Duration::seconds(60),
+            )),
+            Subpacket::regular(SubpacketData::KeyFlags(flags.into())),
+            Subpacket::regular(SubpacketData::KeyExpirationTime(Duration::seconds(1))),
+        ],
+        vec![],
+    );
+    let forged = config
+        .sign_key_binding(&mallory, || "".into(), &public_key.public_subkeys[0].key)
+        .unwrap();
+    public_key.public_subkeys[0].signatures.push(forged);
+
+    let (public_key, _) = SignedPublicKey::from_armor_single(
+        &public_key
+            .to_armored_bytes(pgp::ArmorOptions::default())
+            .unwrap()[..],
+    )
+    .unwrap();
+    let subkey = &public_key.public_subkeys[0];
+    assert!(subkey.key_flags().unwrap().encrypt_comms());
+    assert_eq!(subkey.expires_at(), None);
+    assert_eq!(subkey.as_unsigned().key_flags(), Some(binding.key_flags()));
+    assert!(public_key
+        .best_encryption_key(EncryptionPurpose::Any)
+        .is_some());
+
+    // a subkey on its own has no primary key to check the bindings against
+    let detached = SignedPublicSubKey::new(subkey.key.clone(), subkey.signatures.clone());
+    assert_eq!(detached.key_flags(), None);
+    assert_eq!(&detached, subkey);
+}
+
+#[test]
+#[cfg(feature = "x25519")]
+fn test_extend_expiration() {
+    use pgp::{KeyType, SecretKeyParamsBuilder, SubkeyParamsBuilder};
+
+    let key = SecretKeyParamsBuilder::default()
+        .key_type(KeyType::EdDSA)
+        .can_certify(true)
+        .can_sign(true)
+        .direct_key_signature(true)
+        .passphrase(Some("pw".into()))
+        .expiration(Some(std::time::Duration::from_secs(24 * 60 * 60)))
+        .primary_user_id("Me <me@mail.com>".into())
+        .user_id("Other <other@mail.com>".to_string())
+        .subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::ECDH(ECCCurve::Curve25519))
+                .passphrase(Some("pw".into()))
+                .expiration(Some(std::time::Duration::from_secs(24 * 60 * 60)))
+                .can_encrypt(true)
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap()
+        .generate()
+        .unwrap()
+        .sign(|| "pw".into())
+        .unwrap();
+    let armored = key.to_armored_string(Default::default()).unwrap();
+
+    let refreshed =
+        pgp::maintain::extend_expiration(&armored, || "pw".into(), chrono::Duration::days(90))
+            .unwrap();
+    let (refreshed, _) = SignedSecretKey::from_string(&refreshed).unwrap();
+    refreshed.verify().unwrap();
+
+    // the same key material, with an additional self-signature on each component
+    assert_eq!(refreshed.fingerprint(), key.fingerprint());
+    assert_eq!(
+        refreshed.secret_subkeys[0].fingerprint(),
+        key.secret_subkeys[0].fingerprint()
+    );
+    for (user, old) in refreshed.details.users.iter().zip(&key.details.users) {
+        assert_eq!(user.signatures.len(), old.signatures.len() + 1);
+    }
+    assert_eq!(refreshed.details.direct_signatures.len(), 2);
+    assert_eq!(refreshed.secret_subkeys[0].signatures.len(), 2);
+
+    let expected = Utc::now() + chrono::Duration::days(90);
+    let close_to_expected =
+        |expires_at: DateTime<Utc>| (expires_at - expected).num_seconds().abs() < 60;
+    assert!(close_to_expected(refreshed.expires_at().unwrap()));
+    assert!(close_to_expected(
+        refreshed.secret_subkeys[0].expires_at().unwrap()
+    ));
+
+    // errors name the failing step
+    let err =
+        pgp::maintain::extend_expiration(&armored, || "wrong".into(), chrono::Duration::days(90))
+            .unwrap_err();
+    match &err {
+        Error::Maintenance { step, .. } => assert!(step.starts_with("re-sign user id"), "{err}"),
+        _ => panic!("unexpected error {err:?}"),
+    }
+
+    let err =
+        pgp::maintain::extend_expiration("garbage", || "pw".into(), chrono::Duration::days(90))
+            .unwrap_err();
+    assert!(matches!(&err, Error::Maintenance { step, .. } if step == "parse the key"));
+}
+
+#[test]
+fn test_generate_with_subpacket_criticality() {
+    use pgp::packet::SubpacketCriticality;
+    use pgp::{KeyType, SecretKeyParamsBuilder, SubkeyParamsBuilder};
+
+    let criticality = SubpacketCriticality {
+        key_flags: true,
+        ..Default::default()
+    };
+    let key = SecretKeyParamsBuilder::default()
+        .key_type(KeyType::EdDSA)
+        .can_certify(true)
+        .can_sign(true)
+        .direct_key_signature(true)
+        .subpacket_criticality(criticality)
+        .primary_user_id("Me <me@mail.com>".into())
+        .user_id("Other <other@mail.com>".to_string())
+        .subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::EdDSA)
+                .can_sign(true)
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap()
+        .generate()
+        .unwrap()
+        .sign(String::new)
+        .unwrap();
+    key.verify().unwrap();
+
+    let subkey = &key.secret_subkeys[0];
+    let backsig = subkey.signatures[0]
+        .embedded_signature()
+        .expect("signing subkeys have a backsig");
+    let sigs = key
+        .details
+        .direct_signatures
+        .iter()
+        .chain(key.details.users.iter().flat_map(|u| &u.signatures))
+        .chain(&subkey.signatures)
+        .chain(std::iter::once(backsig));
+
+    let mut key_flags = 0;
+    for sig in sigs {
+        for subpacket in sig.config.hashed_subpackets() {
+            let critical = matches!(subpacket.data, SubpacketData::KeyFlags(_));
+            key_flags += usize::from(critical);
+            assert_eq!(subpacket.is_critical, critical, "{subpacket:?}");
+        }
+    }
+    // the direct key signature, two user ids and the subkey binding
+    assert_eq!(key_flags, 4);
+}
+
+#[test]
+fn test_generate_user_attribute_subpacket_criticality() {
+    use pgp::packet::SubpacketCriticality;
+    use pgp::{KeyType, SecretKeyParamsBuilder};
+
+    let mut header = vec![0x01, 0x01];
+    header.resize(14, 0);
+    let photo = UserAttribute::Image {
+        packet_version: Default::default(),
+        header,
+        data: vec![0xff, 0xd8, 0xff, 0xe0, 1, 2, 3, 4, 0xff, 0xd9],
+    };
+
+    let criticality = SubpacketCriticality {
+        signature_creation_time: true,
+        issuer_fingerprint: true,
+        ..Default::default()
+    };
+    let key = SecretKeyParamsBuilder::default()
+        .key_type(KeyType::EdDSA)
+        .can_certify(true)
+        .subpacket_criticality(criticality)
+        .primary_user_id("Me <me@mail.com>".into())
+        .user_attributes(vec![photo])
+        .build()
+        .unwrap()
+        .generate()
+        .unwrap()
+        .sign(String::new)
+        .unwrap();
+    key.verify().unwrap();
+
+    let sigs = &key.details.user_attributes[0].signatures;
+    assert_eq!(sigs.len(), 1);
+    let sig = &sigs[0];
+    assert_eq!(sig.typ(), SignatureType::CertPositive);
+
+    let mut issuer_fingerprint = None;
+    for subpacket in sig.config.hashed_subpackets() {
+        match &subpacket.data {
+            SubpacketData::SignatureCreationTime(_) => assert!(subpacket.is_critical),
+            SubpacketData::IssuerFingerprint(version, fp) => {
+                assert!(subpacket.is_critical);
+                issuer_fingerprint = Some((*version, fp.to_vec()));
+            }
+            _ => assert!(!subpacket.is_critical, "{subpacket:?}"),
+        }
+    }
+    assert_eq!(
+        issuer_fingerprint,
+        Some((key.primary_key.version(), key.fingerprint()))
+    );
+}
+
+#[test]
+fn test_merge_public_keys() {
+    let read_pub = |path: &str| {
+        SignedPublicKey::from_armor_single(File::open(path).unwrap())
+            .unwrap()
+            .0
+    };
+    let alice = read_pub("./tests/autocrypt/alice@autocrypt.example.pub.asc");
+    let bob = read_pub("./tests/autocrypt/bob@autocrypt.example.pub.asc");
+    let (bob_sec, _) = SignedSecretKey::from_armor_single(
+        File::open("./tests/autocrypt/bob@autocrypt.example.sec.asc").unwrap(),
+    )
+    .unwrap();
+
+    // unrelated keys are not merged
+    assert!(!alice.same_primary(&bob));
+    let err = alice.clone().merge(bob.clone()).unwrap_err();
+    match err {
+        Error::PrimaryKeyMismatch { expected, found } => {
+            assert_eq!(expected, alice.fingerprint());
+            assert_eq!(found, bob.fingerprint());
+        }
+        err => panic!("unexpected error {err:?}"),
+    }
+
+    // merging a key with itself changes nothing
+    assert!(alice.same_primary(&alice));
+    assert_eq!(alice.clone().merge(alice.clone()).unwrap(), alice);
+
+    // a certification by bob is picked up
+    let mut certified = alice.clone();
+    let user = &mut certified.details.users[0];
+    let cert = bob_sec
+        .certify_user_id(String::new, &alice, &user.id, SignatureType::CertGeneric)
+        .unwrap();
+    user.signatures.push(cert.clone());
+    assert!(alice.same_primary(&certified));
+
+    let merged = alice.clone().merge(certified).unwrap();
+    assert_eq!(
+        merged.details.users[0].signatures.len(),
+        alice.details.users[0].signatures.len() + 1
+    );
+    assert_eq!(merged.details.users[0].signatures.last(), Some(&cert));
+    assert_eq!(merged.public_subkeys, alice.public_subkeys);
+}
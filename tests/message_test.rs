@@ -6,13 +6,18 @@ extern crate serde_json;
 extern crate serde;
 extern crate pgp;
 extern crate pretty_env_logger;
+#[cfg(any(feature = "rsa", feature = "x25519"))]
 #[macro_use]
 extern crate log;
 
 use std::fs::File;
+#[cfg(any(feature = "rsa", feature = "x25519"))]
 use std::io::Read;
 
-use pgp::composed::{Deserializable, Message, SignedPublicKey, SignedSecretKey};
+use pgp::composed::{Deserializable, Message};
+#[cfg(any(feature = "rsa", feature = "x25519", feature = "compression-deflate"))]
+use pgp::composed::{SignedPublicKey, SignedSecretKey};
+#[cfg(any(feature = "rsa", feature = "x25519", feature = "compression-deflate"))]
 use pgp::types::KeyTrait;
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -28,6 +33,7 @@ struct Testcase {
     keyid: Option<String>,
 }
 
+#[cfg(any(feature = "rsa", feature = "x25519"))]
 fn test_parse_msg(entry: &str, base_path: &str, is_normalized: bool) {
     let _ = pretty_env_logger::try_init();
 
@@ -146,9 +152,11 @@ fn test_parse_msg(entry: &str, base_path: &str, is_normalized: bool) {
     assert_eq!(message, message2);
 }
 
+// All of these fixtures are encrypted to RSA keys.
 macro_rules! msg_test {
     ($name:ident, $pos:expr, $normalized:expr) => {
         #[test]
+        #[cfg(feature = "rsa")]
         fn $name() {
             test_parse_msg(
                 &format!("{}.json", $pos),
@@ -210,8 +218,11 @@ msg_test!(msg_pgp_10_0_005, "pgp-10-0-005", false);
 msg_test!(msg_pgp_10_0_006, "pgp-10-0-006", false);
 msg_test!(msg_pgp_10_0_007, "pgp-10-0-007", false);
 
+#[cfg(feature = "camellia")]
 msg_test!(msg_camellia128_001, "camellia128-001", false);
+#[cfg(feature = "camellia")]
 msg_test!(msg_camellia192_001, "camellia192-001", false);
+#[cfg(feature = "camellia")]
 msg_test!(msg_camellia256_001, "camellia256-001", false);
 
 // ECDH
@@ -219,9 +230,11 @@ msg_test!(msg_camellia256_001, "camellia256-001", false);
 
 msg_test!(msg_openpgp_001, "openpgp-001", false);
 
+// The openpgp.js fixtures are encrypted to X25519 keys.
 macro_rules! msg_test_js {
     ($name:ident, $pos:expr, $normalized:expr) => {
         #[test]
+        #[cfg(feature = "x25519")]
         fn $name() {
             test_parse_msg(&format!("{}.json", $pos), "./tests/openpgpjs", $normalized);
         }
@@ -243,6 +256,55 @@ fn msg_regression_01() {
 }
 
 #[test]
+#[cfg(feature = "compression-deflate")]
+fn msg_compressed_only() {
+    use pgp::packet::DataMode;
+    use pgp::ser::Serialize;
+    use pgp::types::CompressionAlgorithm;
+
+    // `gpg -z 6 --compress-algo <alg> --store hello.txt`, an old format compressed data packet
+    // of indeterminate length holding the literal data
+    for (alg, name) in [
+        (CompressionAlgorithm::ZIP, "zip"),
+        (CompressionAlgorithm::ZLIB, "zlib"),
+    ] {
+        let bytes =
+            std::fs::read(format!("./tests/unit-tests/compressed/hello-{name}.pgp")).unwrap();
+        let msg = Message::from_bytes(&bytes[..]).unwrap();
+        let Message::Compressed(ref data) = msg else {
+            panic!("{name}: not compressed: {msg:?}");
+        };
+        assert_eq!(data.compression_algorithm(), alg, "{name}");
+        assert_eq!(
+            msg.get_content().unwrap().unwrap(),
+            b"hello from a compressed message\n",
+            "{name}"
+        );
+
+        // serializing keeps the compressed data as is
+        let reparsed = Message::from_bytes(&msg.to_bytes().unwrap()[..]).unwrap();
+        assert_eq!(reparsed, msg, "{name}");
+
+        let literal = reparsed.decompress().unwrap();
+        let literal = literal.get_literal().expect("not a literal");
+        assert_eq!(literal.mode(), DataMode::Binary, "{name}");
+        assert_eq!(literal.file_name(), "hello.txt", "{name}");
+        assert_eq!(
+            literal.data(),
+            b"hello from a compressed message\n",
+            "{name}"
+        );
+    }
+
+    // BZip2 is not supported, the message parses but does not decompress
+    let bytes = std::fs::read("./tests/unit-tests/compressed/hello-bzip2.pgp").unwrap();
+    let msg = Message::from_bytes(&bytes[..]).unwrap();
+    assert!(matches!(msg, Message::Compressed(_)));
+    assert!(msg.decompress().is_err());
+}
+
+#[test]
+#[cfg(feature = "x25519")]
 fn msg_large_indeterminate_len() {
     let _ = pretty_env_logger::try_init();
 
@@ -338,6 +400,7 @@ test1
 }
 
 #[test]
+#[cfg(feature = "compression-deflate")]
 fn msg_literal_signature() {
     let (pkey, _) = SignedPublicKey::from_armor_single(
         File::open("./tests/autocrypt/alice@autocrypt.example.pub.asc").unwrap(),
@@ -348,3 +411,709 @@ fn msg_literal_signature() {
 
     msg.verify(&pkey).unwrap();
 }
+
+#[test]
+#[cfg(feature = "x25519")]
+fn msg_decrypt_detailed() {
+    use pgp::crypto::sym::SymmetricKeyAlgorithm;
+    use pgp::types::{SecretKeyTrait, StringToKey};
+    use pgp::{Decrypted, MessageBuilder, OpenedBy};
+
+    let mut rng = rand::thread_rng();
+    let (alice, _) = SignedSecretKey::from_armor_single(
+        File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+    )
+    .unwrap();
+    let (bob, _) = SignedSecretKey::from_armor_single(
+        File::open("./tests/draft-bre-openpgp-samples-00/bob.sec.asc").unwrap(),
+    )
+    .unwrap();
+    let subkey = &alice.secret_subkeys[0];
+
+    let bytes = MessageBuilder::from_bytes("", b"hello world")
+        .seipd_v1(SymmetricKeyAlgorithm::AES128)
+        .encrypt_to_key(&subkey.public_key())
+        .encrypt_with_password(StringToKey::new_default(&mut rng), || "secret".into())
+        .to_bytes(&mut rng)
+        .unwrap();
+    let msg = Message::from_bytes(&bytes[..]).unwrap();
+    let password = Some(|| "secret".to_string());
+    let no_password: Option<fn() -> String> = None;
+
+    let check = |res: Decrypted, opened_by: OpenedBy| {
+        assert_eq!(res.opened_by, opened_by);
+        assert_eq!(
+            res.message.decompress().unwrap().get_content().unwrap(),
+            Some(b"hello world".to_vec())
+        );
+    };
+    let by_key = OpenedBy::Pkesk {
+        index: 0,
+        fingerprint: subkey.fingerprint(),
+    };
+
+    // keys are preferred when both are given
+    check(
+        msg.decrypt_detailed(String::new, &[&alice], password)
+            .unwrap(),
+        by_key.clone(),
+    );
+    check(
+        msg.decrypt_detailed(String::new, &[&alice], no_password)
+            .unwrap(),
+        by_key,
+    );
+    check(
+        msg.decrypt_detailed(String::new, &[&bob], password)
+            .unwrap(),
+        OpenedBy::Skesk { index: 1 },
+    );
+    check(
+        msg.decrypt_detailed(String::new, &[], password).unwrap(),
+        OpenedBy::Skesk { index: 1 },
+    );
+
+    assert!(msg
+        .decrypt_detailed(String::new, &[&bob], no_password)
+        .is_err());
+    assert!(msg
+        .decrypt_detailed(String::new, &[&bob], Some(|| "wrong".to_string()))
+        .is_err());
+}
+
+#[test]
+#[cfg(feature = "rsa")]
+fn msg_decrypt_key_id_collision() {
+    use pgp::crypto::sym::SymmetricKeyAlgorithm;
+    use pgp::errors::Error;
+    use pgp::types::SecretKeyTrait;
+    use pgp::MessageBuilder;
+
+    let (alice, _) = SignedSecretKey::from_armor_single(
+        File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+    )
+    .unwrap();
+    let (bob, _) = SignedSecretKey::from_armor_single(
+        File::open("./tests/draft-bre-openpgp-samples-00/bob.sec.asc").unwrap(),
+    )
+    .unwrap();
+    let alice_subkey = alice.secret_subkeys[0].public_key();
+    let bob_subkey = bob.secret_subkeys[0].public_key();
+
+    // Replaces the key id of the packet to bob with the one of alice, to simulate a collision.
+    let collide = |bytes: Vec<u8>| {
+        let bob_id = bob_subkey.key_id();
+        let pos = bytes.windows(8).position(|w| w == bob_id.as_ref()).unwrap();
+        let mut bytes = bytes;
+        bytes[pos..pos + 8].copy_from_slice(alice_subkey.key_id().as_ref());
+        Message::from_bytes(&bytes[..]).unwrap()
+    };
+
+    let msg = collide(
+        MessageBuilder::from_bytes("", b"hello world")
+            .seipd_v1(SymmetricKeyAlgorithm::AES128)
+            .encrypt_to_key(&bob_subkey)
+            .encrypt_to_key(&alice_subkey)
+            .to_bytes(rand::thread_rng())
+            .unwrap(),
+    );
+    assert_eq!(
+        msg.get_recipients(),
+        vec![&alice_subkey.key_id(), &alice_subkey.key_id()]
+    );
+
+    // the bogus packet comes first, but the next one is tried when it fails
+    let (decrypted, ids) = msg.decrypt(String::new, &[&alice]).unwrap();
+    assert_eq!(ids, vec![alice_subkey.key_id()]);
+    assert_eq!(
+        decrypted.decompress().unwrap().get_content().unwrap(),
+        Some(b"hello world".to_vec())
+    );
+
+    // with only the bogus packet, the cause is reported
+    let msg = collide(
+        MessageBuilder::from_bytes("", b"hello world")
+            .seipd_v1(SymmetricKeyAlgorithm::AES128)
+            .encrypt_to_key(&bob_subkey)
+            .to_bytes(rand::thread_rng())
+            .unwrap(),
+    );
+    match msg.decrypt(String::new, &[&alice]) {
+        Err(Error::NoEskOpened(errors)) => {
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].0, 0);
+        }
+        res => panic!("unexpected result: {:?}", res.map(|(_, ids)| ids)),
+    }
+    assert!(matches!(
+        msg.decrypt_detailed(String::new, &[&alice], None::<fn() -> String>),
+        Err(Error::NoEskOpened(_))
+    ));
+}
+
+#[test]
+#[cfg(feature = "x25519")]
+fn msg_decrypt_wildcard_recipient() {
+    use pgp::crypto::sym::SymmetricKeyAlgorithm;
+    use pgp::types::SecretKeyTrait;
+    use pgp::{MessageBuilder, OpenedBy, SecretKeyRing};
+
+    let (alice, _) = SignedSecretKey::from_armor_single(
+        File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+    )
+    .unwrap();
+    let alice_subkey = alice.secret_subkeys[0].public_key();
+
+    // Replaces the recipient key id with the wildcard.
+    let mut bytes = MessageBuilder::from_bytes("", b"hello world")
+        .seipd_v1(SymmetricKeyAlgorithm::AES128)
+        .encrypt_to_key(&alice_subkey)
+        .to_bytes(rand::thread_rng())
+        .unwrap();
+    let id = alice_subkey.key_id();
+    let pos = bytes.windows(8).position(|w| w == id.as_ref()).unwrap();
+    bytes[pos..pos + 8].fill(0);
+    let msg = Message::from_bytes(&bytes[..]).unwrap();
+    assert!(msg.get_recipients()[0].is_wildcard());
+
+    // all decryption paths try the wildcard packet with every (sub)key
+    let (_, ids) = msg.decrypt(String::new, &[&alice]).unwrap();
+    assert_eq!(ids, vec![alice_subkey.key_id()]);
+
+    let ring = SecretKeyRing::from(alice.clone());
+    let (_, id) = msg.decrypt_with_ring(|_| String::new(), &ring).unwrap();
+    assert_eq!(id, alice_subkey.key_id());
+
+    let decrypted = msg
+        .decrypt_detailed(String::new, &[&alice], None::<fn() -> String>)
+        .unwrap();
+    assert_eq!(
+        decrypted.opened_by,
+        OpenedBy::Pkesk {
+            index: 0,
+            fingerprint: alice_subkey.fingerprint(),
+        }
+    );
+}
+
+#[test]
+#[cfg(feature = "x25519")]
+fn msg_parse_esk_only() {
+    use pgp::crypto::public_key::PublicKeyAlgorithm;
+    use pgp::crypto::sym::SymmetricKeyAlgorithm;
+    use pgp::ser::Serialize;
+    use pgp::types::{SecretKeyTrait, StringToKey};
+    use pgp::{Esk, MessageBuilder};
+
+    let (alice, _) = SignedSecretKey::from_armor_single(
+        File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+    )
+    .unwrap();
+    let subkey = alice.secret_subkeys[0].public_key();
+
+    let mut plaintext = vec![0u8; 100_000];
+    rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut plaintext);
+    let bytes = MessageBuilder::from_bytes("", &plaintext[..])
+        .seipd_v1(SymmetricKeyAlgorithm::AES128)
+        .encrypt_to_key(&subkey)
+        .encrypt_with_password(StringToKey::new_default(rand::thread_rng()), || {
+            "secret".into()
+        })
+        .to_bytes(rand::thread_rng())
+        .unwrap();
+    let full = Message::from_bytes(&bytes[..]).unwrap();
+    let Message::Encrypted { esk: full_esk, .. } = &full else {
+        panic!("not encrypted");
+    };
+
+    // the encrypted data is not needed
+    let truncated = &bytes[..bytes.len() - 90_000];
+    assert!(Message::from_bytes(truncated).is_err());
+
+    let esk = Message::parse_esk_only(truncated).unwrap();
+    assert_eq!(&esk, full_esk);
+    assert_eq!(esk.len(), 2);
+    match &esk[0] {
+        Esk::PublicKeyEncryptedSessionKey(pkesk) => {
+            assert_eq!(pkesk.id(), &subkey.key_id());
+            assert_eq!(pkesk.algorithm(), PublicKeyAlgorithm::ECDH);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(matches!(esk[1], Esk::SymKeyEncryptedSessionKey(_)));
+
+    // not an encrypted message
+    let literal = Message::new_literal("", "hello").to_bytes().unwrap();
+    assert!(Message::parse_esk_only(&literal[..]).is_err());
+
+    // ESKs without encrypted data
+    let esk_len = bytes.len()
+        - match &full {
+            Message::Encrypted { edata, .. } => edata.to_bytes().unwrap().len(),
+            _ => unreachable!(),
+        };
+    assert!(Message::parse_esk_only(&bytes[..esk_len]).is_err());
+}
+
+#[test]
+#[cfg(feature = "compression-deflate")]
+fn msg_classify() {
+    use pgp::crypto::hash::HashAlgorithm;
+    use pgp::crypto::sym::SymmetricKeyAlgorithm;
+    use pgp::ser::Serialize;
+    use pgp::types::CompressionAlgorithm;
+    use pgp::MessageKind;
+
+    let literal = Message::new_literal("", "hello");
+    let bytes = literal.to_bytes().unwrap();
+    assert_eq!(Message::classify(&bytes[..]).unwrap(), MessageKind::Literal);
+
+    // marker and padding packets are skipped
+    let mut padded = vec![0xCA, 0x03, b'P', b'G', b'P', 0xD5, 0x02, 0x00, 0x00];
+    padded.extend_from_slice(&bytes);
+    assert_eq!(
+        Message::classify(&padded[..]).unwrap(),
+        MessageKind::Literal
+    );
+
+    let compressed = literal.compress(CompressionAlgorithm::ZLIB).unwrap();
+    let bytes = compressed.to_bytes().unwrap();
+    assert_eq!(
+        Message::classify(&bytes[..]).unwrap(),
+        MessageKind::Compressed
+    );
+
+    let (alice, _) = SignedSecretKey::from_armor_single(
+        File::open("./tests/unit-tests/text_signature_normalization_alice.key").unwrap(),
+    )
+    .unwrap();
+    let signed = literal
+        .clone()
+        .sign(&alice, String::new, HashAlgorithm::SHA2_256)
+        .unwrap();
+    let armored = signed.to_armored_bytes(None.into()).unwrap();
+    assert_eq!(
+        Message::classify(&armored[..]).unwrap(),
+        MessageKind::OnePassSigned
+    );
+
+    // a signature packet in front of the signed data
+    let Message::Signed { signature, .. } = signed else {
+        panic!("not signed");
+    };
+    let mut bytes = Vec::new();
+    pgp::packet::write_packet(&mut bytes, &signature).unwrap();
+    literal.to_writer(&mut bytes).unwrap();
+    assert_eq!(Message::classify(&bytes[..]).unwrap(), MessageKind::Signed);
+
+    let msg = File::open("./tests/unit-tests/text_signature_normalization.msg").unwrap();
+    assert_eq!(Message::classify(msg).unwrap(), MessageKind::OnePassSigned);
+
+    let cleartext = File::open("./tests/unit-tests/cleartext-msg-01.asc").unwrap();
+    assert_eq!(
+        Message::classify(cleartext).unwrap(),
+        MessageKind::CleartextSigned
+    );
+
+    // only the first packet header is needed
+    let encrypted = pgp::encrypt_with_password(
+        &mut rand::thread_rng(),
+        &[0u8; 10_000],
+        "secret",
+        SymmetricKeyAlgorithm::AES128,
+        None,
+    )
+    .unwrap();
+    assert_eq!(
+        Message::classify(&encrypted[..4]).unwrap(),
+        MessageKind::Encrypted
+    );
+
+    // not a message
+    assert!(Message::classify(&[][..]).is_err());
+    let key = File::open("./tests/unit-tests/cleartext-key-01.asc").unwrap();
+    assert!(Message::classify(key).is_err());
+    let user_id = [0xCD, 0x01, b'a'];
+    assert!(Message::classify(&user_id[..]).is_err());
+}
+
+#[test]
+#[cfg(feature = "compression-deflate")]
+fn msg_decrypt_cancellation() {
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use pgp::crypto::aead::AeadAlgorithm;
+    use pgp::crypto::hash::HashAlgorithm;
+    use pgp::crypto::sym::SymmetricKeyAlgorithm;
+    use pgp::errors::Error;
+    use pgp::types::{Cancellation, StringToKey};
+    use pgp::MessageBuilder;
+
+    let s2k = StringToKey::Salted {
+        hash_alg: HashAlgorithm::SHA2_256,
+        salt: [7; 8],
+    };
+    let no_keys: &[&SignedSecretKey] = &[];
+
+    // 512 chunks of 16 KiB
+    let mut plaintext = vec![0u8; 8 * 1024 * 1024];
+    rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut plaintext);
+    let bytes = MessageBuilder::from_bytes("", &plaintext[..])
+        .compression(None)
+        .seipd_v2(SymmetricKeyAlgorithm::AES128, AeadAlgorithm::Ocb, 8)
+        .encrypt_with_password(s2k.clone(), || "secret".into())
+        .to_bytes(rand::thread_rng())
+        .unwrap();
+    let message = Arc::new(Message::from_bytes(&bytes[..]).unwrap());
+
+    let decrypt = |message: &Message, cancellation: &Cancellation| {
+        message.decrypt_detailed_cancellable(
+            String::new,
+            no_keys,
+            Some(|| "secret".to_string()),
+            cancellation,
+        )
+    };
+
+    let cancellation = Cancellation::new();
+    let decrypted = decrypt(&message, &cancellation).unwrap();
+    assert_eq!(decrypted.message.get_content().unwrap().unwrap(), plaintext);
+
+    cancellation.cancel();
+    assert!(matches!(
+        decrypt(&message, &cancellation),
+        Err(Error::Cancelled)
+    ));
+
+    // cancel from another thread, while decrypting
+    let cancellation = Cancellation::new();
+    let worker = {
+        let message = message.clone();
+        let cancellation = cancellation.clone();
+        std::thread::spawn(move || loop {
+            if let Err(err) = decrypt(&message, &cancellation) {
+                return (err, Instant::now());
+            }
+        })
+    };
+    std::thread::sleep(Duration::from_millis(50));
+    let cancelled_at = Instant::now();
+    cancellation.cancel();
+
+    let (err, returned_at) = worker.join().unwrap();
+    assert!(matches!(err, Error::Cancelled), "{err:?}");
+    assert!(returned_at.duration_since(cancelled_at) < Duration::from_millis(250));
+
+    // decompression
+    let compressed = MessageBuilder::from_bytes("", &plaintext[..1024 * 1024])
+        .to_message(rand::thread_rng())
+        .unwrap();
+    assert!(matches!(compressed, Message::Compressed(_)));
+
+    let cancellation = Cancellation::new();
+    let decompressed = compressed
+        .clone()
+        .decompress_cancellable(&cancellation)
+        .unwrap();
+    assert_eq!(
+        decompressed.get_content().unwrap().unwrap(),
+        &plaintext[..1024 * 1024]
+    );
+
+    cancellation.cancel();
+    assert!(matches!(
+        compressed.decompress_cancellable(&cancellation),
+        Err(Error::Cancelled)
+    ));
+}
+
+#[test]
+#[cfg(feature = "x25519")]
+fn msg_empty_roundtrip() {
+    use pgp::crypto::aead::AeadAlgorithm;
+    use pgp::crypto::hash::HashAlgorithm;
+    use pgp::crypto::sym::SymmetricKeyAlgorithm;
+    use pgp::types::{SecretKeyTrait, StringToKey};
+    use pgp::MessageBuilder;
+
+    let mut rng = rand::thread_rng();
+    let (alice, _) = SignedSecretKey::from_armor_single(
+        File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+    )
+    .unwrap();
+    let alice_pub = alice.public_key();
+    let subkey = alice.secret_subkeys[0].public_key();
+
+    for streaming in [false, true] {
+        for sign in [false, true] {
+            for compress in [false, true] {
+                // none, SEIPDv1 to a key, SEIPDv2 to a key, SEIPDv1 with a password
+                for encryption in 0..4 {
+                    let case = format!(
+                        "streaming: {streaming}, sign: {sign}, compress: {compress}, encryption: {encryption}"
+                    );
+
+                    let mut builder = if streaming {
+                        MessageBuilder::from_reader("", &b""[..])
+                    } else {
+                        MessageBuilder::from_bytes("", b"")
+                    };
+                    if sign {
+                        builder = builder.sign(&alice, String::new, HashAlgorithm::SHA2_256);
+                    }
+                    if !compress {
+                        builder = builder.compression(None);
+                    }
+                    builder = match encryption {
+                        0 => builder,
+                        1 => builder.encrypt_to_key(&subkey),
+                        2 => builder
+                            .seipd_v2(SymmetricKeyAlgorithm::AES128, AeadAlgorithm::Ocb, 0)
+                            .encrypt_to_key(&subkey),
+                        _ => builder
+                            .encrypt_with_password(StringToKey::new_default(&mut rng), || {
+                                "secret".into()
+                            }),
+                    };
+                    let bytes = builder.to_bytes(&mut rng).unwrap();
+
+                    let msg = Message::from_bytes(&bytes[..]).unwrap();
+                    let msg = match encryption {
+                        0 => msg,
+                        3 => msg.decrypt_with_password(|| "secret".into()).unwrap(),
+                        _ => msg.decrypt(String::new, &[&alice]).unwrap().0,
+                    };
+                    assert_eq!(matches!(msg, Message::Compressed(_)), compress, "{case}");
+                    let msg = msg.decompress().unwrap();
+                    if sign {
+                        msg.verify(&alice_pub).unwrap();
+                    }
+                    assert_eq!(msg.get_content().unwrap(), Some(Vec::new()), "{case}");
+                }
+            }
+        }
+    }
+}
+
+#[test]
+#[cfg(feature = "compression-deflate")]
+fn msg_key_block_gnupg() {
+    use pgp::composed::EncryptionPurpose;
+    use pgp::MessageBuilder;
+
+    // signed by GnuPG with `--include-key-block`
+    let (msg, _) = Message::from_armor_single(
+        File::open("./tests/unit-tests/key-block/gnupg-signed.asc").unwrap(),
+    )
+    .unwrap();
+    let msg = msg.decompress().unwrap();
+    let Message::Signed { ref signature, .. } = msg else {
+        panic!("unexpected message {:?}", msg);
+    };
+
+    let sender = SignedPublicKey::from_key_block(signature).unwrap().unwrap();
+    assert_eq!(
+        hex::encode_upper(sender.fingerprint()),
+        "A3F0CC27A1477166EC96C5A541770CC0B3DF80BE"
+    );
+    // GnuPG embeds only the user id the message was signed as
+    assert_eq!(sender.details.users.len(), 1);
+    msg.verify(&sender).unwrap();
+
+    let subkey = sender
+        .best_encryption_key(EncryptionPurpose::Communications)
+        .unwrap();
+    let reply = MessageBuilder::from_bytes("", b"thanks, got it")
+        .encrypt_to_key(subkey)
+        .to_bytes(rand::thread_rng())
+        .unwrap();
+
+    let (carol, _) = SignedSecretKey::from_armor_single(
+        File::open("./tests/unit-tests/key-block/carol.sec.asc").unwrap(),
+    )
+    .unwrap();
+    let (reply, _) = Message::from_bytes(&reply[..])
+        .unwrap()
+        .decrypt(String::new, &[&carol])
+        .unwrap();
+    assert_eq!(
+        reply.decompress().unwrap().get_content().unwrap(),
+        Some(b"thanks, got it".to_vec())
+    );
+}
+
+#[test]
+#[cfg(feature = "rsa")]
+fn msg_key_block_roundtrip() {
+    use pgp::crypto::hash::HashAlgorithm;
+    use pgp::packet::{Subpacket, SubpacketData, MAX_KEY_BLOCK_SIZE};
+    use pgp::types::SecretKeyTrait;
+    use pgp::MessageBuilder;
+
+    let (alice, _) = SignedSecretKey::from_armor_single(
+        File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+    )
+    .unwrap();
+    let alice_pub = alice.public_key().sign(&alice, String::new).unwrap();
+    let (bob, _) = SignedSecretKey::from_armor_single(
+        File::open("./tests/draft-bre-openpgp-samples-00/bob.sec.asc").unwrap(),
+    )
+    .unwrap();
+
+    for minimal in [false, true] {
+        let bytes = MessageBuilder::from_bytes("", b"hello")
+            .sign(&alice, String::new, HashAlgorithm::SHA2_256)
+            .embed_sender_key(&alice_pub, minimal)
+            .to_bytes(rand::thread_rng())
+            .unwrap();
+        let msg = Message::from_bytes(&bytes[..])
+            .unwrap()
+            .decompress()
+            .unwrap();
+        let Message::Signed { ref signature, .. } = msg else {
+            panic!("unexpected message {:?}", msg);
+        };
+
+        let sender = SignedPublicKey::from_key_block(signature).unwrap().unwrap();
+        let expected = if minimal {
+            alice_pub.minimal()
+        } else {
+            alice_pub.clone()
+        };
+        assert_eq!(sender, expected);
+        msg.verify(&sender).unwrap();
+    }
+
+    // only embedded in the signatures of the sender
+    let bytes = MessageBuilder::from_bytes("", b"hello")
+        .sign(&alice, String::new, HashAlgorithm::SHA2_256)
+        .sign(&bob, String::new, HashAlgorithm::SHA2_256)
+        .embed_sender_key(&alice_pub, true)
+        .to_bytes(rand::thread_rng())
+        .unwrap();
+    let mut msg = Message::from_bytes(&bytes[..])
+        .unwrap()
+        .decompress()
+        .unwrap();
+    let mut key_blocks = Vec::new();
+    while let Message::Signed {
+        message, signature, ..
+    } = msg
+    {
+        key_blocks.push(signature.key_block().is_some());
+        msg = *message.unwrap();
+    }
+    assert_eq!(key_blocks, [false, true]);
+
+    assert!(MessageBuilder::from_bytes("", b"hello")
+        .sign(&bob, String::new, HashAlgorithm::SHA2_256)
+        .embed_sender_key(&alice_pub, true)
+        .to_bytes(rand::thread_rng())
+        .is_err());
+
+    // oversized key blocks are rejected when parsing them
+    let msg = Message::from_bytes(&bytes[..])
+        .unwrap()
+        .decompress()
+        .unwrap();
+    let Message::Signed { mut signature, .. } = msg else {
+        panic!("unexpected message {:?}", msg);
+    };
+    let key_block = vec![0; MAX_KEY_BLOCK_SIZE + 1];
+    *signature.config.hashed_subpackets_mut() =
+        vec![Subpacket::regular(SubpacketData::KeyBlock(key_block))];
+    assert!(SignedPublicKey::from_key_block(&signature).is_err());
+}
+
+#[test]
+#[cfg(feature = "x25519")]
+fn msg_dyn_rng() {
+    use pgp::composed::{KeyType, SecretKeyParamsBuilder, SubkeyParamsBuilder};
+    use pgp::crypto::ecc_curve::ECCCurve;
+    use pgp::crypto::hash::HashAlgorithm;
+    use pgp::crypto::sym::SymmetricKeyAlgorithm;
+    use pgp::types::{SecretKeyTrait, StringToKey};
+    use pgp::{ArmorOptions, MessageBuilder};
+    use rand::SeedableRng;
+    use rand_core::CryptoRngCore;
+
+    // the RNG is only known at runtime, as when it is passed in over FFI
+    let mut boxed: Box<dyn CryptoRngCore> = Box::new(rand_chacha::ChaCha8Rng::seed_from_u64(0));
+    let rng: &mut dyn CryptoRngCore = &mut boxed;
+
+    let key = SecretKeyParamsBuilder::default()
+        .key_type(KeyType::EdDSA)
+        .can_certify(true)
+        .can_sign(true)
+        .primary_user_id("Me <me@mail.com>".into())
+        .subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::ECDH(ECCCurve::Curve25519))
+                .can_encrypt(true)
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap()
+        .generate_with_rng(&mut *rng)
+        .unwrap()
+        .sign(String::new)
+        .unwrap();
+    let public = key.public_key().sign(&key, String::new).unwrap();
+    let subkey = &public.public_subkeys[0];
+
+    let check = |msg: Message| {
+        let (msg, _) = msg.decrypt(String::new, &[&key]).unwrap();
+        let msg = msg.decompress().unwrap();
+        msg.verify(&public).unwrap();
+        assert_eq!(msg.get_content().unwrap(), Some(b"hello".to_vec()));
+    };
+    let builder = || {
+        MessageBuilder::from_bytes("", b"hello")
+            .sign(&key, String::new, HashAlgorithm::SHA2_256)
+            .encrypt_to_key(subkey)
+    };
+
+    let bytes = builder().to_bytes(&mut *rng).unwrap();
+    check(Message::from_bytes(&bytes[..]).unwrap());
+
+    let mut bytes = Vec::new();
+    builder().to_writer(&mut boxed, &mut bytes).unwrap();
+    check(Message::from_bytes(&bytes[..]).unwrap());
+
+    let armored = builder()
+        .to_armored_string(boxed.as_mut(), ArmorOptions::default())
+        .unwrap();
+    check(Message::from_string(&armored).unwrap().0);
+
+    let rng: &mut dyn CryptoRngCore = &mut boxed;
+    let msg = Message::new_literal("", "hello")
+        .sign(&key, String::new, HashAlgorithm::SHA2_256)
+        .unwrap()
+        .encrypt_to_keys(rng, SymmetricKeyAlgorithm::AES128, &[subkey])
+        .unwrap();
+    check(msg);
+
+    let s2k = StringToKey::new_argon2(rng, 1, 1, 10);
+    let bytes = MessageBuilder::from_bytes("", b"hello")
+        .encrypt_with_password(s2k, || "secret".into())
+        .to_bytes(&mut *rng)
+        .unwrap();
+    let msg = Message::from_bytes(&bytes[..])
+        .unwrap()
+        .decrypt_with_password(|| "secret".into())
+        .unwrap();
+    assert_eq!(
+        msg.decompress().unwrap().get_content().unwrap(),
+        Some(b"hello".to_vec())
+    );
+
+    let bytes =
+        pgp::encrypt_with_password(rng, b"hello", "secret", SymmetricKeyAlgorithm::AES256, None)
+            .unwrap();
+    assert_eq!(
+        pgp::decrypt_with_password(&bytes, "secret").unwrap(),
+        b"hello"
+    );
+}